@@ -0,0 +1,169 @@
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use crate::error::{with_context, Result};
+use crate::paths;
+use crate::time::unix_millis;
+use crate::tmux;
+
+/// Output rate (bytes per second) at or above which a session counts as active.
+const ACTIVE_RATE: f64 = 32.0;
+/// A session that wrote nothing for this long is reported as silent.
+const SILENT_AFTER: Duration = Duration::from_secs(10);
+/// Samples older than this are too coarse to derive a meaningful rate from.
+const MAX_SAMPLE_AGE: Duration = Duration::from_secs(60);
+/// Minimum spacing between samples so back-to-back calls still measure something.
+const MIN_SAMPLE_AGE: Duration = Duration::from_secs(1);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Activity {
+    Active,
+    Trickling,
+    Silent,
+    Unknown,
+}
+
+impl fmt::Display for Activity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = match self {
+            Activity::Active => "active",
+            Activity::Trickling => "trickling",
+            Activity::Silent => "silent",
+            Activity::Unknown => "unknown",
+        };
+        write!(f, "{label}")
+    }
+}
+
+/// Scrollback lines logged for a session whose agent exited before logging began.
+const EXITED_HISTORY: usize = 10_000;
+
+/// Pipe the session's pane output into its log file so output volume can be tracked.
+pub fn enable(session: &str) -> Result<()> {
+    let log = paths::session_log(session)?;
+    if let Some(dir) = log.parent() {
+        fs::create_dir_all(dir).map_err(|err| {
+            with_context(
+                err,
+                format!("failed to create log directory {}", dir.display()),
+            )
+        })?;
+    }
+    // A command that is already done has nothing left to pipe; keep what it printed.
+    if tmux::session_info(session)?.pane_dead {
+        let output = tmux::capture_pane(&tmux::agent_pane(session)?, Some(EXITED_HISTORY))?;
+        return fs::write(&log, output)
+            .map_err(|err| with_context(err, format!("failed to write {}", log.display())));
+    }
+    let target = shell_words::quote(&log.to_string_lossy()).into_owned();
+    tmux::pipe_pane(session, &format!("cat >> {target}"))
+}
+
+/// Measure the session's recent output rate and classify it.
+pub fn sample(session: &str) -> Activity {
+    let (Ok(log), Ok(sample_path)) = (paths::session_log(session), sample_path(session)) else {
+        return Activity::Unknown;
+    };
+    let Ok(meta) = fs::metadata(&log) else {
+        return Activity::Unknown;
+    };
+
+    let now = SystemTime::now();
+    let size = meta.len();
+    if size == 0 {
+        return Activity::Silent;
+    }
+    let idle = meta
+        .modified()
+        .ok()
+        .and_then(|modified| now.duration_since(modified).ok())
+        .unwrap_or_default();
+
+    let previous = read_sample(&sample_path);
+    let now_ms = unix_millis(now);
+    let rate = previous.and_then(|(bytes, at_ms)| {
+        let age = Duration::from_millis(now_ms.saturating_sub(at_ms));
+        if (MIN_SAMPLE_AGE..=MAX_SAMPLE_AGE).contains(&age) {
+            Some(size.saturating_sub(bytes) as f64 / age.as_secs_f64())
+        } else {
+            None
+        }
+    });
+
+    let stale = previous.is_none_or(|(_, at_ms)| {
+        Duration::from_millis(now_ms.saturating_sub(at_ms)) >= MIN_SAMPLE_AGE
+    });
+    if stale {
+        // Best effort: a missing sample only degrades the next reading.
+        let _ = write_sample(&sample_path, size, now_ms);
+    }
+
+    classify(idle, rate)
+}
+
+/// Drop the rate sample for a session that no longer exists.
+pub fn forget(session: &str) {
+    if let Ok(path) = sample_path(session) {
+        let _ = fs::remove_file(path);
+    }
+}
+
+fn classify(idle: Duration, rate: Option<f64>) -> Activity {
+    if idle >= SILENT_AFTER {
+        return Activity::Silent;
+    }
+    match rate {
+        Some(rate) if rate >= ACTIVE_RATE => Activity::Active,
+        Some(_) => Activity::Trickling,
+        // Without a usable rate, only output within the last couple of seconds counts as active.
+        None if idle <= Duration::from_secs(2) => Activity::Active,
+        None => Activity::Trickling,
+    }
+}
+
+fn sample_path(session: &str) -> Result<PathBuf> {
+    Ok(paths::state_dir()?.join("activity").join(session))
+}
+
+fn read_sample(path: &Path) -> Option<(u64, u64)> {
+    let raw = fs::read_to_string(path).ok()?;
+    let mut parts = raw.split_whitespace();
+    let bytes = parts.next()?.parse().ok()?;
+    let at_ms = parts.next()?.parse().ok()?;
+    Some((bytes, at_ms))
+}
+
+fn write_sample(path: &Path, bytes: u64, at_ms: u64) -> std::io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, format!("{bytes} {at_ms}\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classify_reports_silent_after_quiet_period() {
+        assert_eq!(
+            classify(Duration::from_secs(30), Some(500.0)),
+            Activity::Silent
+        );
+    }
+
+    #[test]
+    fn classify_distinguishes_active_from_trickling() {
+        let idle = Duration::from_secs(1);
+        assert_eq!(classify(idle, Some(ACTIVE_RATE * 2.0)), Activity::Active);
+        assert_eq!(classify(idle, Some(1.0)), Activity::Trickling);
+    }
+
+    #[test]
+    fn classify_falls_back_to_recency_without_rate() {
+        assert_eq!(classify(Duration::from_secs(1), None), Activity::Active);
+        assert_eq!(classify(Duration::from_secs(5), None), Activity::Trickling);
+    }
+}
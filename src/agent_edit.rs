@@ -0,0 +1,292 @@
+//! `amux agents add/edit/remove`: change agent definitions in the config
+//! file without editing TOML by hand. Each function takes the file's current
+//! text and returns the new text, which has already been checked to load.
+//! `add` appends a section so the rest of the file stays as written; `edit`
+//! and `remove` rewrite the file in amux's own layout, without comments.
+
+use std::collections::BTreeMap;
+
+use crate::agents;
+use crate::config;
+use crate::error::{bail, fail_with, with_context, DynError, ErrorKind, Result};
+use crate::toml::{self, Table, Value};
+
+/// Agent settings given on the command line. For `edit`, an empty string
+/// removes the setting.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Definition {
+    pub command: Option<String>,
+    pub description: Option<String>,
+    pub params: Option<String>,
+    pub cwd: Option<String>,
+    /// Extra environment variables for the agent's sessions.
+    pub env: BTreeMap<String, String>,
+}
+
+impl Definition {
+    fn fields(&self) -> [(&'static str, Option<&String>); 4] {
+        [
+            ("command", self.command.as_ref()),
+            ("description", self.description.as_ref()),
+            ("params", self.params.as_ref()),
+            ("cwd", self.cwd.as_ref()),
+        ]
+    }
+
+    fn is_empty(&self) -> bool {
+        self.fields().iter().all(|(_, value)| value.is_none()) && self.env.is_empty()
+    }
+
+    fn env_table(&self) -> Table {
+        self.env
+            .iter()
+            .filter(|(_, value)| !value.is_empty())
+            .map(|(key, value)| (key.clone(), Value::String(value.clone())))
+            .collect()
+    }
+}
+
+/// `raw` with a new agent `name` defined by `definition`.
+pub fn add(raw: &str, name: &str, definition: &Definition) -> Result<String> {
+    let mut doc = toml::parse(raw)?;
+    if agents::is_default_agent(name) {
+        return Err(fail_with(
+            ErrorKind::InvalidInput,
+            format!("'{name}' is a built-in agent"),
+            Some(format!(
+                "change its settings with `amux agents edit {name}`"
+            )),
+        ));
+    }
+    let existing = agent_table(&doc, name);
+    if existing.is_some_and(|table| table.contains_key("command")) {
+        return Err(fail_with(
+            ErrorKind::InvalidInput,
+            format!("agent '{name}' is already defined in the config"),
+            Some(format!("change it with `amux agents edit {name}`")),
+        ));
+    }
+    check_command(definition.command.as_deref())?;
+
+    let mut table = Table::new();
+    for (key, value) in definition.fields() {
+        if let Some(value) = value.filter(|value| !value.is_empty()) {
+            table.insert(key.to_string(), Value::String(value.clone()));
+        }
+    }
+    let env = definition.env_table();
+    if !env.is_empty() {
+        table.insert("env".into(), Value::Table(env));
+    }
+    let updated = if existing.is_none() {
+        // A new section goes at the end, keeping the rest of the file as written.
+        let mut section = Table::new();
+        section.insert("agents".into(), agents_doc(name, table));
+        let mut text = raw.trim_end().to_string();
+        if !text.is_empty() {
+            text.push_str("\n\n");
+        }
+        text.push_str(&toml::to_string(&section));
+        text
+    } else {
+        agent_table_mut(&mut doc, name)?.extend(table);
+        toml::to_string(&doc)
+    };
+    validate(&updated)?;
+    Ok(updated)
+}
+
+/// `raw` with `changes` applied to agent `name`, which is built in or
+/// defined in the config.
+pub fn edit(raw: &str, name: &str, changes: &Definition) -> Result<String> {
+    if changes.is_empty() {
+        return Err(fail_with(
+            ErrorKind::InvalidInput,
+            format!("nothing to change for '{name}'"),
+            Some("pass --command, --description, --params, or --cwd".into()),
+        ));
+    }
+    let mut doc = toml::parse(raw)?;
+    let built_in = agents::is_default_agent(name);
+    if !built_in && agent_table(&doc, name).is_none() {
+        return Err(unknown_agent(name));
+    }
+    match changes.command.as_deref() {
+        Some("") if !built_in => {
+            return Err(fail_with(
+                ErrorKind::InvalidInput,
+                format!("agent '{name}' needs a command"),
+                Some(format!("remove it with `amux agents remove {name}`")),
+            ));
+        }
+        Some("") | None => {}
+        Some(command) => check_command(Some(command))?,
+    }
+
+    let table = agent_table_mut(&mut doc, name)?;
+    for (key, value) in changes.fields() {
+        match value.map(String::as_str) {
+            Some("") => {
+                table.remove(key);
+            }
+            Some(value) => {
+                table.insert(key.to_string(), Value::String(value.to_string()));
+            }
+            None => {}
+        }
+    }
+    if !changes.env.is_empty() {
+        let mut env = match table.remove("env") {
+            Some(Value::Table(env)) => env,
+            _ => Table::new(),
+        };
+        for key in changes.env.keys() {
+            env.remove(key);
+        }
+        env.extend(changes.env_table());
+        if !env.is_empty() {
+            table.insert("env".into(), Value::Table(env));
+        }
+    }
+    if table.is_empty() {
+        remove_agent(&mut doc, name);
+    }
+    let updated = toml::to_string(&doc);
+    validate(&updated)?;
+    Ok(updated)
+}
+
+/// `raw` without agent `name`'s section.
+pub fn remove(raw: &str, name: &str) -> Result<String> {
+    let mut doc = toml::parse(raw)?;
+    if agent_table(&doc, name).is_none() {
+        return Err(unknown_agent(name));
+    }
+    remove_agent(&mut doc, name);
+    let updated = toml::to_string(&doc);
+    validate(&updated)?;
+    Ok(updated)
+}
+
+fn check_command(command: Option<&str>) -> Result<()> {
+    match command {
+        Some(command) => agents::parse_tokens("--command", command).map(|_| ()),
+        None => Err(fail_with(
+            ErrorKind::InvalidInput,
+            "a new agent needs --command".to_string(),
+            None,
+        )),
+    }
+}
+
+fn validate(text: &str) -> Result<()> {
+    config::parse(text)
+        .map(|_| ())
+        .map_err(|err| with_context(err, "the changed config would not load"))
+}
+
+fn unknown_agent(name: &str) -> DynError {
+    fail_with(
+        ErrorKind::InvalidInput,
+        format!("agent '{name}' is not defined in the config"),
+        Some(format!(
+            "add it with `amux agents add {name} --command ...`"
+        )),
+    )
+}
+
+fn agents_doc(name: &str, table: Table) -> Value {
+    Value::Table(Table::from([(name.to_string(), Value::Table(table))]))
+}
+
+fn agent_table<'a>(doc: &'a Table, name: &str) -> Option<&'a Table> {
+    match doc.get("agents")? {
+        Value::Table(agents) => match agents.get(name)? {
+            Value::Table(table) => Some(table),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn agent_table_mut<'a>(doc: &'a mut Table, name: &str) -> Result<&'a mut Table> {
+    let agents = doc
+        .entry("agents".to_string())
+        .or_insert_with(|| Value::Table(Table::new()));
+    let Value::Table(agents) = agents else {
+        return bail("'agents' in the config is not a table");
+    };
+    match agents
+        .entry(name.to_string())
+        .or_insert_with(|| Value::Table(Table::new()))
+    {
+        Value::Table(table) => Ok(table),
+        _ => bail(format!("'agents.{name}' in the config is not a table")),
+    }
+}
+
+fn remove_agent(doc: &mut Table, name: &str) {
+    if let Some(Value::Table(agents)) = doc.get_mut("agents") {
+        agents.remove(name);
+        if agents.is_empty() {
+            doc.remove("agents");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn definition(command: &str) -> Definition {
+        Definition {
+            command: Some(command.into()),
+            description: Some("Reviews the branch".into()),
+            ..Definition::default()
+        }
+    }
+
+    #[test]
+    fn add_appends_a_section_and_refuses_duplicates() {
+        let raw = "# my agents\nversion = 1\n";
+        let added = add(raw, "reviewer", &definition("my-agent --review")).expect("add");
+        assert!(added.starts_with("# my agents\nversion = 1\n\n[agents.reviewer]\n"));
+        assert!(added.contains("command = \"my-agent --review\""));
+        let err = add(&added, "reviewer", &definition("other")).expect_err("duplicate");
+        assert!(err.to_string().contains("already defined"));
+        assert!(add("", "codex", &definition("codex")).is_err());
+        assert!(add("", "quoted", &definition("'unclosed")).is_err());
+    }
+
+    #[test]
+    fn edit_sets_and_clears_fields() {
+        let raw = add("", "reviewer", &definition("my-agent")).expect("add");
+        let changes = Definition {
+            description: Some(String::new()),
+            params: Some("--fast".into()),
+            ..Definition::default()
+        };
+        let edited = edit(&raw, "reviewer", &changes).expect("edit");
+        let config = config::parse(&edited).expect("config should parse");
+        let agent = config.agent("reviewer").expect("agent kept");
+        assert_eq!(agent.description, None);
+        assert_eq!(agent.params.as_deref(), Some("--fast"));
+        let clear = Definition {
+            command: Some(String::new()),
+            ..Definition::default()
+        };
+        assert!(edit(&edited, "reviewer", &clear).is_err());
+        assert!(edit("", "stranger", &changes).is_err());
+    }
+
+    #[test]
+    fn remove_drops_the_section() {
+        let raw = add("", "reviewer", &definition("my-agent")).expect("add");
+        let removed = remove(&raw, "reviewer").expect("remove");
+        assert!(config::parse(&removed)
+            .expect("config should parse")
+            .agent("reviewer")
+            .is_none());
+        assert!(remove(&removed, "reviewer").is_err());
+    }
+}
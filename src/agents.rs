@@ -1,6 +1,7 @@
 use std::collections::BTreeSet;
 use std::env;
 
+use crate::config::{self, Config};
 use crate::error::{bail, with_context, Result};
 
 #[derive(Clone, Copy)]
@@ -33,25 +34,43 @@ const DEFAULT_AGENTS: &[DefaultAgent] = &[
     },
 ];
 
+/// The agent's command: `command_override`, else `CA_AGENT_CMD_<agent>`, else
+/// `command` under `[agents.<agent>]` in the config file, else the default.
 pub fn resolve_agent_command(agent: &str, command_override: Option<&str>) -> Result<Vec<String>> {
     if let Some(raw) = command_override {
         return parse_tokens("command override", raw);
     }
+    command_for(agent, &config::load()?)
+}
 
+fn command_for(agent: &str, config: &Config) -> Result<Vec<String>> {
     if let Some(raw) = lookup_env_command(agent) {
         return parse_tokens("environment override", &raw);
     }
 
+    if let Some(raw) = config
+        .agent(agent)
+        .and_then(|agent| agent.command.as_deref())
+    {
+        return parse_tokens(&format!("agents.{agent}.command"), raw);
+    }
+
     if let Some(default) = default_agent(agent) {
         return Ok(default.command.iter().map(|s| (*s).to_string()).collect());
     }
 
     bail(format!(
-        "no command configured for agent '{agent}'. Set CA_AGENT_CMD_{agent} or provide --cmd explicitly."
+        "no command configured for agent '{agent}'. Set CA_AGENT_CMD_{agent}, add a command under [agents.{agent}] in the config file, or provide --cmd explicitly."
     ))
 }
 
+/// Default agents, agents with a `CA_AGENT_CMD_*` variable, and agents with a
+/// command in the config file.
 pub fn configured_agents() -> Vec<String> {
+    agent_names(&config::load().unwrap_or_default())
+}
+
+fn agent_names(config: &Config) -> Vec<String> {
     let mut names = BTreeSet::new();
     for default in DEFAULT_AGENTS {
         names.insert(default.name.to_string());
@@ -61,17 +80,43 @@ pub fn configured_agents() -> Vec<String> {
             names.insert(agent.to_ascii_lowercase());
         }
     }
+    for (name, agent) in &config.agents {
+        if agent.command.is_some() {
+            names.insert(name.clone());
+        }
+    }
     names.into_iter().collect()
 }
 
-pub fn agent_description(name: &str) -> Option<&'static str> {
-    default_agent(name).map(|agent| agent.description)
+/// The agent's `description` from the config file, else the default agent's.
+pub fn agent_description(name: &str) -> Option<String> {
+    description_for(name, &config::load().unwrap_or_default())
+}
+
+fn description_for(name: &str, config: &Config) -> Option<String> {
+    config
+        .agent(name)
+        .and_then(|agent| agent.description.clone())
+        .or_else(|| default_agent(name).map(|agent| agent.description.to_string()))
 }
 
+/// The agent `amux run-adhoc` sessions are listed under unless `-a` names another.
+pub const ADHOC_AGENT: &str = "adhoc";
+
 pub fn is_default_agent(name: &str) -> bool {
     default_agent(name).is_some()
 }
 
+/// Whether `name` may be given as a positional agent: a built-in agent,
+/// [`ADHOC_AGENT`], or one defined in the config file or by `CA_AGENT_CMD_*` unless
+/// `strict_positional_agents` is set.
+pub fn accepts_positional(config: &Config, name: &str) -> bool {
+    is_default_agent(name)
+        || name == ADHOC_AGENT
+        || (!config.strict_positional_agents
+            && agent_names(config).iter().any(|known| known == name))
+}
+
 pub fn parse_tokens(origin: &str, raw: &str) -> Result<Vec<String>> {
     let trimmed = raw.trim();
     if trimmed.is_empty() {
@@ -144,6 +189,49 @@ mod tests {
         assert!(err.to_string().contains("origin is empty"));
     }
 
+    #[test]
+    fn config_file_agents_have_commands_and_descriptions() {
+        let config = config::parse(
+            r#"
+[agents.reviewer]
+command = "my-agent --mode review"
+description = "Code reviewer"
+
+[agents.codex]
+description = "Codex, reviewing"
+
+[agents.notes.response]
+quiet_ms = 1000
+"#,
+        )
+        .expect("config should parse");
+        assert_eq!(
+            command_for("reviewer", &config).expect("command should resolve"),
+            ["my-agent", "--mode", "review"]
+        );
+        assert_eq!(
+            command_for("codex", &config).expect("default should resolve"),
+            ["codex"]
+        );
+        assert!(command_for("notes", &config).is_err());
+
+        let names = agent_names(&config);
+        assert!(names.contains(&"reviewer".to_string()));
+        assert!(!names.contains(&"notes".to_string()));
+        assert_eq!(
+            description_for("reviewer", &config).as_deref(),
+            Some("Code reviewer")
+        );
+        assert_eq!(
+            description_for("codex", &config).as_deref(),
+            Some("Codex, reviewing")
+        );
+        assert_eq!(
+            description_for("claude", &config).as_deref(),
+            Some("Claude CLI")
+        );
+    }
+
     #[test]
     fn configured_agents_include_defaults() {
         let agents = configured_agents();
@@ -152,4 +240,18 @@ mod tests {
         assert!(agents.contains(&"gemini".to_string()));
         assert!(agents.contains(&"opencode".to_string()));
     }
+
+    #[test]
+    fn accepts_positional_allows_configured_agents_unless_strict() {
+        let raw = "[agents.reviewer]\ncommand = \"my-agent\"\n";
+        let config = config::parse(raw).expect("config should parse");
+        assert!(accepts_positional(&config, "codex"));
+        assert!(accepts_positional(&config, "reviewer"));
+        assert!(!accepts_positional(&config, "stranger"));
+
+        let strict = config::parse(&format!("strict_positional_agents = true\n{raw}"))
+            .expect("config should parse");
+        assert!(accepts_positional(&strict, "codex"));
+        assert!(!accepts_positional(&strict, "reviewer"));
+    }
 }
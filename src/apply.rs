@@ -0,0 +1,312 @@
+//! `amux apply`: converge running sessions on a declared workspace file.
+//!
+//! ```toml
+//! [[session]]
+//! agent = "codex"
+//! name = "api"
+//! params = "--full-auto"
+//! cwd = "services/api"
+//! ```
+//!
+//! Each declaration is stored on the session amux starts for it, so a later
+//! apply can tell a session still matching its declaration from one whose
+//! command or directory changed in the file since. `amux freeze` goes the
+//! other way and writes the running sessions out as such a file.
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::config::{expect_string, expect_table};
+use crate::error::{bail, with_context, Result};
+use crate::json;
+use crate::plan::{Action, Plan};
+use crate::tmux;
+use crate::toml::{self, Value};
+
+/// One `[[session]]` entry.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Declared {
+    pub agent: String,
+    pub name: Option<String>,
+    /// Replaces the agent's configured command, like `start --cmd`.
+    pub cmd: Option<String>,
+    /// Appended to the command, like `start --params`.
+    pub params: Option<String>,
+    /// Absolute; relative paths in the file are resolved against its directory.
+    pub cwd: PathBuf,
+}
+
+impl Declared {
+    pub fn session(&self) -> String {
+        tmux::session_name(&self.agent, self.name.as_deref())
+    }
+
+    /// The declaration recorded on a session by [`Declared::spec`].
+    pub fn from_spec(agent: &str, name: Option<&str>, spec: &str) -> Option<Self> {
+        let spec = json::parse(spec).ok()?;
+        let text = |key: &str| {
+            spec.get(key)
+                .and_then(json::Value::as_str)
+                .map(str::to_string)
+        };
+        Some(Self {
+            agent: agent.to_string(),
+            name: name.map(str::to_string),
+            cmd: text("cmd"),
+            params: text("params"),
+            cwd: PathBuf::from(text("cwd")?),
+        })
+    }
+
+    /// What is recorded on the session and compared on the next apply.
+    pub fn spec(&self) -> String {
+        json::object([
+            ("cmd", json::Value::from(self.cmd.clone())),
+            ("params", json::Value::from(self.params.clone())),
+            (
+                "cwd",
+                json::Value::from(self.cwd.to_string_lossy().into_owned()),
+            ),
+        ])
+        .to_string()
+    }
+}
+
+/// A session running now, as far as planning is concerned.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Running {
+    pub session: String,
+    /// Whether amux started (or adopted) it.
+    pub amux: bool,
+    pub pinned: bool,
+    /// The declaration it was applied from, if any.
+    pub spec: Option<String>,
+}
+
+/// Read the workspace file at `path`.
+pub fn load(path: &Path) -> Result<Vec<Declared>> {
+    let raw = fs::read_to_string(path)
+        .map_err(|err| with_context(err, format!("failed to read {}", path.display())))?;
+    let path = fs::canonicalize(path)
+        .map_err(|err| with_context(err, format!("failed to resolve {}", path.display())))?;
+    let base = path.parent().unwrap_or(Path::new("/"));
+    parse(&raw, base).or_else(|err| bail(format!("{}: {err}", path.display())))
+}
+
+/// Parse a workspace file whose relative `cwd`s are relative to `base`.
+pub fn parse(raw: &str, base: &Path) -> Result<Vec<Declared>> {
+    let doc = toml::parse(raw)?;
+    let mut declared = Vec::new();
+    for (key, value) in &doc {
+        if key != "session" {
+            return bail(format!("unknown key '{key}'"));
+        }
+        let Value::Array(entries) = value else {
+            return bail("'session' must be written as [[session]] tables");
+        };
+        for (index, entry) in entries.iter().enumerate() {
+            let context = format!("session[{index}]");
+            declared.push(parse_session(
+                expect_table(entry, &context)?,
+                base,
+                &context,
+            )?);
+        }
+    }
+
+    let mut seen = BTreeSet::new();
+    for entry in &declared {
+        if !seen.insert(entry.session()) {
+            return bail(format!("session '{}' is declared twice", entry.session()));
+        }
+    }
+    Ok(declared)
+}
+
+fn parse_session(table: &toml::Table, base: &Path, context: &str) -> Result<Declared> {
+    let mut agent = None;
+    let mut declared = Declared {
+        agent: String::new(),
+        name: None,
+        cmd: None,
+        params: None,
+        cwd: base.to_path_buf(),
+    };
+    for (key, value) in table {
+        let path = format!("{context}.{key}");
+        match key.as_str() {
+            "agent" => agent = Some(expect_string(value, &path)?),
+            "name" => declared.name = Some(expect_string(value, &path)?),
+            "cmd" => declared.cmd = Some(expect_string(value, &path)?),
+            "params" => declared.params = Some(expect_string(value, &path)?),
+            "cwd" => declared.cwd = base.join(expect_string(value, &path)?),
+            _ => return bail(format!("unknown key '{path}'")),
+        }
+    }
+    let Some(agent) = agent else {
+        return bail(format!("'{context}' needs an agent"));
+    };
+    declared.agent = agent;
+    Ok(declared)
+}
+
+/// `declared` as a workspace file, with absolute directories.
+pub fn render(declared: &[Declared]) -> String {
+    let sessions = declared
+        .iter()
+        .map(|entry| {
+            let mut table = toml::Table::new();
+            table.insert("agent".into(), Value::String(entry.agent.clone()));
+            for (key, value) in [
+                ("name", &entry.name),
+                ("cmd", &entry.cmd),
+                ("params", &entry.params),
+            ] {
+                if let Some(value) = value {
+                    table.insert(key.into(), Value::String(value.clone()));
+                }
+            }
+            let cwd = entry.cwd.to_string_lossy().into_owned();
+            table.insert("cwd".into(), Value::String(cwd));
+            Value::Table(table)
+        })
+        .collect();
+    let mut doc = toml::Table::new();
+    doc.insert("session".into(), Value::Array(sessions));
+    toml::to_string(&doc)
+}
+
+/// What applying `declared` to `running` does, one step per session. With
+/// `prune`, amux sessions that are not declared are removed.
+pub fn plan(declared: &[Declared], running: &[Running], prune: bool) -> Plan {
+    let mut plan = Plan::default();
+    for entry in declared {
+        let session = entry.session();
+        let Some(current) = running.iter().find(|running| running.session == session) else {
+            plan.push(Action::Create, &session, None);
+            continue;
+        };
+        let (action, reason) = if !current.amux {
+            (Action::Conflict, Some("not started by amux"))
+        } else if current.spec.is_none() {
+            (Action::Keep, Some("not started by apply"))
+        } else if current.spec.as_deref() == Some(entry.spec().as_str()) {
+            (Action::Keep, None)
+        } else if current.pinned {
+            (Action::Keep, Some("changed, but pinned"))
+        } else {
+            (Action::Replace, Some("changed"))
+        };
+        plan.push(action, &session, reason);
+    }
+
+    if prune {
+        let declared: BTreeSet<String> = declared.iter().map(Declared::session).collect();
+        for current in running {
+            if !current.amux || declared.contains(&current.session) {
+                continue;
+            }
+            if current.pinned {
+                plan.push(
+                    Action::Keep,
+                    &current.session,
+                    Some("not declared, but pinned"),
+                );
+            } else {
+                plan.push(Action::Remove, &current.session, Some("not declared"));
+            }
+        }
+    }
+    plan
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn declared(agent: &str, name: Option<&str>) -> Declared {
+        Declared {
+            agent: agent.into(),
+            name: name.map(str::to_owned),
+            cmd: None,
+            params: None,
+            cwd: PathBuf::from("/src"),
+        }
+    }
+
+    fn running(session: &str, spec: Option<String>, pinned: bool) -> Running {
+        Running {
+            session: session.into(),
+            amux: true,
+            pinned,
+            spec,
+        }
+    }
+
+    #[test]
+    fn parse_resolves_cwd_against_the_file() {
+        let raw = "[[session]]\nagent = \"codex\"\nname = \"api\"\nparams = \"--full-auto\"\n\
+                   cwd = \"services/api\"\n\n[[session]]\nagent = \"claude\"\ncwd = \"/tmp\"\n";
+        let declared = parse(raw, Path::new("/src")).expect("workspace should parse");
+        assert_eq!(declared[0].session(), "amux-codex--api");
+        assert_eq!(declared[0].params.as_deref(), Some("--full-auto"));
+        assert_eq!(declared[0].cwd, Path::new("/src/services/api"));
+        assert_eq!(declared[1].cwd, Path::new("/tmp"));
+
+        let err = parse(
+            "[[session]]\nagent = \"codex\"\nimage = \"x\"\n",
+            Path::new("/"),
+        )
+        .expect_err("unknown keys should fail");
+        assert_eq!(err.to_string(), "unknown key 'session[0].image'");
+        let twice = "[[session]]\nagent = \"codex\"\n[[session]]\nagent = \"codex\"\n";
+        assert!(parse(twice, Path::new("/")).is_err());
+    }
+
+    #[test]
+    fn render_round_trips_through_parse() {
+        let mut api = declared("codex", Some("api"));
+        api.cmd = Some("codex --model 'o3 mini'".into());
+        let claude = declared("claude", None);
+        let frozen = render(&[api.clone(), claude.clone()]);
+        assert!(frozen.starts_with("[[session]]\n"));
+        let parsed = parse(&frozen, Path::new("/elsewhere")).expect("frozen file should parse");
+        assert_eq!(parsed, [api.clone(), claude]);
+        let restored = Declared::from_spec("codex", Some("api"), &api.spec());
+        assert_eq!(restored, Some(api));
+    }
+
+    #[test]
+    fn plan_replaces_sessions_whose_declaration_changed() {
+        let api = declared("codex", Some("api"));
+        let mut web = declared("codex", Some("web"));
+        let old_web = web.spec();
+        web.params = Some("--full-auto".into());
+        let running = [
+            running("amux-codex--api", Some(api.spec()), false),
+            running("amux-codex--web", Some(old_web), false),
+            running("amux-claude", None, false),
+        ];
+        let plan = plan(&[api, web, declared("claude", None)], &running, false);
+        let actions: Vec<_> = plan.steps.iter().map(|step| step.action).collect();
+        assert_eq!(actions, [Action::Keep, Action::Replace, Action::Keep]);
+        assert_eq!(
+            plan.steps[2].reason.as_deref(),
+            Some("not started by apply")
+        );
+    }
+
+    #[test]
+    fn plan_prunes_undeclared_sessions_but_not_pinned_ones() {
+        let running = [
+            running("amux-gemini", None, false),
+            running("amux-aider", None, true),
+        ];
+        let declared = [declared("codex", None)];
+        assert_eq!(plan(&declared, &running, false).steps.len(), 1);
+        let plan = plan(&declared, &running, true);
+        let actions: Vec<_> = plan.steps.iter().map(|step| step.action).collect();
+        assert_eq!(actions, [Action::Create, Action::Remove, Action::Keep]);
+    }
+}
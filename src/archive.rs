@@ -0,0 +1,230 @@
+//! Compressed logs of removed sessions. When a session with a log is removed,
+//! its log is gzipped into the archive directory next to a small JSON file with
+//! the run report, the session's name, and its note, so past runs can be browsed
+//! with `amux archive` without keeping every transcript uncompressed.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use crate::error::{fail_with, with_context, ErrorKind, Result};
+use crate::json::{self, Value};
+use crate::paths;
+use crate::report::RunReport;
+
+/// One archived run.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Entry {
+    /// `<session>-<ended>`; names both files in the archive directory.
+    pub id: String,
+    pub name: Option<String>,
+    pub note: Option<String>,
+    pub report: RunReport,
+}
+
+impl Entry {
+    fn to_json(&self) -> Value {
+        json::object([
+            ("id", Value::from(self.id.as_str())),
+            ("name", Value::from(self.name.clone())),
+            ("note", Value::from(self.note.clone())),
+            ("report", self.report.to_json()),
+        ])
+    }
+
+    fn from_json(value: &Value) -> Option<Self> {
+        let text = |key: &str| value.get(key).and_then(Value::as_str).map(str::to_string);
+        Some(Self {
+            id: text("id")?,
+            name: text("name"),
+            note: text("note"),
+            report: RunReport::from_json(value.get("report")?)?,
+        })
+    }
+
+    /// The compressed log.
+    pub fn log_path(&self) -> Result<PathBuf> {
+        Ok(paths::archive_dir()?.join(format!("{}.log.gz", self.id)))
+    }
+}
+
+/// Compress `log` into the archive with `report`'s metadata and delete the
+/// original; the returned entry's report points at the compressed log. The session must already be gone, so nothing still writes to it.
+pub fn store(
+    log: &Path,
+    report: &RunReport,
+    name: Option<&str>,
+    note: Option<&str>,
+) -> Result<Entry> {
+    let dir = paths::archive_dir()?;
+    fs::create_dir_all(&dir)
+        .map_err(|err| with_context(err, format!("failed to create {}", dir.display())))?;
+
+    let mut entry = Entry {
+        id: format!("{}-{}", report.session, report.ended),
+        name: name.map(str::to_string),
+        note: note.map(str::to_string),
+        report: report.clone(),
+    };
+    let gz = entry.log_path()?;
+    compress(log, &gz)?;
+    entry.report.log_path = Some(gz.to_string_lossy().into_owned());
+
+    let meta = dir.join(format!("{}.json", entry.id));
+    let tmp = meta.with_extension("json.tmp");
+    fs::write(&tmp, format!("{}\n", entry.to_json().to_pretty()))
+        .and_then(|()| fs::rename(&tmp, &meta))
+        .map_err(|err| with_context(err, format!("failed to write {}", meta.display())))?;
+    fs::remove_file(log)
+        .map_err(|err| with_context(err, format!("failed to remove {}", log.display())))?;
+    Ok(entry)
+}
+
+fn compress(source: &Path, target: &Path) -> Result<()> {
+    let tmp = target.with_extension("gz.tmp");
+    let file = fs::File::create(&tmp)
+        .map_err(|err| with_context(err, format!("failed to create {}", tmp.display())))?;
+    let status = Command::new("gzip")
+        .arg("-c")
+        .arg(source)
+        .stdout(file)
+        .status()
+        .map_err(|err| with_context(err, "failed to run gzip"))?;
+    if !status.success() {
+        let _ = fs::remove_file(&tmp);
+        return Err(fail_with(
+            ErrorKind::Other,
+            format!("gzip could not compress {}", source.display()),
+            None,
+        ));
+    }
+    fs::rename(&tmp, target)
+        .map_err(|err| with_context(err, format!("failed to write {}", target.display())))
+}
+
+/// Archived runs, newest first.
+pub fn list() -> Result<Vec<Entry>> {
+    let dir = paths::archive_dir()?;
+    let read = match fs::read_dir(&dir) {
+        Ok(read) => read,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => {
+            return Err(with_context(
+                err,
+                format!("failed to read {}", dir.display()),
+            ))
+        }
+    };
+    let mut entries: Vec<Entry> = read
+        .filter_map(|item| item.ok())
+        .map(|item| item.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .filter_map(|path| fs::read_to_string(path).ok())
+        .filter_map(|raw| json::parse(&raw).ok())
+        .filter_map(|value| Entry::from_json(&value))
+        .collect();
+    newest_first(&mut entries);
+    Ok(entries)
+}
+
+fn newest_first(entries: &mut [Entry]) {
+    entries.sort_by(|a, b| {
+        b.report
+            .ended
+            .cmp(&a.report.ended)
+            .then_with(|| a.id.cmp(&b.id))
+    });
+}
+
+/// The archived run `id`; a unique prefix of it is enough.
+pub fn find(id: &str) -> Result<Entry> {
+    let entries = list()?;
+    if let Some(entry) = entries.iter().find(|entry| entry.id == id) {
+        return Ok(entry.clone());
+    }
+    let mut matches = entries.into_iter().filter(|entry| entry.id.starts_with(id));
+    match (matches.next(), matches.next()) {
+        (Some(entry), None) => Ok(entry),
+        (Some(_), Some(_)) => Err(fail_with(
+            ErrorKind::InvalidInput,
+            format!("'{id}' matches more than one archived run"),
+            Some("use the full id from `amux archive list`".into()),
+        )),
+        (None, _) => Err(fail_with(
+            ErrorKind::SessionNotFound,
+            format!("no archived run '{id}'"),
+            Some("see `amux archive list`".into()),
+        )),
+    }
+}
+
+/// Write `entry`'s decompressed log to stdout.
+pub fn print_log(entry: &Entry) -> Result<()> {
+    let path = entry.log_path()?;
+    let status = Command::new("gzip")
+        .arg("-dc")
+        .arg(&path)
+        .stdout(Stdio::inherit())
+        .status()
+        .map_err(|err| with_context(err, "failed to run gzip"))?;
+    if !status.success() {
+        return Err(fail_with(
+            ErrorKind::Other,
+            format!("gzip could not read {}", path.display()),
+            None,
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::report::EndReason;
+
+    fn entry(session: &str, ended: u64) -> Entry {
+        Entry {
+            id: format!("{session}-{ended}"),
+            name: Some("pr#1".into()),
+            note: Some("review the parser".into()),
+            report: RunReport {
+                agent: "codex".into(),
+                session: session.into(),
+                reason: EndReason::Removed,
+                started: Some(ended - 90),
+                ended,
+                cwd: Some("/src".into()),
+                changes: None,
+                exit_status: None,
+                log_path: None,
+                usage: None,
+                started_by: None,
+                removed_by: None,
+            },
+        }
+    }
+
+    #[test]
+    fn entry_round_trips_through_json() {
+        let entry = entry("amux-codex--pr%231", 1_700_000_000);
+        let raw = entry.to_json().to_pretty();
+        let parsed = json::parse(&raw).expect("metadata should parse");
+        assert_eq!(Entry::from_json(&parsed), Some(entry));
+    }
+
+    #[test]
+    fn newest_first_sorts_by_end_time() {
+        let mut entries = vec![
+            entry("amux-codex", 100),
+            entry("amux-claude", 300),
+            entry("amux-gemini", 200),
+        ];
+        newest_first(&mut entries);
+        let ids: Vec<_> = entries.iter().map(|entry| entry.id.as_str()).collect();
+        assert_eq!(
+            ids,
+            ["amux-claude-300", "amux-gemini-200", "amux-codex-100"]
+        );
+    }
+}
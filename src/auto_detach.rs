@@ -0,0 +1,127 @@
+//! Detach clients that have had no input for a while, so a terminal left
+//! attached on another machine stops holding (and resizing) the session.
+//!
+//! The idle limit lives in the `@amux_auto_detach` session option. Hooks on the
+//! session start `amux idle-watch` for each client that attaches or switches
+//! to it; the watcher polls the client's activity until it detaches it, the
+//! client goes away, or the session it shows has no limit.
+
+use std::fs;
+use std::path::PathBuf;
+use std::process;
+use std::thread;
+use std::time::Duration;
+
+use crate::error::{with_context, Result};
+use crate::paths;
+use crate::time::unix_now;
+use crate::tmux;
+
+/// Hooks that start a watcher for the client that triggered them.
+const HOOKS: &[&str] = &["client-attached", "client-session-changed"];
+
+/// Longest sleep between checks, so a changed limit is noticed.
+const MAX_POLL: Duration = Duration::from_secs(60);
+
+/// Detach `session`'s clients after `minutes` without input, starting with
+/// the ones attached now.
+pub fn enable(session: &str, minutes: u64) -> Result<()> {
+    tmux::set_auto_detach_minutes(session, Some(minutes))?;
+    let exe = std::env::current_exe()
+        .map_err(|err| with_context(err, "failed to locate the amux executable"))?;
+    let exe = exe.to_string_lossy();
+    let hook = tmux::run_shell_command(&[&exe, "idle-watch", "--client", "#{client_name}"]);
+    for name in HOOKS {
+        tmux::set_hook(session, name, Some(&hook))?;
+    }
+    for client in tmux::session_clients(session)? {
+        tmux::run_in_background(&[&exe, "idle-watch", "--client", &client])?;
+    }
+    Ok(())
+}
+
+/// Stop detaching `session`'s clients; running watchers exit on their next check.
+pub fn disable(session: &str) -> Result<()> {
+    tmux::set_auto_detach_minutes(session, None)?;
+    for name in HOOKS {
+        tmux::set_hook(session, name, None)?;
+    }
+    Ok(())
+}
+
+/// What a watcher does after looking at its client.
+#[derive(Debug, PartialEq, Eq)]
+enum Action {
+    Detach,
+    Wait(Duration),
+}
+
+fn next_action(idle_secs: u64, limit_secs: u64) -> Action {
+    match limit_secs.checked_sub(idle_secs) {
+        Some(0) | None => Action::Detach,
+        Some(left) => Action::Wait(Duration::from_secs(left).min(MAX_POLL)),
+    }
+}
+
+/// Watch `client` until it has been idle past its session's limit and detach
+/// it. Only the newest watcher of a client keeps running.
+pub fn watch(client: &str) -> Result<()> {
+    let owner = owner_file(client)?;
+    if let Some(dir) = owner.parent() {
+        fs::create_dir_all(dir)
+            .map_err(|err| with_context(err, format!("failed to create {}", dir.display())))?;
+    }
+    let pid = process::id().to_string();
+    fs::write(&owner, &pid)
+        .map_err(|err| with_context(err, format!("failed to write {}", owner.display())))?;
+
+    loop {
+        if fs::read_to_string(&owner).ok().as_deref() != Some(pid.as_str()) {
+            return Ok(());
+        }
+        let Some((session, activity)) = tmux::client_activity(client)? else {
+            break;
+        };
+        let Some(minutes) = tmux::auto_detach_minutes(&session)? else {
+            break;
+        };
+        match next_action(unix_now().saturating_sub(activity), minutes * 60) {
+            Action::Detach => {
+                tmux::detach_client(client)?;
+                break;
+            }
+            Action::Wait(delay) => thread::sleep(delay),
+        }
+    }
+    let _ = fs::remove_file(&owner);
+    Ok(())
+}
+
+/// File naming the pid of the watcher in charge of `client`.
+fn owner_file(client: &str) -> Result<PathBuf> {
+    let name: String = client
+        .trim_start_matches('/')
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect();
+    Ok(paths::state_dir()?.join("idle").join(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn next_action_waits_for_the_remaining_time() {
+        assert_eq!(next_action(0, 30), Action::Wait(Duration::from_secs(30)));
+        assert_eq!(next_action(100, 3600), Action::Wait(MAX_POLL));
+        assert_eq!(next_action(600, 600), Action::Detach);
+        assert_eq!(next_action(900, 600), Action::Detach);
+    }
+
+    #[test]
+    fn owner_file_is_named_after_the_client() {
+        let path = owner_file("/dev/pts/3").expect("state dir should resolve");
+        assert!(path.ends_with("idle/dev-pts-3"));
+    }
+}
@@ -0,0 +1,155 @@
+//! `amux status --format waybar|xbar`: every session summed up for a desktop
+//! status bar. Waybar (and polybar through a script) reads one JSON object
+//! with `text`, `tooltip`, and `class`; xbar and SwiftBar read a title line,
+//! `---`, and one menu line per session. The most urgent session state picks
+//! the class, so a bar can turn red on a crash or orange on a question.
+
+use crate::json::{self, Value};
+
+/// What a session needs from you, most urgent first.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum State {
+    /// The agent exited with an error or keep-alive gave up on it.
+    Crashed,
+    /// The agent's input prompt is showing after it went quiet.
+    NeedsInput,
+    /// The agent is printing output.
+    Active,
+    Idle,
+}
+
+impl State {
+    /// The CSS class (waybar) and the word shown in menus.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Crashed => "crashed",
+            Self::NeedsInput => "needs-input",
+            Self::Active => "active",
+            Self::Idle => "idle",
+        }
+    }
+
+    /// xbar color for the state, if it should stand out.
+    fn color(self) -> Option<&'static str> {
+        match self {
+            Self::Crashed => Some("red"),
+            Self::NeedsInput => Some("orange"),
+            Self::Active | Self::Idle => None,
+        }
+    }
+}
+
+/// One session on the bar.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Entry {
+    /// `agent` or `agent/name`.
+    pub label: String,
+    pub state: State,
+}
+
+/// The waybar custom-module object for `entries`.
+pub fn waybar(entries: &[Entry]) -> Value {
+    let tooltip = entries
+        .iter()
+        .map(|entry| format!("{}: {}", entry.label, entry.state.as_str()))
+        .collect::<Vec<_>>()
+        .join("\n");
+    json::object([
+        ("text", Value::from(title(entries))),
+        ("tooltip", Value::from(tooltip)),
+        ("class", Value::from(class(entries))),
+    ])
+}
+
+/// The xbar/SwiftBar plugin output for `entries`.
+pub fn xbar(entries: &[Entry]) -> String {
+    let urgent = entries.iter().map(|entry| entry.state).min();
+    let mut out = line(&title(entries), urgent.and_then(State::color));
+    out.push_str("---\n");
+    for entry in entries {
+        out.push_str(&line(
+            &format!("{}: {}", entry.label, entry.state.as_str()),
+            entry.state.color(),
+        ));
+    }
+    out
+}
+
+fn line(text: &str, color: Option<&str>) -> String {
+    // `|` starts xbar's parameters, so keep it out of the text.
+    let text = text.replace('|', "/");
+    match color {
+        Some(color) => format!("{text} | color={color}\n"),
+        None => format!("{text}\n"),
+    }
+}
+
+/// `3 agents`, followed by the sessions that need attention.
+fn title(entries: &[Entry]) -> String {
+    let count = |state: State| entries.iter().filter(|entry| entry.state == state).count();
+    let mut parts = vec![format!(
+        "{} agent{}",
+        entries.len(),
+        if entries.len() == 1 { "" } else { "s" }
+    )];
+    let input = count(State::NeedsInput);
+    if input > 0 {
+        parts.push(format!("{input} needs input"));
+    }
+    let crashed = count(State::Crashed);
+    if crashed > 0 {
+        parts.push(format!("{crashed} crashed"));
+    }
+    parts.join(", ")
+}
+
+/// The most urgent state, or `none` without sessions.
+fn class(entries: &[Entry]) -> &'static str {
+    entries
+        .iter()
+        .map(|entry| entry.state)
+        .min()
+        .map_or("none", State::as_str)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entries() -> Vec<Entry> {
+        vec![
+            Entry {
+                label: "claude".into(),
+                state: State::Active,
+            },
+            Entry {
+                label: "codex/review".into(),
+                state: State::NeedsInput,
+            },
+        ]
+    }
+
+    #[test]
+    fn waybar_uses_the_most_urgent_state_as_class() {
+        let value = waybar(&entries());
+        let text = |key: &str| value.get(key).and_then(Value::as_str).map(str::to_string);
+        assert_eq!(text("text").as_deref(), Some("2 agents, 1 needs input"));
+        assert_eq!(text("class").as_deref(), Some("needs-input"));
+        assert_eq!(
+            text("tooltip").as_deref(),
+            Some("claude: active\ncodex/review: needs-input")
+        );
+        let empty = waybar(&[]);
+        assert_eq!(empty.get("class").and_then(Value::as_str), Some("none"));
+    }
+
+    #[test]
+    fn xbar_colors_the_title_and_sessions_that_need_attention() {
+        assert_eq!(
+            xbar(&entries()),
+            "2 agents, 1 needs input | color=orange\n---\n\
+             claude: active\ncodex/review: needs-input | color=orange\n"
+        );
+        assert_eq!(xbar(&[]), "0 agents\n---\n");
+    }
+}
@@ -0,0 +1,180 @@
+//! A minimal line-based checklist for picking items in a terminal, used by
+//! `amux rm --interactive`, and a yes/no prompt for destructive commands.
+
+use std::io::{self, BufRead, Write};
+
+use crate::error::{bail, with_context, Result};
+
+#[derive(Debug, PartialEq, Eq)]
+enum Command {
+    /// Flip the listed 1-based item numbers.
+    Toggle(Vec<usize>),
+    All,
+    None,
+    Confirm,
+    Quit,
+}
+
+/// Show `rows` with checkboxes and let the user toggle them until they confirm.
+///
+/// Returns the indices of the checked rows, or `None` if the user quit.
+pub fn run(
+    header: &str,
+    rows: &[String],
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+) -> Result<Option<Vec<usize>>> {
+    let mut checked = vec![false; rows.len()];
+    loop {
+        render(header, rows, &checked, output)
+            .map_err(|err| with_context(err, "failed to write checklist"))?;
+
+        let mut line = String::new();
+        let read = input
+            .read_line(&mut line)
+            .map_err(|err| with_context(err, "failed to read selection"))?;
+        if read == 0 {
+            return Ok(None);
+        }
+
+        match parse(&line, rows.len()) {
+            Ok(Command::Toggle(items)) => {
+                for item in items {
+                    checked[item - 1] = !checked[item - 1];
+                }
+            }
+            Ok(Command::All) => checked.iter_mut().for_each(|value| *value = true),
+            Ok(Command::None) => checked.iter_mut().for_each(|value| *value = false),
+            Ok(Command::Confirm) => {
+                return Ok(Some(
+                    (0..rows.len()).filter(|index| checked[*index]).collect(),
+                ))
+            }
+            Ok(Command::Quit) => return Ok(None),
+            Err(err) => {
+                writeln!(output, "{err}").map_err(|err| with_context(err, "failed to write"))?
+            }
+        }
+    }
+}
+
+/// Ask `question` and read one answer; only `y` or `yes` counts as consent.
+pub fn confirm(question: &str, input: &mut impl BufRead, output: &mut impl Write) -> Result<bool> {
+    write!(output, "{question} [y/N] ")
+        .and_then(|()| output.flush())
+        .map_err(|err| with_context(err, "failed to write prompt"))?;
+    let mut line = String::new();
+    input
+        .read_line(&mut line)
+        .map_err(|err| with_context(err, "failed to read answer"))?;
+    Ok(matches!(line.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+fn render(
+    header: &str,
+    rows: &[String],
+    checked: &[bool],
+    output: &mut impl Write,
+) -> io::Result<()> {
+    writeln!(output)?;
+    writeln!(output, "      {header}")?;
+    for (index, (row, checked)) in rows.iter().zip(checked).enumerate() {
+        let mark = if *checked { 'x' } else { ' ' };
+        writeln!(output, "[{mark}] {:>2} {row}", index + 1)?;
+    }
+    write!(
+        output,
+        "Toggle numbers (e.g. 1 3 5-7), a = all, n = none, y = confirm, q = quit: "
+    )?;
+    output.flush()
+}
+
+fn parse(line: &str, count: usize) -> Result<Command> {
+    match line.trim() {
+        "y" | "yes" => return Ok(Command::Confirm),
+        "q" | "quit" => return Ok(Command::Quit),
+        "a" | "all" => return Ok(Command::All),
+        "n" | "none" => return Ok(Command::None),
+        _ => {}
+    }
+
+    let mut items = Vec::new();
+    for token in line.split(|c: char| c.is_whitespace() || c == ',') {
+        if token.is_empty() {
+            continue;
+        }
+        let (start, end) = match token.split_once('-') {
+            Some((start, end)) => (item_number(start, count)?, item_number(end, count)?),
+            None => {
+                let item = item_number(token, count)?;
+                (item, item)
+            }
+        };
+        if start > end {
+            return bail(format!("invalid range '{token}'"));
+        }
+        items.extend(start..=end);
+    }
+    Ok(Command::Toggle(items))
+}
+
+fn item_number(raw: &str, count: usize) -> Result<usize> {
+    match raw.parse::<usize>() {
+        Ok(item) if (1..=count).contains(&item) => Ok(item),
+        _ => bail(format!(
+            "'{raw}' is not an item number between 1 and {count}"
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_reads_numbers_ranges_and_keywords() {
+        assert_eq!(
+            parse("1 3-4,6\n", 6).expect("numbers should parse"),
+            Command::Toggle(vec![1, 3, 4, 6])
+        );
+        assert_eq!(parse(" y \n", 3).expect("y should parse"), Command::Confirm);
+        assert_eq!(parse("a", 3).expect("a should parse"), Command::All);
+        assert!(parse("0", 3).is_err());
+        assert!(parse("3-1", 3).is_err());
+    }
+
+    #[test]
+    fn confirm_needs_an_explicit_yes() {
+        let answer = |text: &str| {
+            let mut output = Vec::new();
+            confirm("Remove?", &mut io::Cursor::new(text), &mut output).expect("prompt should run")
+        };
+        assert!(answer("y\n"));
+        assert!(answer(" YES \n"));
+        assert!(!answer("\n"));
+        assert!(!answer("nope\n"));
+        assert!(!answer(""));
+    }
+
+    #[test]
+    fn run_returns_checked_rows_on_confirm() {
+        let rows = vec!["one".to_string(), "two".to_string(), "three".to_string()];
+        let mut input = io::Cursor::new("a\n2\ny\n");
+        let mut output = Vec::new();
+        let picked = run("name", &rows, &mut input, &mut output).expect("checklist should run");
+        assert_eq!(picked, Some(vec![0, 2]));
+        assert!(String::from_utf8_lossy(&output).contains("[x]  3 three"));
+    }
+
+    #[test]
+    fn run_returns_none_on_quit_or_eof() {
+        let rows = vec!["one".to_string()];
+        let mut output = Vec::new();
+        let picked = run("", &rows, &mut io::Cursor::new("1\nq\n"), &mut output)
+            .expect("checklist should run");
+        assert_eq!(picked, None);
+        let picked =
+            run("", &rows, &mut io::Cursor::new(""), &mut output).expect("checklist should run");
+        assert_eq!(picked, None);
+    }
+}
@@ -0,0 +1,181 @@
+//! Working-tree snapshots for `amux checkpoint` and `amux rollback`.
+//!
+//! A checkpoint is a commit of the whole working tree (tracked and untracked,
+//! minus ignored files) stored under `refs/amux/checkpoints/<session>/<id>`.
+//! Taking one never touches the branch, the index, or the files on disk.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::error::{fail_with, ErrorKind, Result};
+use crate::git;
+use crate::time::unix_now;
+
+const REF_PREFIX: &str = "refs/amux/checkpoints";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Checkpoint {
+    /// Per-session sequence number, starting at 1.
+    pub id: u32,
+    pub commit: String,
+    /// Unix timestamp the checkpoint was taken.
+    pub created: u64,
+    pub message: String,
+}
+
+/// Snapshot the working tree of the repository at `repo` for `session`.
+pub fn create(repo: &Path, session: &str, message: Option<&str>) -> Result<Checkpoint> {
+    let id = list(repo, session)?
+        .iter()
+        .map(|checkpoint| checkpoint.id)
+        .max()
+        .unwrap_or_default()
+        + 1;
+    let message = message
+        .map(str::to_string)
+        .unwrap_or_else(|| format!("checkpoint {id}"));
+
+    let head = git::head(repo).ok().flatten();
+    let tree = with_scratch_index(repo, |index| {
+        if let Some(head) = &head {
+            git::run(repo, Some(index), &["read-tree", head])?;
+        }
+        git::run(repo, Some(index), &["add", "--all", "."])?;
+        git::run(repo, Some(index), &["write-tree"])
+    })?;
+
+    // Checkpoints are private refs, so a missing user identity should not block them.
+    let mut args = if git::run(repo, None, &["var", "GIT_COMMITTER_IDENT"]).is_ok() {
+        Vec::new()
+    } else {
+        vec!["-c", "user.name=amux", "-c", "user.email=amux@localhost"]
+    };
+    args.extend(["commit-tree", tree.as_str(), "-m", message.as_str()]);
+    if let Some(head) = &head {
+        args.extend(["-p", head.as_str()]);
+    }
+    let commit = git::run(repo, None, &args)?;
+    git::run(repo, None, &["update-ref", &ref_name(session, id), &commit])?;
+
+    Ok(Checkpoint {
+        id,
+        commit,
+        created: unix_now(),
+        message,
+    })
+}
+
+/// Checkpoints taken for `session`, oldest first.
+pub fn list(repo: &Path, session: &str) -> Result<Vec<Checkpoint>> {
+    let output = git::run(
+        repo,
+        None,
+        &[
+            "for-each-ref",
+            "--format=%(refname)%09%(objectname)%09%(creatordate:unix)%09%(subject)",
+            &format!("{REF_PREFIX}/{session}/"),
+        ],
+    )?;
+    let mut checkpoints: Vec<Checkpoint> = output.lines().filter_map(parse_ref_line).collect();
+    checkpoints.sort_by_key(|checkpoint| checkpoint.id);
+    Ok(checkpoints)
+}
+
+/// Make the working tree match checkpoint `id` and reset the index to HEAD.
+///
+/// The current state is checkpointed first, so a rollback can itself be rolled
+/// back; that safety checkpoint is returned.
+pub fn restore(repo: &Path, session: &str, id: u32) -> Result<Checkpoint> {
+    let checkpoints = list(repo, session)?;
+    let Some(target) = checkpoints.iter().find(|checkpoint| checkpoint.id == id) else {
+        return Err(fail_with(
+            ErrorKind::InvalidInput,
+            format!("no checkpoint {id} for session '{session}'"),
+            Some("run `amux checkpoint --list` to see available checkpoints".into()),
+        ));
+    };
+
+    let safety = create(repo, session, Some(&format!("before rollback to {id}")))?;
+    // Load the current snapshot into a scratch index so files created since the
+    // checkpoint, tracked or not, are removed when switching to its tree.
+    with_scratch_index(repo, |index| {
+        git::run(repo, Some(index), &["read-tree", &safety.commit])?;
+        git::run(
+            repo,
+            Some(index),
+            &["read-tree", "-u", "--reset", &target.commit],
+        )
+    })?;
+    match git::head(repo).ok().flatten() {
+        Some(_) => git::run(repo, None, &["reset", "--quiet"])?,
+        None => git::run(repo, None, &["read-tree", "--empty"])?,
+    };
+    Ok(safety)
+}
+
+/// Run `work` against a temporary index file inside the repository's git dir.
+fn with_scratch_index<T>(repo: &Path, work: impl FnOnce(&Path) -> Result<T>) -> Result<T> {
+    let path = git::run(
+        repo,
+        None,
+        &["rev-parse", "--git-path", "amux-checkpoint-index"],
+    )?;
+    let index: PathBuf = repo.join(path);
+    let _ = fs::remove_file(&index);
+    let result = work(&index);
+    let _ = fs::remove_file(&index);
+    result
+}
+
+fn ref_name(session: &str, id: u32) -> String {
+    format!("{REF_PREFIX}/{session}/{id}")
+}
+
+fn parse_ref_line(line: &str) -> Option<Checkpoint> {
+    let mut fields = line.splitn(4, '\t');
+    let refname = fields.next()?;
+    let commit = fields.next()?.to_string();
+    let created = fields.next()?.parse().ok()?;
+    let message = fields.next().unwrap_or_default().to_string();
+    let id = refname.rsplit('/').next()?.parse().ok()?;
+    Some(Checkpoint {
+        id,
+        commit,
+        created,
+        message,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_ref_line_reads_id_from_ref_name() {
+        let checkpoint = parse_ref_line(
+            "refs/amux/checkpoints/amux-codex--x/12\tabc123\t1700000000\tbefore refactor",
+        )
+        .expect("line should parse");
+        assert_eq!(
+            checkpoint,
+            Checkpoint {
+                id: 12,
+                commit: "abc123".into(),
+                created: 1_700_000_000,
+                message: "before refactor".into(),
+            }
+        );
+    }
+
+    #[test]
+    fn parse_ref_line_skips_foreign_refs() {
+        assert_eq!(
+            parse_ref_line("refs/amux/checkpoints/amux-codex/latest\tabc\t1\tmsg"),
+            None
+        );
+        assert_eq!(
+            ref_name("amux-codex", 3),
+            "refs/amux/checkpoints/amux-codex/3"
+        );
+    }
+}
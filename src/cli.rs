@@ -1,10 +1,79 @@
+use std::cell::OnceCell;
 use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::thread;
+use std::time::Duration;
 
-use clap::{Parser, Subcommand};
+use clap::{ArgGroup, CommandFactory, FromArgMatches, Parser, Subcommand};
 
+use crate::activity;
+use crate::agent_edit::{self, Definition};
 use crate::agents;
-use crate::error::{bail, with_context, Result};
+use crate::apply;
+use crate::archive;
+use crate::auto_detach;
+use crate::bar;
+use crate::checklist;
+use crate::checkpoint;
+use crate::clients;
+use crate::completions;
+use crate::config::{self, Config, GroupMember, Stop};
+use crate::dispatch;
+use crate::dnd;
+use crate::doctor::{self, Setup};
+use crate::error::{self, bail, fail_with, with_context, ErrorKind, Result};
+use crate::explain;
+use crate::git;
+use crate::heartbeat;
+use crate::here;
+use crate::hooks;
+use crate::hosts;
+use crate::html;
+use crate::ide;
+use crate::import;
+use crate::input;
+use crate::json::{self, Value};
+use crate::keep_alive;
+use crate::logs;
+use crate::matrix;
+use crate::messages;
+use crate::multiplexer::{self, Backend, Multiplexer};
+use crate::naming::{self, AutoName};
+use crate::nested;
+use crate::notify;
+use crate::open;
+use crate::origin::Origin;
+use crate::parallel;
+use crate::paths;
+use crate::pattern::Pattern;
+use crate::pipe;
+use crate::plan::{self, Action, Plan};
+use crate::preflight;
+use crate::profile;
+use crate::readiness;
+use crate::relay;
+use crate::report::{self, EndReason, RunReport};
+use crate::response::{self, Boundary};
+use crate::session_env::{self, Source};
+use crate::shell_init::{self, Shell};
+use crate::snapshot;
+use crate::stats;
+use crate::status_diff;
+use crate::status_table::{self, Column, Row, SortKey};
+use crate::stop;
+use crate::tasks;
+use crate::template;
+use crate::theme::{self, Theme};
+use crate::time::{self, format_duration, format_timestamp, unix_now};
 use crate::tmux::{self, SessionDetail};
+use crate::tmux_keys;
+use crate::transfer;
+use crate::usage;
+use crate::webhook;
+use crate::worktree;
 
 #[derive(Parser, Debug)]
 #[command(
@@ -14,6 +83,18 @@ use crate::tmux::{self, SessionDetail};
     disable_help_subcommand = true
 )]
 struct Cli {
+    /// Retry transient tmux failures (e.g. server still starting) this many times
+    #[arg(long, global = true, value_name = "N", default_value_t = tmux::DEFAULT_RETRIES)]
+    retries: u32,
+    /// Report errors on stderr as JSON: {"error": {"kind", "message", "hint"}}
+    #[arg(long, global = true)]
+    json_errors: bool,
+    /// Start agents even in directories [dir_guard] denies or outside git repositories
+    #[arg(long, global = true)]
+    allow_any_dir: bool,
+    /// Run sessions in this multiplexer (default: AMUX_BACKEND, else the config's backend, else tmux)
+    #[arg(long, global = true, value_name = "BACKEND", value_parser = ["tmux", "zellij"])]
+    backend: Option<String>,
     #[command(subcommand)]
     command: Commands,
 }
@@ -22,24 +103,99 @@ struct Cli {
 enum Commands {
     /// Show available commands and configured agents
     Help,
-    /// List all configured agents
-    Agents,
-    /// Show the current state of configured agent sessions
+    /// List all configured agents, or add, edit, and remove them
+    Agents {
+        /// Print only the agent names, one per line (used by shell completions)
+        #[arg(long)]
+        names: bool,
+        #[command(subcommand)]
+        command: Option<AgentsCommand>,
+    },
+    /// Print shell functions (ams, ama, amp) and completions to eval in your rc file
+    ShellInit {
+        /// Shell to generate for: bash, zsh, or fish
+        #[arg(value_name = "SHELL")]
+        shell: String,
+    },
+    /// Print a tab-completion script for amux's commands, flags, agents, and sessions
+    Completions {
+        /// Shell to generate for: bash, zsh, or fish
+        #[arg(value_name = "SHELL")]
+        shell: String,
+    },
+    /// Print the names of an agent's running sessions (used by shell completions)
+    #[command(hide = true)]
+    SessionNames {
+        /// Only sessions of this agent
+        agent: Option<String>,
+    },
+    /// Show running agent sessions
     Status {
         /// Optional agent name to filter results
         agent: Option<String>,
+        /// Also show configured agents that have no running session
+        #[arg(long)]
+        all: bool,
+        /// Skip the git working-tree summary for each session
+        #[arg(long)]
+        no_git: bool,
+        /// Add token and cost estimates read from the agent CLI's own logs
+        #[arg(long)]
+        usage: bool,
+        /// Show sessions that ended in the last HOURS hours (default 24) instead
+        #[arg(long, value_name = "HOURS", num_args = 0..=1, default_missing_value = "24")]
+        recent: Option<u64>,
+        /// Show a table sorted by agent, name, uptime, idle, or cpu (largest numbers first)
+        #[arg(long, value_name = "KEY", conflicts_with = "recent")]
+        sort: Option<String>,
+        /// Show a table with these comma-separated columns: agent, name, session,
+        /// state, clients, command, cwd, activity, uptime, idle, cpu, git, usage, note
+        #[arg(long, value_name = "LIST", conflicts_with = "recent")]
+        columns: Option<String>,
+        /// Print only what changed since the last `status --diff`: new and gone
+        /// sessions, and state or activity changes (nothing if nothing changed)
+        #[arg(long, conflicts_with_all = ["agent", "all", "recent", "sort", "columns"])]
+        diff: bool,
+        /// Show the sessions on the hosts under [hosts] in the config, read over ssh
+        #[arg(long, conflicts_with_all = ["agent", "all", "recent", "sort", "columns", "diff"])]
+        hosts: bool,
+        /// Redraw the table every SECONDS seconds (default 2), highlighting new
+        /// sessions, exited panes, and client count changes
+        #[arg(
+            long,
+            value_name = "SECONDS",
+            num_args = 0..=1,
+            default_missing_value = "2",
+            value_parser = clap::value_parser!(u64).range(1..),
+            conflicts_with_all = ["recent", "diff", "hosts"]
+        )]
+        watch: Option<u64>,
+        /// Print text, a JSON array of the sessions (session, agent, name, clients,
+        /// command, pinned, crash_looping, note) for scripts, or a summary for a
+        /// waybar custom module or an xbar/SwiftBar plugin
+        #[arg(
+            long,
+            value_name = "FORMAT",
+            default_value = "text",
+            value_parser = ["text", "json", "waybar", "xbar"],
+            conflicts_with_all = ["all", "usage", "recent", "sort", "columns", "diff", "hosts", "watch"]
+        )]
+        format: String,
     },
-    /// Alias for `status`
+    /// Show every configured agent, running or stopped, with its session count
     List {
         /// Optional agent name to filter results
         agent: Option<String>,
+        /// Print text, or a JSON array of agents (agent, state, sessions, description)
+        #[arg(long, value_name = "FORMAT", default_value = "text", value_parser = ["text", "json"])]
+        format: String,
     },
     /// Launch an agent inside tmux (use --force to restart)
     Start {
         /// Agent identifier (alphanumeric, '-' or '_')
         #[arg(short = 'a', long, value_name = "AGENT", conflicts_with = "agent_pos")]
         agent: Option<String>,
-        /// Optional positional shortcut for default agents
+        /// Optional positional shortcut for built-in and configured agents, or @GROUP for a configured group
         #[arg(value_name = "AGENT", conflicts_with = "agent")]
         agent_pos: Option<String>,
         /// Optional session name to allow multiple sessions per agent
@@ -48,31 +204,162 @@ enum Commands {
         /// Replace the configured command with a custom one (parsed like a shell command)
         #[arg(short = 'c', long = "cmd", value_name = "COMMAND")]
         command_override: Option<String>,
+        /// Run this string with `sh -c` instead (pipes, redirects, and variables work)
+        #[arg(long, value_name = "SCRIPT", conflicts_with_all = ["command_override", "params"])]
+        raw_cmd: Option<String>,
         /// Additional parameters appended to the agent command (parsed like a shell command)
         #[arg(short = 'p', long, value_name = "PARAMS")]
         params: Option<String>,
+        /// Start in this directory instead of the agent's configured `cwd` or the current one
+        #[arg(short = 'd', long, value_name = "DIR")]
+        dir: Option<PathBuf>,
         /// Kill an existing session before starting
-        #[arg(short = 'f', long)]
+        #[arg(short = 'f', long, conflicts_with_all = ["force_adopt", "strict"])]
         force: bool,
+        /// Take over a session that occupies the name but was not started by amux
+        #[arg(long, conflicts_with = "strict")]
+        force_adopt: bool,
+        /// Fail if the session name is taken at all, instead of reusing or renaming
+        #[arg(long, conflicts_with = "auto_name")]
+        strict: bool,
+        /// Maximum number of agents launched at once when starting a group
+        #[arg(short = 'j', long, value_name = "N", default_value_t = 4)]
+        jobs: usize,
+        /// Without --name, pick a fresh session name if the agent is already running
+        /// (index, branch, or petname; default index)
+        #[arg(
+            long,
+            value_name = "STRATEGY",
+            num_args = 0..=1,
+            default_missing_value = "index",
+            conflicts_with = "name"
+        )]
+        auto_name: Option<String>,
+        /// Without --name, name the session after the current git branch (sanitized)
+        #[arg(long, conflicts_with_all = ["name", "auto_name"])]
+        name_from_branch: bool,
+        /// Start one session per combination of the agent's [agents.AGENT.matrix.NAME]
+        /// values (--name becomes a prefix of their names)
+        #[arg(long, value_name = "NAME", conflicts_with_all = ["raw_cmd", "auto_name", "name_from_branch"])]
+        matrix: Option<String>,
+        /// Block until the agent is ready for input, giving up after SECONDS
+        /// (default: the agent's `ready.timeout_secs`, or 60)
+        #[arg(long, value_name = "SECONDS", num_args = 0..=1, require_equals = true)]
+        wait_ready: Option<Option<u64>>,
+        /// With @GROUP or --matrix, print what would be created, kept, or replaced, and change nothing
+        #[arg(long, conflicts_with = "auto_approve")]
+        plan: bool,
+        /// With @GROUP or --matrix, replace running sessions (-f) without asking
+        #[arg(long)]
+        auto_approve: bool,
     },
     /// Remove the tmux session for an agent
     Rm {
         /// Agent identifier (alphanumeric, '-' or '_')
         #[arg(short = 'a', long, value_name = "AGENT", conflicts_with = "agent_pos")]
         agent: Option<String>,
-        /// Optional positional shortcut for default agents
+        /// Optional positional shortcut for built-in and configured agents
+        #[arg(value_name = "AGENT", conflicts_with = "agent")]
+        agent_pos: Option<String>,
+        /// Optional session name if the agent has multiple sessions
+        #[arg(short = 'n', long)]
+        name: Option<String>,
+        /// Pick sessions to remove from a checklist (optionally only AGENT's)
+        #[arg(short = 'i', long, conflicts_with = "name")]
+        interactive: bool,
+        /// Remove every amux session (optionally only AGENT's) after confirmation
+        #[arg(long, conflicts_with_all = ["name", "interactive"])]
+        all: bool,
+        /// With --all, do not ask for confirmation
+        #[arg(
+            short = 'y',
+            long,
+            alias = "auto-approve",
+            requires = "all",
+            conflicts_with = "plan"
+        )]
+        yes: bool,
+        /// With --all, print which sessions would be removed or kept, and change nothing
+        #[arg(long, requires = "all")]
+        plan: bool,
+        /// Also remove pinned sessions
+        #[arg(long)]
+        include_pinned: bool,
+    },
+    /// Remove the sessions whose agent has already exited
+    Prune {
+        /// Do not ask for confirmation
+        #[arg(short = 'y', long, alias = "auto-approve", conflicts_with = "plan")]
+        yes: bool,
+        /// Also remove pinned sessions
+        #[arg(long)]
+        include_pinned: bool,
+        /// Print which sessions would be removed or kept, and change nothing
+        #[arg(long)]
+        plan: bool,
+    },
+    /// Ask an agent to exit (SIGTERM or its stop keys), then remove its session
+    Stop {
+        /// Agent identifier (alphanumeric, '-' or '_')
+        #[arg(short = 'a', long, value_name = "AGENT", conflicts_with = "agent_pos")]
+        agent: Option<String>,
+        /// Optional positional shortcut for built-in and configured agents
         #[arg(value_name = "AGENT", conflicts_with = "agent")]
         agent_pos: Option<String>,
         /// Optional session name if the agent has multiple sessions
         #[arg(short = 'n', long)]
         name: Option<String>,
+        /// Seconds to wait for the agent to exit before killing the session
+        /// (default: [agents.NAME.stop] grace_secs, else 10)
+        #[arg(long, value_name = "SECS")]
+        grace: Option<u64>,
+        /// Also stop pinned sessions
+        #[arg(long)]
+        include_pinned: bool,
+    },
+    /// Remove every amux session, leaving other tmux sessions alone
+    KillServer {
+        /// Do not ask for confirmation
+        #[arg(short = 'y', long, alias = "auto-approve", conflicts_with = "plan")]
+        yes: bool,
+        /// Also remove pinned sessions
+        #[arg(long)]
+        include_pinned: bool,
+        /// Print which sessions would be removed or kept, and change nothing
+        #[arg(long)]
+        plan: bool,
+    },
+    /// Start, restart, or remove sessions to match a workspace file
+    Apply {
+        /// Workspace file with one [[session]] table per agent session
+        file: PathBuf,
+        /// Also remove amux sessions the file does not declare (pinned ones are kept)
+        #[arg(long)]
+        prune: bool,
+        /// Print what would be created, replaced, or removed, and change nothing
+        #[arg(long, conflicts_with = "auto_approve")]
+        plan: bool,
+        /// Replace and remove sessions without asking
+        #[arg(long)]
+        auto_approve: bool,
+    },
+    /// Print the running amux sessions as a workspace file for `amux apply`
+    Freeze,
+    /// Restart the amux sessions on your default tmux server on amux's own server
+    MigrateSocket {
+        /// Do not ask for confirmation
+        #[arg(short = 'y', long, alias = "auto-approve", conflicts_with = "plan")]
+        yes: bool,
+        /// Print which sessions would be moved or kept, and change nothing
+        #[arg(long)]
+        plan: bool,
     },
     /// Attach to an agent's tmux session
     Attach {
         /// Agent identifier (alphanumeric, '-' or '_')
         #[arg(short = 'a', long, value_name = "AGENT", conflicts_with = "agent_pos")]
         agent: Option<String>,
-        /// Optional positional shortcut for default agents
+        /// Optional positional shortcut for built-in and configured agents
         #[arg(value_name = "AGENT", conflicts_with = "agent")]
         agent_pos: Option<String>,
         /// Optional session name if the agent has multiple sessions
@@ -81,69 +368,1134 @@ enum Commands {
         /// Launch the agent if the session does not exist
         #[arg(short = 's', long)]
         start: bool,
+        /// Detach the clients already attached to the session
+        #[arg(long)]
+        take_over: bool,
+        /// Attach on a host under [hosts] in the config, over ssh
+        #[arg(long, value_name = "HOST")]
+        host: Option<String>,
+        /// Start the session running this command (with `sh -c`) if it does not exist
+        #[arg(long = "command", value_name = "SCRIPT", conflicts_with = "host")]
+        command: Option<String>,
+    },
+    /// Run a one-off command (tests, a dev server) in a managed session, like an agent
+    RunAdhoc {
+        /// The command, run with `sh -c`
+        #[arg(value_name = "COMMAND")]
+        command: String,
+        /// Session name (default: the command's program name)
+        #[arg(short = 'n', long)]
+        name: Option<String>,
+        /// Agent name the session is listed under
+        #[arg(short = 'a', long, value_name = "AGENT", default_value = agents::ADHOC_AGENT)]
+        agent: String,
+        /// Run in this directory instead of the current one
+        #[arg(short = 'd', long, value_name = "DIR")]
+        dir: Option<PathBuf>,
+        /// Replace a session of the same name that is still running
+        #[arg(short = 'f', long)]
+        force: bool,
+        /// Attach to the session once it is started
+        #[arg(long)]
+        attach: bool,
     },
     /// Detach all clients from an agent's tmux session
     Detach {
         /// Agent identifier (alphanumeric, '-' or '_')
         #[arg(short = 'a', long, value_name = "AGENT", conflicts_with = "agent_pos")]
         agent: Option<String>,
-        /// Optional positional shortcut for default agents
+        /// Optional positional shortcut for built-in and configured agents
+        #[arg(value_name = "AGENT", conflicts_with = "agent")]
+        agent_pos: Option<String>,
+        /// Optional session name if the agent has multiple sessions
+        #[arg(short = 'n', long)]
+        name: Option<String>,
+    },
+    /// Protect a session from rm and bulk cleanup
+    Pin {
+        /// Agent identifier (alphanumeric, '-' or '_')
+        #[arg(short = 'a', long, value_name = "AGENT", conflicts_with = "agent_pos")]
+        agent: Option<String>,
+        /// Optional positional shortcut for built-in and configured agents
+        #[arg(value_name = "AGENT", conflicts_with = "agent")]
+        agent_pos: Option<String>,
+        /// Optional session name if the agent has multiple sessions
+        #[arg(short = 'n', long)]
+        name: Option<String>,
+    },
+    /// Remove the protection added by `pin`
+    Unpin {
+        /// Agent identifier (alphanumeric, '-' or '_')
+        #[arg(short = 'a', long, value_name = "AGENT", conflicts_with = "agent_pos")]
+        agent: Option<String>,
+        /// Optional positional shortcut for built-in and configured agents
+        #[arg(value_name = "AGENT", conflicts_with = "agent")]
+        agent_pos: Option<String>,
+        /// Optional session name if the agent has multiple sessions
+        #[arg(short = 'n', long)]
+        name: Option<String>,
+    },
+    /// Show, set, or clear a session's note (also shown in tmux's choose-tree)
+    Note {
+        /// Agent identifier (alphanumeric, '-' or '_')
+        #[arg(short = 'a', long, value_name = "AGENT", conflicts_with = "agent_pos")]
+        agent: Option<String>,
+        /// Optional positional shortcut for built-in and configured agents
+        #[arg(value_name = "AGENT", conflicts_with = "agent")]
+        agent_pos: Option<String>,
+        /// Optional session name if the agent has multiple sessions
+        #[arg(short = 'n', long)]
+        name: Option<String>,
+        /// New note text
+        #[arg(short = 'm', long, value_name = "TEXT", conflicts_with = "clear")]
+        message: Option<String>,
+        /// Remove the note
+        #[arg(long)]
+        clear: bool,
+    },
+    /// Mute a session's notifications and prompts while it runs unattended
+    Dnd {
+        /// Agent identifier (alphanumeric, '-' or '_')
+        #[arg(short = 'a', long, value_name = "AGENT", conflicts_with = "agent_pos")]
+        agent: Option<String>,
+        /// Optional positional shortcut for built-in and configured agents
+        #[arg(value_name = "AGENT", conflicts_with = "agent")]
+        agent_pos: Option<String>,
+        /// Optional session name if the agent has multiple sessions
+        #[arg(short = 'n', long)]
+        name: Option<String>,
+        /// Lift the mute after this long (e.g. 30m, 2h, 1h30m); default: until --off
+        #[arg(long = "for", value_name = "DURATION", conflicts_with = "off")]
+        duration: Option<String>,
+        /// Lift the mute now
+        #[arg(long)]
+        off: bool,
+    },
+    /// Print a tmux choose-tree format that shows amux agents, names, and notes
+    ChooseTreeFormat {
+        /// Print a tmux.conf `bind-key` line for KEY instead of the bare format
+        #[arg(long, value_name = "KEY")]
+        bind: Option<String>,
+    },
+    /// Show how a --cmd/--params string is split into arguments and flag quoting pitfalls
+    ExplainCmd {
+        /// The command or params string, exactly as you would pass it
+        #[arg(value_name = "RAW", allow_hyphen_values = true)]
+        raw: String,
+    },
+    /// Show the environment variables a session of an agent will receive
+    Env {
+        /// Agent identifier (alphanumeric, '-' or '_')
+        #[arg(short = 'a', long, value_name = "AGENT", conflicts_with = "agent_pos")]
+        agent: Option<String>,
+        /// Optional positional shortcut for built-in and configured agents
+        #[arg(value_name = "AGENT", conflicts_with = "agent")]
+        agent_pos: Option<String>,
+        /// Optional session name to allow multiple sessions per agent
+        #[arg(short = 'n', long)]
+        name: Option<String>,
+        /// Print values as well as names (may reveal secrets)
+        #[arg(long)]
+        show_values: bool,
+    },
+    /// Show the environment a running agent actually sees, and how it differs from this shell
+    InspectEnv {
+        /// Agent identifier (alphanumeric, '-' or '_')
+        #[arg(short = 'a', long, value_name = "AGENT", conflicts_with = "agent_pos")]
+        agent: Option<String>,
+        /// Optional positional shortcut for built-in and configured agents
+        #[arg(value_name = "AGENT", conflicts_with = "agent")]
+        agent_pos: Option<String>,
+        /// Optional session name if the agent has multiple sessions
+        #[arg(short = 'n', long)]
+        name: Option<String>,
+        /// Print values as well as names (may reveal secrets)
+        #[arg(long)]
+        show_values: bool,
+    },
+    /// Summarize the local run history: sessions per agent per week, average
+    /// duration, restarts, and crashes
+    Stats {
+        /// Agent identifier (alphanumeric, '-' or '_'); default: every agent
+        #[arg(short = 'a', long, value_name = "AGENT", conflicts_with = "agent_pos")]
+        agent: Option<String>,
+        /// Optional positional shortcut for built-in and configured agents
+        #[arg(value_name = "AGENT", conflicts_with = "agent")]
+        agent_pos: Option<String>,
+        /// How many weeks back to cover, this week included
+        #[arg(long, value_name = "N", default_value_t = 8, value_parser = clap::value_parser!(u64).range(1..))]
+        weeks: u64,
+        /// Print text, or a JSON array with one object per agent
+        #[arg(long, value_name = "FORMAT", default_value = "text", value_parser = ["text", "json"])]
+        format: String,
+    },
+    /// Check the TERM and locale new sessions get for rendering problems
+    Doctor {
+        /// Agent identifier (alphanumeric, '-' or '_'); default: every known agent
+        #[arg(short = 'a', long, value_name = "AGENT", conflicts_with = "agent_pos")]
+        agent: Option<String>,
+        /// Optional positional shortcut for built-in and configured agents
+        #[arg(value_name = "AGENT", conflicts_with = "agent")]
+        agent_pos: Option<String>,
+    },
+    /// Print the directories amux uses for config, data, state, and logs
+    Paths,
+    /// Work with the config file
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+    /// Track work items for the current workspace and which session has each
+    Tasks {
+        #[command(subcommand)]
+        command: TasksCommand,
+    },
+    /// Switch the tmux client to the next amux session
+    Next {
+        /// tmux client to switch (default: the one showing this pane)
+        #[arg(long, value_name = "CLIENT")]
+        client: Option<String>,
+    },
+    /// Switch the tmux client to the previous amux session
+    Prev {
+        /// tmux client to switch (default: the one showing this pane)
+        #[arg(long, value_name = "CLIENT")]
+        client: Option<String>,
+    },
+    /// Accept authenticated send/start requests over HTTP on 127.0.0.1
+    Serve {
+        /// Port to listen on instead of [webhook] port (default 7468)
+        #[arg(long, value_name = "PORT")]
+        port: Option<u16>,
+    },
+    /// Chat notifications about agent events, and relaying Slack replies
+    Notify {
+        #[command(subcommand)]
+        command: NotifyCommand,
+    },
+    /// Browse the compressed logs of removed sessions
+    Archive {
+        #[command(subcommand)]
+        command: ArchiveCommand,
+    },
+    /// Browse the pane captures saved every [snapshots] interval_secs
+    Snapshots {
+        #[command(subcommand)]
+        command: SnapshotsCommand,
+    },
+    /// Key bindings for switching between agent sessions inside tmux
+    TmuxKeys {
+        #[command(subcommand)]
+        command: TmuxKeysCommand,
+    },
+    /// Machine-readable commands for editor integrations
+    Ide {
+        #[command(subcommand)]
+        command: IdeCommand,
+    },
+    /// Show everything known about one agent session
+    Show {
+        /// Agent identifier (alphanumeric, '-' or '_')
+        #[arg(short = 'a', long, value_name = "AGENT", conflicts_with = "agent_pos")]
+        agent: Option<String>,
+        /// Optional positional shortcut for built-in and configured agents
+        #[arg(value_name = "AGENT", conflicts_with = "agent")]
+        agent_pos: Option<String>,
+        /// Optional session name if the agent has multiple sessions
+        #[arg(short = 'n', long)]
+        name: Option<String>,
+        /// Print a single JSON object instead of text
+        #[arg(long)]
+        json: bool,
+    },
+    /// Open an agent's working directory in an editor or file manager
+    Open {
+        /// Agent identifier (alphanumeric, '-' or '_')
+        #[arg(short = 'a', long, value_name = "AGENT", conflicts_with = "agent_pos")]
+        agent: Option<String>,
+        /// Optional positional shortcut for built-in and configured agents
+        #[arg(value_name = "AGENT", conflicts_with = "agent")]
+        agent_pos: Option<String>,
+        /// Optional session name if the agent has multiple sessions
+        #[arg(short = 'n', long)]
+        name: Option<String>,
+        /// Program to open it with, e.g. "code -n" (overrides open_with and $EDITOR)
+        #[arg(long, value_name = "PROGRAM")]
+        with: Option<String>,
+    },
+    /// Find the sessions working on the current directory, and attach or send to one
+    Here {
+        /// Attach to the closest one instead of asking
+        #[arg(long, conflicts_with = "send")]
+        attach: bool,
+        /// Type TEXT into the closest one
+        #[arg(long, value_name = "TEXT")]
+        send: Option<String>,
+        /// Look up DIR instead of the current directory
+        #[arg(long, value_name = "DIR")]
+        dir: Option<PathBuf>,
+    },
+    /// Print everything a session has output since it started, from its log
+    Logs {
+        /// Agent identifier (alphanumeric, '-' or '_')
+        #[arg(short = 'a', long, value_name = "AGENT", conflicts_with = "agent_pos")]
+        agent: Option<String>,
+        /// Optional positional shortcut for built-in and configured agents
         #[arg(value_name = "AGENT", conflicts_with = "agent")]
         agent_pos: Option<String>,
         /// Optional session name if the agent has multiple sessions
         #[arg(short = 'n', long)]
         name: Option<String>,
+        /// Keep printing new output until the session ends
+        #[arg(short = 'f', long)]
+        follow: bool,
+        /// Start with only the last LINES lines
+        #[arg(long, value_name = "LINES")]
+        lines: Option<usize>,
+        /// Keep terminal escape sequences (colors), e.g. for `less -R`
+        #[arg(long)]
+        raw: bool,
+    },
+    /// Print an agent's screen and scrollback, optionally with its colors
+    Capture {
+        /// Agent identifier (alphanumeric, '-' or '_')
+        #[arg(short = 'a', long, value_name = "AGENT", conflicts_with = "agent_pos")]
+        agent: Option<String>,
+        /// Optional positional shortcut for built-in and configured agents
+        #[arg(value_name = "AGENT", conflicts_with = "agent")]
+        agent_pos: Option<String>,
+        /// Optional session name if the agent has multiple sessions
+        #[arg(short = 'n', long)]
+        name: Option<String>,
+        /// Scrollback lines to include above the screen
+        #[arg(long, value_name = "LINES", default_value_t = 1000)]
+        history: usize,
+        /// Keep the colors as ANSI escape sequences
+        #[arg(long, conflicts_with = "html")]
+        ansi: bool,
+        /// Print a standalone HTML page with the colors, for sharing
+        #[arg(long)]
+        html: bool,
+    },
+    /// Detach a session's clients after MINUTES without input (0 turns it off)
+    AutoDetach {
+        /// Agent identifier (alphanumeric, '-' or '_')
+        #[arg(short = 'a', long, value_name = "AGENT", conflicts_with = "agent_pos")]
+        agent: Option<String>,
+        /// Optional positional shortcut for built-in and configured agents
+        #[arg(value_name = "AGENT", conflicts_with = "agent")]
+        agent_pos: Option<String>,
+        /// Optional session name if the agent has multiple sessions
+        #[arg(short = 'n', long)]
+        name: Option<String>,
+        /// Idle minutes before clients are detached; 0 turns it off
+        #[arg(long, value_name = "MINUTES")]
+        after: u64,
+    },
+    /// Detach a client once it has been idle too long (called by a tmux hook)
+    #[command(hide = true)]
+    IdleWatch {
+        #[arg(long)]
+        client: String,
+    },
+    /// Record a run report for a session whose agent exited (called by a tmux hook)
+    #[command(hide = true)]
+    RecordExit { agent: String, session: String },
+    /// Measure amux's own performance, for bug reports
+    #[command(hide = true)]
+    Profile {
+        #[command(subcommand)]
+        command: ProfileCommand,
+    },
+    /// Show uncommitted changes in an agent session's git working directory
+    Diff {
+        /// Agent identifier (alphanumeric, '-' or '_')
+        #[arg(short = 'a', long, value_name = "AGENT", conflicts_with = "agent_pos")]
+        agent: Option<String>,
+        /// Optional positional shortcut for built-in and configured agents
+        #[arg(value_name = "AGENT", conflicts_with = "agent")]
+        agent_pos: Option<String>,
+        /// Optional session name if the agent has multiple sessions
+        #[arg(short = 'n', long)]
+        name: Option<String>,
+        /// Print the full diff instead of a diffstat
+        #[arg(long)]
+        full: bool,
+    },
+    /// Snapshot an agent session's working tree so its changes can be rolled back
+    Checkpoint {
+        /// Agent identifier (alphanumeric, '-' or '_')
+        #[arg(short = 'a', long, value_name = "AGENT", conflicts_with = "agent_pos")]
+        agent: Option<String>,
+        /// Optional positional shortcut for built-in and configured agents
+        #[arg(value_name = "AGENT", conflicts_with = "agent")]
+        agent_pos: Option<String>,
+        /// Optional session name if the agent has multiple sessions
+        #[arg(short = 'n', long)]
+        name: Option<String>,
+        /// Describe the checkpoint
+        #[arg(short = 'm', long, value_name = "MESSAGE", conflicts_with = "list")]
+        message: Option<String>,
+        /// List the session's checkpoints instead of taking one
+        #[arg(short = 'l', long)]
+        list: bool,
+    },
+    /// Restore an agent session's working tree to a checkpoint
+    Rollback {
+        /// Agent identifier (alphanumeric, '-' or '_')
+        #[arg(short = 'a', long, value_name = "AGENT", conflicts_with = "agent_pos")]
+        agent: Option<String>,
+        /// Optional positional shortcut for built-in and configured agents
+        #[arg(value_name = "AGENT", conflicts_with = "agent")]
+        agent_pos: Option<String>,
+        /// Optional session name if the agent has multiple sessions
+        #[arg(short = 'n', long)]
+        name: Option<String>,
+        /// Checkpoint number, as shown by `checkpoint --list`
+        #[arg(long, value_name = "CHECKPOINT")]
+        to: u32,
+    },
+    /// Run a command here and send its output to an agent session when it finishes
+    Pipe {
+        /// Agent identifier (alphanumeric, '-' or '_')
+        #[arg(short = 'a', long, value_name = "AGENT", conflicts_with = "agent_pos")]
+        agent: Option<String>,
+        /// Optional positional shortcut for built-in and configured agents
+        #[arg(value_name = "AGENT", conflicts_with = "agent")]
+        agent_pos: Option<String>,
+        /// Optional session name if the agent has multiple sessions
+        #[arg(short = 'n', long)]
+        name: Option<String>,
+        /// Text to introduce the output with, instead of naming the command
+        #[arg(short = 'm', long, value_name = "TEXT")]
+        message: Option<String>,
+        /// Send only the last N lines of output
+        #[arg(long, value_name = "N", default_value_t = 200)]
+        tail: usize,
+        /// The command to run, after `--`
+        #[arg(last = true, required = true, value_name = "COMMAND")]
+        command: Vec<String>,
+    },
+    /// Forward one agent's responses (or the parts matching a filter) to another
+    Relay {
+        /// Session to read from, as AGENT or AGENT:NAME
+        #[arg(long, value_name = "AGENT[:NAME]")]
+        from: String,
+        /// Session to send to, as AGENT or AGENT:NAME
+        #[arg(long, value_name = "AGENT[:NAME]")]
+        to: String,
+        /// Forward only the text matching this regular expression
+        #[arg(long, value_name = "REGEX")]
+        filter: Option<String>,
+        /// Text to put above each forwarded block
+        #[arg(short = 'm', long, value_name = "TEXT")]
+        message: Option<String>,
+        /// Stop after forwarding one block
+        #[arg(long)]
+        once: bool,
+    },
+    /// Feed tasks from a file to a pool of agent sessions and track their progress
+    Dispatch {
+        /// Task file: one task per line, or per blank-line-separated block with --blocks
+        #[arg(value_name = "FILE", required_unless_present = "status")]
+        file: Option<PathBuf>,
+        /// Agents in the pool; AGENT:NAME uses that exact session
+        #[arg(
+            long,
+            value_name = "AGENT[:NAME],...",
+            value_delimiter = ',',
+            required_unless_present = "status"
+        )]
+        agents: Vec<String>,
+        /// Sessions per plain AGENT, named dispatch-1, dispatch-2, ...
+        #[arg(short = 'j', long, value_name = "N", default_value_t = 1)]
+        parallel: usize,
+        /// Treat blank-line-separated blocks as tasks instead of single lines
+        #[arg(long)]
+        blocks: bool,
+        /// Give up on a task after this many seconds
+        #[arg(short = 't', long, value_name = "SECONDS")]
+        timeout: Option<u64>,
+        /// Show the progress of the latest dispatch run instead
+        #[arg(long, conflicts_with_all = ["file", "agents", "blocks", "timeout"])]
+        status: bool,
+    },
+    /// Copy a file or directory to where an agent can see it
+    Cp {
+        /// Local file or directory
+        #[arg(value_name = "SOURCE")]
+        source: PathBuf,
+        /// Agent and path, relative to the agent's directory
+        #[arg(value_name = "AGENT:PATH")]
+        destination: String,
+        /// Optional session name if the agent has multiple sessions
+        #[arg(short = 'n', long)]
+        name: Option<String>,
+    },
+    /// Type the same prompt into several agent sessions at once
+    Broadcast {
+        /// Sessions to type into; AGENT:NAME picks a named session
+        #[arg(
+            long,
+            value_name = "AGENT[:NAME],...",
+            value_delimiter = ',',
+            required = true
+        )]
+        agents: Vec<String>,
+        /// Open a tiled view of the sessions where typing goes to all of them
+        #[arg(short = 'i', long)]
+        interactive: bool,
+        /// Text to send to each session; read from stdin when omitted
+        #[arg(value_name = "TEXT", conflicts_with = "interactive")]
+        text: Option<String>,
+    },
+    /// List the panes of an agent session and which one runs the agent
+    Panes {
+        /// Agent identifier (alphanumeric, '-' or '_')
+        #[arg(short = 'a', long, value_name = "AGENT", conflicts_with = "agent_pos")]
+        agent: Option<String>,
+        /// Optional positional shortcut for built-in and configured agents
+        #[arg(value_name = "AGENT", conflicts_with = "agent")]
+        agent_pos: Option<String>,
+        /// Optional session name if the agent has multiple sessions
+        #[arg(short = 'n', long)]
+        name: Option<String>,
+    },
+    /// Type a prompt into a running agent session
+    Send {
+        /// Agent identifier (alphanumeric, '-' or '_')
+        #[arg(short = 'a', long, value_name = "AGENT")]
+        agent: Option<String>,
+        /// Optional positional shortcut for built-in and configured agents
+        #[arg(value_name = "AGENT")]
+        agent_pos: Option<String>,
+        /// Text to send; read from stdin when omitted
+        #[arg(value_name = "TEXT")]
+        text: Option<String>,
+        /// Optional session name if the agent has multiple sessions
+        #[arg(short = 'n', long)]
+        name: Option<String>,
+        /// Render a named prompt template from the config ({stdin} and {input} are available)
+        #[arg(short = 't', long, value_name = "NAME")]
+        template: Option<String>,
+        /// Send to this window (index or name) instead of the agent's
+        #[arg(short = 'w', long, value_name = "WINDOW")]
+        window: Option<String>,
+        /// Send to this pane (index within the window, or a %id) instead of the agent's
+        #[arg(short = 'P', long, value_name = "PANE")]
+        pane: Option<String>,
+        /// Press Enter after the text (the default)
+        #[arg(long, overrides_with = "no_enter")]
+        enter: bool,
+        /// Leave the text in the agent's input line without pressing Enter
+        #[arg(long, overrides_with = "enter")]
+        no_enter: bool,
+    },
+    /// Block until an agent session finishes responding or its output matches a pattern
+    #[command(group(ArgGroup::new("condition").required(true).args(["for_response", "pattern"])))]
+    Wait {
+        /// Agent identifier (alphanumeric, '-' or '_')
+        #[arg(short = 'a', long, value_name = "AGENT", conflicts_with = "agent_pos")]
+        agent: Option<String>,
+        /// Optional positional shortcut for built-in and configured agents
+        #[arg(value_name = "AGENT", conflicts_with = "agent")]
+        agent_pos: Option<String>,
+        /// Optional session name if the agent has multiple sessions
+        #[arg(short = 'n', long)]
+        name: Option<String>,
+        /// Wait until output is quiet and the agent's input prompt is visible
+        #[arg(long = "for-response")]
+        for_response: bool,
+        /// Wait until new output matches this regular expression
+        #[arg(long = "for", value_name = "PATTERN")]
+        pattern: Option<String>,
+        /// Give up after this many seconds (exits with status 1)
+        #[arg(short = 't', long, value_name = "SECONDS")]
+        timeout: Option<u64>,
     },
 }
 
-pub fn run() -> Result<()> {
-    let cli = Cli::parse();
+#[derive(Subcommand, Debug)]
+enum ConfigCommand {
+    /// Rewrite the config file in the current format, keeping a backup
+    Upgrade {
+        /// Print the upgraded config instead of rewriting the file
+        #[arg(long)]
+        print: bool,
+    },
+}
 
-    match cli.command {
-        Commands::Help => {
-            print_help();
-        }
-        Commands::Agents => {
-            print_agents();
-        }
-        Commands::Status { agent } | Commands::List { agent } => {
-            handle_status(agent)?;
-        }
-        Commands::Start {
-            agent,
-            agent_pos,
-            name,
-            command_override,
-            params,
-            force,
-        } => {
-            let agent = resolve_agent_input(agent, agent_pos, "start")?;
-            handle_start(
-                &agent,
-                name.as_deref(),
-                command_override.as_deref(),
-                params.as_deref(),
-                force,
-            )?;
-        }
-        Commands::Rm {
-            agent,
-            agent_pos,
-            name,
-        } => {
-            let agent = resolve_agent_input(agent, agent_pos, "rm")?;
-            handle_rm(&agent, name.as_deref())?;
-        }
-        Commands::Attach {
-            agent,
-            agent_pos,
-            name,
-            start,
-        } => {
-            let agent = resolve_agent_input(agent, agent_pos, "attach")?;
-            handle_attach(&agent, name.as_deref(), start)?;
-        }
-        Commands::Detach {
+#[derive(Subcommand, Debug)]
+enum AgentsCommand {
+    /// List all configured agents (the same as `amux agents`)
+    List {
+        /// Print only the agent names, one per line
+        #[arg(long)]
+        names: bool,
+    },
+    /// Define a new agent in the config file
+    Add {
+        /// Agent identifier (alphanumeric, '-' or '_')
+        name: String,
+        /// Command line that starts the agent (parsed like a shell command)
+        #[arg(short = 'c', long, value_name = "COMMAND", allow_hyphen_values = true)]
+        command: String,
+        /// Shown next to the agent in `amux agents`
+        #[arg(long)]
+        description: Option<String>,
+        /// Arguments added after the command
+        #[arg(short = 'p', long, value_name = "PARAMS", allow_hyphen_values = true)]
+        params: Option<String>,
+        /// Directory new sessions start in
+        #[arg(long, value_name = "DIR")]
+        cwd: Option<String>,
+    },
+    /// Change an agent's settings in the config file ("" removes a setting)
+    Edit {
+        /// Agent identifier, built in or from the config file
+        name: String,
+        /// Command line that starts the agent (parsed like a shell command)
+        #[arg(short = 'c', long, value_name = "COMMAND", allow_hyphen_values = true)]
+        command: Option<String>,
+        /// Shown next to the agent in `amux agents`
+        #[arg(long)]
+        description: Option<String>,
+        /// Arguments added after the command
+        #[arg(short = 'p', long, value_name = "PARAMS", allow_hyphen_values = true)]
+        params: Option<String>,
+        /// Directory new sessions start in
+        #[arg(long, value_name = "DIR")]
+        cwd: Option<String>,
+    },
+    /// Remove an agent's section from the config file
+    Remove {
+        /// Agent identifier
+        name: String,
+    },
+    /// Define agents from another tool's config: a team agents.yaml or
+    /// agents.json, an aider config, or an MCP client config
+    Import {
+        /// The file to read
+        #[arg(value_name = "FILE")]
+        file: PathBuf,
+        /// What the file is (detected from its name and contents by default)
+        #[arg(long, value_name = "KIND", value_parser = ["agents", "aider", "mcp"])]
+        from: Option<String>,
+        /// Print what would be added without changing the config
+        #[arg(long)]
+        plan: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum ProfileCommand {
+    /// Time each tmux query amux makes and `amux status` as a whole
+    Bench {
+        /// How many times to run each query
+        #[arg(long, default_value_t = 5, value_parser = clap::value_parser!(u32).range(1..))]
+        runs: u32,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum TasksCommand {
+    /// Show the workspace's open and assigned tasks
+    List {
+        /// Include tasks that are done
+        #[arg(long)]
+        all: bool,
+    },
+    /// Add a task to the board
+    Add {
+        /// What needs doing
+        text: String,
+        /// Assign it right away, as AGENT or AGENT:NAME
+        #[arg(long, value_name = "AGENT[:NAME]")]
+        assign: Option<String>,
+        /// Also type the task into the assigned session
+        #[arg(long, requires = "assign")]
+        send: bool,
+    },
+    /// Assign a task to an agent session
+    Assign {
+        id: u32,
+        /// Session to assign it to, as AGENT or AGENT:NAME
+        #[arg(value_name = "AGENT[:NAME]")]
+        to: String,
+        /// Also type the task into the session
+        #[arg(long)]
+        send: bool,
+    },
+    /// Mark a task done
+    Done { id: u32 },
+    /// Mark a done task as not done again
+    Reopen { id: u32 },
+}
+
+#[derive(Subcommand, Debug)]
+enum NotifyCommand {
+    /// Type replies in the threads of posted Slack messages into their sessions
+    Bridge,
+    /// Post needs_input events for a session (started by amux)
+    #[command(hide = true)]
+    Watch {
+        #[arg(long, value_name = "SESSION")]
+        session: String,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum ArchiveCommand {
+    /// List archived runs, newest first
+    List {
+        /// Only runs of this agent
+        #[arg(short = 'a', long, value_name = "AGENT")]
+        agent: Option<String>,
+    },
+    /// Show an archived run's details and, with --log, its log
+    Show {
+        /// Archive id from `amux archive list` (a unique prefix is enough)
+        id: String,
+        /// Print the decompressed log after the details
+        #[arg(long)]
+        log: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum SnapshotsCommand {
+    /// List a session's snapshots, newest first (also after the session is gone)
+    List {
+        /// Agent identifier (alphanumeric, '-' or '_')
+        #[arg(short = 'a', long, value_name = "AGENT", conflicts_with = "agent_pos")]
+        agent: Option<String>,
+        /// Optional positional shortcut for built-in and configured agents
+        #[arg(value_name = "AGENT", conflicts_with = "agent")]
+        agent_pos: Option<String>,
+        /// Optional session name if the agent has multiple sessions
+        #[arg(short = 'n', long)]
+        name: Option<String>,
+    },
+    /// Print a snapshot: the newest, or the Nth newest with --back N
+    Show {
+        /// Agent identifier (alphanumeric, '-' or '_')
+        #[arg(short = 'a', long, value_name = "AGENT", conflicts_with = "agent_pos")]
+        agent: Option<String>,
+        /// Optional positional shortcut for built-in and configured agents
+        #[arg(value_name = "AGENT", conflicts_with = "agent")]
+        agent_pos: Option<String>,
+        /// Optional session name if the agent has multiple sessions
+        #[arg(short = 'n', long)]
+        name: Option<String>,
+        /// Which snapshot, counting back from the newest (1)
+        #[arg(long, value_name = "N", default_value_t = 1)]
+        back: usize,
+    },
+    /// Save snapshots of a session until it ends (started by amux)
+    #[command(hide = true)]
+    Watch {
+        #[arg(long, value_name = "SESSION")]
+        session: String,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum TmuxKeysCommand {
+    /// Print the tmux.conf bindings
+    Print {
+        /// Key (after the prefix) that pops up a menu of amux sessions
+        #[arg(long, value_name = "KEY", default_value = "A")]
+        picker_key: String,
+        /// Key (after the prefix) that switches to the next amux session
+        #[arg(long, value_name = "KEY", default_value = "N")]
+        next_key: String,
+        /// Key (after the prefix) that switches to the previous amux session
+        #[arg(long, value_name = "KEY", default_value = "P")]
+        prev_key: String,
+    },
+    /// Add the bindings to tmux.conf (replacing an earlier install) and load them
+    Install {
+        /// Key (after the prefix) that pops up a menu of amux sessions
+        #[arg(long, value_name = "KEY", default_value = "A")]
+        picker_key: String,
+        /// Key (after the prefix) that switches to the next amux session
+        #[arg(long, value_name = "KEY", default_value = "N")]
+        next_key: String,
+        /// Key (after the prefix) that switches to the previous amux session
+        #[arg(long, value_name = "KEY", default_value = "P")]
+        prev_key: String,
+        /// Configuration file to edit instead of the one amux's tmux server reads
+        #[arg(long, value_name = "PATH")]
+        file: Option<PathBuf>,
+    },
+    /// Pop up the session menu on a client (run by the binding)
+    #[command(hide = true)]
+    Menu {
+        #[arg(long)]
+        client: String,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum IdeCommand {
+    /// List agent sessions for an editor session picker
+    List {
+        /// Print a JSON array instead of tab-separated lines
+        #[arg(long)]
+        json: bool,
+    },
+    /// Print a JSON terminal-launch spec for attaching to an agent session
+    Open {
+        /// Agent identifier (alphanumeric, '-' or '_')
+        #[arg(short = 'a', long, value_name = "AGENT", conflicts_with = "agent_pos")]
+        agent: Option<String>,
+        /// Optional positional shortcut for built-in and configured agents
+        #[arg(value_name = "AGENT", conflicts_with = "agent")]
+        agent_pos: Option<String>,
+        /// Optional session name if the agent has multiple sessions
+        #[arg(short = 'n', long)]
+        name: Option<String>,
+        /// Launch the agent first if the session does not exist
+        #[arg(short = 's', long)]
+        start: bool,
+    },
+}
+
+pub fn run() -> Result<()> {
+    let parsed = Cli::command().try_get_matches().and_then(|matches| {
+        let name = matches.subcommand_name().unwrap_or_default().to_string();
+        Cli::from_arg_matches(&matches).map(|cli| (cli, name))
+    });
+    let (cli, name) = match parsed {
+        Ok(parsed) => parsed,
+        // Usage errors only become JSON when asked for; help and version still print normally.
+        Err(err) if err.use_stderr() && std::env::args().any(|arg| arg == "--json-errors") => {
+            error::set_json_errors(true);
+            let rendered = err.render().to_string();
+            let first_line = rendered.lines().next().unwrap_or_default();
+            let message = first_line.trim_start_matches("error: ").to_string();
+            return Err(fail_with(
+                ErrorKind::InvalidInput,
+                message,
+                Some("run `amux --help` for usage".into()),
+            ));
+        }
+        Err(err) => err.exit(),
+    };
+    error::set_json_errors(cli.json_errors);
+    tmux::set_retries(cli.retries);
+    preflight::set_allow_any_dir(cli.allow_any_dir);
+    // A config that fails to load is reported by the command that reads it.
+    let loaded = OnceCell::new();
+    let config = || loaded.get_or_init(|| config::load().ok());
+    let backend = multiplexer::select(cli.backend.as_deref(), || {
+        config()
+            .as_ref()
+            .map(|config| config.backend)
+            .unwrap_or_default()
+    })?;
+    tmux::set_socket(tmux::select_socket(|| {
+        config()
+            .as_ref()
+            .and_then(|config| config.tmux_socket.clone())
+    })?);
+    let command = cli.command;
+    ensure_supported(backend, &command, &name)?;
+
+    match command {
+        Commands::Help => {
+            print_help();
+        }
+        Commands::Agents { names, command } => match command {
+            Some(AgentsCommand::List { names: list_names }) => list_agents(names || list_names),
+            Some(AgentsCommand::Import { file, from, plan }) => {
+                handle_agents_import(&file, from.as_deref(), plan)?
+            }
+            Some(command) => handle_agents(command)?,
+            None => list_agents(names),
+        },
+        Commands::ShellInit { shell } => {
+            let shell = shell.parse::<Shell>()?;
+            print!(
+                "{}",
+                shell_init::script(shell, &completions::specs(&Cli::command()))
+            );
+        }
+        Commands::Completions { shell } => {
+            let shell = shell.parse::<Shell>()?;
+            print!(
+                "# amux completions; load with: eval \"$(amux completions {shell})\"\n{}",
+                completions::script(shell, &completions::specs(&Cli::command()))
+            );
+        }
+        Commands::SessionNames { agent } => {
+            // Completion scripts pass an empty agent when none is typed yet.
+            let agent = agent.filter(|agent| !agent.is_empty());
+            for session in mux().list_sessions()? {
+                if agent.as_ref().is_none_or(|agent| session.agent == *agent) {
+                    if let Some(name) = session.name {
+                        println!("{name}");
+                    }
+                }
+            }
+        }
+        Commands::Status {
+            agent,
+            all,
+            no_git,
+            usage,
+            recent,
+            sort,
+            columns,
+            diff,
+            hosts,
+            watch,
+            format,
+        } => match recent {
+            Some(hours) => handle_recent(agent, hours)?,
+            None if format == "json" => handle_status_json(agent)?,
+            None if format != "text" => handle_status_bar(agent, &format)?,
+            None if diff => handle_status_diff()?,
+            None if hosts => handle_status_hosts()?,
+            None if watch.is_some() => {
+                let seconds = watch.unwrap_or(2);
+                handle_status_watch(agent, all, !no_git, sort, columns, seconds)?
+            }
+            None if sort.is_some() || columns.is_some() => {
+                handle_status_table(agent, all, !no_git, sort, columns)?
+            }
+            None => handle_status(agent, all, !no_git, usage)?,
+        },
+        Commands::List { agent, format } => {
+            handle_list(agent, format == "json")?;
+        }
+        Commands::Start {
+            agent,
+            agent_pos,
+            name,
+            command_override,
+            raw_cmd,
+            params,
+            dir,
+            force,
+            force_adopt,
+            strict,
+            jobs,
+            auto_name,
+            name_from_branch,
+            matrix,
+            wait_ready,
+            plan,
+            auto_approve,
+        } => {
+            if let Some(dir) = dir {
+                set_start_dir(&dir)?;
+            }
+            let conflict = if force {
+                Conflict::Restart
+            } else if force_adopt {
+                Conflict::Adopt
+            } else if strict {
+                Conflict::Refuse
+            } else {
+                Conflict::Reuse
+            };
+            let batch = Batch {
+                conflict,
+                jobs,
+                wait_ready,
+                plan_only: plan,
+                auto_approve,
+            };
+            if let Some(group) = agent_pos.as_deref().and_then(|pos| pos.strip_prefix('@')) {
+                if name.is_some()
+                    || command_override.is_some()
+                    || raw_cmd.is_some()
+                    || params.is_some()
+                    || auto_name.is_some()
+                    || name_from_branch
+                    || matrix.is_some()
+                {
+                    return bail(
+                        "start: --name, --cmd, --raw-cmd, --params, --auto-name, --name-from-branch, and --matrix cannot be combined with a group",
+                    );
+                }
+                return handle_start_group(group, &batch);
+            }
+            let agent = resolve_agent_input(agent, agent_pos, "start")?;
+            if let Some(matrix) = matrix {
+                let command = match &command_override {
+                    Some(raw) => CommandSpec::Tokens(raw),
+                    None => CommandSpec::Configured,
+                };
+                return handle_start_matrix(
+                    &agent,
+                    &matrix,
+                    name.as_deref(),
+                    command,
+                    params.as_deref(),
+                    &batch,
+                );
+            }
+            if plan || auto_approve {
+                return bail(
+                    "start: --plan and --auto-approve only apply to a group (@GROUP) or --matrix",
+                );
+            }
+            let auto_name = auto_name.map(|raw| raw.parse::<AutoName>()).transpose()?;
+            let command = match (&command_override, &raw_cmd) {
+                (_, Some(script)) => CommandSpec::Shell(script),
+                (Some(raw), None) => CommandSpec::Tokens(raw),
+                (None, None) => CommandSpec::Configured,
+            };
+            handle_start(
+                &agent,
+                name.as_deref(),
+                command,
+                params.as_deref(),
+                conflict,
+                Naming {
+                    auto_name,
+                    from_branch: name_from_branch.then_some(true),
+                },
+                wait_ready,
+            )?;
+        }
+        Commands::Rm {
+            agent,
+            agent_pos,
+            name,
+            interactive,
+            all,
+            yes,
+            plan,
+            include_pinned,
+        } => {
+            if interactive {
+                return handle_rm_interactive(agent.or(agent_pos).as_deref(), include_pinned);
+            }
+            if all {
+                let bulk = Bulk {
+                    yes,
+                    include_pinned,
+                    plan_only: plan,
+                };
+                return handle_rm_all(agent.or(agent_pos).as_deref(), &bulk);
+            }
+            let agent = resolve_agent_input(agent, agent_pos, "rm")?;
+            handle_rm(&agent, name.as_deref(), include_pinned)?;
+        }
+        Commands::Stop {
+            agent,
+            agent_pos,
+            name,
+            grace,
+            include_pinned,
+        } => {
+            let agent = resolve_agent_input(agent, agent_pos, "stop")?;
+            handle_stop(&agent, name.as_deref(), grace, include_pinned)?;
+        }
+        Commands::KillServer {
+            yes,
+            include_pinned,
+            plan,
+        } => {
+            let bulk = Bulk {
+                yes,
+                include_pinned,
+                plan_only: plan,
+            };
+            handle_kill_server(&bulk)?
+        }
+        Commands::Prune {
+            yes,
+            include_pinned,
+            plan,
+        } => {
+            let bulk = Bulk {
+                yes,
+                include_pinned,
+                plan_only: plan,
+            };
+            handle_prune(&bulk)?
+        }
+        Commands::Apply {
+            file,
+            prune,
+            plan,
+            auto_approve,
+        } => handle_apply(&file, prune, plan, auto_approve)?,
+        Commands::Freeze => handle_freeze()?,
+        Commands::MigrateSocket { yes, plan } => handle_migrate_socket(yes, plan)?,
+        Commands::Attach {
+            agent,
+            agent_pos,
+            name,
+            start,
+            take_over,
+            host,
+            command,
+        } => {
+            // A command stands in for the agent, which need not be configured,
+            // and names the session the way `run-adhoc` does.
+            let (agent, name) = match &command {
+                Some(script) => (
+                    agent
+                        .or(agent_pos)
+                        .unwrap_or_else(|| agents::ADHOC_AGENT.to_string()),
+                    Some(name.map_or_else(|| adhoc_name(script), Ok)?),
+                ),
+                None => (resolve_agent_input(agent, agent_pos, "attach")?, name),
+            };
+            let name = name.as_deref();
+            let start = match &command {
+                Some(script) => Some(CommandSpec::Shell(script)),
+                None => start.then_some(CommandSpec::Configured),
+            };
+            match host {
+                Some(host) => {
+                    handle_remote_attach(&host, &agent, name, start.is_some(), take_over)?
+                }
+                None => handle_attach(&agent, name, start, take_over)?,
+            }
+        }
+        Commands::RunAdhoc {
+            command,
+            name,
+            agent,
+            dir,
+            force,
+            attach,
+        } => {
+            if let Some(dir) = dir {
+                set_start_dir(&dir)?;
+            }
+            let name = match name {
+                Some(name) => name,
+                None => adhoc_name(&command)?,
+            };
+            let conflict = if force {
+                Conflict::Restart
+            } else {
+                Conflict::Reuse
+            };
+            handle_start(
+                &agent,
+                Some(&name),
+                CommandSpec::Shell(&command),
+                None,
+                conflict,
+                Naming {
+                    auto_name: None,
+                    from_branch: Some(false),
+                },
+                None,
+            )?;
+            if attach {
+                handle_attach(&agent, Some(&name), None, false)?;
+            }
+        }
+        Commands::Detach {
             agent,
             agent_pos,
             name,
@@ -151,119 +1503,3579 @@ pub fn run() -> Result<()> {
             let agent = resolve_agent_input(agent, agent_pos, "detach")?;
             handle_detach(&agent, name.as_deref())?;
         }
+        Commands::Pin {
+            agent,
+            agent_pos,
+            name,
+        } => {
+            let agent = resolve_agent_input(agent, agent_pos, "pin")?;
+            handle_pin(&agent, name.as_deref(), true)?;
+        }
+        Commands::Unpin {
+            agent,
+            agent_pos,
+            name,
+        } => {
+            let agent = resolve_agent_input(agent, agent_pos, "unpin")?;
+            handle_pin(&agent, name.as_deref(), false)?;
+        }
+        Commands::Note {
+            agent,
+            agent_pos,
+            name,
+            message,
+            clear,
+        } => {
+            let agent = resolve_agent_input(agent, agent_pos, "note")?;
+            let change = if clear { Some(None) } else { message.map(Some) };
+            handle_note(&agent, name.as_deref(), change)?;
+        }
+        Commands::Dnd {
+            agent,
+            agent_pos,
+            name,
+            duration,
+            off,
+        } => {
+            let agent = resolve_agent_input(agent, agent_pos, "dnd")?;
+            let seconds = match duration {
+                Some(raw) => Some(time::parse_duration(&raw).ok_or_else(|| {
+                    fail_with(
+                        ErrorKind::InvalidInput,
+                        format!("--for: cannot read '{raw}' as a duration"),
+                        Some("use a number with s, m, h, or d, such as 30m or 1h30m".into()),
+                    )
+                })?),
+                None => None,
+            };
+            handle_dnd(&agent, name.as_deref(), (!off).then_some(seconds))?;
+        }
+        Commands::ChooseTreeFormat { bind } => match bind {
+            Some(key) => println!("{}", choose_tree_binding(&key)),
+            None => println!("{}", tmux::CHOOSE_TREE_FORMAT),
+        },
+        Commands::ExplainCmd { raw } => {
+            explain_command(&raw)?;
+        }
+        Commands::Env {
+            agent,
+            agent_pos,
+            name,
+            show_values,
+        } => {
+            let agent = resolve_agent_input(agent, agent_pos, "env")?;
+            handle_env(&agent, name.as_deref(), show_values)?;
+        }
+        Commands::InspectEnv {
+            agent,
+            agent_pos,
+            name,
+            show_values,
+        } => {
+            let agent = resolve_agent_input(agent, agent_pos, "inspect-env")?;
+            handle_inspect_env(&agent, name.as_deref(), show_values)?;
+        }
+        Commands::Stats {
+            agent,
+            agent_pos,
+            weeks,
+            format,
+        } => {
+            handle_stats(agent.or(agent_pos), weeks, format == "json")?;
+        }
+        Commands::Doctor { agent, agent_pos } => {
+            handle_doctor(agent.or(agent_pos).as_deref())?;
+        }
+        Commands::Paths => {
+            print_paths()?;
+        }
+        Commands::Config { command } => handle_config(command)?,
+        Commands::Tasks { command } => handle_tasks(command)?,
+        Commands::Next { client } => handle_cycle(client, true)?,
+        Commands::Prev { client } => handle_cycle(client, false)?,
+        Commands::Serve { port } => handle_serve(port)?,
+        Commands::Notify { command } => handle_notify(command)?,
+        Commands::Archive { command } => handle_archive(command)?,
+        Commands::Snapshots { command } => handle_snapshots(command)?,
+        Commands::TmuxKeys { command } => handle_tmux_keys(command)?,
+        Commands::Ide { command } => match command {
+            IdeCommand::List { json } => handle_ide_list(json)?,
+            IdeCommand::Open {
+                agent,
+                agent_pos,
+                name,
+                start,
+            } => {
+                let agent = resolve_agent_input(agent, agent_pos, "ide open")?;
+                handle_ide_open(&agent, name.as_deref(), start)?;
+            }
+        },
+        Commands::Show {
+            agent,
+            agent_pos,
+            name,
+            json,
+        } => {
+            let agent = resolve_agent_input(agent, agent_pos, "show")?;
+            handle_show(&agent, name.as_deref(), json)?;
+        }
+        Commands::Open {
+            agent,
+            agent_pos,
+            name,
+            with,
+        } => {
+            let agent = resolve_agent_input(agent, agent_pos, "open")?;
+            handle_open(&agent, name.as_deref(), with.as_deref())?;
+        }
+        Commands::Here { attach, send, dir } => handle_here(attach, send, dir)?,
+        Commands::Logs {
+            agent,
+            agent_pos,
+            name,
+            follow,
+            lines,
+            raw,
+        } => {
+            let agent = resolve_agent_input(agent, agent_pos, "logs")?;
+            let view = logs::View { lines, raw, follow };
+            handle_logs(&agent, name.as_deref(), view)?;
+        }
+        Commands::Capture {
+            agent,
+            agent_pos,
+            name,
+            history,
+            ansi,
+            html,
+        } => {
+            let agent = resolve_agent_input(agent, agent_pos, "capture")?;
+            handle_capture(&agent, name.as_deref(), history, ansi, html)?;
+        }
+        Commands::AutoDetach {
+            agent,
+            agent_pos,
+            name,
+            after,
+        } => {
+            let agent = resolve_agent_input(agent, agent_pos, "auto-detach")?;
+            handle_auto_detach(&agent, name.as_deref(), after)?;
+        }
+        Commands::IdleWatch { client } => auto_detach::watch(&client)?,
+        Commands::RecordExit { agent, session } => {
+            record_exit(&agent, &session)?;
+        }
+        Commands::Profile {
+            command: ProfileCommand::Bench { runs },
+        } => handle_profile_bench(runs)?,
+        Commands::Diff {
+            agent,
+            agent_pos,
+            name,
+            full,
+        } => {
+            let agent = resolve_agent_input(agent, agent_pos, "diff")?;
+            handle_diff(&agent, name.as_deref(), full)?;
+        }
+        Commands::Checkpoint {
+            agent,
+            agent_pos,
+            name,
+            message,
+            list,
+        } => {
+            let agent = resolve_agent_input(agent, agent_pos, "checkpoint")?;
+            handle_checkpoint(&agent, name.as_deref(), message.as_deref(), list)?;
+        }
+        Commands::Rollback {
+            agent,
+            agent_pos,
+            name,
+            to,
+        } => {
+            let agent = resolve_agent_input(agent, agent_pos, "rollback")?;
+            handle_rollback(&agent, name.as_deref(), to)?;
+        }
+        Commands::Send {
+            agent,
+            agent_pos,
+            text,
+            name,
+            template,
+            window,
+            pane,
+            enter: _,
+            no_enter,
+        } => {
+            // With --agent, the first positional is the text rather than an agent.
+            let (agent_pos, text) = match (&agent, agent_pos, text) {
+                (Some(_), Some(first), None) => (None, Some(first)),
+                (Some(_), Some(first), Some(_)) => {
+                    return bail(format!(
+                        "send: unexpected argument '{first}' (agent already given with --agent)"
+                    ));
+                }
+                (_, agent_pos, text) => (agent_pos, text),
+            };
+            let agent = resolve_agent_input(agent, agent_pos, "send")?;
+            let target = PaneChoice {
+                window: window.as_deref(),
+                pane: pane.as_deref(),
+            };
+            handle_send(
+                &agent,
+                name.as_deref(),
+                text,
+                template.as_deref(),
+                target,
+                !no_enter,
+            )?;
+        }
+        Commands::Cp {
+            source,
+            destination,
+            name,
+        } => handle_cp(&source, &destination, name.as_deref())?,
+        Commands::Broadcast {
+            agents,
+            interactive,
+            text,
+        } => handle_broadcast(&agents, interactive, text)?,
+        Commands::Pipe {
+            agent,
+            agent_pos,
+            name,
+            message,
+            tail,
+            command,
+        } => {
+            let agent = resolve_agent_input(agent, agent_pos, "pipe")?;
+            handle_pipe(&agent, name.as_deref(), message.as_deref(), tail, &command)?;
+        }
+        Commands::Relay {
+            from,
+            to,
+            filter,
+            message,
+            once,
+        } => {
+            handle_relay(&from, &to, filter.as_deref(), message, once)?;
+        }
+        Commands::Dispatch {
+            file,
+            agents,
+            parallel,
+            blocks,
+            timeout,
+            status,
+        } => match file {
+            Some(file) if !status => handle_dispatch(
+                &file,
+                &agents,
+                parallel,
+                blocks,
+                timeout.map(Duration::from_secs),
+            )?,
+            _ => print_dispatch_status()?,
+        },
+        Commands::Panes {
+            agent,
+            agent_pos,
+            name,
+        } => {
+            let agent = resolve_agent_input(agent, agent_pos, "panes")?;
+            handle_panes(&agent, name.as_deref())?;
+        }
+        Commands::Wait {
+            agent,
+            agent_pos,
+            name,
+            for_response: _,
+            pattern,
+            timeout,
+        } => {
+            let agent = resolve_agent_input(agent, agent_pos, "wait")?;
+            handle_wait(
+                &agent,
+                name.as_deref(),
+                pattern.as_deref(),
+                timeout.map(Duration::from_secs),
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Where a session's command comes from.
+#[derive(Clone, Copy)]
+enum CommandSpec<'a> {
+    /// The agent's configured or default command.
+    Configured,
+    /// `--cmd`: split like a shell command line and run directly.
+    Tokens(&'a str),
+    /// `--raw-cmd`: handed to `sh -c` unchanged.
+    Shell(&'a str),
+}
+
+enum StartOutcome {
+    Started,
+    AlreadyRunning,
+    Adopted,
+}
+
+/// What `start` does when the session name is already in use.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Conflict {
+    /// Leave a running amux session alone.
+    Reuse,
+    /// Kill the amux session and start a new one (`--force`).
+    Restart,
+    /// Mark a session amux did not start as its own (`--force-adopt`).
+    Adopt,
+    /// Treat any existing session as an error (`--strict`).
+    Refuse,
+}
+
+/// How to name a session started without `-n`; `None`s defer to the config.
+#[derive(Clone, Copy)]
+struct Naming {
+    /// `--auto-name`.
+    auto_name: Option<AutoName>,
+    /// `--name-from-branch`.
+    from_branch: Option<bool>,
+}
+
+fn handle_start(
+    agent: &str,
+    session_name: Option<&str>,
+    command: CommandSpec,
+    params: Option<&str>,
+    conflict: Conflict,
+    naming: Naming,
+    wait_ready: Option<Option<u64>>,
+) -> Result<()> {
+    let config = config::load()?;
+    let auto_name = naming.auto_name;
+    // The branch name is the same on every start, so it reuses the session
+    // rather than picking a fresh one like --auto-name.
+    let branch_name = if session_name.is_none()
+        && auto_name.is_none()
+        && naming
+            .from_branch
+            .unwrap_or_else(|| config.name_from_branch(agent))
+    {
+        naming::from_branch(&session_dir(&config, agent)?)?
+    } else {
+        None
+    };
+    let session_name = branch_name.as_deref().or(session_name);
+    let strategy = match conflict {
+        Conflict::Reuse => {
+            auto_name.or_else(|| config.agent(agent).and_then(|agent| agent.auto_name))
+        }
+        Conflict::Refuse => None,
+        _ => auto_name,
+    };
+    let generated = match strategy {
+        Some(strategy) if session_name.is_none() && conflict != Conflict::Restart => {
+            generate_session_name(agent, strategy)?
+        }
+        _ => None,
+    };
+    let session_name = generated.as_deref().or(session_name);
+    let session_id = tmux::session_name(agent, session_name);
+    match start_agent(&config, agent, session_name, command, params, conflict)? {
+        StartOutcome::Started => println!("{}", session_text("started", agent, &session_id)),
+        StartOutcome::AlreadyRunning => {
+            println!("{}", session_text("already_running", agent, &session_id))
+        }
+        StartOutcome::Adopted => println!("{}", session_text("adopted", agent, &session_id)),
+    }
+    if let Some(timeout) = wait_ready {
+        wait_until_ready(&config, agent, &session_id, timeout)?;
+        println!("{}", session_text("ready", agent, &session_id));
+    }
+    Ok(())
+}
+
+/// Block until `agent` in `session_id` passes its readiness probe, or fail
+/// with a timeout error.
+fn wait_until_ready(
+    config: &Config,
+    agent: &str,
+    session_id: &str,
+    timeout: Option<u64>,
+) -> Result<()> {
+    let probe = readiness::Probe::for_agent(config, agent)?;
+    let timeout = timeout.map(Duration::from_secs).unwrap_or(probe.timeout);
+    if readiness::wait(&probe, agent, session_id, timeout)? {
+        return Ok(());
+    }
+    Err(fail_with(
+        ErrorKind::Timeout,
+        messages::text(
+            "timed_out_ready",
+            &[
+                ("agent", agent),
+                ("seconds", &timeout.as_secs().to_string()),
+                ("session", session_id),
+            ],
+        ),
+        Some(format!(
+            "configure how to detect readiness under [agents.{agent}.ready]"
+        )),
+    ))
+}
+
+/// A fresh name for `agent` if its default session is taken, or `None` to use the default.
+fn generate_session_name(agent: &str, strategy: AutoName) -> Result<Option<String>> {
+    ensure_valid_identifier("agent", agent)?;
+    let running = mux().list_sessions()?;
+    let taken: Vec<Option<&str>> = running
+        .iter()
+        .filter(|session| session.agent == agent)
+        .map(|session| session.name.as_deref())
+        .collect();
+    if !taken.contains(&None) {
+        return Ok(None);
+    }
+    let cwd = std::env::current_dir()
+        .map_err(|err| with_context(err, "failed to read the current directory"))?;
+    naming::generate(strategy, &cwd, |name| taken.contains(&Some(name))).map(Some)
+}
+
+/// Options for starting several sessions at once (a group or a matrix).
+struct Batch {
+    conflict: Conflict,
+    /// Sessions started at once.
+    jobs: usize,
+    wait_ready: Option<Option<u64>>,
+    /// `--plan`: show what would happen and stop.
+    plan_only: bool,
+    auto_approve: bool,
+}
+
+/// One session of a batch, with the params it starts with.
+struct Launch {
+    member: GroupMember,
+    params: Option<String>,
+}
+
+fn handle_start_group(group: &str, batch: &Batch) -> Result<()> {
+    let config = config::load()?;
+    let members = config.group(group)?;
+    if members.is_empty() {
+        println!("{}", messages::text("group_empty", &[("group", group)]));
+        return Ok(());
+    }
+    let launches: Vec<Launch> = members
+        .iter()
+        .map(|member| Launch {
+            member: member.clone(),
+            params: None,
+        })
+        .collect();
+    start_batch(
+        &config,
+        &format!("@{group}"),
+        &launches,
+        CommandSpec::Configured,
+        batch,
+    )
+}
+
+/// `start AGENT --matrix NAME`: one session per combination, named after its
+/// values; `params` goes after the matrix's own.
+fn handle_start_matrix(
+    agent: &str,
+    matrix: &str,
+    prefix: Option<&str>,
+    command: CommandSpec,
+    params: Option<&str>,
+    batch: &Batch,
+) -> Result<()> {
+    ensure_valid_identifier("agent", agent)?;
+    if let Some(prefix) = prefix {
+        ensure_valid_session_name(prefix)?;
+    }
+    let config = config::load()?;
+    let launches: Vec<Launch> = matrix::combinations(config.matrix(agent, matrix)?, prefix)
+        .into_iter()
+        .map(|combination| Launch {
+            member: GroupMember {
+                agent: agent.to_string(),
+                name: Some(combination.name),
+            },
+            params: Some(match params {
+                Some(extra) => format!("{} {extra}", combination.params),
+                None => combination.params,
+            }),
+        })
+        .collect();
+    start_batch(
+        &config,
+        &format!("{agent} --matrix {matrix}"),
+        &launches,
+        command,
+        batch,
+    )
+}
+
+/// Start `launches` in parallel after showing and approving the plan, and
+/// report each outcome; `label` names the batch in prompts and errors.
+fn start_batch(
+    config: &Config,
+    label: &str,
+    launches: &[Launch],
+    command: CommandSpec,
+    batch: &Batch,
+) -> Result<()> {
+    let plan = batch_plan(launches, batch.conflict)?;
+    if batch.plan_only || (plan.is_destructive() && !batch.auto_approve) {
+        print!("{}", plan.render());
+    }
+    if batch.plan_only {
+        return Ok(());
+    }
+    if !plan::approve(&plan, &format!("start {label}"), batch.auto_approve)? {
+        println!("Nothing changed.");
+        return Ok(());
+    }
+
+    let outcomes = parallel::map_bounded(launches, batch.jobs, |launch| -> Result<StartOutcome> {
+        let member = &launch.member;
+        let outcome = start_agent(
+            config,
+            &member.agent,
+            member.name.as_deref(),
+            command,
+            launch.params.as_deref(),
+            batch.conflict,
+        )?;
+        if let Some(timeout) = batch.wait_ready {
+            let session_id = tmux::session_name(&member.agent, member.name.as_deref());
+            wait_until_ready(config, &member.agent, &session_id, timeout)?;
+        }
+        Ok(outcome)
+    });
+
+    let mut failures = 0;
+    for (launch, outcome) in launches.iter().zip(outcomes) {
+        let member = &launch.member;
+        let session_id = tmux::session_name(&member.agent, member.name.as_deref());
+        let agent = &member.agent;
+        match outcome {
+            Ok(StartOutcome::Started) => {
+                println!("{}", session_text("started", agent, &session_id))
+            }
+            Ok(StartOutcome::AlreadyRunning) => {
+                println!(
+                    "{}",
+                    session_text("already_running_short", agent, &session_id)
+                )
+            }
+            Ok(StartOutcome::Adopted) => {
+                println!("{}", session_text("adopted", agent, &session_id))
+            }
+            Err(err) => {
+                failures += 1;
+                let error = err.to_string();
+                let text = messages::text("start_failed", &[("agent", agent), ("error", &error)]);
+                println!("{text}");
+            }
+        }
+    }
+
+    if failures > 0 {
+        return bail(format!(
+            "{label}: {failures} of {} agents failed to start",
+            launches.len()
+        ));
+    }
+    Ok(())
+}
+
+/// What starting `launches` with `conflict` would do to each session; mirrors
+/// the checks in `start_agent`.
+fn batch_plan(launches: &[Launch], conflict: Conflict) -> Result<Plan> {
+    let mut plan = Plan::default();
+    for Launch { member, .. } in launches {
+        let session_id = tmux::session_name(&member.agent, member.name.as_deref());
+        if !tmux::has_session(&session_id)? {
+            plan.push(Action::Create, &session_id, None);
+            continue;
+        }
+        let (action, reason) = match (conflict, tmux::is_amux_session(&session_id)?) {
+            (Conflict::Refuse, _) => (Action::Conflict, "already exists"),
+            (Conflict::Reuse | Conflict::Adopt, true) => (Action::Keep, "already running"),
+            (Conflict::Restart, true) => (Action::Replace, "running"),
+            (Conflict::Adopt, false) => (Action::Adopt, "not started by amux"),
+            (Conflict::Reuse | Conflict::Restart, false) => {
+                (Action::Conflict, "not started by amux")
+            }
+        };
+        plan.push(action, &session_id, Some(reason));
+    }
+    Ok(plan)
+}
+
+fn start_agent(
+    config: &Config,
+    agent: &str,
+    session_name: Option<&str>,
+    command: CommandSpec,
+    params: Option<&str>,
+    conflict: Conflict,
+) -> Result<StartOutcome> {
+    ensure_valid_identifier("agent", agent)?;
+    if let Some(name) = session_name {
+        ensure_valid_session_name(name)?;
+    }
+
+    let session_id = tmux::session_name(agent, session_name);
+    let context = template::Context {
+        cwd: session_dir(config, agent)?,
+        agent: agent.to_string(),
+        name: session_name.map(str::to_owned),
+        session: session_id.clone(),
+    };
+
+    let command_tokens = start_command(config, agent, command, params, &context)?;
+
+    let mux = mux();
+    if mux.has_session(&session_id)? {
+        let managed = mux.is_managed(&session_id)?;
+        match (conflict, managed) {
+            (Conflict::Refuse, _) => {
+                return Err(fail_with(
+                    ErrorKind::SessionConflict,
+                    format!("session '{session_id}' already exists (--strict refuses to reuse it)"),
+                    None,
+                ));
+            }
+            (Conflict::Reuse | Conflict::Adopt, true) => return Ok(StartOutcome::AlreadyRunning),
+            (Conflict::Restart, true) => {
+                if let Some(settings) = config.configured_stop(agent) {
+                    if mux.holds_options() {
+                        ask_to_exit(agent, &session_id, &settings)?;
+                    }
+                }
+                if mux.has_session(&session_id)? {
+                    mux.kill_session(&session_id)?;
+                }
+            }
+            (Conflict::Adopt, false) => {
+                adopt_session(agent, session_name, &session_id)?;
+                apply_theme(config, agent, &session_id);
+                apply_auto_detach(config, agent, &session_id);
+                return Ok(StartOutcome::Adopted);
+            }
+            (Conflict::Reuse | Conflict::Restart, false) => {
+                return Err(fail_with(
+                    ErrorKind::SessionConflict,
+                    format!(
+                        "tmux session '{session_id}' already exists but was not started by amux"
+                    ),
+                    Some(foreign_session_hint(agent, &session_id)),
+                ));
+            }
+        }
+    }
+
+    spawn_session(config, agent, session_name, &session_id, &command_tokens)?;
+    Ok(StartOutcome::Started)
+}
+
+/// The multiplexer sessions run in for this process.
+fn mux() -> &'static dyn Multiplexer {
+    multiplexer::backend().multiplexer()
+}
+
+/// Refuse `command` up front when `backend` cannot run it. Every command works on
+/// tmux; other backends start, attach to, list, read from, type into, and
+/// remove single sessions, and run the commands that never touch one. `name` is
+/// the subcommand as typed, e.g. `kill-server`.
+fn ensure_supported(backend: Backend, command: &Commands, name: &str) -> Result<()> {
+    if backend == Backend::Tmux {
+        return Ok(());
+    }
+    let mux = backend.multiplexer();
+    let unsupported = |what: &str| {
+        Err(fail_with(
+            ErrorKind::InvalidInput,
+            format!("`amux {what}` needs the tmux backend"),
+            Some(format!(
+                "the {} backend cannot do this; pass --backend tmux",
+                mux.name()
+            )),
+        ))
+    };
+    match command {
+        Commands::Help
+        | Commands::Agents { .. }
+        | Commands::ShellInit { .. }
+        | Commands::Completions { .. }
+        | Commands::Paths
+        | Commands::Config { .. }
+        | Commands::Stats { .. }
+        | Commands::SessionNames { .. }
+        | Commands::List { .. } => Ok(()),
+        Commands::Status {
+            diff: false,
+            watch: None,
+            format,
+            ..
+        } if format == "text" || format == "json" => Ok(()),
+        Commands::Status { .. } => unsupported("status --diff, --watch, or --format waybar|xbar"),
+        Commands::Start {
+            agent_pos,
+            matrix: None,
+            wait_ready: None,
+            plan: false,
+            ..
+        } if !agent_pos.as_deref().is_some_and(|pos| pos.starts_with('@')) => Ok(()),
+        Commands::Start { .. } => unsupported("start @GROUP, --matrix, --wait-ready, or --plan"),
+        Commands::Attach {
+            host: None,
+            command: None,
+            take_over: false,
+            ..
+        } => Ok(()),
+        Commands::Attach { .. } => unsupported("attach --host, --command, or --take-over"),
+        Commands::Rm {
+            interactive: false,
+            all: false,
+            ..
+        } => Ok(()),
+        Commands::Rm { .. } => unsupported("rm --all or -i"),
+        Commands::Capture {
+            ansi: false,
+            html: false,
+            ..
+        } => Ok(()),
+        Commands::Capture { .. } => unsupported("capture --ansi or --html"),
+        Commands::Send {
+            window: None,
+            pane: None,
+            ..
+        } => Ok(()),
+        Commands::Send { .. } => unsupported("send --window or --pane"),
+        _ => unsupported(name),
+    }
+}
+
+/// A session name for `command`: its program's name, e.g. `pytest` for
+/// `pytest -x` or `cargo` for `RUST_LOG=debug cargo test`.
+fn adhoc_name(command: &str) -> Result<String> {
+    let words = shell_words::split(command).unwrap_or_default();
+    let program = words
+        .iter()
+        .find(|word| !word.contains('='))
+        .map(|word| word.rsplit('/').next().unwrap_or(word))
+        .map(naming::sanitize)
+        .unwrap_or_default();
+    if program.is_empty() {
+        return Err(fail_with(
+            ErrorKind::InvalidInput,
+            "run-adhoc: cannot name a session after this command".to_string(),
+            Some("pass a name with -n NAME".into()),
+        ));
+    }
+    Ok(program)
+}
+
+/// What a new session of `agent` runs, with placeholders filled in from `context`.
+fn start_command(
+    config: &Config,
+    agent: &str,
+    command: CommandSpec,
+    params: Option<&str>,
+    context: &template::Context,
+) -> Result<Vec<String>> {
+    let command_override = match command {
+        CommandSpec::Shell(script) => return shell_command(script, context),
+        CommandSpec::Tokens(raw) => Some(raw),
+        CommandSpec::Configured => None,
+    };
+    let mut command_tokens = agent_command(config, agent, command_override)?;
+
+    if let Some(extra) = params {
+        let mut extra_tokens = agents::parse_tokens("params", extra)?;
+        command_tokens.append(&mut extra_tokens);
+    }
+
+    if command_tokens.is_empty() {
+        return bail(format!("resolved command for '{agent}' is empty"));
+    }
+
+    command_tokens
+        .iter()
+        .map(|token| template::expand(token, context))
+        .collect()
+}
+
+/// The agent's command with the config's default params, unless `command_override`
+/// replaces both.
+fn agent_command(
+    config: &Config,
+    agent: &str,
+    command_override: Option<&str>,
+) -> Result<Vec<String>> {
+    let mut tokens = agents::resolve_agent_command(agent, command_override)?;
+    let defaults = config
+        .agent(agent)
+        .and_then(|agent| agent.params.as_deref());
+    if let (None, Some(defaults)) = (command_override, defaults) {
+        tokens.extend(agents::parse_tokens(
+            &format!("agents.{agent}.params"),
+            defaults,
+        )?);
+    }
+    Ok(tokens)
+}
+
+/// `start --dir`, which wins over every agent's configured `cwd`.
+static START_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+fn set_start_dir(dir: &Path) -> Result<()> {
+    if !dir.is_dir() {
+        return Err(fail_with(
+            ErrorKind::InvalidInput,
+            format!("start: {} is not a directory", dir.display()),
+            None,
+        ));
+    }
+    let dir = dir
+        .canonicalize()
+        .map_err(|err| with_context(err, format!("failed to resolve {}", dir.display())))?;
+    let _ = START_DIR.set(dir);
+    Ok(())
+}
+
+/// Where `agent`'s new sessions start: `start --dir`, the config's `cwd`, or
+/// the current directory.
+fn session_dir(config: &Config, agent: &str) -> Result<PathBuf> {
+    if let Some(dir) = START_DIR.get() {
+        return Ok(dir.clone());
+    }
+    match config.agent_dir(agent) {
+        Some(dir) if dir.is_dir() => Ok(dir),
+        Some(dir) => Err(fail_with(
+            ErrorKind::Config,
+            format!("agents.{agent}.cwd: {} is not a directory", dir.display()),
+            None,
+        )),
+        None => std::env::current_dir()
+            .map_err(|err| with_context(err, "failed to read the current directory")),
+    }
+}
+
+/// `sh -c SCRIPT`, with placeholders in `script` replaced by shell-quoted values.
+fn shell_command(script: &str, context: &template::Context) -> Result<Vec<String>> {
+    if script.trim().is_empty() {
+        return bail("--raw-cmd is empty");
+    }
+    let script = template::expand_with(script, |key| {
+        Ok(context
+            .lookup(key)?
+            .map(|value| shell_words::quote(&value).into_owned()))
+    })?;
+    Ok(vec!["sh".to_string(), "-c".to_string(), script])
+}
+
+fn foreign_session_hint(agent: &str, session_id: &str) -> String {
+    format!(
+        "pass --force-adopt to manage it as '{agent}', --auto-name to start under another name, \
+         or rename it with `{} rename-session -t '={session_id}' NEW_NAME`",
+        tmux::command_prefix()
+    )
+}
+
+/// Message `id`, which takes `{agent}` and `{session}`, in the selected locale.
+fn session_text(id: &str, agent: &str, session_id: &str) -> String {
+    messages::text(id, &[("agent", agent), ("session", session_id)])
+}
+
+fn no_session(agent: &str, session_name: Option<&str>) -> error::DynError {
+    let session_id = tmux::session_name(agent, session_name);
+    let start = match session_name {
+        Some(name) => format!("amux start -a {agent} -n {}", shell_words::quote(name)),
+        None => format!("amux start -a {agent}"),
+    };
+    fail_with(
+        ErrorKind::SessionNotFound,
+        session_text("no_session", agent, &session_id),
+        Some(format!("start it with `{start}`")),
+    )
+}
+
+fn adopt_session(agent: &str, name: Option<&str>, session_id: &str) -> Result<()> {
+    tmux::set_environment(session_id, "AMUX_AGENT", agent)?;
+    tmux::set_environment(session_id, "AMUX_SESSION", session_id)?;
+    watch_session(agent, name, session_id);
+    heartbeat::refresh("adopted", session_id);
+    Ok(())
+}
+
+fn spawn_session(
+    config: &Config,
+    agent: &str,
+    name: Option<&str>,
+    session_id: &str,
+    command_tokens: &[String],
+) -> Result<()> {
+    let cwd = session_dir(config, agent)?;
+    preflight::check_dir(&config.dir_guard, agent, &cwd)?;
+    preflight::check(&config.preflight(agent), agent, session_id, name, &cwd)?;
+    let env = session_env::explicit(config, agent, session_id)?;
+    let command_tokens = session_env::with_term(config, agent, command_tokens);
+    let mux = mux();
+    mux.new_session(session_id, &command_tokens, &env, Some(&cwd))
+        .map_err(|err| with_context(err, format!("failed to start agent '{agent}'")))?;
+    if !mux.holds_options() {
+        return Ok(());
+    }
+    watch_session(agent, name, session_id);
+    let origin = Origin::current().to_json().to_string();
+    if let Err(err) = tmux::set_origin(session_id, &origin) {
+        eprintln!("amux: warning: origin not recorded for '{session_id}': {err}");
+    }
+    if let Ok(Some(head)) = git::head(&cwd) {
+        if let Err(err) = tmux::set_base(session_id, &head) {
+            eprintln!("amux: warning: start commit not recorded for '{session_id}': {err}");
+        }
+    }
+    apply_theme(config, agent, session_id);
+    apply_auto_detach(config, agent, session_id);
+    heartbeat::refresh("started", session_id);
+    if let Err(err) = report::catch_early_exit(agent, session_id) {
+        eprintln!("amux: warning: exit of '{session_id}' not recorded: {err}");
+    }
+    Ok(())
+}
+
+/// Turn on the configured idle detach for a new session.
+fn apply_auto_detach(config: &Config, agent: &str, session_id: &str) {
+    let Some(minutes) = config.auto_detach_minutes(agent) else {
+        return;
+    };
+    if let Err(err) = auto_detach::enable(session_id, minutes) {
+        eprintln!("amux: warning: auto-detach disabled for '{session_id}': {err}");
+    }
+}
+
+/// Put the agent's icon in the window name and its color on the status-line entry.
+fn apply_theme(config: &Config, agent: &str, session_id: &str) {
+    let theme = config.theme(agent);
+    if theme.is_empty() {
+        return;
+    }
+    let name = theme.icon.as_ref().map(|icon| format!("{icon} {agent}"));
+    let color = theme.color.map(|color| color.tmux());
+    if let Err(err) = tmux::style_window(session_id, name.as_deref(), color.as_deref()) {
+        eprintln!("amux: warning: could not theme '{session_id}': {err}");
+    }
+}
+
+/// Start output tracking and exit reporting; either failing only costs features.
+fn watch_session(agent: &str, name: Option<&str>, session_id: &str) {
+    if let Err(err) = tmux::label_session(session_id, agent, name) {
+        eprintln!("amux: warning: could not label '{session_id}': {err}");
+    }
+    if let Err(err) = tmux::record_agent_pane(session_id) {
+        eprintln!("amux: warning: agent pane not recorded for '{session_id}': {err}");
+    }
+    if let Err(err) = activity::enable(session_id) {
+        eprintln!("amux: warning: output tracking disabled for '{session_id}': {err}");
+    }
+    if let Err(err) = report::watch(agent, session_id) {
+        eprintln!("amux: warning: exit reports disabled for '{session_id}': {err}");
+    }
+    if let Err(err) = start_input_watch(session_id) {
+        eprintln!("amux: warning: input notifications disabled for '{session_id}': {err}");
+    }
+    if let Err(err) = start_snapshots(session_id) {
+        eprintln!("amux: warning: snapshots disabled for '{session_id}': {err}");
+    }
+}
+
+/// Start the background watcher that saves pane snapshots, when they are
+/// configured.
+fn start_snapshots(session_id: &str) -> Result<()> {
+    if config::load()?.snapshots.is_none() {
+        return Ok(());
+    }
+    let exe = std::env::current_exe()
+        .map_err(|err| with_context(err, "failed to locate the amux executable"))?;
+    tmux::run_in_background(&[
+        &exe.to_string_lossy(),
+        "snapshots",
+        "watch",
+        "--session",
+        session_id,
+    ])
+}
+
+/// Start the background watcher that posts `needs_input` notifications, when
+/// they are configured.
+fn start_input_watch(session_id: &str) -> Result<()> {
+    if !config::load()?
+        .notify
+        .wants(notify::Event::NeedsInput.as_str())
+    {
+        return Ok(());
+    }
+    let exe = std::env::current_exe()
+        .map_err(|err| with_context(err, "failed to locate the amux executable"))?;
+    tmux::run_in_background(&[
+        &exe.to_string_lossy(),
+        "notify",
+        "watch",
+        "--session",
+        session_id,
+    ])
+}
+
+fn handle_rm(agent: &str, session_name: Option<&str>, include_pinned: bool) -> Result<()> {
+    ensure_valid_identifier("agent", agent)?;
+    if let Some(name) = session_name {
+        ensure_valid_session_name(name)?;
+    }
+
+    let session_id = tmux::session_name(agent, session_name);
+
+    let mux = mux();
+    if !mux.has_session(&session_id)? {
+        println!("{}", session_text("no_session", agent, &session_id));
+        return Ok(());
+    }
+
+    if !mux.holds_options() {
+        mux.kill_session(&session_id)?;
+        println!("{}", session_text("removed", agent, &session_id));
+        return Ok(());
+    }
+
+    if !include_pinned && tmux::is_pinned(&session_id)? {
+        return Err(fail_with(
+            ErrorKind::SessionPinned,
+            session_text("pinned_refused", agent, &session_id),
+            Some("pass --include-pinned, or run `amux unpin` first".into()),
+        ));
+    }
+
+    remove_session(agent, &session_id)
+}
+
+fn handle_stop(
+    agent: &str,
+    session_name: Option<&str>,
+    grace: Option<u64>,
+    include_pinned: bool,
+) -> Result<()> {
+    ensure_valid_identifier("agent", agent)?;
+    if let Some(name) = session_name {
+        ensure_valid_session_name(name)?;
+    }
+
+    let session_id = tmux::session_name(agent, session_name);
+    if !tmux::has_session(&session_id)? {
+        return Err(no_session(agent, session_name));
+    }
+    if !include_pinned && tmux::is_pinned(&session_id)? {
+        return Err(fail_with(
+            ErrorKind::SessionPinned,
+            session_text("pinned_refused", agent, &session_id),
+            Some("pass --include-pinned, or run `amux unpin` first".into()),
+        ));
+    }
+
+    let mut settings = config::load()?.stop(agent);
+    if let Some(seconds) = grace {
+        settings.grace_secs = seconds;
+    }
+    ask_to_exit(agent, &session_id, &settings)?;
+    if !tmux::has_session(&session_id)? {
+        return Ok(());
+    }
+    end_session(agent, &session_id, EndReason::Stopped)
+}
+
+/// Ask the agent in `session_id` to exit the way `settings` say and wait out
+/// the grace period; the session is left for the caller to end.
+fn ask_to_exit(agent: &str, session_id: &str, settings: &Stop) -> Result<()> {
+    let seconds = settings.grace_secs.to_string();
+    // Report the run from the caller rather than from the pane-died hook,
+    // which would call it an exit and let keep-alive restart the agent.
+    tmux::set_hook(session_id, "pane-died", None)?;
+    let agent_pane = tmux::agent_pane(session_id)?;
+    if tmux::pane_info(&agent_pane).is_ok_and(|info| info.pane_dead) {
+        return Ok(());
+    }
+    let args = [
+        ("agent", agent),
+        ("session", session_id),
+        ("seconds", seconds.as_str()),
+    ];
+    println!("{}", messages::text("stopping", &args));
+    stop::request(session_id, settings)?;
+    if !stop::wait(session_id, Duration::from_secs(settings.grace_secs))? {
+        eprintln!("{}", messages::text("stop_timed_out", &args));
+    }
+    Ok(())
+}
+
+/// How a bulk removal asks and what it spares.
+struct Bulk {
+    /// Do not ask for confirmation.
+    yes: bool,
+    include_pinned: bool,
+    /// Print the plan and change nothing.
+    plan_only: bool,
+}
+
+/// Every session amux manages. Sessions are checked for amux's own markers,
+/// so tmux sessions that merely look like `amux-*` are left alone.
+fn amux_sessions() -> Result<Vec<SessionDetail>> {
+    Ok(tmux::list_sessions()?
+        .into_iter()
+        .filter(|session| tmux::is_amux_session(&session.session_name).unwrap_or(false))
+        .collect())
+}
+
+/// Remove every session amux manages after listing them and asking (unless
+/// `--yes`), leaving other sessions and the tmux server itself alone.
+fn handle_kill_server(bulk: &Bulk) -> Result<()> {
+    remove_sessions(amux_sessions()?, "kill-server", bulk)
+}
+
+/// `rm --all`: like `kill-server`, optionally for one agent only.
+fn handle_rm_all(agent_filter: Option<&str>, bulk: &Bulk) -> Result<()> {
+    if let Some(agent) = agent_filter {
+        ensure_valid_identifier("agent", agent)?;
+    }
+    let mut sessions = amux_sessions()?;
+    sessions.retain(|session| agent_filter.is_none_or(|agent| session.agent == agent));
+    remove_sessions(sessions, "rm --all", bulk)
+}
+
+/// Remove the sessions whose agent exited and whose pane was kept, such as
+/// crash-looping ones keep-alive gave up on.
+fn handle_prune(bulk: &Bulk) -> Result<()> {
+    let sessions = amux_sessions()?;
+    let dead = parallel::map_bounded(&sessions, 4, |session| {
+        tmux::session_info(&session.session_name).is_ok_and(|info| info.pane_dead)
+    });
+    let exited: Vec<SessionDetail> = sessions
+        .into_iter()
+        .zip(dead)
+        .filter_map(|(session, dead)| dead.then_some(session))
+        .collect();
+    if exited.is_empty() {
+        println!("{}", messages::text("no_exited_sessions", &[]));
+        return Ok(());
+    }
+    remove_sessions(exited, "prune", bulk)
+}
+
+/// List `sessions`, ask (unless `--yes`), and remove them with run reports.
+/// Pinned ones are kept unless `--include-pinned`.
+fn remove_sessions(sessions: Vec<SessionDetail>, command: &str, bulk: &Bulk) -> Result<()> {
+    let (sessions, pinned): (Vec<SessionDetail>, Vec<SessionDetail>) = sessions
+        .into_iter()
+        .partition(|session| bulk.include_pinned || !session.pinned);
+    if !pinned.is_empty() {
+        let count = pinned.len().to_string();
+        println!("{}", messages::text("pinned_kept", &[("count", &count)]));
+    }
+    if sessions.is_empty() {
+        println!("{}", messages::text("no_sessions", &[]));
+        return Ok(());
+    }
+
+    let mut plan = Plan::default();
+    for session in &sessions {
+        plan.push(Action::Remove, &session.session_name, None);
+    }
+    for session in &pinned {
+        plan.push(Action::Keep, &session.session_name, Some("pinned"));
+    }
+    print!("{}", plan.render());
+    if bulk.plan_only {
+        return Ok(());
+    }
+    if !plan::approve(&plan, command, bulk.yes)? {
+        println!("{}", messages::text("nothing_removed", &[]));
+        return Ok(());
+    }
+
+    let mut failed = 0;
+    for session in &sessions {
+        if let Err(err) = remove_session(&session.agent, &session.session_name) {
+            eprintln!("amux: {}: {err}", session.session_name);
+            failed += 1;
+        }
+    }
+    let count = (sessions.len() - failed).to_string();
+    println!("{}", messages::text("removed_count", &[("count", &count)]));
+    if failed > 0 {
+        return bail(format!("{failed} session(s) could not be removed"));
+    }
+    Ok(())
+}
+
+fn handle_apply(file: &Path, prune: bool, plan_only: bool, auto_approve: bool) -> Result<()> {
+    let declared = apply::load(file)?;
+    for entry in &declared {
+        ensure_valid_identifier("agent", &entry.agent)?;
+        if let Some(name) = &entry.name {
+            ensure_valid_session_name(name)?;
+        }
+    }
+
+    let sessions = tmux::list_sessions()?;
+    let mut running = Vec::new();
+    for session in &sessions {
+        running.push(apply::Running {
+            session: session.session_name.clone(),
+            amux: true,
+            pinned: session.pinned,
+            spec: tmux::spec(&session.session_name)?,
+        });
+    }
+    for entry in &declared {
+        let session_id = entry.session();
+        let listed = running.iter().any(|current| current.session == session_id);
+        if !listed && tmux::has_session(&session_id)? {
+            running.push(apply::Running {
+                session: session_id,
+                amux: tmux::is_amux_session(&entry.session())?,
+                pinned: false,
+                spec: None,
+            });
+        }
+    }
+
+    let plan = apply::plan(&declared, &running, prune);
+    print!("{}", plan.render());
+    if plan_only {
+        return Ok(());
+    }
+    if !plan::approve(&plan, &format!("apply {}", file.display()), auto_approve)? {
+        println!("Nothing changed.");
+        return Ok(());
+    }
+
+    let exe = std::env::current_exe()
+        .map_err(|err| with_context(err, "failed to locate the amux executable"))?;
+    let mut failures = 0;
+    for step in &plan.steps {
+        let declaration = declared
+            .iter()
+            .find(|entry| entry.session() == step.session);
+        let result = match (step.action, declaration) {
+            (Action::Create, Some(entry)) => apply_start(&exe, entry),
+            (Action::Replace, Some(entry)) => {
+                remove_session(&entry.agent, &step.session).and_then(|()| apply_start(&exe, entry))
+            }
+            (Action::Remove, _) => match sessions
+                .iter()
+                .find(|session| session.session_name == step.session)
+            {
+                Some(session) => remove_session(&session.agent, &session.session_name),
+                None => Ok(()),
+            },
+            (Action::Conflict, _) => bail(format!(
+                "session '{}' exists but was not started by amux",
+                step.session
+            )),
+            _ => Ok(()),
+        };
+        if let Err(err) = result {
+            eprintln!("amux: {}: {err}", step.session);
+            failures += 1;
+        }
+    }
+    if failures > 0 {
+        return bail(format!(
+            "apply {}: {failures} session(s) could not be converged",
+            file.display()
+        ));
+    }
+    Ok(())
+}
+
+fn handle_freeze() -> Result<()> {
+    let mut declared = Vec::new();
+    for session in tmux::list_sessions()? {
+        match declare_running(&session)? {
+            Some(entry) => declared.push(entry),
+            None => eprintln!(
+                "amux: warning: skipped '{}': its directory is unknown",
+                session.session_name
+            ),
+        }
+    }
+    if declared.is_empty() {
+        eprintln!("{}", messages::text("no_sessions", &[]));
+        return Ok(());
+    }
+    print!("{}", apply::render(&declared));
+    Ok(())
+}
+
+/// How to start `session` again: the declaration recorded on it, else its
+/// directory and command. `None` when its directory is unknown.
+fn declare_running(session: &SessionDetail) -> Result<Option<apply::Declared>> {
+    let id = &session.session_name;
+    let recorded = tmux::spec(id)?.and_then(|spec| {
+        apply::Declared::from_spec(&session.agent, session.name.as_deref(), &spec)
+    });
+    if recorded.is_some() {
+        return Ok(recorded);
+    }
+    let info = tmux::session_info(id)?;
+    Ok(info.start_dir.or(info.cwd).map(|cwd| apply::Declared {
+        agent: session.agent.clone(),
+        name: session.name.clone(),
+        cmd: info.start_command,
+        params: None,
+        cwd: PathBuf::from(cwd),
+    }))
+}
+
+/// Run `f` against the user's own tmux server rather than amux's.
+fn on_shared_server<T>(f: impl FnOnce() -> T) -> T {
+    tmux::use_shared_server(true);
+    let result = f();
+    tmux::use_shared_server(false);
+    result
+}
+
+/// An amux session on the user's own tmux server, to be moved.
+struct Stranded {
+    entry: apply::Declared,
+    pinned: bool,
+    note: Option<String>,
+}
+
+/// `migrate-socket`: move the amux sessions left on the user's own tmux
+/// server to amux's. A process cannot change servers, so each one is removed
+/// there and started again in its directory with its command, keeping its
+/// pin and note. Attached sessions are left for the user to detach first.
+fn handle_migrate_socket(yes: bool, plan_only: bool) -> Result<()> {
+    if tmux::socket().is_none() {
+        return Err(fail_with(
+            ErrorKind::InvalidInput,
+            "sessions already run on your default tmux server".to_string(),
+            Some("set tmux_socket to a socket name, or unset it, to use a dedicated server".into()),
+        ));
+    }
+    let sessions = on_shared_server(amux_sessions)?;
+    if sessions.is_empty() {
+        println!("{}", messages::text("no_stranded_sessions", &[]));
+        return Ok(());
+    }
+
+    let mut plan = Plan::default();
+    let mut moving = Vec::new();
+    for session in sessions {
+        let id = session.session_name.clone();
+        if session.client_count > 0 {
+            plan.push(Action::Keep, &id, Some("attached; detach it first"));
+            continue;
+        }
+        if tmux::has_session(&id)? {
+            plan.push(
+                Action::Conflict,
+                &id,
+                Some("already running on the amux server"),
+            );
+            continue;
+        }
+        let declared = on_shared_server(|| declare_running(&session))?;
+        let Some(entry) = declared else {
+            plan.push(Action::Conflict, &id, Some("its directory is unknown"));
+            continue;
+        };
+        plan.push(Action::Replace, &id, None);
+        let note = on_shared_server(|| tmux::note(&id))?;
+        moving.push(Stranded {
+            entry,
+            pinned: session.pinned,
+            note,
+        });
+    }
+    print!("{}", plan.render());
+    if plan_only || moving.is_empty() {
+        return Ok(());
+    }
+    if !plan::approve(&plan, "migrate-socket", yes)? {
+        println!("{}", messages::text("nothing_moved", &[]));
+        return Ok(());
+    }
+
+    let exe = std::env::current_exe()
+        .map_err(|err| with_context(err, "failed to locate the amux executable"))?;
+    let mut failed = 0;
+    for stranded in &moving {
+        let entry = &stranded.entry;
+        let id = entry.session();
+        let result = on_shared_server(|| retire_session(&entry.agent, &id))
+            .and_then(|()| {
+                heartbeat::refresh(EndReason::Removed.as_str(), &id);
+                apply_start(&exe, entry)
+            })
+            .and_then(|()| tmux::set_pinned(&id, stranded.pinned))
+            .and_then(|()| tmux::set_note(&id, stranded.note.as_deref()));
+        if let Err(err) = result {
+            eprintln!("amux: {id}: {err}");
+            failed += 1;
+        }
+    }
+    let count = (moving.len() - failed).to_string();
+    let server = tmux::command_prefix();
+    let args = [("count", count.as_str()), ("server", server.as_str())];
+    println!("{}", messages::text("moved_count", &args));
+    if failed > 0 {
+        return bail(format!("{failed} session(s) could not be moved"));
+    }
+    Ok(())
+}
+
+/// Start `entry` in its directory through `amux start`, then record the
+/// declaration on the session.
+fn apply_start(exe: &Path, entry: &apply::Declared) -> Result<()> {
+    let mut command = std::process::Command::new(exe);
+    command
+        .arg("start")
+        .arg(format!("--agent={}", entry.agent))
+        .current_dir(&entry.cwd);
+    if let Some(name) = &entry.name {
+        command.arg(format!("--name={name}"));
+    }
+    if let Some(cmd) = &entry.cmd {
+        command.arg(format!("--cmd={cmd}"));
+    }
+    if let Some(params) = &entry.params {
+        command.arg(format!("--params={params}"));
+    }
+    let status = command
+        .status()
+        .map_err(|err| with_context(err, "failed to run amux start"))?;
+    if !status.success() {
+        return bail(format!("amux start exited with {status}"));
+    }
+    tmux::set_spec(&entry.session(), &entry.spec())
+}
+
+/// Kill a session, then print and record its run report. An agent with a
+/// `[stop]` table is asked to exit first.
+fn remove_session(agent: &str, session_id: &str) -> Result<()> {
+    retire_session(agent, session_id)?;
+    heartbeat::refresh(EndReason::Removed.as_str(), session_id);
+    Ok(())
+}
+
+/// [`remove_session`] without the heartbeat refresh, for `migrate-socket`,
+/// which removes sessions from the user's own server and refreshes the
+/// heartbeat once it is back on amux's.
+fn retire_session(agent: &str, session_id: &str) -> Result<()> {
+    if let Some(settings) = config::load()?.configured_stop(agent) {
+        ask_to_exit(agent, session_id, &settings)?;
+    }
+    close_session(agent, session_id, EndReason::Removed)
+}
+
+fn end_session(agent: &str, session_id: &str, reason: EndReason) -> Result<()> {
+    close_session(agent, session_id, reason)?;
+    heartbeat::refresh(reason.as_str(), session_id);
+    Ok(())
+}
+
+/// Kill a session, then print, archive, and record its run report.
+fn close_session(agent: &str, session_id: &str, reason: EndReason) -> Result<()> {
+    let report = report::collect(agent, session_id, reason);
+    let name = tmux::identify(session_id)
+        .ok()
+        .flatten()
+        .and_then(|(_, name)| name);
+    let note = tmux::note(session_id).ok().flatten();
+    tmux::kill_session(session_id)?;
+    activity::forget(session_id);
+
+    println!("{}", session_text(reason.as_str(), agent, session_id));
+    match report {
+        Ok(mut report) => {
+            if let Some(log) = report.log_path.clone() {
+                match archive::store(Path::new(&log), &report, name.as_deref(), note.as_deref()) {
+                    Ok(entry) => report = entry.report,
+                    Err(err) => eprintln!("amux: warning: log not archived: {err}"),
+                }
+            }
+            println!("{report}");
+            if let Err(err) = report::record(&report) {
+                eprintln!("amux: warning: run report not saved: {err}");
+            }
+        }
+        Err(err) => eprintln!("amux: warning: no run report for '{session_id}': {err}"),
+    }
+    Ok(())
+}
+
+/// Called from the `pane-died` hook: record how the run ended, then clean up the
+/// session the way it would have disappeared without `remain-on-exit`.
+fn record_exit(agent: &str, session_id: &str) -> Result<()> {
+    let report = report::collect(agent, session_id, EndReason::Exited)?;
+    report::record(&report)?;
+    let config = config::load()?;
+    let name = tmux::identify(session_id)
+        .ok()
+        .flatten()
+        .and_then(|(_, name)| name);
+    let output = if config.notify.has_sink() {
+        tmux::agent_pane(session_id)
+            .and_then(|pane| tmux::capture_pane(&pane, Some(200)))
+            .unwrap_or_default()
+    } else {
+        String::new()
+    };
+    let keep_alive = config
+        .agent(agent)
+        .and_then(|agent| agent.keep_alive.clone());
+    // Read before the session goes away with its options.
+    let muted = dnd::active(session_id).is_some();
+    let result = match &keep_alive {
+        Some(settings) => keep_alive::restart(settings, &report),
+        None => tmux::kill_session(session_id).map(|()| activity::forget(session_id)),
+    };
+    heartbeat::refresh("exited", session_id);
+
+    // A restarted agent is still running, so only an exit that stays one is news.
+    let event = match (&keep_alive, report.exit_status) {
+        (None, Some(0)) => Some(notify::Event::Finished),
+        (None, _) => Some(notify::Event::Crashed),
+        (Some(_), _) if tmux::is_crash_looping(session_id).unwrap_or(false) => {
+            Some(notify::Event::Crashed)
+        }
+        (Some(_), _) => None,
+    };
+    if let Some(event) = event.filter(|_| !muted) {
+        let source = notify::Source {
+            agent,
+            name: name.as_deref(),
+            session: session_id,
+            exit_status: report.exit_status,
+        };
+        notify::post(&config.notify, event, &source, &output);
+    }
+    result
+}
+
+fn handle_rm_interactive(agent_filter: Option<&str>, include_pinned: bool) -> Result<()> {
+    if let Some(agent) = agent_filter {
+        ensure_valid_identifier("agent", agent)?;
+    }
+    if !io::stdin().is_terminal() {
+        return bail("rm --interactive needs a terminal on stdin");
+    }
+
+    let (mut sessions, pinned): (Vec<SessionDetail>, Vec<SessionDetail>) = tmux::list_sessions()?
+        .into_iter()
+        .filter(|session| agent_filter.is_none_or(|agent| session.agent == agent))
+        .partition(|session| include_pinned || !session.pinned);
+    sessions.sort_by(|a, b| a.session_name.cmp(&b.session_name));
+    if !pinned.is_empty() {
+        let count = pinned.len().to_string();
+        println!("{}", messages::text("pinned_skipped", &[("count", &count)]));
+    }
+    if sessions.is_empty() {
+        println!("{}", messages::text("no_sessions", &[]));
+        return Ok(());
+    }
+
+    let now = unix_now();
+    let config = config::load()?;
+    let colored = theme::use_color();
+    let rows: Vec<String> = sessions
+        .iter()
+        .map(|session| {
+            let info = tmux::session_info(&session.session_name).ok();
+            let theme = config.theme(&session.agent);
+            review_row(session, info.as_ref(), &theme, colored, now)
+        })
+        .collect();
+    let header = format!(
+        "{:<28} {:<8} {:<8} {:<8} NOTE",
+        "SESSION", "STATE", "IDLE", "CLIENTS"
+    );
+
+    let picked = checklist::run(&header, &rows, &mut io::stdin().lock(), &mut io::stdout())?;
+    println!();
+    let Some(picked) = picked else {
+        println!("{}", messages::text("nothing_removed", &[]));
+        return Ok(());
+    };
+
+    for index in picked {
+        let session = &sessions[index];
+        remove_session(&session.agent, &session.session_name)?;
+    }
+    Ok(())
+}
+
+/// One checklist row: session, state, idle time, attached clients, and pane title.
+fn review_row(
+    session: &SessionDetail,
+    info: Option<&tmux::SessionInfo>,
+    theme: &Theme,
+    colored: bool,
+    now: u64,
+) -> String {
+    let state = match info {
+        Some(info) if info.pane_dead => "exited",
+        _ => "running",
+    };
+    let idle = info
+        .and_then(|info| info.last_activity)
+        .map(|activity| format_duration(now.saturating_sub(activity)))
+        .unwrap_or_else(|| "-".to_string());
+    let note = info
+        .and_then(|info| info.pane_title.as_deref())
+        .unwrap_or("-");
+    let row = format!(
+        "{} {:<8} {:<8} {:<8} {}",
+        theme.paint(&session.session_name, 28, colored),
+        state,
+        idle,
+        session.client_count,
+        note
+    );
+    row.trim_end().to_string()
+}
+
+fn handle_pin(agent: &str, session_name: Option<&str>, pinned: bool) -> Result<()> {
+    ensure_valid_identifier("agent", agent)?;
+    if let Some(name) = session_name {
+        ensure_valid_session_name(name)?;
+    }
+
+    let session_id = tmux::session_name(agent, session_name);
+    if !tmux::has_session(&session_id)? {
+        return Err(no_session(agent, session_name));
+    }
+
+    tmux::set_pinned(&session_id, pinned)?;
+    let id = if pinned { "pinned" } else { "unpinned" };
+    heartbeat::refresh(id, &session_id);
+    println!("{}", session_text(id, agent, &session_id));
+    Ok(())
+}
+
+fn handle_auto_detach(agent: &str, session_name: Option<&str>, minutes: u64) -> Result<()> {
+    ensure_valid_identifier("agent", agent)?;
+    if let Some(name) = session_name {
+        ensure_valid_session_name(name)?;
+    }
+
+    let session_id = tmux::session_name(agent, session_name);
+    if !tmux::has_session(&session_id)? {
+        return Err(no_session(agent, session_name));
+    }
+
+    if minutes == 0 {
+        auto_detach::disable(&session_id)?;
+        println!("{}", session_text("auto_detach_off", agent, &session_id));
+    } else {
+        auto_detach::enable(&session_id, minutes)?;
+        println!(
+            "{}",
+            messages::text(
+                "auto_detach_on",
+                &[
+                    ("agent", agent),
+                    ("session", &session_id),
+                    ("minutes", &minutes.to_string()),
+                ],
+            )
+        );
+    }
+    Ok(())
+}
+
+/// Print the session's note, or replace it when `change` is given (`None`
+/// clears it).
+fn handle_note(
+    agent: &str,
+    session_name: Option<&str>,
+    change: Option<Option<String>>,
+) -> Result<()> {
+    ensure_valid_identifier("agent", agent)?;
+    if let Some(name) = session_name {
+        ensure_valid_session_name(name)?;
+    }
+
+    let session_id = tmux::session_name(agent, session_name);
+    if !tmux::has_session(&session_id)? {
+        return Err(no_session(agent, session_name));
+    }
+
+    match change {
+        Some(note) => {
+            let note = note.filter(|note| !note.trim().is_empty());
+            tmux::set_note(&session_id, note.as_deref())?;
+            let id = if note.is_some() {
+                "note_set"
+            } else {
+                "note_cleared"
+            };
+            println!("{}", session_text(id, agent, &session_id));
+        }
+        None => match tmux::note(&session_id)? {
+            Some(note) => println!("{note}"),
+            None => println!("{}", session_text("no_note", agent, &session_id)),
+        },
+    }
+    Ok(())
+}
+
+/// Mute a session for `change`'s seconds (inner `None` until lifted), or lift
+/// the mute with `None`.
+fn handle_dnd(agent: &str, session_name: Option<&str>, change: Option<Option<u64>>) -> Result<()> {
+    ensure_valid_identifier("agent", agent)?;
+    if let Some(name) = session_name {
+        ensure_valid_session_name(name)?;
+    }
+
+    let session_id = tmux::session_name(agent, session_name);
+    if !tmux::has_session(&session_id)? {
+        return Err(no_session(agent, session_name));
+    }
+
+    let Some(seconds) = change else {
+        dnd::clear(&session_id)?;
+        println!("{}", session_text("dnd_off", agent, &session_id));
+        return Ok(());
+    };
+    let mute = dnd::set(&session_id, seconds)?;
+    let text = messages::text(
+        "dnd_on",
+        &[
+            ("agent", agent),
+            ("session", &session_id),
+            ("remaining", &mute.remaining(unix_now())),
+        ],
+    );
+    println!("{text}");
+    Ok(())
+}
+
+/// A tmux.conf line that binds KEY to a session tree showing amux metadata.
+fn choose_tree_binding(key: &str) -> String {
+    format!(
+        "bind-key {} choose-tree -Zs -F '{}'",
+        shell_words::quote(key),
+        tmux::CHOOSE_TREE_FORMAT
+    )
+}
+
+fn explain_command(raw: &str) -> Result<()> {
+    let tokens = agents::parse_tokens("command", raw)?;
+    println!("input:  {raw}");
+    println!("tokens: {}", tokens.len());
+    for (index, token) in tokens.iter().enumerate() {
+        println!("  [{index}] {token}");
+    }
+    println!(
+        "tmux:   tmux new-session -d -s SESSION -- {}",
+        shell_words::join(&tokens)
+    );
+
+    if tokens.iter().any(|token| token.contains('{')) {
+        println!("note:   {{placeholders}} are expanded per token when the session starts");
+    }
+    let warnings = explain::pitfalls(&tokens);
+    if warnings.is_empty() {
+        println!("No quoting pitfalls found.");
+    } else {
+        println!("warnings:");
+        for warning in warnings {
+            println!("  - {warning}");
+        }
+    }
+    Ok(())
+}
+
+fn handle_remote_attach(
+    host: &str,
+    agent: &str,
+    session_name: Option<&str>,
+    start: bool,
+    take_over: bool,
+) -> Result<()> {
+    ensure_valid_identifier("agent", agent)?;
+    if let Some(name) = session_name {
+        ensure_valid_session_name(name)?;
+    }
+    let config = config::load()?;
+    let Some(remote) = config.hosts.get(host) else {
+        return Err(fail_with(
+            ErrorKind::Config,
+            format!("unknown host '{host}'"),
+            Some(format!(
+                "add it under [hosts.{host}] with ssh = \"user@host\""
+            )),
+        ));
+    };
+    hosts::attach(host, remote, agent, session_name, start, take_over)
+}
+
+/// Attach to `agent`'s session, first starting it with `start` if given and
+/// the session does not exist.
+fn handle_attach(
+    agent: &str,
+    session_name: Option<&str>,
+    start: Option<CommandSpec>,
+    take_over: bool,
+) -> Result<()> {
+    ensure_valid_identifier("agent", agent)?;
+    if let Some(name) = session_name {
+        ensure_valid_session_name(name)?;
+    }
+
+    let session_id = tmux::session_name(agent, session_name);
+    nested::refuse_interactive(
+        "attach",
+        &format!(
+            "use `amux send` and `amux wait` from here, or `{} switch-client -t ={session_id}` \
+             from a terminal that is attached to amux's tmux server",
+            tmux::command_prefix()
+        ),
+    )?;
+
+    let mux = mux();
+    if !mux.has_session(&session_id)? {
+        if let Some(command) = start {
+            handle_start(
+                agent,
+                session_name,
+                command,
+                None,
+                Conflict::Reuse,
+                // attach goes on to the session it was given.
+                Naming {
+                    auto_name: None,
+                    from_branch: Some(false),
+                },
+                None,
+            )?;
+        } else {
+            println!(
+                "{}",
+                session_text("no_session_start_hint", agent, &session_id)
+            );
+            return Ok(());
+        }
+    }
+
+    let hooks = config::load()?.attach_hooks(agent);
+    let run_hook = |stage: &str, command: &Option<String>| {
+        if let Some(command) = command {
+            if let Err(err) = hooks::run(stage, command, agent, &session_id, session_name) {
+                eprintln!("amux: warning: {err}");
+            }
+        }
+    };
+    // A forgotten client, say from a dropped SSH login, keeps the session at
+    // its size and sees every keystroke, so say who is already there.
+    let others = if mux.holds_options() {
+        clients::list(&session_id)?
+    } else {
+        Vec::new()
+    };
+    if !others.is_empty() {
+        let count = others.len().to_string();
+        let id = if take_over {
+            "attach_taking_over"
+        } else {
+            "attach_shared"
+        };
+        let args = [
+            ("agent", agent),
+            ("session", &session_id),
+            ("count", &count),
+        ];
+        eprintln!("{}", messages::text(id, &args));
+        for line in clients::describe_all(&others) {
+            eprintln!("  {line}");
+        }
+        if !take_over {
+            eprintln!("{}", messages::text("attach_take_over_hint", &[]));
+        }
+    }
+
+    run_hook("pre_attach", &hooks.pre_attach);
+    let attached = mux.attach(&session_id, take_over);
+    // Restore the terminal even if tmux exited with an error.
+    run_hook("post_detach", &hooks.post_detach);
+    attached
+}
+
+fn handle_detach(agent: &str, session_name: Option<&str>) -> Result<()> {
+    ensure_valid_identifier("agent", agent)?;
+    if let Some(name) = session_name {
+        ensure_valid_session_name(name)?;
+    }
+
+    let session_id = tmux::session_name(agent, session_name);
+
+    if !tmux::has_session(&session_id)? {
+        println!("{}", session_text("no_session", agent, &session_id));
+        return Ok(());
+    }
+
+    let clients = tmux::client_count(&session_id)?;
+    if clients == 0 {
+        println!("{}", messages::text("no_clients", &[("agent", agent)]));
+        return Ok(());
+    }
+
+    tmux::detach_clients(&session_id)?;
+    println!("{}", session_text("detached", agent, &session_id));
+    Ok(())
+}
+
+fn handle_env(agent: &str, session_name: Option<&str>, show_values: bool) -> Result<()> {
+    ensure_valid_identifier("agent", agent)?;
+    if let Some(name) = session_name {
+        ensure_valid_session_name(name)?;
+    }
+
+    let session_id = tmux::session_name(agent, session_name);
+    let config = config::load()?;
+    let plan = session_env::plan(&config, agent, &session_id)?;
+    let entry = |name: &str, value: &str| {
+        if show_values {
+            format!("  {name}={value}")
+        } else {
+            format!("  {name}")
+        }
+    };
+
+    println!("{agent}: environment for session '{session_id}'");
+    for (title, source) in [
+        ("set by amux", Source::Amux),
+        ("set by config", Source::Config),
+        ("forwarded from this shell", Source::Forwarded),
+        ("fixed up by env_fixups", Source::Fixup),
+    ] {
+        let vars: Vec<_> = plan
+            .explicit
+            .iter()
+            .filter(|(_, _, from)| *from == source)
+            .collect();
+        if !vars.is_empty() {
+            println!("{title}:");
+            for (name, value, _) in vars {
+                println!("{}", entry(name, value));
+            }
+        }
+    }
+
+    println!("inherited from the tmux server ({}):", plan.inherited.len());
+    for (name, value) in &plan.inherited {
+        println!("{}", entry(name, value));
+    }
+
+    if !plan.missing.is_empty() {
+        println!("missing (listed in forward_env but unset here):");
+        for name in &plan.missing {
+            println!("  {name}");
+        }
+    }
+    if !plan.shell_only.is_empty() {
+        println!("only in this shell (not visible to the session; add to forward_env to pass):");
+        for name in &plan.shell_only {
+            println!("  {name}");
+        }
+    }
+    Ok(())
+}
+
+fn handle_doctor(agent: Option<&str>) -> Result<()> {
+    let config = config::load()?;
+    let agents = match agent {
+        Some(agent) => {
+            ensure_valid_identifier("agent", agent)?;
+            vec![agent.to_string()]
+        }
+        None => {
+            // Agents can be both built in and configured.
+            let mut known = known_agents(&config);
+            known.sort();
+            known.dedup();
+            known
+        }
+    };
+    let default_term = tmux::default_terminal()?;
+    let mut found = 0;
+    for agent in &agents {
+        let plan = session_env::plan(&config, agent, &tmux::session_name(agent, None))?;
+        // tmux sets TERM itself, so an inherited TERM never reaches the pane.
+        let term = config
+            .agent(agent)
+            .and_then(|agent| agent.term.as_deref())
+            .unwrap_or(&default_term);
+        let setup = Setup {
+            agent: agent.clone(),
+            term: Some(term.to_string()),
+            locale: doctor::locale(|name| plan.value(name).map(str::to_string)),
+        };
+        let problems = doctor::check(&setup, doctor::has_terminfo);
+        if problems.is_empty() {
+            let locale = setup
+                .locale
+                .map(|(name, value)| format!("{name}={value}"))
+                .unwrap_or_default();
+            println!(
+                "{agent}: ok (TERM={}, {locale})",
+                setup.term.unwrap_or_default()
+            );
+            continue;
+        }
+        println!("{agent}:");
+        for problem in &problems {
+            println!("  {}", problem.message);
+            println!("    fix: {}", problem.fix);
+        }
+        found += problems.len();
+    }
+    if found > 0 {
+        return bail(format!(
+            "doctor: {found} problem{} found",
+            if found == 1 { "" } else { "s" }
+        ));
+    }
+    Ok(())
+}
+
+fn handle_inspect_env(agent: &str, session_name: Option<&str>, show_values: bool) -> Result<()> {
+    ensure_valid_identifier("agent", agent)?;
+    if let Some(name) = session_name {
+        ensure_valid_session_name(name)?;
+    }
+
+    let session_id = tmux::session_name(agent, session_name);
+    if !tmux::has_session(&session_id)? {
+        return Err(no_session(agent, session_name));
+    }
+    let entry = |name: &str, value: &str| {
+        if show_values {
+            format!("  {name}={value}")
+        } else {
+            format!("  {name}")
+        }
+    };
+
+    println!("{agent}: live environment of session '{session_id}'");
+    let session_vars = tmux::session_environment(&session_id)?;
+    println!("tmux session environment ({}):", session_vars.len());
+    for (name, value) in &session_vars {
+        match value {
+            Some(value) => println!("{}", entry(name, value)),
+            None => println!("  -{name} (removed from the session)"),
+        }
+    }
+
+    let info = tmux::session_info(&session_id)?;
+    let Some(pid) = info.pid else {
+        println!("agent process: unknown (tmux reported no pane pid)");
+        return Ok(());
+    };
+    let program = info.pane_command.as_deref().unwrap_or("-");
+    let process_vars = match session_env::process_environment(pid) {
+        Ok(vars) => vars,
+        Err(err) => {
+            println!("process {pid} ({program}) environment: unavailable ({err})");
+            return Ok(());
+        }
+    };
+    println!(
+        "process {pid} ({program}) environment ({}):",
+        process_vars.len()
+    );
+    for (name, value) in &process_vars {
+        println!("{}", entry(name, value));
+    }
+
+    let shell_vars: BTreeMap<String, String> = std::env::vars().collect();
+    let drift = session_env::drift(&shell_vars, &process_vars);
+    if !drift.shell_only.is_empty() {
+        println!("in this shell but not in the agent process:");
+        for name in &drift.shell_only {
+            println!("  {name}");
+        }
+    }
+    if !drift.changed.is_empty() {
+        println!("different in this shell than in the agent process:");
+        for name in &drift.changed {
+            if show_values {
+                println!(
+                    "  {name}: shell={} agent={}",
+                    shell_vars[name], process_vars[name]
+                );
+            } else {
+                println!("  {name}");
+            }
+        }
+    }
+    Ok(())
+}
+
+fn handle_ide_list(as_json: bool) -> Result<()> {
+    let mut sessions = tmux::list_sessions()?;
+    sessions.sort_by(|a, b| a.session_name.cmp(&b.session_name));
+
+    if as_json {
+        let config = config::load()?;
+        let entries = sessions
+            .iter()
+            .map(|session| {
+                let info = tmux::session_info(&session.session_name).ok();
+                ide::session_entry(session, info.as_ref(), &config.theme(&session.agent))
+            })
+            .collect::<Vec<_>>();
+        println!("{}", Value::Array(entries));
+        return Ok(());
+    }
+
+    for session in &sessions {
+        println!(
+            "{}\t{}\t{}\t{}",
+            session.session_name,
+            session.agent,
+            session.name.as_deref().unwrap_or("-"),
+            session.client_count
+        );
+    }
+    Ok(())
+}
+
+fn handle_ide_open(agent: &str, session_name: Option<&str>, start: bool) -> Result<()> {
+    ensure_valid_identifier("agent", agent)?;
+    if let Some(name) = session_name {
+        ensure_valid_session_name(name)?;
+    }
+
+    let session_id = tmux::session_name(agent, session_name);
+
+    if !tmux::has_session(&session_id)? {
+        if !start {
+            return Err(fail_with(
+                ErrorKind::SessionNotFound,
+                session_text("no_session", agent, &session_id),
+                Some("pass --start to launch".into()),
+            ));
+        }
+        // Keep stdout reserved for the JSON spec.
+        start_quietly(agent, session_name)?;
     }
 
+    let program = std::env::current_exe()
+        .map_err(|err| with_context(err, "failed to locate the amux executable"))?;
+    let info = tmux::session_info(&session_id)?;
+    let spec = ide::launch_spec(
+        &program.to_string_lossy(),
+        agent,
+        session_name,
+        &session_id,
+        info.cwd.as_deref(),
+    );
+    println!("{spec}");
     Ok(())
 }
 
-fn handle_start(
+fn start_quietly(agent: &str, session_name: Option<&str>) -> Result<()> {
+    let session_id = tmux::session_name(agent, session_name);
+    let config = config::load()?;
+    let command_tokens = agent_command(&config, agent, None)?;
+    spawn_session(&config, agent, session_name, &session_id, &command_tokens)
+}
+
+fn handle_diff(agent: &str, session_name: Option<&str>, full: bool) -> Result<()> {
+    let (_, cwd) = session_repo(agent, session_name)?;
+    println!("{agent}: {}", cwd.display());
+    git::print_changes(&cwd, full)
+}
+
+fn handle_checkpoint(
     agent: &str,
     session_name: Option<&str>,
-    command_override: Option<&str>,
-    params: Option<&str>,
-    force: bool,
+    message: Option<&str>,
+    list: bool,
 ) -> Result<()> {
+    let (session_id, cwd) = session_repo(agent, session_name)?;
+    let repo = git::toplevel(&cwd)?.unwrap_or(cwd);
+
+    if list {
+        let checkpoints = checkpoint::list(&repo, &session_id)?;
+        if checkpoints.is_empty() {
+            println!("{agent}: no checkpoints for session '{session_id}'");
+        }
+        let now = unix_now();
+        for checkpoint in checkpoints {
+            println!(
+                "{:>4}  {}  {:>8} ago  {}",
+                checkpoint.id,
+                &checkpoint.commit[..checkpoint.commit.len().min(10)],
+                format_duration(now.saturating_sub(checkpoint.created)),
+                checkpoint.message
+            );
+        }
+        return Ok(());
+    }
+
+    let checkpoint = checkpoint::create(&repo, &session_id, message)?;
+    println!(
+        "{agent}: saved checkpoint {} of {} ({})",
+        checkpoint.id,
+        repo.display(),
+        checkpoint.message
+    );
+    Ok(())
+}
+
+fn handle_rollback(agent: &str, session_name: Option<&str>, id: u32) -> Result<()> {
+    let (session_id, cwd) = session_repo(agent, session_name)?;
+    let repo = git::toplevel(&cwd)?.unwrap_or(cwd);
+    let safety = checkpoint::restore(&repo, &session_id, id)?;
+    println!(
+        "{agent}: restored checkpoint {id} in {} (previous state saved as checkpoint {})",
+        repo.display(),
+        safety.id
+    );
+    Ok(())
+}
+
+/// Session name and working directory of a running session whose cwd is inside
+/// a git repository.
+fn session_repo(agent: &str, session_name: Option<&str>) -> Result<(String, PathBuf)> {
     ensure_valid_identifier("agent", agent)?;
     if let Some(name) = session_name {
-        ensure_valid_identifier("session name", name)?;
+        ensure_valid_session_name(name)?;
     }
 
     let session_id = tmux::session_name(agent, session_name);
-    let mut command_tokens = agents::resolve_agent_command(agent, command_override)?;
+    if !tmux::has_session(&session_id)? {
+        return Err(no_session(agent, session_name));
+    }
 
-    if let Some(extra) = params {
-        let mut extra_tokens = agents::parse_tokens("params", extra)?;
-        command_tokens.append(&mut extra_tokens);
+    let Some(cwd) = tmux::session_info(&session_id)?.cwd else {
+        return bail(format!(
+            "could not determine the working directory of session '{session_id}'"
+        ));
+    };
+    let cwd = PathBuf::from(cwd);
+    if git::toplevel(&cwd)?.is_none() {
+        return Err(fail_with(
+            ErrorKind::InvalidInput,
+            format!(
+                "session '{session_id}' is not inside a git repository ({})",
+                cwd.display()
+            ),
+            None,
+        ));
     }
+    Ok((session_id, cwd))
+}
 
-    if command_tokens.is_empty() {
-        return bail(format!("resolved command for '{agent}' is empty"));
+fn handle_open(agent: &str, session_name: Option<&str>, with: Option<&str>) -> Result<()> {
+    ensure_valid_identifier("agent", agent)?;
+    if let Some(name) = session_name {
+        ensure_valid_session_name(name)?;
+    }
+    let session_id = tmux::session_name(agent, session_name);
+    if !tmux::has_session(&session_id)? {
+        return Err(no_session(agent, session_name));
+    }
+
+    let Some(cwd) = tmux::session_info(&session_id)?.cwd else {
+        return bail(format!(
+            "could not determine the working directory of session '{session_id}'"
+        ));
+    };
+    let opener = open::opener(with, config::load()?.open_with.as_deref())?;
+    println!("{agent}: opening {cwd} with {}", opener.join(" "));
+    open::run(&opener, Path::new(&cwd))
+}
+
+fn handle_here(attach: bool, send: Option<String>, dir: Option<PathBuf>) -> Result<()> {
+    let dir = match dir {
+        Some(dir) => dir,
+        None => std::env::current_dir()
+            .map_err(|err| with_context(err, "failed to read the current directory"))?,
+    };
+    let dir = dir
+        .canonicalize()
+        .map_err(|err| with_context(err, format!("failed to resolve {}", dir.display())))?;
+
+    let owners = here::owners(&dir)?;
+    if owners.is_empty() {
+        return Err(fail_with(
+            ErrorKind::SessionNotFound,
+            format!("no amux session is working in {}", dir.display()),
+            Some("start one here with `amux start -a AGENT`".into()),
+        ));
+    }
+    for owner in &owners {
+        println!(
+            "{} (session '{}', clients: {}) in {}",
+            owner.agent,
+            owner.session,
+            owner.clients,
+            owner.cwd.display()
+        );
+    }
+
+    let Some(closest) = here::closest(&owners) else {
+        if attach || send.is_some() {
+            return Err(fail_with(
+                ErrorKind::InvalidInput,
+                "several sessions work in the same directory",
+                Some("pick one with `amux attach AGENT -n NAME` or `amux send`".into()),
+            ));
+        }
+        return Ok(());
+    };
+    let (agent, name) = (closest.agent.as_str(), closest.name.as_deref());
+    if let Some(text) = send {
+        let target = PaneChoice {
+            window: None,
+            pane: None,
+        };
+        return handle_send(agent, name, Some(text), None, target, true);
+    }
+    // A muted session is left alone unless asked for by --attach.
+    let attach = attach
+        || (io::stdin().is_terminal()
+            && io::stdout().is_terminal()
+            && dnd::active(&closest.session).is_none()
+            && checklist::confirm(
+                &format!("Attach to '{}'?", closest.session),
+                &mut io::stdin().lock(),
+                &mut io::stdout(),
+            )?);
+    if attach {
+        handle_attach(agent, name, None, false)?;
+    }
+    Ok(())
+}
+
+fn handle_logs(agent: &str, session_name: Option<&str>, view: logs::View) -> Result<()> {
+    ensure_valid_identifier("agent", agent)?;
+    if let Some(name) = session_name {
+        ensure_valid_session_name(name)?;
+    }
+    let session_id = tmux::session_name(agent, session_name);
+    let log = paths::session_log(&session_id)?;
+    if log.exists() {
+        return logs::show(&log, &session_id, view);
     }
 
+    // Removing a session moves its log into the archive.
+    let archived = archive::list()?
+        .into_iter()
+        .find(|entry| entry.report.session == session_id);
+    if let Some(entry) = archived {
+        return Err(fail_with(
+            ErrorKind::SessionNotFound,
+            format!("session '{session_id}' was removed and its log archived"),
+            Some(format!(
+                "read it with `amux archive show {} --log`",
+                entry.id
+            )),
+        ));
+    }
     if tmux::has_session(&session_id)? {
-        if force {
-            tmux::kill_session(&session_id)?;
+        return Err(fail_with(
+            ErrorKind::Other,
+            format!("session '{session_id}' has no log"),
+            Some("only output since amux started logging the session is kept".into()),
+        ));
+    }
+    Err(no_session(agent, session_name))
+}
+
+fn handle_capture(
+    agent: &str,
+    session_name: Option<&str>,
+    history: usize,
+    ansi: bool,
+    html: bool,
+) -> Result<()> {
+    ensure_valid_identifier("agent", agent)?;
+    if let Some(name) = session_name {
+        ensure_valid_session_name(name)?;
+    }
+    let session_id = tmux::session_name(agent, session_name);
+    let mux = mux();
+    if !mux.has_session(&session_id)? {
+        return Err(no_session(agent, session_name));
+    }
+
+    let text = if ansi || html {
+        tmux::capture_pane_with_escapes(&tmux::agent_pane(&session_id)?, Some(history))?
+    } else {
+        mux.capture(&session_id, Some(history))?
+    };
+    // The screen below the last output is blank lines.
+    let text = text.trim_end();
+    if html {
+        print!("{}", html::from_ansi(text, &session_id));
+    } else {
+        println!("{text}");
+    }
+    Ok(())
+}
+
+fn handle_show(agent: &str, session_name: Option<&str>, as_json: bool) -> Result<()> {
+    ensure_valid_identifier("agent", agent)?;
+    if let Some(name) = session_name {
+        ensure_valid_session_name(name)?;
+    }
+
+    let session_id = tmux::session_name(agent, session_name);
+
+    if !tmux::has_session(&session_id)? {
+        return Err(no_session(agent, session_name));
+    }
+
+    let info = tmux::session_info(&session_id)?;
+    let clients = tmux::client_count(&session_id)?;
+    let env = tmux::environment_names(&session_id)?;
+    let activity = activity::sample(&session_id);
+    let log_path = paths::session_log(&session_id)?;
+    let log_path = log_path
+        .exists()
+        .then(|| log_path.to_string_lossy().into_owned());
+
+    let now = unix_now();
+    let uptime = info.created.map(|created| now.saturating_sub(created));
+    let idle = info
+        .last_activity
+        .map(|activity| now.saturating_sub(activity));
+    let state = if info.pane_dead { "exited" } else { "running" };
+    let origin = tmux::origin(&session_id)?
+        .and_then(|raw| json::parse(&raw).ok())
+        .map(|value| Origin::from_json(&value));
+    let base = tmux::base(&session_id)?;
+    let drift = session_drift(base.as_deref(), info.cwd.as_deref());
+    let mute = dnd::active(&session_id);
+
+    if as_json {
+        let detail = json::object([
+            ("agent", Value::from(agent)),
+            ("name", Value::from(session_name)),
+            ("session", Value::from(session_id.as_str())),
+            ("state", Value::from(state)),
+            ("activity", Value::from(activity.to_string())),
+            ("command", Value::from(info.start_command.clone())),
+            ("current_command", Value::from(info.pane_command.clone())),
+            ("cwd", Value::from(info.cwd.clone())),
+            ("pid", Value::from(info.pid)),
+            ("clients", Value::from(clients)),
+            ("created_at", Value::from(info.created)),
+            ("uptime_seconds", Value::from(uptime)),
+            ("last_activity_at", Value::from(info.last_activity)),
+            ("idle_seconds", Value::from(idle)),
+            ("log_path", Value::from(log_path)),
+            ("env", Value::from(env)),
+            (
+                "origin",
+                origin.map_or(Value::Null, |origin| origin.to_json()),
+            ),
+            ("base_commit", Value::from(base)),
+            ("muted", Value::from(mute.is_some())),
+            (
+                "muted_until",
+                Value::from(match mute {
+                    Some(dnd::Mute::Until(until)) => Some(until),
+                    _ => None,
+                }),
+            ),
+            (
+                "drift",
+                drift.map_or(Value::Null, |drift| {
+                    json::object([
+                        ("commits", Value::from(drift.commits)),
+                        ("files", Value::from(drift.files)),
+                    ])
+                }),
+            ),
+        ]);
+        println!("{}", detail.to_pretty());
+        return Ok(());
+    }
+
+    let text = |value: Option<String>| value.unwrap_or_else(|| "-".to_string());
+    println!("{agent}: session '{session_id}'");
+    println!("  state:         {state} ({activity})");
+    println!("  command:       {}", text(info.start_command));
+    println!("  current:       {}", text(info.pane_command));
+    println!("  cwd:           {}", text(info.cwd));
+    println!(
+        "  pid:           {}",
+        text(info.pid.map(|pid| pid.to_string()))
+    );
+    println!("  clients:       {clients}");
+    println!("  uptime:        {}", text(uptime.map(format_duration)));
+    println!("  idle:          {}", text(idle.map(format_duration)));
+    println!("  log:           {}", text(log_path));
+    println!(
+        "  started by:    {}",
+        text(origin.as_ref().map(Origin::summary))
+    );
+    println!(
+        "  started in:    {}",
+        text(origin.and_then(|origin| origin.cwd))
+    );
+    println!(
+        "  drift:         {}",
+        text(drift.zip(base).map(|(drift, base)| {
+            format!("{} since {}", drift.summary(), &base[..base.len().min(7)])
+        }))
+    );
+    println!(
+        "  dnd:           {}",
+        text(mute.map(|mute| format!("muted {}", mute.remaining(now))))
+    );
+    println!(
+        "  env:           {}",
+        if env.is_empty() {
+            "-".to_string()
         } else {
-            println!("{agent}: session '{session_id}' already running (use --force to restart)");
+            env.join(", ")
+        }
+    );
+    Ok(())
+}
+
+/// `--window`/`--pane` as given; both unset means the agent's pane.
+struct PaneChoice<'a> {
+    window: Option<&'a str>,
+    pane: Option<&'a str>,
+}
+
+fn handle_send(
+    agent: &str,
+    session_name: Option<&str>,
+    text: Option<String>,
+    template_name: Option<&str>,
+    target: PaneChoice,
+    enter: bool,
+) -> Result<()> {
+    ensure_valid_identifier("agent", agent)?;
+    if let Some(name) = session_name {
+        ensure_valid_session_name(name)?;
+    }
+
+    let session_id = tmux::session_name(agent, session_name);
+
+    let mux = mux();
+    if !mux.has_session(&session_id)? {
+        return Err(no_session(agent, session_name));
+    }
+
+    let prompt = match template_name {
+        Some(template_name) => {
+            let config = config::load()?;
+            // Backends other than tmux do not report the directory.
+            let cwd = tmux::session_info(&session_id)
+                .ok()
+                .and_then(|info| info.cwd)
+                .map(PathBuf::from)
+                .map_or_else(std::env::current_dir, Ok)
+                .map_err(|err| with_context(err, "failed to read the current directory"))?;
+            let context = template::Context {
+                cwd,
+                agent: agent.to_string(),
+                name: session_name.map(str::to_owned),
+                session: session_id.clone(),
+            };
+            let mut stdin: Option<String> = None;
+            template::expand_with(config.template(template_name)?, |key| match key {
+                "stdin" => {
+                    if stdin.is_none() {
+                        stdin = Some(read_stdin()?);
+                    }
+                    Ok(stdin.clone())
+                }
+                "input" => Ok(Some(text.clone().unwrap_or_default())),
+                other => context.lookup(other),
+            })?
+        }
+        None => match text {
+            Some(text) => text,
+            None if !io::stdin().is_terminal() => read_stdin()?,
+            None => {
+                return bail("send: no text given; pass TEXT, pipe it on stdin, or use --template")
+            }
+        },
+    };
+
+    let prompt = prompt.trim_end_matches('\n');
+    if prompt.is_empty() {
+        return bail("send: refusing to send an empty prompt");
+    }
+
+    let settings = input::Settings::for_agent(&config::load()?, agent);
+    match (target.window, target.pane) {
+        (None, None) => mux.send_text(&session_id, prompt, &settings, enter)?,
+        (window, pane) => {
+            let pane = tmux::pane(&session_id, window, pane)?;
+            input::type_text(&pane, prompt, &settings, enter)?;
+        }
+    }
+
+    let bytes = prompt.len().to_string();
+    let text = messages::text(
+        "sent",
+        &[
+            ("agent", agent),
+            ("bytes", &bytes),
+            ("session", &session_id),
+        ],
+    );
+    println!("{text}");
+    Ok(())
+}
+
+fn read_stdin() -> Result<String> {
+    let mut input = String::new();
+    io::stdin()
+        .read_to_string(&mut input)
+        .map_err(|err| with_context(err, "failed to read stdin"))?;
+    Ok(input)
+}
+
+fn handle_cp(source: &Path, destination: &str, session_name: Option<&str>) -> Result<()> {
+    let Some((agent, path)) = destination.split_once(':') else {
+        return Err(fail_with(
+            ErrorKind::InvalidInput,
+            format!("cp: '{destination}' does not name an agent"),
+            Some("write the destination as AGENT:PATH, e.g. codex:docs/spec.md".into()),
+        ));
+    };
+    ensure_valid_identifier("agent", agent)?;
+    if let Some(name) = session_name {
+        ensure_valid_session_name(name)?;
+    }
+    let absolute = source
+        .canonicalize()
+        .map_err(|err| with_context(err, format!("cp: cannot read {}", source.display())))?;
+
+    let config = config::load()?;
+    let transfer = config.transfer(agent);
+    let cwd = match transfer {
+        Some(_) => PathBuf::new(),
+        None => {
+            let session_id = tmux::session_name(agent, session_name);
+            if !tmux::has_session(&session_id)? {
+                return Err(no_session(agent, session_name));
+            }
+            let Some(cwd) = tmux::session_info(&session_id)?.cwd else {
+                return bail(format!("cp: the directory of '{session_id}' is unknown"));
+            };
+            PathBuf::from(cwd)
+        }
+    };
+    let command = transfer::command(transfer, &cwd, &absolute, path);
+    transfer::run(&command)?;
+    let source = source.display().to_string();
+    let destination = command.last().cloned().unwrap_or_default();
+    let text = messages::text(
+        "copied",
+        &[
+            ("agent", agent),
+            ("destination", &destination),
+            ("source", &source),
+        ],
+    );
+    println!("{text}");
+    Ok(())
+}
+
+fn handle_broadcast(agents: &[String], interactive: bool, text: Option<String>) -> Result<()> {
+    let mut targets: Vec<(GroupMember, String)> = Vec::new();
+    for raw in agents {
+        let member = GroupMember::parse(raw);
+        ensure_valid_identifier("agent", &member.agent)?;
+        if let Some(name) = &member.name {
+            ensure_valid_session_name(name)?;
+        }
+        let session_id = tmux::session_name(&member.agent, member.name.as_deref());
+        if !tmux::has_session(&session_id)? {
+            return Err(no_session(&member.agent, member.name.as_deref()));
+        }
+        if !targets.iter().any(|(_, taken)| *taken == session_id) {
+            targets.push((member, session_id));
+        }
+    }
+
+    if !interactive {
+        let text =
+            match text {
+                Some(text) => text,
+                None if !io::stdin().is_terminal() => read_stdin()?,
+                None => return bail(
+                    "broadcast: no text given; pass TEXT, pipe it on stdin, or use --interactive",
+                ),
+            };
+        for (member, _) in &targets {
+            let target = PaneChoice {
+                window: None,
+                pane: None,
+            };
+            handle_send(
+                &member.agent,
+                member.name.as_deref(),
+                Some(text.clone()),
+                None,
+                target,
+                true,
+            )?;
+        }
+        return Ok(());
+    }
+
+    nested::refuse_interactive(
+        "open a broadcast view",
+        "leave out --interactive to send one prompt to every session",
+    )?;
+    // No `amux-` prefix, so the view is never mistaken for an agent session.
+    let view = format!("amux_broadcast_{}", std::process::id());
+    let sessions: Vec<String> = targets.into_iter().map(|(_, session)| session).collect();
+    tmux::new_broadcast_session(&view, &sessions)?;
+    let attached = tmux::attach_session(&view, false);
+    if tmux::has_session(&view)? {
+        tmux::kill_session(&view)?;
+    }
+    attached
+}
+
+fn handle_pipe(
+    agent: &str,
+    session_name: Option<&str>,
+    message: Option<&str>,
+    tail: usize,
+    command: &[String],
+) -> Result<()> {
+    ensure_valid_identifier("agent", agent)?;
+    if let Some(name) = session_name {
+        ensure_valid_session_name(name)?;
+    }
+
+    let session_id = tmux::session_name(agent, session_name);
+    if !tmux::has_session(&session_id)? {
+        return Err(no_session(agent, session_name));
+    }
+
+    let captured = pipe::run(command)?;
+    let prompt = pipe::prompt(
+        message,
+        command,
+        captured.status.code(),
+        &captured.output,
+        tail,
+    );
+    // The command may have run for a while; make sure the agent is still there.
+    if !tmux::has_session(&session_id)? {
+        return Err(no_session(agent, session_name));
+    }
+    let settings = input::Settings::for_agent(&config::load()?, agent);
+    input::send(&tmux::agent_pane(&session_id)?, &prompt, &settings)?;
+
+    let bytes = prompt.len().to_string();
+    let text = messages::text(
+        "sent",
+        &[
+            ("agent", agent),
+            ("bytes", &bytes),
+            ("session", &session_id),
+        ],
+    );
+    println!("{text}");
+    Ok(())
+}
+
+fn handle_relay(
+    from: &str,
+    to: &str,
+    filter: Option<&str>,
+    message: Option<String>,
+    once: bool,
+) -> Result<()> {
+    let config = config::load()?;
+    let mut sessions = Vec::new();
+    for raw in [from, to] {
+        let member = GroupMember::parse(raw);
+        ensure_valid_identifier("agent", &member.agent)?;
+        if let Some(name) = &member.name {
+            ensure_valid_session_name(name)?;
+        }
+        if !tmux::has_session(&tmux::session_name(&member.agent, member.name.as_deref()))? {
+            return Err(no_session(&member.agent, member.name.as_deref()));
+        }
+        sessions.push(member);
+    }
+    let (source, target) = (&sessions[0], &sessions[1]);
+    let relay = relay::Relay {
+        from: tmux::session_name(&source.agent, source.name.as_deref()),
+        to: tmux::session_name(&target.agent, target.name.as_deref()),
+        filter: filter.map(Pattern::new).transpose()?,
+        intro: message,
+        boundary: Boundary::for_agent(&config, &source.agent)?,
+        input: input::Settings::for_agent(&config, &target.agent),
+    };
+    if relay.from == relay.to {
+        return bail("relay: --from and --to name the same session");
+    }
+
+    eprintln!(
+        "amux: relaying '{}' to '{}' (Ctrl-C to stop)",
+        relay.from, relay.to
+    );
+    relay.run(once, |prompt| {
+        let bytes = prompt.len().to_string();
+        let text = messages::text(
+            "sent",
+            &[
+                ("agent", &target.agent),
+                ("bytes", &bytes),
+                ("session", &relay.to),
+            ],
+        );
+        println!("{text}");
+    })
+}
+
+fn handle_dispatch(
+    file: &Path,
+    agents: &[String],
+    parallel: usize,
+    blocks: bool,
+    timeout: Option<Duration>,
+) -> Result<()> {
+    let raw = fs::read_to_string(file)
+        .map_err(|err| with_context(err, format!("failed to read {}", file.display())))?;
+    let tasks = dispatch::parse_tasks(&raw, blocks);
+    if tasks.is_empty() {
+        return bail(format!("dispatch: no tasks in {}", file.display()));
+    }
+    if parallel == 0 {
+        return bail("dispatch: --parallel must be at least 1");
+    }
+
+    let config = config::load()?;
+    let mut workers = Vec::new();
+    for raw in agents {
+        let member = GroupMember::parse(raw);
+        let names: Vec<Option<String>> = match member.name {
+            Some(name) => vec![Some(name)],
+            None => (1..=parallel)
+                .map(|index| Some(format!("dispatch-{index}")))
+                .collect(),
+        };
+        for name in names {
+            let outcome = start_agent(
+                &config,
+                &member.agent,
+                name.as_deref(),
+                CommandSpec::Configured,
+                None,
+                Conflict::Reuse,
+            )?;
+            let session_id = tmux::session_name(&member.agent, name.as_deref());
+            if matches!(outcome, StartOutcome::Started) {
+                println!("{}", session_text("started", &member.agent, &session_id));
+                wait_until_ready(&config, &member.agent, &session_id, None)?;
+            }
+            workers.push(dispatch::Worker {
+                session: session_id,
+                boundary: Boundary::for_agent(&config, &member.agent)?,
+                input: input::Settings::for_agent(&config, &member.agent),
+            });
+        }
+    }
+    if workers.is_empty() {
+        return bail("dispatch: --agents names no sessions");
+    }
+
+    let progress = dispatch::Progress::new(&file.display().to_string(), tasks);
+    let total = progress.tasks.len();
+    let progress = dispatch::run(progress, &workers, timeout, |event| match event {
+        dispatch::Event::Assigned { index, session } => {
+            println!("task {}/{total} -> '{session}'", index + 1);
+        }
+        dispatch::Event::Finished { index, task } => {
+            let took = task
+                .started
+                .zip(task.finished)
+                .map(|(started, finished)| format_duration(finished.saturating_sub(started)))
+                .unwrap_or_default();
+            match &task.error {
+                None => println!("task {}/{total} done in {took}", index + 1),
+                Some(error) => println!("task {}/{total} failed: {error}", index + 1),
+            }
+        }
+    })?;
+    println!("{}: {}", progress.source, progress.summary());
+    if progress.count(dispatch::TaskState::Done) < total {
+        return bail("dispatch: not every task was completed; see `amux dispatch --status`");
+    }
+    Ok(())
+}
+
+fn print_dispatch_status() -> Result<()> {
+    let Some(progress) = dispatch::load()? else {
+        println!("No dispatch runs yet.");
+        return Ok(());
+    };
+    let now = unix_now();
+    println!(
+        "{}: {} (started {} ago)",
+        progress.source,
+        progress.summary(),
+        format_duration(now.saturating_sub(progress.started))
+    );
+    for (index, task) in progress.tasks.iter().enumerate() {
+        let took = match (task.started, task.finished) {
+            (Some(started), Some(finished)) => format_duration(finished.saturating_sub(started)),
+            (Some(started), None) => format_duration(now.saturating_sub(started)),
+            _ => "-".to_string(),
+        };
+        let summary = task.text.lines().next().unwrap_or_default();
+        println!(
+            "{:>4}  {:<8} {:<28} {:<8} {}",
+            index + 1,
+            task.state.as_str(),
+            task.session.as_deref().unwrap_or("-"),
+            took,
+            summary
+        );
+    }
+    Ok(())
+}
+
+fn handle_serve(port: Option<u16>) -> Result<()> {
+    let mut settings = config::load()?.webhook;
+    if let Some(port) = port {
+        settings.port = port;
+    }
+    let token = std::env::var("AMUX_WEBHOOK_TOKEN")
+        .ok()
+        .filter(|token| !token.is_empty())
+        .or_else(|| settings.token.clone());
+    if token.is_none() && settings.tokens.is_empty() {
+        return Err(fail_with(
+            ErrorKind::Config,
+            "amux serve needs a token",
+            Some("set token or [webhook.tokens] in the config, or AMUX_WEBHOOK_TOKEN".into()),
+        ));
+    }
+    if settings.allow.is_empty() {
+        return Err(fail_with(
+            ErrorKind::Config,
+            "no webhook operations are allowed",
+            Some("list them under [webhook], e.g. allow = [\"send\"]".into()),
+        ));
+    }
+    let exe = std::env::current_exe()
+        .map_err(|err| with_context(err, "failed to locate the amux executable"))?;
+    webhook::serve(&settings, token.as_deref(), &exe)
+}
+
+fn handle_notify(command: NotifyCommand) -> Result<()> {
+    let config = config::load()?;
+    match command {
+        NotifyCommand::Bridge => {
+            let exe = std::env::current_exe()
+                .map_err(|err| with_context(err, "failed to locate the amux executable"))?;
+            notify::bridge(&config.notify, &exe)
+        }
+        NotifyCommand::Watch { session } => {
+            let Some((agent, name)) = tmux::identify(&session)? else {
+                return Ok(());
+            };
+            let boundary = Boundary::for_agent(&config, &agent)?;
+            let source = notify::Source {
+                agent: &agent,
+                name: name.as_deref(),
+                session: &session,
+                exit_status: None,
+            };
+            notify::watch_for_input(&config.notify, &boundary, &source)
+        }
+    }
+}
+
+fn handle_archive(command: ArchiveCommand) -> Result<()> {
+    match command {
+        ArchiveCommand::List { agent } => {
+            let entries: Vec<_> = archive::list()?
+                .into_iter()
+                .filter(|entry| {
+                    agent
+                        .as_ref()
+                        .is_none_or(|agent| &entry.report.agent == agent)
+                })
+                .collect();
+            if entries.is_empty() {
+                println!("No archived runs.");
+                return Ok(());
+            }
+            let now = unix_now();
+            for entry in entries {
+                let mut label = entry.report.agent.clone();
+                if let Some(name) = &entry.name {
+                    label.push('/');
+                    label.push_str(name);
+                }
+                let mut line = format!(
+                    "{}  {label}  {}",
+                    entry.id,
+                    entry.report.recent_summary(now)
+                );
+                if let Some(note) = &entry.note {
+                    line.push_str(&format!("  - {note}"));
+                }
+                println!("{line}");
+            }
+        }
+        ArchiveCommand::Show { id, log } => {
+            let entry = archive::find(&id)?;
+            println!("{}", entry.id);
+            println!("  agent:         {}", entry.report.agent);
+            println!("  name:          {}", entry.name.as_deref().unwrap_or("-"));
+            println!("  note:          {}", entry.note.as_deref().unwrap_or("-"));
+            println!("  ended:         {}", format_timestamp(entry.report.ended));
+            println!("{}", entry.report);
+            if log {
+                println!();
+                archive::print_log(&entry)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn handle_snapshots(command: SnapshotsCommand) -> Result<()> {
+    let (agent, agent_pos, name, back) = match command {
+        SnapshotsCommand::Watch { session } => {
+            let Some(settings) = config::load()?.snapshots else {
+                return Ok(());
+            };
+            return snapshot::watch(&session, &settings);
+        }
+        SnapshotsCommand::List {
+            agent,
+            agent_pos,
+            name,
+        } => (agent, agent_pos, name, None),
+        SnapshotsCommand::Show {
+            agent,
+            agent_pos,
+            name,
+            back,
+        } => (agent, agent_pos, name, Some(back)),
+    };
+    let agent = resolve_agent_input(agent, agent_pos, "snapshots")?;
+    ensure_valid_identifier("agent", &agent)?;
+    if let Some(name) = &name {
+        ensure_valid_session_name(name)?;
+    }
+    let session_id = tmux::session_name(&agent, name.as_deref());
+    let snapshots = snapshot::list(&session_id)?;
+    if snapshots.is_empty() {
+        return Err(fail_with(
+            ErrorKind::InvalidInput,
+            format!("no snapshots of '{session_id}'"),
+            Some("snapshots are taken when [snapshots] is set in the config".into()),
+        ));
+    }
+
+    let Some(back) = back else {
+        let now = unix_now();
+        for (index, snapshot) in snapshots.iter().rev().enumerate() {
+            println!(
+                "{:>3}  {}  ({} ago)  {}",
+                index + 1,
+                format_timestamp(snapshot.taken),
+                format_duration(now.saturating_sub(snapshot.taken)),
+                snapshot.path.display()
+            );
+        }
+        return Ok(());
+    };
+    let Some(snapshot) = back
+        .checked_sub(1)
+        .and_then(|index| snapshots.iter().rev().nth(index))
+    else {
+        return bail(format!(
+            "--back {back}: '{session_id}' has {} snapshot(s)",
+            snapshots.len()
+        ));
+    };
+    let screen = fs::read_to_string(&snapshot.path)
+        .map_err(|err| with_context(err, format!("failed to read {}", snapshot.path.display())))?;
+    print!("{screen}");
+    Ok(())
+}
+
+fn handle_tmux_keys(command: TmuxKeysCommand) -> Result<()> {
+    let snippet = |picker: &str, next: &str, prev: &str| -> Result<String> {
+        let exe = std::env::current_exe()
+            .map_err(|err| with_context(err, "failed to locate the amux executable"))?;
+        let keys = tmux_keys::Keys { picker, next, prev };
+        Ok(tmux_keys::snippet(&exe.to_string_lossy(), &keys))
+    };
+    match command {
+        TmuxKeysCommand::Print {
+            picker_key,
+            next_key,
+            prev_key,
+        } => print!("{}", snippet(&picker_key, &next_key, &prev_key)?),
+        TmuxKeysCommand::Install {
+            picker_key,
+            next_key,
+            prev_key,
+            file,
+        } => {
+            let conf = match file {
+                Some(file) => file,
+                None => tmux::conf_file()?,
+            };
+            let replaced = tmux_keys::install(&conf, &snippet(&picker_key, &next_key, &prev_key)?)?;
+            let verb = if replaced { "updated" } else { "added" };
+            println!(
+                "{verb} amux key bindings in {} (prefix {picker_key}: session menu, \
+                 prefix {next_key}/{prev_key}: next/previous session)",
+                conf.display()
+            );
+            if tmux::source_file(&conf)? {
+                println!("loaded them into the running tmux server");
+            }
+        }
+        TmuxKeysCommand::Menu { client } => {
+            let sessions = ordered_sessions()?;
+            let current = tmux::client_session(&client)?;
+            if sessions.is_empty() {
+                return bail("no amux sessions are running");
+            }
+            let items = tmux_keys::menu_items(&sessions, &client, &current);
+            tmux::display_menu(&client, "amux", &items)?;
+        }
+    }
+    Ok(())
+}
+
+/// Switch `client` (by default the one showing this shell's pane) to the
+/// next or previous amux session.
+fn handle_cycle(client: Option<String>, forward: bool) -> Result<()> {
+    let client = match client {
+        Some(client) => client,
+        None => tmux::current_client()?.ok_or_else(|| {
+            fail_with(
+                ErrorKind::InvalidInput,
+                "no tmux client to switch; run this inside tmux or pass --client",
+                None,
+            )
+        })?,
+    };
+    let sessions = ordered_sessions()?;
+    let current = tmux::client_session(&client)?;
+    match tmux_keys::step(&sessions, &current, forward) {
+        Some(target) if target.session_name != current => {
+            tmux::switch_client(&client, &target.session_name)
+        }
+        Some(_) => Ok(()),
+        None => bail("no amux sessions are running"),
+    }
+}
+
+/// Running amux sessions in the order the session menu and cycling use.
+fn ordered_sessions() -> Result<Vec<SessionDetail>> {
+    let mut sessions = tmux::list_sessions()?;
+    tmux_keys::order(&mut sessions);
+    Ok(sessions)
+}
+
+fn handle_profile_bench(runs: u32) -> Result<()> {
+    let exe = std::env::current_exe()
+        .map_err(|err| with_context(err, "failed to locate the amux executable"))?;
+    print!("{}", profile::render(&profile::bench(&exe, runs)?));
+    Ok(())
+}
+
+fn handle_config(command: ConfigCommand) -> Result<()> {
+    let ConfigCommand::Upgrade { print } = command;
+    let path = paths::config_file()?;
+    let raw = match fs::read_to_string(&path) {
+        Ok(raw) => raw,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {
+            println!("No config file at {}; nothing to upgrade.", path.display());
             return Ok(());
         }
+        Err(err) => {
+            return Err(with_context(
+                err,
+                format!("failed to read config {}", path.display()),
+            ))
+        }
+    };
+    let upgraded = config::upgrade(&raw)
+        .map_err(|err| with_context(err, format!("cannot upgrade {}", path.display())))?;
+    let Some(upgraded) = upgraded else {
+        if print {
+            print!("{raw}");
+        } else {
+            println!(
+                "{} is already at config version {}.",
+                path.display(),
+                config::CONFIG_VERSION
+            );
+        }
+        return Ok(());
+    };
+    if print {
+        print!("{upgraded}");
+        return Ok(());
     }
 
-    tmux::new_session(&session_id, &command_tokens)
-        .map_err(|err| with_context(err, format!("failed to start agent '{agent}'")))?;
-
-    println!("{agent}: started in session '{session_id}'");
+    let backup = config::save(&path, &upgraded)?.unwrap_or_default();
+    println!(
+        "Upgraded {} to config version {} (the previous file is {}).",
+        path.display(),
+        config::CONFIG_VERSION,
+        backup.display()
+    );
     Ok(())
 }
 
-fn handle_rm(agent: &str, session_name: Option<&str>) -> Result<()> {
-    ensure_valid_identifier("agent", agent)?;
-    if let Some(name) = session_name {
-        ensure_valid_identifier("session name", name)?;
-    }
+fn handle_tasks(command: TasksCommand) -> Result<()> {
+    let cwd = std::env::current_dir()
+        .map_err(|err| with_context(err, "failed to read the current directory"))?;
+    let workspace = tasks::workspace(&cwd);
+    let _lock = match command {
+        TasksCommand::List { .. } => None,
+        _ => Some(tasks::lock(&workspace)?),
+    };
+    let mut board = tasks::load(&workspace)?;
 
-    let session_id = tmux::session_name(agent, session_name);
+    let (id, send) = match command {
+        TasksCommand::List { all } => {
+            print_board(&board, all);
+            return Ok(());
+        }
+        TasksCommand::Add { text, assign, send } => {
+            let id = board.add(&text).id;
+            println!("added task {id}");
+            match assign {
+                Some(to) => {
+                    board.assign(id, &task_session(&to)?)?;
+                    (id, send)
+                }
+                None => return tasks::save(&board),
+            }
+        }
+        TasksCommand::Assign { id, to, send } => {
+            board.assign(id, &task_session(&to)?)?;
+            (id, send)
+        }
+        TasksCommand::Done { id } => {
+            board.set_done(id, true)?;
+            (id, false)
+        }
+        TasksCommand::Reopen { id } => {
+            board.set_done(id, false)?;
+            (id, false)
+        }
+    };
+    tasks::save(&board)?;
 
-    if !tmux::has_session(&session_id)? {
-        println!("{agent}: no active session (looked for '{session_id}')");
+    let Some(task) = board.tasks.iter().find(|task| task.id == id) else {
         return Ok(());
+    };
+    let session = task.session.as_deref().unwrap_or("-");
+    println!("task {id}: {} ({session})", task.state.as_str());
+    if send {
+        let settings = match tmux::identify(session)? {
+            Some((agent, _)) => input::Settings::for_agent(&config::load()?, &agent),
+            None => input::Settings::default(),
+        };
+        input::send(&tmux::agent_pane(session)?, &task.text, &settings)?;
+        println!("task {id}: sent to '{session}'");
     }
+    Ok(())
+}
 
-    tmux::kill_session(&session_id)?;
+/// Session id for an `AGENT[:NAME]` assignment, which must be running.
+fn task_session(raw: &str) -> Result<String> {
+    let member = GroupMember::parse(raw);
+    ensure_valid_identifier("agent", &member.agent)?;
+    if let Some(name) = &member.name {
+        ensure_valid_session_name(name)?;
+    }
+    let session_id = tmux::session_name(&member.agent, member.name.as_deref());
+    if !tmux::has_session(&session_id)? {
+        return Err(no_session(&member.agent, member.name.as_deref()));
+    }
+    Ok(session_id)
+}
 
-    println!("{agent}: removed session '{session_id}'");
-    Ok(())
+fn print_board(board: &tasks::Board, all: bool) {
+    let shown: Vec<&tasks::Task> = board
+        .tasks
+        .iter()
+        .filter(|task| all || task.state != tasks::State::Done)
+        .collect();
+    if shown.is_empty() {
+        println!("No tasks for {}.", board.workspace.display());
+        return;
+    }
+    let now = unix_now();
+    for task in shown {
+        println!(
+            "{:>4}  {:<8} {:<28} {:<8} {}",
+            task.id,
+            task.state.as_str(),
+            task.session.as_deref().unwrap_or("-"),
+            format_duration(now.saturating_sub(task.updated)),
+            task.text.lines().next().unwrap_or_default()
+        );
+    }
 }
 
-fn handle_attach(agent: &str, session_name: Option<&str>, start: bool) -> Result<()> {
+fn handle_panes(agent: &str, session_name: Option<&str>) -> Result<()> {
     ensure_valid_identifier("agent", agent)?;
     if let Some(name) = session_name {
-        ensure_valid_identifier("session name", name)?;
+        ensure_valid_session_name(name)?;
     }
 
     let session_id = tmux::session_name(agent, session_name);
-
     if !tmux::has_session(&session_id)? {
-        if start {
-            handle_start(agent, session_name, None, None, false)?;
-        } else {
-            println!(
-                "{agent}: no active session (looked for '{session_id}'); pass --start to launch"
-            );
-            return Ok(());
-        }
+        return Err(no_session(agent, session_name));
     }
 
-    tmux::attach_session(&session_id)?;
+    for pane in tmux::list_panes(&session_id)? {
+        let marks: Vec<&str> = [(pane.agent, "agent"), (pane.active, "active")]
+            .into_iter()
+            .filter_map(|(set, mark)| set.then_some(mark))
+            .collect();
+        let position = format!("{}.{}", pane.window, pane.index);
+        let line = format!(
+            "{position:<6} {:<5} {:<12} {}",
+            pane.id,
+            pane.command,
+            marks.join(", ")
+        );
+        println!("{}", line.trim_end());
+    }
     Ok(())
 }
 
-fn handle_detach(agent: &str, session_name: Option<&str>) -> Result<()> {
+fn handle_wait(
+    agent: &str,
+    session_name: Option<&str>,
+    pattern: Option<&str>,
+    timeout: Option<Duration>,
+) -> Result<()> {
     ensure_valid_identifier("agent", agent)?;
     if let Some(name) = session_name {
-        ensure_valid_identifier("session name", name)?;
+        ensure_valid_session_name(name)?;
     }
 
     let session_id = tmux::session_name(agent, session_name);
 
     if !tmux::has_session(&session_id)? {
-        println!("{agent}: no active session (looked for '{session_id}')");
-        return Ok(());
+        return Err(no_session(agent, session_name));
     }
 
-    let clients = tmux::client_count(&session_id)?;
-    if clients == 0 {
-        println!("{agent}: no clients to detach");
+    let seconds = timeout.map(|t| t.as_secs()).unwrap_or_default();
+    if let Some(raw) = pattern {
+        let pattern = Pattern::new(raw)?;
+        if !response::wait_for_pattern(&session_id, &pattern, timeout)? {
+            return Err(fail_with(
+                ErrorKind::Timeout,
+                messages::text(
+                    "timed_out_pattern",
+                    &[
+                        ("agent", agent),
+                        ("seconds", &seconds.to_string()),
+                        ("pattern", raw),
+                        ("session", &session_id),
+                    ],
+                ),
+                None,
+            ));
+        }
+        let text = messages::text(
+            "output_matched",
+            &[("agent", agent), ("pattern", raw), ("session", &session_id)],
+        );
+        println!("{text}");
         return Ok(());
     }
 
-    tmux::detach_clients(&session_id)?;
-    println!("{agent}: detached clients from '{session_id}'");
+    let config = config::load()?;
+    let boundary = Boundary::for_agent(&config, agent)?;
+    if !response::wait_for_response(&session_id, &boundary, timeout)? {
+        return Err(fail_with(
+            ErrorKind::Timeout,
+            messages::text(
+                "timed_out_response",
+                &[
+                    ("agent", agent),
+                    ("seconds", &seconds.to_string()),
+                    ("session", &session_id),
+                ],
+            ),
+            None,
+        ));
+    }
+
+    println!("{}", session_text("response_complete", agent, &session_id));
     Ok(())
 }
 
-fn handle_status(agent_filter: Option<String>) -> Result<()> {
-    let sessions = tmux::list_sessions()?;
+/// `status --format json`: every session, sorted by session name.
+fn handle_status_json(agent_filter: Option<String>) -> Result<()> {
+    let mut sessions = mux().list_sessions()?;
+    sessions.retain(|session| {
+        agent_filter
+            .as_ref()
+            .is_none_or(|agent| session.agent == *agent)
+    });
+    sessions.sort_by(|a, b| a.session_name.cmp(&b.session_name));
+    let entries = sessions.iter().map(ide::status_entry).collect::<Vec<_>>();
+    println!("{}", Value::Array(entries));
+    Ok(())
+}
+
+fn handle_status_bar(agent_filter: Option<String>, format: &str) -> Result<()> {
+    let config = config::load()?;
+    let mut sessions = tmux::list_sessions()?;
+    sessions.retain(|session| {
+        agent_filter
+            .as_ref()
+            .is_none_or(|agent| session.agent == *agent)
+    });
+    sessions.sort_by(|a, b| a.session_name.cmp(&b.session_name));
+    let states = parallel::map_bounded(&sessions, 4, |session| bar_state(&config, session));
+    let entries: Vec<bar::Entry> = sessions
+        .iter()
+        .zip(states)
+        .map(|(session, state)| bar::Entry {
+            label: match &session.name {
+                Some(name) => format!("{}/{name}", session.agent),
+                None => session.agent.clone(),
+            },
+            state,
+        })
+        .collect();
+    if format == "waybar" {
+        println!("{}", bar::waybar(&entries));
+    } else {
+        print!("{}", bar::xbar(&entries));
+    }
+    Ok(())
+}
+
+/// Whether a session crashed, waits at its prompt, or is working.
+fn bar_state(config: &Config, session: &SessionDetail) -> bar::State {
+    let info = tmux::session_info(&session.session_name).ok();
+    let failed = info
+        .as_ref()
+        .is_some_and(|info| info.pane_dead && info.exit_status != Some(0));
+    if session.crash_looping || failed {
+        return bar::State::Crashed;
+    }
+    let activity = activity::sample(&session.session_name);
+    if matches!(
+        activity,
+        activity::Activity::Active | activity::Activity::Trickling
+    ) {
+        return bar::State::Active;
+    }
+    let waiting = Boundary::for_agent(config, &session.agent)
+        .ok()
+        .zip(tmux::agent_pane(&session.session_name).ok())
+        .and_then(|(boundary, pane)| {
+            let screen = tmux::capture_pane(&pane, None).ok()?;
+            Some(boundary.shows_prompt(&screen))
+        })
+        .unwrap_or(false);
+    if waiting {
+        bar::State::NeedsInput
+    } else {
+        bar::State::Idle
+    }
+}
+
+fn handle_status(
+    agent_filter: Option<String>,
+    all: bool,
+    show_git: bool,
+    show_usage: bool,
+) -> Result<()> {
+    let sessions = mux().list_sessions()?;
+    let shown: Vec<&SessionDetail> = sessions
+        .iter()
+        .filter(|session| {
+            agent_filter
+                .as_ref()
+                .is_none_or(|agent| session.agent == *agent)
+        })
+        .collect();
+    let mut notes: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    if show_git {
+        for (session, summary) in git_summaries(&shown) {
+            notes
+                .entry(session)
+                .or_default()
+                .push(format!("git: {summary}"));
+        }
+        for (session, drift) in drift_summaries(&shown) {
+            notes
+                .entry(session)
+                .or_default()
+                .push(format!("since start: {drift}"));
+        }
+    }
+    let now = unix_now();
+    let mutes = parallel::map_bounded(&shown, 4, |session| dnd::active(&session.session_name));
+    for (session, mute) in shown.iter().zip(mutes) {
+        if let Some(mute) = mute {
+            notes
+                .entry(session.session_name.clone())
+                .or_default()
+                .push(format!("dnd {}", mute.remaining(now)));
+        }
+    }
+    if show_usage {
+        for (session, summary) in usage_summaries(&shown) {
+            notes
+                .entry(session)
+                .or_default()
+                .push(format!("usage: {summary}"));
+        }
+    }
+
+    let config = config::load()?;
+    let print = |agent: &str, agent_sessions: &[&SessionDetail]| {
+        print_agent_sessions(agent, &config.theme(agent), agent_sessions, &notes)
+    };
 
     let mut sessions_by_agent: BTreeMap<&str, Vec<&SessionDetail>> = BTreeMap::new();
     for session in &sessions {
@@ -276,26 +5088,482 @@ fn handle_status(agent_filter: Option<String>) -> Result<()> {
     if let Some(agent) = agent_filter {
         ensure_valid_identifier("agent", &agent)?;
         if let Some(agent_sessions) = sessions_by_agent.get(agent.as_str()) {
-            print_agent_sessions(&agent, agent_sessions);
+            print(&agent, agent_sessions);
         } else {
-            println!("{agent}: no sessions running.");
+            println!(
+                "{}",
+                messages::text("agent_no_sessions", &[("agent", &agent)])
+            );
+        }
+        return Ok(());
+    }
+
+    if all {
+        let known = known_agents(&config);
+        for agent in session_counts(&known, &sessions).keys() {
+            match sessions_by_agent.get(agent.as_str()) {
+                Some(agent_sessions) => print(agent, agent_sessions),
+                None => println!(
+                    "{}",
+                    messages::text("agent_not_running", &[("agent", agent)])
+                ),
+            }
         }
         return Ok(());
     }
 
     if sessions.is_empty() {
-        println!("No agent sessions are running.");
+        println!("{}", messages::text("no_sessions", &[]));
+        if let Some(hint) = stranded_sessions_hint() {
+            eprintln!("amux: hint: {hint}");
+        }
         return Ok(());
     }
 
     for (agent, agent_sessions) in &sessions_by_agent {
-        print_agent_sessions(agent, agent_sessions);
+        print(agent, agent_sessions);
+    }
+
+    Ok(())
+}
+
+fn handle_status_hosts() -> Result<()> {
+    let config = config::load()?;
+    if config.hosts.is_empty() {
+        return Err(fail_with(
+            ErrorKind::Config,
+            "no remote hosts are configured",
+            Some("add one under [hosts.NAME] with ssh = \"user@host\"".into()),
+        ));
+    }
+    let statuses = hosts::check(&config.hosts)?;
+    for (name, host) in &config.hosts {
+        if let Some(status) = statuses.get(name) {
+            print!("{}", hosts::render(name, host, status));
+        }
+    }
+    Ok(())
+}
+
+fn handle_status_diff() -> Result<()> {
+    let mut current = status_diff::Snapshot {
+        taken_at: unix_now(),
+        sessions: BTreeMap::new(),
+    };
+    for session in tmux::list_sessions()? {
+        let exited = tmux::session_info(&session.session_name)
+            .map(|info| info.pane_dead)
+            .unwrap_or(false);
+        let state = if session.crash_looping {
+            "crash-looping"
+        } else if exited {
+            "exited"
+        } else {
+            "running"
+        };
+        let entry = status_diff::Entry {
+            agent: session.agent,
+            name: session.name,
+            state: state.into(),
+            activity: activity::sample(&session.session_name).to_string(),
+        };
+        current.sessions.insert(session.session_name, entry);
+    }
+
+    let previous = status_diff::load()?;
+    let changes = status_diff::changes(&previous.clone().unwrap_or_default(), &current);
+    status_diff::save(&current)?;
+    if changes.is_empty() {
+        return Ok(());
+    }
+    if let Some(previous) = previous {
+        println!("Since {}:", format_timestamp(previous.taken_at));
+    }
+    for change in changes {
+        println!("{change}");
+    }
+    Ok(())
+}
+
+/// `status --sort`/`--columns`: one table row per session instead of one
+/// line of prose.
+fn handle_status_table(
+    agent_filter: Option<String>,
+    all: bool,
+    show_git: bool,
+    sort: Option<String>,
+    columns: Option<String>,
+) -> Result<()> {
+    let (sort, columns) = table_layout(agent_filter.as_deref(), sort, columns)?;
+    let rows = status_rows(agent_filter.as_deref(), all, show_git, sort, &columns)?;
+    if rows.is_empty() {
+        println!("{}", no_sessions_message(agent_filter.as_deref()));
+        if let Some(hint) = stranded_sessions_hint() {
+            eprintln!("amux: hint: {hint}");
+        }
+        return Ok(());
+    }
+    print!("{}", status_table::render(&columns, &rows));
+    Ok(())
+}
+
+/// Points to `amux migrate-socket` while amux sessions still run on the
+/// user's own tmux server, as they do after upgrading to a dedicated one.
+fn stranded_sessions_hint() -> Option<String> {
+    if !mux().holds_options() {
+        return None;
+    }
+    tmux::socket()?;
+    let count = on_shared_server(amux_sessions).ok()?.len();
+    (count > 0).then(|| messages::text("stranded_sessions", &[("count", &count.to_string())]))
+}
+
+/// `status --watch`: the table, redrawn every `seconds` until interrupted,
+/// with what changed between redraws highlighted and logged below it.
+fn handle_status_watch(
+    agent_filter: Option<String>,
+    all: bool,
+    show_git: bool,
+    sort: Option<String>,
+    columns: Option<String>,
+    seconds: u64,
+) -> Result<()> {
+    const LOG_LINES: usize = 10;
+    let (sort, columns) = table_layout(agent_filter.as_deref(), sort, columns)?;
+    let terminal = io::stdout().is_terminal();
+    let colored = theme::use_color();
+    let mut previous: Option<Vec<Row>> = None;
+    let mut log: Vec<String> = Vec::new();
+    loop {
+        let rows = status_rows(agent_filter.as_deref(), all, show_git, sort, &columns)?;
+        let now = format_timestamp(unix_now());
+        if let Some(before) = &previous {
+            for change in status_table::changes(before, &rows) {
+                log.push(format!("{}  {change}", &now[11..]));
+            }
+            let excess = log.len().saturating_sub(LOG_LINES);
+            log.drain(..excess);
+        }
+        let mut out = String::new();
+        if terminal {
+            // Home the cursor and clear, so the table redraws in place.
+            out.push_str("\u{1b}[H\u{1b}[2J");
+        }
+        out.push_str(&format!("Every {seconds}s: amux status  {now}\n\n"));
+        if rows.is_empty() {
+            out.push_str(&no_sessions_message(agent_filter.as_deref()));
+            out.push('\n');
+        } else {
+            let before = previous.as_deref().unwrap_or(&rows);
+            out.push_str(&status_table::render_changes(
+                &columns, &rows, before, colored,
+            ));
+        }
+        if !log.is_empty() {
+            out.push_str("\nChanges:\n");
+            for line in &log {
+                out.push_str(&format!("  {line}\n"));
+            }
+        }
+        print!("{out}");
+        let _ = io::stdout().flush();
+        previous = Some(rows);
+        thread::sleep(Duration::from_secs(seconds));
+    }
+}
+
+fn table_layout(
+    agent_filter: Option<&str>,
+    sort: Option<String>,
+    columns: Option<String>,
+) -> Result<(SortKey, Vec<Column>)> {
+    let sort = match sort {
+        Some(raw) => raw.parse::<SortKey>()?,
+        None => SortKey::Agent,
+    };
+    let columns = match columns {
+        Some(raw) => status_table::parse_columns(&raw)?,
+        None => status_table::DEFAULT_COLUMNS.to_vec(),
+    };
+    if let Some(agent) = agent_filter {
+        ensure_valid_identifier("agent", agent)?;
+    }
+    Ok((sort, columns))
+}
+
+fn no_sessions_message(agent_filter: Option<&str>) -> String {
+    match agent_filter {
+        Some(agent) => messages::text("agent_no_sessions", &[("agent", agent)]),
+        None => messages::text("no_sessions", &[]),
+    }
+}
+
+/// The table rows for the sessions `agent_filter` selects, sorted by `sort`.
+fn status_rows(
+    agent_filter: Option<&str>,
+    all: bool,
+    show_git: bool,
+    sort: SortKey,
+    columns: &[Column],
+) -> Result<Vec<Row>> {
+    let wants = |column: Column| columns.contains(&column);
+
+    let sessions = mux().list_sessions()?;
+    let shown: Vec<&SessionDetail> = sessions
+        .iter()
+        .filter(|session| agent_filter.is_none_or(|agent| session.agent == agent))
+        .collect();
+    let git = if show_git && wants(Column::Git) {
+        git_summaries(&shown)
+    } else {
+        BTreeMap::new()
+    };
+    let usage = if wants(Column::Usage) {
+        usage_summaries(&shown)
+    } else {
+        BTreeMap::new()
+    };
+    let infos: Vec<_> = shown
+        .iter()
+        .map(|session| tmux::session_info(&session.session_name).ok())
+        .collect();
+    let cpu = if wants(Column::Cpu) || sort == SortKey::Cpu {
+        let pids: Vec<u32> = infos.iter().flatten().filter_map(|info| info.pid).collect();
+        status_table::cpu_usage(&pids)
+    } else {
+        BTreeMap::new()
+    };
+
+    let now = unix_now();
+    let mut rows: Vec<Row> = shown
+        .iter()
+        .zip(&infos)
+        .map(|(session, info)| {
+            let info = info.as_ref();
+            Row {
+                agent: session.agent.clone(),
+                name: session.name.clone(),
+                session: session.session_name.clone(),
+                state: if session.crash_looping {
+                    "crash-looping".into()
+                } else if info.is_some_and(|info| info.pane_dead) {
+                    "exited".into()
+                } else {
+                    "running".into()
+                },
+                clients: session.client_count,
+                command: session.pane_command.clone(),
+                cwd: session.cwd.as_deref().map(paths::tildify),
+                activity: activity::sample(&session.session_name).to_string(),
+                uptime: info
+                    .and_then(|info| info.created)
+                    .map(|created| now.saturating_sub(created)),
+                idle: info
+                    .and_then(|info| info.last_activity)
+                    .map(|activity| now.saturating_sub(activity)),
+                cpu: info
+                    .and_then(|info| info.pid)
+                    .and_then(|pid| cpu.get(&pid).copied()),
+                git: git.get(&session.session_name).cloned(),
+                usage: usage.get(&session.session_name).cloned(),
+                note: session.note.clone(),
+            }
+        })
+        .collect();
+    if all && agent_filter.is_none() {
+        let config = config::load()?;
+        for (agent, count) in session_counts(&known_agents(&config), &sessions) {
+            if count == 0 {
+                rows.push(Row {
+                    agent,
+                    state: "stopped".into(),
+                    activity: "-".into(),
+                    ..Row::default()
+                });
+            }
+        }
+    }
+    status_table::sort(&mut rows, sort);
+    Ok(rows)
+}
+
+fn handle_recent(agent_filter: Option<String>, hours: u64) -> Result<()> {
+    if let Some(agent) = &agent_filter {
+        ensure_valid_identifier("agent", agent)?;
+    }
+    let now = unix_now();
+    let mut reports: Vec<RunReport> = report::recent(now.saturating_sub(hours * 3_600))?
+        .into_iter()
+        .filter(|report| {
+            agent_filter
+                .as_ref()
+                .is_none_or(|agent| report.agent == *agent)
+        })
+        .collect();
+    if reports.is_empty() {
+        println!("No sessions ended in the last {hours}h.");
+        return Ok(());
+    }
+
+    let config = config::load()?;
+    let colored = theme::use_color();
+    reports.reverse();
+    for report in reports {
+        println!(
+            "{}: {} (session '{}')",
+            config.theme(&report.agent).paint(&report.agent, 0, colored),
+            report.recent_summary(now),
+            report.session
+        );
+    }
+    Ok(())
+}
+
+fn handle_stats(agent_filter: Option<String>, weeks: u64, as_json: bool) -> Result<()> {
+    if let Some(agent) = &agent_filter {
+        ensure_valid_identifier("agent", agent)?;
+    }
+    let now = unix_now();
+    let since = stats::week_start(now).saturating_sub((weeks - 1) * 7 * 86_400);
+    let runs: Vec<RunReport> = report::recent(since)?
+        .into_iter()
+        .filter(|run| {
+            agent_filter
+                .as_ref()
+                .is_none_or(|agent| run.agent == *agent)
+        })
+        .collect();
+    let summary = stats::summarize(&runs, now, weeks);
+    if as_json {
+        println!("{}", summary.to_json().to_pretty());
+    } else if summary.agents.is_empty() {
+        println!("No sessions ended in the last {weeks} weeks.");
+    } else {
+        print!("{}", summary.render());
+    }
+    Ok(())
+}
+
+/// Agents from the built-in defaults, `CA_AGENT_CMD_*` variables, and the config file.
+fn known_agents(config: &Config) -> Vec<String> {
+    let mut known = agents::configured_agents();
+    known.extend(config.agents.keys().cloned());
+    known
+}
+
+fn handle_list(agent_filter: Option<String>, as_json: bool) -> Result<()> {
+    if let Some(agent) = &agent_filter {
+        ensure_valid_identifier("agent", agent)?;
+    }
+    let config = config::load()?;
+    let sessions = mux().list_sessions()?;
+    let colored = theme::use_color();
+
+    let counts = session_counts(&known_agents(&config), &sessions);
+
+    if as_json {
+        let entries = counts
+            .iter()
+            .filter(|(agent, _)| agent_filter.as_ref().is_none_or(|filter| filter == *agent))
+            .map(|(agent, count)| {
+                let description = agents::agent_description(agent);
+                ide::agent_entry(agent, *count, description.as_deref())
+            })
+            .collect::<Vec<_>>();
+        println!("{}", Value::Array(entries));
+        return Ok(());
+    }
+
+    for (agent, count) in &counts {
+        if agent_filter.as_ref().is_some_and(|filter| filter != agent) {
+            continue;
+        }
+        let state = if *count > 0 { "running" } else { "stopped" };
+        let sessions = match count {
+            0 => String::new(),
+            1 => "1 session".to_string(),
+            n => format!("{n} sessions"),
+        };
+        let description = agents::agent_description(agent).unwrap_or_default();
+        let label = config.theme(agent).paint(agent, 12, colored);
+        let line = format!("{label} {state:<8} {sessions:<11} {description}");
+        println!("{}", line.trim_end());
     }
 
+    if let Some(agent) = agent_filter {
+        if !counts.contains_key(&agent) {
+            println!("{agent}: not configured and not running.");
+        }
+    }
     Ok(())
 }
 
-fn print_agent_sessions(agent: &str, sessions: &[&SessionDetail]) {
+/// Running-session count for every known agent, plus any agent that only has sessions.
+fn session_counts(known: &[String], sessions: &[SessionDetail]) -> BTreeMap<String, usize> {
+    let mut counts: BTreeMap<String, usize> =
+        known.iter().map(|agent| (agent.clone(), 0)).collect();
+    for session in sessions {
+        *counts.entry(session.agent.clone()).or_default() += 1;
+    }
+    counts
+}
+
+/// Working-tree summary keyed by session name, for sessions whose cwd is in a git repository.
+fn git_summaries(sessions: &[&SessionDetail]) -> BTreeMap<String, String> {
+    let cwds: Vec<(String, PathBuf)> = sessions
+        .iter()
+        .filter_map(|session| {
+            let cwd = tmux::session_info(&session.session_name).ok()?.cwd?;
+            Some((session.session_name.clone(), PathBuf::from(cwd)))
+        })
+        .collect();
+    let dirs: Vec<PathBuf> = cwds.iter().map(|(_, cwd)| cwd.clone()).collect();
+    let summaries = worktree::summaries(&dirs);
+    cwds.into_iter()
+        .filter_map(|(session, cwd)| Some((session, summaries.get(&cwd)?.clone())))
+        .collect()
+}
+
+/// Work done since each session started, keyed by session name, for sessions
+/// started in a git repository.
+fn drift_summaries(sessions: &[&SessionDetail]) -> BTreeMap<String, String> {
+    let drifts = parallel::map_bounded(sessions, 4, |session| {
+        let base = tmux::base(&session.session_name).ok()??;
+        let cwd = tmux::session_info(&session.session_name).ok()?.cwd;
+        session_drift(Some(&base), cwd.as_deref())
+    });
+    sessions
+        .iter()
+        .zip(drifts)
+        .filter_map(|(session, drift)| Some((session.session_name.clone(), drift?.summary())))
+        .collect()
+}
+
+fn session_drift(base: Option<&str>, cwd: Option<&str>) -> Option<git::Drift> {
+    git::drift(Path::new(cwd?), base?).ok().flatten()
+}
+
+/// Token and cost estimates keyed by session name, for agents with a usage adapter.
+fn usage_summaries(sessions: &[&SessionDetail]) -> BTreeMap<String, String> {
+    let usages = parallel::map_bounded(sessions, 4, |session| {
+        let info = tmux::session_info(&session.session_name).ok()?;
+        usage::for_session(&session.agent, &info)
+    });
+    sessions
+        .iter()
+        .zip(usages)
+        .filter_map(|(session, usage)| Some((session.session_name.clone(), usage?.summary())))
+        .collect()
+}
+
+/// One line per session; `notes` adds extra `, key: value` details by session name.
+fn print_agent_sessions(
+    agent: &str,
+    theme: &Theme,
+    sessions: &[&SessionDetail],
+    notes: &BTreeMap<String, Vec<String>>,
+) {
+    let colored = theme::use_color();
     let mut entries = sessions.to_vec();
     entries.sort_unstable_by(|a, b| a.session_name.cmp(&b.session_name));
 
@@ -306,9 +5574,42 @@ fn print_agent_sessions(agent: &str, sessions: &[&SessionDetail]) {
             .map(|name| format!(", name '{name}'"))
             .unwrap_or_default();
         let pane = session.pane_command.as_deref().unwrap_or("-");
+        let cwd = session
+            .cwd
+            .as_deref()
+            .map(|cwd| format!(", cwd: {}", paths::tildify(cwd)))
+            .unwrap_or_default();
+        let activity = activity::sample(&session.session_name);
+        let notes_part: String = notes
+            .get(&session.session_name)
+            .into_iter()
+            .flatten()
+            .map(|note| format!(", {note}"))
+            .collect();
+        let pinned = if session.pinned { ", pinned" } else { "" };
+        let note = session
+            .note
+            .as_ref()
+            .map(|note| format!(", note: {note}"))
+            .unwrap_or_default();
+        let state = if session.crash_looping {
+            "crash-looping"
+        } else {
+            "running"
+        };
         println!(
-            "{agent}: running (session '{}'{}, clients: {}, command: {})",
-            session.session_name, name_part, session.client_count, pane
+            "{}: {} (session '{}'{}, clients: {}, command: {}{}, activity: {}{}{}{})",
+            theme.paint(agent, 0, colored),
+            state,
+            session.session_name,
+            name_part,
+            session.client_count,
+            pane,
+            cwd,
+            activity,
+            notes_part,
+            pinned,
+            note
         );
     }
 }
@@ -319,15 +5620,162 @@ fn print_help() {
     println!("Commands:");
     println!("  amux help                Show this overview");
     println!("  amux agents              List all configured agents");
-    println!("  amux status [agent]      Show agent session state");
-    println!("  amux start [-a NAME|NAME] [-n SESSION] [-p \"...\"] [-f]");
+    println!("  amux agents add NAME -c \"COMMAND\" [--description TEXT] [-p \"...\"] [--cwd DIR]");
+    println!(
+        "  amux agents edit NAME [-c \"COMMAND\"] [--description TEXT] [-p \"...\"] [--cwd DIR]"
+    );
+    println!("  amux agents remove NAME");
+    println!("  amux agents import FILE [--from agents|aider|mcp] [--plan]");
+    println!("                         Manage agent definitions in the config file");
+    println!("  amux status [agent] [--all] [--usage]");
+    println!("  amux status [agent] --recent [HOURS]");
+    println!("  amux status [agent] [--all] [--sort KEY] [--columns LIST]");
+    println!("                         Show running agent sessions (--all adds stopped agents)");
+    println!("                         with each session's git state (--no-git to skip)");
+    println!("                         and token/cost estimates from agent logs (--usage)");
+    println!(
+        "                         --sort (agent|name|uptime|idle|cpu) or --columns shows a table"
+    );
+    println!("  amux status --diff       Print only what changed since the last --diff (for cron)");
+    println!("  amux status [agent] --watch [SECONDS]");
+    println!("                         Redraw the table on an interval, highlighting changes");
+    println!("  amux stats [agent] [--weeks N] [--format json]");
+    println!("                         Sessions per agent per week, durations, restarts, crashes");
+    println!("  amux status --hosts      Show sessions on the configured remote hosts (over ssh)");
+    println!("  amux list [agent]        Show every configured agent, running or stopped");
+    println!("  amux status|list [agent] --format json");
+    println!("                         Print sessions or agents as a JSON array for scripts");
+    println!("  amux start [-a NAME|NAME] [-n SESSION] [-p \"...\"] [-d DIR] [-f]");
+    println!("                         (--cmd \"...\" replaces the command; --raw-cmd runs it via sh -c)");
     println!("                         Launch an agent session (use -f/--force to restart)");
+    println!("  amux start NAME [--force-adopt|--strict]");
+    println!("                         Take over a foreign session with the same name, or refuse any reuse");
+    println!("  amux start NAME --auto-name[=index|branch|petname]");
+    println!(
+        "                         Start another session with a generated name if one is running"
+    );
+    println!("  amux start @GROUP [-j N] [-f] [--plan | --auto-approve]");
+    println!("                         Launch every agent in a configured group concurrently");
+    println!("  amux start NAME --matrix MATRIX [-n PREFIX] [-j N] [-f] [--plan]");
+    println!("                         Launch one session per combination of a parameter matrix");
+    println!("                         (--plan shows what would change; -f asks before replacing)");
+    println!("  amux start NAME|@GROUP --wait-ready[=SECONDS]");
+    println!("                         Return only once the agent is ready for input");
     println!("  amux rm [-a NAME|NAME] [-n SESSION]");
     println!("                         Remove the agent's tmux session");
-    println!("  amux attach [-a NAME|NAME] [-n SESSION] [-s]");
+    println!(
+        "  amux rm -i [AGENT]       Review sessions in a checklist and remove the picked ones"
+    );
+    println!("  amux rm --all [AGENT] [--yes|--plan] [--include-pinned]");
+    println!("                         Remove every amux session, or every one of AGENT's");
+    println!("  amux prune [--yes|--plan] [--include-pinned]");
+    println!("                         Remove the sessions whose agent has already exited");
+    println!("  amux stop [-a NAME|NAME] [-n SESSION] [--grace SECS]");
+    println!("                         Ask the agent to exit, then remove its session");
+    println!("  amux kill-server [--yes|--auto-approve] [--include-pinned] [--plan]");
+    println!("                         Remove every amux session; other tmux sessions are kept");
+    println!("  amux apply FILE [--prune] [--plan | --auto-approve]");
+    println!(
+        "                         Start, restart, or remove sessions to match a workspace file"
+    );
+    println!("  amux freeze > FILE       Write the running sessions as a workspace file for apply");
+    println!("  amux migrate-socket [--yes|--plan]");
+    println!("                         Move sessions from your default tmux server to amux's own");
+    println!("  amux attach [-a NAME|NAME] [-n SESSION] [-s] [--take-over] [--host HOST]");
     println!("                         Attach to an agent session (use -s/--start to launch)");
+    println!("                         (--take-over: detach the clients already attached;");
+    println!("                         --host: on a remote host from [hosts], over ssh)");
+    println!("  amux run-adhoc COMMAND [-n SESSION] [-a NAME] [-d DIR] [-f] [--attach]");
+    println!("                         Run a one-off command in a managed session like an agent");
+    println!("                         (`amux attach --command COMMAND` starts it and attaches)");
     println!("  amux detach [-a NAME|NAME] [-n SESSION]");
     println!("                         Detach all clients from an agent session");
+    println!("  amux env [-a NAME|NAME] [-n SESSION] [--show-values]");
+    println!("                         Show the environment a session will receive");
+    println!("  amux inspect-env [-a NAME|NAME] [-n SESSION] [--show-values]");
+    println!("                         Show the environment a running agent actually sees");
+    println!("  amux doctor [-a NAME|NAME]");
+    println!("                         Check new sessions' TERM and locale for rendering problems");
+    println!("  amux auto-detach [-a NAME|NAME] [-n SESSION] --after MINUTES");
+    println!("                         Detach clients idle that long (0 turns it off)");
+    println!("  amux note [-a NAME|NAME] [-n SESSION] [-m TEXT|--clear]");
+    println!("                         Show, set, or clear a note on a session");
+    println!("  amux dnd [-a NAME|NAME] [-n SESSION] [--for DURATION|--off]");
+    println!("                         Mute a session's notifications while it runs unattended");
+    println!("  amux choose-tree-format [--bind KEY]");
+    println!("                         Print a tmux choose-tree format showing amux labels");
+    println!("  amux next|prev [--client CLIENT]");
+    println!("                         Switch the tmux client to the next/previous amux session");
+    println!("  amux serve [--port PORT]");
+    println!(
+        "                         Accept token-authenticated POST /send and /start on localhost"
+    );
+    println!(
+        "  amux notify bridge       Type replies in Slack notification threads into their sessions"
+    );
+    println!("  amux archive list [-a AGENT] | amux archive show ID [--log]");
+    println!("                         Browse compressed logs of removed sessions");
+    println!("  amux snapshots list|show [-a NAME|NAME] [-n SESSION] [--back N]");
+    println!("                         Browse the pane captures saved for a session");
+    println!("  amux tmux-keys print|install [--picker-key KEY] [--next-key KEY] [--prev-key KEY]");
+    println!("                         Bind tmux keys to an amux session menu and session cycling");
+    println!("  amux shell-init bash|zsh|fish");
+    println!("                         Print shell shortcuts (ams, ama, amp) and completions");
+    println!("  amux completions bash|zsh|fish");
+    println!(
+        "                         Print only the completions: commands, flags, agents, sessions"
+    );
+    println!("  amux paths               Show config, data, state, and log directories");
+    println!("  amux config upgrade [--print]");
+    println!(
+        "                         Rewrite the config file in the current format (keeps a .bak)"
+    );
+    println!("  amux ide list [--json]   List sessions for editor integrations");
+    println!("  amux ide open [-a NAME|NAME] [-n SESSION] [-s]");
+    println!("                         Print a JSON terminal-launch spec for an editor");
+    println!("  amux show [-a NAME|NAME] [-n SESSION] [--json]");
+    println!("                         Show details about one agent session");
+    println!("  amux open [-a NAME|NAME] [-n SESSION] [--with PROGRAM]");
+    println!("                         Open the session's working directory in an editor");
+    println!("  amux here [--attach|--send TEXT] [--dir DIR]");
+    println!("                         Find the sessions working on this directory");
+    println!("  amux logs [-a NAME|NAME] [-n SESSION] [-f] [--lines N] [--raw]");
+    println!("                         Print a session's output log (-f follows it until the end)");
+    println!("  amux capture [-a NAME|NAME] [-n SESSION] [--history LINES] [--ansi|--html]");
+    println!(
+        "                         Print the agent's screen and scrollback (--html for sharing)"
+    );
+    println!("  amux diff [-a NAME|NAME] [-n SESSION] [--full]");
+    println!("                         Show git status and a diffstat for the session's directory");
+    println!("  amux checkpoint [-a NAME|NAME] [-n SESSION] [-m MSG] [--list]");
+    println!("                         Snapshot the session's working tree without touching it");
+    println!("  amux rollback [-a NAME|NAME] [-n SESSION] --to N");
+    println!("                         Restore the working tree to checkpoint N");
+    println!("  amux send [-a NAME|NAME] [-n SESSION] [TEXT] [-t TEMPLATE]");
+    println!("                         Type a prompt (or rendered template) into a session");
+    println!("                         (--window/--pane target another pane than the agent's)");
+    println!("  amux pipe [-a NAME|NAME] [-n SESSION] [-m TEXT] [--tail N] -- COMMAND...");
+    println!("                         Run a command and send its output to the agent");
+    println!("  amux relay --from AGENT[:NAME] --to AGENT[:NAME] [--filter REGEX] [--once]");
+    println!("                         Forward one agent's responses to another as prompts");
+    println!("  amux cp SOURCE AGENT:PATH [-n SESSION]");
+    println!(
+        "                         Copy a file to where the agent runs (see [agents.X.transfer])"
+    );
+    println!("  amux broadcast --agents A,B[:NAME] (--interactive | [TEXT])");
+    println!("                         Type into several sessions at once, live or one prompt");
+    println!("  amux dispatch FILE --agents A,B[:NAME] [-j N] [--blocks] [-t SECS]");
+    println!("                         Feed tasks from FILE to a pool of agent sessions");
+    println!("  amux dispatch --status   Show which task went where and which are done");
+    println!("  amux tasks list [--all] | add TEXT [--assign AGENT[:NAME] [--send]]");
+    println!("  amux tasks assign ID AGENT[:NAME] [--send] | done ID | reopen ID");
+    println!("                         Keep a task board for the current workspace");
+    println!("  amux panes [-a NAME|NAME] [-n SESSION]");
+    println!("                         List a session's panes and mark the agent's");
+    println!("  amux wait [-a NAME|NAME] [-n SESSION] (--for-response|--for REGEX) [-t SECS]");
+    println!("                         Block until the agent responds or its output matches");
+    println!("  amux --backend zellij start|attach|status|send|capture|rm ...");
+    println!("                         Run sessions in Zellij instead of tmux");
     println!();
 
     let agents = agents::configured_agents();
@@ -346,6 +5794,159 @@ fn print_help() {
     }
 }
 
+fn print_paths() -> Result<()> {
+    let entries = [
+        ("config", paths::config_file()?),
+        ("config dir", paths::config_dir()?),
+        ("data dir", paths::data_dir()?),
+        ("locales dir", paths::locales_dir()?),
+        ("state dir", paths::state_dir()?),
+        ("logs dir", paths::logs_dir()?),
+        ("history", paths::history_file()?),
+        ("archive", paths::archive_dir()?),
+        ("snapshots", paths::snapshots_dir()?),
+        ("heartbeat", paths::heartbeat_file()?),
+        ("dispatch", paths::dispatch_file()?),
+        ("task boards", paths::tasks_dir()?),
+    ];
+    for (label, path) in entries {
+        println!("{:<12} {}", format!("{label}:"), path.display());
+    }
+    Ok(())
+}
+
+/// `amux agents [list]`: bare names with `--names`, for scripts and completions.
+fn list_agents(names_only: bool) {
+    if names_only {
+        for agent in agents::configured_agents() {
+            println!("{agent}");
+        }
+    } else {
+        print_agents();
+    }
+}
+
+/// `amux agents add/edit/remove`.
+fn handle_agents(command: AgentsCommand) -> Result<()> {
+    let path = paths::config_file()?;
+    let raw = read_raw_config(&path)?;
+    let (name, updated, done) = match command {
+        AgentsCommand::List { .. } | AgentsCommand::Import { .. } => return Ok(()),
+        AgentsCommand::Add {
+            name,
+            command,
+            description,
+            params,
+            cwd,
+        } => {
+            ensure_valid_identifier("agent", &name)?;
+            let definition = Definition {
+                command: Some(command),
+                description,
+                params,
+                cwd,
+                env: BTreeMap::new(),
+            };
+            let updated = agent_edit::add(&raw, &name, &definition)?;
+            (name, updated, "added")
+        }
+        AgentsCommand::Edit {
+            name,
+            command,
+            description,
+            params,
+            cwd,
+        } => {
+            ensure_valid_identifier("agent", &name)?;
+            let changes = Definition {
+                command,
+                description,
+                params,
+                cwd,
+                env: BTreeMap::new(),
+            };
+            let updated = agent_edit::edit(&raw, &name, &changes)?;
+            (name, updated, "updated")
+        }
+        AgentsCommand::Remove { name } => {
+            ensure_valid_identifier("agent", &name)?;
+            let updated = agent_edit::remove(&raw, &name)?;
+            (name, updated, "removed")
+        }
+    };
+    let backup = config::save(&path, &updated)?;
+    match backup {
+        Some(backup) => println!(
+            "{name}: {done} in {} (the previous file is {})",
+            path.display(),
+            backup.display()
+        ),
+        None => println!("{name}: {done} in {}", path.display()),
+    }
+    let variable = format!("CA_AGENT_CMD_{name}");
+    if std::env::vars().any(|(key, _)| key.eq_ignore_ascii_case(&variable)) {
+        eprintln!(
+            "amux: warning: {variable} is set in this shell and takes precedence over the config's command"
+        );
+    }
+    Ok(())
+}
+
+/// The config file as written, or nothing when there is none yet.
+fn read_raw_config(path: &Path) -> Result<String> {
+    match fs::read_to_string(path) {
+        Ok(raw) => Ok(raw),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(String::new()),
+        Err(err) => Err(with_context(
+            err,
+            format!("failed to read config {}", path.display()),
+        )),
+    }
+}
+
+fn handle_agents_import(file: &Path, from: Option<&str>, plan_only: bool) -> Result<()> {
+    let from = from.map(str::parse::<import::Source>).transpose()?;
+    let (source, import) = import::read(file, from)?;
+    let path = paths::config_file()?;
+    let raw = read_raw_config(&path)?;
+    let defined = config::parse(&raw)?;
+    let mut plan = Plan::default();
+    let mut updated = raw.clone();
+    for (name, definition) in &import.agents {
+        if agents::is_default_agent(name) {
+            plan.push(Action::Keep, name, Some("built in"));
+        } else if defined
+            .agent(name)
+            .is_some_and(|agent| agent.command.is_some())
+        {
+            plan.push(Action::Keep, name, Some("already defined"));
+        } else if plan.steps.iter().any(|step| step.session == *name) {
+            plan.push(Action::Keep, name, Some("defined twice in the file"));
+        } else {
+            updated = agent_edit::add(&updated, name, definition)
+                .map_err(|err| with_context(err, format!("cannot import agent '{name}'")))?;
+            plan.push(Action::Create, name, None);
+        }
+    }
+    for (name, reason) in &import.skipped {
+        plan.push(Action::Keep, name, Some(&format!("skipped: {reason}")));
+    }
+    println!("Importing {} as {source} config:", file.display());
+    print!("{}", plan.render());
+    if plan_only || updated == raw {
+        return Ok(());
+    }
+    match config::save(&path, &updated)? {
+        Some(backup) => println!(
+            "Added to {} (the previous file is {})",
+            path.display(),
+            backup.display()
+        ),
+        None => println!("Added to {}", path.display()),
+    }
+    Ok(())
+}
+
 fn print_agents() {
     let agents = agents::configured_agents();
     if agents.is_empty() {
@@ -374,12 +5975,32 @@ fn ensure_valid_identifier(kind: &str, value: &str) -> Result<()> {
     if is_valid {
         Ok(())
     } else {
-        bail(format!(
-            "{kind} '{value}' contains invalid characters (allowed: a-z, A-Z, 0-9, '-', '_')"
+        Err(fail_with(
+            ErrorKind::InvalidInput,
+            format!(
+                "{kind} '{value}' contains invalid characters (allowed: a-z, A-Z, 0-9, '-', '_')"
+            ),
+            None,
         ))
     }
 }
 
+/// Session names may be anything printable; `tmux::session_name` encodes
+/// what tmux cannot take.
+fn ensure_valid_session_name(name: &str) -> Result<()> {
+    if !name.trim().is_empty() && !name.chars().any(char::is_control) {
+        return Ok(());
+    }
+    Err(fail_with(
+        ErrorKind::InvalidInput,
+        format!(
+            "session name '{}' is empty or contains control characters",
+            name.escape_default()
+        ),
+        None,
+    ))
+}
+
 fn resolve_agent_input(
     agent_flag: Option<String>,
     agent_pos: Option<String>,
@@ -393,13 +6014,32 @@ fn resolve_agent_input(
         if agents::is_default_agent(&agent) {
             return Ok(agent);
         }
-        return bail(format!(
-            "{command}: '{agent}' is not a default agent; use --agent/-a to specify custom agents"
+        let config = config::load()?;
+        if agents::accepts_positional(&config, &agent) {
+            return Ok(agent);
+        }
+        let (message, hint) = if config.strict_positional_agents {
+            (
+                format!("{command}: '{agent}' is not a default agent"),
+                "strict_positional_agents is set; use --agent/-a to specify custom agents",
+            )
+        } else {
+            (
+                format!("{command}: '{agent}' is not a known agent"),
+                "define it under [agents.NAME] in the config, or use --agent/-a",
+            )
+        };
+        return Err(fail_with(
+            ErrorKind::InvalidInput,
+            message,
+            Some(hint.into()),
         ));
     }
 
-    bail(format!(
-        "{command}: agent name required; supply a default agent shortcut or --agent/-a <name>"
+    Err(fail_with(
+        ErrorKind::InvalidInput,
+        format!("{command}: agent name required"),
+        Some("supply an agent name or --agent/-a <name>".into()),
     ))
 }
 
@@ -421,6 +6061,64 @@ mod tests {
         );
     }
 
+    #[test]
+    fn ensure_valid_session_name_allows_human_names() {
+        ensure_valid_session_name("feature/login").expect("slashes should be allowed");
+        ensure_valid_session_name("pr#123 v1.2").expect("punctuation should be allowed");
+        assert!(ensure_valid_session_name(" ").is_err());
+        assert!(ensure_valid_session_name("a\nb").is_err());
+    }
+
+    #[test]
+    fn session_counts_include_stopped_and_unconfigured_agents() {
+        let session = |agent: &str, name: Option<&str>| SessionDetail {
+            session_name: tmux::session_name(agent, name),
+            agent: agent.into(),
+            name: name.map(str::to_owned),
+            client_count: 0,
+            pane_command: None,
+            pinned: false,
+            crash_looping: false,
+            note: None,
+            cwd: None,
+        };
+        let sessions = vec![
+            session("codex", None),
+            session("codex", Some("review")),
+            session("adhoc", None),
+        ];
+        let counts = session_counts(&["claude".into(), "codex".into()], &sessions);
+        assert_eq!(
+            counts.into_iter().collect::<Vec<_>>(),
+            vec![
+                ("adhoc".to_string(), 1),
+                ("claude".to_string(), 0),
+                ("codex".to_string(), 2),
+            ]
+        );
+    }
+
+    #[test]
+    fn shell_command_quotes_placeholder_values() {
+        let context = template::Context {
+            cwd: PathBuf::from("/work/my repo"),
+            agent: "codex".into(),
+            name: None,
+            session: "amux-codex".into(),
+        };
+        let tokens =
+            shell_command("cd {cwd} && codex | tee log", &context).expect("script should expand");
+        assert_eq!(tokens[..2], ["sh", "-c"]);
+        assert_eq!(tokens[2], "cd '/work/my repo' && codex | tee log");
+    }
+
+    #[test]
+    fn foreign_session_hint_suggests_remedies() {
+        let message = foreign_session_hint("codex", "amux-codex");
+        assert!(message.contains("--force-adopt"));
+        assert!(message.contains("--auto-name"));
+    }
+
     #[test]
     fn resolve_agent_input_prefers_flag() {
         let agent = resolve_agent_input(Some("custom".into()), Some("codex".into()), "start")
@@ -437,10 +6135,62 @@ mod tests {
     #[test]
     fn resolve_agent_input_rejects_unknown_positional() {
         let err = resolve_agent_input(None, Some("unknown".into()), "start")
-            .expect_err("non-default should error");
+            .expect_err("unknown agent should error");
         assert!(
-            err.to_string().contains("is not a default agent"),
-            "error should flag non-default agent"
+            err.to_string().contains("is not a known agent"),
+            "error should flag unknown agent"
+        );
+    }
+
+    #[test]
+    fn choose_tree_binding_quotes_the_key() {
+        let line = choose_tree_binding("C-a");
+        assert!(line.starts_with("bind-key C-a choose-tree -Zs -F '#{?@amux_agent,"));
+        assert!(line.ends_with("'"));
+        assert!(choose_tree_binding("a b").starts_with("bind-key 'a b' "));
+    }
+
+    #[test]
+    fn adhoc_name_is_the_program_name() {
+        assert_eq!(adhoc_name("pytest -x").expect("named"), "pytest");
+        assert_eq!(
+            adhoc_name("RUST_LOG=debug ./bin/cargo.sh test").expect("named"),
+            "cargo-sh"
         );
+        assert!(adhoc_name("  ").is_err());
+    }
+
+    #[test]
+    fn ensure_supported_refuses_tmux_only_options_on_zellij() {
+        let send = |pane: Option<&str>| Commands::Send {
+            agent: Some("codex".into()),
+            agent_pos: None,
+            text: Some("hi".into()),
+            name: None,
+            template: None,
+            window: None,
+            pane: pane.map(str::to_string),
+            enter: false,
+            no_enter: false,
+        };
+        ensure_supported(Backend::Zellij, &send(None), "send").expect("send works on zellij");
+        let err = ensure_supported(Backend::Zellij, &send(Some("1")), "send")
+            .expect_err("--pane needs tmux");
+        assert!(err
+            .to_string()
+            .contains("`amux send --window or --pane` needs the tmux backend"));
+        ensure_supported(Backend::Tmux, &send(Some("1")), "send").expect("tmux runs every command");
+        ensure_supported(Backend::Zellij, &Commands::Paths, "paths")
+            .expect("paths touches no session");
+        let kill_server = Commands::KillServer {
+            yes: true,
+            include_pinned: false,
+            plan: false,
+        };
+        let err = ensure_supported(Backend::Zellij, &kill_server, "kill-server")
+            .expect_err("kill-server needs tmux");
+        assert!(err
+            .to_string()
+            .contains("`amux kill-server` needs the tmux backend"));
     }
 }
@@ -0,0 +1,84 @@
+//! Who else is attached to a session, shown before `amux attach` joins them.
+//! A client left behind by a dropped SSH login still counts: it holds the
+//! session at its terminal's size and sees everything typed.
+
+use crate::error::Result;
+use crate::origin;
+use crate::time::{format_duration, unix_now};
+use crate::tmux::{self, ClientInfo};
+
+/// An attached client and where it connects from.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Attached {
+    pub client: ClientInfo,
+    /// Address of the SSH client the tmux client runs under, if known.
+    pub remote: Option<String>,
+}
+
+/// The clients attached to `session` now.
+pub fn list(session: &str) -> Result<Vec<Attached>> {
+    Ok(tmux::list_clients(session)?
+        .into_iter()
+        .map(|client| {
+            let remote = client.pid.and_then(origin::process_remote);
+            Attached { client, remote }
+        })
+        .collect())
+}
+
+/// One line such as `/dev/pts/3 via ssh from 10.0.0.5, 80x24, idle 3h 12m`.
+pub fn describe(attached: &Attached, now: u64) -> String {
+    let client = &attached.client;
+    let mut line = client.tty.clone();
+    if let Some(remote) = &attached.remote {
+        line.push_str(&format!(" via ssh from {remote}"));
+    }
+    line.push_str(&format!(", {}x{}", client.width, client.height));
+    if let Some(activity) = client.activity {
+        line.push_str(&format!(
+            ", idle {}",
+            format_duration(now.saturating_sub(activity))
+        ));
+    }
+    line
+}
+
+/// [`describe`] for each client, as of now.
+pub fn describe_all(attached: &[Attached]) -> Vec<String> {
+    let now = unix_now();
+    attached
+        .iter()
+        .map(|attached| describe(attached, now))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn describe_shows_origin_size_and_idle_time() {
+        let mut attached = Attached {
+            client: ClientInfo {
+                name: "/dev/pts/3".into(),
+                tty: "/dev/pts/3".into(),
+                pid: Some(4242),
+                activity: Some(1_000),
+                width: 80,
+                height: 24,
+            },
+            remote: Some("10.0.0.5".into()),
+        };
+        let now = 1_000 + 3 * 3600 + 12 * 60;
+        assert_eq!(
+            describe(&attached, now),
+            format!(
+                "/dev/pts/3 via ssh from 10.0.0.5, 80x24, idle {}",
+                format_duration(now - 1_000)
+            )
+        );
+        attached.remote = None;
+        attached.client.activity = None;
+        assert_eq!(describe(&attached, now), "/dev/pts/3, 80x24");
+    }
+}
@@ -0,0 +1,319 @@
+//! `amux completions SHELL`: tab completion for every subcommand, its flags,
+//! and nested subcommands, generated from the command-line definitions. Agent
+//! names (`amux agents --names`) and the running sessions of an agent
+//! (`amux session-names AGENT`, for `-n`) are looked up at completion time,
+//! so new agents and sessions complete without reloading the script.
+
+use clap::Command;
+
+use crate::shell_init::Shell;
+
+/// A flag of a subcommand.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Flag {
+    pub long: Option<String>,
+    pub short: Option<char>,
+    /// First line of the flag's help, shown by fish.
+    pub help: String,
+    pub takes_value: bool,
+}
+
+impl Flag {
+    fn words(&self) -> impl Iterator<Item = String> + '_ {
+        self.long
+            .iter()
+            .map(|long| format!("--{long}"))
+            .chain(self.short.map(|short| format!("-{short}")))
+    }
+}
+
+/// What can follow one subcommand.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CommandSpec {
+    pub name: String,
+    pub flags: Vec<Flag>,
+    /// Nested subcommands, such as `add` in `amux agents add`.
+    pub subcommands: Vec<String>,
+}
+
+/// The visible subcommands of `cli`, each with its own flags and the global ones.
+pub fn specs(cli: &Command) -> Vec<CommandSpec> {
+    let global: Vec<Flag> = flags(cli)
+        .filter(|(global, _)| *global)
+        .map(|(_, flag)| flag)
+        .collect();
+    cli.get_subcommands()
+        .filter(|command| !command.is_hide_set())
+        .map(|command| CommandSpec {
+            name: command.get_name().to_string(),
+            flags: flags(command)
+                .map(|(_, flag)| flag)
+                .chain(global.iter().cloned())
+                .collect(),
+            subcommands: command
+                .get_subcommands()
+                .filter(|command| !command.is_hide_set())
+                .map(|command| command.get_name().to_string())
+                .collect(),
+        })
+        .collect()
+}
+
+fn flags(command: &Command) -> impl Iterator<Item = (bool, Flag)> + '_ {
+    command
+        .get_arguments()
+        .filter(|arg| !arg.is_positional() && !arg.is_hide_set())
+        .map(|arg| {
+            let help = arg
+                .get_help()
+                .map(|help| help.to_string())
+                .unwrap_or_default();
+            let flag = Flag {
+                long: arg.get_long().map(str::to_string),
+                short: arg.get_short(),
+                help: help.lines().next().unwrap_or_default().to_string(),
+                takes_value: arg.get_action().takes_values(),
+            };
+            (arg.is_global_set(), flag)
+        })
+}
+
+/// The completion script for `shell`.
+pub fn script(shell: Shell, specs: &[CommandSpec]) -> String {
+    match shell {
+        Shell::Bash => shell_script(BASH, specs, |spec, flags, subcommands| {
+            format!(
+                "    {}) flags=\"{flags}\"; subcommands=\"{subcommands}\" ;;\n",
+                spec.name
+            )
+        }),
+        Shell::Zsh => shell_script(ZSH, specs, |spec, flags, subcommands| {
+            format!(
+                "    ({}) flags=\"{flags}\"; subcommands=\"{subcommands}\" ;;\n",
+                spec.name
+            )
+        }),
+        Shell::Fish => fish(specs),
+    }
+}
+
+fn shell_script(
+    template: &str,
+    specs: &[CommandSpec],
+    case: impl Fn(&CommandSpec, &str, &str) -> String,
+) -> String {
+    let commands: Vec<&str> = specs.iter().map(|spec| spec.name.as_str()).collect();
+    let cases: String = specs
+        .iter()
+        .map(|spec| {
+            let flags: Vec<String> = spec.flags.iter().flat_map(Flag::words).collect();
+            case(spec, &flags.join(" "), &spec.subcommands.join(" "))
+        })
+        .collect();
+    template
+        .replace("{commands}", &commands.join(" "))
+        .replace("{cases}\n", &cases)
+}
+
+const BASH: &str = r#"_amux_agent_names() { amux agents --names 2>/dev/null; }
+_amux_agent_word() {
+  local i
+  for ((i = 2; i < COMP_CWORD; i++)); do
+    case ${COMP_WORDS[i]} in
+      -a|--agent) echo "${COMP_WORDS[i+1]}"; return ;;
+    esac
+  done
+  [[ ${COMP_WORDS[2]} != -* ]] && echo "${COMP_WORDS[2]}"
+}
+_amux() {
+  local cur=${COMP_WORDS[COMP_CWORD]} prev=${COMP_WORDS[COMP_CWORD-1]} flags subcommands
+  if [ "$COMP_CWORD" -eq 1 ]; then
+    COMPREPLY=($(compgen -W "{commands}" -- "$cur"))
+    return
+  fi
+  case $prev in
+    -a|--agent) COMPREPLY=($(compgen -W "$(_amux_agent_names)" -- "$cur")); return ;;
+    -n|--name) COMPREPLY=($(compgen -W "$(amux session-names "$(_amux_agent_word)" 2>/dev/null)" -- "$cur")); return ;;
+  esac
+  case ${COMP_WORDS[1]} in
+{cases}
+  esac
+  if [[ $cur == -* ]]; then
+    COMPREPLY=($(compgen -W "$flags" -- "$cur"))
+  elif [ "$COMP_CWORD" -eq 2 ] && [ -n "$subcommands" ]; then
+    COMPREPLY=($(compgen -W "$subcommands" -- "$cur"))
+  elif [ "$COMP_CWORD" -eq 2 ]; then
+    COMPREPLY=($(compgen -W "$(_amux_agent_names)" -- "$cur"))
+  else
+    COMPREPLY=($(compgen -f -- "$cur"))
+  fi
+}
+complete -F _amux amux
+"#;
+
+const ZSH: &str = r#"_amux_agent_names() { amux agents --names 2>/dev/null; }
+_amux_agent_word() {
+  local i
+  for (( i = 3; i < CURRENT; i++ )); do
+    [[ $words[i] == (-a|--agent) ]] && { print -r -- $words[i+1]; return; }
+  done
+  [[ $words[3] != -* ]] && print -r -- $words[3]
+}
+_amux() {
+  local cur=$words[CURRENT] prev=$words[CURRENT-1] flags subcommands
+  if (( CURRENT == 2 )); then
+    compadd -- {commands}
+    return
+  fi
+  case $prev in
+    (-a|--agent) compadd -- ${(f)"$(_amux_agent_names)"}; return ;;
+    (-n|--name) compadd -- ${(f)"$(amux session-names "$(_amux_agent_word)" 2>/dev/null)"}; return ;;
+  esac
+  case $words[2] in
+{cases}
+  esac
+  if [[ $cur == -* ]]; then
+    compadd -- ${=flags}
+  elif (( CURRENT == 3 )) && [[ -n $subcommands ]]; then
+    compadd -- ${=subcommands}
+  elif (( CURRENT == 3 )); then
+    compadd -- ${(f)"$(_amux_agent_names)"}
+  else
+    _files
+  fi
+}
+if (( $+functions[compdef] )); then
+  compdef _amux amux
+fi
+"#;
+
+const FISH: &str = r#"function __amux_session_names
+    set -l tokens (commandline -opc)
+    set -l agent
+    for i in (seq 3 (count $tokens))
+        if contains -- $tokens[(math $i - 1)] -a --agent
+            set agent $tokens[$i]
+        end
+    end
+    if test -z "$agent"; and test (count $tokens) -ge 3; and not string match -q -- '-*' $tokens[3]
+        set agent $tokens[3]
+    end
+    amux session-names $agent 2>/dev/null
+end
+
+complete -c amux -f
+complete -c amux -n __fish_use_subcommand -a '{commands}'
+complete -c amux -n 'not __fish_use_subcommand' -a '(amux agents --names 2>/dev/null)'
+"#;
+
+fn fish(specs: &[CommandSpec]) -> String {
+    let commands: Vec<&str> = specs.iter().map(|spec| spec.name.as_str()).collect();
+    let mut out = FISH.replace("{commands}", &commands.join(" "));
+    for spec in specs {
+        let condition = format!("'__fish_seen_subcommand_from {}'", spec.name);
+        if !spec.subcommands.is_empty() {
+            out.push_str(&format!(
+                "complete -c amux -n {condition} -a '{}'\n",
+                spec.subcommands.join(" ")
+            ));
+        }
+        for flag in &spec.flags {
+            let mut line = format!("complete -c amux -n {condition}");
+            if let Some(long) = &flag.long {
+                line.push_str(&format!(" -l {long}"));
+            }
+            if let Some(short) = flag.short {
+                line.push_str(&format!(" -s {short}"));
+            }
+            match flag.long.as_deref() {
+                Some("agent") => line.push_str(" -x -a '(amux agents --names 2>/dev/null)'"),
+                Some("name") => line.push_str(" -x -a '(__amux_session_names)'"),
+                _ if flag.takes_value => line.push_str(" -r"),
+                _ => {}
+            }
+            if !flag.help.is_empty() {
+                line.push_str(&format!(" -d {}", fish_quote(&flag.help)));
+            }
+            out.push_str(&line);
+            out.push('\n');
+        }
+    }
+    out
+}
+
+fn fish_quote(text: &str) -> String {
+    format!("'{}'", text.replace('\\', "\\\\").replace('\'', "\\'"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::{Arg, ArgAction};
+
+    fn cli() -> Command {
+        Command::new("amux")
+            .arg(
+                Arg::new("retries")
+                    .long("retries")
+                    .global(true)
+                    .action(ArgAction::Set),
+            )
+            .subcommand(
+                Command::new("attach")
+                    .arg(
+                        Arg::new("agent")
+                            .short('a')
+                            .long("agent")
+                            .help("Agent\nmore"),
+                    )
+                    .arg(
+                        Arg::new("take-over")
+                            .long("take-over")
+                            .action(ArgAction::SetTrue),
+                    )
+                    .arg(Arg::new("agent_pos")),
+            )
+            .subcommand(
+                Command::new("agents")
+                    .subcommand(Command::new("add"))
+                    .subcommand(Command::new("internal").hide(true)),
+            )
+            .subcommand(Command::new("record-exit").hide(true))
+    }
+
+    #[test]
+    fn specs_list_flags_and_nested_subcommands() {
+        let specs = specs(&cli());
+        let names: Vec<&str> = specs.iter().map(|spec| spec.name.as_str()).collect();
+        assert_eq!(names, ["attach", "agents"]);
+        let words: Vec<String> = specs[0].flags.iter().flat_map(Flag::words).collect();
+        assert_eq!(words, ["--agent", "-a", "--take-over", "--retries"]);
+        assert_eq!(specs[0].flags[0].help, "Agent");
+        assert!(!specs[0].flags[1].takes_value);
+        assert_eq!(specs[1].subcommands, ["add"]);
+    }
+
+    #[test]
+    fn scripts_complete_commands_flags_and_names() {
+        let specs = specs(&cli());
+        for shell in [Shell::Bash, Shell::Zsh] {
+            let script = script(shell, &specs);
+            assert!(
+                script.contains("attach agents"),
+                "{shell} completes commands"
+            );
+            assert!(
+                script.contains("flags=\"--agent -a --take-over --retries\"; subcommands=\"\""),
+                "{shell} completes flags"
+            );
+            assert!(script.contains("subcommands=\"add\""));
+            assert!(script.contains("amux session-names"));
+            assert!(!script.contains("{cases}"));
+        }
+        let fish = script(Shell::Fish, &specs);
+        assert!(fish.contains(
+            "complete -c amux -n '__fish_seen_subcommand_from attach' -l agent -s a -x -a '(amux agents --names 2>/dev/null)' -d 'Agent'"
+        ));
+        assert!(fish.contains("-n '__fish_seen_subcommand_from agents' -a 'add'"));
+    }
+}
@@ -0,0 +1,1717 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::error::{bail, fail, fail_with, with_context, with_kind, DynError, ErrorKind, Result};
+use crate::multiplexer::Backend;
+use crate::naming::AutoName;
+use crate::paths;
+use crate::theme::Theme;
+use crate::tmux;
+use crate::toml::{self, Table, Value};
+
+#[derive(Debug, Default, Clone)]
+pub struct Config {
+    pub agents: BTreeMap<String, AgentConfig>,
+    pub groups: BTreeMap<String, Vec<GroupMember>>,
+    /// Named prompt templates for `amux send --template`.
+    pub templates: BTreeMap<String, String>,
+    /// Message catalog to use when `AMUX_LOCALE` is unset.
+    pub locale: Option<String>,
+    /// Commands run around `amux attach` for every agent.
+    pub attach_hooks: AttachHooks,
+    /// Detach clients idle for this many minutes; 0 or unset keeps them.
+    pub auto_detach_minutes: Option<u64>,
+    /// Name sessions after the git branch when `-n` is omitted.
+    pub name_from_branch: bool,
+    /// Accept only built-in agents as positional names, so custom agents
+    /// need `-a`.
+    pub strict_positional_agents: bool,
+    /// The localhost endpoint run by `amux serve`.
+    pub webhook: Webhook,
+    /// Chat notifications about agent events.
+    pub notify: Notify,
+    /// Other machines `amux status --hosts` reads sessions from.
+    pub hosts: BTreeMap<String, Host>,
+    /// Program `amux open` opens working directories with.
+    pub open_with: Option<String>,
+    /// Directories agents must not be started in.
+    pub dir_guard: DirGuard,
+    /// Periodic pane captures kept for every session; `None` takes none.
+    pub snapshots: Option<Snapshots>,
+    /// The multiplexer sessions run in.
+    pub backend: Backend,
+    /// The `tmux -L` socket sessions run on; `"default"` is the user's own
+    /// server. Unset means amux's dedicated one.
+    pub tmux_socket: Option<String>,
+    /// Keys skipped because the file is from a newer amux.
+    pub warnings: Vec<String>,
+}
+
+/// A machine running its own amux, reached over ssh.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Host {
+    /// Destination passed to ssh, e.g. `me@devbox` or a `Host` alias.
+    pub ssh: String,
+    /// Give up on the host after this long, connecting included.
+    pub timeout_secs: u64,
+    /// amux on the host; non-interactive ssh shells often lack its directory in PATH.
+    pub amux: String,
+    /// Keep the ssh connection open this long after the last command, so the
+    /// next probe or attach skips the handshake. 0 opens a connection each time.
+    pub control_persist_secs: u64,
+}
+
+/// Where agent events are posted. Replies are read back only through the
+/// Slack bot token, since incoming webhooks cannot read a thread.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Notify {
+    pub slack_webhook: Option<String>,
+    pub discord_webhook: Option<String>,
+    /// Bot token and channel for posting with `chat.postMessage`, which lets
+    /// `amux notify bridge` pick up replies in the message's thread.
+    pub slack_token: Option<String>,
+    pub slack_channel: Option<String>,
+    /// Any URL that takes a JSON POST with the event, session, exit status, and output.
+    pub webhook: Option<String>,
+    /// Also show a desktop notification (`notify-send`, or `osascript` on macOS).
+    pub desktop: bool,
+    /// Events to post: `needs_input`, `finished`, `crashed`.
+    pub events: Vec<String>,
+    /// Lines of pane output included with each event.
+    pub tail_lines: usize,
+    /// Seconds between checks for replies in `amux notify bridge`.
+    pub poll_secs: u64,
+}
+
+impl Notify {
+    pub fn has_sink(&self) -> bool {
+        self.slack_webhook.is_some()
+            || self.discord_webhook.is_some()
+            || (self.slack_token.is_some() && self.slack_channel.is_some())
+            || self.webhook.is_some()
+            || self.desktop
+    }
+
+    pub fn wants(&self, event: &str) -> bool {
+        self.has_sink() && self.events.iter().any(|wanted| wanted == event)
+    }
+}
+
+impl Default for Notify {
+    fn default() -> Self {
+        Self {
+            slack_webhook: None,
+            discord_webhook: None,
+            slack_token: None,
+            slack_channel: None,
+            webhook: None,
+            desktop: false,
+            events: NOTIFY_EVENTS
+                .iter()
+                .map(|event| event.to_string())
+                .collect(),
+            tail_lines: 20,
+            poll_secs: 10,
+        }
+    }
+}
+
+/// `amux serve`: an HTTP endpoint on 127.0.0.1 for other local tools.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Webhook {
+    pub port: u16,
+    /// Bearer token with the `admin` scope; `AMUX_WEBHOOK_TOKEN` overrides it.
+    pub token: Option<String>,
+    /// Operations the endpoint accepts from any token; none by default.
+    pub allow: Vec<String>,
+    /// Further tokens by label, each limited to a scope.
+    pub tokens: BTreeMap<String, WebhookToken>,
+}
+
+impl Default for Webhook {
+    fn default() -> Self {
+        Self {
+            port: 7468,
+            token: None,
+            allow: Vec::new(),
+            tokens: BTreeMap::new(),
+        }
+    }
+}
+
+/// A token from `[webhook.tokens.<label>]`, for a tool that should only be
+/// able to do part of what the endpoint allows.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WebhookToken {
+    pub token: String,
+    pub scope: Scope,
+    /// Narrows `[webhook] allow` further for this token.
+    pub allow: Option<Vec<String>>,
+}
+
+/// What a webhook token may do. Each scope includes the ones before it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Scope {
+    /// Look at sessions.
+    Read,
+    /// Also send prompts and start agents.
+    Control,
+    /// Also remove sessions.
+    Admin,
+}
+
+impl Scope {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Read => "read",
+            Self::Control => "control",
+            Self::Admin => "admin",
+        }
+    }
+}
+
+impl FromStr for Scope {
+    type Err = DynError;
+
+    fn from_str(raw: &str) -> Result<Self> {
+        match raw {
+            "read" => Ok(Self::Read),
+            "control" => Ok(Self::Control),
+            "admin" => Ok(Self::Admin),
+            other => bail(format!(
+                "unknown scope '{other}' (expected read, control, or admin)"
+            )),
+        }
+    }
+}
+
+/// Shell commands run in the attaching terminal just before `amux attach`
+/// hands it to tmux and right after the client detaches.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct AttachHooks {
+    pub pre_attach: Option<String>,
+    pub post_detach: Option<String>,
+}
+
+/// Restart an agent whose program exits on its own, unless it has exited more
+/// than `max_restarts` times within `window_minutes`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeepAlive {
+    pub max_restarts: u64,
+    pub window_minutes: u64,
+    /// Command run once the restarts stop, with the pane's last lines in `AMUX_OUTPUT_TAIL`.
+    pub notify: Option<String>,
+}
+
+impl Default for KeepAlive {
+    fn default() -> Self {
+        Self {
+            max_restarts: 5,
+            window_minutes: 10,
+            notify: None,
+        }
+    }
+}
+
+/// How `amux stop` asks an agent to exit before killing its session, and
+/// `rm` and `start --force` too when the agent has a `[stop]` table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Stop {
+    /// tmux key names to type instead of sending SIGTERM, such as `C-c`.
+    pub keys: Option<String>,
+    /// Signals sent in order, without the `SIG` prefix (`INT`, `TERM`, ...).
+    /// Empty sends SIGTERM unless `keys` are set.
+    pub signals: Vec<String>,
+    /// Seconds to wait for the agent to exit before killing the session.
+    pub grace_secs: u64,
+}
+
+impl Default for Stop {
+    fn default() -> Self {
+        Self {
+            keys: None,
+            signals: Vec::new(),
+            grace_secs: 10,
+        }
+    }
+}
+
+/// Signals `[agents.NAME.stop] signal` may name.
+pub const STOP_SIGNALS: &[&str] = &["HUP", "INT", "QUIT", "KILL", "TERM", "USR1", "USR2"];
+
+/// How prompts too large to type at once are pasted into the agent.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Paste {
+    /// Largest piece pasted at a time, in bytes.
+    pub chunk_bytes: usize,
+    /// Pause between pieces, and before the final Enter, in milliseconds.
+    pub chunk_delay_ms: u64,
+}
+
+impl Default for Paste {
+    fn default() -> Self {
+        Self {
+            chunk_bytes: 4096,
+            chunk_delay_ms: 30,
+        }
+    }
+}
+
+/// What to do before typing into a pane that is not showing its program's
+/// normal screen.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SendGuard {
+    /// Leave copy mode (or another tmux pane mode) instead of refusing to send.
+    pub exit_copy_mode: bool,
+    /// Refuse to send while the pane shows the alternate screen, e.g. a pager
+    /// the agent opened. Off by default since full-screen agents use it too.
+    pub refuse_alternate_screen: bool,
+}
+
+/// How often each session's visible pane is saved, and how many are kept.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshots {
+    pub interval_secs: u64,
+    pub keep: usize,
+}
+
+impl Default for Snapshots {
+    fn default() -> Self {
+        Self {
+            interval_secs: 60,
+            keep: 10,
+        }
+    }
+}
+
+/// Where agents may be started, checked for every new session.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirGuard {
+    /// Directories no agent starts in; `~` is the home directory, and a
+    /// trailing `/**` covers everything below the directory as well.
+    pub deny: Vec<String>,
+    /// What to do when the directory is not inside a git repository.
+    pub outside_git: OutsideGit,
+}
+
+impl Default for DirGuard {
+    fn default() -> Self {
+        Self {
+            deny: vec!["/".into(), "~".into()],
+            outside_git: OutsideGit::Ask,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutsideGit {
+    Allow,
+    /// Ask on a terminal; elsewhere warn and go ahead.
+    Ask,
+    Refuse,
+}
+
+impl FromStr for OutsideGit {
+    type Err = DynError;
+
+    fn from_str(raw: &str) -> Result<Self> {
+        match raw {
+            "allow" => Ok(Self::Allow),
+            "ask" => Ok(Self::Ask),
+            "refuse" => Ok(Self::Refuse),
+            other => bail(format!(
+                "unknown outside_git setting '{other}' (expected allow, ask, or refuse)"
+            )),
+        }
+    }
+}
+
+/// Checks run before launching an agent; any failing one refuses the start.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Preflight {
+    /// Least free disk space, in megabytes, in the directory the agent starts in.
+    pub min_free_disk_mb: Option<u64>,
+    /// Shell command (e.g. an API quota check) that must exit with status 0.
+    pub command: Option<String>,
+}
+
+/// How `amux cp` reaches an agent that runs somewhere else, e.g. over SSH or
+/// in a container, instead of in the session's directory on this machine.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Transfer {
+    pub via: TransferVia,
+    /// SSH host, container, or pod (`namespace/pod` for kubectl).
+    pub target: String,
+    /// Directory on the target that relative paths are resolved against.
+    pub dir: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TransferVia {
+    Ssh,
+    Docker,
+    Kubectl,
+}
+
+impl FromStr for TransferVia {
+    type Err = DynError;
+
+    fn from_str(raw: &str) -> Result<Self> {
+        match raw {
+            "ssh" => Ok(Self::Ssh),
+            "docker" => Ok(Self::Docker),
+            "kubectl" => Ok(Self::Kubectl),
+            other => bail(format!(
+                "unknown transfer '{other}' (expected ssh, docker, or kubectl)"
+            )),
+        }
+    }
+}
+
+/// How `start --wait-ready` tells that an agent is interactive.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Readiness {
+    /// Pattern the agent's pane shows once it is ready.
+    pub pattern: Option<String>,
+    /// Shell command that exits with status 0 once the agent is ready.
+    pub command: Option<String>,
+    pub timeout_secs: Option<u64>,
+}
+
+/// Settings to compare side by side with `amux start --matrix`: one session
+/// per combination of axis values, each started with `params` after `{axis}`
+/// placeholders are replaced by the combination's values.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Matrix {
+    pub params: String,
+    /// Values of each axis, e.g. `model = ["opus", "sonnet"]`.
+    pub axes: BTreeMap<String, Vec<String>>,
+}
+
+/// One session in a named group, written as `"agent"` or `"agent:name"`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GroupMember {
+    pub agent: String,
+    pub name: Option<String>,
+}
+
+impl GroupMember {
+    pub fn parse(raw: &str) -> Self {
+        match raw.split_once(':') {
+            Some((agent, name)) => Self {
+                agent: agent.to_string(),
+                name: Some(name.to_string()),
+            },
+            None => Self {
+                agent: raw.to_string(),
+                name: None,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct AgentConfig {
+    /// Command line that starts the agent, for agents amux does not know.
+    pub command: Option<String>,
+    /// Shown next to the agent in `amux list`.
+    pub description: Option<String>,
+    /// Arguments added after the command; `--params` goes after these.
+    pub params: Option<String>,
+    /// Directory new sessions start in instead of the current one; `~/` is
+    /// the home directory.
+    pub cwd: Option<String>,
+    pub response: ResponseConfig,
+    /// Variables set in every session of this agent.
+    pub env: BTreeMap<String, String>,
+    /// Variables copied from the invoking shell into new sessions.
+    pub forward_env: Vec<String>,
+    /// TERM for new sessions instead of tmux's `default-terminal`.
+    pub term: Option<String>,
+    /// Variables set only where a session would otherwise get them unset,
+    /// empty, or (for the locale) without UTF-8.
+    pub env_fixups: BTreeMap<String, String>,
+    /// How to name a new session when `-n` is omitted and the default one is running.
+    pub auto_name: Option<AutoName>,
+    /// Per-agent attach hooks; each one set here replaces the top-level one.
+    pub attach_hooks: AttachHooks,
+    /// Color and icon that mark this agent's sessions.
+    pub theme: Theme,
+    /// Restart policy for agents that exit; `None` lets the session end.
+    pub keep_alive: Option<KeepAlive>,
+    /// `None` without a `[stop]` table, which `stop` treats as the defaults.
+    pub stop: Option<Stop>,
+    pub ready: Readiness,
+    /// Overrides the top-level `auto_detach_minutes` for this agent.
+    pub auto_detach_minutes: Option<u64>,
+    /// Overrides the top-level `name_from_branch` for this agent.
+    pub name_from_branch: Option<bool>,
+    /// Parameter matrices for `amux start --matrix NAME`.
+    pub matrices: BTreeMap<String, Matrix>,
+    pub paste: Paste,
+    pub send: SendGuard,
+    pub preflight: Preflight,
+    /// Where `amux cp` copies files; `None` means the session's directory.
+    pub transfer: Option<Transfer>,
+}
+
+/// How to tell that an agent has finished responding and is waiting for input.
+#[derive(Debug, Default, Clone)]
+pub struct ResponseConfig {
+    /// Pattern matching the agent's input prompt near the bottom of the pane.
+    pub prompt: Option<String>,
+    /// How long the pane must stay unchanged before the response counts as done.
+    pub quiet_ms: Option<u64>,
+}
+
+impl Config {
+    pub fn agent(&self, name: &str) -> Option<&AgentConfig> {
+        self.agents.get(name)
+    }
+
+    pub fn theme(&self, agent: &str) -> Theme {
+        self.agent(agent)
+            .map(|agent| agent.theme.clone())
+            .unwrap_or_default()
+    }
+
+    /// Attach hooks for `agent`, with its own settings taking precedence.
+    pub fn attach_hooks(&self, agent: &str) -> AttachHooks {
+        let own = self
+            .agent(agent)
+            .map(|agent| agent.attach_hooks.clone())
+            .unwrap_or_default();
+        AttachHooks {
+            pre_attach: own
+                .pre_attach
+                .or_else(|| self.attach_hooks.pre_attach.clone()),
+            post_detach: own
+                .post_detach
+                .or_else(|| self.attach_hooks.post_detach.clone()),
+        }
+    }
+
+    /// Where `agent`'s sessions start, if the config says.
+    pub fn agent_dir(&self, agent: &str) -> Option<PathBuf> {
+        let cwd = self.agent(agent)?.cwd.as_deref()?;
+        match (cwd.strip_prefix("~/"), env::var_os("HOME")) {
+            (Some(rest), Some(home)) => Some(PathBuf::from(home).join(rest)),
+            _ => Some(PathBuf::from(cwd)),
+        }
+    }
+
+    pub fn paste(&self, agent: &str) -> Paste {
+        self.agent(agent)
+            .map(|agent| agent.paste.clone())
+            .unwrap_or_default()
+    }
+
+    pub fn stop(&self, agent: &str) -> Stop {
+        self.configured_stop(agent).unwrap_or_default()
+    }
+
+    /// The agent's `[stop]` table, if it has one.
+    pub fn configured_stop(&self, agent: &str) -> Option<Stop> {
+        self.agent(agent).and_then(|agent| agent.stop.clone())
+    }
+
+    pub fn preflight(&self, agent: &str) -> Preflight {
+        self.agent(agent)
+            .map(|agent| agent.preflight.clone())
+            .unwrap_or_default()
+    }
+
+    pub fn transfer(&self, agent: &str) -> Option<&Transfer> {
+        self.agent(agent).and_then(|agent| agent.transfer.as_ref())
+    }
+
+    pub fn send_guard(&self, agent: &str) -> SendGuard {
+        self.agent(agent)
+            .map(|agent| agent.send.clone())
+            .unwrap_or_default()
+    }
+
+    /// Idle minutes after which `agent`'s clients are detached, if enabled.
+    pub fn auto_detach_minutes(&self, agent: &str) -> Option<u64> {
+        self.agent(agent)
+            .and_then(|agent| agent.auto_detach_minutes)
+            .or(self.auto_detach_minutes)
+            .filter(|minutes| *minutes > 0)
+    }
+
+    /// Whether `agent`'s sessions are named after the git branch by default.
+    pub fn name_from_branch(&self, agent: &str) -> bool {
+        self.agent(agent)
+            .and_then(|agent| agent.name_from_branch)
+            .unwrap_or(self.name_from_branch)
+    }
+
+    pub fn template(&self, name: &str) -> Result<&str> {
+        match self.templates.get(name) {
+            Some(template) => Ok(template),
+            None => Err(fail_with(
+                ErrorKind::Config,
+                format!("unknown template '{name}'"),
+                Some("define it under [templates] in the config file".into()),
+            )),
+        }
+    }
+
+    pub fn matrix(&self, agent: &str, name: &str) -> Result<&Matrix> {
+        match self
+            .agent(agent)
+            .and_then(|config| config.matrices.get(name))
+        {
+            Some(matrix) => Ok(matrix),
+            None => Err(fail_with(
+                ErrorKind::Config,
+                format!("unknown matrix '{name}' for agent '{agent}'"),
+                Some(format!(
+                    "define it under [agents.{agent}.matrix.{name}] in the config file"
+                )),
+            )),
+        }
+    }
+
+    pub fn group(&self, name: &str) -> Result<&[GroupMember]> {
+        match self.groups.get(name) {
+            Some(members) => Ok(members),
+            None => Err(fail_with(
+                ErrorKind::Config,
+                format!("unknown group '{name}'"),
+                Some("define it under [groups] in the config file".into()),
+            )),
+        }
+    }
+}
+
+/// Load the user config, treating a missing file as an empty config.
+pub fn load() -> Result<Config> {
+    let path = paths::config_file()?;
+    match fs::read_to_string(&path) {
+        Ok(raw) => {
+            let config = parse(&raw).map_err(|err| {
+                with_kind(
+                    with_context(err, format!("invalid config {}", path.display())),
+                    ErrorKind::Config,
+                )
+            })?;
+            for warning in &config.warnings {
+                eprintln!("amux: warning: {}: {warning}", path.display());
+            }
+            Ok(config)
+        }
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Config::default()),
+        Err(err) => Err(with_kind(
+            with_context(err, format!("failed to read config {}", path.display())),
+            ErrorKind::Config,
+        )),
+    }
+}
+
+/// Replace the config file at `path` with `text`, keeping the previous file
+/// as `config.toml.bak`. Returns the backup's path when there was a file.
+pub fn save(path: &Path, text: &str) -> Result<Option<PathBuf>> {
+    let backup = if path.exists() {
+        let backup = path.with_extension("toml.bak");
+        fs::copy(path, &backup)
+            .map_err(|err| with_context(err, format!("failed to write {}", backup.display())))?;
+        Some(backup)
+    } else {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)
+                .map_err(|err| with_context(err, format!("failed to create {}", dir.display())))?;
+        }
+        None
+    };
+    let tmp = path.with_extension("toml.tmp");
+    fs::write(&tmp, text)
+        .and_then(|()| fs::rename(&tmp, path))
+        .map_err(|err| with_context(err, format!("failed to write {}", path.display())))?;
+    Ok(backup)
+}
+
+/// The config format this amux understands in full. A file without a
+/// `version` key predates versioning and reads the same as version 1.
+pub const CONFIG_VERSION: u64 = 1;
+
+/// Steps rewriting a config document from version `n` (0 for files without
+/// a `version` key) to `n + 1`, for `amux config upgrade`.
+const MIGRATIONS: [fn(&mut Table); CONFIG_VERSION as usize] = [
+    // 0 -> 1: the first versioned format only adds the `version` key.
+    |_| {},
+];
+
+pub fn parse(raw: &str) -> Result<Config> {
+    let doc = toml::parse(raw)?;
+    let mut config = Config::default();
+    let version = match doc.get("version") {
+        Some(value) => expect_version(value)?,
+        None => CONFIG_VERSION,
+    };
+    let mut unknown = Unknown {
+        newer: (version > CONFIG_VERSION).then_some(version),
+        warnings: Vec::new(),
+    };
+    let unknown = &mut unknown;
+
+    for (key, value) in &doc {
+        match key.as_str() {
+            "agents" => {
+                for (name, agent) in expect_table(value, "agents")? {
+                    let context = format!("agents.{name}");
+                    let agent = parse_agent(expect_table(agent, &context)?, &context, unknown)?;
+                    config.agents.insert(name.clone(), agent);
+                }
+            }
+            "groups" => {
+                for (name, members) in expect_table(value, "groups")? {
+                    let context = format!("groups.{name}");
+                    let members = expect_string_array(members, &context)?
+                        .iter()
+                        .map(|member| GroupMember::parse(member))
+                        .collect();
+                    config.groups.insert(name.clone(), members);
+                }
+            }
+            "templates" => {
+                for (name, template) in expect_table(value, "templates")? {
+                    let template = expect_string(template, &format!("templates.{name}"))?;
+                    config.templates.insert(name.clone(), template);
+                }
+            }
+            "locale" => config.locale = Some(expect_string(value, "locale")?),
+            "attach_hooks" => {
+                config.attach_hooks = parse_attach_hooks(
+                    expect_table(value, "attach_hooks")?,
+                    "attach_hooks",
+                    unknown,
+                )?;
+            }
+            "auto_detach_minutes" => {
+                config.auto_detach_minutes = Some(expect_unsigned(value, "auto_detach_minutes")?);
+            }
+            "name_from_branch" => {
+                config.name_from_branch = expect_bool(value, "name_from_branch")?;
+            }
+            "strict_positional_agents" => {
+                config.strict_positional_agents = expect_bool(value, "strict_positional_agents")?;
+            }
+            "webhook" => {
+                config.webhook = parse_webhook(expect_table(value, "webhook")?, "webhook", unknown)?
+            }
+            "notify" => {
+                config.notify = parse_notify(expect_table(value, "notify")?, "notify", unknown)?
+            }
+            "hosts" => {
+                for (name, host) in expect_table(value, "hosts")? {
+                    let context = format!("hosts.{name}");
+                    let host = parse_host(expect_table(host, &context)?, &context, unknown)?;
+                    config.hosts.insert(name.clone(), host);
+                }
+            }
+            "open_with" => config.open_with = Some(expect_string(value, "open_with")?),
+            "backend" => config.backend = expect_string(value, "backend")?.parse()?,
+            "tmux_socket" => {
+                let raw = expect_string(value, "tmux_socket")?;
+                tmux::parse_socket(&raw)?;
+                config.tmux_socket = Some(raw);
+            }
+            "dir_guard" => {
+                config.dir_guard =
+                    parse_dir_guard(expect_table(value, "dir_guard")?, "dir_guard", unknown)?
+            }
+            "snapshots" => {
+                config.snapshots = Some(parse_snapshots(
+                    expect_table(value, "snapshots")?,
+                    "snapshots",
+                    unknown,
+                )?)
+            }
+            "version" => {}
+            other => unknown.key(other)?,
+        }
+    }
+
+    config.warnings = std::mem::take(&mut unknown.warnings);
+    Ok(config)
+}
+
+/// `raw` rewritten in the current format, or `None` if it already is.
+pub fn upgrade(raw: &str) -> Result<Option<String>> {
+    let mut doc = toml::parse(raw)?;
+    let version = match doc.get("version") {
+        Some(value) => expect_unsigned(value, "version")?,
+        None => 0,
+    };
+    if version > CONFIG_VERSION {
+        return Err(fail_with(
+            ErrorKind::Config,
+            format!(
+                "config version {version} is newer than this amux understands ({CONFIG_VERSION})"
+            ),
+            Some("upgrade amux instead".into()),
+        ));
+    }
+    if version == CONFIG_VERSION {
+        return Ok(None);
+    }
+    let original = doc.clone();
+    for migrate in &MIGRATIONS[version as usize..] {
+        migrate(&mut doc);
+    }
+    let upgraded = if doc == original && !doc.contains_key("version") {
+        // Only the version changes, so keep the file as written, comments included.
+        format!("version = {CONFIG_VERSION}\n\n{raw}")
+    } else {
+        doc.insert("version".into(), Value::Integer(CONFIG_VERSION as i64));
+        toml::to_string(&doc)
+    };
+    parse(&upgraded)?;
+    Ok(Some(upgraded))
+}
+
+/// Keys amux does not know are a mistake in a config written for this
+/// version, but likely settings from a later amux in a config that says it
+/// is newer. Such a config is read as far as this amux understands it.
+struct Unknown {
+    newer: Option<u64>,
+    warnings: Vec<String>,
+}
+
+impl Unknown {
+    fn key(&mut self, path: &str) -> Result<()> {
+        let Some(version) = self.newer else {
+            return bail(format!("unknown key '{path}'"));
+        };
+        self.warnings.push(format!(
+            "ignoring unknown key '{path}' (the config is version {version}, \
+             this amux understands up to {CONFIG_VERSION})"
+        ));
+        Ok(())
+    }
+}
+
+fn expect_version(value: &Value) -> Result<u64> {
+    match expect_unsigned(value, "version")? {
+        0 => bail("'version' must be 1 or more"),
+        version => Ok(version),
+    }
+}
+
+fn parse_agent(table: &Table, context: &str, unknown: &mut Unknown) -> Result<AgentConfig> {
+    let mut agent = AgentConfig::default();
+    for (key, value) in table {
+        let path = format!("{context}.{key}");
+        match key.as_str() {
+            "command" => agent.command = Some(expect_string(value, &path)?),
+            "description" => agent.description = Some(expect_string(value, &path)?),
+            "params" => agent.params = Some(expect_string(value, &path)?),
+            "cwd" => agent.cwd = Some(expect_string(value, &path)?),
+            "response" => {
+                agent.response = parse_response(expect_table(value, &path)?, &path, unknown)?
+            }
+            "env" => {
+                for (name, value) in expect_table(value, &path)? {
+                    let value = expect_string(value, &format!("{path}.{name}"))?;
+                    agent.env.insert(name.clone(), value);
+                }
+            }
+            "forward_env" => agent.forward_env = expect_string_array(value, &path)?,
+            "term" => agent.term = Some(expect_string(value, &path)?),
+            "env_fixups" => {
+                for (name, value) in expect_table(value, &path)? {
+                    let context = format!("{path}.{name}");
+                    if name == "TERM" {
+                        return bail(format!("'{context}': set TERM with `term` instead"));
+                    }
+                    agent
+                        .env_fixups
+                        .insert(name.clone(), expect_string(value, &context)?);
+                }
+            }
+            "auto_name" => {
+                let strategy = expect_string(value, &path)?;
+                agent.auto_name = Some(
+                    strategy
+                        .parse()
+                        .map_err(|err| with_context(err, format!("'{path}'")))?,
+                );
+            }
+            "attach_hooks" => {
+                agent.attach_hooks =
+                    parse_attach_hooks(expect_table(value, &path)?, &path, unknown)?;
+            }
+            "color" => {
+                let color = expect_string(value, &path)?;
+                agent.theme.color = Some(
+                    color
+                        .parse()
+                        .map_err(|err| with_context(err, format!("'{path}'")))?,
+                );
+            }
+            "icon" => agent.theme.icon = Some(expect_string(value, &path)?),
+            "ready" => agent.ready = parse_ready(expect_table(value, &path)?, &path, unknown)?,
+            "keep_alive" => {
+                agent.keep_alive = Some(parse_keep_alive(
+                    expect_table(value, &path)?,
+                    &path,
+                    unknown,
+                )?);
+            }
+            "stop" => agent.stop = Some(parse_stop(expect_table(value, &path)?, &path, unknown)?),
+            "auto_detach_minutes" => {
+                agent.auto_detach_minutes = Some(expect_unsigned(value, &path)?)
+            }
+            "name_from_branch" => agent.name_from_branch = Some(expect_bool(value, &path)?),
+            "matrix" => {
+                for (name, matrix) in expect_table(value, &path)? {
+                    let context = format!("{path}.{name}");
+                    let matrix = parse_matrix(expect_table(matrix, &context)?, &context)?;
+                    agent.matrices.insert(name.clone(), matrix);
+                }
+            }
+            "paste" => agent.paste = parse_paste(expect_table(value, &path)?, &path, unknown)?,
+            "send" => agent.send = parse_send_guard(expect_table(value, &path)?, &path, unknown)?,
+            "preflight" => {
+                agent.preflight = parse_preflight(expect_table(value, &path)?, &path, unknown)?;
+            }
+            "transfer" => {
+                agent.transfer = Some(parse_transfer(expect_table(value, &path)?, &path, unknown)?);
+            }
+            _ => unknown.key(&path)?,
+        }
+    }
+    Ok(agent)
+}
+
+fn parse_attach_hooks(table: &Table, context: &str, unknown: &mut Unknown) -> Result<AttachHooks> {
+    let mut hooks = AttachHooks::default();
+    for (key, value) in table {
+        let path = format!("{context}.{key}");
+        match key.as_str() {
+            "pre_attach" => hooks.pre_attach = Some(expect_string(value, &path)?),
+            "post_detach" => hooks.post_detach = Some(expect_string(value, &path)?),
+            _ => unknown.key(&path)?,
+        }
+    }
+    Ok(hooks)
+}
+
+/// Operations `[webhook] allow` may list, with the scope each needs.
+pub const WEBHOOK_OPERATIONS: &[(&str, Scope)] = &[
+    ("status", Scope::Read),
+    ("send", Scope::Control),
+    ("start", Scope::Control),
+    ("rm", Scope::Admin),
+];
+
+/// The scope `operation` needs, or `None` for an unknown operation.
+pub fn webhook_scope(operation: &str) -> Option<Scope> {
+    WEBHOOK_OPERATIONS
+        .iter()
+        .find(|(name, _)| *name == operation)
+        .map(|(_, scope)| *scope)
+}
+
+fn parse_webhook(table: &Table, context: &str, unknown: &mut Unknown) -> Result<Webhook> {
+    let mut webhook = Webhook::default();
+    for (key, value) in table {
+        let path = format!("{context}.{key}");
+        match key.as_str() {
+            "port" => {
+                webhook.port = u16::try_from(expect_unsigned(value, &path)?)
+                    .map_err(|_| fail(format!("'{path}' must be a port number")))?;
+            }
+            "token" => {
+                let value = expect_string(value, &path)?;
+                if value.is_empty() {
+                    return bail(format!("'{path}' must not be empty"));
+                }
+                webhook.token = Some(value);
+            }
+            "allow" => webhook.allow = expect_webhook_operations(value, &path)?,
+            "tokens" => {
+                for (label, token) in expect_table(value, &path)? {
+                    let context = format!("{path}.{label}");
+                    let token =
+                        parse_webhook_token(expect_table(token, &context)?, &context, unknown)?;
+                    webhook.tokens.insert(label.clone(), token);
+                }
+            }
+            _ => unknown.key(&path)?,
+        }
+    }
+    Ok(webhook)
+}
+
+fn parse_webhook_token(
+    table: &Table,
+    context: &str,
+    unknown: &mut Unknown,
+) -> Result<WebhookToken> {
+    let mut token = None;
+    let mut scope = None;
+    let mut allow = None;
+    for (key, value) in table {
+        let path = format!("{context}.{key}");
+        match key.as_str() {
+            "token" => {
+                let value = expect_string(value, &path)?;
+                if value.is_empty() {
+                    return bail(format!("'{path}' must not be empty"));
+                }
+                token = Some(value);
+            }
+            "scope" => {
+                let value = expect_string(value, &path)?;
+                scope = Some(
+                    value
+                        .parse()
+                        .map_err(|err| with_context(err, format!("'{path}'")))?,
+                );
+            }
+            "allow" => allow = Some(expect_webhook_operations(value, &path)?),
+            _ => unknown.key(&path)?,
+        }
+    }
+    let Some(token) = token else {
+        return bail(format!("'{context}' needs a token"));
+    };
+    let Some(scope) = scope else {
+        return bail(format!(
+            "'{context}' needs a scope (read, control, or admin)"
+        ));
+    };
+    Ok(WebhookToken {
+        token,
+        scope,
+        allow,
+    })
+}
+
+fn expect_webhook_operations(value: &Value, path: &str) -> Result<Vec<String>> {
+    let operations = expect_string_array(value, path)?;
+    if let Some(unknown) = operations
+        .iter()
+        .find(|operation| webhook_scope(operation).is_none())
+    {
+        let names: Vec<&str> = WEBHOOK_OPERATIONS.iter().map(|(name, _)| *name).collect();
+        return bail(format!(
+            "'{path}' has unknown operation '{unknown}' (expected {})",
+            names.join(", ")
+        ));
+    }
+    Ok(operations)
+}
+
+/// Events `[notify] events` may list.
+pub const NOTIFY_EVENTS: &[&str] = &["needs_input", "finished", "crashed"];
+
+fn parse_notify(table: &Table, context: &str, unknown: &mut Unknown) -> Result<Notify> {
+    let mut notify = Notify::default();
+    for (key, value) in table {
+        let path = format!("{context}.{key}");
+        match key.as_str() {
+            "slack_webhook" => notify.slack_webhook = Some(expect_string(value, &path)?),
+            "discord_webhook" => notify.discord_webhook = Some(expect_string(value, &path)?),
+            "slack_token" => notify.slack_token = Some(expect_string(value, &path)?),
+            "slack_channel" => notify.slack_channel = Some(expect_string(value, &path)?),
+            "webhook" => notify.webhook = Some(expect_string(value, &path)?),
+            "desktop" => notify.desktop = expect_bool(value, &path)?,
+            "events" => {
+                notify.events = expect_string_array(value, &path)?;
+                if let Some(unknown) = notify
+                    .events
+                    .iter()
+                    .find(|event| !NOTIFY_EVENTS.contains(&event.as_str()))
+                {
+                    return bail(format!(
+                        "'{path}' has unknown event '{unknown}' (expected {})",
+                        NOTIFY_EVENTS.join(", ")
+                    ));
+                }
+            }
+            "tail_lines" => notify.tail_lines = expect_unsigned(value, &path)? as usize,
+            "poll_secs" => notify.poll_secs = expect_unsigned(value, &path)?.max(1),
+            _ => unknown.key(&path)?,
+        }
+    }
+    Ok(notify)
+}
+
+fn parse_keep_alive(table: &Table, context: &str, unknown: &mut Unknown) -> Result<KeepAlive> {
+    let mut keep_alive = KeepAlive::default();
+    for (key, value) in table {
+        let path = format!("{context}.{key}");
+        match key.as_str() {
+            "max_restarts" => keep_alive.max_restarts = expect_unsigned(value, &path)?,
+            "window_minutes" => keep_alive.window_minutes = expect_unsigned(value, &path)?,
+            "notify" => keep_alive.notify = Some(expect_string(value, &path)?),
+            _ => unknown.key(&path)?,
+        }
+    }
+    Ok(keep_alive)
+}
+
+/// A signal name, or an array of them to send in turn, e.g. `["INT", "INT"]`.
+fn expect_signals(value: &Value, path: &str) -> Result<Vec<String>> {
+    let names = match value {
+        Value::String(name) => vec![name.clone()],
+        other => expect_string_array(other, path)?,
+    };
+    if names.is_empty() {
+        return bail(format!("'{path}' must name at least one signal"));
+    }
+    names
+        .iter()
+        .map(|name| {
+            let upper = name.to_ascii_uppercase();
+            let bare = upper.strip_prefix("SIG").unwrap_or(&upper);
+            if STOP_SIGNALS.contains(&bare) {
+                Ok(bare.to_string())
+            } else {
+                bail(format!(
+                    "'{path}': unknown signal '{name}' (expected one of {})",
+                    STOP_SIGNALS.join(", ")
+                ))
+            }
+        })
+        .collect()
+}
+
+fn parse_stop(table: &Table, context: &str, unknown: &mut Unknown) -> Result<Stop> {
+    let mut stop = Stop::default();
+    for (key, value) in table {
+        let path = format!("{context}.{key}");
+        match key.as_str() {
+            "keys" => stop.keys = Some(expect_string(value, &path)?),
+            "signal" => stop.signals = expect_signals(value, &path)?,
+            "grace_secs" => stop.grace_secs = expect_unsigned(value, &path)?,
+            _ => unknown.key(&path)?,
+        }
+    }
+    Ok(stop)
+}
+
+fn parse_paste(table: &Table, context: &str, unknown: &mut Unknown) -> Result<Paste> {
+    let mut paste = Paste::default();
+    for (key, value) in table {
+        let path = format!("{context}.{key}");
+        match key.as_str() {
+            "chunk_bytes" => {
+                let bytes = expect_unsigned(value, &path)?;
+                if bytes == 0 {
+                    return bail(format!("'{path}' must be at least 1"));
+                }
+                paste.chunk_bytes = usize::try_from(bytes).unwrap_or(usize::MAX);
+            }
+            "chunk_delay_ms" => paste.chunk_delay_ms = expect_unsigned(value, &path)?,
+            _ => unknown.key(&path)?,
+        }
+    }
+    Ok(paste)
+}
+
+fn parse_send_guard(table: &Table, context: &str, unknown: &mut Unknown) -> Result<SendGuard> {
+    let mut guard = SendGuard::default();
+    for (key, value) in table {
+        let path = format!("{context}.{key}");
+        match key.as_str() {
+            "exit_copy_mode" => guard.exit_copy_mode = expect_bool(value, &path)?,
+            "refuse_alternate_screen" => {
+                guard.refuse_alternate_screen = expect_bool(value, &path)?;
+            }
+            _ => unknown.key(&path)?,
+        }
+    }
+    Ok(guard)
+}
+
+fn parse_dir_guard(table: &Table, context: &str, unknown: &mut Unknown) -> Result<DirGuard> {
+    let mut guard = DirGuard::default();
+    for (key, value) in table {
+        let path = format!("{context}.{key}");
+        match key.as_str() {
+            "deny" => guard.deny = expect_string_array(value, &path)?,
+            "outside_git" => {
+                guard.outside_git = expect_string(value, &path)?
+                    .parse()
+                    .map_err(|err| with_context(err, format!("'{path}'")))?;
+            }
+            _ => unknown.key(&path)?,
+        }
+    }
+    Ok(guard)
+}
+
+fn parse_snapshots(table: &Table, context: &str, unknown: &mut Unknown) -> Result<Snapshots> {
+    let mut snapshots = Snapshots::default();
+    for (key, value) in table {
+        let path = format!("{context}.{key}");
+        match key.as_str() {
+            "interval_secs" | "keep" => {
+                let number = expect_unsigned(value, &path)?;
+                if number == 0 {
+                    return bail(format!("'{path}' must be at least 1"));
+                }
+                if key == "keep" {
+                    snapshots.keep = usize::try_from(number).unwrap_or(usize::MAX);
+                } else {
+                    snapshots.interval_secs = number;
+                }
+            }
+            _ => unknown.key(&path)?,
+        }
+    }
+    Ok(snapshots)
+}
+
+fn parse_preflight(table: &Table, context: &str, unknown: &mut Unknown) -> Result<Preflight> {
+    let mut preflight = Preflight::default();
+    for (key, value) in table {
+        let path = format!("{context}.{key}");
+        match key.as_str() {
+            "min_free_disk_mb" => preflight.min_free_disk_mb = Some(expect_unsigned(value, &path)?),
+            "command" => preflight.command = Some(expect_string(value, &path)?),
+            _ => unknown.key(&path)?,
+        }
+    }
+    Ok(preflight)
+}
+
+fn parse_transfer(table: &Table, context: &str, unknown: &mut Unknown) -> Result<Transfer> {
+    let mut via = None;
+    let mut target = None;
+    let mut dir = None;
+    for (key, value) in table {
+        let path = format!("{context}.{key}");
+        match key.as_str() {
+            "via" => {
+                let raw = expect_string(value, &path)?;
+                via = Some(
+                    raw.parse()
+                        .map_err(|err| with_context(err, format!("'{path}'")))?,
+                );
+            }
+            "target" => target = Some(expect_string(value, &path)?),
+            "dir" => dir = Some(expect_string(value, &path)?),
+            _ => unknown.key(&path)?,
+        }
+    }
+    let Some(via) = via else {
+        return bail(format!("'{context}' needs via (ssh, docker, or kubectl)"));
+    };
+    let Some(target) = target.filter(|target| !target.is_empty()) else {
+        return bail(format!("'{context}' needs a target"));
+    };
+    Ok(Transfer { via, target, dir })
+}
+
+fn parse_host(table: &Table, context: &str, unknown: &mut Unknown) -> Result<Host> {
+    let mut ssh = None;
+    let mut timeout_secs = 5;
+    let mut amux = "amux".to_string();
+    let mut control_persist_secs = 300;
+    for (key, value) in table {
+        let path = format!("{context}.{key}");
+        match key.as_str() {
+            "ssh" => ssh = Some(expect_string(value, &path)?),
+            "timeout_secs" => timeout_secs = expect_unsigned(value, &path)?.max(1),
+            "amux" => amux = expect_string(value, &path)?,
+            "control_persist_secs" => control_persist_secs = expect_unsigned(value, &path)?,
+            _ => unknown.key(&path)?,
+        }
+    }
+    let Some(ssh) = ssh.filter(|ssh| !ssh.is_empty()) else {
+        return bail(format!("'{context}' needs an ssh destination"));
+    };
+    Ok(Host {
+        ssh,
+        timeout_secs,
+        amux,
+        control_persist_secs,
+    })
+}
+
+/// Every key but `params` is an axis, so there are no unknown keys to report.
+fn parse_matrix(table: &Table, context: &str) -> Result<Matrix> {
+    let mut params = None;
+    let mut axes = BTreeMap::new();
+    for (key, value) in table {
+        let path = format!("{context}.{key}");
+        match key.as_str() {
+            "params" => params = Some(expect_string(value, &path)?),
+            axis => {
+                let values = expect_string_array(value, &path)?;
+                if values.is_empty() {
+                    return bail(format!("'{path}' needs at least one value"));
+                }
+                axes.insert(axis.to_string(), values);
+            }
+        }
+    }
+    let Some(params) = params else {
+        return bail(format!(
+            "'{context}' needs params using its axes, e.g. \"--model {{model}}\""
+        ));
+    };
+    if axes.is_empty() {
+        return bail(format!("'{context}' needs at least one axis of values"));
+    }
+    Ok(Matrix { params, axes })
+}
+
+fn parse_ready(table: &Table, context: &str, unknown: &mut Unknown) -> Result<Readiness> {
+    let mut ready = Readiness::default();
+    for (key, value) in table {
+        let path = format!("{context}.{key}");
+        match key.as_str() {
+            "pattern" => ready.pattern = Some(expect_string(value, &path)?),
+            "command" => ready.command = Some(expect_string(value, &path)?),
+            "timeout_secs" => ready.timeout_secs = Some(expect_unsigned(value, &path)?),
+            _ => unknown.key(&path)?,
+        }
+    }
+    Ok(ready)
+}
+
+fn parse_response(table: &Table, context: &str, unknown: &mut Unknown) -> Result<ResponseConfig> {
+    let mut response = ResponseConfig::default();
+    for (key, value) in table {
+        let path = format!("{context}.{key}");
+        match key.as_str() {
+            "prompt" => response.prompt = Some(expect_string(value, &path)?),
+            "quiet_ms" => response.quiet_ms = Some(expect_unsigned(value, &path)?),
+            _ => unknown.key(&path)?,
+        }
+    }
+    Ok(response)
+}
+
+pub fn expect_table<'a>(value: &'a Value, path: &str) -> Result<&'a Table> {
+    match value {
+        Value::Table(table) => Ok(table),
+        other => bail(format!(
+            "'{path}' must be a table, found {}",
+            other.type_name()
+        )),
+    }
+}
+
+pub fn expect_string(value: &Value, path: &str) -> Result<String> {
+    match value {
+        Value::String(text) => Ok(text.clone()),
+        other => bail(format!(
+            "'{path}' must be a string, found {}",
+            other.type_name()
+        )),
+    }
+}
+
+fn expect_string_array(value: &Value, path: &str) -> Result<Vec<String>> {
+    let Value::Array(items) = value else {
+        return bail(format!(
+            "'{path}' must be an array of strings, found {}",
+            value.type_name()
+        ));
+    };
+    items.iter().map(|item| expect_string(item, path)).collect()
+}
+
+fn expect_bool(value: &Value, path: &str) -> Result<bool> {
+    match value {
+        Value::Boolean(flag) => Ok(*flag),
+        other => bail(format!(
+            "'{path}' must be true or false, found {}",
+            other.type_name()
+        )),
+    }
+}
+
+fn expect_unsigned(value: &Value, path: &str) -> Result<u64> {
+    match value {
+        Value::Integer(number) if *number >= 0 => Ok(*number as u64),
+        Value::Integer(_) => bail(format!("'{path}' must not be negative")),
+        other => bail(format!(
+            "'{path}' must be an integer, found {}",
+            other.type_name()
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_reads_agent_response_settings() {
+        let config = parse("[agents.claude.response]\nprompt = '^> $'\nquiet_ms = 1500\n")
+            .expect("config should parse");
+        let claude = config.agent("claude").expect("claude should be configured");
+        assert_eq!(claude.response.prompt.as_deref(), Some("^> $"));
+        assert_eq!(claude.response.quiet_ms, Some(1500));
+    }
+
+    #[test]
+    fn parse_reads_custom_agent_definitions() {
+        let config = parse(
+            "[agents.reviewer]\ncommand = 'my-agent'\nparams = '--mode review'\ncwd = '/src/api'\n",
+        )
+        .expect("config should parse");
+        let agent = config.agent("reviewer").expect("agent should be read");
+        assert_eq!(agent.command.as_deref(), Some("my-agent"));
+        assert_eq!(agent.params.as_deref(), Some("--mode review"));
+        assert_eq!(
+            config.agent_dir("reviewer"),
+            Some(PathBuf::from("/src/api"))
+        );
+        assert_eq!(config.agent_dir("codex"), None);
+    }
+
+    #[test]
+    fn parse_reads_webhook_settings() {
+        let config = parse("[webhook]\nport = 9000\ntoken = 's3cret'\nallow = ['send']\n")
+            .expect("config should parse");
+        assert_eq!(config.webhook.port, 9000);
+        assert_eq!(config.webhook.token.as_deref(), Some("s3cret"));
+        assert_eq!(config.webhook.allow, ["send"]);
+        assert!(parse("").expect("empty config").webhook.allow.is_empty());
+        assert!(parse("[webhook]\ntoken = ''\n").is_err());
+
+        let err = parse("[webhook]\nallow = ['kill']\n").expect_err("kill is not an operation");
+        assert!(err.to_string().contains("unknown operation 'kill'"));
+        assert!(parse("[webhook]\nport = 70000\n").is_err());
+    }
+
+    #[test]
+    fn parse_skips_unknown_keys_only_in_newer_configs() {
+        let raw = "future = 1\n[agents.codex]\nsandbox = \"strict\"\nicon = \"x\"\n";
+        let err = parse(raw).expect_err("unknown keys are errors in a current config");
+        assert_eq!(err.to_string(), "unknown key 'agents.codex.sandbox'");
+        assert!(parse(&format!("version = 1\n{raw}")).is_err());
+
+        let config = parse(&format!("version = 2\n{raw}")).expect("newer config should parse");
+        assert_eq!(config.warnings.len(), 2);
+        assert!(config.warnings[0].starts_with("ignoring unknown key 'agents.codex.sandbox'"));
+        assert_eq!(config.theme("codex").icon.as_deref(), Some("x"));
+        assert!(parse("version = 0\n").is_err());
+    }
+
+    #[test]
+    fn upgrade_stamps_the_version_and_keeps_the_file_as_written() {
+        let raw = "# review setup\n[groups]\nreview = [\"codex\"]\n";
+        let upgraded = upgrade(raw)
+            .expect("config should upgrade")
+            .expect("an unversioned config needs upgrading");
+        assert_eq!(upgraded, format!("version = 1\n\n{raw}"));
+        assert_eq!(upgrade(&upgraded).expect("config should parse"), None);
+        assert!(upgrade("version = 3\n").is_err());
+    }
+
+    #[test]
+    fn parse_reads_hosts_with_default_timeout() {
+        let config = parse(
+            "[hosts.devbox]\nssh = \"me@devbox\"\n\n[hosts.ci]\nssh = \"ci\"\ntimeout_secs = 2\n\
+             amux = \"~/.cargo/bin/amux\"\ncontrol_persist_secs = 0\n",
+        )
+        .expect("config should parse");
+        let devbox = &config.hosts["devbox"];
+        assert_eq!((devbox.ssh.as_str(), devbox.timeout_secs), ("me@devbox", 5));
+        assert_eq!(devbox.amux, "amux");
+        assert_eq!(devbox.control_persist_secs, 300);
+        assert_eq!(config.hosts["ci"].timeout_secs, 2);
+        assert_eq!(config.hosts["ci"].control_persist_secs, 0);
+        assert_eq!(config.hosts["ci"].amux, "~/.cargo/bin/amux");
+        assert!(parse("[hosts.devbox]\ntimeout_secs = 2\n").is_err());
+    }
+
+    #[test]
+    fn parse_reads_agent_transfer() {
+        let config =
+            parse("[agents.box.transfer]\nvia = 'ssh'\ntarget = 'devbox'\ndir = '/work'\n")
+                .expect("config should parse");
+        let transfer = config.transfer("box").expect("box has a transfer");
+        assert_eq!(transfer.via, TransferVia::Ssh);
+        assert_eq!(transfer.target, "devbox");
+        assert_eq!(transfer.dir.as_deref(), Some("/work"));
+        assert!(config.transfer("codex").is_none());
+
+        assert!(parse("[agents.box.transfer]\nvia = 'ftp'\ntarget = 'x'\n").is_err());
+        let err = parse("[agents.box.transfer]\nvia = 'docker'\n").expect_err("no target");
+        assert!(err.to_string().contains("needs a target"));
+    }
+
+    #[test]
+    fn parse_reads_scoped_webhook_tokens() {
+        let config = parse(
+            "[webhook.tokens.statusbar]\ntoken = 'r0'\nscope = 'read'\n\
+             [webhook.tokens.mcp]\ntoken = 'c0'\nscope = 'control'\nallow = ['status', 'send']\n",
+        )
+        .expect("config should parse");
+        let statusbar = &config.webhook.tokens["statusbar"];
+        assert_eq!(statusbar.scope, Scope::Read);
+        assert_eq!(statusbar.allow, None);
+        let mcp = &config.webhook.tokens["mcp"];
+        assert_eq!(mcp.scope, Scope::Control);
+        assert_eq!(
+            mcp.allow.as_deref(),
+            Some(&["status".into(), "send".into()][..])
+        );
+        assert!(Scope::Read < Scope::Control && Scope::Control < Scope::Admin);
+
+        let err = parse("[webhook.tokens.bar]\ntoken = 'x'\n").expect_err("scope is required");
+        assert!(err.to_string().contains("needs a scope"));
+        assert!(parse("[webhook.tokens.bar]\ntoken = 'x'\nscope = 'root'\n").is_err());
+        assert!(parse("[webhook.tokens.bar]\ntoken = ''\nscope = 'read'\n").is_err());
+    }
+
+    #[test]
+    fn parse_reads_notify_settings() {
+        let config = parse(
+            "[notify]\ndiscord_webhook = 'https://discord.example/hook'\nevents = ['crashed']\n",
+        )
+        .expect("config should parse");
+        assert!(config.notify.wants("crashed"));
+        assert!(!config.notify.wants("finished"));
+        assert_eq!(config.notify.tail_lines, 20);
+
+        let defaults = parse("").expect("empty config");
+        assert!(!defaults.notify.has_sink());
+        assert!(!defaults.notify.wants("crashed"));
+        let err = parse("[notify]\nevents = ['started']\n").expect_err("unknown event");
+        assert!(err.to_string().contains("unknown event 'started'"));
+    }
+
+    #[test]
+    fn parse_reads_auto_name_strategy() {
+        let config =
+            parse("[agents.codex]\nauto_name = \"branch\"\n").expect("config should parse");
+        assert_eq!(
+            config.agent("codex").and_then(|agent| agent.auto_name),
+            Some(AutoName::Branch)
+        );
+        let err = parse("[agents.codex]\nauto_name = \"random\"\n").expect_err("should fail");
+        assert!(err.to_string().contains("agents.codex.auto_name"));
+    }
+
+    #[test]
+    fn auto_detach_minutes_prefer_the_agent_setting() {
+        let config = parse(
+            "auto_detach_minutes = 30\n\n[agents.codex]\nauto_detach_minutes = 5\n\n\
+             [agents.claude]\nauto_detach_minutes = 0\n",
+        )
+        .expect("config should parse");
+        assert_eq!(config.auto_detach_minutes("codex"), Some(5));
+        assert_eq!(config.auto_detach_minutes("gemini"), Some(30));
+        assert_eq!(config.auto_detach_minutes("claude"), None);
+    }
+
+    #[test]
+    fn name_from_branch_prefers_the_agent_setting() {
+        let config = parse(
+            "name_from_branch = true
+
+[agents.claude]
+name_from_branch = false
+",
+        )
+        .expect("config should parse");
+        assert!(config.name_from_branch("codex"));
+        assert!(!config.name_from_branch("claude"));
+        assert!(!Config::default().name_from_branch("codex"));
+    }
+
+    #[test]
+    fn parse_reads_matrices() {
+        let config = parse(
+            "[agents.claude.matrix.models]\nparams = '--model {model}'\nmodel = ['opus', 'sonnet']\n",
+        )
+        .expect("config should parse");
+        let matrix = config
+            .matrix("claude", "models")
+            .expect("matrix should be read");
+        assert_eq!(matrix.params, "--model {model}");
+        assert_eq!(matrix.axes["model"], ["opus", "sonnet"]);
+        assert!(config.matrix("claude", "effort").is_err());
+
+        let err = parse("[agents.claude.matrix.models]\nmodel = ['opus']\n")
+            .expect_err("params are required");
+        assert!(err.to_string().contains("agents.claude.matrix.models"));
+        assert!(parse("[agents.claude.matrix.models]\nparams = ''\nmodel = []\n").is_err());
+    }
+
+    #[test]
+    fn parse_reads_dir_guard_with_defaults() {
+        assert_eq!(Config::default().dir_guard.deny, ["/", "~"]);
+        let config = parse("[dir_guard]\ndeny = ['~/Documents/**']\noutside_git = 'refuse'\n")
+            .expect("config should parse");
+        assert_eq!(config.dir_guard.deny, ["~/Documents/**"]);
+        assert_eq!(config.dir_guard.outside_git, OutsideGit::Refuse);
+        let err = parse("[dir_guard]\noutside_git = 'maybe'\n").expect_err("should fail");
+        assert!(err.to_string().contains("dir_guard.outside_git"));
+    }
+
+    #[test]
+    fn parse_reads_snapshots_and_rejects_zero() {
+        assert_eq!(Config::default().snapshots, None);
+        let config = parse("[snapshots]\nkeep = 3\n").expect("config should parse");
+        assert_eq!(
+            config.snapshots,
+            Some(Snapshots {
+                interval_secs: 60,
+                keep: 3,
+            })
+        );
+        let err = parse("[snapshots]\ninterval_secs = 0\n").expect_err("should fail");
+        assert!(err.to_string().contains("snapshots.interval_secs"));
+    }
+
+    #[test]
+    fn parse_reads_paste_settings() {
+        let config =
+            parse("[agents.codex.paste]\nchunk_bytes = 1024\n").expect("config should parse");
+        assert_eq!(
+            config.paste("codex"),
+            Paste {
+                chunk_bytes: 1024,
+                chunk_delay_ms: 30,
+            }
+        );
+        assert_eq!(config.paste("claude"), Paste::default());
+        assert!(parse("[agents.codex.paste]\nchunk_bytes = 0\n").is_err());
+    }
+
+    #[test]
+    fn parse_reads_send_guard() {
+        let config =
+            parse("[agents.claude.send]\nexit_copy_mode = true\n").expect("config should parse");
+        assert_eq!(
+            config.send_guard("claude"),
+            SendGuard {
+                exit_copy_mode: true,
+                refuse_alternate_screen: false,
+            }
+        );
+        let err = parse("[agents.claude.send]\nexit_copy_mode = \"yes\"\n")
+            .expect_err("a string is not a flag");
+        assert!(err.to_string().contains("must be true or false"));
+    }
+
+    #[test]
+    fn parse_reads_stop_with_defaults() {
+        let config = parse(
+            "[agents.claude.stop]\nkeys = \"C-c C-c\"\n\n[agents.aider.stop]\nsignal = [\"SIGINT\", \"int\"]\ngrace_secs = 3\n",
+        )
+        .expect("config should parse");
+        assert_eq!(
+            config.stop("claude"),
+            Stop {
+                keys: Some("C-c C-c".into()),
+                signals: Vec::new(),
+                grace_secs: 10,
+            }
+        );
+        assert_eq!(
+            config.configured_stop("aider").expect("stop table").signals,
+            ["INT", "INT"]
+        );
+        assert_eq!(config.stop("codex"), Stop::default());
+        assert_eq!(config.configured_stop("codex"), None);
+        let err = parse("[agents.x.stop]\nsignal = \"STOP\"\n").expect_err("unknown signal");
+        assert!(err.to_string().contains("unknown signal 'STOP'"));
+    }
+
+    #[test]
+    fn parse_reads_keep_alive_with_defaults() {
+        let config = parse("[agents.codex.keep_alive]\nmax_restarts = 2\n\n[agents.claude]\n")
+            .expect("config should parse");
+        assert_eq!(
+            config
+                .agent("codex")
+                .and_then(|agent| agent.keep_alive.clone()),
+            Some(KeepAlive {
+                max_restarts: 2,
+                window_minutes: 10,
+                notify: None,
+            })
+        );
+        assert_eq!(
+            config
+                .agent("claude")
+                .and_then(|agent| agent.keep_alive.clone()),
+            None
+        );
+    }
+
+    #[test]
+    fn attach_hooks_prefer_agent_settings() {
+        let config = parse(
+            "[attach_hooks]\npre_attach = \"profile agent\"\npost_detach = \"profile default\"\n\n[agents.claude.attach_hooks]\npre_attach = \"profile claude\"\n",
+        )
+        .expect("config should parse");
+        assert_eq!(
+            config.attach_hooks("claude"),
+            AttachHooks {
+                pre_attach: Some("profile claude".into()),
+                post_detach: Some("profile default".into()),
+            }
+        );
+        assert_eq!(config.attach_hooks("codex"), config.attach_hooks);
+        assert!(parse("[attach_hooks]\non_start = \"x\"\n").is_err());
+    }
+
+    #[test]
+    fn parse_reads_agent_theme() {
+        let config = parse("[agents.claude]\ncolor = \"#d97757\"\nicon = \"✻\"\n")
+            .expect("config should parse");
+        let theme = config.theme("claude");
+        assert_eq!(
+            theme.color.map(|color| color.to_string()).as_deref(),
+            Some("#d97757")
+        );
+        assert_eq!(theme.icon.as_deref(), Some("✻"));
+        assert!(config.theme("codex").is_empty());
+        let err = parse("[agents.codex]\ncolor = \"mauve\"\n").expect_err("should fail");
+        assert!(err.to_string().contains("agents.codex.color"));
+    }
+
+    #[test]
+    fn parse_reads_groups() {
+        let config = parse("[groups]\nreview = [\"codex\", \"claude:second\"]\n")
+            .expect("config should parse");
+        let members = config.group("review").expect("group should exist");
+        assert_eq!(members[0], GroupMember::parse("codex"));
+        assert_eq!(members[1].agent, "claude");
+        assert_eq!(members[1].name.as_deref(), Some("second"));
+        assert!(config.group("missing").is_err());
+    }
+
+    #[test]
+    fn parse_reads_templates() {
+        let config = parse("templates.review = \"Review the diff:\\n{stdin}\"\n")
+            .expect("config should parse");
+        assert_eq!(
+            config.template("review").expect("template should exist"),
+            "Review the diff:\n{stdin}"
+        );
+        assert!(config.template("missing").is_err());
+    }
+
+    #[test]
+    fn parse_rejects_unknown_keys() {
+        let err = parse("[agents.claude]\nbogus = 1\n").expect_err("unknown key should fail");
+        assert!(err.to_string().contains("agents.claude.bogus"));
+    }
+
+    #[test]
+    fn parse_rejects_wrong_types() {
+        let err = parse("[agents.claude.response]\nquiet_ms = \"soon\"\n")
+            .expect_err("string quiet_ms should fail");
+        assert!(err.to_string().contains("must be an integer"));
+    }
+}
@@ -0,0 +1,339 @@
+//! `amux dispatch`: hand tasks from a file to a pool of agent sessions, one at
+//! a time per session, and keep track of where each went and whether the agent
+//! has finished it.
+//!
+//! A task is done when its agent finishes responding, by the same rule as
+//! `wait --for-response`. Progress is rewritten to `dispatch.json` in the state
+//! directory at every step, so `amux dispatch --status` can follow a run from
+//! another terminal.
+
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use crate::error::Result;
+use crate::input;
+use crate::json::{self, Value};
+use crate::paths;
+use crate::response::{self, Boundary};
+use crate::state;
+use crate::time::{format_duration, unix_now};
+use crate::tmux;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TaskState {
+    Pending,
+    Running,
+    Done,
+    Failed,
+}
+
+impl TaskState {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Running => "running",
+            Self::Done => "done",
+            Self::Failed => "failed",
+        }
+    }
+
+    fn parse(raw: &str) -> Option<Self> {
+        match raw {
+            "pending" => Some(Self::Pending),
+            "running" => Some(Self::Running),
+            "done" => Some(Self::Done),
+            "failed" => Some(Self::Failed),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Task {
+    pub text: String,
+    pub state: TaskState,
+    pub session: Option<String>,
+    pub started: Option<u64>,
+    pub finished: Option<u64>,
+    /// Why a failed task failed.
+    pub error: Option<String>,
+}
+
+impl Task {
+    fn to_json(&self) -> Value {
+        json::object([
+            ("text", Value::from(self.text.as_str())),
+            ("state", Value::from(self.state.as_str())),
+            ("session", Value::from(self.session.clone())),
+            ("started_at", Value::from(self.started)),
+            ("finished_at", Value::from(self.finished)),
+            ("error", Value::from(self.error.clone())),
+        ])
+    }
+
+    fn from_json(value: &Value) -> Option<Self> {
+        let text = |key: &str| value.get(key).and_then(Value::as_str).map(str::to_string);
+        Some(Self {
+            text: text("text")?,
+            state: TaskState::parse(value.get("state")?.as_str()?)?,
+            session: text("session"),
+            started: value.get("started_at").and_then(Value::as_u64),
+            finished: value.get("finished_at").and_then(Value::as_u64),
+            error: text("error"),
+        })
+    }
+}
+
+/// One dispatch run: where its tasks came from and how far each has got.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Progress {
+    pub source: String,
+    pub started: u64,
+    pub tasks: Vec<Task>,
+}
+
+impl Progress {
+    pub fn new(source: &str, texts: Vec<String>) -> Self {
+        Self {
+            source: source.to_string(),
+            started: unix_now(),
+            tasks: texts
+                .into_iter()
+                .map(|text| Task {
+                    text,
+                    state: TaskState::Pending,
+                    session: None,
+                    started: None,
+                    finished: None,
+                    error: None,
+                })
+                .collect(),
+        }
+    }
+
+    pub fn count(&self, state: TaskState) -> usize {
+        self.tasks.iter().filter(|task| task.state == state).count()
+    }
+
+    /// e.g. `3/10 done, 2 running, 5 pending`; states with no tasks are left out.
+    pub fn summary(&self) -> String {
+        let mut parts = vec![format!(
+            "{}/{} done",
+            self.count(TaskState::Done),
+            self.tasks.len()
+        )];
+        for state in [TaskState::Running, TaskState::Pending, TaskState::Failed] {
+            let count = self.count(state);
+            if count > 0 {
+                parts.push(format!("{count} {}", state.as_str()));
+            }
+        }
+        parts.join(", ")
+    }
+
+    fn to_json(&self) -> Value {
+        json::object([
+            ("source", Value::from(self.source.as_str())),
+            ("started_at", Value::from(self.started)),
+            (
+                "tasks",
+                Value::Array(self.tasks.iter().map(Task::to_json).collect()),
+            ),
+        ])
+    }
+
+    fn from_json(value: &Value) -> Option<Self> {
+        Some(Self {
+            source: value.get("source")?.as_str()?.to_string(),
+            started: value.get("started_at")?.as_u64()?,
+            tasks: value
+                .get("tasks")?
+                .as_array()?
+                .iter()
+                .map(Task::from_json)
+                .collect::<Option<_>>()?,
+        })
+    }
+}
+
+/// Tasks in a task file: each non-empty line, or with `blocks` each run of
+/// lines separated by blank lines. Lines starting with `#` are comments.
+pub fn parse_tasks(raw: &str, blocks: bool) -> Vec<String> {
+    let lines = raw
+        .lines()
+        .filter(|line| !line.trim_start().starts_with('#'));
+    if !blocks {
+        return lines
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(str::to_string)
+            .collect();
+    }
+    let mut tasks = Vec::new();
+    let mut current: Vec<&str> = Vec::new();
+    for line in lines.chain([""]) {
+        if line.trim().is_empty() {
+            if !current.is_empty() {
+                tasks.push(current.join("\n"));
+                current.clear();
+            }
+        } else {
+            current.push(line.trim_end());
+        }
+    }
+    tasks
+}
+
+/// A session in the pool, with the rule that says its agent is done.
+pub struct Worker {
+    pub session: String,
+    pub boundary: Boundary,
+    pub input: input::Settings,
+}
+
+/// Something that happened to task `index` (0-based).
+pub enum Event<'a> {
+    Assigned { index: usize, session: &'a str },
+    Finished { index: usize, task: &'a Task },
+}
+
+/// Feed every pending task to the first idle worker until none are left.
+/// A worker whose task fails (its session vanished, or the agent never
+/// finished within `timeout`) takes no more tasks.
+pub fn run(
+    progress: Progress,
+    workers: &[Worker],
+    timeout: Option<Duration>,
+    on_event: impl Fn(Event) + Sync,
+) -> Result<Progress> {
+    save(&progress)?;
+    let progress = Mutex::new(progress);
+    let results: Vec<Result<()>> = thread::scope(|scope| {
+        let handles: Vec<_> = workers
+            .iter()
+            .map(|worker| {
+                let (progress, on_event) = (&progress, &on_event);
+                scope.spawn(move || work(worker, progress, timeout, on_event))
+            })
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().expect("dispatch worker panicked"))
+            .collect()
+    });
+    results.into_iter().collect::<Result<()>>()?;
+    Ok(progress.into_inner().unwrap_or_else(|err| err.into_inner()))
+}
+
+fn work(
+    worker: &Worker,
+    progress: &Mutex<Progress>,
+    timeout: Option<Duration>,
+    on_event: &(impl Fn(Event) + Sync),
+) -> Result<()> {
+    loop {
+        let (index, text) = {
+            let mut progress = progress.lock().expect("dispatch progress poisoned");
+            let Some(index) = progress
+                .tasks
+                .iter()
+                .position(|task| task.state == TaskState::Pending)
+            else {
+                return Ok(());
+            };
+            let task = &mut progress.tasks[index];
+            task.state = TaskState::Running;
+            task.session = Some(worker.session.clone());
+            task.started = Some(unix_now());
+            let text = task.text.clone();
+            save(&progress)?;
+            (index, text)
+        };
+        on_event(Event::Assigned {
+            index,
+            session: &worker.session,
+        });
+
+        let error = match deliver(worker, &text, timeout) {
+            Ok(true) => None,
+            Ok(false) => Some(format!(
+                "no response within {}",
+                format_duration(timeout.unwrap_or_default().as_secs())
+            )),
+            Err(err) => Some(err.to_string()),
+        };
+        let failed = error.is_some();
+        {
+            let mut progress = progress.lock().expect("dispatch progress poisoned");
+            let task = &mut progress.tasks[index];
+            task.state = if failed {
+                TaskState::Failed
+            } else {
+                TaskState::Done
+            };
+            task.finished = Some(unix_now());
+            task.error = error;
+            save(&progress)?;
+            on_event(Event::Finished {
+                index,
+                task: &progress.tasks[index],
+            });
+        }
+        if failed {
+            return Ok(());
+        }
+    }
+}
+
+/// Type `text` into the worker's agent and wait for it to finish responding.
+fn deliver(worker: &Worker, text: &str, timeout: Option<Duration>) -> Result<bool> {
+    input::send(&tmux::agent_pane(&worker.session)?, text, &worker.input)?;
+    response::wait_for_response(&worker.session, &worker.boundary, timeout)
+}
+
+const SCHEMA: state::Schema = state::Schema {
+    what: "dispatch progress",
+    version: 1,
+    migrate: state::unchanged,
+};
+
+pub fn save(progress: &Progress) -> Result<()> {
+    state::write(&paths::dispatch_file()?, &SCHEMA, progress.to_json())
+}
+
+/// The most recent run, if any.
+pub fn load() -> Result<Option<Progress>> {
+    Ok(state::read(&paths::dispatch_file()?, &SCHEMA)?
+        .and_then(|value| Progress::from_json(&value)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_tasks_by_line_or_block() {
+        let raw = "# tasks\nFix login\n\nAdd tests\nfor the parser\n\n\n";
+        assert_eq!(
+            parse_tasks(raw, false),
+            vec!["Fix login", "Add tests", "for the parser"]
+        );
+        assert_eq!(
+            parse_tasks(raw, true),
+            vec!["Fix login", "Add tests\nfor the parser"]
+        );
+    }
+
+    #[test]
+    fn progress_round_trips_and_summarizes() {
+        let mut progress = Progress::new("tasks.txt", vec!["a".into(), "b".into(), "c".into()]);
+        progress.tasks[0].state = TaskState::Done;
+        progress.tasks[0].session = Some("amux-codex--dispatch-1".into());
+        progress.tasks[1].state = TaskState::Running;
+        assert_eq!(progress.summary(), "1/3 done, 1 running, 1 pending");
+
+        let value = json::parse(&progress.to_json().to_string()).expect("json should parse");
+        assert_eq!(Progress::from_json(&value), Some(progress));
+    }
+}
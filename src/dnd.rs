@@ -0,0 +1,87 @@
+//! `amux dnd`: mute a session you are deliberately letting run for a long
+//! time. A muted session posts no notifications, its keep-alive `notify`
+//! command does not run, and `amux here` does not offer to attach to it. The
+//! mute lasts until it is lifted or, when given a duration, until it expires.
+
+use std::fmt;
+
+use crate::error::Result;
+use crate::time::{format_duration, unix_now};
+use crate::tmux;
+
+/// How long a session stays muted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mute {
+    /// Until this unix time.
+    Until(u64),
+    Indefinitely,
+}
+
+impl Mute {
+    /// `for 1h 20m`, or `until lifted`.
+    pub fn remaining(self, now: u64) -> String {
+        match self {
+            Self::Until(until) => format!("for {}", format_duration(until.saturating_sub(now))),
+            Self::Indefinitely => "until lifted".to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Mute {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Until(until) => write!(f, "{until}"),
+            Self::Indefinitely => write!(f, "on"),
+        }
+    }
+}
+
+/// Mute `session`, for `seconds` or until lifted.
+pub fn set(session: &str, seconds: Option<u64>) -> Result<Mute> {
+    let mute = match seconds {
+        Some(seconds) => Mute::Until(unix_now().saturating_add(seconds)),
+        None => Mute::Indefinitely,
+    };
+    tmux::set_dnd(session, Some(&mute.to_string()))?;
+    Ok(mute)
+}
+
+pub fn clear(session: &str) -> Result<()> {
+    tmux::set_dnd(session, None)
+}
+
+/// The mute on `session`, if one is set and has not expired. A session that
+/// cannot be read counts as not muted.
+pub fn active(session: &str) -> Option<Mute> {
+    let raw = tmux::dnd(session).ok()??;
+    parse(&raw, unix_now())
+}
+
+fn parse(raw: &str, now: u64) -> Option<Mute> {
+    match raw {
+        "on" => Some(Mute::Indefinitely),
+        until => {
+            let until: u64 = until.parse().ok()?;
+            (until > now).then_some(Mute::Until(until))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_drops_expired_mutes() {
+        assert_eq!(parse("on", 100), Some(Mute::Indefinitely));
+        assert_eq!(parse("160", 100), Some(Mute::Until(160)));
+        assert_eq!(parse("100", 100), None);
+        assert_eq!(parse("soon", 100), None);
+    }
+
+    #[test]
+    fn remaining_counts_down_to_the_end() {
+        assert_eq!(Mute::Until(7_300).remaining(100), "for 2h 0m");
+        assert_eq!(Mute::Indefinitely.remaining(100), "until lifted");
+    }
+}
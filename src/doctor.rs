@@ -0,0 +1,138 @@
+//! `amux doctor`: look for settings known to make agent CLIs render badly
+//! inside tmux, such as a TERM that tmux does not emulate, a TERM without a
+//! terminfo entry, or a locale without UTF-8, and name the config setting
+//! (`term` or `env_fixups`) that fixes each.
+
+use std::process::{Command, Stdio};
+
+use crate::session_env::{is_utf8_locale, LOCALE_VARS};
+
+/// What a new session of an agent would run with.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Setup {
+    pub agent: String,
+    pub term: Option<String>,
+    /// The locale variable that decides the character set, and its value.
+    pub locale: Option<(String, String)>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Problem {
+    pub message: String,
+    pub fix: String,
+}
+
+/// The first non-empty locale variable `lookup` knows, most specific first.
+pub fn locale(lookup: impl Fn(&str) -> Option<String>) -> Option<(String, String)> {
+    LOCALE_VARS.iter().find_map(|name| {
+        lookup(name)
+            .filter(|value| !value.is_empty())
+            .map(|value| (name.to_string(), value))
+    })
+}
+
+/// Everything wrong with `setup`. `has_terminfo` says whether a TERM has an
+/// entry on this machine, or `None` when that cannot be checked.
+pub fn check(setup: &Setup, has_terminfo: impl Fn(&str) -> Option<bool>) -> Vec<Problem> {
+    let agent = &setup.agent;
+    let set_term = |term: &str| format!("set term = \"{term}\" under [agents.{agent}]");
+    let mut problems = Vec::new();
+    match setup.term.as_deref() {
+        None | Some("") | Some("dumb") => problems.push(Problem {
+            message: "TERM is unset or dumb: agents print without colors or cursor movement"
+                .to_string(),
+            fix: set_term("tmux-256color"),
+        }),
+        Some(term) => {
+            if !term.starts_with("tmux") && !term.starts_with("screen") {
+                problems.push(Problem {
+                    message: format!(
+                        "TERM={term} inside tmux: tmux does not emulate that terminal, so redraws and colors can break"
+                    ),
+                    fix: set_term("tmux-256color"),
+                });
+            }
+            if has_terminfo(term) == Some(false) {
+                problems.push(Problem {
+                    message: format!(
+                        "TERM={term} has no terminfo entry here: agents fall back to plain output"
+                    ),
+                    fix: set_term("screen-256color"),
+                });
+            }
+        }
+    }
+    match &setup.locale {
+        Some((_, value)) if is_utf8_locale(value) => {}
+        Some((name, value)) => problems.push(Problem {
+            message: format!(
+                "{name}={value} is not UTF-8: box drawing and emoji show up as '?' or garbage"
+            ),
+            fix: format!("add {name} = \"en_US.UTF-8\" to env_fixups under [agents.{agent}]"),
+        }),
+        None => problems.push(Problem {
+            message: "no locale is set (LANG, LC_CTYPE, LC_ALL): agents assume ASCII".to_string(),
+            fix: format!("add LANG = \"en_US.UTF-8\" to env_fixups under [agents.{agent}]"),
+        }),
+    }
+    problems
+}
+
+/// Whether `term` has a terminfo entry, or `None` without `infocmp`.
+pub fn has_terminfo(term: &str) -> Option<bool> {
+    Command::new("infocmp")
+        .arg(term)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .ok()
+        .map(|status| status.success())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn setup(term: &str, locale: Option<(&str, &str)>) -> Setup {
+        Setup {
+            agent: "codex".into(),
+            term: Some(term.into()),
+            locale: locale.map(|(name, value)| (name.into(), value.into())),
+        }
+    }
+
+    #[test]
+    fn check_accepts_tmux_terms_with_a_utf8_locale() {
+        let good = setup("tmux-256color", Some(("LANG", "en_US.UTF-8")));
+        assert_eq!(check(&good, |_| Some(true)), Vec::new());
+        let unknown = setup("screen-256color", Some(("LC_ALL", "C.utf8")));
+        assert_eq!(check(&unknown, |_| None), Vec::new());
+    }
+
+    #[test]
+    fn check_flags_foreign_terms_missing_terminfo_and_ascii_locales() {
+        let bad = setup("xterm-kitty", Some(("LC_ALL", "C")));
+        let problems = check(&bad, |_| Some(false));
+        assert_eq!(problems.len(), 3);
+        assert!(problems[0]
+            .message
+            .starts_with("TERM=xterm-kitty inside tmux"));
+        assert!(problems[1].fix.contains("screen-256color"));
+        assert_eq!(
+            problems[2].fix,
+            "add LC_ALL = \"en_US.UTF-8\" to env_fixups under [agents.codex]"
+        );
+    }
+
+    #[test]
+    fn locale_prefers_the_most_specific_variable() {
+        let vars = |name: &str| match name {
+            "LC_ALL" => Some(String::new()),
+            "LC_CTYPE" => Some("C".into()),
+            "LANG" => Some("en_US.UTF-8".into()),
+            _ => None,
+        };
+        assert_eq!(locale(vars), Some(("LC_CTYPE".into(), "C".into())));
+        assert_eq!(locale(|_| None), None);
+    }
+}
@@ -1,18 +1,78 @@
 use std::error::Error;
 use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::json::{self, Value};
+
+/// Stable, machine-readable category of a failure, reported by `--json-errors`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// The requested agent session does not exist.
+    SessionNotFound,
+    /// The session name is taken by a session amux cannot reuse.
+    SessionConflict,
+    /// The session is pinned and the operation would remove it.
+    SessionPinned,
+    /// A command-line argument or identifier is malformed.
+    InvalidInput,
+    /// The config file is unreadable or invalid, or lacks a referenced entry.
+    Config,
+    /// A tmux command failed.
+    Tmux,
+    /// tmux is not installed.
+    TmuxNotFound,
+    /// A wait ran out of time.
+    Timeout,
+    /// A pre-start check (free disk, quota command) refused to launch the agent.
+    PreflightFailed,
+    Other,
+}
+
+impl ErrorKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::SessionNotFound => "session_not_found",
+            Self::SessionConflict => "session_conflict",
+            Self::SessionPinned => "session_pinned",
+            Self::InvalidInput => "invalid_input",
+            Self::Config => "config",
+            Self::Tmux => "tmux",
+            Self::TmuxNotFound => "tmux_not_found",
+            Self::Timeout => "timeout",
+            Self::PreflightFailed => "preflight_failed",
+            Self::Other => "other",
+        }
+    }
+}
 
 #[derive(Debug)]
-pub struct AmuxError(String);
+pub struct AmuxError {
+    kind: ErrorKind,
+    message: String,
+    hint: Option<String>,
+}
 
 impl AmuxError {
     pub fn new(message: impl Into<String>) -> Self {
-        Self(message.into())
+        Self {
+            kind: ErrorKind::Other,
+            message: message.into(),
+            hint: None,
+        }
+    }
+
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+
+    pub fn hint(&self) -> Option<&str> {
+        self.hint.as_deref()
     }
 }
 
 impl fmt::Display for AmuxError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self.0)
+        write!(f, "{}", self.message)
     }
 }
 
@@ -30,7 +90,104 @@ pub fn bail<T>(msg: impl Into<String>) -> Result<T> {
     Err(fail(msg))
 }
 
+/// An error of a specific kind, optionally with a suggestion for the user.
+pub fn fail_with(kind: ErrorKind, msg: impl Into<String>, hint: Option<String>) -> DynError {
+    Box::new(AmuxError {
+        kind,
+        message: msg.into(),
+        hint,
+    })
+}
+
+/// Prefix `err` with `msg`, keeping its kind and hint.
 pub fn with_context(err: impl Into<DynError>, msg: impl Into<String>) -> DynError {
     let err = err.into();
-    fail(format!("{}: {}", msg.into(), err))
+    let message = format!("{}: {}", msg.into(), err);
+    match err.downcast_ref::<AmuxError>() {
+        Some(inner) => fail_with(inner.kind, message, inner.hint.clone()),
+        None => fail(message),
+    }
+}
+
+/// Re-tag `err` with `kind` unless it already carries a more specific one.
+pub fn with_kind(err: DynError, kind: ErrorKind) -> DynError {
+    match err.downcast_ref::<AmuxError>() {
+        Some(inner) if inner.kind != ErrorKind::Other => err,
+        Some(inner) => fail_with(kind, inner.message.clone(), inner.hint.clone()),
+        None => fail_with(kind, err.to_string(), None),
+    }
+}
+
+pub fn kind_of(err: &(dyn Error + 'static)) -> ErrorKind {
+    if err.is::<crate::tmux::TransientError>() {
+        return ErrorKind::Tmux;
+    }
+    err.downcast_ref::<AmuxError>()
+        .map_or(ErrorKind::Other, AmuxError::kind)
+}
+
+static JSON_ERRORS: AtomicBool = AtomicBool::new(false);
+
+/// Report errors as JSON on stderr instead of text (`--json-errors`).
+pub fn set_json_errors(enabled: bool) {
+    JSON_ERRORS.store(enabled, Ordering::Relaxed);
+}
+
+/// Print `err` to stderr in the selected format.
+pub fn report(err: &(dyn Error + 'static)) {
+    let hint = err.downcast_ref::<AmuxError>().and_then(AmuxError::hint);
+    if JSON_ERRORS.load(Ordering::Relaxed) {
+        eprintln!("{}", to_json(kind_of(err), &err.to_string(), hint));
+    } else {
+        eprintln!("amux: {err}");
+        if let Some(hint) = hint {
+            eprintln!("amux: hint: {hint}");
+        }
+    }
+}
+
+fn to_json(kind: ErrorKind, message: &str, hint: Option<&str>) -> Value {
+    json::object([(
+        "error",
+        json::object([
+            ("kind", Value::from(kind.as_str())),
+            ("message", Value::from(message)),
+            ("hint", Value::from(hint)),
+        ]),
+    )])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn with_context_keeps_kind_and_hint() {
+        let err = fail_with(
+            ErrorKind::SessionNotFound,
+            "no session",
+            Some("start it".into()),
+        );
+        let err = with_context(err, "codex");
+        assert_eq!(err.to_string(), "codex: no session");
+        assert_eq!(kind_of(err.as_ref()), ErrorKind::SessionNotFound);
+        let hint = err.downcast_ref::<AmuxError>().and_then(AmuxError::hint);
+        assert_eq!(hint, Some("start it"));
+    }
+
+    #[test]
+    fn with_kind_does_not_override_specific_kinds() {
+        let err = with_kind(fail("bad"), ErrorKind::Config);
+        assert_eq!(kind_of(err.as_ref()), ErrorKind::Config);
+        let err = with_kind(err, ErrorKind::Tmux);
+        assert_eq!(kind_of(err.as_ref()), ErrorKind::Config);
+    }
+
+    #[test]
+    fn to_json_nests_error_fields() {
+        assert_eq!(
+            to_json(ErrorKind::Timeout, "timed out", None).to_string(),
+            r#"{"error":{"hint":null,"kind":"timeout","message":"timed out"}}"#
+        );
+    }
 }
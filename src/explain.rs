@@ -0,0 +1,100 @@
+//! `amux explain-cmd`: show how a `--cmd`/`--params` string is tokenized and
+//! point out quoting that probably does not do what was intended.
+//!
+//! amux never runs agent commands through a shell; tmux executes the tokens
+//! directly, so shell syntax inside a token is passed through literally.
+
+/// Likely mistakes in `tokens`, one human-readable line each.
+pub fn pitfalls(tokens: &[String]) -> Vec<String> {
+    let mut warnings = Vec::new();
+
+    if let Some(first) = tokens.first() {
+        if is_env_assignment(first) {
+            warnings.push(format!(
+                "token 0 `{first}` looks like an environment assignment, but it will be run as the program; set it under [agents.NAME] env instead"
+            ));
+        }
+    }
+
+    for (index, token) in tokens.iter().enumerate() {
+        if token.contains('"') || token.contains('\'') {
+            warnings.push(format!(
+                "token {index} contains literal quote characters; nested quotes were kept as text"
+            ));
+        }
+        if let Some(operator) = shell_operator(token) {
+            warnings.push(format!(
+                "token {index} `{token}` is shell syntax ({operator}); it is passed to the program as an argument, not interpreted (use --raw-cmd to run through sh -c)"
+            ));
+        }
+        if token.contains('$') && shell_operator(token).is_none() {
+            warnings.push(format!(
+                "token {index} contains `$`; variables are not expanded when amux starts the command"
+            ));
+        }
+        if token.starts_with('~') {
+            warnings.push(format!(
+                "token {index} starts with `~`; home directories are not expanded, use {{cwd}} or an absolute path"
+            ));
+        }
+        if token.contains('*') {
+            warnings.push(format!(
+                "token {index} contains a glob character; globs are not expanded"
+            ));
+        }
+    }
+
+    warnings
+}
+
+fn is_env_assignment(token: &str) -> bool {
+    match token.split_once('=') {
+        Some((name, _)) => {
+            !name.is_empty()
+                && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+                && !name.starts_with(|c: char| c.is_ascii_digit())
+        }
+        None => false,
+    }
+}
+
+fn shell_operator(token: &str) -> Option<&'static str> {
+    match token {
+        "|" | "|&" => Some("pipe"),
+        ">" | ">>" | "<" | "2>" | "2>&1" | "&>" => Some("redirect"),
+        "&&" | "||" | ";" => Some("command list"),
+        "&" => Some("background"),
+        _ if token.starts_with("$(") || token.starts_with('`') => Some("command substitution"),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tokens(raw: &[&str]) -> Vec<String> {
+        raw.iter().map(|token| token.to_string()).collect()
+    }
+
+    #[test]
+    fn pitfalls_flags_shell_syntax_and_env_assignments() {
+        let warnings = pitfalls(&tokens(&["DEBUG=1", "agent", "|", "tee", "~/log"]));
+        assert_eq!(warnings.len(), 3);
+        assert!(warnings[0].contains("environment assignment"));
+        assert!(warnings[1].contains("pipe"));
+        assert!(warnings[2].contains("`~`"));
+    }
+
+    #[test]
+    fn pitfalls_flags_leftover_quotes() {
+        let warnings = pitfalls(&tokens(&["codex", "--mode \"review\""]));
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("token 1"));
+    }
+
+    #[test]
+    fn pitfalls_accepts_plain_commands() {
+        assert!(pitfalls(&tokens(&["codex", "--model", "o3", "--dir={cwd}"])).is_empty());
+    }
+}
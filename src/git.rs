@@ -0,0 +1,239 @@
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use crate::error::{bail, with_context, Result};
+
+/// Current branch name in `dir`, or `None` outside a repository or on a detached HEAD.
+pub fn branch(dir: &Path) -> Result<Option<String>> {
+    let branch = git_output(dir, &["rev-parse", "--abbrev-ref", "HEAD"])?;
+    Ok(branch.filter(|branch| branch != "HEAD"))
+}
+
+/// Top-level directory of the repository containing `dir`.
+pub fn toplevel(dir: &Path) -> Result<Option<PathBuf>> {
+    Ok(git_output(dir, &["rev-parse", "--show-toplevel"])?.map(PathBuf::from))
+}
+
+/// Commit checked out in `dir`, or `None` outside a repository or before the
+/// first commit.
+pub fn head(dir: &Path) -> Result<Option<String>> {
+    git_output(dir, &["rev-parse", "--verify", "--quiet", "HEAD"])
+}
+
+/// How far a working tree has moved since a session started on `base`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Drift {
+    /// Commits made on top of `base`.
+    pub commits: u32,
+    /// Files that differ from `base`, committed or not, plus untracked ones.
+    pub files: usize,
+}
+
+impl Drift {
+    /// Compact summary such as `2 commits, 5 files` or `no changes`.
+    pub fn summary(&self) -> String {
+        let plural = |count: usize, noun: &str| {
+            format!("{count} {noun}{}", if count == 1 { "" } else { "s" })
+        };
+        let mut parts = Vec::new();
+        if self.commits > 0 {
+            parts.push(plural(self.commits as usize, "commit"));
+        }
+        if self.files > 0 {
+            parts.push(plural(self.files, "file"));
+        }
+        if parts.is_empty() {
+            "no changes".to_string()
+        } else {
+            parts.join(", ")
+        }
+    }
+}
+
+/// Drift of `dir` from `base`, or `None` when `base` is not a commit there
+/// (another repository, or history rewritten since).
+pub fn drift(dir: &Path, base: &str) -> Result<Option<Drift>> {
+    let range = format!("{base}..HEAD");
+    let Some(commits) = git_output(dir, &["rev-list", "--count", &range])? else {
+        return Ok(None);
+    };
+    let changed = git_output(dir, &["diff", "--name-only", base, "--"])?.unwrap_or_default();
+    let untracked =
+        git_output(dir, &["ls-files", "--others", "--exclude-standard"])?.unwrap_or_default();
+    Ok(Some(Drift {
+        commits: commits.parse().unwrap_or_default(),
+        files: count_files(&changed, &untracked),
+    }))
+}
+
+fn count_files(changed: &str, untracked: &str) -> usize {
+    changed
+        .lines()
+        .chain(untracked.lines())
+        .filter(|line| !line.is_empty())
+        .collect::<std::collections::BTreeSet<_>>()
+        .len()
+}
+
+/// Uncommitted changes and divergence from upstream for a working tree.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct WorkTreeStatus {
+    /// Changed, staged, or untracked paths.
+    pub changed: usize,
+    /// Commits ahead of / behind the upstream branch, when one is configured.
+    pub ahead: u32,
+    pub behind: u32,
+}
+
+impl WorkTreeStatus {
+    /// Compact summary such as `clean`, `3 changed`, or `3 changed, +2/-1`.
+    pub fn summary(&self) -> String {
+        let mut parts = Vec::new();
+        if self.changed > 0 {
+            parts.push(format!("{} changed", self.changed));
+        }
+        if self.ahead > 0 || self.behind > 0 {
+            parts.push(format!("+{}/-{}", self.ahead, self.behind));
+        }
+        if parts.is_empty() {
+            "clean".to_string()
+        } else {
+            parts.join(", ")
+        }
+    }
+}
+
+/// Working-tree status of `dir`, or `None` outside a repository.
+pub fn status(dir: &Path) -> Result<Option<WorkTreeStatus>> {
+    let output = git_output(dir, &["status", "--porcelain=v2", "--branch"])?;
+    Ok(output.map(|text| parse_status(&text)))
+}
+
+fn parse_status(porcelain: &str) -> WorkTreeStatus {
+    let mut status = WorkTreeStatus::default();
+    for line in porcelain.lines() {
+        if let Some(counts) = line.strip_prefix("# branch.ab ") {
+            for count in counts.split_whitespace() {
+                if let Some(ahead) = count.strip_prefix('+') {
+                    status.ahead = ahead.parse().unwrap_or_default();
+                } else if let Some(behind) = count.strip_prefix('-') {
+                    status.behind = behind.parse().unwrap_or_default();
+                }
+            }
+        } else if !line.starts_with('#') && !line.is_empty() {
+            status.changed += 1;
+        }
+    }
+    status
+}
+
+/// Print `git status` and a diffstat (or the full diff) of uncommitted changes in
+/// `dir` straight to the terminal.
+pub fn print_changes(dir: &Path, full: bool) -> Result<()> {
+    // A repository without commits has nothing to diff against, so compare the index.
+    let base = if git_output(dir, &["rev-parse", "--verify", "--quiet", "HEAD"])?.is_some() {
+        "HEAD"
+    } else {
+        "--cached"
+    };
+    run_git(dir, &["--no-pager", "status", "--short", "--branch"])?;
+    if full {
+        run_git(dir, &["diff", base])
+    } else {
+        run_git(dir, &["--no-pager", "diff", "--stat", base])
+    }
+}
+
+/// Run git in `dir`, optionally against a separate index file, returning trimmed
+/// stdout; a failing command becomes an error carrying git's message.
+pub fn run(dir: &Path, index: Option<&Path>, args: &[&str]) -> Result<String> {
+    let mut command = Command::new("git");
+    command.arg("-C").arg(dir).args(args).stdin(Stdio::null());
+    if let Some(index) = index {
+        command.env("GIT_INDEX_FILE", index);
+    }
+    let output = command
+        .output()
+        .map_err(|err| with_context(err, "failed to invoke git"))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return bail(format!("git {} failed: {}", args.join(" "), stderr.trim()));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Run git in `dir` with its output going to the terminal.
+fn run_git(dir: &Path, args: &[&str]) -> Result<()> {
+    let status = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .stdin(Stdio::null())
+        .status()
+        .map_err(|err| with_context(err, "failed to invoke git"))?;
+    if !status.success() {
+        return bail(format!("git {} failed with {status}", args.join(" ")));
+    }
+    Ok(())
+}
+
+/// Run a git query in `dir`, returning trimmed stdout or `None` when git reports
+/// an error (typically "not a git repository").
+fn git_output(dir: &Path, args: &[&str]) -> Result<Option<String>> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .stdin(Stdio::null())
+        .output();
+
+    match output {
+        Ok(output) if output.status.success() => {
+            let text = String::from_utf8_lossy(&output.stdout).trim().to_string();
+            Ok((!text.is_empty()).then_some(text))
+        }
+        Ok(_) => Ok(None),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+            bail("git not found; install git to use repository features")
+        }
+        Err(err) => Err(with_context(err, "failed to invoke git")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_status_counts_entries_and_divergence() {
+        let status = parse_status(
+            "# branch.oid abc\n# branch.head main\n# branch.upstream origin/main\n# branch.ab +2 -1\n1 .M N... 100644 100644 100644 a b src/lib.rs\n? notes.txt",
+        );
+        assert_eq!(
+            status,
+            WorkTreeStatus {
+                changed: 2,
+                ahead: 2,
+                behind: 1,
+            }
+        );
+        assert_eq!(status.summary(), "2 changed, +2/-1");
+    }
+
+    #[test]
+    fn drift_counts_each_file_once_and_pluralizes() {
+        let drift = Drift {
+            commits: 1,
+            files: count_files("src/lib.rs\nsrc/cli.rs", "notes.txt\n"),
+        };
+        assert_eq!(drift.summary(), "1 commit, 3 files");
+        assert_eq!(count_files("a\nb", ""), 2);
+        assert_eq!(Drift::default().summary(), "no changes");
+    }
+
+    #[test]
+    fn summary_reports_clean_tree() {
+        let status = parse_status("# branch.oid abc\n# branch.head main");
+        assert_eq!(status.summary(), "clean");
+    }
+}
@@ -0,0 +1,125 @@
+//! A small JSON snapshot of the amux sessions, rewritten after every start,
+//! removal, exit, and pin change, so status bars and scripts can read
+//! `<state>/heartbeat.json` instead of running amux or tmux themselves.
+
+use crate::error::Result;
+use crate::json::{self, Value};
+use crate::paths;
+use crate::state;
+use crate::time::unix_now;
+use crate::tmux::{self, SessionDetail};
+
+/// The version is bumped when a field changes meaning or goes away.
+const SCHEMA: state::Schema = state::Schema {
+    what: "heartbeat",
+    version: 1,
+    migrate: state::unchanged,
+};
+
+/// Rewrite the heartbeat after `event` happened to `session`. Monitoring is a
+/// side channel, so a failure is only reported.
+pub fn refresh(event: &str, session: &str) {
+    if let Err(err) = write(event, session) {
+        eprintln!("amux: warning: heartbeat not updated: {err}");
+    }
+}
+
+fn write(event: &str, session: &str) -> Result<()> {
+    let path = paths::heartbeat_file()?;
+    // Sessions are listed under the lock, so of two events at once the one
+    // written last also saw the sessions last.
+    let _lock = state::lock(&path)?;
+    let sessions = tmux::list_sessions()?;
+    state::write(
+        &path,
+        &SCHEMA,
+        snapshot(event, session, unix_now(), &sessions),
+    )
+}
+
+fn snapshot(event: &str, session: &str, now: u64, sessions: &[SessionDetail]) -> Value {
+    let entries: Vec<Value> = sessions
+        .iter()
+        .map(|detail| {
+            json::object([
+                ("agent", Value::from(detail.agent.as_str())),
+                ("name", Value::from(detail.name.clone())),
+                ("session", Value::from(detail.session_name.as_str())),
+                ("clients", Value::from(detail.client_count)),
+                ("pinned", Value::from(detail.pinned)),
+                ("crash_looping", Value::from(detail.crash_looping)),
+                ("note", Value::from(detail.note.clone())),
+            ])
+        })
+        .collect();
+    let crash_looping = sessions.iter().filter(|s| s.crash_looping).count();
+    json::object([
+        ("version", Value::from(SCHEMA.version)),
+        ("updated_at", Value::from(now)),
+        (
+            "last_event",
+            json::object([
+                ("event", Value::from(event)),
+                ("session", Value::from(session)),
+            ]),
+        ),
+        ("running", Value::from(sessions.len())),
+        ("crash_looping", Value::from(crash_looping)),
+        ("sessions", Value::from(entries)),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(agent: &str, name: Option<&str>, crash_looping: bool) -> SessionDetail {
+        SessionDetail {
+            session_name: tmux::session_name(agent, name),
+            agent: agent.into(),
+            name: name.map(str::to_owned),
+            client_count: 1,
+            pane_command: None,
+            pinned: false,
+            crash_looping,
+            note: None,
+            cwd: None,
+        }
+    }
+
+    #[test]
+    fn snapshot_counts_sessions_and_names_the_event() {
+        let sessions = [
+            session("codex", None, false),
+            session("claude", Some("b"), true),
+        ];
+        let value = snapshot("removed", "amux-gemini", 1_700_000_000, &sessions);
+        assert_eq!(value.get("running").and_then(Value::as_u64), Some(2));
+        assert_eq!(value.get("crash_looping").and_then(Value::as_u64), Some(1));
+        assert_eq!(
+            value
+                .path(&["last_event", "session"])
+                .and_then(Value::as_str),
+            Some("amux-gemini")
+        );
+        let listed = value
+            .get("sessions")
+            .and_then(Value::as_array)
+            .expect("sessions should be an array");
+        assert_eq!(listed[1].get("name").and_then(Value::as_str), Some("b"));
+    }
+
+    #[test]
+    fn snapshot_of_no_sessions_is_still_complete() {
+        let value = snapshot("exited", "amux-codex", 5, &[]);
+        assert_eq!(value.get("running").and_then(Value::as_u64), Some(0));
+        assert_eq!(value.get("updated_at").and_then(Value::as_u64), Some(5));
+        assert_eq!(
+            value
+                .get("sessions")
+                .and_then(Value::as_array)
+                .map(<[_]>::len),
+            Some(0)
+        );
+    }
+}
@@ -0,0 +1,119 @@
+//! `amux here`: which sessions are working on a directory, for when you cd
+//! into a repository and forget which agent you pointed at it. A session
+//! owns a directory when its working directory is that directory or one of
+//! its parents.
+
+use std::path::{Path, PathBuf};
+
+use crate::error::Result;
+use crate::parallel;
+use crate::tmux::{self, SessionDetail};
+
+/// A session whose working directory contains the directory asked about.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Owner {
+    pub agent: String,
+    pub name: Option<String>,
+    pub session: String,
+    pub cwd: PathBuf,
+    pub clients: usize,
+}
+
+/// The sessions owning `dir`, closest first.
+pub fn owners(dir: &Path) -> Result<Vec<Owner>> {
+    let sessions = tmux::list_sessions()?;
+    let cwds = parallel::map_bounded(&sessions, 4, |session| {
+        let cwd = tmux::session_info(&session.session_name).ok()?.cwd?;
+        // Compare real paths, so symlinked checkouts still match.
+        Some(PathBuf::from(&cwd).canonicalize().unwrap_or(cwd.into()))
+    });
+    let candidates = sessions
+        .into_iter()
+        .zip(cwds)
+        .filter_map(|(session, cwd)| Some((session, cwd?)))
+        .collect();
+    Ok(rank(dir, candidates))
+}
+
+fn rank(dir: &Path, candidates: Vec<(SessionDetail, PathBuf)>) -> Vec<Owner> {
+    let mut owners: Vec<Owner> = candidates
+        .into_iter()
+        .filter(|(_, cwd)| dir.starts_with(cwd))
+        .map(|(session, cwd)| Owner {
+            agent: session.agent,
+            name: session.name,
+            session: session.session_name,
+            cwd,
+            clients: session.client_count,
+        })
+        .collect();
+    owners.sort_by(|a, b| {
+        let depth = |owner: &Owner| owner.cwd.components().count();
+        depth(b)
+            .cmp(&depth(a))
+            .then_with(|| a.session.cmp(&b.session))
+    });
+    owners
+}
+
+/// The one session to attach or send to: the closest owner, unless another
+/// is just as close.
+pub fn closest(owners: &[Owner]) -> Option<&Owner> {
+    match owners {
+        [first, second, ..] if first.cwd == second.cwd => None,
+        [first, ..] => Some(first),
+        [] => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(agent: &str, name: Option<&str>) -> SessionDetail {
+        SessionDetail {
+            session_name: tmux::session_name(agent, name),
+            agent: agent.into(),
+            name: name.map(str::to_string),
+            client_count: 0,
+            pane_command: None,
+            pinned: false,
+            crash_looping: false,
+            note: None,
+            cwd: None,
+        }
+    }
+
+    #[test]
+    fn rank_keeps_parent_directories_closest_first() {
+        let owners = rank(
+            Path::new("/src/api/handlers"),
+            vec![
+                (session("codex", None), "/src".into()),
+                (session("claude", None), "/src/api".into()),
+                (session("codex", Some("web")), "/src/web".into()),
+                (session("codex", Some("ap")), "/src/ap".into()),
+            ],
+        );
+        let sessions: Vec<&str> = owners.iter().map(|owner| owner.session.as_str()).collect();
+        assert_eq!(sessions, ["amux-claude", "amux-codex"]);
+        assert_eq!(
+            closest(&owners).map(|owner| owner.agent.as_str()),
+            Some("claude")
+        );
+    }
+
+    #[test]
+    fn closest_is_ambiguous_between_sessions_in_the_same_directory() {
+        let owners = rank(
+            Path::new("/src/api"),
+            vec![
+                (session("codex", None), "/src/api".into()),
+                (session("claude", None), "/src/api".into()),
+            ],
+        );
+        assert_eq!(owners.len(), 2);
+        assert_eq!(closest(&owners), None);
+        assert_eq!(closest(&[]), None);
+    }
+}
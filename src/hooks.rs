@@ -0,0 +1,72 @@
+//! User hook commands, run through `sh -c` in the invoking terminal so they can
+//! talk to it directly (escape sequences, profile switches, ...).
+
+use std::process::Command;
+
+use crate::error::{bail, with_context, Result};
+
+/// Run hook `stage` (e.g. `pre_attach`) with the session described in its
+/// environment as `AMUX_HOOK`, `AMUX_AGENT`, `AMUX_SESSION`, and, for named
+/// sessions, `AMUX_SESSION_NAME`.
+pub fn run(
+    stage: &str,
+    command: &str,
+    agent: &str,
+    session: &str,
+    name: Option<&str>,
+) -> Result<()> {
+    run_with_env(stage, command, agent, session, name, &[])
+}
+
+/// [`run`], with `extra` variables added to the hook's environment.
+pub fn run_with_env(
+    stage: &str,
+    command: &str,
+    agent: &str,
+    session: &str,
+    name: Option<&str>,
+    extra: &[(&str, &str)],
+) -> Result<()> {
+    let mut cmd = Command::new("sh");
+    cmd.arg("-c")
+        .arg(command)
+        .env("AMUX_HOOK", stage)
+        .env("AMUX_AGENT", agent)
+        .env("AMUX_SESSION", session)
+        .envs(extra.iter().copied());
+    match name {
+        Some(name) => cmd.env("AMUX_SESSION_NAME", name),
+        None => cmd.env_remove("AMUX_SESSION_NAME"),
+    };
+    let status = cmd
+        .status()
+        .map_err(|err| with_context(err, format!("failed to run {stage} hook")))?;
+    if !status.success() {
+        return bail(format!("{stage} hook exited with {status}"));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn run_passes_session_details_in_the_environment() {
+        run(
+            "pre_attach",
+            r#"test "$AMUX_HOOK:$AMUX_AGENT:$AMUX_SESSION:$AMUX_SESSION_NAME" = "pre_attach:codex:amux-codex--x:x""#,
+            "codex",
+            "amux-codex--x",
+            Some("x"),
+        )
+        .expect("hook should see its environment");
+    }
+
+    #[test]
+    fn run_reports_failing_hooks() {
+        let err = run("post_detach", "exit 3", "codex", "amux-codex", None)
+            .expect_err("hook should fail");
+        assert!(err.to_string().contains("post_detach hook exited"));
+    }
+}
@@ -0,0 +1,387 @@
+//! `amux status --hosts`: sessions on other machines, read by running
+//! `amux ide list --json` there over ssh. Hosts are probed in parallel, each
+//! with its own timeout, and the last answer from each is kept in
+//! `<state>/hosts.json`, so a host that is down or slow shows what it was
+//! running when last seen instead of failing the whole status.
+//!
+//! Every ssh to a host goes through one shared connection (ssh's
+//! `ControlMaster`), kept open for `control_persist_secs` after its last use,
+//! so repeated probes and `attach --host` skip the ssh handshake.
+
+use std::collections::BTreeMap;
+use std::fs::DirBuilder;
+use std::io::Read;
+use std::os::unix::fs::DirBuilderExt;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::config::Host;
+use crate::error::{bail, fail_with, with_context, ErrorKind, Result};
+use crate::json::{self, Value};
+use crate::parallel;
+use crate::paths;
+use crate::state;
+use crate::time::{format_timestamp, unix_now};
+
+const SCHEMA: state::Schema = state::Schema {
+    what: "host cache",
+    version: 1,
+    migrate: state::unchanged,
+};
+
+/// A session on a remote host.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RemoteSession {
+    pub session: String,
+    pub agent: String,
+    pub name: Option<String>,
+    /// `running` or `exited`.
+    pub state: String,
+    pub clients: u64,
+}
+
+/// What is known about one host.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct HostStatus {
+    /// The sessions as of `seen_at`.
+    pub sessions: Vec<RemoteSession>,
+    /// When the host last answered.
+    pub seen_at: Option<u64>,
+    /// Why the latest probe failed, or `None` if the host answered it.
+    pub error: Option<String>,
+}
+
+/// Probe every host at once and update the cache with the answers. Hosts
+/// that do not answer keep their last known sessions.
+pub fn check(hosts: &BTreeMap<String, Host>) -> Result<BTreeMap<String, HostStatus>> {
+    let entries: Vec<(&String, &Host)> = hosts.iter().collect();
+    let probes = parallel::map_bounded(&entries, entries.len(), |(_, host)| probe(host));
+    let now = unix_now();
+    state::update(&paths::hosts_file()?, &SCHEMA, |cached| {
+        let mut known = cached.as_ref().map(statuses_from_json).unwrap_or_default();
+        known.retain(|name, _| hosts.contains_key(name));
+        for ((name, _), probe) in entries.iter().zip(probes) {
+            let status = known.entry(name.to_string()).or_default();
+            match probe {
+                Ok(sessions) => {
+                    *status = HostStatus {
+                        sessions,
+                        seen_at: Some(now),
+                        error: None,
+                    }
+                }
+                Err(err) => status.error = Some(err.to_string()),
+            }
+        }
+        Ok((statuses_to_json(&known), known))
+    })
+}
+
+/// The host's sessions, or why they could not be read in time.
+fn probe(host: &Host) -> Result<Vec<RemoteSession>> {
+    let mut child = ssh(host)?
+        // Fail instead of prompting for a password nobody is there to type.
+        .args(["-o", "BatchMode=yes"])
+        .arg("-o")
+        .arg(format!("ConnectTimeout={}", host.timeout_secs))
+        .args([
+            host.ssh.as_str(),
+            "--",
+            host.amux.as_str(),
+            "ide",
+            "list",
+            "--json",
+        ])
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| with_context(err, "failed to run ssh"))?;
+    let stdout = read_in_background(child.stdout.take());
+    let stderr = read_in_background(child.stderr.take());
+
+    // ConnectTimeout only covers connecting; this also bounds a remote amux
+    // or tmux that hangs.
+    let deadline = Instant::now() + Duration::from_secs(host.timeout_secs);
+    let status = loop {
+        if let Some(status) = child
+            .try_wait()
+            .map_err(|err| with_context(err, "failed to wait for ssh"))?
+        {
+            break status;
+        }
+        if Instant::now() >= deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            return bail(format!("timed out after {}s", host.timeout_secs));
+        }
+        thread::sleep(Duration::from_millis(50));
+    };
+
+    let stdout = stdout.join().unwrap_or_default();
+    let stderr = stderr.join().unwrap_or_default();
+    if !status.success() {
+        let reason = stderr.lines().rev().find(|line| !line.trim().is_empty());
+        return bail(match reason {
+            Some(reason) => reason.trim().to_string(),
+            None => format!("ssh exited with {status}"),
+        });
+    }
+    parse_sessions(&stdout)
+}
+
+/// Attach this terminal to a session on `host` through its amux, with
+/// `attach`'s `--start` and `--take-over`.
+pub fn attach(
+    name: &str,
+    host: &Host,
+    agent: &str,
+    session_name: Option<&str>,
+    start: bool,
+    take_over: bool,
+) -> Result<()> {
+    let mut command = ssh(host)?;
+    command.args([
+        "-t",
+        host.ssh.as_str(),
+        "--",
+        host.amux.as_str(),
+        "attach",
+        "-a",
+        agent,
+    ]);
+    if let Some(session_name) = session_name {
+        command.args(["-n", session_name]);
+    }
+    if start {
+        command.arg("-s");
+    }
+    if take_over {
+        command.arg("--take-over");
+    }
+    let status = command
+        .status()
+        .map_err(|err| with_context(err, "failed to run ssh"))?;
+    if !status.success() {
+        return Err(fail_with(
+            ErrorKind::Other,
+            format!("attach on host '{name}' failed ({status})"),
+            Some(format!("check that `ssh {}` works", host.ssh)),
+        ));
+    }
+    Ok(())
+}
+
+/// `ssh` set up to share the host's connection, before the destination.
+fn ssh(host: &Host) -> Result<Command> {
+    let mut command = Command::new("ssh");
+    if host.control_persist_secs == 0 {
+        return Ok(command);
+    }
+    let dir = paths::ssh_control_dir()?;
+    DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(&dir)
+        .map_err(|err| with_context(err, format!("failed to create {}", dir.display())))?;
+    command.args(multiplex_args(&dir, host.control_persist_secs));
+    Ok(command)
+}
+
+fn multiplex_args(dir: &Path, persist_secs: u64) -> Vec<String> {
+    vec![
+        "-o".into(),
+        "ControlMaster=auto".into(),
+        "-o".into(),
+        // %C hashes the destination, keeping the socket path short enough.
+        format!("ControlPath={}", dir.join("%C").display()),
+        "-o".into(),
+        format!("ControlPersist={persist_secs}"),
+    ]
+}
+
+fn read_in_background(pipe: Option<impl Read + Send + 'static>) -> thread::JoinHandle<String> {
+    thread::spawn(move || {
+        let mut text = String::new();
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_string(&mut text);
+        }
+        text
+    })
+}
+
+/// Sessions from `amux ide list --json` output.
+fn parse_sessions(raw: &str) -> Result<Vec<RemoteSession>> {
+    let value = json::parse(raw.trim())?;
+    let Some(entries) = value.as_array() else {
+        return bail("the host's amux did not list sessions");
+    };
+    Ok(entries.iter().filter_map(session_from_json).collect())
+}
+
+fn session_from_json(value: &Value) -> Option<RemoteSession> {
+    let text = |key: &str| value.get(key).and_then(Value::as_str).map(str::to_string);
+    Some(RemoteSession {
+        session: text("session")?,
+        agent: text("agent")?,
+        name: text("name"),
+        state: text("state").unwrap_or_else(|| "running".into()),
+        clients: value.get("clients").and_then(Value::as_u64).unwrap_or(0),
+    })
+}
+
+fn statuses_to_json(statuses: &BTreeMap<String, HostStatus>) -> Value {
+    let hosts = statuses.iter().map(|(name, status)| {
+        let sessions = status
+            .sessions
+            .iter()
+            .map(|session| {
+                json::object([
+                    ("session", Value::from(session.session.as_str())),
+                    ("agent", Value::from(session.agent.as_str())),
+                    ("name", Value::from(session.name.clone())),
+                    ("state", Value::from(session.state.as_str())),
+                    ("clients", Value::from(session.clients)),
+                ])
+            })
+            .collect::<Vec<_>>();
+        let status = json::object([
+            ("sessions", Value::from(sessions)),
+            ("seen_at", Value::from(status.seen_at)),
+            ("error", Value::from(status.error.clone())),
+        ]);
+        (name.clone(), status)
+    });
+    json::object([("hosts", json::object(hosts))])
+}
+
+fn statuses_from_json(value: &Value) -> BTreeMap<String, HostStatus> {
+    let Some(Value::Object(hosts)) = value.get("hosts") else {
+        return BTreeMap::new();
+    };
+    hosts
+        .iter()
+        .map(|(name, host)| {
+            let status = HostStatus {
+                sessions: host
+                    .get("sessions")
+                    .and_then(Value::as_array)
+                    .unwrap_or_default()
+                    .iter()
+                    .filter_map(session_from_json)
+                    .collect(),
+                seen_at: host.get("seen_at").and_then(Value::as_u64),
+                error: host
+                    .get("error")
+                    .and_then(Value::as_str)
+                    .map(str::to_string),
+            };
+            (name.clone(), status)
+        })
+        .collect()
+}
+
+/// The host's heading and one line per session. Sessions of an unreachable
+/// host are those it last reported.
+pub fn render(name: &str, host: &Host, status: &HostStatus) -> String {
+    let mut text = match (&status.error, status.seen_at) {
+        (None, _) => format!("{name} ({}):\n", host.ssh),
+        (Some(error), Some(seen_at)) => format!(
+            "{name} ({}): unreachable ({error}); last seen {}:\n",
+            host.ssh,
+            format_timestamp(seen_at)
+        ),
+        (Some(error), None) => {
+            return format!(
+                "{name} ({}): unreachable ({error}); never reached\n",
+                host.ssh
+            )
+        }
+    };
+    if status.sessions.is_empty() {
+        text.push_str("  no agent sessions\n");
+    }
+    for session in &status.sessions {
+        let name = session
+            .name
+            .as_ref()
+            .map(|name| format!(", name '{name}'"))
+            .unwrap_or_default();
+        text.push_str(&format!(
+            "  {}: {} (session '{}'{name}, clients: {})\n",
+            session.agent, session.state, session.session, session.clients
+        ));
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn host() -> Host {
+        Host {
+            ssh: "me@devbox".into(),
+            timeout_secs: 5,
+            amux: "amux".into(),
+            control_persist_secs: 300,
+        }
+    }
+
+    #[test]
+    fn multiplex_args_share_one_socket_per_destination() {
+        assert_eq!(
+            multiplex_args(Path::new("/state/ssh"), 300),
+            [
+                "-o",
+                "ControlMaster=auto",
+                "-o",
+                "ControlPath=/state/ssh/%C",
+                "-o",
+                "ControlPersist=300"
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_sessions_reads_ide_list_output() {
+        let raw = r#"[{"agent":"codex","clients":1,"name":"api","session":"amux-codex--api","state":"running"},{"bogus":true}]"#;
+        let sessions = parse_sessions(raw).expect("list should parse");
+        assert_eq!(
+            sessions,
+            [RemoteSession {
+                session: "amux-codex--api".into(),
+                agent: "codex".into(),
+                name: Some("api".into()),
+                state: "running".into(),
+                clients: 1,
+            }]
+        );
+        assert!(parse_sessions("{}").is_err());
+    }
+
+    #[test]
+    fn render_marks_unreachable_hosts_with_their_last_sessions() {
+        let mut status = HostStatus {
+            sessions: parse_sessions(r#"[{"agent":"codex","session":"amux-codex"}]"#)
+                .expect("list should parse"),
+            seen_at: Some(1_700_000_000),
+            error: None,
+        };
+        assert_eq!(
+            render("devbox", &host(), &status),
+            "devbox (me@devbox):\n  codex: running (session 'amux-codex', clients: 0)\n"
+        );
+        status.error = Some("timed out after 5s".into());
+        assert!(render("devbox", &host(), &status).starts_with(
+            "devbox (me@devbox): unreachable (timed out after 5s); last seen 2023-11-14T22:13:20Z:\n  codex"
+        ));
+        let cached = statuses_from_json(&statuses_to_json(&BTreeMap::from([(
+            "devbox".to_string(),
+            status.clone(),
+        )])));
+        assert_eq!(cached["devbox"], status);
+    }
+}
@@ -0,0 +1,226 @@
+//! Terminal output as a standalone HTML page, for sharing an agent's
+//! transcript with its colors (`amux capture --html`). Color and text
+//! attributes (SGR sequences) become styled spans; every other escape
+//! sequence is dropped.
+
+use std::fmt::Write as _;
+
+use crate::output::{self, Token};
+
+/// A color set by SGR: one of the 256 palette entries, or RGB.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Color {
+    Indexed(u8),
+    Rgb(u8, u8, u8),
+}
+
+impl Color {
+    fn css(self) -> String {
+        let (r, g, b) = match self {
+            Color::Indexed(index) => palette(index),
+            Color::Rgb(r, g, b) => (r, g, b),
+        };
+        format!("#{r:02x}{g:02x}{b:02x}")
+    }
+}
+
+/// xterm's default colors for palette entry `index`.
+fn palette(index: u8) -> (u8, u8, u8) {
+    const BASIC: [(u8, u8, u8); 16] = [
+        (0x00, 0x00, 0x00),
+        (0xcd, 0x00, 0x00),
+        (0x00, 0xcd, 0x00),
+        (0xcd, 0xcd, 0x00),
+        (0x00, 0x00, 0xee),
+        (0xcd, 0x00, 0xcd),
+        (0x00, 0xcd, 0xcd),
+        (0xe5, 0xe5, 0xe5),
+        (0x7f, 0x7f, 0x7f),
+        (0xff, 0x00, 0x00),
+        (0x00, 0xff, 0x00),
+        (0xff, 0xff, 0x00),
+        (0x5c, 0x5c, 0xff),
+        (0xff, 0x00, 0xff),
+        (0x00, 0xff, 0xff),
+        (0xff, 0xff, 0xff),
+    ];
+    match index {
+        0..=15 => BASIC[usize::from(index)],
+        16..=231 => {
+            let level = |value: u8| if value == 0 { 0 } else { 55 + value * 40 };
+            let cube = index - 16;
+            (level(cube / 36), level(cube / 6 % 6), level(cube % 6))
+        }
+        _ => {
+            let gray = 8 + (index - 232) * 10;
+            (gray, gray, gray)
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+struct Style {
+    bold: bool,
+    dim: bool,
+    italic: bool,
+    underline: bool,
+    inverse: bool,
+    fg: Option<Color>,
+    bg: Option<Color>,
+}
+
+impl Style {
+    /// Apply the parameters of one SGR sequence (`ESC [ ... m`).
+    fn apply(&mut self, params: &str) {
+        let mut codes = params
+            .split([';', ':'])
+            .map(|code| code.parse::<u16>().unwrap_or(0));
+        while let Some(code) = codes.next() {
+            match code {
+                0 => *self = Style::default(),
+                1 => self.bold = true,
+                2 => self.dim = true,
+                3 => self.italic = true,
+                4 => self.underline = true,
+                7 => self.inverse = true,
+                22 => (self.bold, self.dim) = (false, false),
+                23 => self.italic = false,
+                24 => self.underline = false,
+                27 => self.inverse = false,
+                30..=37 => self.fg = Some(Color::Indexed((code - 30) as u8)),
+                38 => self.fg = extended(&mut codes),
+                39 => self.fg = None,
+                40..=47 => self.bg = Some(Color::Indexed((code - 40) as u8)),
+                48 => self.bg = extended(&mut codes),
+                49 => self.bg = None,
+                90..=97 => self.fg = Some(Color::Indexed((code - 90 + 8) as u8)),
+                100..=107 => self.bg = Some(Color::Indexed((code - 100 + 8) as u8)),
+                _ => {}
+            }
+        }
+    }
+
+    fn css(self) -> String {
+        let (fg, bg) = if self.inverse {
+            (
+                Some(self.bg.map_or_else(|| BACKGROUND.into(), Color::css)),
+                Some(self.fg.map_or_else(|| FOREGROUND.into(), Color::css)),
+            )
+        } else {
+            (self.fg.map(Color::css), self.bg.map(Color::css))
+        };
+        let mut css = String::new();
+        if let Some(fg) = fg {
+            let _ = write!(css, "color:{fg};");
+        }
+        if let Some(bg) = bg {
+            let _ = write!(css, "background:{bg};");
+        }
+        for (on, rule) in [
+            (self.bold, "font-weight:bold;"),
+            (self.dim, "opacity:0.7;"),
+            (self.italic, "font-style:italic;"),
+            (self.underline, "text-decoration:underline;"),
+        ] {
+            if on {
+                css.push_str(rule);
+            }
+        }
+        css
+    }
+}
+
+/// The color after `38`/`48`: `5;N` for the palette, `2;R;G;B` for RGB.
+fn extended(codes: &mut impl Iterator<Item = u16>) -> Option<Color> {
+    let byte = |code: Option<u16>| code.map(|code| code.min(255) as u8);
+    match codes.next()? {
+        5 => byte(codes.next()).map(Color::Indexed),
+        2 => Some(Color::Rgb(
+            byte(codes.next())?,
+            byte(codes.next())?,
+            byte(codes.next())?,
+        )),
+        _ => None,
+    }
+}
+
+const FOREGROUND: &str = "#d4d4d4";
+const BACKGROUND: &str = "#1e1e1e";
+
+/// `raw` terminal output as an HTML page titled `title`.
+pub fn from_ansi(raw: &str, title: &str) -> String {
+    let mut body = String::with_capacity(raw.len());
+    let mut style = Style::default();
+    // The style of the span written last; spans open only once text follows,
+    // so consecutive sequences do not leave empty ones behind.
+    let mut written = Style::default();
+    for token in output::tokens(raw) {
+        match token {
+            Token::Sgr(params) => style.apply(&params),
+            Token::Char(c) => {
+                if style != written {
+                    if written != Style::default() {
+                        body.push_str("</span>");
+                    }
+                    if style != Style::default() {
+                        let _ = write!(body, "<span style=\"{}\">", style.css());
+                    }
+                    written = style;
+                }
+                push_escaped(&mut body, c);
+            }
+        }
+    }
+    if written != Style::default() {
+        body.push_str("</span>");
+    }
+
+    let mut title_html = String::new();
+    title.chars().for_each(|c| push_escaped(&mut title_html, c));
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{title_html}</title>\n\
+         <style>body {{ margin: 0; background: {BACKGROUND}; color: {FOREGROUND}; }} \
+         pre {{ margin: 0; padding: 1em; font: 13px/1.3 ui-monospace, Menlo, Consolas, monospace; }}\
+         </style>\n</head>\n<body>\n<pre>{body}</pre>\n</body>\n</html>\n"
+    )
+}
+
+fn push_escaped(out: &mut String, c: char) {
+    match c {
+        '&' => out.push_str("&amp;"),
+        '<' => out.push_str("&lt;"),
+        '>' => out.push_str("&gt;"),
+        '"' => out.push_str("&quot;"),
+        c => out.push(c),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn body(page: &str) -> &str {
+        let start = page.find("<pre>").expect("page has a body") + "<pre>".len();
+        let end = page.find("</pre>").expect("page has a body");
+        &page[start..end]
+    }
+
+    #[test]
+    fn from_ansi_turns_colors_into_spans() {
+        let raw =
+            "\u{1b}[1;32mok\u{1b}[0m <done>\r\n\u{1b}[38;5;196mred\u{1b}[38;2;1;2;3mrgb\u{1b}[m";
+        assert_eq!(
+            body(&from_ansi(raw, "codex")),
+            "<span style=\"color:#00cd00;font-weight:bold;\">ok</span> &lt;done&gt;\n\
+             <span style=\"color:#ff0000;\">red</span><span style=\"color:#010203;\">rgb</span>"
+        );
+    }
+
+    #[test]
+    fn from_ansi_drops_other_sequences_and_escapes_the_title() {
+        let raw = "\u{1b}]0;title\u{7}\u{1b}[2K\u{1b}[?25lplain";
+        let page = from_ansi(raw, "a <b>");
+        assert_eq!(body(&page), "plain");
+        assert!(page.contains("<title>a &lt;b&gt;</title>"));
+    }
+}
@@ -0,0 +1,160 @@
+//! Stable, versioned JSON shapes consumed by editor extensions via `amux ide`,
+//! and by scripts via `status`/`list --format json`.
+//!
+//! Fields may be added in later versions but existing ones keep their meaning;
+//! a breaking change bumps `SPEC_VERSION`.
+
+use crate::json::{self, Value};
+use crate::theme::Theme;
+use crate::tmux::{SessionDetail, SessionInfo};
+
+pub const SPEC_VERSION: u64 = 1;
+
+/// One entry of `amux ide list --json`.
+pub fn session_entry(detail: &SessionDetail, info: Option<&SessionInfo>, theme: &Theme) -> Value {
+    json::object([
+        (
+            "color",
+            Value::from(theme.color.map(|color| color.to_string())),
+        ),
+        ("icon", Value::from(theme.icon.clone())),
+        ("agent", Value::from(detail.agent.as_str())),
+        ("name", Value::from(detail.name.clone())),
+        ("session", Value::from(detail.session_name.as_str())),
+        ("clients", Value::from(detail.client_count)),
+        ("command", Value::from(detail.pane_command.clone())),
+        ("cwd", Value::from(info.and_then(|info| info.cwd.clone()))),
+        (
+            "state",
+            Value::from(match info {
+                Some(info) if info.pane_dead => "exited",
+                _ => "running",
+            }),
+        ),
+    ])
+}
+
+/// One entry of `amux status --format json`: a session as tmux lists it.
+pub fn status_entry(detail: &SessionDetail) -> Value {
+    json::object([
+        ("session", Value::from(detail.session_name.as_str())),
+        ("agent", Value::from(detail.agent.as_str())),
+        ("name", Value::from(detail.name.clone())),
+        ("clients", Value::from(detail.client_count)),
+        ("command", Value::from(detail.pane_command.clone())),
+        ("cwd", Value::from(detail.cwd.clone())),
+        ("pinned", Value::from(detail.pinned)),
+        ("crash_looping", Value::from(detail.crash_looping)),
+        ("note", Value::from(detail.note.clone())),
+    ])
+}
+
+/// One entry of `amux list --format json`.
+pub fn agent_entry(agent: &str, sessions: usize, description: Option<&str>) -> Value {
+    json::object([
+        ("agent", Value::from(agent)),
+        (
+            "state",
+            Value::from(if sessions > 0 { "running" } else { "stopped" }),
+        ),
+        ("sessions", Value::from(sessions)),
+        ("description", Value::from(description)),
+    ])
+}
+
+/// Describe how an editor should launch an integrated terminal attached to a session.
+pub fn launch_spec(
+    program: &str,
+    agent: &str,
+    name: Option<&str>,
+    session: &str,
+    cwd: Option<&str>,
+) -> Value {
+    let mut args = vec![
+        "attach".to_string(),
+        "--agent".to_string(),
+        agent.to_string(),
+    ];
+    if let Some(name) = name {
+        args.push("--name".to_string());
+        args.push(name.to_string());
+    }
+    let title = match name {
+        Some(name) => format!("amux: {agent} ({name})"),
+        None => format!("amux: {agent}"),
+    };
+
+    json::object([
+        ("version", Value::from(SPEC_VERSION)),
+        ("title", Value::from(title)),
+        ("program", Value::from(program)),
+        ("args", Value::from(args)),
+        ("cwd", Value::from(cwd)),
+        ("session", Value::from(session)),
+        ("agent", Value::from(agent)),
+        ("name", Value::from(name)),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn launch_spec_attaches_through_amux() {
+        let spec = launch_spec(
+            "/usr/bin/amux",
+            "codex",
+            Some("review"),
+            "amux-codex--review",
+            Some("/repo"),
+        );
+        assert_eq!(
+            spec.to_string(),
+            r#"{"agent":"codex","args":["attach","--agent","codex","--name","review"],"cwd":"/repo","name":"review","program":"/usr/bin/amux","session":"amux-codex--review","title":"amux: codex (review)","version":1}"#
+        );
+    }
+
+    #[test]
+    fn session_entry_reports_running_without_info() {
+        let detail = SessionDetail {
+            session_name: "amux-claude".into(),
+            agent: "claude".into(),
+            name: None,
+            client_count: 1,
+            pane_command: Some("node".into()),
+            pinned: false,
+            crash_looping: false,
+            note: None,
+            cwd: None,
+        };
+        let entry = session_entry(&detail, None, &Theme::default());
+        assert_eq!(
+            entry.to_string(),
+            r#"{"agent":"claude","clients":1,"color":null,"command":"node","cwd":null,"icon":null,"name":null,"session":"amux-claude","state":"running"}"#
+        );
+    }
+
+    #[test]
+    fn status_and_agent_entries_keep_their_shape() {
+        let detail = SessionDetail {
+            session_name: "amux-codex--api".into(),
+            agent: "codex".into(),
+            name: Some("api".into()),
+            client_count: 0,
+            pane_command: None,
+            pinned: true,
+            crash_looping: false,
+            note: Some("fix login".into()),
+            cwd: Some("/src/api".into()),
+        };
+        assert_eq!(
+            status_entry(&detail).to_string(),
+            r#"{"agent":"codex","clients":0,"command":null,"crash_looping":false,"cwd":"/src/api","name":"api","note":"fix login","pinned":true,"session":"amux-codex--api"}"#
+        );
+        assert_eq!(
+            agent_entry("codex", 2, Some("Codex CLI")).to_string(),
+            r#"{"agent":"codex","description":"Codex CLI","sessions":2,"state":"running"}"#
+        );
+    }
+}
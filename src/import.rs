@@ -0,0 +1,315 @@
+//! `amux agents import`: turn agent definitions that a team already keeps
+//! for another tool into amux agents. Three sources are understood: a
+//! shared `agents.yaml`/`agents.json` (`agents:` mapping names to a command,
+//! description, params, cwd, and env), an aider config (`.aider.conf.yml`,
+//! whose settings become aider's params), and an MCP client config (JSON
+//! with `mcpServers`, each local server becoming an agent).
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::agent_edit::Definition;
+use crate::error::{bail, fail_with, with_context, DynError, ErrorKind, Result};
+use crate::json::{self, Value};
+use crate::yaml;
+
+/// The kind of file being imported.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Source {
+    Agents,
+    Aider,
+    Mcp,
+}
+
+impl FromStr for Source {
+    type Err = DynError;
+
+    fn from_str(raw: &str) -> Result<Self> {
+        match raw {
+            "agents" => Ok(Self::Agents),
+            "aider" => Ok(Self::Aider),
+            "mcp" => Ok(Self::Mcp),
+            other => bail(format!(
+                "unknown import source '{other}' (expected agents, aider, or mcp)"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Agents => "agents",
+            Self::Aider => "aider",
+            Self::Mcp => "mcp",
+        })
+    }
+}
+
+/// What a file holds: agents to define, and entries that cannot become one.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Import {
+    pub agents: Vec<(String, Definition)>,
+    /// Entry name and why it was left out.
+    pub skipped: Vec<(String, String)>,
+}
+
+/// Read `path` as `from`, or as whatever kind of file it looks like.
+pub fn read(path: &Path, from: Option<Source>) -> Result<(Source, Import)> {
+    let text = fs::read_to_string(path)
+        .map_err(|err| with_context(err, format!("failed to read {}", path.display())))?;
+    let is_json = path.extension().is_some_and(|ext| ext == "json");
+    let doc = if is_json {
+        json::parse(&text)
+    } else {
+        yaml::parse(&text)
+    }
+    .map_err(|err| with_context(err, format!("failed to parse {}", path.display())))?;
+    let source = match from {
+        Some(source) => source,
+        None => detect(path, &doc)?,
+    };
+    Ok((source, convert(source, &doc)?))
+}
+
+fn detect(path: &Path, doc: &Value) -> Result<Source> {
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    if file_name.contains("aider") {
+        Ok(Source::Aider)
+    } else if doc.get("mcpServers").is_some() {
+        Ok(Source::Mcp)
+    } else if doc.get("agents").is_some() {
+        Ok(Source::Agents)
+    } else {
+        Err(fail_with(
+            ErrorKind::InvalidInput,
+            format!("cannot tell what kind of config {} is", path.display()),
+            Some("name it with --from agents, --from aider, or --from mcp".into()),
+        ))
+    }
+}
+
+/// The agents `doc` defines when read as `source`.
+pub fn convert(source: Source, doc: &Value) -> Result<Import> {
+    match source {
+        Source::Agents => from_agents(doc),
+        Source::Aider => from_aider(doc),
+        Source::Mcp => from_mcp(doc),
+    }
+}
+
+fn from_agents(doc: &Value) -> Result<Import> {
+    let entries: Vec<(String, &Value)> = match doc.get("agents") {
+        Some(Value::Object(agents)) => agents
+            .iter()
+            .map(|(name, entry)| (name.clone(), entry))
+            .collect(),
+        Some(Value::Array(agents)) => agents
+            .iter()
+            .map(|entry| (text(entry.get("name")).unwrap_or_default(), entry))
+            .collect(),
+        _ => return bail("'agents' should map agent names to their settings"),
+    };
+    let mut import = Import::default();
+    for (name, entry) in entries {
+        if name.is_empty() {
+            import.skipped.push(("(unnamed)".into(), "no name".into()));
+            continue;
+        }
+        let Some(command) = words(entry.get("command")) else {
+            import.skipped.push((name, "no command".into()));
+            continue;
+        };
+        let mut params = words(entry.get("params")).unwrap_or_default();
+        if let Some(args) = words(entry.get("args")) {
+            params = [params, args]
+                .into_iter()
+                .filter(|part| !part.is_empty())
+                .collect::<Vec<_>>()
+                .join(" ");
+        }
+        let definition = Definition {
+            command: Some(command),
+            description: text(entry.get("description")),
+            params: Some(params).filter(|params| !params.is_empty()),
+            cwd: text(entry.get("cwd")),
+            env: env(entry.get("env")),
+        };
+        import.agents.push((agent_name(&name), definition));
+    }
+    Ok(import)
+}
+
+fn from_aider(doc: &Value) -> Result<Import> {
+    let settings = match doc {
+        Value::Object(settings) => settings,
+        Value::Null => &json::Object::new(),
+        _ => return bail("an aider config should be a mapping of settings"),
+    };
+    let mut params = Vec::new();
+    for (key, value) in settings {
+        let flag = format!("--{key}");
+        match value {
+            Value::Null => {}
+            Value::Bool(true) => params.push(flag),
+            Value::Bool(false) => params.push(format!("--no-{key}")),
+            Value::Array(items) => {
+                for item in items {
+                    params.push(flag.clone());
+                    params.push(text(Some(item)).unwrap_or_default());
+                }
+            }
+            Value::Object(_) => return bail(format!("aider setting '{key}' is a mapping")),
+            other => {
+                params.push(flag);
+                params.push(text(Some(other)).unwrap_or_default());
+            }
+        }
+    }
+    let definition = Definition {
+        command: Some("aider".into()),
+        description: text(settings.get("model")).map(|model| format!("aider with {model}")),
+        params: Some(shell_words::join(&params)).filter(|params| !params.is_empty()),
+        ..Definition::default()
+    };
+    Ok(Import {
+        agents: vec![("aider".into(), definition)],
+        skipped: Vec::new(),
+    })
+}
+
+fn from_mcp(doc: &Value) -> Result<Import> {
+    let Some(Value::Object(servers)) = doc.get("mcpServers") else {
+        return bail("'mcpServers' should map server names to their settings");
+    };
+    let mut import = Import::default();
+    for (name, server) in servers {
+        let Some(command) = text(server.get("command")) else {
+            let reason = if server.get("url").is_some() {
+                "a remote server, with no command to run"
+            } else {
+                "no command"
+            };
+            import.skipped.push((name.clone(), reason.into()));
+            continue;
+        };
+        let definition = Definition {
+            command: Some(shell_words::quote(&command).into_owned()),
+            description: Some(format!("MCP server '{name}'")),
+            params: words(server.get("args")).filter(|args| !args.is_empty()),
+            cwd: text(server.get("cwd")),
+            env: env(server.get("env")),
+        };
+        import.agents.push((agent_name(name), definition));
+    }
+    Ok(import)
+}
+
+/// `name` with everything but letters, digits, '-' and '_' turned into '-'.
+fn agent_name(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect()
+}
+
+/// A scalar as text.
+fn text(value: Option<&Value>) -> Option<String> {
+    match value? {
+        Value::String(text) => Some(text.clone()),
+        Value::Integer(number) => Some(number.to_string()),
+        Value::Float(number) => Some(number.to_string()),
+        Value::Bool(flag) => Some(flag.to_string()),
+        _ => None,
+    }
+}
+
+/// A command line given as one string or as a list of words.
+fn words(value: Option<&Value>) -> Option<String> {
+    match value? {
+        Value::Array(items) => {
+            let items: Vec<String> = items.iter().filter_map(|item| text(Some(item))).collect();
+            Some(shell_words::join(&items))
+        }
+        other => text(Some(other)),
+    }
+}
+
+fn env(value: Option<&Value>) -> BTreeMap<String, String> {
+    match value {
+        Some(Value::Object(vars)) => vars
+            .iter()
+            .filter_map(|(key, value)| Some((key.clone(), text(Some(value))?)))
+            .collect(),
+        _ => BTreeMap::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn agents_files_map_names_to_definitions() {
+        let doc = yaml::parse(
+            "agents:\n  code reviewer:\n    command: [my-agent, --review]\n    args: [--fast]\n    description: Reviews the branch\n    env:\n      REVIEW_LEVEL: 2\n  broken:\n    description: no command\n",
+        )
+        .expect("yaml should parse");
+        let import = convert(Source::Agents, &doc).expect("convert");
+        assert_eq!(
+            import.skipped,
+            [("broken".to_string(), "no command".to_string())]
+        );
+        let (name, definition) = &import.agents[0];
+        assert_eq!(name, "code-reviewer");
+        assert_eq!(definition.command.as_deref(), Some("my-agent --review"));
+        assert_eq!(definition.params.as_deref(), Some("--fast"));
+        assert_eq!(
+            definition.env.get("REVIEW_LEVEL").map(String::as_str),
+            Some("2")
+        );
+    }
+
+    #[test]
+    fn aider_settings_become_params() {
+        let doc = yaml::parse(
+            "model: sonnet\nauto-commits: false\ndark-mode: true\nread: [CONVENTIONS.md, 'my notes.md']\n",
+        )
+        .expect("yaml should parse");
+        let import = convert(Source::Aider, &doc).expect("convert");
+        let (name, definition) = &import.agents[0];
+        assert_eq!(name, "aider");
+        assert_eq!(definition.description.as_deref(), Some("aider with sonnet"));
+        assert_eq!(
+            definition.params.as_deref(),
+            Some("--no-auto-commits --dark-mode --model sonnet --read CONVENTIONS.md --read 'my notes.md'")
+        );
+    }
+
+    #[test]
+    fn mcp_servers_with_a_command_become_agents() {
+        let doc = json::parse(
+            r#"{"mcpServers": {"files": {"command": "npx", "args": ["-y", "@mcp/files", "/tmp"], "env": {"DEBUG": "1"}}, "remote": {"url": "https://example.com/mcp"}}}"#,
+        )
+        .expect("json should parse");
+        let import = convert(Source::Mcp, &doc).expect("convert");
+        assert_eq!(import.agents.len(), 1);
+        let (name, definition) = &import.agents[0];
+        assert_eq!(name, "files");
+        assert_eq!(definition.params.as_deref(), Some("-y @mcp/files /tmp"));
+        assert_eq!(import.skipped[0].0, "remote");
+        assert!(detect(Path::new("mcp.json"), &doc).is_ok_and(|source| source == Source::Mcp));
+        assert!(detect(Path::new("x.yml"), &Value::Null).is_err());
+    }
+}
@@ -0,0 +1,153 @@
+//! Typing prompts into an agent's pane. Short single lines are typed as keys;
+//! anything else is pasted, in pieces when it is large, because some agent
+//! CLIs drop characters from one huge paste. Either way the pane is checked
+//! first, so keys are not swallowed by a pane someone left in copy mode.
+
+use std::thread;
+use std::time::Duration;
+
+use crate::config::{Config, Paste, SendGuard};
+use crate::error::{fail_with, ErrorKind, Result};
+use crate::tmux::{self, PaneState};
+
+/// How prompts are typed into one agent's panes.
+#[derive(Debug, Default, Clone)]
+pub struct Settings {
+    pub paste: Paste,
+    pub guard: SendGuard,
+}
+
+impl Settings {
+    pub fn for_agent(config: &Config, agent: &str) -> Self {
+        Self {
+            paste: config.paste(agent),
+            guard: config.send_guard(agent),
+        }
+    }
+}
+
+/// Type `text` into `pane` and press Enter.
+pub fn send(pane: &str, text: &str, settings: &Settings) -> Result<()> {
+    type_text(pane, text, settings, true)
+}
+
+/// Type `text` into `pane`, pressing Enter afterwards only if `enter`, so a
+/// prompt can be left in the input line for a person to finish.
+pub fn type_text(pane: &str, text: &str, settings: &Settings, enter: bool) -> Result<()> {
+    prepare(pane, &tmux::pane_state(pane)?, &settings.guard)?;
+
+    let paste = &settings.paste;
+    if !text.contains('\n') && text.len() <= paste.chunk_bytes {
+        return tmux::send_keys(pane, text, enter);
+    }
+    let pieces = chunks(text, paste.chunk_bytes);
+    if pieces.len() == 1 {
+        return tmux::paste_text(pane, text, enter);
+    }
+    let delay = Duration::from_millis(paste.chunk_delay_ms);
+    for piece in pieces {
+        tmux::paste_text(pane, piece, false)?;
+        thread::sleep(delay);
+    }
+    if enter {
+        tmux::press_enter(pane)?;
+    }
+    Ok(())
+}
+
+/// Make sure keys sent to `pane` reach its program: leave copy mode when the
+/// guard allows it, and refuse otherwise.
+fn prepare(pane: &str, state: &PaneState, guard: &SendGuard) -> Result<()> {
+    if let Some(mode) = &state.mode {
+        if !guard.exit_copy_mode {
+            return Err(fail_with(
+                ErrorKind::InvalidInput,
+                format!("pane {pane} is in {mode}; not sending keys into it"),
+                Some(
+                    "press q in the pane and retry, or set exit_copy_mode = true \
+                     under [agents.NAME.send]"
+                        .into(),
+                ),
+            ));
+        }
+        tmux::cancel_mode(pane)?;
+    }
+    if state.alternate_screen && guard.refuse_alternate_screen {
+        return Err(fail_with(
+            ErrorKind::InvalidInput,
+            format!(
+                "pane {pane} is showing a full-screen program (alternate screen); \
+                 not sending keys into it"
+            ),
+            Some("close the pager or editor in the pane and retry".into()),
+        ));
+    }
+    Ok(())
+}
+
+/// Split `text` into pieces of at most `max` bytes, cutting after the last
+/// newline in a piece when there is one and never inside a character.
+fn chunks(text: &str, max: usize) -> Vec<&str> {
+    let max = max.max(4);
+    let mut pieces = Vec::new();
+    let mut rest = text;
+    while rest.len() > max {
+        let mut end = max;
+        while !rest.is_char_boundary(end) {
+            end -= 1;
+        }
+        if let Some(newline) = rest[..end].rfind('\n') {
+            end = newline + 1;
+        }
+        let (piece, tail) = rest.split_at(end);
+        pieces.push(piece);
+        rest = tail;
+    }
+    if !rest.is_empty() {
+        pieces.push(rest);
+    }
+    pieces
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prepare_refuses_modes_and_guarded_screens() {
+        let copy_mode = PaneState {
+            mode: Some("copy-mode".into()),
+            alternate_screen: false,
+        };
+        let err = prepare("%1", &copy_mode, &SendGuard::default())
+            .expect_err("copy mode should be refused");
+        assert!(err.to_string().contains("pane %1 is in copy-mode"));
+
+        let full_screen = PaneState {
+            mode: None,
+            alternate_screen: true,
+        };
+        prepare("%1", &full_screen, &SendGuard::default())
+            .expect("alternate screen is allowed by default");
+        let guard = SendGuard {
+            exit_copy_mode: false,
+            refuse_alternate_screen: true,
+        };
+        assert!(prepare("%1", &full_screen, &guard).is_err());
+    }
+
+    #[test]
+    fn chunks_cut_after_newlines() {
+        assert_eq!(chunks("one\ntwo\nthree\n", 9), ["one\ntwo\n", "three\n"]);
+        assert_eq!(chunks("short", 100), ["short"]);
+        assert!(chunks("", 10).is_empty());
+    }
+
+    #[test]
+    fn chunks_split_long_lines_on_char_boundaries() {
+        let text = "ééééé";
+        let pieces = chunks(text, 5);
+        assert_eq!(pieces, ["éé", "éé", "é"]);
+        assert_eq!(pieces.concat(), text);
+    }
+}
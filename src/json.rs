@@ -0,0 +1,461 @@
+//! Minimal JSON value type with a stable (key-sorted) serializer for the
+//! machine-readable outputs, and a parser for reading other tools' JSON logs.
+
+use std::collections::BTreeMap;
+use std::fmt::{self, Write as _};
+use std::iter::Peekable;
+use std::str::Chars;
+
+use crate::error::{bail, Result};
+
+pub type Object = BTreeMap<String, Value>;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Integer(i64),
+    Float(f64),
+    String(String),
+    Array(Vec<Value>),
+    Object(Object),
+}
+
+impl Value {
+    /// Member `key` of an object; `None` for missing keys and non-objects.
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Object(entries) => entries.get(key),
+            _ => None,
+        }
+    }
+
+    /// Follow a chain of object keys, e.g. `["message", "usage"]`.
+    pub fn path(&self, keys: &[&str]) -> Option<&Value> {
+        keys.iter().try_fold(self, |value, key| value.get(key))
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Value::Integer(number) => Some(*number),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::Integer(number) => Some(*number as f64),
+            Value::Float(number) => Some(*number),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Value]> {
+        match self {
+            Value::Array(items) => Some(items),
+            _ => None,
+        }
+    }
+
+    pub fn as_u64(&self) -> Option<u64> {
+        match self {
+            Value::Integer(number) => u64::try_from(*number).ok(),
+            _ => None,
+        }
+    }
+
+    /// Render with two-space indentation.
+    pub fn to_pretty(&self) -> String {
+        let mut out = String::new();
+        write_pretty(&mut out, self, 0);
+        out
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Null => f.write_str("null"),
+            Value::Bool(flag) => write!(f, "{flag}"),
+            Value::Integer(number) => write!(f, "{number}"),
+            Value::Float(number) if number.is_finite() => write!(f, "{number}"),
+            Value::Float(_) => f.write_str("null"),
+            Value::String(text) => f.write_str(&quote(text)),
+            Value::Array(items) => {
+                f.write_str("[")?;
+                for (index, item) in items.iter().enumerate() {
+                    if index > 0 {
+                        f.write_str(",")?;
+                    }
+                    write!(f, "{item}")?;
+                }
+                f.write_str("]")
+            }
+            Value::Object(entries) => {
+                f.write_str("{")?;
+                for (index, (key, value)) in entries.iter().enumerate() {
+                    if index > 0 {
+                        f.write_str(",")?;
+                    }
+                    write!(f, "{}:{value}", quote(key))?;
+                }
+                f.write_str("}")
+            }
+        }
+    }
+}
+
+impl From<bool> for Value {
+    fn from(flag: bool) -> Self {
+        Value::Bool(flag)
+    }
+}
+
+impl From<i64> for Value {
+    fn from(number: i64) -> Self {
+        Value::Integer(number)
+    }
+}
+
+impl From<u64> for Value {
+    fn from(number: u64) -> Self {
+        Value::Integer(i64::try_from(number).unwrap_or(i64::MAX))
+    }
+}
+
+impl From<u32> for Value {
+    fn from(number: u32) -> Self {
+        Value::Integer(number.into())
+    }
+}
+
+impl From<usize> for Value {
+    fn from(number: usize) -> Self {
+        Value::Integer(i64::try_from(number).unwrap_or(i64::MAX))
+    }
+}
+
+impl From<f64> for Value {
+    fn from(number: f64) -> Self {
+        Value::Float(number)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(text: &str) -> Self {
+        Value::String(text.to_string())
+    }
+}
+
+impl From<String> for Value {
+    fn from(text: String) -> Self {
+        Value::String(text)
+    }
+}
+
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(Value::Null, Into::into)
+    }
+}
+
+impl<T: Into<Value>> From<Vec<T>> for Value {
+    fn from(items: Vec<T>) -> Self {
+        Value::Array(items.into_iter().map(Into::into).collect())
+    }
+}
+
+impl From<Object> for Value {
+    fn from(entries: Object) -> Self {
+        Value::Object(entries)
+    }
+}
+
+/// Build an object from `(key, value)` pairs.
+pub fn object<I, K, V>(entries: I) -> Value
+where
+    I: IntoIterator<Item = (K, V)>,
+    K: Into<String>,
+    V: Into<Value>,
+{
+    Value::Object(
+        entries
+            .into_iter()
+            .map(|(key, value)| (key.into(), value.into()))
+            .collect(),
+    )
+}
+
+/// Parse a complete JSON document.
+pub fn parse(text: &str) -> Result<Value> {
+    let mut chars = text.chars().peekable();
+    let value = parse_value(&mut chars)?;
+    skip_whitespace(&mut chars);
+    match chars.next() {
+        None => Ok(value),
+        Some(c) => bail(format!("unexpected '{c}' after JSON value")),
+    }
+}
+
+fn parse_value(chars: &mut Peekable<Chars>) -> Result<Value> {
+    skip_whitespace(chars);
+    match chars.peek() {
+        Some('{') => {
+            chars.next();
+            let mut entries = Object::new();
+            skip_whitespace(chars);
+            if chars.next_if_eq(&'}').is_some() {
+                return Ok(Value::Object(entries));
+            }
+            loop {
+                skip_whitespace(chars);
+                if chars.next() != Some('"') {
+                    return bail("expected a string key in JSON object");
+                }
+                let key = parse_string(chars)?;
+                skip_whitespace(chars);
+                if chars.next() != Some(':') {
+                    return bail("expected ':' in JSON object");
+                }
+                entries.insert(key, parse_value(chars)?);
+                skip_whitespace(chars);
+                match chars.next() {
+                    Some(',') => continue,
+                    Some('}') => return Ok(Value::Object(entries)),
+                    _ => return bail("expected ',' or '}' in JSON object"),
+                }
+            }
+        }
+        Some('[') => {
+            chars.next();
+            let mut items = Vec::new();
+            skip_whitespace(chars);
+            if chars.next_if_eq(&']').is_some() {
+                return Ok(Value::Array(items));
+            }
+            loop {
+                items.push(parse_value(chars)?);
+                skip_whitespace(chars);
+                match chars.next() {
+                    Some(',') => continue,
+                    Some(']') => return Ok(Value::Array(items)),
+                    _ => return bail("expected ',' or ']' in JSON array"),
+                }
+            }
+        }
+        Some('"') => {
+            chars.next();
+            Ok(Value::String(parse_string(chars)?))
+        }
+        Some('t') => parse_literal(chars, "true", Value::Bool(true)),
+        Some('f') => parse_literal(chars, "false", Value::Bool(false)),
+        Some('n') => parse_literal(chars, "null", Value::Null),
+        Some(c) if *c == '-' || c.is_ascii_digit() => parse_number(chars),
+        Some(c) => bail(format!("unexpected '{c}' in JSON")),
+        None => bail("unexpected end of JSON"),
+    }
+}
+
+fn parse_string(chars: &mut Peekable<Chars>) -> Result<String> {
+    let mut out = String::new();
+    loop {
+        match chars.next() {
+            Some('"') => return Ok(out),
+            Some('\\') => match chars.next() {
+                Some('n') => out.push('\n'),
+                Some('t') => out.push('\t'),
+                Some('r') => out.push('\r'),
+                Some('b') => out.push('\u{8}'),
+                Some('f') => out.push('\u{c}'),
+                Some('u') => {
+                    let high = parse_hex4(chars)?;
+                    let code = if (0xd800..0xdc00).contains(&high)
+                        && chars.next() == Some('\\')
+                        && chars.next() == Some('u')
+                    {
+                        let low = parse_hex4(chars)?;
+                        0x10000 + ((high - 0xd800) << 10) + (low.wrapping_sub(0xdc00) & 0x3ff)
+                    } else {
+                        high
+                    };
+                    out.push(char::from_u32(code).unwrap_or('\u{fffd}'));
+                }
+                Some(c) => out.push(c),
+                None => return bail("unterminated JSON string"),
+            },
+            Some(c) => out.push(c),
+            None => return bail("unterminated JSON string"),
+        }
+    }
+}
+
+fn parse_hex4(chars: &mut Peekable<Chars>) -> Result<u32> {
+    let digits: String = chars.take(4).collect();
+    match u32::from_str_radix(&digits, 16) {
+        Ok(code) if digits.len() == 4 => Ok(code),
+        _ => bail(format!("invalid \\u escape '{digits}' in JSON string")),
+    }
+}
+
+fn parse_number(chars: &mut Peekable<Chars>) -> Result<Value> {
+    let mut raw = String::new();
+    while let Some(c) = chars.next_if(|c| c.is_ascii_digit() || "+-.eE".contains(*c)) {
+        raw.push(c);
+    }
+    if let Ok(number) = raw.parse::<i64>() {
+        return Ok(Value::Integer(number));
+    }
+    match raw.parse::<f64>() {
+        Ok(number) => Ok(Value::Float(number)),
+        Err(_) => bail(format!("invalid JSON number '{raw}'")),
+    }
+}
+
+fn parse_literal(chars: &mut Peekable<Chars>, word: &str, value: Value) -> Result<Value> {
+    for expected in word.chars() {
+        if chars.next() != Some(expected) {
+            return bail(format!("invalid JSON literal, expected '{word}'"));
+        }
+    }
+    Ok(value)
+}
+
+fn skip_whitespace(chars: &mut Peekable<Chars>) {
+    while chars.next_if(|c| c.is_ascii_whitespace()).is_some() {}
+}
+
+fn write_pretty(out: &mut String, value: &Value, depth: usize) {
+    let indent = "  ".repeat(depth + 1);
+    let closing = "  ".repeat(depth);
+    match value {
+        Value::Array(items) if !items.is_empty() => {
+            out.push_str("[\n");
+            for (index, item) in items.iter().enumerate() {
+                out.push_str(&indent);
+                write_pretty(out, item, depth + 1);
+                out.push_str(if index + 1 < items.len() { ",\n" } else { "\n" });
+            }
+            out.push_str(&closing);
+            out.push(']');
+        }
+        Value::Object(entries) if !entries.is_empty() => {
+            out.push_str("{\n");
+            for (index, (key, item)) in entries.iter().enumerate() {
+                let _ = write!(out, "{indent}{}: ", quote(key));
+                write_pretty(out, item, depth + 1);
+                out.push_str(if index + 1 < entries.len() {
+                    ",\n"
+                } else {
+                    "\n"
+                });
+            }
+            out.push_str(&closing);
+            out.push('}');
+        }
+        other => {
+            let _ = write!(out, "{other}");
+        }
+    }
+}
+
+fn quote(text: &str) -> String {
+    let mut out = String::with_capacity(text.len() + 2);
+    out.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display_renders_compact_sorted_objects() {
+        let value = object([
+            ("name", Value::from("review")),
+            ("clients", Value::from(2u64)),
+            ("note", Value::Null),
+        ]);
+        assert_eq!(
+            value.to_string(),
+            r#"{"clients":2,"name":"review","note":null}"#
+        );
+    }
+
+    #[test]
+    fn quote_escapes_control_characters() {
+        let value = Value::from("a\"b\\c\nd\u{1}");
+        assert_eq!(value.to_string(), r#""a\"b\\c\nd\u0001""#);
+    }
+
+    #[test]
+    fn parse_reads_nested_documents() {
+        let value = parse(r#" {"message": {"usage": {"input_tokens": 12}, "id": "msg_1"}, "ok": [true, null, -1.5]} "#)
+            .expect("document should parse");
+        assert_eq!(
+            value
+                .path(&["message", "usage", "input_tokens"])
+                .and_then(Value::as_u64),
+            Some(12)
+        );
+        assert_eq!(
+            value.path(&["message", "id"]).and_then(Value::as_str),
+            Some("msg_1")
+        );
+        assert_eq!(
+            value.get("ok"),
+            Some(&Value::Array(vec![
+                Value::Bool(true),
+                Value::Null,
+                Value::Float(-1.5)
+            ]))
+        );
+    }
+
+    #[test]
+    fn parse_round_trips_escapes_and_rejects_garbage() {
+        let original = Value::from("tab\t quote\" snow \u{2603} \u{1f600}");
+        assert_eq!(
+            parse(&original.to_string()).expect("should parse"),
+            original
+        );
+        assert_eq!(
+            parse(r#""\u2603 \ud83d\ude00""#).expect("should parse"),
+            Value::from("\u{2603} \u{1f600}")
+        );
+        assert!(parse("{\"a\": 1,}").is_err());
+        assert!(parse("[1] 2").is_err());
+    }
+
+    #[test]
+    fn to_pretty_indents_nested_values() {
+        let value = object([
+            ("env", Value::from(vec!["A", "B"])),
+            ("empty", Value::Array(Vec::new())),
+        ]);
+        assert_eq!(
+            value.to_pretty(),
+            "{\n  \"empty\": [],\n  \"env\": [\n    \"A\",\n    \"B\"\n  ]\n}"
+        );
+    }
+}
@@ -0,0 +1,103 @@
+//! Restarting agents that exit on their own, for agents configured with
+//! `[agents.NAME.keep_alive]`.
+//!
+//! A circuit breaker stops the restarts when an agent keeps exiting: after more
+//! than `max_restarts` exits within `window_minutes`, the dead pane is left in
+//! place with its output, the session shows as `crash-looping` in `status`, and
+//! the `notify` command runs. Exits are counted from the run history, so every
+//! restart still leaves a report.
+
+use crate::config::KeepAlive;
+use crate::dnd;
+use crate::error::Result;
+use crate::hooks;
+use crate::output;
+use crate::report::{self, EndReason, RunReport};
+use crate::tmux;
+
+/// How many of the pane's last lines the notification carries.
+const TAIL_LINES: usize = 20;
+
+/// Respawn the agent that just exited with `exit`, or trip the breaker.
+pub fn restart(settings: &KeepAlive, exit: &RunReport) -> Result<()> {
+    let since = exit.ended.saturating_sub(settings.window_minutes * 60);
+    let history = report::recent(since)?;
+    let pane = tmux::agent_pane(&exit.session)?;
+    if !crash_looping(settings, exit, &history) {
+        return tmux::respawn_pane(&pane);
+    }
+
+    tmux::set_crash_looping(&exit.session, true)?;
+    if dnd::active(&exit.session).is_some() {
+        return Ok(());
+    }
+    if let Some(command) = &settings.notify {
+        let output = tmux::capture_pane(&pane, Some(200)).unwrap_or_default();
+        let name = tmux::identify(&exit.session)?.and_then(|(_, name)| name);
+        hooks::run_with_env(
+            "crash_loop",
+            command,
+            &exit.agent,
+            &exit.session,
+            name.as_deref(),
+            &[("AMUX_OUTPUT_TAIL", &output::tail(&output, TAIL_LINES))],
+        )?;
+    }
+    Ok(())
+}
+
+/// Whether this session's agent has exited more often than the policy allows.
+/// `history` holds the runs that ended within the window, `exit` included;
+/// only exits since the session was created count, so an earlier session that
+/// reused the name does not.
+fn crash_looping(settings: &KeepAlive, exit: &RunReport, history: &[RunReport]) -> bool {
+    let exits = history
+        .iter()
+        .filter(|run| {
+            run.reason == EndReason::Exited
+                && run.session == exit.session
+                && run.started == exit.started
+        })
+        .count();
+    exits as u64 > settings.max_restarts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn exit(started: u64, ended: u64) -> RunReport {
+        RunReport {
+            agent: "codex".into(),
+            session: "amux-codex".into(),
+            reason: EndReason::Exited,
+            started: Some(started),
+            ended,
+            cwd: None,
+            changes: None,
+            exit_status: Some(1),
+            log_path: None,
+            usage: None,
+            started_by: None,
+            removed_by: None,
+        }
+    }
+
+    #[test]
+    fn breaker_trips_after_too_many_exits_of_the_same_session() {
+        let settings = KeepAlive {
+            max_restarts: 2,
+            ..KeepAlive::default()
+        };
+        let mut history = vec![exit(100, 200), exit(100, 260)];
+        let latest = exit(100, 300);
+        history.push(latest.clone());
+        assert!(crash_looping(&settings, &latest, &history));
+        assert!(!crash_looping(&settings, &latest, &history[1..]));
+
+        // Exits of an older session with the same name do not count.
+        let fresh = exit(500, 510);
+        history.push(fresh.clone());
+        assert!(!crash_looping(&settings, &fresh, &history));
+    }
+}
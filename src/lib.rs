@@ -1,6 +1,74 @@
+pub mod activity;
+pub mod agent_edit;
 pub mod agents;
+pub mod apply;
+pub mod archive;
+pub mod auto_detach;
+pub mod bar;
+pub mod checklist;
+pub mod checkpoint;
 pub mod cli;
+pub mod clients;
+pub mod completions;
+pub mod config;
+pub mod dispatch;
+pub mod dnd;
+pub mod doctor;
 pub mod error;
+pub mod explain;
+pub mod git;
+pub mod heartbeat;
+pub mod here;
+pub mod hooks;
+pub mod hosts;
+pub mod html;
+pub mod ide;
+pub mod import;
+pub mod input;
+pub mod json;
+pub mod keep_alive;
+pub mod logs;
+pub mod matrix;
+pub mod messages;
+pub mod multiplexer;
+pub mod naming;
+pub mod nested;
+pub mod notify;
+pub mod open;
+pub mod origin;
+pub mod output;
+pub mod parallel;
+pub mod paths;
+pub mod pattern;
+pub mod pipe;
+pub mod plan;
+pub mod preflight;
+pub mod profile;
+pub mod readiness;
+pub mod relay;
+pub mod report;
+pub mod response;
+pub mod session_env;
+pub mod shell_init;
+pub mod snapshot;
+pub mod state;
+pub mod stats;
+pub mod status_diff;
+pub mod status_table;
+pub mod stop;
+pub mod tasks;
+pub mod template;
+pub mod theme;
+pub mod time;
 pub mod tmux;
+pub mod tmux_format;
+pub mod tmux_keys;
+pub mod toml;
+pub mod transfer;
+pub mod usage;
+pub mod webhook;
+pub mod worktree;
+pub mod yaml;
+pub mod zellij;
 
 pub use cli::run;
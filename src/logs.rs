@@ -0,0 +1,142 @@
+//! `amux logs`: what an agent printed, read back from the session's output
+//! log (`<state>/logs/<session>.log`, written through `tmux pipe-pane` from
+//! the moment the session starts), so output produced while nobody was
+//! attached is not lost. Terminal escape sequences are removed unless asked
+//! for, and `--follow` keeps printing until the session ends.
+
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
+
+use crate::error::{with_context, Result};
+use crate::output;
+use crate::tmux;
+
+const POLL: Duration = Duration::from_millis(250);
+
+/// How to print a log.
+#[derive(Clone, Copy, Debug)]
+pub struct View {
+    /// Only the last this many lines of what is already there.
+    pub lines: Option<usize>,
+    /// Keep escape sequences (colors) as written.
+    pub raw: bool,
+    /// Keep printing new output until the agent in `session` exits.
+    pub follow: bool,
+}
+
+/// Print `log`, which belongs to `session`, as `view` says.
+pub fn show(log: &Path, session: &str, view: View) -> Result<()> {
+    let mut file = File::open(log)
+        .map_err(|err| with_context(err, format!("failed to open {}", log.display())))?;
+    let mut bytes = Vec::new();
+    file.read_to_end(&mut bytes)
+        .map_err(|err| with_context(err, format!("failed to read {}", log.display())))?;
+    let text = render(&String::from_utf8_lossy(&bytes), view.raw);
+    let text = match view.lines {
+        Some(lines) => last_lines(&text, lines),
+        None => &text,
+    };
+    write(text)?;
+    if view.follow {
+        follow(&mut file, session, view.raw)?;
+    }
+    Ok(())
+}
+
+/// Print what is appended to `file` until the agent exits. Only whole
+/// lines are printed, so no escape sequence or character is cut in half.
+fn follow(file: &mut File, session: &str, raw: bool) -> Result<()> {
+    let mut pending = Vec::new();
+    loop {
+        let before = pending.len();
+        file.read_to_end(&mut pending)
+            .map_err(|err| with_context(err, "failed to read session log"))?;
+        let grew = pending.len() > before;
+        let ended = !grew && !running(session)?;
+        let cut = if ended {
+            pending.len()
+        } else {
+            complete_lines(&pending)
+        };
+        if cut > 0 {
+            let chunk: Vec<u8> = pending.drain(..cut).collect();
+            write(&render(&String::from_utf8_lossy(&chunk), raw))?;
+        }
+        if ended {
+            return Ok(());
+        }
+        if !grew {
+            thread::sleep(POLL);
+        }
+    }
+}
+
+/// Whether the agent in `session` can still print anything. A session whose
+/// agent exited stays around, dead, until its run report is written.
+fn running(session: &str) -> Result<bool> {
+    // The session may be removed between the two calls.
+    Ok(
+        tmux::has_session(session)?
+            && tmux::session_info(session).is_ok_and(|info| !info.pane_dead),
+    )
+}
+
+fn render(text: &str, raw: bool) -> String {
+    if raw {
+        text.to_string()
+    } else {
+        output::strip_ansi(text)
+    }
+}
+
+/// Length of `bytes` up to and including its last newline.
+fn complete_lines(bytes: &[u8]) -> usize {
+    bytes
+        .iter()
+        .rposition(|&byte| byte == b'\n')
+        .map_or(0, |index| index + 1)
+}
+
+/// The last `count` lines of `text`, blank ones included.
+fn last_lines(text: &str, count: usize) -> &str {
+    let body = text.strip_suffix('\n').unwrap_or(text);
+    if count == 0 {
+        return "";
+    }
+    match body.match_indices('\n').rev().nth(count - 1) {
+        Some((index, _)) => &text[index + 1..],
+        None => text,
+    }
+}
+
+fn write(text: &str) -> Result<()> {
+    let mut stdout = io::stdout().lock();
+    stdout
+        .write_all(text.as_bytes())
+        .and_then(|()| stdout.flush())
+        .map_err(|err| with_context(err, "failed to write output"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn last_lines_keeps_blank_lines_and_the_final_newline() {
+        let text = "one\n\nthree\nfour\n";
+        assert_eq!(last_lines(text, 3), "\nthree\nfour\n");
+        assert_eq!(last_lines(text, 10), text);
+        assert_eq!(last_lines("no newline", 1), "no newline");
+        assert_eq!(last_lines(text, 0), "");
+    }
+
+    #[test]
+    fn complete_lines_holds_back_a_partial_line() {
+        assert_eq!(complete_lines(b"done\n\x1b[3"), 5);
+        assert_eq!(complete_lines(b"partial"), 0);
+        assert_eq!(complete_lines(b"a\nb\n"), 4);
+    }
+}
@@ -1,8 +1,9 @@
+use amux::error;
 use amux::run;
 
 fn main() {
     if let Err(err) = run() {
-        eprintln!("amux: {}", err);
+        error::report(err.as_ref());
         std::process::exit(1);
     }
 }
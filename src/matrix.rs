@@ -0,0 +1,85 @@
+//! `amux start AGENT --matrix NAME`: the sessions a parameter matrix expands
+//! to, one per combination of its axis values, for trying several models or
+//! settings on the same task side by side.
+
+use crate::config::Matrix;
+use crate::naming;
+
+/// One session of a matrix.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Combination {
+    /// Session name: the values, in axis order, joined with `-`.
+    pub name: String,
+    /// The matrix's params with this combination's values filled in.
+    pub params: String,
+}
+
+/// Every combination of `matrix`'s axes, axes in name order. `prefix` (from
+/// `-n`) goes in front of each session name.
+pub fn combinations(matrix: &Matrix, prefix: Option<&str>) -> Vec<Combination> {
+    let mut picks: Vec<Vec<(&str, &str)>> = vec![Vec::new()];
+    for (axis, values) in &matrix.axes {
+        picks = picks
+            .into_iter()
+            .flat_map(|pick| {
+                values.iter().map(move |value| {
+                    let mut pick = pick.clone();
+                    pick.push((axis.as_str(), value.as_str()));
+                    pick
+                })
+            })
+            .collect();
+    }
+
+    picks
+        .into_iter()
+        .map(|pick| {
+            let mut parts: Vec<String> = prefix.into_iter().map(str::to_string).collect();
+            parts.extend(pick.iter().map(|(_, value)| naming::sanitize(value)));
+            let mut params = matrix.params.clone();
+            for (axis, value) in &pick {
+                // Params are split like a command line, so keep each value one word.
+                params = params.replace(&format!("{{{axis}}}"), &shell_words::quote(value));
+            }
+            Combination {
+                name: parts.join("-"),
+                params,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+
+    fn matrix() -> Matrix {
+        Matrix {
+            params: "--model {model} --effort {effort}".into(),
+            axes: BTreeMap::from([
+                ("model".to_string(), vec!["opus".into(), "sonnet 4".into()]),
+                ("effort".to_string(), vec!["low".into(), "high".into()]),
+            ]),
+        }
+    }
+
+    #[test]
+    fn combinations_cover_every_pair_of_values() {
+        let combinations = combinations(&matrix(), None);
+        let names: Vec<&str> = combinations.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(
+            names,
+            ["low-opus", "low-sonnet-4", "high-opus", "high-sonnet-4"]
+        );
+        assert_eq!(combinations[1].params, "--model 'sonnet 4' --effort low");
+    }
+
+    #[test]
+    fn combinations_put_the_prefix_first() {
+        let combinations = combinations(&matrix(), Some("eval"));
+        assert_eq!(combinations[0].name, "eval-low-opus");
+        assert_eq!(combinations[0].params, "--model opus --effort low");
+    }
+}
@@ -0,0 +1,229 @@
+//! User-facing status and error text, looked up by stable message id.
+//!
+//! English is built in. Set `AMUX_LOCALE` (or `locale` in the config file) to
+//! load `<data>/locales/<locale>.toml`, a flat table mapping message ids to
+//! translated text with the same `{placeholders}`. Missing or broken entries
+//! fall back to English, so a partial translation is always safe. Scripts
+//! should rely on ids and `--json-errors` kinds, never on the text.
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::sync::OnceLock;
+
+use crate::config;
+use crate::error::{with_context, Result};
+use crate::paths;
+use crate::template;
+use crate::toml::{self, Value};
+
+const ENGLISH: &[(&str, &str)] = &[
+    ("started", "{agent}: started in session '{session}'"),
+    (
+        "already_running",
+        "{agent}: session '{session}' already running (use --force to restart)",
+    ),
+    (
+        "already_running_short",
+        "{agent}: session '{session}' already running",
+    ),
+    ("adopted", "{agent}: adopted existing session '{session}'"),
+    ("start_failed", "{agent}: failed ({error})"),
+    ("group_empty", "@{group}: no agents in group"),
+    (
+        "no_session",
+        "{agent}: no active session (looked for '{session}')",
+    ),
+    (
+        "no_session_start_hint",
+        "{agent}: no active session (looked for '{session}'); pass --start to launch",
+    ),
+    ("removed", "{agent}: removed session '{session}'"),
+    (
+        "stopping",
+        "{agent}: stopping '{session}' (waiting up to {seconds}s)",
+    ),
+    ("stopped", "{agent}: stopped session '{session}'"),
+    (
+        "stop_timed_out",
+        "{agent}: '{session}' did not exit within {seconds}s; killing it",
+    ),
+    ("pinned", "{agent}: pinned session '{session}'"),
+    ("unpinned", "{agent}: unpinned session '{session}'"),
+    ("note_set", "{agent}: noted session '{session}'"),
+    ("note_cleared", "{agent}: cleared the note on '{session}'"),
+    ("no_note", "{agent}: session '{session}' has no note"),
+    ("dnd_on", "{agent}: muted '{session}' {remaining}"),
+    ("dnd_off", "{agent}: unmuted '{session}'"),
+    (
+        "auto_detach_on",
+        "{agent}: clients of '{session}' are detached after {minutes} idle minute(s)",
+    ),
+    (
+        "auto_detach_off",
+        "{agent}: clients of '{session}' stay attached when idle",
+    ),
+    (
+        "pinned_refused",
+        "{agent}: session '{session}' is pinned; not removing it",
+    ),
+    (
+        "pinned_skipped",
+        "Skipping {count} pinned session(s); pass --include-pinned to review them.",
+    ),
+    (
+        "pinned_kept",
+        "Keeping {count} pinned session(s); pass --include-pinned to remove them too.",
+    ),
+    (
+        "attach_shared",
+        "{agent}: {count} client(s) already attached to '{session}':",
+    ),
+    (
+        "attach_taking_over",
+        "{agent}: detaching {count} client(s) from '{session}':",
+    ),
+    (
+        "attach_take_over_hint",
+        "Attaching alongside them; pass --take-over to detach them first.",
+    ),
+    ("no_clients", "{agent}: no clients to detach"),
+    ("detached", "{agent}: detached clients from '{session}'"),
+    ("sent", "{agent}: sent {bytes} bytes to '{session}'"),
+    ("copied", "{agent}: copied {source} to {destination}"),
+    (
+        "output_matched",
+        "{agent}: output matched '{pattern}' in '{session}'",
+    ),
+    (
+        "response_complete",
+        "{agent}: response complete in '{session}'",
+    ),
+    (
+        "timed_out_pattern",
+        "{agent}: timed out after {seconds}s waiting for '{pattern}' in '{session}'",
+    ),
+    ("ready", "{agent}: ready in session '{session}'"),
+    (
+        "timed_out_ready",
+        "{agent}: not ready after {seconds}s in session '{session}'",
+    ),
+    (
+        "timed_out_response",
+        "{agent}: timed out after {seconds}s waiting for a response in '{session}'",
+    ),
+    ("agent_not_running", "{agent}: not running"),
+    ("agent_no_sessions", "{agent}: no sessions running."),
+    ("no_sessions", "No agent sessions are running."),
+    ("no_exited_sessions", "No exited sessions to prune."),
+    ("nothing_removed", "Nothing removed."),
+    ("removed_count", "Removed {count} amux session(s)."),
+    (
+        "no_stranded_sessions",
+        "No amux sessions on your default tmux server.",
+    ),
+    ("nothing_moved", "Nothing moved."),
+    (
+        "moved_count",
+        "Moved {count} amux session(s) to `{server}`.",
+    ),
+    (
+        "stranded_sessions",
+        "{count} amux session(s) still run on your default tmux server; \
+         `amux migrate-socket` moves them to amux's own",
+    ),
+];
+
+static CATALOG: OnceLock<BTreeMap<String, String>> = OnceLock::new();
+
+/// Render message `id` in the selected locale with `args` filled in.
+pub fn text(id: &str, args: &[(&str, &str)]) -> String {
+    let catalog = CATALOG.get_or_init(load_catalog);
+    if let Some(rendered) = catalog.get(id).and_then(|raw| render(raw, args).ok()) {
+        return rendered;
+    }
+    let english = ENGLISH
+        .iter()
+        .find(|(key, _)| *key == id)
+        .map_or(id, |(_, text)| text);
+    render(english, args).unwrap_or_else(|_| english.to_string())
+}
+
+/// The selected locale, if any: `AMUX_LOCALE`, then `locale` in the config.
+pub fn locale() -> Option<String> {
+    env::var("AMUX_LOCALE")
+        .ok()
+        .filter(|locale| !locale.is_empty())
+        .or_else(|| config::load().ok().and_then(|config| config.locale))
+}
+
+fn load_catalog() -> BTreeMap<String, String> {
+    let Some(locale) = locale() else {
+        return BTreeMap::new();
+    };
+    match read_catalog(&locale) {
+        Ok(catalog) => catalog,
+        Err(err) => {
+            eprintln!("amux: warning: using English messages: {err}");
+            BTreeMap::new()
+        }
+    }
+}
+
+fn read_catalog(locale: &str) -> Result<BTreeMap<String, String>> {
+    let path = paths::locales_dir()?.join(format!("{locale}.toml"));
+    let raw = fs::read_to_string(&path)
+        .map_err(|err| with_context(err, format!("failed to read {}", path.display())))?;
+    parse_catalog(&raw).map_err(|err| with_context(err, format!("invalid {}", path.display())))
+}
+
+fn parse_catalog(raw: &str) -> Result<BTreeMap<String, String>> {
+    Ok(toml::parse(raw)?
+        .into_iter()
+        .filter_map(|(id, value)| match value {
+            Value::String(text) => Some((id, text)),
+            _ => None,
+        })
+        .collect())
+}
+
+fn render(raw: &str, args: &[(&str, &str)]) -> Result<String> {
+    template::expand_with(raw, |key| {
+        Ok(args
+            .iter()
+            .find(|(name, _)| *name == key)
+            .map(|(_, value)| value.to_string()))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn english_ids_are_unique() {
+        let mut ids: Vec<_> = ENGLISH.iter().map(|(id, _)| *id).collect();
+        ids.sort_unstable();
+        ids.dedup();
+        assert_eq!(ids.len(), ENGLISH.len());
+    }
+
+    #[test]
+    fn render_fills_placeholders_and_rejects_unknown_ones() {
+        let args = [("agent", "codex"), ("session", "amux-codex")];
+        assert_eq!(
+            render("{agent}: removed session '{session}'", &args).expect("should render"),
+            "codex: removed session 'amux-codex'"
+        );
+        assert!(render("{agent} {bogus}", &args).is_err());
+    }
+
+    #[test]
+    fn parse_catalog_keeps_string_entries() {
+        let catalog =
+            parse_catalog("removed = \"{agent}: Sitzung '{session}' entfernt\"\nbad = 1\n")
+                .expect("catalog should parse");
+        assert_eq!(catalog.len(), 1);
+        assert!(catalog["removed"].contains("entfernt"));
+    }
+}
@@ -0,0 +1,175 @@
+//! The terminal multiplexer amux runs agents in. tmux is the default and the
+//! only backend with every feature; `--backend zellij` (or `backend =
+//! "zellij"` in the config, or `AMUX_BACKEND`) runs sessions in Zellij
+//! instead, for machines without tmux. The [`Multiplexer`] trait holds what
+//! every backend provides: starting, listing, attaching to, reading from,
+//! typing into, and removing sessions. The command handlers go through it, so
+//! `start`, `attach`, `status`, `send`, `capture`, and `rm` behave the same on
+//! every backend; commands that need more than it offers are refused up front
+//! on the others.
+
+use std::env;
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+use crate::error::{bail, DynError, Result};
+use crate::input::{self, Settings};
+use crate::tmux::{self, SessionDetail};
+use crate::zellij::Zellij;
+
+static BACKEND: OnceLock<Backend> = OnceLock::new();
+
+/// Session operations every backend supports.
+pub trait Multiplexer {
+    fn name(&self) -> &'static str;
+    /// The amux sessions that are running, with what the backend knows about them.
+    fn list_sessions(&self) -> Result<Vec<SessionDetail>>;
+    fn has_session(&self, session: &str) -> Result<bool>;
+    /// Whether amux started `session`, as opposed to something else using an
+    /// `amux-*` name.
+    fn is_managed(&self, session: &str) -> Result<bool>;
+    /// Whether sessions carry amux's options, hooks, and watchers: labels,
+    /// pins, notes, output tracking, exit reports. Without them a session is
+    /// just the agent running, and the handlers skip those steps.
+    fn holds_options(&self) -> bool;
+    /// Start `command_tokens` detached in a new session.
+    fn new_session(
+        &self,
+        session: &str,
+        command_tokens: &[String],
+        env: &[(String, String)],
+        cwd: Option<&Path>,
+    ) -> Result<()>;
+    /// Attach the current terminal, returning once it detaches; `take_over`
+    /// detaches the other clients first.
+    fn attach(&self, session: &str, take_over: bool) -> Result<()>;
+    fn kill_session(&self, session: &str) -> Result<()>;
+    /// The agent's screen; `history` adds scrollback above it.
+    fn capture(&self, session: &str, history: Option<usize>) -> Result<String>;
+    /// Type `text` into the agent, then press Enter when `enter`. `settings`
+    /// say how to split long pastes, for backends that type in pieces.
+    fn send_text(&self, session: &str, text: &str, settings: &Settings, enter: bool) -> Result<()>;
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Backend {
+    #[default]
+    Tmux,
+    Zellij,
+}
+
+impl Backend {
+    pub fn multiplexer(self) -> &'static dyn Multiplexer {
+        match self {
+            Self::Tmux => &Tmux,
+            Self::Zellij => &Zellij,
+        }
+    }
+}
+
+impl FromStr for Backend {
+    type Err = DynError;
+
+    fn from_str(raw: &str) -> Result<Self> {
+        match raw {
+            "tmux" => Ok(Self::Tmux),
+            "zellij" => Ok(Self::Zellij),
+            other => bail(format!(
+                "unknown backend '{other}' (expected tmux or zellij)"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Backend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.multiplexer().name())
+    }
+}
+
+/// Pick the backend for this process: `flag`, else `AMUX_BACKEND`, else
+/// `configured()`, which is only read when needed.
+pub fn select(flag: Option<&str>, configured: impl FnOnce() -> Backend) -> Result<Backend> {
+    let backend = match flag {
+        Some(raw) => raw.parse()?,
+        None => match env::var("AMUX_BACKEND") {
+            Ok(raw) if !raw.is_empty() => raw.parse()?,
+            _ => configured(),
+        },
+    };
+    let _ = BACKEND.set(backend);
+    Ok(backend)
+}
+
+/// The backend [`select`] picked, or tmux before it runs.
+pub fn backend() -> Backend {
+    BACKEND.get().copied().unwrap_or_default()
+}
+
+/// The tmux backend, through the functions in [`crate::tmux`].
+pub struct Tmux;
+
+impl Multiplexer for Tmux {
+    fn name(&self) -> &'static str {
+        "tmux"
+    }
+
+    fn list_sessions(&self) -> Result<Vec<SessionDetail>> {
+        tmux::list_sessions()
+    }
+
+    fn has_session(&self, session: &str) -> Result<bool> {
+        tmux::has_session(session)
+    }
+
+    fn is_managed(&self, session: &str) -> Result<bool> {
+        tmux::is_amux_session(session)
+    }
+
+    fn holds_options(&self) -> bool {
+        true
+    }
+
+    fn new_session(
+        &self,
+        session: &str,
+        command_tokens: &[String],
+        env: &[(String, String)],
+        cwd: Option<&Path>,
+    ) -> Result<()> {
+        tmux::new_session(session, command_tokens, env, cwd)
+    }
+
+    fn attach(&self, session: &str, take_over: bool) -> Result<()> {
+        tmux::attach_session(session, take_over)
+    }
+
+    fn kill_session(&self, session: &str) -> Result<()> {
+        tmux::kill_session(session)
+    }
+
+    fn capture(&self, session: &str, history: Option<usize>) -> Result<String> {
+        tmux::capture_pane(&tmux::agent_pane(session)?, history)
+    }
+
+    fn send_text(&self, session: &str, text: &str, settings: &Settings, enter: bool) -> Result<()> {
+        input::type_text(&tmux::agent_pane(session)?, text, settings, enter)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backends_parse_and_print_their_names() {
+        for backend in [Backend::Tmux, Backend::Zellij] {
+            let name = backend.to_string();
+            assert_eq!(name.parse::<Backend>().expect("known backend"), backend);
+        }
+        assert!("screen".parse::<Backend>().is_err());
+        assert_eq!(Backend::default(), Backend::Tmux);
+    }
+}
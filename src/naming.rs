@@ -0,0 +1,168 @@
+//! Generated session names for agents started without `-n`: from the git
+//! branch, or fresh ones when the default session is already running.
+
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::error::{bail, DynError, Result};
+use crate::git;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AutoName {
+    /// `2`, `3`, ... — the lowest free index.
+    Index,
+    /// The current git branch (sanitized), falling back to an index outside a repo.
+    Branch,
+    /// A random `adjective-animal` pair.
+    Petname,
+}
+
+impl FromStr for AutoName {
+    type Err = DynError;
+
+    fn from_str(raw: &str) -> Result<Self> {
+        match raw {
+            "index" => Ok(Self::Index),
+            "branch" => Ok(Self::Branch),
+            "petname" => Ok(Self::Petname),
+            other => bail(format!(
+                "unknown auto-name strategy '{other}' (expected index, branch, or petname)"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for AutoName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Index => "index",
+            Self::Branch => "branch",
+            Self::Petname => "petname",
+        })
+    }
+}
+
+const ADJECTIVES: &[&str] = &[
+    "amber", "brave", "calm", "dapper", "eager", "fuzzy", "gentle", "humble", "jolly", "keen",
+    "lucky", "mellow", "nimble", "plucky", "quiet", "rapid", "sunny", "tidy", "vivid", "witty",
+];
+
+const ANIMALS: &[&str] = &[
+    "badger", "crane", "dingo", "egret", "ferret", "gecko", "heron", "ibis", "jackal", "koala",
+    "lemur", "marten", "newt", "otter", "panda", "quail", "raven", "stoat", "tapir", "wombat",
+];
+
+/// Pick a session name for `strategy` that `is_taken` rejects, starting from `cwd`.
+pub fn generate(strategy: AutoName, cwd: &Path, is_taken: impl Fn(&str) -> bool) -> Result<String> {
+    let base = match strategy {
+        AutoName::Index => return Ok(first_free_index(&is_taken)),
+        AutoName::Branch => match git::branch(cwd)? {
+            Some(branch) => sanitize(&branch),
+            None => return Ok(first_free_index(&is_taken)),
+        },
+        AutoName::Petname => petname(seed()),
+    };
+    if base.is_empty() {
+        return Ok(first_free_index(&is_taken));
+    }
+    Ok(with_suffix(&base, &is_taken))
+}
+
+/// The session name for `--name-from-branch`: the branch checked out in
+/// `cwd`, sanitized, or `None` outside a repository or on a detached HEAD.
+pub fn from_branch(cwd: &Path) -> Result<Option<String>> {
+    Ok(git::branch(cwd)?
+        .map(|branch| sanitize(&branch))
+        .filter(|name| !name.is_empty()))
+}
+
+/// Map arbitrary text (a branch name, say) onto the characters allowed in session names.
+pub fn sanitize(raw: &str) -> String {
+    let mut name = String::with_capacity(raw.len());
+    for c in raw.chars() {
+        let c = if c.is_ascii_alphanumeric() || c == '_' {
+            c
+        } else {
+            '-'
+        };
+        if c == '-' && (name.is_empty() || name.ends_with('-')) {
+            continue;
+        }
+        name.push(c);
+    }
+    name.trim_end_matches('-').to_string()
+}
+
+fn first_free_index(is_taken: &impl Fn(&str) -> bool) -> String {
+    (2u32..)
+        .map(|index| index.to_string())
+        .find(|name| !is_taken(name))
+        .expect("some index is free")
+}
+
+fn with_suffix(base: &str, is_taken: &impl Fn(&str) -> bool) -> String {
+    if !is_taken(base) {
+        return base.to_string();
+    }
+    (2u32..)
+        .map(|index| format!("{base}-{index}"))
+        .find(|name| !is_taken(name))
+        .expect("some suffix is free")
+}
+
+fn petname(seed: u64) -> String {
+    // One round of a 64-bit mix so nearby seeds give unrelated names.
+    let mut x = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^= x >> 31;
+    let adjective = ADJECTIVES[(x % ADJECTIVES.len() as u64) as usize];
+    let animal = ANIMALS[((x >> 32) % ANIMALS.len() as u64) as usize];
+    format!("{adjective}-{animal}")
+}
+
+fn seed() -> u64 {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_nanos() as u64)
+        .unwrap_or_default();
+    nanos ^ (u64::from(std::process::id()) << 32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sanitize_maps_branch_names_to_identifiers() {
+        assert_eq!(sanitize("feature/login-form"), "feature-login-form");
+        assert_eq!(sanitize("fix//weird..name/"), "fix-weird-name");
+        assert_eq!(sanitize("///"), "");
+    }
+
+    #[test]
+    fn generate_index_skips_taken_names() {
+        let name = generate(AutoName::Index, Path::new("/"), |name| {
+            name == "2" || name == "3"
+        })
+        .expect("index should be generated");
+        assert_eq!(name, "4");
+    }
+
+    #[test]
+    fn with_suffix_appends_counter_on_collision() {
+        assert_eq!(with_suffix("main", &|_: &str| false), "main");
+        assert_eq!(with_suffix("main", &|name: &str| name == "main"), "main-2");
+    }
+
+    #[test]
+    fn petname_is_a_valid_identifier() {
+        for seed in 0..50 {
+            let name = petname(seed);
+            assert_eq!(sanitize(&name), name);
+            assert!(name.contains('-'));
+        }
+    }
+}
@@ -0,0 +1,64 @@
+//! amux run from inside one of its own agent sessions, typically by the agent
+//! through a shell tool or an MCP server. Every session's environment carries
+//! `AMUX_SESSION`, so such a call can be told apart from one made by a person
+//! in their own terminal: it may not attach (that would nest a tmux client in
+//! the agent's pane), and what it starts or removes is marked in the history.
+
+use std::env;
+
+use crate::error::{fail_with, DynError, ErrorKind, Result};
+
+/// The agent session this amux runs in, if any.
+pub fn caller() -> Option<String> {
+    caller_from(env::var("AMUX_SESSION").ok())
+}
+
+fn caller_from(marker: Option<String>) -> Option<String> {
+    marker.filter(|session| !session.trim().is_empty())
+}
+
+/// Refuse `action`, which needs a terminal of its own, when run inside an
+/// agent session; `hint` says what to do instead.
+pub fn refuse_interactive(action: &str, hint: &str) -> Result<()> {
+    let Some(caller) = caller() else {
+        return Ok(());
+    };
+    Err(refusal(action, &caller, hint))
+}
+
+fn refusal(action: &str, caller: &str, hint: &str) -> DynError {
+    fail_with(
+        ErrorKind::InvalidInput,
+        format!(
+            "refusing to {action} from inside agent session '{caller}'; \
+             it would nest a tmux client in that pane"
+        ),
+        Some(hint.to_string()),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::AmuxError;
+
+    #[test]
+    fn caller_needs_a_non_empty_marker() {
+        assert_eq!(caller_from(None), None);
+        assert_eq!(caller_from(Some(" ".into())), None);
+        assert_eq!(
+            caller_from(Some("amux-codex".into())).as_deref(),
+            Some("amux-codex")
+        );
+    }
+
+    #[test]
+    fn refusal_names_the_calling_session() {
+        let err = refusal("attach", "amux-codex", "use `amux send`");
+        assert!(err
+            .to_string()
+            .contains("refusing to attach from inside agent session 'amux-codex'"));
+        let hint = err.downcast_ref::<AmuxError>().and_then(AmuxError::hint);
+        assert_eq!(hint, Some("use `amux send`"));
+    }
+}
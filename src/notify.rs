@@ -0,0 +1,579 @@
+//! Chat notifications about agent events (it needs input, it finished, it
+//! crashed) with the agent's exit status and the pane's last lines, posted to
+//! Slack, Discord, or any webhook with `curl`, or shown on the desktop.
+//!
+//! Messages posted through a Slack bot token are remembered with the session
+//! they came from, so `amux notify bridge` can poll their threads and type
+//! replies into that session with `amux send`.
+
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::Duration;
+
+use crate::config::Notify;
+use crate::dnd;
+use crate::error::{fail_with, with_context, ErrorKind, Result};
+use crate::json::{self, Value};
+use crate::output;
+use crate::paths;
+use crate::response::{self, Boundary};
+use crate::state;
+use crate::tmux;
+
+/// Discord rejects messages longer than this.
+const DISCORD_LIMIT: usize = 2000;
+
+/// Desktop notifications show a few lines at most; longer bodies are cut.
+const DESKTOP_LIMIT: usize = 240;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Event {
+    NeedsInput,
+    Finished,
+    Crashed,
+}
+
+impl Event {
+    /// The name used in `[notify] events`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::NeedsInput => "needs_input",
+            Self::Finished => "finished",
+            Self::Crashed => "crashed",
+        }
+    }
+
+    fn summary(self) -> &'static str {
+        match self {
+            Self::NeedsInput => "is waiting for input",
+            Self::Finished => "finished",
+            Self::Crashed => "crashed",
+        }
+    }
+}
+
+/// The session an event is about.
+pub struct Source<'a> {
+    pub agent: &'a str,
+    pub name: Option<&'a str>,
+    pub session: &'a str,
+    /// The agent's exit status, for `finished` and `crashed`.
+    pub exit_status: Option<i32>,
+}
+
+impl Source<'_> {
+    fn label(&self) -> String {
+        match self.name {
+            Some(name) => format!("{}/{name}", self.agent),
+            None => self.agent.to_string(),
+        }
+    }
+}
+
+/// Post `event` with the last lines of `output` to every configured sink that
+/// wants it. A sink failing is reported and does not stop the others.
+pub fn post(settings: &Notify, event: Event, source: &Source, output: &str) {
+    if !settings.wants(event.as_str()) {
+        return;
+    }
+    let tail = output::tail(output, settings.tail_lines);
+    let text = message(event, source, &tail);
+    if let Some(url) = &settings.slack_webhook {
+        let payload = json::object([("text", Value::from(text.as_str()))]);
+        warn("Slack", post_json(url, None, &payload).map(drop));
+    }
+    if let Some(url) = &settings.discord_webhook {
+        let content = truncate(&text, DISCORD_LIMIT);
+        let payload = json::object([("content", Value::from(content))]);
+        warn("Discord", post_json(url, None, &payload).map(drop));
+    }
+    if let (Some(token), Some(channel)) = (&settings.slack_token, &settings.slack_channel) {
+        warn("Slack", post_to_thread(token, channel, &text, source));
+    }
+    if let Some(url) = &settings.webhook {
+        let payload = webhook_payload(event, source, &text, &tail);
+        warn("webhook", post_json(url, None, &payload).map(drop));
+    }
+    if settings.desktop {
+        warn(
+            "desktop",
+            show_on_desktop(&headline(event, source), &truncate(&tail, DESKTOP_LIMIT)),
+        );
+    }
+}
+
+fn warn(sink: &str, result: Result<()>) {
+    if let Err(err) = result {
+        eprintln!("amux: warning: {sink} notification not sent: {err}");
+    }
+}
+
+/// `codex/review crashed with exit status 1`.
+fn headline(event: Event, source: &Source) -> String {
+    let mut text = format!("{} {}", source.label(), event.summary());
+    if let (Event::Finished | Event::Crashed, Some(status)) = (event, source.exit_status) {
+        text.push_str(&format!(" with exit status {status}"));
+    }
+    text
+}
+
+fn message(event: Event, source: &Source, tail: &str) -> String {
+    let mut text = format!("{} (session `{}`)", headline(event, source), source.session);
+    if !tail.is_empty() {
+        text.push_str(&format!("\n```\n{}\n```", tail.replace("```", "'''")));
+    }
+    text
+}
+
+/// Everything about the event for a generic webhook, with the output uncut.
+fn webhook_payload(event: Event, source: &Source, text: &str, tail: &str) -> Value {
+    json::object([
+        ("event", Value::from(event.as_str())),
+        ("agent", Value::from(source.agent)),
+        ("name", Value::from(source.name)),
+        ("session", Value::from(source.session)),
+        (
+            "exit_status",
+            Value::from(source.exit_status.map(i64::from)),
+        ),
+        ("text", Value::from(text)),
+        ("output", Value::from(tail)),
+    ])
+}
+
+/// Show a notification with `notify-send`, or `osascript` on macOS.
+fn show_on_desktop(title: &str, body: &str) -> Result<()> {
+    let mut command = if cfg!(target_os = "macos") {
+        let quote = |text: &str| format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""));
+        let script = format!(
+            "display notification {} with title {}",
+            quote(body),
+            quote(title)
+        );
+        let mut command = Command::new("osascript");
+        command.args(["-e", &script]);
+        command
+    } else {
+        let mut command = Command::new("notify-send");
+        // Agent output can start with '-'; keep it from reading as an option.
+        command.args(["--app-name", "amux", "--", title, body]);
+        command
+    };
+    let status = command
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map_err(|err| with_context(err, "failed to run the desktop notifier"))?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(fail_with(
+            ErrorKind::Other,
+            format!("the desktop notifier exited with {status}"),
+            None,
+        ))
+    }
+}
+
+/// `text` cut to at most `limit` characters, keeping the end, which holds the
+/// newest output.
+fn truncate(text: &str, limit: usize) -> String {
+    let count = text.chars().count();
+    if count <= limit {
+        return text.to_string();
+    }
+    let kept: String = text.chars().skip(count - limit + 1).collect();
+    format!("…{kept}")
+}
+
+/// Post through the Slack API and remember the message so replies in its
+/// thread reach the session.
+fn post_to_thread(token: &str, channel: &str, text: &str, source: &Source) -> Result<()> {
+    let payload = json::object([
+        ("channel", Value::from(channel)),
+        ("text", Value::from(text)),
+    ]);
+    let reply = slack_reply(post_json(
+        "https://slack.com/api/chat.postMessage",
+        Some(token),
+        &payload,
+    )?)?;
+    let (Some(channel), Some(ts)) = (
+        reply.get("channel").and_then(Value::as_str),
+        reply.get("ts").and_then(Value::as_str),
+    ) else {
+        return Ok(());
+    };
+    let _lock = state::lock(&paths::notify_threads_file()?)?;
+    let mut threads = load_threads()?;
+    threads.push(Thread {
+        channel: channel.to_string(),
+        ts: ts.to_string(),
+        seen: ts.to_string(),
+        agent: source.agent.to_string(),
+        name: source.name.map(str::to_string),
+        session: source.session.to_string(),
+    });
+    save_threads(&threads)
+}
+
+fn post_json(url: &str, token: Option<&str>, payload: &Value) -> Result<String> {
+    let auth = token.map(|token| format!("Authorization: Bearer {token}"));
+    let mut options = vec![
+        ("url", url),
+        ("header", "Content-Type: application/json; charset=utf-8"),
+    ];
+    if let Some(auth) = &auth {
+        options.push(("header", auth));
+    }
+    let payload = payload.to_string();
+    options.push(("data-binary", &payload));
+    curl(&options)
+}
+
+/// Run curl with `options` passed as a config file on stdin, which keeps
+/// tokens and webhook URLs out of the process list.
+fn curl(options: &[(&str, &str)]) -> Result<String> {
+    let mut child = Command::new("curl")
+        .args(["--silent", "--show-error", "--fail", "--max-time", "20"])
+        .args(["--config", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| with_context(err, "failed to run curl"))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(curl_config(options).as_bytes())
+            .map_err(|err| with_context(err, "failed to pass options to curl"))?;
+    }
+    let output = child
+        .wait_with_output()
+        .map_err(|err| with_context(err, "failed to run curl"))?;
+    if !output.status.success() {
+        return Err(fail_with(
+            ErrorKind::Other,
+            format!(
+                "curl failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ),
+            None,
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+fn curl_config(options: &[(&str, &str)]) -> String {
+    options
+        .iter()
+        .map(|(key, value)| {
+            let escaped = value
+                .replace('\\', "\\\\")
+                .replace('"', "\\\"")
+                .replace('\n', "\\n")
+                .replace('\r', "\\r")
+                .replace('\t', "\\t");
+            format!("{key} = \"{escaped}\"\n")
+        })
+        .collect()
+}
+
+/// The body of a Slack API reply, or its error.
+fn slack_reply(raw: String) -> Result<Value> {
+    let value = json::parse(&raw)?;
+    if value.get("ok") == Some(&Value::Bool(true)) {
+        return Ok(value);
+    }
+    let error = value
+        .get("error")
+        .and_then(Value::as_str)
+        .unwrap_or("unknown error");
+    Err(fail_with(
+        ErrorKind::Other,
+        format!("Slack API error: {error}"),
+        None,
+    ))
+}
+
+/// Post `needs_input` each time the agent in `session` goes from working to
+/// waiting while nobody is attached. The agent settling after its start does
+/// not count. Returns once the session is gone.
+pub fn watch_for_input(settings: &Notify, boundary: &Boundary, source: &Source) -> Result<()> {
+    if response::wait_for_response(source.session, boundary, None).is_err() {
+        return Ok(());
+    }
+    loop {
+        let mut first: Option<String> = None;
+        let changed = |screen: &str, _: Duration| match &first {
+            Some(first) => first != screen,
+            None => {
+                first = Some(screen.to_string());
+                false
+            }
+        };
+        if response::watch_pane(source.session, None, changed).is_err()
+            || response::wait_for_response(source.session, boundary, None).is_err()
+        {
+            return Ok(());
+        }
+        if !tmux::session_clients(source.session)?.is_empty()
+            || dnd::active(source.session).is_some()
+        {
+            continue;
+        }
+        let pane = tmux::agent_pane(source.session)?;
+        let output = tmux::capture_pane(&pane, Some(200)).unwrap_or_default();
+        post(settings, Event::NeedsInput, source, &output);
+    }
+}
+
+/// A message posted through the Slack API whose thread is watched for replies.
+#[derive(Clone, Debug, PartialEq)]
+struct Thread {
+    channel: String,
+    ts: String,
+    /// Timestamp of the newest message already handled.
+    seen: String,
+    agent: String,
+    name: Option<String>,
+    session: String,
+}
+
+impl Thread {
+    fn to_json(&self) -> Value {
+        json::object([
+            ("channel", Value::from(self.channel.as_str())),
+            ("ts", Value::from(self.ts.as_str())),
+            ("seen", Value::from(self.seen.as_str())),
+            ("agent", Value::from(self.agent.as_str())),
+            ("name", Value::from(self.name.clone())),
+            ("session", Value::from(self.session.as_str())),
+        ])
+    }
+
+    fn from_json(value: &Value) -> Option<Self> {
+        let text = |key: &str| value.get(key).and_then(Value::as_str).map(str::to_string);
+        Some(Self {
+            channel: text("channel")?,
+            ts: text("ts")?,
+            seen: text("seen")?,
+            agent: text("agent")?,
+            name: text("name"),
+            session: text("session")?,
+        })
+    }
+}
+
+/// Version 1 wrapped the bare array of threads in an object.
+const THREADS_SCHEMA: state::Schema = state::Schema {
+    what: "notify thread list",
+    version: 1,
+    migrate: |_, threads| Ok(json::object([("threads", threads)])),
+};
+
+fn load_threads() -> Result<Vec<Thread>> {
+    let Some(value) = state::read(&paths::notify_threads_file()?, &THREADS_SCHEMA)? else {
+        return Ok(Vec::new());
+    };
+    Ok(value
+        .get("threads")
+        .and_then(Value::as_array)
+        .unwrap_or_default()
+        .iter()
+        .filter_map(Thread::from_json)
+        .collect())
+}
+
+fn save_threads(threads: &[Thread]) -> Result<()> {
+    let threads = Value::from(threads.iter().map(Thread::to_json).collect::<Vec<_>>());
+    let value = json::object([("threads", threads)]);
+    state::write(&paths::notify_threads_file()?, &THREADS_SCHEMA, value)
+}
+
+/// Poll the threads of posted messages and type each new human reply into
+/// its session with the amux executable at `exe`. Runs until stopped.
+pub fn bridge(settings: &Notify, exe: &Path) -> Result<()> {
+    let Some(token) = &settings.slack_token else {
+        return Err(fail_with(
+            ErrorKind::Config,
+            "the notify bridge needs a Slack bot token",
+            Some("set slack_token and slack_channel under [notify]".into()),
+        ));
+    };
+    println!(
+        "amux: relaying Slack thread replies every {}s",
+        settings.poll_secs
+    );
+    loop {
+        relay_threads(token, exe)?;
+        thread::sleep(Duration::from_secs(settings.poll_secs));
+    }
+}
+
+/// One pass over the threads, holding their file's lock so a message posted
+/// meanwhile is not dropped when the pass saves.
+fn relay_threads(token: &str, exe: &Path) -> Result<()> {
+    let _lock = state::lock(&paths::notify_threads_file()?)?;
+    let mut threads = load_threads()?;
+    threads.retain(|thread| tmux::has_session(&thread.session).unwrap_or(true));
+    for thread in &mut threads {
+        if let Err(err) = relay_replies(token, thread, exe) {
+            eprintln!("amux: warning: thread {}: {err}", thread.ts);
+        }
+    }
+    save_threads(&threads)
+}
+
+fn relay_replies(token: &str, thread: &mut Thread, exe: &Path) -> Result<()> {
+    let url = format!(
+        "https://slack.com/api/conversations.replies?channel={}&ts={}&oldest={}",
+        thread.channel, thread.ts, thread.seen
+    );
+    let auth = format!("Authorization: Bearer {token}");
+    let reply = slack_reply(curl(&[("url", &url), ("header", &auth)])?)?;
+    for (ts, text) in new_replies(&reply, &thread.seen) {
+        let mut send = Command::new(exe);
+        send.arg("send").arg(format!("--agent={}", thread.agent));
+        if let Some(name) = &thread.name {
+            send.arg(format!("--name={name}"));
+        }
+        let mut child = send
+            .stdin(Stdio::piped())
+            .spawn()
+            .map_err(|err| with_context(err, "failed to run amux send"))?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin
+                .write_all(text.as_bytes())
+                .map_err(|err| with_context(err, "failed to pass the reply to amux send"))?;
+        }
+        child
+            .wait()
+            .map_err(|err| with_context(err, "failed to run amux send"))?;
+        thread.seen = ts;
+    }
+    Ok(())
+}
+
+/// Replies newer than `seen` written by people (not bots), oldest first, as
+/// `(ts, text)` with Slack's HTML escapes undone.
+fn new_replies(reply: &Value, seen: &str) -> Vec<(String, String)> {
+    let mut replies: Vec<(String, String)> = reply
+        .get("messages")
+        .and_then(Value::as_array)
+        .unwrap_or_default()
+        .iter()
+        .filter(|message| message.get("bot_id").is_none() && message.get("subtype").is_none())
+        .filter_map(|message| {
+            let ts = message.get("ts")?.as_str()?;
+            let text = message.get("text")?.as_str()?;
+            Some((ts.to_string(), text.to_string()))
+        })
+        .filter(|(ts, _)| ts_key(ts) > ts_key(seen))
+        .map(|(ts, text)| {
+            let text = text
+                .replace("&lt;", "<")
+                .replace("&gt;", ">")
+                .replace("&amp;", "&");
+            (ts, text)
+        })
+        .collect();
+    replies.sort_by_key(|(ts, _)| ts_key(ts));
+    replies
+}
+
+/// Slack timestamps (`1700000000.000200`) compared exactly, as seconds and
+/// microseconds, since they do not fit a float.
+fn ts_key(ts: &str) -> (u64, u64) {
+    let (secs, micros) = ts.split_once('.').unwrap_or((ts, "0"));
+    (secs.parse().unwrap_or(0), micros.parse().unwrap_or(0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn message_labels_the_session_and_quotes_output() {
+        let source = Source {
+            agent: "codex",
+            name: Some("review"),
+            session: "amux-codex--review",
+            exit_status: None,
+        };
+        let text = message(Event::NeedsInput, &source, "Apply the patch? [y/n]");
+        assert_eq!(
+            text,
+            "codex/review is waiting for input (session `amux-codex--review`)\n\
+             ```\nApply the patch? [y/n]\n```"
+        );
+        let plain = Source {
+            agent: "claude",
+            name: None,
+            session: "amux-claude",
+            exit_status: None,
+        };
+        assert_eq!(
+            message(Event::Crashed, &plain, ""),
+            "claude crashed (session `amux-claude`)"
+        );
+        assert_eq!(truncate("abcdef", 4), "…def");
+    }
+
+    #[test]
+    fn exits_carry_their_status_and_output() {
+        let source = Source {
+            agent: "codex",
+            name: None,
+            session: "amux-codex",
+            exit_status: Some(101),
+        };
+        assert_eq!(
+            message(Event::Crashed, &source, ""),
+            "codex crashed with exit status 101 (session `amux-codex`)"
+        );
+        assert_eq!(
+            headline(Event::NeedsInput, &source),
+            "codex is waiting for input"
+        );
+        let tail = "test result: FAILED\nerror: 1 test failed";
+        let payload = webhook_payload(Event::Crashed, &source, "text", tail);
+        assert_eq!(payload.get("exit_status"), Some(&Value::Integer(101)));
+        assert_eq!(payload.get("output").and_then(Value::as_str), Some(tail));
+        assert_eq!(payload.get("name"), Some(&Value::Null));
+    }
+
+    #[test]
+    fn curl_config_quotes_values() {
+        let config = curl_config(&[("url", "https://x"), ("data-binary", "{\"a\":\"b\\n\"}\n")]);
+        assert_eq!(
+            config,
+            "url = \"https://x\"\ndata-binary = \"{\\\"a\\\":\\\"b\\\\n\\\"}\\n\"\n"
+        );
+    }
+
+    #[test]
+    fn new_replies_skip_bots_and_seen_messages() {
+        let reply = json::parse(
+            r#"{"ok":true,"messages":[
+                {"ts":"1700000000.000100","text":"codex finished","bot_id":"B1"},
+                {"ts":"1700000000.000900","text":"old reply","user":"U1"},
+                {"ts":"1700000001.000001","text":"run &lt;tests&gt; &amp; fix","user":"U1"},
+                {"ts":"1700000000.001000","text":"try again","user":"U2"}
+            ]}"#,
+        )
+        .expect("reply should parse");
+        let replies = new_replies(&reply, "1700000000.000900");
+        assert_eq!(
+            replies,
+            [
+                ("1700000000.001000".to_string(), "try again".to_string()),
+                (
+                    "1700000001.000001".to_string(),
+                    "run <tests> & fix".to_string()
+                ),
+            ]
+        );
+    }
+}
@@ -0,0 +1,107 @@
+//! `amux open`: an agent's working directory in an editor or file manager.
+//! The program is `--with`, else `open_with` from the config, else `$VISUAL`
+//! or `$EDITOR`, else VS Code if it is installed, else the desktop's file
+//! manager.
+
+use std::env;
+use std::path::Path;
+use std::process::Command;
+
+use crate::error::{fail_with, with_context, ErrorKind, Result};
+
+/// The desktop's "open this" command.
+const FILE_MANAGER: &str = if cfg!(target_os = "macos") {
+    "open"
+} else {
+    "xdg-open"
+};
+
+/// The command line to open a directory with, before the directory. `flag`
+/// is `--with`, `configured` is `open_with`.
+pub fn opener(flag: Option<&str>, configured: Option<&str>) -> Result<Vec<String>> {
+    let raw = choose(flag, configured, |name| env::var(name).ok(), on_path);
+    let words = shell_words::split(&raw).map_err(|err| {
+        fail_with(
+            ErrorKind::InvalidInput,
+            format!("cannot parse opener '{raw}': {err}"),
+            None,
+        )
+    })?;
+    if words.is_empty() {
+        return Err(fail_with(
+            ErrorKind::InvalidInput,
+            "the opener is empty",
+            Some("pass --with PROGRAM or set open_with in the config".into()),
+        ));
+    }
+    Ok(words)
+}
+
+fn choose(
+    flag: Option<&str>,
+    configured: Option<&str>,
+    var: impl Fn(&str) -> Option<String>,
+    installed: impl Fn(&str) -> bool,
+) -> String {
+    flag.or(configured)
+        .map(str::to_string)
+        .or_else(|| {
+            ["VISUAL", "EDITOR"]
+                .into_iter()
+                .filter_map(&var)
+                .find(|value| !value.trim().is_empty())
+        })
+        .unwrap_or_else(|| {
+            if installed("code") {
+                "code".into()
+            } else {
+                FILE_MANAGER.into()
+            }
+        })
+}
+
+fn on_path(program: &str) -> bool {
+    env::var_os("PATH")
+        .map(|path| env::split_paths(&path).any(|dir| dir.join(program).is_file()))
+        .unwrap_or(false)
+}
+
+/// Run `opener` on `dir` in the foreground, so terminal editors get the
+/// terminal until they exit.
+pub fn run(opener: &[String], dir: &Path) -> Result<()> {
+    let (program, args) = opener.split_first().expect("opener is not empty");
+    let status = Command::new(program)
+        .args(args)
+        .arg(dir)
+        .status()
+        .map_err(|err| with_context(err, format!("failed to run {program}")))?;
+    if !status.success() {
+        return Err(fail_with(
+            ErrorKind::Other,
+            format!("{program} exited with {status}"),
+            None,
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn choose_prefers_flag_then_config_then_editor() {
+        let env = |name: &str| (name == "EDITOR").then(|| "vim".to_string());
+        let installed = |_: &str| true;
+        assert_eq!(choose(Some("zed"), Some("code -n"), env, installed), "zed");
+        assert_eq!(choose(None, Some("code -n"), env, installed), "code -n");
+        assert_eq!(choose(None, None, env, installed), "vim");
+    }
+
+    #[test]
+    fn choose_falls_back_to_code_then_the_file_manager() {
+        let env = |name: &str| (name == "VISUAL").then(String::new);
+        assert_eq!(choose(None, None, env, |program| program == "code"), "code");
+        assert_eq!(choose(None, None, env, |_| false), FILE_MANAGER);
+    }
+}
@@ -0,0 +1,158 @@
+//! Where a session was started from: the user, machine, terminal, and
+//! directory of the `amux start` that created it. On a shared machine this
+//! answers who launched an agent nobody remembers starting.
+
+use std::env;
+use std::fs;
+use std::process::{Command, Stdio};
+
+use crate::json::{self, Value};
+use crate::nested;
+
+/// The terminal `amux start` ran in.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Origin {
+    pub user: Option<String>,
+    pub host: Option<String>,
+    /// Address of the SSH client, when started over SSH.
+    pub remote: Option<String>,
+    pub tty: Option<String>,
+    pub cwd: Option<String>,
+    /// The agent session whose amux started this one, if an agent did.
+    pub session: Option<String>,
+}
+
+impl Origin {
+    /// The origin of the current process.
+    pub fn current() -> Self {
+        let var = |name: &str| env::var(name).ok().filter(|value| !value.is_empty());
+        Self {
+            user: var("USER").or_else(|| var("LOGNAME")),
+            host: command_output("hostname").or_else(|| var("HOSTNAME")),
+            remote: var("SSH_CONNECTION")
+                .or_else(|| var("SSH_CLIENT"))
+                .and_then(|value| ssh_client(&value)),
+            tty: command_output("tty"),
+            cwd: env::current_dir()
+                .ok()
+                .map(|cwd| cwd.to_string_lossy().into_owned()),
+            session: nested::caller(),
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        json::object([
+            ("user", Value::from(self.user.clone())),
+            ("host", Value::from(self.host.clone())),
+            ("remote", Value::from(self.remote.clone())),
+            ("tty", Value::from(self.tty.clone())),
+            ("cwd", Value::from(self.cwd.clone())),
+            ("session", Value::from(self.session.clone())),
+        ])
+    }
+
+    pub fn from_json(value: &Value) -> Self {
+        let text = |key: &str| value.get(key).and_then(Value::as_str).map(str::to_string);
+        Self {
+            user: text("user"),
+            host: text("host"),
+            remote: text("remote"),
+            tty: text("tty"),
+            cwd: text("cwd"),
+            session: text("session"),
+        }
+    }
+
+    /// One line such as `alice@devbox on /dev/pts/3 via ssh from 10.0.0.5`.
+    pub fn summary(&self) -> String {
+        let mut summary = match (&self.user, &self.host) {
+            (Some(user), Some(host)) => format!("{user}@{host}"),
+            (Some(who), None) | (None, Some(who)) => who.clone(),
+            (None, None) => "unknown".to_string(),
+        };
+        if let Some(tty) = &self.tty {
+            summary.push_str(&format!(" on {tty}"));
+        }
+        if let Some(remote) = &self.remote {
+            summary.push_str(&format!(" via ssh from {remote}"));
+        }
+        if let Some(session) = &self.session {
+            summary.push_str(&format!(", by agent session {session}"));
+        }
+        summary
+    }
+}
+
+/// The SSH client address in the environment of process `pid`, such as a
+/// tmux client. Only Linux lets amux read another process's environment;
+/// elsewhere this is `None`.
+pub fn process_remote(pid: u32) -> Option<String> {
+    let environ = fs::read(format!("/proc/{pid}/environ")).ok()?;
+    let var = |name: &str| {
+        environ.split(|&byte| byte == 0).find_map(|entry| {
+            let value = entry.strip_prefix(name.as_bytes())?.strip_prefix(b"=")?;
+            Some(String::from_utf8_lossy(value).into_owned())
+        })
+    };
+    var("SSH_CONNECTION")
+        .or_else(|| var("SSH_CLIENT"))
+        .and_then(|value| ssh_client(&value))
+}
+
+/// The client address from `SSH_CONNECTION` or `SSH_CLIENT`.
+fn ssh_client(value: &str) -> Option<String> {
+    value.split_whitespace().next().map(str::to_string)
+}
+
+/// Trimmed stdout of `program`, or `None` if it fails. stdin is inherited so
+/// `tty` sees the invoking terminal.
+fn command_output(program: &str) -> Option<String> {
+    let output = Command::new(program)
+        .stdin(Stdio::inherit())
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let text = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (!text.is_empty()).then_some(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn summary_names_user_terminal_and_ssh_client() {
+        let origin = Origin {
+            user: Some("alice".into()),
+            host: Some("devbox".into()),
+            remote: Some("10.0.0.5".into()),
+            tty: Some("/dev/pts/3".into()),
+            cwd: Some("/src".into()),
+            session: None,
+        };
+        assert_eq!(
+            origin.summary(),
+            "alice@devbox on /dev/pts/3 via ssh from 10.0.0.5"
+        );
+        let nested = Origin {
+            session: Some("amux-codex".into()),
+            ..origin
+        };
+        assert!(nested.summary().ends_with(", by agent session amux-codex"));
+        assert_eq!(Origin::default().summary(), "unknown");
+    }
+
+    #[test]
+    fn origin_round_trips_through_json() {
+        let origin = Origin {
+            user: Some("bob".into()),
+            tty: None,
+            ..Origin::default()
+        };
+        let parsed = json::parse(&origin.to_json().to_string()).expect("origin should parse");
+        assert_eq!(Origin::from_json(&parsed), origin);
+    }
+}
@@ -0,0 +1,149 @@
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+
+use crate::error::{with_context, Result};
+use crate::paths;
+
+/// Reads output appended to a session's log after the tail was opened.
+#[derive(Debug)]
+pub struct Tail {
+    file: File,
+}
+
+impl Tail {
+    /// Open the session log positioned at its current end, or `None` when the
+    /// session has no log (e.g. it was started before output tracking existed).
+    pub fn from_end(session: &str) -> Result<Option<Self>> {
+        let path = paths::session_log(session)?;
+        let mut file = match File::open(&path) {
+            Ok(file) => file,
+            Err(_) => return Ok(None),
+        };
+        file.seek(SeekFrom::End(0))
+            .map_err(|err| with_context(err, format!("failed to read {}", path.display())))?;
+        Ok(Some(Self { file }))
+    }
+
+    /// Text written since the previous call, with terminal escape sequences removed.
+    pub fn read_new(&mut self) -> Result<String> {
+        let mut bytes = Vec::new();
+        self.file
+            .read_to_end(&mut bytes)
+            .map_err(|err| with_context(err, "failed to read session log"))?;
+        Ok(strip_ansi(&String::from_utf8_lossy(&bytes)))
+    }
+}
+
+/// The last `lines` non-blank lines of `output`.
+pub fn tail(output: &str, lines: usize) -> String {
+    let kept: Vec<&str> = output
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .collect();
+    kept[kept.len().saturating_sub(lines)..].join("\n")
+}
+
+/// Remove ANSI escape sequences and carriage returns so raw pane output can be
+/// matched as plain text.
+pub fn strip_ansi(raw: &str) -> String {
+    tokens(raw)
+        .filter_map(|token| match token {
+            Token::Char(c) => Some(c),
+            Token::Sgr(_) => None,
+        })
+        .collect()
+}
+
+/// A piece of raw terminal output, as read by [`tokens`].
+#[derive(Debug, PartialEq, Eq)]
+pub enum Token {
+    /// A printable character, newline, or tab.
+    Char(char),
+    /// The parameters of an SGR sequence (`ESC [ ... m`), e.g. `1;32`.
+    Sgr(String),
+}
+
+/// Split `raw` into text and SGR sequences. Every other escape sequence,
+/// carriage returns, and the remaining control characters are dropped.
+pub fn tokens(raw: &str) -> impl Iterator<Item = Token> + '_ {
+    let mut chars = raw.chars().peekable();
+    std::iter::from_fn(move || {
+        while let Some(c) = chars.next() {
+            match c {
+                '\u{1b}' => match chars.next() {
+                    // CSI: parameters until a final byte in '@'..='~'.
+                    Some('[') => {
+                        let mut params = String::new();
+                        for c in chars.by_ref() {
+                            if ('@'..='~').contains(&c) {
+                                if c == 'm' {
+                                    return Some(Token::Sgr(params));
+                                }
+                                break;
+                            }
+                            params.push(c);
+                        }
+                    }
+                    // OSC/DCS/etc: until BEL or ESC '\'.
+                    Some(']' | 'P' | '^' | '_') => {
+                        while let Some(c) = chars.next() {
+                            if c == '\u{7}' {
+                                break;
+                            }
+                            if c == '\u{1b}' && chars.peek() == Some(&'\\') {
+                                chars.next();
+                                break;
+                            }
+                        }
+                    }
+                    // Charset selection and similar take one more byte.
+                    Some('(' | ')' | '*' | '+') => {
+                        chars.next();
+                    }
+                    _ => {}
+                },
+                '\r' => {}
+                c if c.is_control() && c != '\n' && c != '\t' => {}
+                c => return Some(Token::Char(c)),
+            }
+        }
+        None
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strip_ansi_removes_color_and_cursor_sequences() {
+        let raw = "\u{1b}[1;32mok\u{1b}[0m done\r\n\u{1b}[2K\u{1b}[?25lnext";
+        assert_eq!(strip_ansi(raw), "ok done\nnext");
+    }
+
+    #[test]
+    fn tail_keeps_last_non_blank_lines() {
+        assert_eq!(tail("a\nb\n\nc\n\n\n", 2), "b\nc");
+        assert_eq!(tail("only\n", 5), "only");
+    }
+
+    #[test]
+    fn tokens_keep_sgr_parameters() {
+        let raw = "\u{1b}[1;32mo\u{1b}[2Kk\u{1b}[m";
+        assert_eq!(
+            tokens(raw).collect::<Vec<_>>(),
+            [
+                Token::Sgr("1;32".into()),
+                Token::Char('o'),
+                Token::Char('k'),
+                Token::Sgr(String::new()),
+            ]
+        );
+    }
+
+    #[test]
+    fn strip_ansi_removes_osc_titles() {
+        let raw = "\u{1b}]0;title\u{7}text\u{1b}]2;other\u{1b}\\more";
+        assert_eq!(strip_ansi(raw), "textmore");
+    }
+}
@@ -0,0 +1,188 @@
+//! Where amux keeps its files, following the XDG base directory layout:
+//!
+//! - config: `$XDG_CONFIG_HOME/amux` (default `~/.config/amux`)
+//! - data:   `$XDG_DATA_HOME/amux` (default `~/.local/share/amux`), with
+//!   message catalogs under `locales/`
+//! - state:  `$XDG_STATE_HOME/amux` (default `~/.local/state/amux`), with
+//!   session logs under `logs/` and the run history in `history.jsonl`
+
+use std::env;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+use crate::error::{bail, Result};
+
+pub fn config_file() -> Result<PathBuf> {
+    if let Some(path) = env::var_os("AMUX_CONFIG").filter(|path| !path.is_empty()) {
+        return Ok(PathBuf::from(path));
+    }
+    Ok(config_dir()?.join("config.toml"))
+}
+
+pub fn config_dir() -> Result<PathBuf> {
+    base_dir("XDG_CONFIG_HOME", &[".config"])
+}
+
+pub fn data_dir() -> Result<PathBuf> {
+    base_dir("XDG_DATA_HOME", &[".local", "share"])
+}
+
+pub fn locales_dir() -> Result<PathBuf> {
+    Ok(data_dir()?.join("locales"))
+}
+
+pub fn state_dir() -> Result<PathBuf> {
+    base_dir("XDG_STATE_HOME", &[".local", "state"])
+}
+
+pub fn logs_dir() -> Result<PathBuf> {
+    Ok(state_dir()?.join("logs"))
+}
+
+/// Compressed logs of removed sessions, see [`crate::archive`].
+pub fn archive_dir() -> Result<PathBuf> {
+    Ok(state_dir()?.join("archive"))
+}
+
+/// Periodic captures of session panes, one directory per session, see
+/// [`crate::snapshot`].
+pub fn snapshots_dir() -> Result<PathBuf> {
+    Ok(state_dir()?.join("snapshots"))
+}
+
+/// Session snapshot for external monitors, see [`crate::heartbeat`].
+pub fn heartbeat_file() -> Result<PathBuf> {
+    Ok(state_dir()?.join("heartbeat.json"))
+}
+
+/// Slack messages whose threads `amux notify bridge` relays, see [`crate::notify`].
+pub fn notify_threads_file() -> Result<PathBuf> {
+    Ok(state_dir()?.join("notify-threads.json"))
+}
+
+/// Sessions as the last `status --diff` saw them, see [`crate::status_diff`].
+pub fn status_snapshot_file() -> Result<PathBuf> {
+    Ok(state_dir()?.join("status-snapshot.json"))
+}
+
+/// Last known sessions of remote hosts, see [`crate::hosts`].
+pub fn hosts_file() -> Result<PathBuf> {
+    Ok(state_dir()?.join("hosts.json"))
+}
+
+/// ssh control sockets shared by the connections to remote hosts.
+pub fn ssh_control_dir() -> Result<PathBuf> {
+    Ok(state_dir()?.join("ssh"))
+}
+
+pub fn history_file() -> Result<PathBuf> {
+    Ok(state_dir()?.join("history.jsonl"))
+}
+
+pub fn dispatch_file() -> Result<PathBuf> {
+    Ok(state_dir()?.join("dispatch.json"))
+}
+
+pub fn tasks_dir() -> Result<PathBuf> {
+    Ok(state_dir()?.join("tasks"))
+}
+
+pub fn session_log(session: &str) -> Result<PathBuf> {
+    Ok(logs_dir()?.join(format!("{session}.log")))
+}
+
+/// The tmux configuration file: `~/.tmux.conf`, unless only the XDG location
+/// (`$XDG_CONFIG_HOME/tmux/tmux.conf`) exists.
+pub fn tmux_conf() -> Result<PathBuf> {
+    let Some(home) = env::var_os("HOME").filter(|home| !home.is_empty()) else {
+        return bail("HOME is not set; cannot locate the tmux configuration");
+    };
+    let home_conf = PathBuf::from(&home).join(".tmux.conf");
+    let xdg_conf = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+        .unwrap_or_else(|| PathBuf::from(&home).join(".config"))
+        .join("tmux")
+        .join("tmux.conf");
+    if !home_conf.exists() && xdg_conf.exists() {
+        return Ok(xdg_conf);
+    }
+    Ok(home_conf)
+}
+
+/// `path` with the home directory written as `~`, for display.
+pub fn tildify(path: &str) -> String {
+    shorten_home(path, env::var_os("HOME"))
+}
+
+fn shorten_home(path: &str, home: Option<OsString>) -> String {
+    let home = home.filter(|home| !home.is_empty()).map(PathBuf::from);
+    match home.and_then(|home| {
+        Path::new(path)
+            .strip_prefix(home)
+            .ok()
+            .map(Path::to_path_buf)
+    }) {
+        Some(rest) if rest.as_os_str().is_empty() => "~".to_string(),
+        Some(rest) => format!("~/{}", rest.display()),
+        None => path.to_string(),
+    }
+}
+
+fn base_dir(var: &str, fallback: &[&str]) -> Result<PathBuf> {
+    match resolve_base(env::var_os(var), env::var_os("HOME"), fallback) {
+        Some(dir) => Ok(dir),
+        None => bail(format!(
+            "neither {var} nor HOME is set; cannot locate amux directories"
+        )),
+    }
+}
+
+fn resolve_base(
+    xdg: Option<OsString>,
+    home: Option<OsString>,
+    fallback: &[&str],
+) -> Option<PathBuf> {
+    // The XDG spec says relative paths are invalid and must be ignored.
+    if let Some(dir) = xdg.map(PathBuf::from).filter(|dir| dir.is_absolute()) {
+        return Some(dir.join("amux"));
+    }
+    let mut dir = PathBuf::from(home.filter(|home| !home.is_empty())?);
+    dir.extend(fallback);
+    Some(dir.join("amux"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolve_base_prefers_absolute_xdg_dir() {
+        let dir = resolve_base(
+            Some("/xdg/state".into()),
+            Some("/home/me".into()),
+            &[".local", "state"],
+        );
+        assert_eq!(dir, Some(PathBuf::from("/xdg/state/amux")));
+    }
+
+    #[test]
+    fn resolve_base_falls_back_to_home() {
+        let dir = resolve_base(
+            Some("relative".into()),
+            Some("/home/me".into()),
+            &[".local", "state"],
+        );
+        assert_eq!(dir, Some(PathBuf::from("/home/me/.local/state/amux")));
+        assert_eq!(resolve_base(None, None, &[".config"]), None);
+    }
+
+    #[test]
+    fn shorten_home_writes_home_as_tilde() {
+        let home = || Some(OsString::from("/home/me"));
+        assert_eq!(shorten_home("/home/me/src/api", home()), "~/src/api");
+        assert_eq!(shorten_home("/home/me", home()), "~");
+        assert_eq!(shorten_home("/home/meg", home()), "/home/meg");
+        assert_eq!(shorten_home("/home/me/src", None), "/home/me/src");
+    }
+}
@@ -0,0 +1,612 @@
+//! A small backtracking regular-expression engine for matching agent output.
+//!
+//! Supported syntax: literals, `.`, character classes (`[a-z]`, `[^...]`), the
+//! escapes `\d \w \s \D \W \S \b \B`, groups (`(...)`, `(?:...)`), alternation,
+//! and the quantifiers `* + ? {n} {n,} {n,m}` (append `?` for lazy matching).
+//! A leading `(?i)` makes the pattern case-insensitive. `^` and `$` always match
+//! at line boundaries, since patterns are applied to multi-line pane captures.
+
+use std::fmt;
+
+use crate::error::{bail, Result};
+
+const MAX_PROGRAM: usize = 20_000;
+const MAX_REPEAT: u32 = 1_000;
+
+#[derive(Clone)]
+pub struct Pattern {
+    source: String,
+    program: Vec<Inst>,
+    case_insensitive: bool,
+}
+
+impl fmt::Debug for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Pattern").field(&self.source).finish()
+    }
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+impl Pattern {
+    pub fn new(source: &str) -> Result<Self> {
+        let (case_insensitive, body) = match source.strip_prefix("(?i)") {
+            Some(rest) => (true, rest),
+            None => (false, source),
+        };
+        let mut parser = Parser {
+            chars: body.chars().collect(),
+            pos: 0,
+        };
+        let node = parser
+            .parse_alternation()
+            .map_err(|err| crate::error::fail(format!("invalid pattern '{source}': {err}")))?;
+        if parser.pos < parser.chars.len() {
+            return bail(format!("invalid pattern '{source}': unmatched ')'"));
+        }
+
+        let mut program = Vec::new();
+        compile(&node, &mut program)
+            .map_err(|err| crate::error::fail(format!("invalid pattern '{source}': {err}")))?;
+        program.push(Inst::Match);
+
+        Ok(Self {
+            source: source.to_string(),
+            program,
+            case_insensitive,
+        })
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+
+    pub fn is_match(&self, text: &str) -> bool {
+        self.find(text).is_some()
+    }
+
+    /// Byte range of the leftmost match in `text`.
+    pub fn find(&self, text: &str) -> Option<(usize, usize)> {
+        let chars: Vec<char> = text.chars().collect();
+        let (start, end) = self.find_chars(&chars)?;
+        let offsets: Vec<usize> = text
+            .char_indices()
+            .map(|(offset, _)| offset)
+            .chain(std::iter::once(text.len()))
+            .collect();
+        Some((offsets[start], offsets[end]))
+    }
+
+    fn find_chars(&self, text: &[char]) -> Option<(usize, usize)> {
+        let width = text.len() + 1;
+        let mut visited = vec![0u64; (self.program.len() * width).div_ceil(64)];
+        let mut stack = Vec::new();
+        for start in 0..=text.len() {
+            if let Some(end) = self.run(text, start, &mut visited, &mut stack) {
+                return Some((start, end));
+            }
+        }
+        None
+    }
+
+    fn run(
+        &self,
+        text: &[char],
+        start: usize,
+        visited: &mut [u64],
+        stack: &mut Vec<(usize, usize)>,
+    ) -> Option<usize> {
+        let width = text.len() + 1;
+        stack.clear();
+        stack.push((0, start));
+        while let Some((mut pc, mut pos)) = stack.pop() {
+            loop {
+                // A (pc, pos) state that already failed will fail again, which keeps
+                // matching linear in program size times input length.
+                let bit = pc * width + pos;
+                if visited[bit / 64] & (1 << (bit % 64)) != 0 {
+                    break;
+                }
+                visited[bit / 64] |= 1 << (bit % 64);
+
+                match &self.program[pc] {
+                    Inst::Char(expected) => {
+                        if pos < text.len() && self.chars_equal(text[pos], *expected) {
+                            pc += 1;
+                            pos += 1;
+                        } else {
+                            break;
+                        }
+                    }
+                    Inst::Any => {
+                        if pos < text.len() && text[pos] != '\n' {
+                            pc += 1;
+                            pos += 1;
+                        } else {
+                            break;
+                        }
+                    }
+                    Inst::Class(class) => {
+                        if pos < text.len() && class.matches(text[pos], self.case_insensitive) {
+                            pc += 1;
+                            pos += 1;
+                        } else {
+                            break;
+                        }
+                    }
+                    Inst::LineStart => {
+                        if pos == 0 || text[pos - 1] == '\n' {
+                            pc += 1;
+                        } else {
+                            break;
+                        }
+                    }
+                    Inst::LineEnd => {
+                        if pos == text.len() || text[pos] == '\n' {
+                            pc += 1;
+                        } else {
+                            break;
+                        }
+                    }
+                    Inst::WordBoundary(expected) => {
+                        let before = pos > 0 && is_word_char(text[pos - 1]);
+                        let after = pos < text.len() && is_word_char(text[pos]);
+                        if (before != after) == *expected {
+                            pc += 1;
+                        } else {
+                            break;
+                        }
+                    }
+                    Inst::Split(first, second) => {
+                        stack.push((*second, pos));
+                        pc = *first;
+                    }
+                    Inst::Jump(target) => pc = *target,
+                    Inst::Match => return Some(pos),
+                }
+            }
+        }
+        None
+    }
+
+    fn chars_equal(&self, actual: char, expected: char) -> bool {
+        actual == expected || (self.case_insensitive && fold(actual) == fold(expected))
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Inst {
+    Char(char),
+    Any,
+    Class(Class),
+    LineStart,
+    LineEnd,
+    WordBoundary(bool),
+    Split(usize, usize),
+    Jump(usize),
+    Match,
+}
+
+#[derive(Clone, Debug)]
+enum ClassItem {
+    Range(char, char),
+    Perl(Perl, bool),
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Perl {
+    Digit,
+    Word,
+    Space,
+}
+
+impl Perl {
+    fn matches(self, c: char) -> bool {
+        match self {
+            Perl::Digit => c.is_ascii_digit(),
+            Perl::Word => is_word_char(c),
+            Perl::Space => c.is_whitespace(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Class {
+    negated: bool,
+    items: Vec<ClassItem>,
+}
+
+impl Class {
+    fn matches(&self, c: char, case_insensitive: bool) -> bool {
+        let hit = self.contains(c)
+            || (case_insensitive
+                && (self.contains(fold(c)) || self.contains(c.to_uppercase().next().unwrap_or(c))));
+        hit != self.negated
+    }
+
+    fn contains(&self, c: char) -> bool {
+        self.items.iter().any(|item| match item {
+            ClassItem::Range(lo, hi) => (*lo..=*hi).contains(&c),
+            ClassItem::Perl(perl, negated) => perl.matches(c) != *negated,
+        })
+    }
+}
+
+#[derive(Debug)]
+enum Node {
+    Empty,
+    Literal(char),
+    Any,
+    Class(Class),
+    LineStart,
+    LineEnd,
+    WordBoundary(bool),
+    Concat(Vec<Node>),
+    Alternate(Vec<Node>),
+    Repeat {
+        node: Box<Node>,
+        min: u32,
+        max: Option<u32>,
+        greedy: bool,
+    },
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+type ParseResult<T> = std::result::Result<T, String>;
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn parse_alternation(&mut self) -> ParseResult<Node> {
+        let mut branches = vec![self.parse_concat()?];
+        while self.peek() == Some('|') {
+            self.pos += 1;
+            branches.push(self.parse_concat()?);
+        }
+        Ok(if branches.len() == 1 {
+            branches.remove(0)
+        } else {
+            Node::Alternate(branches)
+        })
+    }
+
+    fn parse_concat(&mut self) -> ParseResult<Node> {
+        let mut items = Vec::new();
+        while let Some(c) = self.peek() {
+            if c == '|' || c == ')' {
+                break;
+            }
+            let atom = self.parse_atom()?;
+            items.push(self.parse_quantifier(atom)?);
+        }
+        Ok(match items.len() {
+            0 => Node::Empty,
+            1 => items.remove(0),
+            _ => Node::Concat(items),
+        })
+    }
+
+    fn parse_atom(&mut self) -> ParseResult<Node> {
+        let c = self.peek().ok_or("unexpected end of pattern")?;
+        self.pos += 1;
+        match c {
+            '.' => Ok(Node::Any),
+            '^' => Ok(Node::LineStart),
+            '$' => Ok(Node::LineEnd),
+            '[' => self.parse_class().map(Node::Class),
+            '(' => {
+                if self.chars[self.pos..].starts_with(&['?', ':']) {
+                    self.pos += 2;
+                } else if self.peek() == Some('?') {
+                    return Err("unsupported group syntax".to_string());
+                }
+                let inner = self.parse_alternation()?;
+                if self.peek() != Some(')') {
+                    return Err("missing ')'".to_string());
+                }
+                self.pos += 1;
+                Ok(inner)
+            }
+            '*' | '+' | '?' => Err(format!("nothing to repeat before '{c}'")),
+            '\\' => self.parse_escape(),
+            other => Ok(Node::Literal(other)),
+        }
+    }
+
+    fn parse_escape(&mut self) -> ParseResult<Node> {
+        let c = self.peek().ok_or("trailing backslash")?;
+        self.pos += 1;
+        Ok(match c {
+            'b' => Node::WordBoundary(true),
+            'B' => Node::WordBoundary(false),
+            _ => match escape_item(c)? {
+                ClassItem::Range(lo, _) => Node::Literal(lo),
+                item => Node::Class(Class {
+                    negated: false,
+                    items: vec![item],
+                }),
+            },
+        })
+    }
+
+    fn parse_class(&mut self) -> ParseResult<Class> {
+        let negated = self.peek() == Some('^');
+        if negated {
+            self.pos += 1;
+        }
+        let mut items = Vec::new();
+        let mut first = true;
+        loop {
+            let c = self.peek().ok_or("missing ']'")?;
+            self.pos += 1;
+            if c == ']' && !first {
+                break;
+            }
+            first = false;
+            let item = if c == '\\' {
+                let escaped = self.peek().ok_or("trailing backslash")?;
+                self.pos += 1;
+                escape_item(escaped)?
+            } else {
+                ClassItem::Range(c, c)
+            };
+            let ClassItem::Range(lo, _) = item else {
+                items.push(item);
+                continue;
+            };
+            if self.peek() == Some('-') && self.chars.get(self.pos + 1).is_some_and(|c| *c != ']') {
+                self.pos += 1;
+                let mut hi = self.peek().ok_or("missing ']'")?;
+                self.pos += 1;
+                if hi == '\\' {
+                    let escaped = self.peek().ok_or("trailing backslash")?;
+                    self.pos += 1;
+                    match escape_item(escaped)? {
+                        ClassItem::Range(c, _) => hi = c,
+                        ClassItem::Perl(..) => return Err("invalid class range".to_string()),
+                    }
+                }
+                if hi < lo {
+                    return Err(format!("invalid class range '{lo}-{hi}'"));
+                }
+                items.push(ClassItem::Range(lo, hi));
+            } else {
+                items.push(item);
+            }
+        }
+        Ok(Class { negated, items })
+    }
+
+    fn parse_quantifier(&mut self, atom: Node) -> ParseResult<Node> {
+        let (min, max) = match self.peek() {
+            Some(c @ ('*' | '+' | '?')) => {
+                self.pos += 1;
+                match c {
+                    '*' => (0, None),
+                    '+' => (1, None),
+                    _ => (0, Some(1)),
+                }
+            }
+            Some('{') => match self.parse_counted() {
+                Some(bounds) => bounds,
+                None => return Ok(atom),
+            },
+            _ => return Ok(atom),
+        };
+        if matches!(
+            atom,
+            Node::LineStart | Node::LineEnd | Node::WordBoundary(_) | Node::Empty
+        ) {
+            return Err("nothing to repeat".to_string());
+        }
+        if max.is_some_and(|max| max < min) {
+            return Err(format!("invalid repetition {{{min},{}}}", max.unwrap_or(0)));
+        }
+        if min > MAX_REPEAT || max.is_some_and(|max| max > MAX_REPEAT) {
+            return Err(format!("repetition exceeds {MAX_REPEAT}"));
+        }
+        let greedy = if self.peek() == Some('?') {
+            self.pos += 1;
+            false
+        } else {
+            true
+        };
+        Ok(Node::Repeat {
+            node: Box::new(atom),
+            min,
+            max,
+            greedy,
+        })
+    }
+
+    /// Parse `{n}`, `{n,}` or `{n,m}`; anything else leaves `{` as a literal.
+    fn parse_counted(&mut self) -> Option<(u32, Option<u32>)> {
+        let rest: String = self.chars[self.pos..].iter().collect();
+        let close = rest.find('}')?;
+        let inner = &rest[1..close];
+        let bounds = match inner.split_once(',') {
+            None => {
+                let n = inner.parse().ok()?;
+                (n, Some(n))
+            }
+            Some((lo, "")) => (lo.parse().ok()?, None),
+            Some((lo, hi)) => (lo.parse().ok()?, Some(hi.parse().ok()?)),
+        };
+        self.pos += inner.chars().count() + 2;
+        Some(bounds)
+    }
+}
+
+fn escape_item(c: char) -> ParseResult<ClassItem> {
+    Ok(match c {
+        'd' => ClassItem::Perl(Perl::Digit, false),
+        'D' => ClassItem::Perl(Perl::Digit, true),
+        'w' => ClassItem::Perl(Perl::Word, false),
+        'W' => ClassItem::Perl(Perl::Word, true),
+        's' => ClassItem::Perl(Perl::Space, false),
+        'S' => ClassItem::Perl(Perl::Space, true),
+        'n' => ClassItem::Range('\n', '\n'),
+        't' => ClassItem::Range('\t', '\t'),
+        'r' => ClassItem::Range('\r', '\r'),
+        c if c.is_ascii_alphanumeric() => return Err(format!("unsupported escape '\\{c}'")),
+        c => ClassItem::Range(c, c),
+    })
+}
+
+fn compile(node: &Node, program: &mut Vec<Inst>) -> ParseResult<()> {
+    if program.len() > MAX_PROGRAM {
+        return Err("pattern is too large".to_string());
+    }
+    match node {
+        Node::Empty => {}
+        Node::Literal(c) => program.push(Inst::Char(*c)),
+        Node::Any => program.push(Inst::Any),
+        Node::Class(class) => program.push(Inst::Class(class.clone())),
+        Node::LineStart => program.push(Inst::LineStart),
+        Node::LineEnd => program.push(Inst::LineEnd),
+        Node::WordBoundary(expected) => program.push(Inst::WordBoundary(*expected)),
+        Node::Concat(items) => {
+            for item in items {
+                compile(item, program)?;
+            }
+        }
+        Node::Alternate(branches) => {
+            let mut jumps = Vec::new();
+            for (index, branch) in branches.iter().enumerate() {
+                if index + 1 < branches.len() {
+                    let split = program.len();
+                    program.push(Inst::Split(split + 1, 0));
+                    compile(branch, program)?;
+                    jumps.push(program.len());
+                    program.push(Inst::Jump(0));
+                    let next = program.len();
+                    program[split] = Inst::Split(split + 1, next);
+                } else {
+                    compile(branch, program)?;
+                }
+            }
+            let end = program.len();
+            for jump in jumps {
+                program[jump] = Inst::Jump(end);
+            }
+        }
+        Node::Repeat {
+            node,
+            min,
+            max,
+            greedy,
+        } => {
+            for _ in 0..*min {
+                compile(node, program)?;
+            }
+            match max {
+                None => {
+                    let split = program.len();
+                    program.push(Inst::Split(0, 0));
+                    compile(node, program)?;
+                    program.push(Inst::Jump(split));
+                    let end = program.len();
+                    program[split] = branch(*greedy, split + 1, end);
+                }
+                Some(max) => {
+                    let mut splits = Vec::new();
+                    for _ in *min..*max {
+                        splits.push(program.len());
+                        program.push(Inst::Split(0, 0));
+                        compile(node, program)?;
+                    }
+                    let end = program.len();
+                    for split in splits {
+                        program[split] = branch(*greedy, split + 1, end);
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn branch(greedy: bool, body: usize, skip: usize) -> Inst {
+    if greedy {
+        Inst::Split(body, skip)
+    } else {
+        Inst::Split(skip, body)
+    }
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+fn fold(c: char) -> char {
+    c.to_lowercase().next().unwrap_or(c)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(pattern: &str, text: &str) -> bool {
+        Pattern::new(pattern)
+            .expect("pattern should compile")
+            .is_match(text)
+    }
+
+    #[test]
+    fn pattern_matches_literals_and_alternation() {
+        assert!(matches(
+            "All tests passed|FAILED",
+            "test result: FAILED. 1 passed"
+        ));
+        assert!(!matches("All tests passed|FAILED", "running 3 tests"));
+    }
+
+    #[test]
+    fn pattern_anchors_match_line_boundaries() {
+        assert!(matches("^> $", "output\n> \nfooter"));
+        assert!(!matches("^>$", "output > "));
+    }
+
+    #[test]
+    fn pattern_supports_classes_and_counted_repeats() {
+        assert!(matches(r"^\d{3}-[a-f0-9]+$", "123-beef"));
+        assert!(!matches(r"^\d{3}-[a-f0-9]+$", "12-beef"));
+        assert!(matches(r"[^\s]+@\w+", "mail me@host"));
+    }
+
+    #[test]
+    fn pattern_find_reports_byte_offsets() {
+        let pattern = Pattern::new("wé+").expect("pattern should compile");
+        assert_eq!(pattern.find("a wéé b"), Some((2, 7)));
+    }
+
+    #[test]
+    fn pattern_honors_case_insensitive_flag_and_laziness() {
+        assert!(matches("(?i)error", "ERROR: boom"));
+        let lazy = Pattern::new("<.+?>").expect("pattern should compile");
+        assert_eq!(lazy.find("<a><b>"), Some((0, 3)));
+    }
+
+    #[test]
+    fn pattern_rejects_invalid_syntax() {
+        assert!(Pattern::new("(unclosed").is_err());
+        assert!(Pattern::new("*start").is_err());
+        assert!(Pattern::new("[z-a]").is_err());
+    }
+
+    #[test]
+    fn pattern_survives_pathological_nesting() {
+        let text = "a".repeat(200);
+        assert!(!matches("^(a+)+b$", &text));
+    }
+}
@@ -0,0 +1,152 @@
+//! `amux pipe`: run a command locally and hand its output to an agent, so build
+//! and test results no longer need to be copied into the session by hand.
+
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::process::{Command, ExitStatus, Stdio};
+use std::sync::Mutex;
+use std::thread;
+
+use crate::error::{bail, with_context, Result};
+use crate::output::strip_ansi;
+
+/// Output of a finished command, stdout and stderr interleaved line by line.
+#[derive(Debug)]
+pub struct Captured {
+    pub output: String,
+    pub status: ExitStatus,
+}
+
+/// Run `command`, echoing its output to this terminal as it arrives and
+/// collecting it for the agent.
+pub fn run(command: &[String]) -> Result<Captured> {
+    let Some((program, args)) = command.split_first() else {
+        return bail("pipe: no command given after --");
+    };
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| with_context(err, format!("failed to run '{program}'")))?;
+
+    let collected = Mutex::new(String::new());
+    let stdout = child.stdout.take();
+    let stderr = child.stderr.take();
+    thread::scope(|scope| {
+        if let Some(stdout) = stdout {
+            scope.spawn(|| relay(stdout, &mut io::stdout(), &collected));
+        }
+        if let Some(stderr) = stderr {
+            scope.spawn(|| relay(stderr, &mut io::stderr(), &collected));
+        }
+    });
+    let status = child
+        .wait()
+        .map_err(|err| with_context(err, format!("failed to wait for '{program}'")))?;
+
+    Ok(Captured {
+        output: collected
+            .into_inner()
+            .unwrap_or_else(|err| err.into_inner()),
+        status,
+    })
+}
+
+/// Copy `source` to `echo` line by line, appending each line to `collected`.
+fn relay(source: impl Read, echo: &mut impl Write, collected: &Mutex<String>) {
+    let mut reader = BufReader::new(source);
+    let mut line = Vec::new();
+    while reader
+        .read_until(b'\n', &mut line)
+        .is_ok_and(|read| read > 0)
+    {
+        let _ = echo.write_all(&line);
+        let _ = echo.flush();
+        if let Ok(mut collected) = collected.lock() {
+            collected.push_str(&String::from_utf8_lossy(&line));
+        }
+        line.clear();
+    }
+}
+
+/// The prompt handed to the agent: `intro` (or a default naming the command),
+/// the exit status, and the last `tail` lines of output in a fenced block.
+pub fn prompt(
+    intro: Option<&str>,
+    command: &[String],
+    status: Option<i32>,
+    output: &str,
+    tail: usize,
+) -> String {
+    let command_line = shell_words::join(command);
+    let intro = intro
+        .map(str::to_string)
+        .unwrap_or_else(|| format!("Here is the output of `{command_line}`"));
+    let outcome = match status {
+        Some(0) => "it succeeded".to_string(),
+        Some(code) => format!("it failed with exit status {code}"),
+        None => "it was killed by a signal".to_string(),
+    };
+
+    let clean = strip_ansi(output);
+    let lines: Vec<&str> = clean.trim_end().lines().collect();
+    let start = lines.len().saturating_sub(tail);
+    let note = if start > 0 {
+        format!(" (last {} of {} lines)", lines.len() - start, lines.len())
+    } else {
+        String::new()
+    };
+    format!(
+        "{intro} ({outcome}){note}:\n```\n{}\n```",
+        lines[start..].join("\n")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn command(raw: &[&str]) -> Vec<String> {
+        raw.iter().map(|part| part.to_string()).collect()
+    }
+
+    #[test]
+    fn prompt_names_command_and_status() {
+        let text = prompt(
+            None,
+            &command(&["cargo", "test", "--all"]),
+            Some(101),
+            "\u{1b}[31mFAILED\u{1b}[0m\n",
+            50,
+        );
+        assert_eq!(
+            text,
+            "Here is the output of `cargo test --all` (it failed with exit status 101):\n```\nFAILED\n```"
+        );
+    }
+
+    #[test]
+    fn prompt_keeps_only_the_tail() {
+        let text = prompt(
+            Some("Fix these"),
+            &command(&["make"]),
+            Some(0),
+            "a\nb\nc\n",
+            2,
+        );
+        assert_eq!(
+            text,
+            "Fix these (it succeeded) (last 2 of 3 lines):\n```\nb\nc\n```"
+        );
+    }
+
+    #[test]
+    fn run_collects_stdout_and_stderr() {
+        let captured = run(&command(&["sh", "-c", "echo out; echo err >&2; exit 3"]))
+            .expect("command should run");
+        assert_eq!(captured.status.code(), Some(3));
+        assert!(captured.output.contains("out\n"));
+        assert!(captured.output.contains("err\n"));
+    }
+}
@@ -0,0 +1,156 @@
+//! Plans for commands that change many sessions at once (`start @group`,
+//! `kill-server`): what each session would go through, printed like a diff
+//! so it can be reviewed with `--plan` before anything happens, and approved
+//! interactively or with `--auto-approve` when it replaces or removes sessions.
+
+use std::io::{self, IsTerminal};
+
+use crate::checklist;
+use crate::error::{fail_with, ErrorKind, Result};
+
+/// What happens to one session.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    Create,
+    Keep,
+    /// Mark a session amux did not start as its own.
+    Adopt,
+    /// Kill the session and start it again.
+    Replace,
+    Remove,
+    /// The session is in the way and the command will fail for it.
+    Conflict,
+}
+
+impl Action {
+    fn symbol(self) -> &'static str {
+        match self {
+            Self::Create => "+",
+            Self::Keep => "=",
+            Self::Adopt => "~",
+            Self::Replace => "-/+",
+            Self::Remove => "-",
+            Self::Conflict => "!",
+        }
+    }
+
+    fn is_destructive(self) -> bool {
+        matches!(self, Self::Replace | Self::Remove)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Step {
+    pub action: Action,
+    pub session: String,
+    /// Why, e.g. `pinned` or `not started by amux`.
+    pub reason: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Plan {
+    pub steps: Vec<Step>,
+}
+
+impl Plan {
+    pub fn push(&mut self, action: Action, session: &str, reason: Option<&str>) {
+        self.steps.push(Step {
+            action,
+            session: session.to_string(),
+            reason: reason.map(str::to_string),
+        });
+    }
+
+    /// Whether applying the plan kills any session.
+    pub fn is_destructive(&self) -> bool {
+        self.steps.iter().any(|step| step.action.is_destructive())
+    }
+
+    fn count(&self, action: Action) -> usize {
+        self.steps
+            .iter()
+            .filter(|step| step.action == action)
+            .count()
+    }
+
+    /// One line per session, then a summary line.
+    pub fn render(&self) -> String {
+        let mut text = String::new();
+        for step in &self.steps {
+            let line = format!("{:>3} {}", step.action.symbol(), step.session);
+            match &step.reason {
+                Some(reason) => text.push_str(&format!("{line} ({reason})\n")),
+                None => text.push_str(&format!("{line}\n")),
+            }
+        }
+        let mut parts = vec![
+            format!("{} to create", self.count(Action::Create)),
+            format!("{} to replace", self.count(Action::Replace)),
+            format!("{} to remove", self.count(Action::Remove)),
+            format!("{} unchanged", self.count(Action::Keep)),
+        ];
+        for (action, label) in [
+            (Action::Adopt, "to adopt"),
+            (Action::Conflict, "in conflict"),
+        ] {
+            let count = self.count(action);
+            if count > 0 {
+                parts.push(format!("{count} {label}"));
+            }
+        }
+        text.push_str(&format!("Plan: {}.\n", parts.join(", ")));
+        text
+    }
+}
+
+/// Ask before applying a destructive `plan` for `command`. `--auto-approve`
+/// skips the question; without a terminal to ask on, the plan is refused.
+pub fn approve(plan: &Plan, command: &str, auto_approve: bool) -> Result<bool> {
+    if auto_approve || !plan.is_destructive() {
+        return Ok(true);
+    }
+    if !io::stdin().is_terminal() {
+        return Err(fail_with(
+            ErrorKind::InvalidInput,
+            format!("{command} would replace or remove sessions and needs confirmation"),
+            Some("review it with --plan, then pass --auto-approve to apply it unattended".into()),
+        ));
+    }
+    checklist::confirm(
+        "Apply this plan?",
+        &mut io::stdin().lock(),
+        &mut io::stdout(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_lists_steps_and_counts_them() {
+        let mut plan = Plan::default();
+        plan.push(Action::Create, "amux-codex", None);
+        plan.push(Action::Keep, "amux-claude", Some("pinned"));
+        plan.push(Action::Replace, "amux-gemini", None);
+        assert_eq!(
+            plan.render(),
+            "  + amux-codex\n  = amux-claude (pinned)\n-/+ amux-gemini\n\
+             Plan: 1 to create, 1 to replace, 0 to remove, 1 unchanged.\n"
+        );
+        plan.push(Action::Conflict, "amux-aider", Some("not started by amux"));
+        assert!(plan.render().ends_with(", 1 in conflict.\n"));
+    }
+
+    #[test]
+    fn only_replacing_or_removing_is_destructive() {
+        let mut plan = Plan::default();
+        plan.push(Action::Create, "amux-codex", None);
+        plan.push(Action::Adopt, "amux-claude", None);
+        assert!(!plan.is_destructive());
+        assert!(approve(&plan, "start @dev", false).expect("nothing to confirm"));
+        plan.push(Action::Remove, "amux-gemini", None);
+        assert!(plan.is_destructive());
+        assert!(approve(&plan, "kill-server", true).expect("auto-approved"));
+    }
+}
@@ -0,0 +1,213 @@
+//! Checks run before an agent is launched, so a full disk or an exhausted API
+//! quota is reported up front instead of killing the agent halfway through,
+//! and an agent is not let loose on a home directory or `/` by accident.
+
+use std::collections::BTreeMap;
+use std::env;
+use std::io::{self, IsTerminal};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use crate::checklist;
+use crate::config::{DirGuard, OutsideGit, Preflight};
+use crate::error::{fail_with, with_context, ErrorKind, Result};
+use crate::git;
+use crate::hooks;
+
+static ANY_DIR: AtomicBool = AtomicBool::new(false);
+
+/// Answers to the outside-a-repository question, by directory, so a group
+/// start asks once. Held while asking, so parallel starts do not ask at once.
+static ANSWERS: Mutex<BTreeMap<PathBuf, bool>> = Mutex::new(BTreeMap::new());
+
+/// Skip the directory guard (`--allow-any-dir`).
+pub fn set_allow_any_dir(allow: bool) {
+    ANY_DIR.store(allow, Ordering::Relaxed);
+}
+
+/// Refuse to start `agent` in a directory `guard` denies, and ask (or refuse)
+/// before starting it outside a git repository.
+pub fn check_dir(guard: &DirGuard, agent: &str, dir: &Path) -> Result<()> {
+    if ANY_DIR.load(Ordering::Relaxed) {
+        return Ok(());
+    }
+    let dir = dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf());
+    let home = env::var_os("HOME").map(PathBuf::from);
+    if let Some(entry) = denied(&guard.deny, &dir, home.as_deref()) {
+        return Err(fail_with(
+            ErrorKind::PreflightFailed,
+            format!(
+                "{agent}: not starting in {} (denied by '{entry}' in dir_guard.deny)",
+                dir.display()
+            ),
+            Some("cd into a project first, or pass --allow-any-dir".into()),
+        ));
+    }
+    if guard.outside_git == OutsideGit::Allow || git::toplevel(&dir)?.is_some() {
+        return Ok(());
+    }
+
+    let outside = format!("{} is not inside a git repository", dir.display());
+    if guard.outside_git == OutsideGit::Refuse {
+        return Err(fail_with(
+            ErrorKind::PreflightFailed,
+            format!("{agent}: not starting, {outside}"),
+            Some("pass --allow-any-dir, or set dir_guard.outside_git = \"ask\"".into()),
+        ));
+    }
+    if !io::stdin().is_terminal() || !io::stderr().is_terminal() {
+        eprintln!("amux: warning: {agent}: {outside}; starting anyway");
+        return Ok(());
+    }
+    let mut answers = ANSWERS.lock().unwrap_or_else(|err| err.into_inner());
+    let consent = match answers.get(&dir) {
+        Some(consent) => *consent,
+        None => {
+            // Ask on stderr so commands that print JSON keep stdout clean.
+            let consent = checklist::confirm(
+                &format!("{outside}. Start {agent} there anyway?"),
+                &mut io::stdin().lock(),
+                &mut io::stderr(),
+            )?;
+            answers.insert(dir.clone(), consent);
+            consent
+        }
+    };
+    if !consent {
+        return Err(fail_with(
+            ErrorKind::PreflightFailed,
+            format!("{agent}: not starting, {outside}"),
+            None,
+        ));
+    }
+    Ok(())
+}
+
+/// The `deny` entry covering `dir`, if any.
+fn denied<'a>(deny: &'a [String], dir: &Path, home: Option<&Path>) -> Option<&'a str> {
+    deny.iter()
+        .find(|entry| {
+            let (raw, subtree) = match entry.strip_suffix("/**") {
+                Some(raw) => (raw, true),
+                None => (entry.as_str(), false),
+            };
+            let path = match (raw, raw.strip_prefix("~/"), home) {
+                ("~", _, Some(home)) => home.to_path_buf(),
+                (_, Some(rest), Some(home)) => home.join(rest),
+                _ => PathBuf::from(raw),
+            };
+            let path = path.canonicalize().unwrap_or(path);
+            dir == path || (subtree && dir.starts_with(&path))
+        })
+        .map(String::as_str)
+}
+
+/// Run `settings`' checks for a session of `agent` about to start in `dir`.
+pub fn check(
+    settings: &Preflight,
+    agent: &str,
+    session: &str,
+    name: Option<&str>,
+    dir: &Path,
+) -> Result<()> {
+    if let Some(min_mb) = settings.min_free_disk_mb {
+        let free_mb = free_disk_mb(dir)?;
+        if free_mb < min_mb {
+            return Err(fail_with(
+                ErrorKind::PreflightFailed,
+                format!(
+                    "{agent}: only {free_mb} MB free in {} (needs {min_mb} MB); not starting",
+                    dir.display()
+                ),
+                Some("free up disk space, or lower min_free_disk_mb".into()),
+            ));
+        }
+    }
+    if let Some(command) = &settings.command {
+        hooks::run("preflight", command, agent, session, name).map_err(|err| {
+            fail_with(
+                ErrorKind::PreflightFailed,
+                format!("{agent}: {err}; not starting"),
+                Some(format!("the check was: {command}")),
+            )
+        })?;
+    }
+    Ok(())
+}
+
+/// Megabytes available to unprivileged users on the filesystem holding `dir`.
+fn free_disk_mb(dir: &Path) -> Result<u64> {
+    let output = Command::new("df")
+        .arg("-Pk")
+        .arg(dir)
+        .output()
+        .map_err(|err| with_context(err, "failed to run df"))?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    match parse_df(&stdout) {
+        Some(kb) if output.status.success() => Ok(kb / 1024),
+        _ => Err(fail_with(
+            ErrorKind::Other,
+            format!("could not read the free disk space of {}", dir.display()),
+            None,
+        )),
+    }
+}
+
+/// The available kilobytes in POSIX `df -Pk` output: the column before the
+/// capacity percentage, which stays put even if names contain spaces.
+fn parse_df(output: &str) -> Option<u64> {
+    let line = output.lines().nth(1)?;
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    let capacity = fields.iter().rposition(|field| field.ends_with('%'))?;
+    fields.get(capacity.checked_sub(1)?)?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn denied_matches_exact_directories_and_marked_subtrees() {
+        let deny = ["/".to_string(), "~".into(), "~/Documents/**".into()];
+        let home = Some(Path::new("/home/me"));
+        let denied = |dir: &str| denied(&deny, Path::new(dir), home);
+        assert_eq!(denied("/"), Some("/"));
+        assert_eq!(denied("/home/me"), Some("~"));
+        assert_eq!(denied("/home/me/src/api"), None);
+        assert_eq!(denied("/home/me/Documents"), Some("~/Documents/**"));
+        assert_eq!(denied("/home/me/Documents/taxes"), Some("~/Documents/**"));
+    }
+
+    #[test]
+    fn parse_df_reads_available_kilobytes() {
+        let output = "Filesystem 1024-blocks Used Available Capacity Mounted on\n\
+                      /dev/disk3s1 971350180 512 83384716 14% /Volumes/My Disk\n";
+        assert_eq!(parse_df(output), Some(83_384_716));
+        assert_eq!(parse_df("Filesystem\n"), None);
+    }
+
+    #[test]
+    fn check_refuses_failing_command_and_full_disk() {
+        let dir = std::env::temp_dir();
+        let failing = Preflight {
+            min_free_disk_mb: None,
+            command: Some("echo 'quota exhausted' >&2; exit 1".into()),
+        };
+        let err = check(&failing, "codex", "amux-codex", None, &dir)
+            .expect_err("failing check should refuse");
+        assert!(err.to_string().contains("preflight hook exited"));
+
+        let huge = Preflight {
+            min_free_disk_mb: Some(u64::MAX),
+            command: None,
+        };
+        let err =
+            check(&huge, "codex", "amux-codex", None, &dir).expect_err("no disk is that large");
+        assert!(err.to_string().contains("MB free"));
+
+        check(&Preflight::default(), "codex", "amux-codex", None, &dir)
+            .expect("no checks configured");
+    }
+}
@@ -0,0 +1,146 @@
+//! `amux profile bench`: how long the tmux queries behind amux take on this
+//! machine, for attaching to a report that `status` is slow. Each query is
+//! run a few times against the live sessions, and `status` itself is timed
+//! end to end as a separate process.
+
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+use crate::error::{with_context, Result};
+use crate::tmux;
+
+/// The timings of one kind of query.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Measurement {
+    pub label: &'static str,
+    pub samples: Vec<Duration>,
+}
+
+impl Measurement {
+    fn new(label: &'static str) -> Self {
+        Self {
+            label,
+            samples: Vec::new(),
+        }
+    }
+
+    /// Run `query` and record how long it took.
+    fn time<T>(&mut self, query: impl FnOnce() -> T) -> T {
+        let started = Instant::now();
+        let result = query();
+        self.samples.push(started.elapsed());
+        result
+    }
+
+    /// Fastest, median, and slowest sample.
+    fn spread(&self) -> Option<(Duration, Duration, Duration)> {
+        let mut sorted = self.samples.clone();
+        sorted.sort_unstable();
+        Some((*sorted.first()?, sorted[sorted.len() / 2], *sorted.last()?))
+    }
+}
+
+/// The benchmark's results, in the order queries are listed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Report {
+    pub sessions: usize,
+    pub runs: u32,
+    pub measurements: Vec<Measurement>,
+}
+
+/// Time each query `runs` times; per-session queries run once per session in
+/// each round. `exe` is the amux whose `status` is timed.
+pub fn bench(exe: &Path, runs: u32) -> Result<Report> {
+    let mut round_trip = Measurement::new("has-session (round trip)");
+    let mut list = Measurement::new("list-sessions");
+    let mut clients = Measurement::new("list-clients, per session");
+    let mut info = Measurement::new("display-message, per session");
+    let mut panes = Measurement::new("list-panes, per session");
+    let mut capture = Measurement::new("capture-pane, per session");
+    let mut status = Measurement::new("amux status, end to end");
+
+    let mut sessions = Vec::new();
+    for _ in 0..runs {
+        round_trip.time(|| tmux::has_session("amux-profile-bench"))?;
+        sessions = list.time(tmux::list_sessions)?;
+        for session in &sessions {
+            let session = session.session_name.as_str();
+            clients.time(|| tmux::client_count(session))?;
+            info.time(|| tmux::session_info(session))?;
+            let pane = panes.time(|| tmux::agent_pane(session))?;
+            capture.time(|| tmux::capture_pane(&pane, None))?;
+        }
+        let exit = status.time(|| {
+            Command::new(exe)
+                .arg("status")
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .status()
+        });
+        exit.map_err(|err| with_context(err, "failed to run amux status"))?;
+    }
+
+    Ok(Report {
+        sessions: sessions.len(),
+        runs,
+        measurements: vec![round_trip, list, clients, info, panes, capture, status],
+    })
+}
+
+/// A table of calls and min/median/max latency per query.
+pub fn render(report: &Report) -> String {
+    let mut text = format!(
+        "amux {}: {} agent session(s), {} run(s)\n",
+        env!("CARGO_PKG_VERSION"),
+        report.sessions,
+        report.runs
+    );
+    text.push_str(&format!(
+        "{:<30} {:>5} {:>9} {:>9} {:>9}\n",
+        "query", "calls", "min", "median", "max"
+    ));
+    for measurement in &report.measurements {
+        let (min, median, max) = match measurement.spread() {
+            Some((min, median, max)) => (millis(min), millis(median), millis(max)),
+            None => ("-".into(), "-".into(), "-".into()),
+        };
+        text.push_str(&format!(
+            "{:<30} {:>5} {min:>9} {median:>9} {max:>9}\n",
+            measurement.label,
+            measurement.samples.len()
+        ));
+    }
+    text
+}
+
+fn millis(duration: Duration) -> String {
+    format!("{:.1}ms", duration.as_secs_f64() * 1000.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_reports_spread_and_skips_unused_queries() {
+        let mut list = Measurement::new("list-sessions");
+        list.samples = [3, 1, 2].map(Duration::from_millis).to_vec();
+        let report = Report {
+            sessions: 0,
+            runs: 3,
+            measurements: vec![list, Measurement::new("capture-pane, per session")],
+        };
+        let text = render(&report);
+        let lines: Vec<&str> = text.lines().collect();
+        assert!(lines[0].ends_with(": 0 agent session(s), 3 run(s)"));
+        assert_eq!(
+            lines[2],
+            "list-sessions                      3     1.0ms     2.0ms     3.0ms"
+        );
+        assert_eq!(
+            lines[3],
+            "capture-pane, per session          0         -         -         -"
+        );
+    }
+}
@@ -0,0 +1,130 @@
+//! Telling when a freshly started agent is actually interactive, for
+//! `amux start --wait-ready`.
+//!
+//! An agent's `[agents.NAME.ready]` table can give a `pattern` its output shows
+//! once it is ready, a probe `command` that succeeds once it is, or both. With
+//! neither, the agent counts as ready when its response boundary is met: output
+//! has gone quiet and, if configured, its input prompt is visible.
+
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::config::Config;
+use crate::error::Result;
+use crate::pattern::Pattern;
+use crate::response::{self, Boundary};
+
+/// How long to wait when neither the command line nor the config says.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(60);
+const PROBE_INTERVAL: Duration = Duration::from_millis(500);
+
+#[derive(Debug)]
+pub struct Probe {
+    pattern: Option<Pattern>,
+    command: Option<String>,
+    boundary: Boundary,
+    pub timeout: Duration,
+}
+
+impl Probe {
+    pub fn for_agent(config: &Config, agent: &str) -> Result<Self> {
+        let ready = config
+            .agent(agent)
+            .map(|agent| agent.ready.clone())
+            .unwrap_or_default();
+        Ok(Self {
+            pattern: ready.pattern.as_deref().map(Pattern::new).transpose()?,
+            command: ready.command,
+            boundary: Boundary::for_agent(config, agent)?,
+            timeout: ready
+                .timeout_secs
+                .map(Duration::from_secs)
+                .unwrap_or(DEFAULT_TIMEOUT),
+        })
+    }
+
+    /// Whether the pane shows the agent as ready; a probe command alone
+    /// leaves the decision to the command.
+    fn screen_ready(&self, screen: &str, stable_for: Duration) -> bool {
+        match (&self.pattern, &self.command) {
+            (Some(pattern), _) => pattern.is_match(screen),
+            (None, Some(_)) => true,
+            (None, None) => self.boundary.is_complete(screen, stable_for),
+        }
+    }
+}
+
+/// Block until the agent in `session` passes `probe`. Returns `false` when
+/// `timeout` elapses first.
+pub fn wait(probe: &Probe, agent: &str, session: &str, timeout: Duration) -> Result<bool> {
+    let started = Instant::now();
+    if !response::watch_pane(session, Some(timeout), |screen, stable_for| {
+        probe.screen_ready(screen, stable_for)
+    })? {
+        return Ok(false);
+    }
+    let Some(command) = &probe.command else {
+        return Ok(true);
+    };
+    loop {
+        if run_probe(command, agent, session) {
+            return Ok(true);
+        }
+        if started.elapsed() >= timeout {
+            return Ok(false);
+        }
+        thread::sleep(PROBE_INTERVAL);
+    }
+}
+
+/// Run the probe command quietly; it passes when it exits with status 0.
+fn run_probe(command: &str, agent: &str, session: &str) -> bool {
+    Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .env("AMUX_AGENT", agent)
+        .env("AMUX_SESSION", session)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config;
+
+    fn probe(raw: &str) -> Probe {
+        let config = config::parse(raw).expect("config should parse");
+        Probe::for_agent(&config, "codex").expect("probe should build")
+    }
+
+    #[test]
+    fn screen_ready_prefers_pattern_then_command_then_boundary() {
+        let pattern = probe("[agents.codex.ready]\npattern = '^› '\n");
+        assert!(pattern.screen_ready("Welcome\n› ", Duration::ZERO));
+        assert!(!pattern.screen_ready("Loading...", Duration::from_secs(10)));
+
+        let command = probe("[agents.codex.ready]\ncommand = 'true'\ntimeout_secs = 5\n");
+        assert!(command.screen_ready("", Duration::ZERO));
+        assert_eq!(command.timeout, Duration::from_secs(5));
+
+        let fallback = probe("[agents.codex.response]\nquiet_ms = 100\n");
+        assert!(!fallback.screen_ready("", Duration::from_millis(50)));
+        assert!(fallback.screen_ready("", Duration::from_millis(150)));
+        assert_eq!(fallback.timeout, DEFAULT_TIMEOUT);
+    }
+
+    #[test]
+    fn run_probe_reports_exit_status_and_sees_session() {
+        assert!(run_probe(
+            r#"test "$AMUX_AGENT:$AMUX_SESSION" = "codex:amux-codex""#,
+            "codex",
+            "amux-codex"
+        ));
+        assert!(!run_probe("echo noisy; exit 1", "codex", "amux-codex"));
+    }
+}
@@ -0,0 +1,157 @@
+//! `amux relay`: forward what one agent says to another, for reviewer and
+//! implementer pairs wired together through amux alone.
+//!
+//! Output is read from the source session's log. Each time the source agent
+//! finishes a response (the same boundary `wait --for-response` uses), the text
+//! it produced is searched with the filter and every match is sent to the
+//! target session as one prompt.
+
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::error::{fail_with, ErrorKind, Result};
+use crate::input;
+use crate::output::Tail;
+use crate::pattern::Pattern;
+use crate::response::Boundary;
+use crate::tmux;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+/// Longest block of source output kept while waiting for a response to end.
+const BLOCK_LIMIT: usize = 64 * 1024;
+
+pub struct Relay {
+    pub from: String,
+    pub to: String,
+    /// Forward only the text matching this; the whole response otherwise.
+    pub filter: Option<Pattern>,
+    /// Placed above the forwarded text.
+    pub intro: Option<String>,
+    /// How to tell that the source agent has finished a response.
+    pub boundary: Boundary,
+    /// How prompts are typed into the target agent.
+    pub input: input::Settings,
+}
+
+impl Relay {
+    /// Forward blocks until the source session ends, or after the first one
+    /// when `once` is set. `forwarded` is called with every prompt sent.
+    pub fn run(&self, once: bool, mut forwarded: impl FnMut(&str)) -> Result<()> {
+        let Some(mut tail) = Tail::from_end(&self.from)? else {
+            return Err(fail_with(
+                ErrorKind::InvalidInput,
+                format!("session '{}' has no output log to relay from", self.from),
+                Some("restart it with `amux start --force` to enable output tracking".into()),
+            ));
+        };
+        let source = tmux::agent_pane(&self.from)?;
+        let mut block = String::new();
+        let mut last_output = Instant::now();
+
+        while tmux::has_session(&self.from)? {
+            let fresh = tail.read_new()?;
+            if !fresh.is_empty() {
+                block.push_str(&fresh);
+                trim_front(&mut block, BLOCK_LIMIT);
+                last_output = Instant::now();
+            } else if !block.is_empty() {
+                let screen = tmux::capture_pane(&source, None)?;
+                if self.boundary.is_complete(&screen, last_output.elapsed()) {
+                    if let Some(prompt) = self.prompt(&block) {
+                        let target = tmux::agent_pane(&self.to)?;
+                        input::send(&target, &prompt, &self.input)?;
+                        forwarded(&prompt);
+                        if once {
+                            return Ok(());
+                        }
+                    }
+                    block.clear();
+                }
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+        Ok(())
+    }
+
+    fn prompt(&self, block: &str) -> Option<String> {
+        let text = extract(block, self.filter.as_ref())?;
+        Some(match &self.intro {
+            Some(intro) => format!("{intro}\n\n{text}"),
+            None => text,
+        })
+    }
+}
+
+/// Every non-overlapping match of `filter` in `block`, separated by blank
+/// lines, or the whole block without a filter; `None` when nothing is left.
+fn extract(block: &str, filter: Option<&Pattern>) -> Option<String> {
+    let text = match filter {
+        None => block.trim().to_string(),
+        Some(filter) => {
+            let mut matches = Vec::new();
+            let mut rest = block;
+            while let Some((start, end)) = filter.find(rest) {
+                let found = rest[start..end].trim();
+                if !found.is_empty() {
+                    matches.push(found);
+                }
+                // Step past empty matches so the search always moves forward.
+                let next = if end > start {
+                    end
+                } else {
+                    rest[end..]
+                        .chars()
+                        .next()
+                        .map_or(rest.len(), |c| end + c.len_utf8())
+                };
+                if next >= rest.len() {
+                    break;
+                }
+                rest = &rest[next..];
+            }
+            matches.join("\n\n")
+        }
+    };
+    (!text.is_empty()).then_some(text)
+}
+
+fn trim_front(text: &mut String, limit: usize) {
+    if text.len() > limit {
+        let mut cut = text.len() - limit;
+        while !text.is_char_boundary(cut) {
+            cut += 1;
+        }
+        text.drain(..cut);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extract_collects_every_match() {
+        let filter = Pattern::new(r"REVIEW:[^\n]*").expect("pattern should compile");
+        let block = "thinking...\nREVIEW: rename foo\nmore\nREVIEW: add a test\n";
+        assert_eq!(
+            extract(block, Some(&filter)).as_deref(),
+            Some("REVIEW: rename foo\n\nREVIEW: add a test")
+        );
+        assert_eq!(extract("nothing here", Some(&filter)), None);
+    }
+
+    #[test]
+    fn extract_without_filter_forwards_the_block() {
+        assert_eq!(extract("\n  done  \n", None).as_deref(), Some("done"));
+        assert_eq!(extract(" \n", None), None);
+        let empty = Pattern::new("x*").expect("pattern should compile");
+        assert_eq!(extract("abc", Some(&empty)), None);
+    }
+
+    #[test]
+    fn trim_front_keeps_the_end_on_a_char_boundary() {
+        let mut text = "ééé".to_string();
+        trim_front(&mut text, 3);
+        assert_eq!(text, "é");
+    }
+}
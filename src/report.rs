@@ -0,0 +1,375 @@
+//! Post-run summaries: when a session is removed or its agent exits, amux
+//! records how long it ran, what it changed, what it cost, and where its log
+//! is, and appends the report to `history.jsonl` in the state directory.
+//!
+//! Natural exits are caught by a tmux `pane-died` hook installed at start, which
+//! calls back into `amux record-exit` before the session is cleaned up.
+
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+
+use crate::error::{with_context, Result};
+use crate::git;
+use crate::json::{self, Value};
+use crate::nested;
+use crate::origin::Origin;
+use crate::paths;
+use crate::time::{format_duration, unix_now};
+use crate::tmux;
+use crate::usage::{self, Usage};
+
+/// Why a run ended.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EndReason {
+    /// The session was removed with `amux rm`.
+    Removed,
+    /// The agent was asked to exit with `amux stop`.
+    Stopped,
+    /// The agent's program exited on its own.
+    Exited,
+}
+
+impl EndReason {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Removed => "removed",
+            Self::Stopped => "stopped",
+            Self::Exited => "exited",
+        }
+    }
+
+    fn parse(raw: &str) -> Option<Self> {
+        match raw {
+            "removed" => Some(Self::Removed),
+            "stopped" => Some(Self::Stopped),
+            "exited" => Some(Self::Exited),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct RunReport {
+    pub agent: String,
+    pub session: String,
+    pub reason: EndReason,
+    pub started: Option<u64>,
+    pub ended: u64,
+    pub cwd: Option<String>,
+    /// Working-tree summary such as `3 changed, +1/-0`, when the cwd is a git repository.
+    pub changes: Option<String>,
+    pub exit_status: Option<i32>,
+    pub log_path: Option<String>,
+    /// Tokens and estimated cost from the agent CLI's own logs, when it has an adapter.
+    pub usage: Option<Usage>,
+    /// Agent sessions whose own amux started or removed this run.
+    pub started_by: Option<String>,
+    pub removed_by: Option<String>,
+}
+
+impl RunReport {
+    pub fn duration(&self) -> Option<u64> {
+        self.started
+            .map(|started| self.ended.saturating_sub(started))
+    }
+
+    pub fn to_json(&self) -> Value {
+        json::object([
+            ("agent", Value::from(self.agent.as_str())),
+            ("session", Value::from(self.session.as_str())),
+            ("reason", Value::from(self.reason.as_str())),
+            ("started_at", Value::from(self.started)),
+            ("ended_at", Value::from(self.ended)),
+            ("duration_seconds", Value::from(self.duration())),
+            ("cwd", Value::from(self.cwd.clone())),
+            ("changes", Value::from(self.changes.clone())),
+            ("exit_status", Value::from(self.exit_status.map(i64::from))),
+            ("log_path", Value::from(self.log_path.clone())),
+            (
+                "usage",
+                self.usage.as_ref().map_or(Value::Null, Usage::to_json),
+            ),
+            ("started_by_session", Value::from(self.started_by.clone())),
+            ("removed_by_session", Value::from(self.removed_by.clone())),
+        ])
+    }
+
+    /// Read back a history line written by [`record`].
+    pub fn from_json(value: &Value) -> Option<Self> {
+        let text = |key: &str| value.get(key).and_then(Value::as_str).map(str::to_string);
+        Some(Self {
+            agent: text("agent")?,
+            session: text("session")?,
+            reason: EndReason::parse(value.get("reason")?.as_str()?)?,
+            started: value.get("started_at").and_then(Value::as_u64),
+            ended: value.get("ended_at")?.as_u64()?,
+            cwd: text("cwd"),
+            changes: text("changes"),
+            exit_status: value
+                .get("exit_status")
+                .and_then(Value::as_i64)
+                .and_then(|status| i32::try_from(status).ok()),
+            log_path: text("log_path"),
+            usage: value.get("usage").and_then(Usage::from_json),
+            started_by: text("started_by_session"),
+            removed_by: text("removed_by_session"),
+        })
+    }
+
+    /// One-line summary for `status --recent`, e.g. `exited 2h 5m ago after 40m, exit status 1`.
+    pub fn recent_summary(&self, now: u64) -> String {
+        let mut summary = format!(
+            "{} {} ago",
+            self.reason.as_str(),
+            format_duration(now.saturating_sub(self.ended))
+        );
+        if let Some(duration) = self.duration() {
+            summary.push_str(&format!(" after {}", format_duration(duration)));
+        }
+        if let Some(status) = self.exit_status {
+            summary.push_str(&format!(", exit status {status}"));
+        }
+        if let Some(session) = &self.removed_by {
+            summary.push_str(&format!(", by agent session {session}"));
+        }
+        summary
+    }
+}
+
+impl fmt::Display for RunReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let text = |value: Option<String>| value.unwrap_or_else(|| "-".to_string());
+        writeln!(
+            f,
+            "  ran:           {}",
+            text(self.duration().map(format_duration))
+        )?;
+        writeln!(f, "  cwd:           {}", text(self.cwd.clone()))?;
+        writeln!(f, "  changes:       {}", text(self.changes.clone()))?;
+        let exit = match (self.reason, self.exit_status) {
+            (_, Some(status)) => status.to_string(),
+            (EndReason::Removed, None) => "- (removed while running)".to_string(),
+            (EndReason::Stopped, None) => "- (ended by a signal)".to_string(),
+            (EndReason::Exited, None) => "-".to_string(),
+        };
+        writeln!(f, "  exit status:   {exit}")?;
+        writeln!(
+            f,
+            "  usage:         {}",
+            text(self.usage.as_ref().map(Usage::summary))
+        )?;
+        for (label, session) in [
+            ("started by", &self.started_by),
+            ("removed by", &self.removed_by),
+        ] {
+            if let Some(session) = session {
+                writeln!(f, "  {label}:    agent session {session}")?;
+            }
+        }
+        write!(f, "  log:           {}", text(self.log_path.clone()))
+    }
+}
+
+/// Gather a report for `session`; call this while the session still exists.
+pub fn collect(agent: &str, session: &str, reason: EndReason) -> Result<RunReport> {
+    let info = tmux::session_info(session)?;
+    let usage = usage::for_session(agent, &info);
+    let changes = info
+        .cwd
+        .as_deref()
+        .and_then(|cwd| git::status(Path::new(cwd)).ok().flatten())
+        .map(|status| status.summary());
+    let log_path = paths::session_log(session)?;
+    let log_path = log_path
+        .exists()
+        .then(|| log_path.to_string_lossy().into_owned());
+    let started_by = tmux::origin(session)
+        .ok()
+        .flatten()
+        .and_then(|raw| json::parse(&raw).ok())
+        .and_then(|value| Origin::from_json(&value).session);
+
+    Ok(RunReport {
+        agent: agent.to_string(),
+        session: session.to_string(),
+        reason,
+        started: info.created,
+        ended: unix_now(),
+        cwd: info.cwd,
+        changes,
+        exit_status: if info.pane_dead {
+            info.exit_status
+        } else {
+            None
+        },
+        log_path,
+        usage,
+        started_by,
+        removed_by: match reason {
+            EndReason::Removed | EndReason::Stopped => nested::caller(),
+            EndReason::Exited => None,
+        },
+    })
+}
+
+/// Append `report` to the history file as one JSON line.
+pub fn record(report: &RunReport) -> Result<()> {
+    let path = paths::history_file()?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)
+            .map_err(|err| with_context(err, format!("failed to create {}", dir.display())))?;
+    }
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .map_err(|err| with_context(err, format!("failed to open {}", path.display())))?;
+    writeln!(file, "{}", report.to_json())
+        .map_err(|err| with_context(err, format!("failed to write {}", path.display())))
+}
+
+/// Runs that ended at or after `since`, oldest first.
+pub fn recent(since: u64) -> Result<Vec<RunReport>> {
+    let path = paths::history_file()?;
+    let raw = match fs::read_to_string(&path) {
+        Ok(raw) => raw,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => {
+            return Err(with_context(
+                err,
+                format!("failed to read {}", path.display()),
+            ))
+        }
+    };
+    Ok(parse_history(&raw, since))
+}
+
+fn parse_history(raw: &str, since: u64) -> Vec<RunReport> {
+    raw.lines()
+        .filter_map(|line| json::parse(line).ok())
+        .filter_map(|value| RunReport::from_json(&value))
+        .filter(|report| report.ended >= since)
+        .collect()
+}
+
+/// Arrange for `amux record-exit` to run when the session's agent exits.
+pub fn watch(agent: &str, session: &str) -> Result<()> {
+    let exe = std::env::current_exe()
+        .map_err(|err| with_context(err, "failed to locate the amux executable"))?;
+    tmux::on_pane_died(session, &hook_command(&exe, agent, session))
+}
+
+/// Record the exit of an agent that was gone before [`watch`] set the hook,
+/// such as a command that fails at once.
+pub fn catch_early_exit(agent: &str, session: &str) -> Result<()> {
+    if !tmux::session_info(session)?.pane_dead {
+        return Ok(());
+    }
+    let exe = std::env::current_exe()
+        .map_err(|err| with_context(err, "failed to locate the amux executable"))?;
+    tmux::run_in_background(&[
+        exe.to_string_lossy().as_ref(),
+        "record-exit",
+        agent,
+        session,
+    ])
+}
+
+/// tmux command that runs `amux record-exit` in the background.
+fn hook_command(exe: &Path, agent: &str, session: &str) -> String {
+    tmux::run_shell_command(&[
+        exe.to_string_lossy().as_ref(),
+        "record-exit",
+        agent,
+        session,
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report() -> RunReport {
+        RunReport {
+            agent: "codex".into(),
+            session: "amux-codex".into(),
+            reason: EndReason::Exited,
+            started: Some(1_700_000_000),
+            ended: 1_700_003_720,
+            cwd: Some("/work/repo".into()),
+            changes: Some("2 changed".into()),
+            exit_status: Some(1),
+            log_path: None,
+            usage: None,
+            started_by: None,
+            removed_by: None,
+        }
+    }
+
+    #[test]
+    fn report_serializes_to_one_json_line() {
+        let line = report().to_json().to_string();
+        assert!(!line.contains('\n'));
+        assert!(line.contains("\"duration_seconds\":3720"));
+        assert!(line.contains("\"reason\":\"exited\""));
+        assert!(line.contains("\"log_path\":null"));
+    }
+
+    #[test]
+    fn history_round_trips_and_filters_by_end_time() {
+        let older = RunReport {
+            ended: 1_600_000_000,
+            ..report()
+        };
+        let raw = format!("{}\nnot json\n{}\n", older.to_json(), report().to_json());
+        assert_eq!(parse_history(&raw, 1_700_000_000), vec![report()]);
+        assert_eq!(parse_history(&raw, 0).len(), 2);
+    }
+
+    #[test]
+    fn report_text_shows_duration_and_exit() {
+        let text = report().to_string();
+        assert!(text.contains("ran:           1h 2m"));
+        assert!(text.contains("exit status:   1"));
+        let removed = RunReport {
+            reason: EndReason::Removed,
+            exit_status: None,
+            ..report()
+        };
+        assert!(removed.to_string().contains("removed while running"));
+    }
+
+    #[test]
+    fn recent_summary_shows_age_duration_and_status() {
+        assert_eq!(
+            report().recent_summary(1_700_011_000),
+            "exited 2h 1m ago after 1h 2m, exit status 1"
+        );
+        let removed = RunReport {
+            reason: EndReason::Removed,
+            started: None,
+            exit_status: None,
+            ..report()
+        };
+        assert_eq!(removed.recent_summary(1_700_003_750), "removed 30s ago");
+        let by_agent = RunReport {
+            removed_by: Some("amux-claude".into()),
+            ..removed
+        };
+        assert_eq!(
+            by_agent.recent_summary(1_700_003_750),
+            "removed 30s ago, by agent session amux-claude"
+        );
+    }
+
+    #[test]
+    fn hook_command_quotes_for_shell_and_tmux() {
+        let command = hook_command(Path::new("/opt/my tools/amux"), "codex", "amux-codex");
+        assert_eq!(
+            command,
+            "run-shell -b \"'/opt/my tools/amux' record-exit codex amux-codex\""
+        );
+    }
+}
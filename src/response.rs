@@ -0,0 +1,175 @@
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::config::Config;
+use crate::error::Result;
+use crate::output::Tail;
+use crate::pattern::Pattern;
+use crate::tmux;
+
+/// Quiet period used when an agent does not configure its own.
+pub const DEFAULT_QUIET: Duration = Duration::from_millis(2000);
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+/// Number of trailing non-blank lines searched for the input prompt.
+const PROMPT_WINDOW: usize = 10;
+/// Amount of recent output kept when matching a pattern against new output.
+const OUTPUT_WINDOW: usize = 64 * 1024;
+
+/// The "is it done yet" heuristic for an agent: its output has been quiet for a
+/// while and, when configured, its input prompt is visible again.
+#[derive(Debug)]
+pub struct Boundary {
+    prompt: Option<Pattern>,
+    quiet: Duration,
+}
+
+impl Boundary {
+    pub fn new(prompt: Option<Pattern>, quiet: Duration) -> Self {
+        Self { prompt, quiet }
+    }
+
+    pub fn for_agent(config: &Config, agent: &str) -> Result<Self> {
+        let response = config.agent(agent).map(|agent| &agent.response);
+        let prompt = response
+            .and_then(|response| response.prompt.as_deref())
+            .map(Pattern::new)
+            .transpose()?;
+        let quiet = response
+            .and_then(|response| response.quiet_ms)
+            .map(Duration::from_millis)
+            .unwrap_or(DEFAULT_QUIET);
+        Ok(Self::new(prompt, quiet))
+    }
+
+    /// Whether the configured input prompt is on `screen`; always `false`
+    /// for agents without one.
+    pub fn shows_prompt(&self, screen: &str) -> bool {
+        self.prompt
+            .as_ref()
+            .is_some_and(|prompt| prompt.is_match(&prompt_window(screen)))
+    }
+
+    pub fn is_complete(&self, screen: &str, stable_for: Duration) -> bool {
+        if stable_for < self.quiet {
+            return false;
+        }
+        match &self.prompt {
+            Some(prompt) => prompt.is_match(&prompt_window(screen)),
+            None => true,
+        }
+    }
+}
+
+/// Block until the agent in `session` has finished responding. Returns `false`
+/// when the timeout elapses first.
+pub fn wait_for_response(
+    session: &str,
+    boundary: &Boundary,
+    timeout: Option<Duration>,
+) -> Result<bool> {
+    watch_pane(session, timeout, |screen, stable_for| {
+        boundary.is_complete(screen, stable_for)
+    })
+}
+
+/// Block until output produced after the call matches `pattern`. Sessions without
+/// a log fall back to matching the visible pane. Returns `false` on timeout.
+pub fn wait_for_pattern(
+    session: &str,
+    pattern: &Pattern,
+    timeout: Option<Duration>,
+) -> Result<bool> {
+    let Some(mut tail) = Tail::from_end(session)? else {
+        return watch_pane(session, timeout, |screen, _| pattern.is_match(screen));
+    };
+
+    let started = Instant::now();
+    let mut recent = String::new();
+    loop {
+        let fresh = tail.read_new()?;
+        if !fresh.is_empty() {
+            recent.push_str(&fresh);
+            if recent.len() > OUTPUT_WINDOW {
+                let mut cut = recent.len() - OUTPUT_WINDOW;
+                while !recent.is_char_boundary(cut) {
+                    cut += 1;
+                }
+                recent.drain(..cut);
+            }
+            if pattern.is_match(&recent) {
+                return Ok(true);
+            }
+        }
+
+        if timeout.is_some_and(|timeout| started.elapsed() >= timeout) {
+            return Ok(false);
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+/// Poll the visible pane until `done` accepts it, passing along how long the
+/// content has been unchanged. Returns `false` on timeout.
+pub fn watch_pane<F>(session: &str, timeout: Option<Duration>, mut done: F) -> Result<bool>
+where
+    F: FnMut(&str, Duration) -> bool,
+{
+    let pane = tmux::agent_pane(session)?;
+    let started = Instant::now();
+    let mut last_screen: Option<String> = None;
+    let mut last_change = started;
+
+    loop {
+        let screen = tmux::capture_pane(&pane, None)?;
+        if last_screen.as_deref() != Some(screen.as_str()) {
+            last_change = Instant::now();
+        }
+        if done(&screen, last_change.elapsed()) {
+            return Ok(true);
+        }
+        last_screen = Some(screen);
+
+        if timeout.is_some_and(|timeout| started.elapsed() >= timeout) {
+            return Ok(false);
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+fn prompt_window(screen: &str) -> String {
+    let lines: Vec<&str> = screen
+        .lines()
+        .map(str::trim_end)
+        .filter(|line| !line.is_empty())
+        .collect();
+    let start = lines.len().saturating_sub(PROMPT_WINDOW);
+    lines[start..].join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn boundary_requires_quiet_period() {
+        let boundary = Boundary::new(None, Duration::from_secs(2));
+        assert!(!boundary.is_complete("working...", Duration::from_secs(1)));
+        assert!(boundary.is_complete("working...", Duration::from_secs(3)));
+    }
+
+    #[test]
+    fn boundary_requires_prompt_when_configured() {
+        let prompt = Pattern::new(r"^>\s*$").expect("pattern should compile");
+        let boundary = Boundary::new(Some(prompt), Duration::ZERO);
+        assert!(!boundary.is_complete("thinking\n", Duration::from_secs(5)));
+        assert!(boundary.is_complete("done.\n>  \n\n\n", Duration::from_secs(5)));
+    }
+
+    #[test]
+    fn prompt_window_keeps_trailing_lines() {
+        let screen: String = (0..20).map(|n| format!("line {n}\n\n")).collect();
+        let window = prompt_window(&screen);
+        assert!(window.starts_with("line 10"));
+        assert!(window.ends_with("line 19"));
+    }
+}
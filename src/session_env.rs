@@ -0,0 +1,320 @@
+//! The environment a new agent session receives, and how a running agent's
+//! environment compares with the invoking shell.
+//!
+//! tmux starts sessions with the *server's* global environment, which was
+//! captured when the server first started. Variables exported in the current
+//! shell afterwards (a fresh API key, say) are therefore invisible to new
+//! sessions unless amux passes them explicitly via `new-session -e`.
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::io;
+
+use crate::config::Config;
+use crate::error::Result;
+use crate::multiplexer::{self, Backend};
+use crate::tmux;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Source {
+    /// Set by amux itself for every session.
+    Amux,
+    /// Set by the agent's `env` table in the config.
+    Config,
+    /// Copied from the invoking shell because it is listed in `forward_env`.
+    Forwarded,
+    /// Set by the agent's `env_fixups` because the value it would otherwise
+    /// get was missing or known not to work.
+    Fixup,
+}
+
+/// Everything that determines a session's environment.
+#[derive(Debug, Default)]
+pub struct EnvPlan {
+    /// Variables passed explicitly to `new-session -e`.
+    pub explicit: Vec<(String, String, Source)>,
+    /// Variables inherited from the tmux server's global environment.
+    pub inherited: BTreeMap<String, String>,
+    /// Variables listed in `forward_env` but not set in the invoking shell.
+    pub missing: Vec<String>,
+    /// Variables in the invoking shell that the session will not see.
+    pub shell_only: Vec<String>,
+}
+
+/// Variables amux passes to `new-session -e` for this session.
+pub fn explicit(config: &Config, agent: &str, session: &str) -> Result<Vec<(String, String)>> {
+    let inherited = inherited()?;
+    Ok(explicit_with_sources(
+        config,
+        agent,
+        session,
+        |name| env::var(name).ok(),
+        &inherited,
+    )
+    .0
+    .into_iter()
+    .map(|(name, value, _)| (name, value))
+    .collect())
+}
+
+pub fn plan(config: &Config, agent: &str, session: &str) -> Result<EnvPlan> {
+    let inherited = inherited()?;
+    let (explicit, missing) = explicit_with_sources(
+        config,
+        agent,
+        session,
+        |name| env::var(name).ok(),
+        &inherited,
+    );
+
+    let shell_only = env::vars()
+        .map(|(name, _)| name)
+        .filter(|name| {
+            !inherited.contains_key(name) && !explicit.iter().any(|(set, _, _)| set == name)
+        })
+        .collect();
+
+    Ok(EnvPlan {
+        explicit,
+        inherited,
+        missing,
+        shell_only,
+    })
+}
+
+impl EnvPlan {
+    /// The value the session will see for `name`.
+    pub fn value(&self, name: &str) -> Option<&str> {
+        self.explicit
+            .iter()
+            .find(|(set, _, _)| set == name)
+            .map(|(_, value, _)| value.as_str())
+            .or_else(|| self.inherited.get(name).map(String::as_str))
+    }
+}
+
+/// `command_tokens` run through `env TERM=<term>` when the agent sets
+/// `term`. tmux overwrites a TERM passed to `new-session -e` with its
+/// `default-terminal`, so the agent's own process has to set it; respawned
+/// panes rerun the same wrapper.
+pub fn with_term(config: &Config, agent: &str, command_tokens: &[String]) -> Vec<String> {
+    match config.agent(agent).and_then(|agent| agent.term.as_deref()) {
+        Some(term) => ["env".to_string(), format!("TERM={term}")]
+            .into_iter()
+            .chain(command_tokens.iter().cloned())
+            .collect(),
+        None => command_tokens.to_vec(),
+    }
+}
+
+/// The tmux server's global environment, which new sessions start from.
+fn inherited() -> Result<BTreeMap<String, String>> {
+    // Zellij sessions, like the server started by a first tmux `new-session`,
+    // inherit this process's environment.
+    if multiplexer::backend() != Backend::Tmux {
+        return Ok(env::vars().collect());
+    }
+    Ok(match tmux::global_environment()? {
+        Some(vars) => vars.into_iter().collect(),
+        None => env::vars().collect(),
+    })
+}
+
+/// Variables that select the character set, most specific first.
+pub const LOCALE_VARS: &[&str] = &["LC_ALL", "LC_CTYPE", "LANG"];
+
+/// Whether `name` set to `value` needs an `env_fixups` entry: it is unset or
+/// empty, or it is a locale variable that does not select UTF-8.
+pub fn needs_fixup(name: &str, value: Option<&str>) -> bool {
+    match value {
+        None | Some("") => true,
+        Some(value) => LOCALE_VARS.contains(&name) && !is_utf8_locale(value),
+    }
+}
+
+pub fn is_utf8_locale(value: &str) -> bool {
+    let value = value.to_ascii_lowercase();
+    value.contains("utf-8") || value.contains("utf8")
+}
+
+/// Variables that differ between any two shells for bookkeeping reasons alone.
+const VOLATILE: &[&str] = &["_", "OLDPWD", "PWD", "SHLVL", "TMUX", "TMUX_PANE"];
+
+/// How the invoking shell's environment differs from a running agent's.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct EnvDrift {
+    /// Set in the shell but absent from the agent process.
+    pub shell_only: Vec<String>,
+    /// Set in both, with different values.
+    pub changed: Vec<String>,
+}
+
+/// The environment a running process was started with, from `/proc/<pid>/environ`.
+pub fn process_environment(pid: u32) -> io::Result<BTreeMap<String, String>> {
+    let raw = fs::read(format!("/proc/{pid}/environ"))?;
+    Ok(raw
+        .split(|byte| *byte == 0)
+        .filter_map(|entry| {
+            let entry = String::from_utf8_lossy(entry);
+            let (name, value) = entry.split_once('=')?;
+            Some((name.to_string(), value.to_string()))
+        })
+        .collect())
+}
+
+pub fn drift(shell: &BTreeMap<String, String>, process: &BTreeMap<String, String>) -> EnvDrift {
+    let mut drift = EnvDrift::default();
+    for (name, value) in shell {
+        if VOLATILE.contains(&name.as_str()) {
+            continue;
+        }
+        match process.get(name) {
+            None => drift.shell_only.push(name.clone()),
+            Some(seen) if seen != value => drift.changed.push(name.clone()),
+            Some(_) => {}
+        }
+    }
+    drift
+}
+
+type Explicit = (Vec<(String, String, Source)>, Vec<String>);
+
+fn explicit_with_sources<F>(
+    config: &Config,
+    agent: &str,
+    session: &str,
+    lookup: F,
+    inherited: &BTreeMap<String, String>,
+) -> Explicit
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let mut vars = vec![
+        ("AMUX_AGENT".to_string(), agent.to_string(), Source::Amux),
+        (
+            "AMUX_SESSION".to_string(),
+            session.to_string(),
+            Source::Amux,
+        ),
+    ];
+    let mut missing = Vec::new();
+
+    if let Some(agent_config) = config.agent(agent) {
+        for name in &agent_config.forward_env {
+            match lookup(name) {
+                Some(value) => vars.push((name.clone(), value, Source::Forwarded)),
+                None => missing.push(name.clone()),
+            }
+        }
+        // Explicit config values win over forwarded ones.
+        for (name, value) in &agent_config.env {
+            vars.retain(|(existing, _, _)| existing != name);
+            vars.push((name.clone(), value.clone(), Source::Config));
+        }
+        for (name, value) in &agent_config.env_fixups {
+            let current = vars
+                .iter()
+                .find(|(set, _, _)| set == name)
+                .map(|(_, value, _)| value.as_str())
+                .or_else(|| inherited.get(name).map(String::as_str));
+            if needs_fixup(name, current) {
+                vars.retain(|(existing, _, _)| existing != name);
+                vars.push((name.clone(), value.clone(), Source::Fixup));
+            }
+        }
+    }
+
+    (vars, missing)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config;
+
+    #[test]
+    fn explicit_sets_amux_markers() {
+        let vars = explicit_with_sources(
+            &Config::default(),
+            "codex",
+            "amux-codex",
+            |_| None,
+            &BTreeMap::new(),
+        )
+        .0;
+        assert_eq!(
+            vars,
+            vec![
+                ("AMUX_AGENT".into(), "codex".into(), Source::Amux),
+                ("AMUX_SESSION".into(), "amux-codex".into(), Source::Amux),
+            ]
+        );
+    }
+
+    #[test]
+    fn drift_reports_missing_and_changed_variables() {
+        let vars = |pairs: &[(&str, &str)]| -> BTreeMap<String, String> {
+            pairs
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect()
+        };
+        let shell = vars(&[
+            ("API_KEY", "new"),
+            ("PATH", "/a"),
+            ("HOME", "/h"),
+            ("PWD", "/x"),
+        ]);
+        let process = vars(&[("API_KEY", "old"), ("HOME", "/h"), ("PWD", "/y")]);
+        assert_eq!(
+            drift(&shell, &process),
+            EnvDrift {
+                shell_only: vec!["PATH".into()],
+                changed: vec!["API_KEY".into()],
+            }
+        );
+    }
+
+    #[test]
+    fn explicit_forwards_and_reports_missing() {
+        let config = config::parse(
+            "[agents.claude]\nforward_env = [\"API_KEY\", \"UNSET\"]\nenv = { MODE = \"fast\", API_KEY = \"override\" }\n",
+        )
+        .expect("config should parse");
+        let (vars, missing) = explicit_with_sources(
+            &config,
+            "claude",
+            "amux-claude",
+            |name| (name == "API_KEY").then(|| "secret".to_string()),
+            &BTreeMap::new(),
+        );
+        assert!(vars.contains(&("MODE".into(), "fast".into(), Source::Config)));
+        assert!(vars.contains(&("API_KEY".into(), "override".into(), Source::Config)));
+        assert!(!vars.iter().any(|(_, value, _)| value == "secret"));
+        assert_eq!(missing, vec!["UNSET".to_string()]);
+    }
+
+    #[test]
+    fn env_fixups_only_replace_missing_or_broken_values() {
+        let config = config::parse(
+            "[agents.claude]\nterm = \"tmux-256color\"\nenv_fixups = { LANG = \"en_US.UTF-8\", LC_CTYPE = \"en_US.UTF-8\", EDITOR = \"vi\" }\n",
+        )
+        .expect("config should parse");
+        let inherited: BTreeMap<String, String> =
+            [("LANG", "C"), ("LC_CTYPE", "C.UTF-8"), ("EDITOR", "nano")]
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect();
+        let vars = explicit_with_sources(&config, "claude", "amux-claude", |_| None, &inherited).0;
+        assert!(vars.contains(&("LANG".into(), "en_US.UTF-8".into(), Source::Fixup)));
+        assert!(!vars
+            .iter()
+            .any(|(name, _, _)| name == "LC_CTYPE" || name == "EDITOR"));
+        assert!(needs_fixup("EDITOR", None));
+        assert_eq!(
+            with_term(&config, "claude", &["claude".into()]),
+            ["env", "TERM=tmux-256color", "claude"]
+        );
+    }
+}
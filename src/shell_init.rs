@@ -0,0 +1,136 @@
+//! Shell functions and completions printed by `amux shell-init`, meant to be
+//! loaded with `eval "$(amux shell-init bash)"` from the shell's rc file.
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::completions::{self, CommandSpec};
+use crate::error::{bail, DynError, Result};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+impl FromStr for Shell {
+    type Err = DynError;
+
+    fn from_str(raw: &str) -> Result<Self> {
+        match raw {
+            "bash" => Ok(Self::Bash),
+            "zsh" => Ok(Self::Zsh),
+            "fish" => Ok(Self::Fish),
+            other => bail(format!(
+                "unsupported shell '{other}' (expected bash, zsh, or fish)"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Shell {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Bash => "bash",
+            Self::Zsh => "zsh",
+            Self::Fish => "fish",
+        })
+    }
+}
+
+/// The init script for `shell`: the functions, `amux completions` for
+/// `specs`, and agent-name completion for `ama` and `amp`. Names are looked
+/// up each time, so newly configured agents complete without re-running the
+/// init.
+pub fn script(shell: Shell, specs: &[CommandSpec]) -> String {
+    let (functions, agent_completions) = match shell {
+        Shell::Bash => (BASH, BASH_AGENTS),
+        Shell::Zsh => (ZSH, ZSH_AGENTS),
+        Shell::Fish => (FISH, FISH_AGENTS),
+    };
+    format!(
+        "# amux shell integration; load with: eval \"$(amux shell-init {shell})\"\n{functions}\n{}{agent_completions}",
+        completions::script(shell, specs)
+    )
+}
+
+const BASH: &str = r#"ams() { amux status "$@"; }
+ama() { amux attach -a "$1" --start "${@:2}"; }
+amp() {
+  local root
+  root=$(git rev-parse --show-toplevel 2>/dev/null || pwd)
+  (cd "$root" && amux attach -a "$1" -n "$(basename "$root")" --start "${@:2}")
+}
+"#;
+
+const BASH_AGENTS: &str = r#"_amux_agent() {
+  [ "$COMP_CWORD" -eq 1 ] && COMPREPLY=($(compgen -W "$(_amux_agent_names)" -- "${COMP_WORDS[1]}"))
+}
+complete -F _amux_agent ama amp
+"#;
+
+const ZSH: &str = r#"ams() { amux status "$@"; }
+ama() { amux attach -a "$1" --start "${@:2}"; }
+amp() {
+  local root
+  root=$(git rev-parse --show-toplevel 2>/dev/null || pwd)
+  (cd "$root" && amux attach -a "$1" -n "$(basename "$root")" --start "${@:2}")
+}
+"#;
+
+const ZSH_AGENTS: &str = r#"_amux_agent() { (( CURRENT == 2 )) && compadd -- ${(f)"$(_amux_agent_names)"}; }
+if (( $+functions[compdef] )); then
+  compdef _amux_agent ama amp
+fi
+"#;
+
+const FISH: &str = r#"function ams; amux status $argv; end
+function ama; amux attach -a $argv[1] --start $argv[2..-1]; end
+function amp
+    set -l root (git rev-parse --show-toplevel 2>/dev/null; or pwd)
+    pushd $root
+    amux attach -a $argv[1] -n (basename $root) --start $argv[2..-1]
+    popd
+end
+"#;
+
+const FISH_AGENTS: &str = r#"complete -c ama -f -a '(amux agents --names 2>/dev/null)'
+complete -c amp -f -a '(amux agents --names 2>/dev/null)'
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shell_parses_known_names() {
+        assert_eq!(
+            "zsh".parse::<Shell>().expect("zsh should parse"),
+            Shell::Zsh
+        );
+        let err = "tcsh".parse::<Shell>().expect_err("tcsh is unsupported");
+        assert!(err.to_string().contains("unsupported shell 'tcsh'"));
+    }
+
+    #[test]
+    fn script_lists_commands_and_functions() {
+        let commands = ["status", "attach"].map(|name| CommandSpec {
+            name: name.to_string(),
+            flags: Vec::new(),
+            subcommands: Vec::new(),
+        });
+        for shell in [Shell::Bash, Shell::Zsh, Shell::Fish] {
+            let script = script(shell, &commands);
+            assert!(script.contains(&format!("amux shell-init {shell}")));
+            assert!(
+                script.contains("status attach"),
+                "{shell} completes commands"
+            );
+            assert!(!script.contains("{commands}"));
+            for function in ["ams", "ama", "amp"] {
+                assert!(script.contains(function), "{shell} defines {function}");
+            }
+        }
+    }
+}
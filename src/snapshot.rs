@@ -0,0 +1,110 @@
+//! Rolling pictures of what each session's pane showed, for finding out what
+//! an agent was doing after its session died or was removed by mistake. With
+//! `[snapshots]` in the config, every new session gets a background watcher
+//! that saves the visible pane every `interval_secs` to
+//! `<state>/snapshots/<session>/<unix time>.txt`, skipping unchanged screens
+//! and keeping the newest `keep`.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+use crate::config::Snapshots;
+use crate::error::{with_context, Result};
+use crate::paths;
+use crate::time::unix_now;
+use crate::tmux;
+
+/// One saved capture.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Snapshot {
+    pub taken: u64,
+    pub path: PathBuf,
+}
+
+/// Save `session`'s pane as `settings` says until the session is gone.
+pub fn watch(session: &str, settings: &Snapshots) -> Result<()> {
+    let dir = paths::snapshots_dir()?.join(session);
+    fs::create_dir_all(&dir)
+        .map_err(|err| with_context(err, format!("failed to create {}", dir.display())))?;
+    let mut last: Option<String> = None;
+    loop {
+        thread::sleep(Duration::from_secs(settings.interval_secs));
+        if !tmux::has_session(session)? {
+            return Ok(());
+        }
+        let Ok(screen) = tmux::agent_pane(session).and_then(|pane| tmux::capture_pane(&pane, None))
+        else {
+            continue;
+        };
+        if last.as_ref() == Some(&screen) {
+            continue;
+        }
+        save(&dir, &screen, settings.keep)?;
+        last = Some(screen);
+    }
+}
+
+fn save(dir: &Path, screen: &str, keep: usize) -> Result<()> {
+    let path = dir.join(format!("{}.txt", unix_now()));
+    fs::write(&path, screen)
+        .map_err(|err| with_context(err, format!("failed to write {}", path.display())))?;
+    let saved = read_dir(dir);
+    for snapshot in &saved[..saved.len().saturating_sub(keep)] {
+        // Another watcher of the same session may have removed it already.
+        let _ = fs::remove_file(&snapshot.path);
+    }
+    Ok(())
+}
+
+/// `session`'s snapshots, oldest first.
+pub fn list(session: &str) -> Result<Vec<Snapshot>> {
+    Ok(read_dir(&paths::snapshots_dir()?.join(session)))
+}
+
+fn read_dir(dir: &Path) -> Vec<Snapshot> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut snapshots: Vec<Snapshot> = entries
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            let taken = parse_name(path.file_name()?.to_str()?)?;
+            Some(Snapshot { taken, path })
+        })
+        .collect();
+    snapshots.sort_by_key(|snapshot| snapshot.taken);
+    snapshots
+}
+
+/// The capture time in a snapshot file name, `<unix time>.txt`.
+fn parse_name(name: &str) -> Option<u64> {
+    name.strip_suffix(".txt")?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_name_reads_the_capture_time() {
+        assert_eq!(parse_name("1760520000.txt"), Some(1760520000));
+        assert_eq!(parse_name("1760520000.txt.tmp"), None);
+        assert_eq!(parse_name("notes.txt"), None);
+    }
+
+    #[test]
+    fn save_keeps_the_newest_snapshots() {
+        let dir = std::env::temp_dir().join(format!("amux-snapshots-{}", std::process::id()));
+        fs::create_dir_all(&dir).expect("create temp dir");
+        for taken in [100, 200, 300] {
+            fs::write(dir.join(format!("{taken}.txt")), "old").expect("write snapshot");
+        }
+        save(&dir, "now", 2).expect("save snapshot");
+        let kept = read_dir(&dir);
+        fs::remove_dir_all(&dir).expect("remove temp dir");
+        assert_eq!(kept.len(), 2);
+        assert_eq!(kept[0].taken, 300);
+    }
+}
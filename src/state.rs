@@ -0,0 +1,222 @@
+//! Reading and writing amux's JSON state files: task boards, notify threads,
+//! dispatch progress, and the like. Several amux processes can touch one file
+//! at the same time (a `start` and a hook, the notify bridge and a watcher),
+//! so code that reads, changes, and writes a file back holds an advisory lock
+//! on it for the whole update. Writes replace the file atomically, and every
+//! file carries a `version` that older files are migrated from on read.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write as _};
+use std::path::{Path, PathBuf};
+use std::process;
+
+use crate::error::{fail_with, with_context, ErrorKind, Result};
+use crate::json::{self, Value};
+
+/// How one kind of state file is versioned.
+pub struct Schema {
+    /// What the file holds, for error messages, e.g. `task board`.
+    pub what: &'static str,
+    /// The version this amux writes.
+    pub version: u64,
+    /// Upgrade a document from version `from` to `from + 1`. Files written
+    /// before they had a version are version 0.
+    pub migrate: fn(u64, Value) -> Result<Value>,
+}
+
+/// For schemas whose older versions only lack the `version` field.
+pub fn unchanged(_from: u64, value: Value) -> Result<Value> {
+    Ok(value)
+}
+
+/// An exclusive advisory lock on a state file, released when dropped.
+pub struct Lock {
+    _file: File,
+}
+
+/// Wait for and take the lock on `path`. The lock lives in a `.lock` file
+/// beside it, so replacing the state file does not drop the lock.
+pub fn lock(path: &Path) -> Result<Lock> {
+    let lock_path = sibling(path, ".lock");
+    create_parent(&lock_path)?;
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&lock_path)
+        .map_err(|err| with_context(err, format!("failed to open {}", lock_path.display())))?;
+    file.lock()
+        .map_err(|err| with_context(err, format!("failed to lock {}", lock_path.display())))?;
+    Ok(Lock { _file: file })
+}
+
+/// The document at `path` upgraded to the current version, or `None` if
+/// there is no file yet.
+pub fn read(path: &Path, schema: &Schema) -> Result<Option<Value>> {
+    let raw = match fs::read_to_string(path) {
+        Ok(raw) => raw,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => {
+            return Err(with_context(
+                err,
+                format!("failed to read {}", path.display()),
+            ))
+        }
+    };
+    let corrupt = |reason: String| {
+        fail_with(
+            ErrorKind::Other,
+            format!("{} is corrupt: {reason}", path.display()),
+            Some(format!(
+                "move it aside to start with an empty {}",
+                schema.what
+            )),
+        )
+    };
+    let value = json::parse(&raw).map_err(|err| corrupt(err.to_string()))?;
+    upgrade(value, schema)
+        .map(Some)
+        .map_err(|err| with_context(err, path.display().to_string()))
+}
+
+fn upgrade(mut value: Value, schema: &Schema) -> Result<Value> {
+    let version = value.get("version").and_then(Value::as_u64).unwrap_or(0);
+    if version > schema.version {
+        return Err(fail_with(
+            ErrorKind::Other,
+            format!(
+                "the {} was written by a newer amux (version {version}, this one reads up to {})",
+                schema.what, schema.version
+            ),
+            Some("upgrade amux on this machine".into()),
+        ));
+    }
+    for from in version..schema.version {
+        value = (schema.migrate)(from, value)?;
+    }
+    Ok(value)
+}
+
+/// Replace the file at `path` with `value`, stamped with the schema version.
+/// A reader sees either the old or the new file, never part of one.
+pub fn write(path: &Path, schema: &Schema, mut value: Value) -> Result<()> {
+    if let Value::Object(fields) = &mut value {
+        fields.insert("version".into(), Value::from(schema.version));
+    }
+    create_parent(path)?;
+    // Per-process temp name: two writers must not share a half-written file.
+    let tmp = sibling(path, &format!(".{}.tmp", process::id()));
+    let written = File::create(&tmp)
+        .and_then(|mut file| {
+            file.write_all(format!("{}\n", value.to_pretty()).as_bytes())?;
+            file.sync_all()
+        })
+        .and_then(|()| fs::rename(&tmp, path));
+    if let Err(err) = written {
+        let _ = fs::remove_file(&tmp);
+        return Err(with_context(
+            err,
+            format!("failed to write {}", path.display()),
+        ));
+    }
+    Ok(())
+}
+
+/// Read, change, and write back the file at `path` under its lock, so
+/// concurrent updates apply one after another instead of overwriting each
+/// other. `change` gets `None` when the file does not exist yet.
+pub fn update<T>(
+    path: &Path,
+    schema: &Schema,
+    change: impl FnOnce(Option<Value>) -> Result<(Value, T)>,
+) -> Result<T> {
+    let _lock = lock(path)?;
+    let (value, result) = change(read(path, schema)?)?;
+    write(path, schema, value)?;
+    Ok(result)
+}
+
+fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+fn create_parent(path: &Path) -> Result<()> {
+    match path.parent() {
+        Some(dir) => fs::create_dir_all(dir)
+            .map_err(|err| with_context(err, format!("failed to create {}", dir.display()))),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    /// Version 1 renamed `n` to `count`.
+    const COUNTER: Schema = Schema {
+        what: "counter",
+        version: 1,
+        migrate: |_, value| {
+            let count = value.get("n").cloned().unwrap_or(Value::from(0_u64));
+            Ok(json::object([("count", count)]))
+        },
+    };
+
+    fn unversioned() -> Schema {
+        Schema {
+            what: "counter",
+            version: 0,
+            migrate: unchanged,
+        }
+    }
+
+    fn scratch(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("amux-state-{}-{name}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir.join("state.json")
+    }
+
+    #[test]
+    fn read_migrates_old_files_and_refuses_newer_ones() {
+        let path = scratch("migrate");
+        assert_eq!(read(&path, &COUNTER).expect("missing file is fine"), None);
+        write(&path, &unversioned(), json::object([("n", 3_u64)])).expect("write");
+        let value = read(&path, &COUNTER).expect("read").expect("file exists");
+        assert_eq!(value.get("count").and_then(Value::as_u64), Some(3));
+
+        write(&path, &COUNTER, value).expect("write");
+        let raw = fs::read_to_string(&path).expect("file should exist");
+        assert!(raw.contains("\"version\": 1"));
+        let err = read(&path, &unversioned()).expect_err("newer files are refused");
+        assert!(err.to_string().contains("written by a newer amux"));
+    }
+
+    #[test]
+    fn concurrent_updates_are_not_lost() {
+        let path = scratch("update");
+        let workers: Vec<_> = (0..8)
+            .map(|_| {
+                let path = path.clone();
+                thread::spawn(move || {
+                    for _ in 0..10 {
+                        update(&path, &COUNTER, |value| {
+                            let count = value
+                                .and_then(|value| value.get("count").and_then(Value::as_u64))
+                                .unwrap_or(0);
+                            Ok((json::object([("count", count + 1)]), ()))
+                        })
+                        .expect("update should succeed");
+                    }
+                })
+            })
+            .collect();
+        for worker in workers {
+            worker.join().expect("worker should finish");
+        }
+        let value = read(&path, &COUNTER).expect("read").expect("file exists");
+        assert_eq!(value.get("count").and_then(Value::as_u64), Some(80));
+    }
+}
@@ -0,0 +1,227 @@
+//! `amux stats`: how each agent has been used, read from the local run
+//! history (`history.jsonl`) and nothing else. A session is one run of a tmux
+//! session, from when it was created to its last report; the reports a
+//! keep-alive session leaves before its last one are its restarts, and a
+//! crash is an agent that exited on its own with a failing or missing status.
+
+use std::collections::BTreeMap;
+
+use crate::json::{self, Value};
+use crate::report::{EndReason, RunReport};
+use crate::time::{format_date, format_duration};
+
+const WEEK: u64 = 7 * 86_400;
+
+/// Totals for one agent.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AgentStats {
+    pub agent: String,
+    pub sessions: usize,
+    /// Sessions per week, keyed by the week's first second (Monday 00:00 UTC).
+    pub weekly: BTreeMap<u64, usize>,
+    /// Summed duration of the sessions whose start is known, and how many those are.
+    pub seconds: u64,
+    pub timed: usize,
+    pub restarts: usize,
+    pub crashes: usize,
+}
+
+impl AgentStats {
+    pub fn average(&self) -> Option<u64> {
+        (self.timed > 0).then(|| self.seconds / self.timed as u64)
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Stats {
+    /// The weeks covered, oldest first.
+    pub weeks: Vec<u64>,
+    pub agents: Vec<AgentStats>,
+}
+
+/// The Monday 00:00 UTC on or before `timestamp`, or the epoch for the few
+/// days before the first Monday after it.
+pub fn week_start(timestamp: u64) -> u64 {
+    // 1970-01-01 was a Thursday, three days after a Monday.
+    let days = timestamp / 86_400;
+    days.saturating_sub((days + 3) % 7) * 86_400
+}
+
+/// Usage over the `weeks` weeks up to the one holding `now`, from `runs`
+/// (the history, oldest first).
+pub fn summarize(runs: &[RunReport], now: u64, weeks: u64) -> Stats {
+    let last = week_start(now);
+    let first = last.saturating_sub(weeks.saturating_sub(1) * WEEK);
+    let mut sessions: BTreeMap<(String, String, u64), Vec<&RunReport>> = BTreeMap::new();
+    for run in runs {
+        // Without a start time, every report stands for a session of its own.
+        let key = (
+            run.agent.clone(),
+            run.session.clone(),
+            run.started.unwrap_or(run.ended),
+        );
+        sessions.entry(key).or_default().push(run);
+    }
+
+    let mut agents: BTreeMap<String, AgentStats> = BTreeMap::new();
+    for ((agent, _, begun), reports) in sessions {
+        let week = week_start(begun);
+        if week < first {
+            continue;
+        }
+        let stats = agents.entry(agent.clone()).or_insert_with(|| AgentStats {
+            agent,
+            ..AgentStats::default()
+        });
+        stats.sessions += 1;
+        *stats.weekly.entry(week).or_default() += 1;
+        let last_report = reports.iter().max_by_key(|run| run.ended);
+        if let Some(duration) = last_report.and_then(|run| run.duration()) {
+            stats.seconds += duration;
+            stats.timed += 1;
+        }
+        if reports[0].started.is_some() {
+            stats.restarts += reports.len() - 1;
+        }
+        stats.crashes += reports.iter().filter(|run| is_crash(run)).count();
+    }
+    Stats {
+        weeks: (first..=last).step_by(WEEK as usize).collect(),
+        agents: agents.into_values().collect(),
+    }
+}
+
+fn is_crash(run: &RunReport) -> bool {
+    run.reason == EndReason::Exited && run.exit_status != Some(0)
+}
+
+impl Stats {
+    /// A table of sessions per week by agent, then one line of totals per agent.
+    pub fn render(&self) -> String {
+        let width = self
+            .agents
+            .iter()
+            .map(|stats| stats.agent.len())
+            .max()
+            .unwrap_or(0)
+            .max("agent".len());
+        let mut out = String::from("Sessions per week (weeks start on Monday, UTC):\n");
+        out.push_str(&format!("  {:<10}", "week of"));
+        for stats in &self.agents {
+            out.push_str(&format!("  {:>width$}", stats.agent));
+        }
+        out.push('\n');
+        for week in &self.weeks {
+            out.push_str(&format!("  {:<10}", format_date(*week)));
+            for stats in &self.agents {
+                let count = stats.weekly.get(week).copied().unwrap_or(0);
+                out.push_str(&format!("  {count:>width$}"));
+            }
+            out.push('\n');
+        }
+        out.push('\n');
+        out.push_str(&format!(
+            "  {:<width$}  {:>8}  {:>12}  {:>8}  {:>7}\n",
+            "agent", "sessions", "avg duration", "restarts", "crashes"
+        ));
+        for stats in &self.agents {
+            let average = stats.average().map_or("-".to_string(), format_duration);
+            out.push_str(&format!(
+                "  {:<width$}  {:>8}  {:>12}  {:>8}  {:>7}\n",
+                stats.agent, stats.sessions, average, stats.restarts, stats.crashes
+            ));
+        }
+        out
+    }
+
+    pub fn to_json(&self) -> Value {
+        Value::Array(
+            self.agents
+                .iter()
+                .map(|stats| {
+                    let weekly = self.weeks.iter().map(|week| {
+                        json::object([
+                            ("week", Value::from(format_date(*week))),
+                            (
+                                "sessions",
+                                Value::from(stats.weekly.get(week).copied().unwrap_or(0)),
+                            ),
+                        ])
+                    });
+                    json::object([
+                        ("agent", Value::from(stats.agent.as_str())),
+                        ("sessions", Value::from(stats.sessions)),
+                        ("average_duration_seconds", Value::from(stats.average())),
+                        ("restarts", Value::from(stats.restarts)),
+                        ("crashes", Value::from(stats.crashes)),
+                        ("weekly", Value::Array(weekly.collect())),
+                    ])
+                })
+                .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Monday 2023-11-13 00:00 UTC.
+    const MONDAY: u64 = 1_699_833_600;
+
+    fn run(agent: &str, started: u64, ended: u64, status: Option<i32>) -> RunReport {
+        RunReport {
+            agent: agent.into(),
+            session: format!("amux-{agent}"),
+            reason: EndReason::Exited,
+            started: Some(started),
+            ended,
+            cwd: None,
+            changes: None,
+            exit_status: status,
+            log_path: None,
+            usage: None,
+            started_by: None,
+            removed_by: None,
+        }
+    }
+
+    #[test]
+    fn week_start_is_the_monday_before() {
+        assert_eq!(week_start(MONDAY), MONDAY);
+        assert_eq!(week_start(MONDAY + 6 * 86_400 + 3_600), MONDAY);
+        assert_eq!(week_start(MONDAY - 1), MONDAY - WEEK);
+        assert_eq!(week_start(0), 0);
+        assert_eq!(format_date(MONDAY), "2023-11-13");
+    }
+
+    #[test]
+    fn summarize_groups_restarts_into_one_session() {
+        let runs = [
+            // A keep-alive session that crashed twice and then finished.
+            run("codex", MONDAY, MONDAY + 60, Some(1)),
+            run("codex", MONDAY, MONDAY + 120, None),
+            run("codex", MONDAY, MONDAY + 600, Some(0)),
+            run("claude", MONDAY - WEEK, MONDAY - WEEK + 1_800, Some(0)),
+            // Too old for a two-week window.
+            run("claude", MONDAY - 3 * WEEK, MONDAY - 3 * WEEK + 60, Some(0)),
+        ];
+        let stats = summarize(&runs, MONDAY + 3_600, 2);
+        assert_eq!(stats.weeks, [MONDAY - WEEK, MONDAY]);
+        let claude = &stats.agents[0];
+        assert_eq!((claude.sessions, claude.average()), (1, Some(1_800)));
+        let codex = &stats.agents[1];
+        assert_eq!(codex.sessions, 1);
+        assert_eq!(codex.average(), Some(600));
+        assert_eq!((codex.restarts, codex.crashes), (2, 2));
+        assert_eq!(codex.weekly.get(&MONDAY), Some(&1));
+    }
+
+    #[test]
+    fn render_lines_up_weeks_and_totals() {
+        let runs = [run("codex", MONDAY, MONDAY + 600, Some(0))];
+        let text = summarize(&runs, MONDAY, 1).render();
+        assert!(text.contains("  week of     codex\n  2023-11-13      1\n"));
+        assert!(text.contains("  codex         1        10m 0s         0        0\n"));
+    }
+}
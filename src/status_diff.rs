@@ -0,0 +1,214 @@
+//! `amux status --diff`: what changed since the last time it ran. Each run
+//! compares the sessions against the snapshot the previous run saved and
+//! then replaces it, so a cron job gets one line per change and no output
+//! at all when nothing happened.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::error::Result;
+use crate::json::{self, Value};
+use crate::paths;
+use crate::state;
+
+/// One session as of a snapshot.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Entry {
+    pub agent: String,
+    pub name: Option<String>,
+    /// `running`, `exited`, or `crash-looping`.
+    pub state: String,
+    /// Output activity, as in `amux status`.
+    pub activity: String,
+}
+
+/// Sessions by tmux session id, and when they were looked at.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Snapshot {
+    pub taken_at: u64,
+    pub sessions: BTreeMap<String, Entry>,
+}
+
+impl Snapshot {
+    fn to_json(&self) -> Value {
+        let sessions = self.sessions.iter().map(|(session, entry)| {
+            let entry = json::object([
+                ("agent", Value::from(entry.agent.as_str())),
+                ("name", Value::from(entry.name.clone())),
+                ("state", Value::from(entry.state.as_str())),
+                ("activity", Value::from(entry.activity.as_str())),
+            ]);
+            (session.clone(), entry)
+        });
+        json::object([
+            ("taken_at", Value::from(self.taken_at)),
+            ("sessions", json::object(sessions)),
+        ])
+    }
+
+    fn from_json(value: &Value) -> Option<Self> {
+        let Value::Object(entries) = value.get("sessions")? else {
+            return None;
+        };
+        let mut sessions = BTreeMap::new();
+        for (session, entry) in entries {
+            let text = |key: &str| entry.get(key).and_then(Value::as_str).map(str::to_string);
+            sessions.insert(
+                session.clone(),
+                Entry {
+                    agent: text("agent")?,
+                    name: text("name"),
+                    state: text("state")?,
+                    activity: text("activity").unwrap_or_else(|| "unknown".into()),
+                },
+            );
+        }
+        Some(Self {
+            taken_at: value.get("taken_at")?.as_u64()?,
+            sessions,
+        })
+    }
+}
+
+/// One difference between two snapshots.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Change {
+    Started(String, Entry),
+    Ended(String, Entry),
+    /// Session, what changed (`state` or `activity`), from, to.
+    Moved(String, &'static str, String, String),
+}
+
+impl Change {
+    fn session(&self) -> &str {
+        match self {
+            Change::Started(session, _) | Change::Ended(session, _) => session,
+            Change::Moved(session, ..) => session,
+        }
+    }
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Change::Started(session, entry) => write!(
+                f,
+                "+ {session} ({}): new, {}, {}",
+                entry.agent, entry.state, entry.activity
+            ),
+            Change::Ended(session, entry) => write!(f, "- {session} ({}): gone", entry.agent),
+            Change::Moved(session, field, from, to) => {
+                write!(f, "~ {session}: {field} {from} -> {to}")
+            }
+        }
+    }
+}
+
+/// Sessions that appeared, disappeared, or changed state between `before`
+/// and `after`, in session order.
+pub fn changes(before: &Snapshot, after: &Snapshot) -> Vec<Change> {
+    let mut changes = Vec::new();
+    for (session, entry) in &before.sessions {
+        if !after.sessions.contains_key(session) {
+            changes.push(Change::Ended(session.clone(), entry.clone()));
+        }
+    }
+    for (session, entry) in &after.sessions {
+        let Some(old) = before.sessions.get(session) else {
+            changes.push(Change::Started(session.clone(), entry.clone()));
+            continue;
+        };
+        for (field, from, to) in [
+            ("state", &old.state, &entry.state),
+            ("activity", &old.activity, &entry.activity),
+        ] {
+            if from != to {
+                changes.push(Change::Moved(
+                    session.clone(),
+                    field,
+                    from.clone(),
+                    to.clone(),
+                ));
+            }
+        }
+    }
+    changes.sort_by(|a, b| a.session().cmp(b.session()));
+    changes
+}
+
+const SCHEMA: state::Schema = state::Schema {
+    what: "status snapshot",
+    version: 1,
+    migrate: state::unchanged,
+};
+
+/// The snapshot the previous `status --diff` saved, if any.
+pub fn load() -> Result<Option<Snapshot>> {
+    Ok(state::read(&paths::status_snapshot_file()?, &SCHEMA)?
+        .and_then(|value| Snapshot::from_json(&value)))
+}
+
+pub fn save(snapshot: &Snapshot) -> Result<()> {
+    state::write(&paths::status_snapshot_file()?, &SCHEMA, snapshot.to_json())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(state: &str, activity: &str) -> Entry {
+        Entry {
+            agent: "codex".into(),
+            name: None,
+            state: state.into(),
+            activity: activity.into(),
+        }
+    }
+
+    fn snapshot(sessions: &[(&str, Entry)]) -> Snapshot {
+        Snapshot {
+            taken_at: 1_700_000_000,
+            sessions: sessions
+                .iter()
+                .map(|(session, entry)| (session.to_string(), entry.clone()))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn changes_lists_new_gone_and_moved_sessions() {
+        let before = snapshot(&[
+            ("amux-codex", entry("running", "active")),
+            ("amux-codex--old", entry("running", "silent")),
+            ("amux-codex--same", entry("running", "silent")),
+        ]);
+        let after = snapshot(&[
+            ("amux-codex", entry("exited", "silent")),
+            ("amux-codex--new", entry("running", "active")),
+            ("amux-codex--same", entry("running", "silent")),
+        ]);
+        let lines: Vec<String> = changes(&before, &after)
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            lines,
+            [
+                "~ amux-codex: state running -> exited",
+                "~ amux-codex: activity active -> silent",
+                "+ amux-codex--new (codex): new, running, active",
+                "- amux-codex--old (codex): gone",
+            ]
+        );
+        assert!(changes(&after, &after).is_empty());
+    }
+
+    #[test]
+    fn snapshot_round_trips_through_json() {
+        let mut named = entry("crash-looping", "unknown");
+        named.name = Some("api".into());
+        let snapshot = snapshot(&[("amux-codex--api", named)]);
+        let parsed = json::parse(&snapshot.to_json().to_string()).expect("snapshot should parse");
+        assert_eq!(Snapshot::from_json(&parsed), Some(snapshot));
+    }
+}
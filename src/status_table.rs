@@ -0,0 +1,453 @@
+//! The table form of `amux status`, used when `--sort` or `--columns` is given:
+//! one row per session with a chosen set of columns, sorted by one key.
+
+use std::collections::BTreeMap;
+use std::process::Command;
+use std::str::FromStr;
+
+use crate::error::{bail, DynError, Result};
+use crate::time::format_duration;
+
+/// Columns shown when `--columns` is not given.
+pub const DEFAULT_COLUMNS: &[Column] = &[
+    Column::Agent,
+    Column::Name,
+    Column::State,
+    Column::Clients,
+    Column::Activity,
+    Column::Uptime,
+    Column::Idle,
+    Column::Cpu,
+    Column::Git,
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Column {
+    Agent,
+    Name,
+    Session,
+    State,
+    Clients,
+    Command,
+    Cwd,
+    Activity,
+    Uptime,
+    Idle,
+    Cpu,
+    Git,
+    Usage,
+    Note,
+}
+
+const COLUMN_NAMES: &[(&str, Column)] = &[
+    ("agent", Column::Agent),
+    ("name", Column::Name),
+    ("session", Column::Session),
+    ("state", Column::State),
+    ("clients", Column::Clients),
+    ("command", Column::Command),
+    ("cwd", Column::Cwd),
+    ("activity", Column::Activity),
+    ("uptime", Column::Uptime),
+    ("idle", Column::Idle),
+    ("cpu", Column::Cpu),
+    ("git", Column::Git),
+    ("usage", Column::Usage),
+    ("note", Column::Note),
+];
+
+impl Column {
+    fn name(self) -> &'static str {
+        COLUMN_NAMES
+            .iter()
+            .find(|(_, column)| *column == self)
+            .map_or("", |(name, _)| name)
+    }
+}
+
+impl FromStr for Column {
+    type Err = DynError;
+
+    fn from_str(raw: &str) -> Result<Self> {
+        match COLUMN_NAMES.iter().find(|(name, _)| *name == raw) {
+            Some((_, column)) => Ok(*column),
+            None => {
+                let names: Vec<&str> = COLUMN_NAMES.iter().map(|(name, _)| *name).collect();
+                bail(format!(
+                    "unknown column '{raw}' (expected {})",
+                    names.join(", ")
+                ))
+            }
+        }
+    }
+}
+
+/// Parse a comma-separated `--columns` list.
+pub fn parse_columns(raw: &str) -> Result<Vec<Column>> {
+    let columns = raw
+        .split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(str::parse)
+        .collect::<Result<Vec<Column>>>()?;
+    if columns.is_empty() {
+        return bail("--columns needs at least one column");
+    }
+    Ok(columns)
+}
+
+/// Row order for `--sort`. Names sort alphabetically; the numeric keys put
+/// the largest value first, so `--sort idle` lists stalled agents at the top.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SortKey {
+    Agent,
+    Name,
+    Uptime,
+    Idle,
+    Cpu,
+}
+
+impl FromStr for SortKey {
+    type Err = DynError;
+
+    fn from_str(raw: &str) -> Result<Self> {
+        match raw {
+            "agent" => Ok(Self::Agent),
+            "name" => Ok(Self::Name),
+            "uptime" => Ok(Self::Uptime),
+            "idle" => Ok(Self::Idle),
+            "cpu" => Ok(Self::Cpu),
+            other => bail(format!(
+                "unknown sort key '{other}' (expected agent, name, uptime, idle, or cpu)"
+            )),
+        }
+    }
+}
+
+/// Everything a row can show about one session.
+#[derive(Clone, Debug, Default)]
+pub struct Row {
+    pub agent: String,
+    pub name: Option<String>,
+    pub session: String,
+    pub state: String,
+    pub clients: usize,
+    pub command: Option<String>,
+    /// Working directory, with the home directory shortened to `~`.
+    pub cwd: Option<String>,
+    pub activity: String,
+    pub uptime: Option<u64>,
+    pub idle: Option<u64>,
+    /// CPU percentage of the pane's process and its children.
+    pub cpu: Option<f64>,
+    pub git: Option<String>,
+    pub usage: Option<String>,
+    pub note: Option<String>,
+}
+
+impl Row {
+    fn cell(&self, column: Column) -> String {
+        let text = |value: &Option<String>| value.clone().unwrap_or_else(|| "-".to_string());
+        let duration = |value: Option<u64>| value.map_or_else(|| "-".to_string(), format_duration);
+        match column {
+            Column::Agent => self.agent.clone(),
+            Column::Name => text(&self.name),
+            Column::Session => self.session.clone(),
+            Column::State => self.state.clone(),
+            Column::Clients => self.clients.to_string(),
+            Column::Command => text(&self.command),
+            Column::Cwd => text(&self.cwd),
+            Column::Activity => self.activity.clone(),
+            Column::Uptime => duration(self.uptime),
+            Column::Idle => duration(self.idle),
+            Column::Cpu => self
+                .cpu
+                .map_or_else(|| "-".to_string(), |cpu| format!("{cpu:.1}%")),
+            Column::Git => text(&self.git),
+            Column::Usage => text(&self.usage),
+            Column::Note => text(&self.note),
+        }
+    }
+}
+
+/// Sort `rows` by `key`, breaking ties by agent and then name.
+pub fn sort(rows: &mut [Row], key: SortKey) {
+    rows.sort_by(|a, b| {
+        let by_agent = (&a.agent, &a.name).cmp(&(&b.agent, &b.name));
+        let first = match key {
+            SortKey::Agent => by_agent,
+            SortKey::Name => (&a.name, &a.agent).cmp(&(&b.name, &b.agent)),
+            SortKey::Uptime => b.uptime.cmp(&a.uptime),
+            SortKey::Idle => b.idle.cmp(&a.idle),
+            SortKey::Cpu => b.cpu.unwrap_or(-1.0).total_cmp(&a.cpu.unwrap_or(-1.0)),
+        };
+        first.then(by_agent)
+    });
+}
+
+/// `rows` as a left-aligned table with a header line.
+pub fn render(columns: &[Column], rows: &[Row]) -> String {
+    render_styled(columns, rows, |_, _| None)
+}
+
+const NEW: &str = "\u{1b}[32m";
+const CHANGED: &str = "\u{1b}[1;33m";
+
+/// `rows` as in [`render`], with what changed since `before` marked when
+/// `colored`: new sessions in green, and a changed state or client count in
+/// bold yellow.
+pub fn render_changes(columns: &[Column], rows: &[Row], before: &[Row], colored: bool) -> String {
+    render_styled(columns, rows, |row, column| {
+        if !colored {
+            return None;
+        }
+        match before.iter().find(|old| key(old) == key(row)) {
+            None => Some(NEW),
+            Some(old)
+                if matches!(column, Column::State | Column::Clients)
+                    && old.cell(column) != row.cell(column) =>
+            {
+                Some(CHANGED)
+            }
+            Some(_) => None,
+        }
+    })
+}
+
+/// Sessions that appeared, disappeared, changed state, or gained or lost
+/// clients between `before` and `after`, one line each.
+pub fn changes(before: &[Row], after: &[Row]) -> Vec<String> {
+    let mut changes = Vec::new();
+    for old in before {
+        if !after.iter().any(|row| key(row) == key(old)) {
+            changes.push(format!("- {}: gone", label(old)));
+        }
+    }
+    for row in after {
+        let Some(old) = before.iter().find(|old| key(old) == key(row)) else {
+            changes.push(format!("+ {}: new, {}", label(row), row.state));
+            continue;
+        };
+        for (field, from, to) in [
+            ("state", old.state.clone(), row.state.clone()),
+            ("clients", old.clients.to_string(), row.clients.to_string()),
+        ] {
+            if from != to {
+                changes.push(format!("~ {}: {field} {from} -> {to}", label(row)));
+            }
+        }
+    }
+    changes
+}
+
+/// Rows for stopped agents (`--all`) have no session, so the agent is part of the key.
+fn key(row: &Row) -> (&str, &str) {
+    (&row.session, &row.agent)
+}
+
+fn label(row: &Row) -> String {
+    if row.session.is_empty() {
+        row.agent.clone()
+    } else {
+        format!("{} ({})", row.session, row.agent)
+    }
+}
+
+fn render_styled(
+    columns: &[Column],
+    rows: &[Row],
+    style: impl Fn(&Row, Column) -> Option<&'static str>,
+) -> String {
+    let mut lines: Vec<Vec<String>> = vec![columns
+        .iter()
+        .map(|column| column.name().to_uppercase())
+        .collect()];
+    lines.extend(
+        rows.iter()
+            .map(|row| columns.iter().map(|column| row.cell(*column)).collect()),
+    );
+    let widths: Vec<usize> = (0..columns.len())
+        .map(|index| {
+            lines
+                .iter()
+                .map(|line| line[index].chars().count())
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+    lines
+        .iter()
+        .enumerate()
+        .map(|(index, line)| {
+            let cells: Vec<String> = line
+                .iter()
+                .zip(&widths)
+                .zip(columns)
+                .map(|((cell, width), column)| {
+                    // The header is line 0; row styles start at line 1.
+                    let code = index
+                        .checked_sub(1)
+                        .and_then(|row| style(&rows[row], *column));
+                    match code {
+                        Some(code) => format!(
+                            "{code}{cell}\u{1b}[0m{}",
+                            " ".repeat(width - cell.chars().count())
+                        ),
+                        None => format!("{cell:<width$}"),
+                    }
+                })
+                .collect();
+            cells.join("  ").trim_end().to_string() + "\n"
+        })
+        .collect()
+}
+
+/// CPU percentage of each of `pids` together with all of its descendants.
+pub fn cpu_usage(pids: &[u32]) -> BTreeMap<u32, f64> {
+    let Ok(output) = Command::new("ps")
+        .args(["-A", "-o", "pid=,ppid=,%cpu="])
+        .output()
+    else {
+        return BTreeMap::new();
+    };
+    if !output.status.success() {
+        return BTreeMap::new();
+    }
+    let processes = parse_ps(&String::from_utf8_lossy(&output.stdout));
+    pids.iter()
+        .map(|pid| (*pid, tree_cpu(&processes, *pid)))
+        .collect()
+}
+
+/// `(pid, parent pid, cpu)` triples from `ps -o pid=,ppid=,%cpu=` output.
+fn parse_ps(output: &str) -> Vec<(u32, u32, f64)> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let pid = fields.next()?.parse().ok()?;
+            let ppid = fields.next()?.parse().ok()?;
+            let cpu = fields.next()?.replace(',', ".").parse().ok()?;
+            Some((pid, ppid, cpu))
+        })
+        .collect()
+}
+
+fn tree_cpu(processes: &[(u32, u32, f64)], root: u32) -> f64 {
+    let mut total = 0.0;
+    let mut pending = vec![root];
+    while let Some(pid) = pending.pop() {
+        for (child, parent, cpu) in processes {
+            if *child == pid {
+                total += cpu;
+            }
+            if *parent == pid && *child != pid {
+                pending.push(*child);
+            }
+        }
+    }
+    total
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(agent: &str, name: Option<&str>, idle: u64, cpu: Option<f64>) -> Row {
+        Row {
+            agent: agent.into(),
+            name: name.map(str::to_owned),
+            session: format!("amux-{agent}"),
+            state: "running".into(),
+            idle: Some(idle),
+            cpu,
+            ..Row::default()
+        }
+    }
+
+    #[test]
+    fn columns_and_sort_keys_parse() {
+        let columns = parse_columns("agent, idle,cpu").expect("known columns");
+        assert_eq!(columns, [Column::Agent, Column::Idle, Column::Cpu]);
+        let err = parse_columns("agent,ram").expect_err("ram is not a column");
+        assert!(err.to_string().contains("unknown column 'ram'"));
+        assert!(parse_columns(" , ").is_err());
+        assert_eq!(
+            "idle".parse::<SortKey>().expect("idle is a key"),
+            SortKey::Idle
+        );
+        assert!("size".parse::<SortKey>().is_err());
+    }
+
+    #[test]
+    fn sort_puts_largest_numbers_first() {
+        let mut rows = vec![
+            row("codex", None, 30, Some(1.0)),
+            row("claude", Some("b"), 900, None),
+            row("claude", None, 60, Some(45.5)),
+        ];
+        sort(&mut rows, SortKey::Idle);
+        let idle: Vec<_> = rows.iter().map(|row| row.idle).collect();
+        assert_eq!(idle, [Some(900), Some(60), Some(30)]);
+
+        sort(&mut rows, SortKey::Cpu);
+        assert_eq!(rows[0].cpu, Some(45.5));
+        assert_eq!(rows[2].cpu, None);
+
+        sort(&mut rows, SortKey::Agent);
+        let names: Vec<_> = rows
+            .iter()
+            .map(|row| (row.agent.as_str(), row.name.as_deref()))
+            .collect();
+        assert_eq!(
+            names,
+            [("claude", None), ("claude", Some("b")), ("codex", None)]
+        );
+    }
+
+    #[test]
+    fn render_aligns_columns() {
+        let rows = [row("claude", None, 125, Some(3.04))];
+        let table = render(&[Column::Agent, Column::Idle, Column::Cpu], &rows);
+        assert_eq!(table, "AGENT   IDLE   CPU\nclaude  2m 5s  3.0%\n");
+    }
+
+    #[test]
+    fn changes_and_highlights_follow_state_and_clients() {
+        let before = [row("claude", None, 10, None), row("codex", None, 10, None)];
+        let mut after = vec![
+            Row {
+                state: "exited".into(),
+                ..row("claude", None, 99, None)
+            },
+            row("gemini", None, 10, None),
+        ];
+        after[0].clients = 1;
+        assert_eq!(
+            changes(&before, &after),
+            [
+                "- amux-codex (codex): gone",
+                "~ amux-claude (claude): state running -> exited",
+                "~ amux-claude (claude): clients 0 -> 1",
+                "+ amux-gemini (gemini): new, running",
+            ]
+        );
+        let columns = [Column::Agent, Column::State];
+        let table = render_changes(&columns, &after, &before, true);
+        assert!(table.contains("claude  \u{1b}[1;33mexited\u{1b}[0m\n"));
+        assert!(table.contains("\u{1b}[32mgemini\u{1b}[0m  \u{1b}[32mrunning"));
+        assert_eq!(
+            render_changes(&columns, &after, &before, false),
+            render(&columns, &after)
+        );
+    }
+
+    #[test]
+    fn tree_cpu_adds_up_descendants() {
+        let processes =
+            parse_ps("  10     1  1.5\n  11    10 20.0\n  12    11  0,5\n  13     1 99.0\n");
+        assert_eq!(processes.len(), 4);
+        assert_eq!(tree_cpu(&processes, 10), 22.0);
+        assert_eq!(tree_cpu(&processes, 42), 0.0);
+    }
+}
@@ -0,0 +1,94 @@
+//! `amux stop`: ask an agent to exit and give it time to finish writing its
+//! state before the session is killed. The request is SIGTERM to the pane's
+//! program, or the keys set in `[agents.NAME.stop] keys` for agents that
+//! quit on an interrupt such as `C-c`, or the signals in `signal` for agents
+//! that want something else, such as SIGINT twice. `rm` and `start --force`
+//! ask the same way for agents with a `[stop]` table.
+
+use std::process::Command;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::config::Stop;
+use crate::error::{fail_with, with_context, ErrorKind, Result};
+use crate::tmux;
+
+const POLL: Duration = Duration::from_millis(200);
+/// Pause between signals, so a program that counts them (a second SIGINT
+/// to quit) sees separate ones.
+const BETWEEN_SIGNALS: Duration = Duration::from_millis(500);
+
+/// Ask the program in `session`'s agent pane to exit.
+pub fn request(session: &str, settings: &Stop) -> Result<()> {
+    // Keys and signals go to the agent's pane, not the one that has focus.
+    let pane = tmux::agent_pane(session)?;
+    if let Some(keys) = &settings.keys {
+        let keys = shell_words::split(keys).map_err(|err| {
+            fail_with(
+                ErrorKind::Config,
+                format!("cannot parse stop keys '{keys}': {err}"),
+                None,
+            )
+        })?;
+        tmux::send_key_names(&pane, &keys)?;
+        if settings.signals.is_empty() {
+            return Ok(());
+        }
+    }
+    let signals = match settings.signals.as_slice() {
+        [] => &["TERM".to_string()][..],
+        signals => signals,
+    };
+    for (index, signal) in signals.iter().enumerate() {
+        if index > 0 {
+            thread::sleep(BETWEEN_SIGNALS);
+            if tmux::pane_info(&pane).is_ok_and(|info| info.pane_dead) {
+                break;
+            }
+        }
+        send_signal(&pane, signal)?;
+    }
+    Ok(())
+}
+
+fn send_signal(pane: &str, signal: &str) -> Result<()> {
+    let pid = tmux::pane_info(pane)?.pid.ok_or_else(|| {
+        fail_with(
+            ErrorKind::Tmux,
+            format!("tmux reports no process for pane '{pane}'"),
+            None,
+        )
+    })?;
+    let status = Command::new("kill")
+        .args([format!("-{signal}"), pid.to_string()])
+        .status()
+        .map_err(|err| with_context(err, "failed to run kill"))?;
+    if !status.success() {
+        return Err(fail_with(
+            ErrorKind::Other,
+            format!("kill -{signal} {pid} exited with {status}"),
+            None,
+        ));
+    }
+    Ok(())
+}
+
+/// Wait up to `grace` for the agent in `session` to exit. Returns `false`
+/// when it is still running afterwards.
+pub fn wait(session: &str, grace: Duration) -> Result<bool> {
+    let deadline = Instant::now() + grace;
+    let pane = tmux::agent_pane(session)?;
+    loop {
+        if !tmux::has_session(session)? {
+            return Ok(true);
+        }
+        // The pane stays, dead, because sessions are kept on exit.
+        if tmux::pane_info(&pane).is_ok_and(|info| info.pane_dead) {
+            return Ok(true);
+        }
+        if Instant::now() >= deadline {
+            return Ok(false);
+        }
+        thread::sleep(POLL);
+    }
+}
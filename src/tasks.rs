@@ -0,0 +1,262 @@
+//! The task board behind `amux tasks`: a small list of work items per
+//! workspace, each open, assigned to a session, or done.
+//!
+//! A workspace is the git repository containing the current directory, or the
+//! directory itself outside a repository. Each board is one JSON file under
+//! `tasks/` in the state directory.
+
+use std::path::{Path, PathBuf};
+
+use crate::error::{fail_with, ErrorKind, Result};
+use crate::git;
+use crate::json::{self, Value};
+use crate::paths;
+use crate::state;
+use crate::time::unix_now;
+
+const SCHEMA: state::Schema = state::Schema {
+    what: "task board",
+    version: 1,
+    migrate: state::unchanged,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum State {
+    Open,
+    Assigned,
+    Done,
+}
+
+impl State {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Open => "open",
+            Self::Assigned => "assigned",
+            Self::Done => "done",
+        }
+    }
+
+    fn parse(raw: &str) -> Option<Self> {
+        match raw {
+            "open" => Some(Self::Open),
+            "assigned" => Some(Self::Assigned),
+            "done" => Some(Self::Done),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Task {
+    pub id: u32,
+    pub text: String,
+    pub state: State,
+    /// The session working on it, once assigned.
+    pub session: Option<String>,
+    pub created: u64,
+    pub updated: u64,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Board {
+    pub workspace: PathBuf,
+    pub tasks: Vec<Task>,
+}
+
+impl Board {
+    pub fn add(&mut self, text: &str) -> &Task {
+        let id = self
+            .tasks
+            .iter()
+            .map(|task| task.id)
+            .max()
+            .unwrap_or_default()
+            + 1;
+        let now = unix_now();
+        self.tasks.push(Task {
+            id,
+            text: text.to_string(),
+            state: State::Open,
+            session: None,
+            created: now,
+            updated: now,
+        });
+        &self.tasks[self.tasks.len() - 1]
+    }
+
+    /// Hand task `id` to `session`; reassigning a task moves it.
+    pub fn assign(&mut self, id: u32, session: &str) -> Result<&Task> {
+        let task = self.task_mut(id)?;
+        if task.state == State::Done {
+            return Err(fail_with(
+                ErrorKind::InvalidInput,
+                format!("task {id} is already done"),
+                Some(format!("run `amux tasks reopen {id}` first")),
+            ));
+        }
+        task.state = State::Assigned;
+        task.session = Some(session.to_string());
+        task.updated = unix_now();
+        Ok(task)
+    }
+
+    pub fn set_done(&mut self, id: u32, done: bool) -> Result<&Task> {
+        let task = self.task_mut(id)?;
+        task.state = match (done, &task.session) {
+            (true, _) => State::Done,
+            (false, Some(_)) => State::Assigned,
+            (false, None) => State::Open,
+        };
+        task.updated = unix_now();
+        Ok(task)
+    }
+
+    fn task_mut(&mut self, id: u32) -> Result<&mut Task> {
+        let workspace = self.workspace.display().to_string();
+        self.tasks
+            .iter_mut()
+            .find(|task| task.id == id)
+            .ok_or_else(|| {
+                fail_with(
+                    ErrorKind::InvalidInput,
+                    format!("no task {id} on the board for {workspace}"),
+                    Some("run `amux tasks list --all` to see its tasks".into()),
+                )
+            })
+    }
+
+    fn to_json(&self) -> Value {
+        let tasks = self
+            .tasks
+            .iter()
+            .map(|task| {
+                json::object([
+                    ("id", Value::from(task.id)),
+                    ("text", Value::from(task.text.as_str())),
+                    ("state", Value::from(task.state.as_str())),
+                    ("session", Value::from(task.session.clone())),
+                    ("created_at", Value::from(task.created)),
+                    ("updated_at", Value::from(task.updated)),
+                ])
+            })
+            .collect();
+        json::object([
+            (
+                "workspace",
+                Value::from(self.workspace.to_string_lossy().into_owned()),
+            ),
+            ("tasks", Value::Array(tasks)),
+        ])
+    }
+
+    fn from_json(value: &Value) -> Option<Self> {
+        let tasks = value
+            .get("tasks")?
+            .as_array()?
+            .iter()
+            .map(|task| {
+                Some(Task {
+                    id: u32::try_from(task.get("id")?.as_u64()?).ok()?,
+                    text: task.get("text")?.as_str()?.to_string(),
+                    state: State::parse(task.get("state")?.as_str()?)?,
+                    session: task
+                        .get("session")
+                        .and_then(Value::as_str)
+                        .map(str::to_string),
+                    created: task.get("created_at")?.as_u64()?,
+                    updated: task.get("updated_at")?.as_u64()?,
+                })
+            })
+            .collect::<Option<_>>()?;
+        Some(Self {
+            workspace: PathBuf::from(value.get("workspace")?.as_str()?),
+            tasks,
+        })
+    }
+}
+
+/// The workspace `dir` belongs to: its repository's top level, or `dir` itself.
+pub fn workspace(dir: &Path) -> PathBuf {
+    git::run(dir, None, &["rev-parse", "--show-toplevel"])
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| dir.to_path_buf())
+}
+
+/// The board for `workspace`, empty if nothing was ever added.
+pub fn load(workspace: &Path) -> Result<Board> {
+    let path = board_file(workspace)?;
+    let Some(value) = state::read(&path, &SCHEMA)? else {
+        return Ok(Board {
+            workspace: workspace.to_path_buf(),
+            tasks: Vec::new(),
+        });
+    };
+    Board::from_json(&value).ok_or_else(|| {
+        fail_with(
+            ErrorKind::Other,
+            format!("{} is corrupt", path.display()),
+            None,
+        )
+    })
+}
+
+/// Hold while changing the board for `workspace`, from `load` to `save`, so
+/// two sessions claiming tasks at once do not lose one of the changes.
+pub fn lock(workspace: &Path) -> Result<state::Lock> {
+    state::lock(&board_file(workspace)?)
+}
+
+pub fn save(board: &Board) -> Result<()> {
+    state::write(&board_file(&board.workspace)?, &SCHEMA, board.to_json())
+}
+
+fn board_file(workspace: &Path) -> Result<PathBuf> {
+    Ok(paths::tasks_dir()?.join(format!("{}.json", board_key(workspace))))
+}
+
+/// File name for a workspace's board, readable and unique per path.
+fn board_key(workspace: &Path) -> String {
+    workspace
+        .to_string_lossy()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect::<String>()
+        .trim_matches('-')
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tasks_move_between_states() {
+        let mut board = Board::default();
+        assert_eq!(board.add("Fix login").id, 1);
+        assert_eq!(board.add("Add tests").id, 2);
+
+        let task = board.assign(2, "amux-codex").expect("task 2 exists");
+        assert_eq!(task.state, State::Assigned);
+        assert_eq!(board.set_done(2, true).expect("done").state, State::Done);
+        assert!(board.assign(2, "amux-claude").is_err());
+        assert_eq!(
+            board.set_done(2, false).expect("reopen").state,
+            State::Assigned
+        );
+        assert_eq!(board.set_done(1, false).expect("reopen").state, State::Open);
+        assert!(board.set_done(9, true).is_err());
+    }
+
+    #[test]
+    fn board_round_trips_through_json() {
+        let mut board = Board {
+            workspace: PathBuf::from("/work/repo"),
+            tasks: Vec::new(),
+        };
+        board.add("Fix login");
+        board.assign(1, "amux-codex").expect("task 1 exists");
+        let value = json::parse(&board.to_json().to_string()).expect("json should parse");
+        assert_eq!(Board::from_json(&value), Some(board));
+        assert_eq!(board_key(Path::new("/work/my repo")), "work-my-repo");
+    }
+}
@@ -0,0 +1,152 @@
+//! `{placeholder}` expansion for configured commands, params, and prompts.
+//!
+//! Only `{identifier}` sequences are placeholders; other braces (JSON, shell
+//! brace expansion) pass through untouched. Write `{{name}}` for a literal
+//! `{name}`.
+
+use std::path::PathBuf;
+
+use crate::error::{bail, Result};
+use crate::git;
+use crate::time;
+
+/// Values available to templates. Git-derived values are looked up lazily so
+/// commands without git placeholders work outside repositories.
+#[derive(Debug)]
+pub struct Context {
+    pub cwd: PathBuf,
+    pub agent: String,
+    pub name: Option<String>,
+    pub session: String,
+}
+
+impl Context {
+    /// Value of a built-in placeholder, or `None` if `key` is not one.
+    pub fn lookup(&self, key: &str) -> Result<Option<String>> {
+        Ok(Some(match key {
+            "cwd" => self.cwd.to_string_lossy().into_owned(),
+            "agent" => self.agent.clone(),
+            "name" => self.name.clone().unwrap_or_default(),
+            "session" => self.session.clone(),
+            "date" => time::format_date(time::unix_now()),
+            "git_branch" => match git::branch(&self.cwd)? {
+                Some(branch) => branch,
+                None => {
+                    return bail(format!(
+                        "{{git_branch}} needs a checked-out branch in {}",
+                        self.cwd.display()
+                    ))
+                }
+            },
+            "repo" => match git::toplevel(&self.cwd)? {
+                Some(root) => root
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_default(),
+                None => {
+                    return bail(format!(
+                        "{{repo}} needs a git repository; {} is not inside one",
+                        self.cwd.display()
+                    ))
+                }
+            },
+            _ => return Ok(None),
+        }))
+    }
+}
+
+pub fn expand(text: &str, context: &Context) -> Result<String> {
+    expand_with(text, |key| context.lookup(key))
+}
+
+/// Expand placeholders using `lookup`, which returns `None` for unknown names.
+pub fn expand_with<F>(text: &str, mut lookup: F) -> Result<String>
+where
+    F: FnMut(&str) -> Result<Option<String>>,
+{
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(open) = rest.find('{') {
+        out.push_str(&rest[..open]);
+        let after = &rest[open..];
+
+        if let Some(key) = escaped_placeholder(after) {
+            out.push('{');
+            out.push_str(key);
+            out.push('}');
+            rest = &after[key.len() + 4..];
+            continue;
+        }
+
+        match placeholder(after) {
+            Some(key) => {
+                match lookup(key)? {
+                    Some(value) => out.push_str(&value),
+                    None => return bail(format!("unknown template variable '{{{key}}}'")),
+                }
+                rest = &after[key.len() + 2..];
+            }
+            None => {
+                out.push('{');
+                rest = &after[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// `{ident}` at the start of `text`.
+fn placeholder(text: &str) -> Option<&str> {
+    let inner = text.strip_prefix('{')?;
+    let end = inner.find('}')?;
+    let key = &inner[..end];
+    is_identifier(key).then_some(key)
+}
+
+/// `{{ident}}` at the start of `text`.
+fn escaped_placeholder(text: &str) -> Option<&str> {
+    let inner = text.strip_prefix("{{")?;
+    let end = inner.find("}}")?;
+    let key = &inner[..end];
+    is_identifier(key).then_some(key)
+}
+
+fn is_identifier(key: &str) -> bool {
+    !key.is_empty() && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expand_map(text: &str) -> Result<String> {
+        expand_with(text, |key| {
+            Ok(match key {
+                "repo" => Some("amux".to_string()),
+                "session" => Some("amux-codex".to_string()),
+                _ => None,
+            })
+        })
+    }
+
+    #[test]
+    fn expand_replaces_known_placeholders() {
+        let text = expand_map("--project {repo} --tag {session}").expect("should expand");
+        assert_eq!(text, "--project amux --tag amux-codex");
+    }
+
+    #[test]
+    fn expand_leaves_other_braces_alone() {
+        let text = expand_map(r#"--json {"a": 1} {a,b} {} {{repo}}"#).expect("should expand");
+        assert_eq!(text, r#"--json {"a": 1} {a,b} {} {repo}"#);
+    }
+
+    #[test]
+    fn expand_rejects_unknown_placeholders() {
+        let err = expand_map("{nope}").expect_err("unknown name should fail");
+        assert!(err
+            .to_string()
+            .contains("unknown template variable '{nope}'"));
+    }
+}
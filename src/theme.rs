@@ -0,0 +1,166 @@
+//! Per-agent color and icon, configured as `color` and `icon` under
+//! `[agents.NAME]`, so sessions of different agents are easy to tell apart in
+//! `status`, `list`, the `rm --interactive` checklist, editor pickers, and tmux
+//! window names.
+
+use std::env;
+use std::fmt;
+use std::io::{self, IsTerminal};
+use std::str::FromStr;
+
+use crate::error::{fail, DynError};
+
+const NAMED: &[(&str, u8)] = &[
+    ("black", 0),
+    ("red", 1),
+    ("green", 2),
+    ("yellow", 3),
+    ("blue", 4),
+    ("magenta", 5),
+    ("cyan", 6),
+    ("white", 7),
+    ("bright-black", 8),
+    ("bright-red", 9),
+    ("bright-green", 10),
+    ("bright-yellow", 11),
+    ("bright-blue", 12),
+    ("bright-magenta", 13),
+    ("bright-cyan", 14),
+    ("bright-white", 15),
+];
+
+/// A terminal color: a name from the basic 16, an index into the 256-color
+/// palette, or `#rrggbb`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Color {
+    Indexed(u8),
+    Rgb(u8, u8, u8),
+}
+
+impl Color {
+    /// SGR escape that switches the foreground to this color.
+    pub fn ansi_foreground(self) -> String {
+        match self {
+            Color::Indexed(index @ 0..=7) => format!("\u{1b}[{}m", 30 + index),
+            Color::Indexed(index @ 8..=15) => format!("\u{1b}[{}m", 82 + index),
+            Color::Indexed(index) => format!("\u{1b}[38;5;{index}m"),
+            Color::Rgb(r, g, b) => format!("\u{1b}[38;2;{r};{g};{b}m"),
+        }
+    }
+
+    /// The color as tmux style syntax understands it.
+    pub fn tmux(self) -> String {
+        match self {
+            Color::Indexed(index) => format!("colour{index}"),
+            Color::Rgb(r, g, b) => format!("#{r:02x}{g:02x}{b:02x}"),
+        }
+    }
+}
+
+impl FromStr for Color {
+    type Err = DynError;
+
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        let raw = raw.trim().to_ascii_lowercase();
+        if let Some((_, index)) = NAMED.iter().find(|(name, _)| *name == raw) {
+            return Ok(Color::Indexed(*index));
+        }
+        if let Ok(index) = raw.parse::<u8>() {
+            return Ok(Color::Indexed(index));
+        }
+        if let Some(hex) = raw.strip_prefix('#').filter(|hex| hex.len() == 6) {
+            if let Ok(rgb) = u32::from_str_radix(hex, 16) {
+                return Ok(Color::Rgb((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8));
+            }
+        }
+        Err(fail(format!(
+            "unknown color '{raw}' (use a name like cyan or bright-red, 0-255, or #rrggbb)"
+        )))
+    }
+}
+
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Color::Indexed(index) => match NAMED.iter().find(|(_, named)| named == index) {
+                Some((name, _)) => f.write_str(name),
+                None => write!(f, "{index}"),
+            },
+            Color::Rgb(r, g, b) => write!(f, "#{r:02x}{g:02x}{b:02x}"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Theme {
+    pub color: Option<Color>,
+    pub icon: Option<String>,
+}
+
+impl Theme {
+    pub fn is_empty(&self) -> bool {
+        self.color.is_none() && self.icon.is_none()
+    }
+
+    /// `text` prefixed with the icon and, when `colored`, wrapped in the color.
+    /// `width` pads the plain text first so columns still line up.
+    pub fn paint(&self, text: &str, width: usize, colored: bool) -> String {
+        let plain = match &self.icon {
+            Some(icon) => format!("{icon} {text}"),
+            None => text.to_string(),
+        };
+        let padding = " ".repeat(width.saturating_sub(plain.chars().count()));
+        match self.color {
+            Some(color) if colored => {
+                format!("{}{plain}\u{1b}[0m{padding}", color.ansi_foreground())
+            }
+            _ => format!("{plain}{padding}"),
+        }
+    }
+}
+
+/// Whether to color stdout: only on a terminal, and never with `NO_COLOR` set.
+pub fn use_color() -> bool {
+    env::var_os("NO_COLOR").is_none_or(|value| value.is_empty()) && io::stdout().is_terminal()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn color_parses_names_indices_and_hex() {
+        assert_eq!("Cyan".parse::<Color>().expect("name"), Color::Indexed(6));
+        assert_eq!(
+            "bright-red".parse::<Color>().expect("name"),
+            Color::Indexed(9)
+        );
+        assert_eq!("208".parse::<Color>().expect("index"), Color::Indexed(208));
+        assert_eq!(
+            "#D97757".parse::<Color>().expect("hex"),
+            Color::Rgb(0xd9, 0x77, 0x57)
+        );
+        assert!("mauve".parse::<Color>().is_err());
+        assert!("#abc".parse::<Color>().is_err());
+    }
+
+    #[test]
+    fn color_renders_for_terminals_and_tmux() {
+        assert_eq!(Color::Indexed(6).ansi_foreground(), "\u{1b}[36m");
+        assert_eq!(Color::Indexed(9).ansi_foreground(), "\u{1b}[91m");
+        assert_eq!(Color::Indexed(208).tmux(), "colour208");
+        assert_eq!(Color::Rgb(1, 2, 255).tmux(), "#0102ff");
+        assert_eq!(Color::Indexed(6).to_string(), "cyan");
+    }
+
+    #[test]
+    fn paint_pads_before_coloring() {
+        let theme = Theme {
+            color: Some(Color::Indexed(2)),
+            icon: Some("◆".into()),
+        };
+        assert_eq!(theme.paint("codex", 9, false), "◆ codex  ");
+        assert_eq!(theme.paint("codex", 0, true), "\u{1b}[32m◆ codex\u{1b}[0m");
+        assert_eq!(Theme::default().paint("codex", 6, true), "codex ");
+    }
+}
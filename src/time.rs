@@ -0,0 +1,187 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default()
+}
+
+/// Milliseconds from the Unix epoch to `time`.
+pub fn unix_millis(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+/// Human-friendly duration using the two largest units, e.g. `3h 2m`.
+pub fn format_duration(seconds: u64) -> String {
+    let (days, hours) = (seconds / 86_400, seconds / 3_600 % 24);
+    let (minutes, secs) = (seconds / 60 % 60, seconds % 60);
+    if days > 0 {
+        format!("{days}d {hours}h")
+    } else if hours > 0 {
+        format!("{hours}h {minutes}m")
+    } else if minutes > 0 {
+        format!("{minutes}m {secs}s")
+    } else {
+        format!("{secs}s")
+    }
+}
+
+/// Seconds in a duration such as `90s`, `30m`, `2h`, `1d`, or `1h30m`.
+pub fn parse_duration(raw: &str) -> Option<u64> {
+    let mut total: u64 = 0;
+    let mut number = String::new();
+    for ch in raw.trim().chars() {
+        if ch.is_ascii_digit() {
+            number.push(ch);
+            continue;
+        }
+        let unit = match ch {
+            's' => 1,
+            'm' => 60,
+            'h' => 3_600,
+            'd' => 86_400,
+            _ => return None,
+        };
+        let count: u64 = std::mem::take(&mut number).parse().ok()?;
+        total = total.checked_add(count.checked_mul(unit)?)?;
+    }
+    (number.is_empty() && total > 0).then_some(total)
+}
+
+/// UTC calendar date (`YYYY-MM-DD`) for a unix timestamp.
+pub fn format_date(timestamp: u64) -> String {
+    let (year, month, day) = civil_from_days((timestamp / 86_400) as i64);
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
+/// UTC timestamp in RFC 3339 form, e.g. `2024-05-01T12:30:00Z`.
+pub fn format_timestamp(timestamp: u64) -> String {
+    let secs = timestamp % 86_400;
+    format!(
+        "{}T{:02}:{:02}:{:02}Z",
+        format_date(timestamp),
+        secs / 3_600,
+        secs / 60 % 60,
+        secs % 60
+    )
+}
+
+/// Parse an RFC 3339 timestamp such as `2024-05-01T12:30:00.123Z` or
+/// `2024-05-01T14:30:00+02:00` into unix seconds.
+pub fn parse_timestamp(raw: &str) -> Option<u64> {
+    let (date, time) = raw.split_once(['T', ' '])?;
+    let mut date_parts = date.splitn(3, '-');
+    let year: i64 = date_parts.next()?.parse().ok()?;
+    let month: u32 = date_parts.next()?.parse().ok()?;
+    let day: u32 = date_parts.next()?.parse().ok()?;
+
+    let (clock, offset) = match time.find(['Z', 'z', '+', '-']) {
+        Some(index) => time.split_at(index),
+        None => (time, ""),
+    };
+    let mut clock_parts = clock.splitn(3, ':');
+    let hours: i64 = clock_parts.next()?.parse().ok()?;
+    let minutes: i64 = clock_parts.next()?.parse().ok()?;
+    let seconds: i64 = clock_parts.next()?.split('.').next()?.parse().ok()?;
+
+    let offset_seconds = match offset.chars().next() {
+        None | Some('Z') | Some('z') => 0,
+        Some(sign) => {
+            let (offset_hours, offset_minutes) = offset[1..].split_once(':')?;
+            let magnitude = offset_hours.parse::<i64>().ok()? * 3_600
+                + offset_minutes.parse::<i64>().ok()? * 60;
+            if sign == '-' {
+                -magnitude
+            } else {
+                magnitude
+            }
+        }
+    };
+
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+    let total = days_from_civil(year, month, day) * 86_400 + hours * 3_600 + minutes * 60 + seconds
+        - offset_seconds;
+    u64::try_from(total).ok()
+}
+
+/// Convert a civil date to days since the unix epoch.
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let month = i64::from(month);
+    let doy = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + i64::from(day) - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+/// Convert days since the unix epoch to a (year, month, day) civil date.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    // Howard Hinnant's days_from_civil inverse.
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_duration_picks_two_largest_units() {
+        assert_eq!(format_duration(42), "42s");
+        assert_eq!(format_duration(125), "2m 5s");
+        assert_eq!(format_duration(3 * 3_600 + 120), "3h 2m");
+        assert_eq!(format_duration(2 * 86_400 + 5 * 3_600), "2d 5h");
+    }
+
+    #[test]
+    fn parse_duration_adds_up_units() {
+        assert_eq!(parse_duration("90s"), Some(90));
+        assert_eq!(parse_duration("2h"), Some(7_200));
+        assert_eq!(parse_duration("1h30m"), Some(5_400));
+        assert_eq!(parse_duration("1d"), Some(86_400));
+        assert_eq!(parse_duration("30"), None);
+        assert_eq!(parse_duration("0m"), None);
+        assert_eq!(parse_duration("2w"), None);
+    }
+
+    #[test]
+    fn format_date_handles_epoch_and_leap_years() {
+        assert_eq!(format_date(0), "1970-01-01");
+        assert_eq!(format_date(951_782_400), "2000-02-29");
+        assert_eq!(format_date(1_700_000_000), "2023-11-14");
+    }
+
+    #[test]
+    fn format_timestamp_includes_time_of_day() {
+        assert_eq!(format_timestamp(1_700_000_000), "2023-11-14T22:13:20Z");
+    }
+
+    #[test]
+    fn parse_timestamp_inverts_format_and_applies_offsets() {
+        assert_eq!(parse_timestamp("2023-11-14T22:13:20Z"), Some(1_700_000_000));
+        assert_eq!(
+            parse_timestamp("2023-11-14T22:13:20.512Z"),
+            Some(1_700_000_000)
+        );
+        assert_eq!(
+            parse_timestamp("2023-11-15T00:13:20+02:00"),
+            Some(1_700_000_000)
+        );
+        assert_eq!(parse_timestamp("2000-02-29T00:00:00Z"), Some(951_782_400));
+        assert_eq!(parse_timestamp("yesterday"), None);
+    }
+}
@@ -1,9 +1,113 @@
-use std::io;
+use std::env;
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::OnceLock;
+use std::thread;
+use std::time::Duration;
 
-use crate::error::{bail, with_context, Result};
+use crate::error::{bail, fail_with, with_context, ErrorKind, Result};
+use crate::paths;
+use crate::tmux_format;
 
 pub const SESSION_PREFIX: &str = "amux-";
+pub const DEFAULT_RETRIES: u32 = 2;
+/// The tmux server (`tmux -L amux`) sessions run on unless configured otherwise.
+pub const DEFAULT_SOCKET: &str = "amux";
+/// The `tmux_socket` value that selects the user's own tmux server.
+pub const SHARED_SOCKET: &str = "default";
+
+static RETRIES: AtomicU32 = AtomicU32::new(DEFAULT_RETRIES);
+static SOCKET: OnceLock<Option<String>> = OnceLock::new();
+static SHARED: AtomicBool = AtomicBool::new(false);
+
+/// Failure that is expected to go away on its own, e.g. the tmux server still
+/// starting up or its socket being briefly unavailable.
+#[derive(Debug)]
+pub struct TransientError(String);
+
+impl fmt::Display for TransientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Error for TransientError {}
+
+/// Set how many times transient tmux failures are retried.
+pub fn set_retries(retries: u32) {
+    RETRIES.store(retries, Ordering::Relaxed);
+}
+
+/// Parse a `tmux_socket` setting: a socket name for `tmux -L`, or
+/// [`SHARED_SOCKET`] (`None`) for the user's own server.
+pub fn parse_socket(raw: &str) -> Result<Option<String>> {
+    match raw {
+        SHARED_SOCKET => Ok(None),
+        "" => bail("tmux_socket must not be empty"),
+        name if name.contains('/') => bail(format!(
+            "tmux_socket '{name}' must be a socket name, not a path"
+        )),
+        name => Ok(Some(name.to_string())),
+    }
+}
+
+/// The socket sessions run on: `AMUX_TMUX_SOCKET`, else `configured()`
+/// (the config's `tmux_socket`, only read when needed), else
+/// [`DEFAULT_SOCKET`].
+pub fn select_socket(configured: impl FnOnce() -> Option<String>) -> Result<Option<String>> {
+    match env::var("AMUX_TMUX_SOCKET") {
+        Ok(raw) if !raw.is_empty() => parse_socket(&raw),
+        _ => match configured() {
+            Some(raw) => parse_socket(&raw),
+            None => Ok(Some(DEFAULT_SOCKET.to_string())),
+        },
+    }
+}
+
+/// Run every tmux command on the server named `socket`, or on the user's own
+/// server with `None`. Only the first call counts.
+pub fn set_socket(socket: Option<String>) {
+    let _ = SOCKET.set(socket);
+}
+
+/// Send tmux commands to the user's own server instead of amux's until
+/// called again with `false`; `amux migrate-socket` reads and removes the
+/// sessions left there with it.
+pub fn use_shared_server(shared: bool) {
+    SHARED.store(shared, Ordering::Relaxed);
+}
+
+/// The `tmux -L` socket name amux uses, `None` for the user's own server.
+pub fn socket() -> Option<&'static str> {
+    if SHARED.load(Ordering::Relaxed) {
+        return None;
+    }
+    SOCKET
+        .get_or_init(|| Some(DEFAULT_SOCKET.to_string()))
+        .as_deref()
+}
+
+/// The tmux configuration the server amux uses reads when it starts: amux's
+/// own `tmux.conf` beside its config file on a dedicated socket, so personal
+/// settings stay out of agent sessions, else the user's.
+pub fn conf_file() -> Result<PathBuf> {
+    match socket() {
+        Some(_) => Ok(paths::config_dir()?.join("tmux.conf")),
+        None => paths::tmux_conf(),
+    }
+}
+
+/// How to run tmux by hand against amux's server, for hints.
+pub fn command_prefix() -> String {
+    match socket() {
+        Some(name) => format!("tmux -L {}", shell_words::quote(name)),
+        None => "tmux".to_string(),
+    }
+}
 
 #[derive(Debug)]
 pub struct SessionDetail {
@@ -12,189 +116,1555 @@ pub struct SessionDetail {
     pub name: Option<String>,
     pub client_count: usize,
     pub pane_command: Option<String>,
+    /// Protected from removal by bulk and cleanup operations (`amux pin`).
+    pub pinned: bool,
+    /// Keep-alive gave up restarting the agent because it kept exiting.
+    pub crash_looping: bool,
+    /// Free-form note set with `amux note`.
+    pub note: Option<String>,
+    /// Working directory of the session's active pane.
+    pub cwd: Option<String>,
+}
+
+/// Live details about a single session's active pane.
+#[derive(Debug)]
+pub struct SessionInfo {
+    pub pid: Option<u32>,
+    pub cwd: Option<String>,
+    pub created: Option<u64>,
+    pub last_activity: Option<u64>,
+    pub pane_dead: bool,
+    pub pane_command: Option<String>,
+    pub start_command: Option<String>,
+    /// Exit status of the pane's program once it has died.
+    pub exit_status: Option<i32>,
+    /// The directory the session was started in.
+    pub start_dir: Option<String>,
+    /// Title the program set for its pane, which agents often use for the current
+    /// task; tmux's default (the host name) is reported as `None`.
+    pub pane_title: Option<String>,
+}
+
+/// Session user option marking a session as pinned.
+const PINNED_OPTION: &str = "@amux_pinned";
+/// Session user option set when keep-alive stops restarting a crash-looping agent.
+const CRASH_LOOP_OPTION: &str = "@amux_crash_loop";
+/// Session user option holding the id (`%N`) of the pane that runs the agent.
+const AGENT_PANE_OPTION: &str = "@amux_agent_pane";
+/// Session user options naming the agent and session name, so the session is
+/// recognized without parsing its name and native tmux views can show them.
+const AGENT_OPTION: &str = "@amux_agent";
+const NAME_OPTION: &str = "@amux_name";
+/// Session user option holding the note set with `amux note`.
+const NOTE_OPTION: &str = "@amux_note";
+/// Session user option holding where `amux start` was run, as JSON.
+const ORIGIN_OPTION: &str = "@amux_origin";
+/// Session user option holding the git commit checked out when the session
+/// started, to measure how much the agent has changed since.
+const BASE_OPTION: &str = "@amux_base";
+/// Session user option holding the `amux apply` declaration the session was
+/// started from, so a later apply can tell whether it drifted.
+const SPEC_OPTION: &str = "@amux_spec";
+/// Session user option set by `amux dnd`: when the mute ends, or `on`.
+const DND_OPTION: &str = "@amux_dnd";
+/// Session user option holding the idle minutes before clients are detached.
+const AUTO_DETACH_OPTION: &str = "@amux_auto_detach";
+
+/// `choose-tree -F` format that puts amux's agent, name, and note in front of
+/// tmux's usual session summary.
+pub const CHOOSE_TREE_FORMAT: &str = "#{?@amux_agent,#{@amux_agent}#{?@amux_name,/#{@amux_name},}\
+#{?@amux_note, - #{@amux_note},} | ,}#{session_windows} windows#{?session_attached, (attached),}";
+
+/// One pane of a session, as listed by `amux panes`.
+#[derive(Debug, PartialEq, Eq)]
+pub struct PaneInfo {
+    /// Stable tmux pane id such as `%3`.
+    pub id: String,
+    pub window: u32,
+    pub window_name: String,
+    pub index: u32,
+    /// The selected pane of its window.
+    pub current: bool,
+    /// The pane tmux would type into: the selected pane of the active window.
+    pub active: bool,
+    pub command: String,
+    /// The pane amux started the agent in.
+    pub agent: bool,
+}
+
+pub fn session_name(agent: &str, name: Option<&str>) -> String {
+    match name {
+        Some(name) => format!("{SESSION_PREFIX}{agent}--{}", encode_name(name)),
+        None => format!("{SESSION_PREFIX}{agent}"),
+    }
+}
+
+/// Encode a session name for the tmux session id. tmux rewrites `.` and `:`
+/// in session names and `/` would break the log paths, so every byte outside
+/// `[A-Za-z0-9_-]` becomes `%XX`. The original name is kept in `@amux_name`.
+fn encode_name(name: &str) -> String {
+    let mut encoded = String::with_capacity(name.len());
+    for byte in name.bytes() {
+        if byte.is_ascii_alphanumeric() || byte == b'-' || byte == b'_' {
+            encoded.push(char::from(byte));
+        } else {
+            encoded.push_str(&format!("%{byte:02X}"));
+        }
+    }
+    encoded
+}
+
+fn decode_name(encoded: &str) -> String {
+    let bytes = encoded.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        let hex = encoded.get(index + 1..index + 3);
+        match hex.and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+            Some(byte) if bytes[index] == b'%' => {
+                decoded.push(byte);
+                index += 3;
+            }
+            _ => {
+                decoded.push(bytes[index]);
+                index += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// What `list-sessions` reads per session, in [`parse_list_line`] order.
+const LIST_FIELDS: [&str; 9] = [
+    "session_name",
+    PINNED_OPTION,
+    CRASH_LOOP_OPTION,
+    "session_attached",
+    "pane_current_command",
+    AGENT_OPTION,
+    NAME_OPTION,
+    NOTE_OPTION,
+    "pane_current_path",
+];
+
+pub fn list_sessions() -> Result<Vec<SessionDetail>> {
+    // Everything status needs comes from this one call.
+    let output = tmux_command()
+        .arg("list-sessions")
+        .arg("-F")
+        .arg(tmux_format::format(&LIST_FIELDS))
+        .output()
+        .map_err(tmux_invoke_error)?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if stderr.contains("no server running") || stderr.contains("error connecting to") {
+            return Ok(Vec::new());
+        }
+        return tmux_failure(format!(
+            "tmux list-sessions exited with status {}",
+            output.status
+        ));
+    }
+
+    let records = tmux_format::parse(&output.stdout, LIST_FIELDS.len())?;
+    Ok(records
+        .iter()
+        .filter_map(|record| {
+            let line = parse_list_line(record);
+            let (agent, name) = line.identity()?;
+            Some(SessionDetail {
+                session_name: line.session.to_string(),
+                agent,
+                name,
+                client_count: line.clients,
+                pane_command: line.command.map(str::to_string),
+                pinned: line.pinned,
+                crash_looping: line.crash_looping,
+                note: line.note.map(str::to_string),
+                cwd: line.cwd.map(str::to_string),
+            })
+        })
+        .collect())
+}
+
+pub fn new_session(
+    session: &str,
+    command_tokens: &[String],
+    env: &[(String, String)],
+    cwd: Option<&Path>,
+) -> Result<()> {
+    with_retries(|| {
+        let mut cmd = tmux_command();
+        cmd.arg("new-session").arg("-d").arg("-s").arg(session);
+        if let Some(cwd) = cwd {
+            cmd.arg("-c").arg(cwd);
+        }
+        for (name, value) in env {
+            cmd.arg("-e").arg(format!("{name}={value}"));
+        }
+        // Keep the pane from the start, in the same tmux invocation, so a
+        // command that exits at once leaves its exit behind for the
+        // pane-died hook amux sets next.
+        let output = cmd
+            .arg("--")
+            .args(command_tokens)
+            .args([";", "set-option", "-w", "-t"])
+            .arg(pane_target(session))
+            .args(["remain-on-exit", "on"])
+            .output()
+            .map_err(tmux_invoke_error)?;
+        if output.status.success() {
+            Ok(())
+        } else {
+            Err(command_error("new-session", &output))
+        }
+    })
+}
+
+pub fn kill_session(session: &str) -> Result<()> {
+    let status = tmux_command()
+        .arg("kill-session")
+        .arg("-t")
+        .arg(session_target(session))
+        .status()
+        .map_err(tmux_invoke_error)?;
+    if status.success() {
+        Ok(())
+    } else {
+        tmux_failure(format!("tmux kill-session exited with status {status}"))
+    }
+}
+
+pub fn has_session(session: &str) -> Result<bool> {
+    let status = tmux_command()
+        .arg("has-session")
+        .arg("-t")
+        .arg(session_target(session))
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map_err(tmux_invoke_error)?;
+    Ok(status.success())
+}
+
+pub fn client_count(session: &str) -> Result<usize> {
+    let output = tmux_command()
+        .arg("list-clients")
+        .arg("-t")
+        .arg(session_target(session))
+        .output()
+        .map_err(tmux_invoke_error)?;
+
+    if output.status.success() {
+        let count = String::from_utf8_lossy(&output.stdout).lines().count();
+        Ok(count)
+    } else if output.stderr.is_empty() {
+        Ok(0)
+    } else {
+        tmux_failure(format!(
+            "tmux list-clients exited with status {}",
+            output.status
+        ))
+    }
+}
+
+/// Attach this terminal to `session`; `detach_others` detaches the clients
+/// already attached to it.
+pub fn attach_session(session: &str, detach_others: bool) -> Result<()> {
+    let mut cmd = tmux_command();
+    cmd.arg("attach-session");
+    if detach_others {
+        cmd.arg("-d");
+    }
+    let status = cmd
+        .arg("-t")
+        .arg(session_target(session))
+        .status()
+        .map_err(tmux_invoke_error)?;
+
+    if status.success() {
+        Ok(())
+    } else {
+        tmux_failure(format!("tmux attach-session exited with status {status}"))
+    }
+}
+
+/// Load `path` into the running server. Returns false when no server is
+/// running; the next one reads the file on its own.
+pub fn source_file(path: &Path) -> Result<bool> {
+    let output = tmux_command()
+        .arg("source-file")
+        .arg(path)
+        .output()
+        .map_err(tmux_invoke_error)?;
+    if output.status.success() {
+        return Ok(true);
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if stderr.contains("no server running") || stderr.contains("error connecting to") {
+        return Ok(false);
+    }
+    Err(command_error("source-file", &output))
+}
+
+/// Point `client` at `session`, as `switch-client` does from a key binding.
+pub fn switch_client(client: &str, session: &str) -> Result<()> {
+    let output = tmux_command()
+        .arg("switch-client")
+        .arg("-c")
+        .arg(client)
+        .arg("-t")
+        .arg(pane_target(session))
+        .output()
+        .map_err(tmux_invoke_error)?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(command_error("switch-client", &output))
+    }
+}
+
+/// Names of the clients attached to `session`.
+pub fn session_clients(session: &str) -> Result<Vec<String>> {
+    let output = tmux_command()
+        .arg("list-clients")
+        .arg("-F")
+        .arg("#{client_name}")
+        .arg("-t")
+        .arg(pane_target(session))
+        .output()
+        .map_err(tmux_invoke_error)?;
+    if !output.status.success() {
+        return Err(command_error("list-clients", &output));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(str::to_string)
+        .collect())
+}
+
+/// A client attached to a session.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ClientInfo {
+    /// Name for `-c`/`-t`, usually the same as the tty.
+    pub name: String,
+    pub tty: String,
+    pub pid: Option<u32>,
+    /// Unix time of the client's last input.
+    pub activity: Option<u64>,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// What `list_clients` reads per client, in [`parse_client`] order.
+const CLIENT_FIELDS: [&str; 6] = [
+    "client_name",
+    "client_tty",
+    "client_pid",
+    "client_activity",
+    "client_width",
+    "client_height",
+];
+
+/// The clients attached to `session`.
+pub fn list_clients(session: &str) -> Result<Vec<ClientInfo>> {
+    let output = tmux_command()
+        .arg("list-clients")
+        .arg("-F")
+        .arg(tmux_format::format(&CLIENT_FIELDS))
+        .arg("-t")
+        .arg(session_target(session))
+        .output()
+        .map_err(tmux_invoke_error)?;
+    if !output.status.success() {
+        return Err(command_error("list-clients", &output));
+    }
+    Ok(tmux_format::parse(&output.stdout, CLIENT_FIELDS.len())?
+        .iter()
+        .map(|record| parse_client(record))
+        .collect())
+}
+
+fn parse_client<S: AsRef<str>>(fields: &[S]) -> ClientInfo {
+    ClientInfo {
+        name: field(fields, 0).to_string(),
+        tty: field(fields, 1).to_string(),
+        pid: field(fields, 2).parse().ok(),
+        activity: field(fields, 3).parse().ok(),
+        width: field(fields, 4).parse().unwrap_or(0),
+        height: field(fields, 5).parse().unwrap_or(0),
+    }
+}
+
+/// The session `client` shows and when it last had input (Unix seconds), or
+/// `None` once the client is gone.
+pub fn client_activity(client: &str) -> Result<Option<(String, u64)>> {
+    let output = tmux_command()
+        .arg("display-message")
+        .arg("-p")
+        .arg("-c")
+        .arg(client)
+        .arg("#{client_session}\t#{client_activity}")
+        .output()
+        .map_err(tmux_invoke_error)?;
+    if !output.status.success() {
+        return Ok(None);
+    }
+    let raw = String::from_utf8_lossy(&output.stdout);
+    let Some((session, activity)) = raw.trim_end().split_once('\t') else {
+        return Ok(None);
+    };
+    Ok(activity
+        .parse()
+        .ok()
+        .map(|activity| (session.to_string(), activity)))
+}
+
+pub fn detach_client(client: &str) -> Result<()> {
+    let output = tmux_command()
+        .arg("detach-client")
+        .arg("-t")
+        .arg(client)
+        .output()
+        .map_err(tmux_invoke_error)?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(command_error("detach-client", &output))
+    }
+}
+
+/// The client showing the pane this process runs in (`$TMUX_PANE`), the most
+/// recently active one if several are; `None` outside tmux.
+pub fn current_client() -> Result<Option<String>> {
+    let Some(pane) = std::env::var("TMUX_PANE")
+        .ok()
+        .filter(|pane| !pane.is_empty())
+    else {
+        return Ok(None);
+    };
+    let output = tmux_command()
+        .arg("display-message")
+        .arg("-p")
+        .arg("-t")
+        .arg(&pane)
+        .arg("#{session_name}")
+        .output()
+        .map_err(tmux_invoke_error)?;
+    if !output.status.success() {
+        return Err(command_error("display-message", &output));
+    }
+    let session = String::from_utf8_lossy(&output.stdout).trim().to_string();
+
+    let output = tmux_command()
+        .arg("list-clients")
+        .arg("-F")
+        .arg("#{client_activity}\t#{client_name}")
+        .arg("-t")
+        .arg(pane_target(&session))
+        .output()
+        .map_err(tmux_invoke_error)?;
+    if !output.status.success() {
+        return Err(command_error("list-clients", &output));
+    }
+    Ok(most_active_client(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// The client name on the `ACTIVITY\tNAME` line with the latest activity.
+fn most_active_client(raw: &str) -> Option<String> {
+    raw.lines()
+        .filter_map(|line| {
+            let (activity, name) = line.split_once('\t')?;
+            Some((activity.parse::<u64>().unwrap_or(0), name))
+        })
+        .max_by_key(|(activity, _)| *activity)
+        .map(|(_, name)| name.to_string())
+}
+
+/// The session `client` is currently showing.
+pub fn client_session(client: &str) -> Result<String> {
+    let output = tmux_command()
+        .arg("display-message")
+        .arg("-p")
+        .arg("-c")
+        .arg(client)
+        .arg("#{client_session}")
+        .output()
+        .map_err(tmux_invoke_error)?;
+    if !output.status.success() {
+        return Err(command_error("display-message", &output));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// One entry of a [`display_menu`]: label, shortcut key, and tmux command.
+pub struct MenuItem {
+    pub label: String,
+    pub key: String,
+    pub command: String,
+}
+
+/// Pop up a menu on `client`, centered. Labels are shown literally.
+pub fn display_menu(client: &str, title: &str, items: &[MenuItem]) -> Result<()> {
+    let mut cmd = tmux_command();
+    cmd.arg("display-menu")
+        .arg("-c")
+        .arg(client)
+        .arg("-T")
+        .arg(format!("#[align=centre]{}", title.replace('#', "##")))
+        .arg("-x")
+        .arg("C")
+        .arg("-y")
+        .arg("C");
+    for item in items {
+        cmd.arg(item.label.replace('#', "##"))
+            .arg(&item.key)
+            .arg(&item.command);
+    }
+    let output = cmd.output().map_err(tmux_invoke_error)?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(command_error("display-menu", &output))
+    }
+}
+
+/// A tmux `run-shell -b` command running `args` in the background; the shell
+/// command is double-quoted for tmux's own parser.
+pub fn run_shell_command(args: &[&str]) -> String {
+    let shell = shell_words::join(args);
+    let escaped: String = shell
+        .chars()
+        .flat_map(|c| match c {
+            '"' | '\\' | '$' => vec!['\\', c],
+            _ => vec![c],
+        })
+        .collect();
+    format!("run-shell -b \"{escaped}\"")
+}
+
+pub fn detach_clients(session: &str) -> Result<()> {
+    let status = tmux_command()
+        .arg("detach-client")
+        .arg("-s")
+        .arg(session_target(session))
+        .status()
+        .map_err(tmux_invoke_error)?;
+
+    if status.success() {
+        Ok(())
+    } else {
+        tmux_failure(format!("tmux detach-client exited with status {status}"))
+    }
+}
+
+pub fn pipe_pane(session: &str, shell_command: &str) -> Result<()> {
+    // tmux runs the command through strftime and its format expansion, which
+    // would mangle the `%XX` of encoded session names in log paths.
+    let shell_command = shell_command.replace('%', "%%").replace('#', "##");
+    let status = tmux_command()
+        .arg("pipe-pane")
+        .arg("-o")
+        .arg("-t")
+        .arg(pane_target(session))
+        .arg(shell_command)
+        .status()
+        .map_err(tmux_invoke_error)?;
+
+    if status.success() {
+        Ok(())
+    } else {
+        tmux_failure(format!("tmux pipe-pane exited with status {status}"))
+    }
+}
+
+/// Type `text` literally into `pane` (a target from [`pane`] or
+/// [`agent_pane`]), optionally followed by Enter.
+pub fn send_keys(pane: &str, text: &str, enter: bool) -> Result<()> {
+    if !text.is_empty() {
+        let output = tmux_command()
+            .arg("send-keys")
+            .arg("-t")
+            .arg(pane)
+            .arg("-l")
+            .arg("--")
+            .arg(text)
+            .output()
+            .map_err(tmux_invoke_error)?;
+        if !output.status.success() {
+            return Err(command_error("send-keys", &output));
+        }
+    }
+    if enter {
+        press_enter(pane)?;
+    }
+    Ok(())
+}
+
+/// Paste `text` into `pane` through a tmux buffer, using bracketed paste when
+/// the application supports it so embedded newlines do not submit early.
+pub fn paste_text(pane: &str, text: &str, enter: bool) -> Result<()> {
+    let buffer = format!("amux-{}", std::process::id());
+    let mut child = tmux_command()
+        .arg("load-buffer")
+        .arg("-b")
+        .arg(&buffer)
+        .arg("-")
+        .stdin(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(tmux_invoke_error)?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(text.as_bytes())
+            .map_err(|err| with_context(err, "failed to write to tmux load-buffer"))?;
+    }
+    let output = child
+        .wait_with_output()
+        .map_err(|err| with_context(err, "failed to wait for tmux load-buffer"))?;
+    if !output.status.success() {
+        return Err(command_error("load-buffer", &output));
+    }
+
+    let output = tmux_command()
+        .arg("paste-buffer")
+        .arg("-p")
+        .arg("-d")
+        .arg("-b")
+        .arg(&buffer)
+        .arg("-t")
+        .arg(pane)
+        .output()
+        .map_err(tmux_invoke_error)?;
+    if !output.status.success() {
+        return Err(command_error("paste-buffer", &output));
+    }
+
+    if enter {
+        press_enter(pane)?;
+    }
+    Ok(())
+}
+
+pub fn press_enter(pane: &str) -> Result<()> {
+    let output = tmux_command()
+        .arg("send-keys")
+        .arg("-t")
+        .arg(pane)
+        .arg("Enter")
+        .output()
+        .map_err(tmux_invoke_error)?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(command_error("send-keys", &output))
+    }
+}
+
+/// Press the keys named in `keys` (tmux key names such as `C-c` or `Escape`)
+/// in `pane`.
+pub fn send_key_names(pane: &str, keys: &[String]) -> Result<()> {
+    let output = tmux_command()
+        .arg("send-keys")
+        .arg("-t")
+        .arg(pane)
+        .args(keys)
+        .output()
+        .map_err(tmux_invoke_error)?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(command_error("send-keys", &output))
+    }
+}
+
+/// Whether keys typed into a pane reach its program's normal screen.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct PaneState {
+    /// The tmux mode the pane is in, such as `copy-mode`.
+    pub mode: Option<String>,
+    /// The program switched to the alternate screen (a pager, editor, or TUI).
+    pub alternate_screen: bool,
+}
+
+pub fn pane_state(pane: &str) -> Result<PaneState> {
+    let output = tmux_command()
+        .arg("display-message")
+        .arg("-p")
+        .arg("-t")
+        .arg(pane)
+        .arg("#{?pane_in_mode,#{pane_mode},}\t#{alternate_on}")
+        .output()
+        .map_err(tmux_invoke_error)?;
+    if !output.status.success() {
+        return Err(command_error("display-message", &output));
+    }
+    Ok(parse_pane_state(&String::from_utf8_lossy(&output.stdout)))
+}
+
+fn parse_pane_state(raw: &str) -> PaneState {
+    let (mode, alternate) = raw
+        .trim_end()
+        .split_once('\t')
+        .unwrap_or((raw.trim_end(), ""));
+    PaneState {
+        mode: (!mode.is_empty()).then(|| mode.to_string()),
+        alternate_screen: alternate == "1",
+    }
+}
+
+/// Leave copy mode (or whichever mode `pane` is in).
+pub fn cancel_mode(pane: &str) -> Result<()> {
+    let output = tmux_command()
+        .arg("send-keys")
+        .arg("-X")
+        .arg("-t")
+        .arg(pane)
+        .arg("cancel")
+        .output()
+        .map_err(tmux_invoke_error)?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(command_error("send-keys", &output))
+    }
+}
+
+/// Capture `pane`'s text; `history` adds that many scrollback lines above the visible screen.
+pub fn capture_pane(pane: &str, history: Option<usize>) -> Result<String> {
+    capture(pane, history, false)
+}
+
+/// [`capture_pane`] keeping the color and attribute escape sequences.
+pub fn capture_pane_with_escapes(pane: &str, history: Option<usize>) -> Result<String> {
+    capture(pane, history, true)
+}
+
+fn capture(pane: &str, history: Option<usize>, escapes: bool) -> Result<String> {
+    let mut cmd = tmux_command();
+    cmd.arg("capture-pane")
+        .arg("-p")
+        .arg("-J")
+        .arg("-t")
+        .arg(pane);
+    if escapes {
+        cmd.arg("-e");
+    }
+    if let Some(lines) = history {
+        cmd.arg("-S").arg(format!("-{lines}"));
+    }
+    let output = cmd.output().map_err(tmux_invoke_error)?;
+
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    } else {
+        tmux_failure(format!(
+            "tmux capture-pane exited with status {}",
+            output.status
+        ))
+    }
+}
+
+/// What `session_info` reads, in [`parse_session_info`] order.
+const INFO_FIELDS: [&str; 11] = [
+    "pane_pid",
+    "pane_current_path",
+    "session_created",
+    "session_activity",
+    "pane_dead",
+    "pane_current_command",
+    "pane_start_command",
+    "pane_dead_status",
+    "session_path",
+    "pane_title",
+    "host",
+];
+
+pub fn session_info(session: &str) -> Result<SessionInfo> {
+    target_info(&pane_target(session), &format!("session '{session}'"))
+}
+
+/// Like [`session_info`], but for `pane` (a `%id` from [`agent_pane`]) rather
+/// than whichever pane of the session has focus.
+pub fn pane_info(pane: &str) -> Result<SessionInfo> {
+    target_info(pane, &format!("pane '{pane}'"))
+}
+
+fn target_info(target: &str, what: &str) -> Result<SessionInfo> {
+    let output = tmux_command()
+        .arg("display-message")
+        .arg("-p")
+        .arg("-t")
+        .arg(target)
+        .arg(tmux_format::format(&INFO_FIELDS))
+        .output()
+        .map_err(tmux_invoke_error)?;
+
+    if !output.status.success() {
+        return tmux_failure(format!(
+            "tmux display-message exited with status {}",
+            output.status
+        ));
+    }
+
+    let records = tmux_format::parse(&output.stdout, INFO_FIELDS.len())?;
+    match records.first() {
+        Some(record) => Ok(parse_session_info(record)),
+        None => tmux_failure(format!("tmux printed nothing for {what}")),
+    }
+}
+
+/// Names of the variables tmux holds in the session environment.
+pub fn environment_names(session: &str) -> Result<Vec<String>> {
+    Ok(session_environment(session)?
+        .into_iter()
+        .filter(|(_, value)| value.is_some())
+        .map(|(name, _)| name)
+        .collect())
+}
+
+/// Variables in the session environment; `None` marks a variable removed from
+/// the session so it is not inherited from the global environment.
+pub fn session_environment(session: &str) -> Result<Vec<(String, Option<String>)>> {
+    let output = tmux_command()
+        .arg("show-environment")
+        .arg("-t")
+        .arg(session_target(session))
+        .output()
+        .map_err(tmux_invoke_error)?;
+
+    if !output.status.success() {
+        return tmux_failure(format!(
+            "tmux show-environment exited with status {}",
+            output.status
+        ));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(parse_environment_line)
+        .collect())
+}
+
+/// Lines look like `NAME=value`, or `-NAME` for variables removed from the session.
+fn parse_environment_line(line: &str) -> Option<(String, Option<String>)> {
+    if let Some(name) = line.strip_prefix('-') {
+        return Some((name.to_string(), None));
+    }
+    line.split_once('=')
+        .map(|(name, value)| (name.to_string(), Some(value.to_string())))
+}
+
+pub fn is_pinned(session: &str) -> Result<bool> {
+    Ok(user_option(session, PINNED_OPTION)?.as_deref() == Some("1"))
+}
+
+pub fn note(session: &str) -> Result<Option<String>> {
+    user_option(session, NOTE_OPTION)
+}
+
+/// The recorded origin of the session, as written by `set_origin`.
+pub fn origin(session: &str) -> Result<Option<String>> {
+    user_option(session, ORIGIN_OPTION)
+}
+
+pub fn set_origin(session: &str, origin: &str) -> Result<()> {
+    set_user_option(session, ORIGIN_OPTION, Some(origin))
+}
+
+/// The commit checked out in the session's directory when it started.
+pub fn base(session: &str) -> Result<Option<String>> {
+    user_option(session, BASE_OPTION)
+}
+
+pub fn set_base(session: &str, commit: &str) -> Result<()> {
+    set_user_option(session, BASE_OPTION, Some(commit))
+}
+
+/// The declaration `amux apply` started the session from, if it did.
+pub fn spec(session: &str) -> Result<Option<String>> {
+    user_option(session, SPEC_OPTION)
 }
 
-pub fn session_name(agent: &str, name: Option<&str>) -> String {
-    match name {
-        Some(name) => format!("{SESSION_PREFIX}{agent}--{name}"),
-        None => format!("{SESSION_PREFIX}{agent}"),
+pub fn set_spec(session: &str, spec: &str) -> Result<()> {
+    set_user_option(session, SPEC_OPTION, Some(spec))
+}
+
+/// Idle minutes after which the session's clients are detached, if set.
+pub fn auto_detach_minutes(session: &str) -> Result<Option<u64>> {
+    Ok(user_option(session, AUTO_DETACH_OPTION)?.and_then(|raw| raw.parse().ok()))
+}
+
+pub fn set_auto_detach_minutes(session: &str, minutes: Option<u64>) -> Result<()> {
+    let minutes = minutes.map(|minutes| minutes.to_string());
+    set_user_option(session, AUTO_DETACH_OPTION, minutes.as_deref())
+}
+
+/// The agent and session name `session` was labeled with, falling back to
+/// parsing its name.
+pub fn identify(session: &str) -> Result<Option<(String, Option<String>)>> {
+    match user_option(session, AGENT_OPTION)? {
+        Some(agent) => Ok(Some((agent, user_option(session, NAME_OPTION)?))),
+        None => Ok(parse_session_name(session)),
     }
 }
 
-pub fn list_sessions() -> Result<Vec<SessionDetail>> {
+fn user_option(session: &str, option: &str) -> Result<Option<String>> {
     let output = tmux_command()
-        .arg("list-sessions")
-        .arg("-F")
-        .arg("#S")
-        .output();
-
-    let raw_sessions = match output {
-        Ok(output) => {
-            if output.status.success() {
-                String::from_utf8_lossy(&output.stdout)
-                    .lines()
-                    .map(str::to_owned)
-                    .collect::<Vec<_>>()
-            } else {
-                let stderr = String::from_utf8_lossy(&output.stderr);
-                if stderr.contains("no server running") {
-                    Vec::new()
-                } else {
-                    return bail(format!(
-                        "tmux list-sessions exited with status {}",
-                        output.status
-                    ));
-                }
-            }
-        }
-        Err(err) => return Err(tmux_invoke_error(err)),
-    };
-
-    let mut sessions = Vec::new();
-    for session in raw_sessions {
-        if let Some((agent, name)) = parse_session_name(&session) {
-            let client_count = client_count(&session)?;
-            let pane_command = current_command(&session)?;
-            sessions.push(SessionDetail {
-                session_name: session,
-                agent,
-                name,
-                client_count,
-                pane_command,
-            });
-        }
+        .arg("display-message")
+        .arg("-p")
+        .arg("-t")
+        .arg(pane_target(session))
+        .arg(format!("#{{{option}}}"))
+        .output()
+        .map_err(tmux_invoke_error)?;
+    if !output.status.success() {
+        return Err(command_error("display-message", &output));
     }
+    let value = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    Ok((!value.is_empty()).then_some(value))
+}
+
+pub fn set_pinned(session: &str, pinned: bool) -> Result<()> {
+    set_flag(session, PINNED_OPTION, pinned)
+}
+
+pub fn is_crash_looping(session: &str) -> Result<bool> {
+    Ok(user_option(session, CRASH_LOOP_OPTION)?.as_deref() == Some("1"))
+}
+
+pub fn set_crash_looping(session: &str, crash_looping: bool) -> Result<()> {
+    set_flag(session, CRASH_LOOP_OPTION, crash_looping)
+}
+
+/// Record which agent and session name `session` belongs to.
+pub fn label_session(session: &str, agent: &str, name: Option<&str>) -> Result<()> {
+    set_user_option(session, AGENT_OPTION, Some(agent))?;
+    set_user_option(session, NAME_OPTION, name)
+}
+
+/// The raw `amux dnd` setting of the session, see [`crate::dnd`].
+pub fn dnd(session: &str) -> Result<Option<String>> {
+    user_option(session, DND_OPTION)
+}
+
+pub fn set_dnd(session: &str, value: Option<&str>) -> Result<()> {
+    set_user_option(session, DND_OPTION, value)
+}
+
+/// Set the session's note, or clear it with `None`. Newlines become spaces so
+/// the note stays on one line in listings.
+pub fn set_note(session: &str, note: Option<&str>) -> Result<()> {
+    let note = note.map(|note| note.split_whitespace().collect::<Vec<_>>().join(" "));
+    set_user_option(session, NOTE_OPTION, note.as_deref())
+}
 
-    Ok(sessions)
+fn set_flag(session: &str, option: &str, on: bool) -> Result<()> {
+    set_user_option(session, option, on.then_some("1"))
 }
 
-pub fn new_session(session: &str, command_tokens: &[String]) -> Result<()> {
+fn set_user_option(session: &str, option: &str, value: Option<&str>) -> Result<()> {
     let mut cmd = tmux_command();
-    cmd.arg("new-session")
-        .arg("-d")
-        .arg("-s")
-        .arg(session)
-        .arg("--")
-        .args(command_tokens);
-    let status = cmd.status().map_err(tmux_invoke_error)?;
-    if status.success() {
+    cmd.arg("set-option").arg("-t").arg(pane_target(session));
+    match value {
+        Some(value) => cmd.arg(option).arg(value),
+        None => cmd.arg("-u").arg(option),
+    };
+    let output = cmd.output().map_err(tmux_invoke_error)?;
+    if output.status.success() {
         Ok(())
     } else {
-        bail(format!("tmux new-session exited with status {status}"))
+        Err(command_error("set-option", &output))
     }
 }
 
-pub fn kill_session(session: &str) -> Result<()> {
-    let status = tmux_command()
-        .arg("kill-session")
+/// Remember the session's active pane as the one running the agent, so later
+/// commands still find it after the session is split into more panes.
+pub fn record_agent_pane(session: &str) -> Result<()> {
+    let output = tmux_command()
+        .arg("display-message")
+        .arg("-p")
         .arg("-t")
-        .arg(session)
-        .status()
+        .arg(pane_target(session))
+        .arg("#{pane_id}")
+        .output()
         .map_err(tmux_invoke_error)?;
-    if status.success() {
+    if !output.status.success() {
+        return Err(command_error("display-message", &output));
+    }
+    let id = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    let output = tmux_command()
+        .arg("set-option")
+        .arg("-t")
+        .arg(pane_target(session))
+        .arg(AGENT_PANE_OPTION)
+        .arg(&id)
+        .output()
+        .map_err(tmux_invoke_error)?;
+    if output.status.success() {
         Ok(())
     } else {
-        bail(format!("tmux kill-session exited with status {status}"))
+        Err(command_error("set-option", &output))
     }
 }
 
-pub fn has_session(session: &str) -> Result<bool> {
-    let status = tmux_command()
-        .arg("has-session")
+/// What `list_panes` reads per pane, in [`parse_pane_line`] order.
+const PANE_FIELDS: [&str; 8] = [
+    "pane_id",
+    "window_index",
+    "pane_index",
+    "pane_active",
+    "window_active",
+    AGENT_PANE_OPTION,
+    "window_name",
+    "pane_current_command",
+];
+
+/// Every pane in every window of `session`.
+pub fn list_panes(session: &str) -> Result<Vec<PaneInfo>> {
+    let output = tmux_command()
+        .arg("list-panes")
+        .arg("-s")
         .arg("-t")
-        .arg(session)
-        .stdout(Stdio::null())
-        .stderr(Stdio::null())
-        .status()
+        .arg(session_target(session))
+        .arg("-F")
+        .arg(tmux_format::format(&PANE_FIELDS))
+        .output()
         .map_err(tmux_invoke_error)?;
-    Ok(status.success())
+    if !output.status.success() {
+        return Err(command_error("list-panes", &output));
+    }
+    Ok(tmux_format::parse(&output.stdout, PANE_FIELDS.len())?
+        .iter()
+        .filter_map(|record| parse_pane_line(record))
+        .collect())
 }
 
-pub fn client_count(session: &str) -> Result<usize> {
+/// Target for the pane running the agent; sessions started before amux
+/// tracked it, or whose agent pane was closed, fall back to the active pane.
+pub fn agent_pane(session: &str) -> Result<String> {
+    Ok(list_panes(session)?
+        .into_iter()
+        .find(|pane| pane.agent)
+        .map_or_else(|| pane_target(session), |pane| pane.id))
+}
+
+/// Target for a pane of `session` picked by window (index or name) and pane
+/// (index within the window, or a `%id`). Either one left out defaults to the
+/// agent's: the agent's window, or the selected pane of the chosen window.
+pub fn pane(session: &str, window: Option<&str>, pane: Option<&str>) -> Result<String> {
+    if window.is_none() && pane.is_none() {
+        return agent_pane(session);
+    }
+    let panes = list_panes(session)?;
+    if let Some(found) = select_pane(&panes, window, pane) {
+        return Ok(found.id.clone());
+    }
+    let wanted = match (window, pane) {
+        (Some(window), Some(pane)) => format!("pane '{pane}' in window '{window}'"),
+        (Some(window), None) => format!("window '{window}'"),
+        (None, pane) => format!("pane '{}'", pane.unwrap_or_default()),
+    };
+    Err(fail_with(
+        ErrorKind::InvalidInput,
+        format!("no {wanted} in session '{session}'"),
+        Some("run `amux panes` to list the session's panes".into()),
+    ))
+}
+
+fn select_pane<'a>(
+    panes: &'a [PaneInfo],
+    window: Option<&str>,
+    pane: Option<&str>,
+) -> Option<&'a PaneInfo> {
+    if let Some(id) = pane.filter(|pane| pane.starts_with('%')) {
+        return panes.iter().find(|candidate| candidate.id == id);
+    }
+    let window = match window {
+        Some(window) => {
+            panes
+                .iter()
+                .find(|candidate| {
+                    candidate.window.to_string() == window || candidate.window_name == window
+                })?
+                .window
+        }
+        None => {
+            panes
+                .iter()
+                .find(|candidate| candidate.agent)
+                .or_else(|| panes.iter().find(|candidate| candidate.active))?
+                .window
+        }
+    };
+    panes.iter().find(|candidate| {
+        candidate.window == window
+            && match pane {
+                Some(index) => candidate.index.to_string() == index,
+                None => candidate.current,
+            }
+    })
+}
+
+/// Rename the session's window and/or color its entry in the tmux status line.
+pub fn style_window(session: &str, name: Option<&str>, color: Option<&str>) -> Result<()> {
+    if let Some(name) = name {
+        let output = tmux_command()
+            .arg("rename-window")
+            .arg("-t")
+            .arg(pane_target(session))
+            .arg(name)
+            .output()
+            .map_err(tmux_invoke_error)?;
+        if !output.status.success() {
+            return Err(command_error("rename-window", &output));
+        }
+    }
+    if let Some(color) = color {
+        for (option, style) in [
+            ("window-status-style", format!("fg={color}")),
+            ("window-status-current-style", format!("fg={color},bold")),
+        ] {
+            let output = tmux_command()
+                .arg("set-option")
+                .arg("-w")
+                .arg("-t")
+                .arg(pane_target(session))
+                .arg(option)
+                .arg(style)
+                .output()
+                .map_err(tmux_invoke_error)?;
+            if !output.status.success() {
+                return Err(command_error("set-option", &output));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Keep the pane around when its program exits and run `command` (a tmux
+/// command) at that point, so the exit can be observed before the session goes.
+pub fn on_pane_died(session: &str, command: &str) -> Result<()> {
     let output = tmux_command()
-        .arg("list-clients")
+        .arg("set-option")
+        .arg("-w")
         .arg("-t")
-        .arg(session)
+        .arg(pane_target(session))
+        .arg("remain-on-exit")
+        .arg("on")
         .output()
         .map_err(tmux_invoke_error)?;
+    if !output.status.success() {
+        return Err(command_error("set-option", &output));
+    }
+
+    set_hook(session, "pane-died", Some(command))
+}
 
+/// Set the session's `hook` to `command`, or remove it with `None`.
+pub fn set_hook(session: &str, hook: &str, command: Option<&str>) -> Result<()> {
+    let mut cmd = tmux_command();
+    cmd.arg("set-hook");
+    if command.is_none() {
+        cmd.arg("-u");
+    }
+    cmd.arg("-t").arg(pane_target(session)).arg(hook);
+    if let Some(command) = command {
+        cmd.arg(command);
+    }
+    let output = cmd.output().map_err(tmux_invoke_error)?;
     if output.status.success() {
-        let count = String::from_utf8_lossy(&output.stdout).lines().count();
-        Ok(count)
-    } else if output.stderr.is_empty() {
-        Ok(0)
+        Ok(())
     } else {
-        bail(format!(
-            "tmux list-clients exited with status {}",
-            output.status
-        ))
+        Err(command_error("set-hook", &output))
     }
 }
 
-pub fn attach_session(session: &str) -> Result<()> {
-    let status = tmux_command()
-        .arg("attach-session")
-        .arg("-t")
-        .arg(session)
-        .status()
+/// Start a detached session `session` showing each of `targets` in its own
+/// pane, through a client nested in that pane, with `synchronize-panes` on so
+/// whatever is typed reaches every target. The session ends when its last
+/// client detaches; the targets are left as they were.
+pub fn new_broadcast_session(session: &str, targets: &[String]) -> Result<()> {
+    let output = tmux_command()
+        .arg("display-message")
+        .arg("-p")
+        .arg("#{socket_path}")
+        .output()
         .map_err(tmux_invoke_error)?;
+    if !output.status.success() {
+        return Err(command_error("display-message", &output));
+    }
+    let socket = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    let client = |target: &str| {
+        [
+            "env",
+            "TMUX=",
+            "tmux",
+            "-S",
+            &socket,
+            "attach-session",
+            "-t",
+        ]
+        .map(str::to_string)
+        .into_iter()
+        .chain([session_target(target)])
+        .collect::<Vec<String>>()
+    };
 
-    if status.success() {
+    let Some((first, rest)) = targets.split_first() else {
+        return tmux_failure("nothing to broadcast to".to_string());
+    };
+    new_session(session, &client(first), &[], None)?;
+    let mut steps: Vec<Vec<String>> = rest
+        .iter()
+        .map(|target| {
+            let mut step = vec![
+                "split-window".to_string(),
+                "-t".into(),
+                pane_target(session),
+            ];
+            step.push("--".into());
+            step.extend(client(target));
+            step
+        })
+        .collect();
+    steps.push(vec![
+        "select-layout".into(),
+        "-t".into(),
+        pane_target(session),
+        "tiled".into(),
+    ]);
+    steps.push(vec![
+        "set-option".into(),
+        "-w".into(),
+        "-t".into(),
+        pane_target(session),
+        "synchronize-panes".into(),
+        "on".into(),
+    ]);
+    // The nested clients have no exit worth keeping.
+    steps.push(vec![
+        "set-option".into(),
+        "-w".into(),
+        "-t".into(),
+        pane_target(session),
+        "remain-on-exit".into(),
+        "off".into(),
+    ]);
+    // `destroy-unattached` would end the session before anyone attaches.
+    steps.push(vec![
+        "set-hook".into(),
+        "-t".into(),
+        pane_target(session),
+        "client-detached".into(),
+        format!("kill-session -t {}", session_target(session)),
+    ]);
+    for step in steps {
+        let output = tmux_command()
+            .args(&step)
+            .output()
+            .map_err(tmux_invoke_error)?;
+        if !output.status.success() {
+            let _ = kill_session(session);
+            return Err(command_error(&step[0], &output));
+        }
+    }
+    Ok(())
+}
+
+/// Run `args` as a background job of the tmux server, so it outlives amux.
+pub fn run_in_background(args: &[&str]) -> Result<()> {
+    let output = tmux_command()
+        .arg("run-shell")
+        .arg("-b")
+        .arg(shell_words::join(args))
+        .output()
+        .map_err(tmux_invoke_error)?;
+    if output.status.success() {
         Ok(())
     } else {
-        bail(format!("tmux attach-session exited with status {status}"))
+        Err(command_error("run-shell", &output))
     }
 }
 
-pub fn detach_clients(session: &str) -> Result<()> {
-    let status = tmux_command()
-        .arg("detach-client")
-        .arg("-s")
-        .arg(session)
-        .status()
+/// Restart the program of `pane` after it has exited.
+pub fn respawn_pane(pane: &str) -> Result<()> {
+    let output = tmux_command()
+        .arg("respawn-pane")
+        .arg("-t")
+        .arg(pane)
+        .output()
         .map_err(tmux_invoke_error)?;
-
-    if status.success() {
+    if output.status.success() {
         Ok(())
     } else {
-        bail(format!("tmux detach-client exited with status {status}"))
+        Err(command_error("respawn-pane", &output))
     }
 }
 
-fn current_command(session: &str) -> Result<Option<String>> {
+/// Whether `session` was started (or adopted) by amux, judged by the
+/// `AMUX_SESSION` marker in its environment.
+pub fn is_amux_session(session: &str) -> Result<bool> {
+    if user_option(session, AGENT_OPTION)?.is_some() {
+        return Ok(true);
+    }
     let output = tmux_command()
-        .arg("display-message")
-        .arg("-p")
+        .arg("show-environment")
         .arg("-t")
-        .arg(session)
-        .arg("#{pane_current_command}")
+        .arg(session_target(session))
+        .arg("AMUX_SESSION")
         .output()
         .map_err(tmux_invoke_error)?;
+    Ok(output.status.success() && output.stdout.starts_with(b"AMUX_SESSION="))
+}
 
+pub fn set_environment(session: &str, name: &str, value: &str) -> Result<()> {
+    let output = tmux_command()
+        .arg("set-environment")
+        .arg("-t")
+        .arg(session_target(session))
+        .arg(name)
+        .arg(value)
+        .output()
+        .map_err(tmux_invoke_error)?;
     if output.status.success() {
-        let text = String::from_utf8_lossy(&output.stdout).trim().to_string();
-        if text.is_empty() {
-            Ok(None)
-        } else {
-            Ok(Some(text))
+        Ok(())
+    } else {
+        Err(command_error("set-environment", &output))
+    }
+}
+
+/// The tmux server's global environment, or `None` when no server is running.
+pub fn global_environment() -> Result<Option<Vec<(String, String)>>> {
+    let output = tmux_command()
+        .arg("show-environment")
+        .arg("-g")
+        .output()
+        .map_err(tmux_invoke_error)?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if stderr.contains("no server running") || stderr.contains("error connecting to") {
+            return Ok(None);
         }
+        return Err(command_error("show-environment", &output));
+    }
+
+    Ok(Some(
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter_map(|line| line.split_once('='))
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect(),
+    ))
+}
+
+/// The TERM tmux gives new panes, its `default-terminal` option. Without a
+/// running server, one is started for the query so that tmux.conf applies;
+/// it exits again right away because it has no sessions.
+pub fn default_terminal() -> Result<String> {
+    let output = tmux_command()
+        .args([
+            "start-server",
+            ";",
+            "show-options",
+            "-gv",
+            "default-terminal",
+        ])
+        .output()
+        .map_err(tmux_invoke_error)?;
+    if !output.status.success() {
+        return Err(command_error("show-options", &output));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+fn parse_session_info<S: AsRef<str>>(fields: &[S]) -> SessionInfo {
+    let text = |index| {
+        Some(field(fields, index).trim())
+            .filter(|value| !value.is_empty())
+            .map(str::to_owned)
+    };
+    let pane_dead = field(fields, 4) == "1";
+    let start_dir = text(8);
+    // A dead pane has no current path, so fall back to the directory the session started in.
+    let cwd = if pane_dead {
+        start_dir.clone()
     } else {
-        Ok(None)
+        text(1)
+    };
+    // tmux titles a pane with the host name until its program sets a title.
+    let pane_title = text(9).filter(|title| *title != field(fields, 10));
+    SessionInfo {
+        pid: field(fields, 0).trim().parse().ok(),
+        cwd,
+        created: field(fields, 2).trim().parse().ok(),
+        last_activity: field(fields, 3).trim().parse().ok(),
+        pane_dead,
+        pane_command: text(5),
+        start_command: text(6),
+        exit_status: field(fields, 7).trim().parse().ok(),
+        start_dir,
+        pane_title,
+    }
+}
+
+/// Field `index` of a parsed record, empty when tmux printed fewer.
+fn field<S: AsRef<str>>(fields: &[S], index: usize) -> &str {
+    fields.get(index).map_or("", AsRef::as_ref)
+}
+
+/// Target `session` by exact name; a bare `-t name` also matches sessions that
+/// merely start with `name`, so `amux-codex` would resolve to `amux-codex--review`.
+fn session_target(session: &str) -> String {
+    format!("={session}")
+}
+
+/// Target the active pane of exactly `session`.
+fn pane_target(session: &str) -> String {
+    format!("={session}:")
+}
+
+fn parse_pane_line<S: AsRef<str>>(fields: &[S]) -> Option<PaneInfo> {
+    let id = field(fields, 0).to_string();
+    let window = field(fields, 1).parse().ok()?;
+    let index = field(fields, 2).parse().ok()?;
+    let current = field(fields, 3) == "1";
+    let active = current && field(fields, 4) == "1";
+    let agent = field(fields, 5) == id;
+    let window_name = field(fields, 6).to_string();
+    let command = field(fields, 7).to_string();
+    Some(PaneInfo {
+        id,
+        window,
+        window_name,
+        index,
+        current,
+        active,
+        command,
+        agent,
+    })
+}
+
+/// A `list-sessions` record: the session name and the user options amux sets.
+#[derive(Debug, PartialEq, Eq)]
+struct ListLine<'a> {
+    session: &'a str,
+    pinned: bool,
+    crash_looping: bool,
+    clients: usize,
+    command: Option<&'a str>,
+    agent: Option<&'a str>,
+    name: Option<&'a str>,
+    note: Option<&'a str>,
+    cwd: Option<&'a str>,
+}
+
+impl ListLine<'_> {
+    /// The agent and session name, from the labels amux sets. Sessions from
+    /// before amux labeled them are still known by their name.
+    fn identity(&self) -> Option<(String, Option<String>)> {
+        match self.agent {
+            Some(agent) => Some((agent.to_string(), self.name.map(str::to_string))),
+            None => parse_session_name(self.session),
+        }
+    }
+}
+
+fn parse_list_line<S: AsRef<str>>(fields: &[S]) -> ListLine<'_> {
+    let text = |index| Some(field(fields, index)).filter(|value| !value.is_empty());
+    ListLine {
+        session: field(fields, 0),
+        pinned: field(fields, 1) == "1",
+        crash_looping: field(fields, 2) == "1",
+        clients: field(fields, 3).parse().unwrap_or(0),
+        command: text(4),
+        agent: text(5),
+        name: text(6),
+        note: text(7),
+        cwd: text(8),
     }
 }
 
-fn parse_session_name(session: &str) -> Option<(String, Option<String>)> {
+/// Split a session id back into agent and session name. Only a fallback for
+/// unlabeled sessions: it cannot tell `amux-a--b` apart from an agent named
+/// `a--b`.
+pub fn parse_session_name(session: &str) -> Option<(String, Option<String>)> {
     let rest = session.strip_prefix(SESSION_PREFIX)?;
     let mut parts = rest.splitn(2, "--");
     let agent = parts.next()?.to_string();
-    let name = parts.next().map(|s| s.to_string());
+    let name = parts.next().map(decode_name);
     Some((agent, name))
 }
 
+/// Run `op`, retrying with exponential backoff while it fails transiently.
+fn with_retries<T>(mut op: impl FnMut() -> Result<T>) -> Result<T> {
+    let retries = RETRIES.load(Ordering::Relaxed);
+    let mut attempt = 0;
+    loop {
+        match op() {
+            Err(err) if err.is::<TransientError>() && attempt < retries => {
+                thread::sleep(backoff_delay(attempt));
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+fn backoff_delay(attempt: u32) -> Duration {
+    let millis = 200u64.saturating_mul(1 << attempt.min(10));
+    Duration::from_millis(millis.min(2_000))
+}
+
+/// Build an error from a failed tmux invocation, flagging known transient failures.
+fn command_error(subcommand: &str, output: &std::process::Output) -> crate::error::DynError {
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let detail = stderr.trim();
+    let message = if detail.is_empty() {
+        format!("tmux {subcommand} exited with status {}", output.status)
+    } else {
+        format!("tmux {subcommand} failed: {detail}")
+    };
+    if is_transient(detail) {
+        Box::new(TransientError(message))
+    } else {
+        fail_with(ErrorKind::Tmux, message, None)
+    }
+}
+
+fn tmux_failure<T>(message: String) -> Result<T> {
+    Err(fail_with(ErrorKind::Tmux, message, None))
+}
+
+fn is_transient(stderr: &str) -> bool {
+    const MARKERS: &[&str] = &[
+        "server exited unexpectedly",
+        "lost server",
+        "error connecting to",
+        "no server running",
+        "Resource temporarily unavailable",
+        "Connection refused",
+    ];
+    MARKERS.iter().any(|marker| stderr.contains(marker))
+}
+
 fn tmux_command() -> Command {
     let mut cmd = Command::new("tmux");
     // Ensure tmux does not inherit an existing server context
     cmd.env("TMUX", "");
+    if let Some(name) = socket() {
+        cmd.arg("-L").arg(name);
+        // Read only when this starts the server. tmux fails to start on a
+        // missing `-f` file, and without `-f` it would read the user's own
+        // configuration, so an absent amux one means none at all.
+        let conf = conf_file()
+            .ok()
+            .filter(|conf| conf.exists())
+            .unwrap_or_else(|| PathBuf::from("/dev/null"));
+        cmd.arg("-f").arg(conf);
+    }
 
     // Fallback: if TERM is (xterm-)ghostty, use a widely supported
     // TERM for the tmux child process to avoid missing terminfo.
@@ -210,15 +1680,21 @@ fn tmux_command() -> Command {
 fn tmux_invoke_error(err: io::Error) -> crate::error::DynError {
     if err.kind() == io::ErrorKind::NotFound {
         // Provide actionable guidance when tmux is not installed
-        crate::error::fail(
+        fail_with(
+            ErrorKind::TmuxNotFound,
             "tmux not found. Please install tmux and try again.\n\
              - macOS: brew install tmux\n\
              - Debian/Ubuntu: sudo apt-get update && sudo apt-get install tmux\n\
              - Nix: nix-env -iA nixpkgs.tmux\n\
              See: https://github.com/tmux/tmux/wiki/Installing",
+            None,
         )
     } else {
-        with_context(err, "failed to invoke tmux")
+        fail_with(
+            ErrorKind::Tmux,
+            format!("failed to invoke tmux: {err}"),
+            None,
+        )
     }
 }
 
@@ -246,6 +1722,17 @@ mod tests {
         assert_eq!(parsed.1.as_deref(), Some("review"));
     }
 
+    #[test]
+    fn parse_socket_accepts_names_and_the_shared_server() {
+        assert_eq!(
+            parse_socket("amux").expect("socket name"),
+            Some("amux".to_string())
+        );
+        assert_eq!(parse_socket(SHARED_SOCKET).expect("shared server"), None);
+        assert!(parse_socket("").is_err());
+        assert!(parse_socket("/tmp/amux.sock").is_err());
+    }
+
     #[test]
     fn parse_session_name_handles_agent_only() {
         let parsed =
@@ -254,6 +1741,225 @@ mod tests {
         assert!(parsed.1.is_none());
     }
 
+    #[test]
+    fn parse_list_line_reads_flags_and_labels() {
+        assert_eq!(
+            parse_list_line(&[
+                "amux-codex--x",
+                "1",
+                "",
+                "2",
+                "node",
+                "codex",
+                "x",
+                "fix\tlogin",
+                "/src/api"
+            ]),
+            ListLine {
+                session: "amux-codex--x",
+                pinned: true,
+                crash_looping: false,
+                clients: 2,
+                command: Some("node"),
+                agent: Some("codex"),
+                name: Some("x"),
+                note: Some("fix\tlogin"),
+                cwd: Some("/src/api"),
+            }
+        );
+        let bare = parse_list_line(&["amux-codex", "", "1", "0", "", "", "", ""]);
+        assert!(bare.crash_looping && !bare.pinned);
+        assert_eq!((bare.clients, bare.command), (0, None));
+        assert_eq!((bare.agent, bare.name, bare.note), (None, None, None));
+        assert_eq!(bare.cwd, None);
+        assert_eq!(parse_list_line(&["amux-codex"]).session, "amux-codex");
+    }
+
+    #[test]
+    fn parse_pane_state_reads_mode_and_screen() {
+        assert_eq!(parse_pane_state("\t0\n"), PaneState::default());
+        assert_eq!(
+            parse_pane_state("copy-mode\t1\n"),
+            PaneState {
+                mode: Some("copy-mode".into()),
+                alternate_screen: true,
+            }
+        );
+    }
+
+    #[test]
+    fn most_active_client_picks_latest_activity() {
+        let raw = "1700000100\t/dev/pts/1\n1700000300\t/dev/pts/4\n1700000200\t/dev/pts/2\n";
+        assert_eq!(most_active_client(raw).as_deref(), Some("/dev/pts/4"));
+        assert_eq!(most_active_client(""), None);
+    }
+
+    #[test]
+    fn session_names_are_encoded_and_decoded() {
+        let session = session_name("codex", Some("feature/login.v2 #3"));
+        assert_eq!(session, "amux-codex--feature%2Flogin%2Ev2%20%233");
+        assert_eq!(
+            parse_session_name(&session),
+            Some(("codex".to_string(), Some("feature/login.v2 #3".to_string())))
+        );
+        assert_eq!(session_name("codex", Some("pr-1_a")), "amux-codex--pr-1_a");
+        assert_eq!(decode_name("caf%C3%A9%2"), "café%2");
+    }
+
+    #[test]
+    fn list_line_identity_prefers_labels() {
+        let labeled = parse_list_line(&["amux-my--agent", "", "", "0", "", "my--agent", "", ""]);
+        assert_eq!(labeled.identity(), Some(("my--agent".to_string(), None)));
+        let unlabeled = parse_list_line(&["amux-my--agent", "", "", "0", "", "", "", ""]);
+        assert_eq!(
+            unlabeled.identity(),
+            Some(("my".to_string(), Some("agent".to_string())))
+        );
+        assert_eq!(
+            parse_list_line(&["work", "", "", "0", "", "", "", ""]).identity(),
+            None
+        );
+    }
+
+    #[test]
+    fn parse_pane_line_reads_flags() {
+        assert_eq!(
+            parse_pane_line(&["%4", "1", "0", "1", "1", "%3", "logs", "zsh"]),
+            Some(PaneInfo {
+                id: "%4".into(),
+                window: 1,
+                window_name: "logs".into(),
+                index: 0,
+                current: true,
+                active: true,
+                command: "zsh".into(),
+                agent: false,
+            })
+        );
+        let agent = parse_pane_line(&["%3", "0", "0", "1", "0", "%3", "main", "node"])
+            .expect("line should parse");
+        assert!(agent.agent && agent.current && !agent.active);
+    }
+
+    #[test]
+    fn select_pane_defaults_to_the_agent_window() {
+        let panes: Vec<PaneInfo> = [
+            ["%0", "0", "0", "0", "0", "%0", "main", "node"],
+            ["%1", "0", "1", "1", "0", "%0", "main", "zsh"],
+            ["%2", "1", "0", "1", "1", "%0", "logs", "zsh"],
+        ]
+        .iter()
+        .filter_map(|record| parse_pane_line(record))
+        .collect();
+        let id = |window, pane| select_pane(&panes, window, pane).map(|pane| pane.id.as_str());
+        assert_eq!(id(None, Some("0")), Some("%0"));
+        assert_eq!(id(Some("logs"), None), Some("%2"));
+        assert_eq!(id(Some("0"), None), Some("%1"));
+        assert_eq!(id(Some("9"), Some("%2")), Some("%2"));
+        assert_eq!(id(Some("9"), None), None);
+        assert_eq!(id(None, Some("5")), None);
+    }
+
+    #[test]
+    fn parse_session_info_reads_fields() {
+        let output = tmux_format::encode(&[&[
+            "4242",
+            "/work/repo/src",
+            "1700000000",
+            "1700000100",
+            "0",
+            "node",
+            "codex --prompt 'a\tb\nc'",
+            "",
+            "/work/repo",
+            "Fix login",
+            "devbox",
+        ]]);
+        let records =
+            tmux_format::parse(output.as_bytes(), INFO_FIELDS.len()).expect("output should parse");
+        let info = parse_session_info(&records[0]);
+        assert_eq!(info.pid, Some(4242));
+        assert_eq!(info.cwd.as_deref(), Some("/work/repo/src"));
+        assert_eq!(info.created, Some(1_700_000_000));
+        assert_eq!(info.last_activity, Some(1_700_000_100));
+        assert!(!info.pane_dead);
+        assert_eq!(info.pane_command.as_deref(), Some("node"));
+        assert_eq!(
+            info.start_command.as_deref(),
+            Some("codex --prompt 'a\tb\nc'")
+        );
+        assert_eq!(info.exit_status, None);
+        assert_eq!(info.start_dir.as_deref(), Some("/work/repo"));
+        assert_eq!(info.pane_title.as_deref(), Some("Fix login"));
+    }
+
+    #[test]
+    fn parse_session_info_tolerates_missing_fields() {
+        let info = parse_session_info(&[
+            "", "/gone", "", "", "1", "", "", "2", "", "devbox", "devbox",
+        ]);
+        assert!(info.pid.is_none());
+        assert!(info.cwd.is_none());
+        assert!(info.pane_dead);
+        assert_eq!(info.exit_status, Some(2));
+        assert!(info.start_command.is_none());
+        assert!(info.pane_title.is_none());
+    }
+
+    #[test]
+    fn parse_environment_line_reads_set_and_removed_variables() {
+        assert_eq!(
+            parse_environment_line("API_URL=http://x?a=b"),
+            Some(("API_URL".into(), Some("http://x?a=b".into())))
+        );
+        assert_eq!(
+            parse_environment_line("-SSH_AUTH_SOCK"),
+            Some(("SSH_AUTH_SOCK".into(), None))
+        );
+        assert_eq!(parse_environment_line("garbage"), None);
+    }
+
+    #[test]
+    fn is_transient_flags_server_startup_races() {
+        assert!(is_transient("server exited unexpectedly"));
+        assert!(is_transient(
+            "error connecting to /tmp/tmux-0/default (No such file or directory)"
+        ));
+        assert!(!is_transient("duplicate session: amux-codex"));
+    }
+
+    #[test]
+    fn backoff_delay_doubles_up_to_cap() {
+        assert_eq!(backoff_delay(0), Duration::from_millis(200));
+        assert_eq!(backoff_delay(1), Duration::from_millis(400));
+        assert_eq!(backoff_delay(5), Duration::from_millis(2_000));
+    }
+
+    #[test]
+    fn with_retries_stops_on_permanent_errors() {
+        let mut attempts = 0;
+        let result: Result<()> = with_retries(|| {
+            attempts += 1;
+            bail("duplicate session")
+        });
+        assert!(result.is_err());
+        assert_eq!(attempts, 1);
+    }
+
+    #[test]
+    fn with_retries_retries_transient_errors() {
+        let mut attempts = 0;
+        let result = with_retries(|| {
+            attempts += 1;
+            if attempts < 2 {
+                Err(Box::new(TransientError("lost server".into())) as crate::error::DynError)
+            } else {
+                Ok(attempts)
+            }
+        });
+        assert_eq!(result.expect("second attempt should succeed"), 2);
+    }
+
     #[test]
     fn parse_session_name_returns_none_for_unexpected_prefix() {
         assert!(parse_session_name("other-codex").is_none());
@@ -0,0 +1,96 @@
+//! Reading tmux `-F` output whose fields can hold anything. Notes, pane
+//! titles, and start commands may contain tabs, newlines, or any other
+//! separator amux could pick, so instead of splitting on one, every field is
+//! printed as `<byte length>:<value>` with tmux's `n:` modifier and read back
+//! by its length.
+
+use std::borrow::Cow;
+
+use crate::error::{fail_with, ErrorKind, Result};
+
+/// A `-F` format printing the tmux format variables `fields` (`session_name`,
+/// `@amux_note`, ...) as one record per line.
+pub fn format(fields: &[&str]) -> String {
+    fields
+        .iter()
+        .map(|field| format!("#{{n:{field}}}:#{{{field}}}"))
+        .collect()
+}
+
+/// Split output printed with a [`format`] of `fields` variables into records.
+/// Values borrow from `output` unless they are not valid UTF-8.
+pub fn parse(output: &[u8], fields: usize) -> Result<Vec<Vec<Cow<'_, str>>>> {
+    let mut records = Vec::new();
+    let mut rest = output;
+    while !rest.is_empty() {
+        let mut record = Vec::with_capacity(fields);
+        for _ in 0..fields {
+            let (value, after) = field(rest).ok_or_else(malformed)?;
+            record.push(String::from_utf8_lossy(value));
+            rest = after;
+        }
+        rest = rest.strip_prefix(b"\n").ok_or_else(malformed)?;
+        records.push(record);
+    }
+    Ok(records)
+}
+
+/// The value at the start of `input` and what follows it.
+fn field(input: &[u8]) -> Option<(&[u8], &[u8])> {
+    let colon = input.iter().position(|&byte| byte == b':')?;
+    let len: usize = std::str::from_utf8(&input[..colon]).ok()?.parse().ok()?;
+    let value = input.get(colon + 1..colon + 1 + len)?;
+    Some((value, &input[colon + 1 + len..]))
+}
+
+fn malformed() -> crate::error::DynError {
+    fail_with(
+        ErrorKind::Tmux,
+        "could not read tmux's output",
+        Some("amux needs tmux 3.0 or newer".into()),
+    )
+}
+
+/// Print `records` the way tmux does for a [`format`], for tests.
+#[cfg(test)]
+pub fn encode(records: &[&[&str]]) -> String {
+    records
+        .iter()
+        .map(|record| {
+            let fields: String = record
+                .iter()
+                .map(|value| format!("{}:{value}", value.len()))
+                .collect();
+            format!("{fields}\n")
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_prefixes_each_field_with_its_length() {
+        assert_eq!(
+            format(&["session_name", "@amux_note"]),
+            "#{n:session_name}:#{session_name}#{n:@amux_note}:#{@amux_note}"
+        );
+    }
+
+    #[test]
+    fn parse_keeps_separators_inside_values() {
+        let output = encode(&[
+            &["amux-codex", "fix\tlogin\nthen: \"tests\""],
+            &["café", ""],
+        ]);
+        let records = parse(output.as_bytes(), 2).expect("output should parse");
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0][1], "fix\tlogin\nthen: \"tests\"");
+        assert_eq!(records[1][0], "café");
+        assert_eq!(records[1][1], "");
+        assert!(parse(b"10:short\n", 1).is_err());
+        assert!(parse(b"1:a1:b\n", 1).is_err());
+        assert!(parse(b"", 3).expect("no sessions").is_empty());
+    }
+}
@@ -0,0 +1,244 @@
+//! Key bindings for switching between agent sessions from inside tmux:
+//! a popup menu of amux sessions and a key to cycle to the next one.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::error::{with_context, Result};
+use crate::tmux::{self, MenuItem, SessionDetail};
+
+const BEGIN_MARKER: &str = "# >>> amux keys >>>";
+const END_MARKER: &str = "# <<< amux keys <<<";
+
+/// Shortcut keys for the menu entries, in session order.
+const MENU_KEYS: &str = "123456789abcdefghijklmnopqrstuvwxyz";
+
+/// The keys bound after the tmux prefix.
+pub struct Keys<'a> {
+    pub picker: &'a str,
+    pub next: &'a str,
+    pub prev: &'a str,
+}
+
+/// The tmux.conf block binding `keys` to `exe`'s menu and cycling commands,
+/// between markers so a later install can replace it.
+pub fn snippet(exe: &str, keys: &Keys) -> String {
+    let bind = |key: &str, command: &[&str]| {
+        let mut args = vec![exe];
+        args.extend_from_slice(command);
+        args.extend_from_slice(&["--client", "#{client_name}"]);
+        format!(
+            "bind-key {} {}",
+            shell_words::quote(key),
+            tmux::run_shell_command(&args)
+        )
+    };
+    [
+        BEGIN_MARKER.to_string(),
+        bind(keys.picker, &["tmux-keys", "menu"]),
+        bind(keys.next, &["next"]),
+        bind(keys.prev, &["prev"]),
+        END_MARKER.to_string(),
+    ]
+    .join("\n")
+        + "\n"
+}
+
+/// Write `snippet` into the tmux configuration at `conf`, replacing the block
+/// from an earlier install. Returns whether an earlier block was replaced.
+pub fn install(conf: &Path, snippet: &str) -> Result<bool> {
+    let existing = match fs::read_to_string(conf) {
+        Ok(existing) => existing,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(err) => {
+            return Err(with_context(
+                err,
+                format!("failed to read {}", conf.display()),
+            ))
+        }
+    };
+    let (updated, replaced) = splice(&existing, snippet);
+    if let Some(dir) = conf.parent() {
+        fs::create_dir_all(dir)
+            .map_err(|err| with_context(err, format!("failed to create {}", dir.display())))?;
+    }
+    let tmp = conf.with_extension("amux-tmp");
+    fs::write(&tmp, updated)
+        .and_then(|()| fs::rename(&tmp, conf))
+        .map_err(|err| with_context(err, format!("failed to write {}", conf.display())))?;
+    Ok(replaced)
+}
+
+/// `config` with its amux block replaced by `snippet`, or with `snippet`
+/// appended when there is none.
+fn splice(config: &str, snippet: &str) -> (String, bool) {
+    if let Some(start) = config.find(BEGIN_MARKER) {
+        if let Some(end) = config[start..].find(END_MARKER) {
+            let mut end = start + end + END_MARKER.len();
+            if config[end..].starts_with('\n') {
+                end += 1;
+            }
+            let updated = format!("{}{snippet}{}", &config[..start], &config[end..]);
+            return (updated, true);
+        }
+    }
+    let separator = match config {
+        "" => "",
+        _ if config.ends_with('\n') => "\n",
+        _ => "\n\n",
+    };
+    (format!("{config}{separator}{snippet}"), false)
+}
+
+/// Sort sessions into the order the menu and cycling use: by agent, then the
+/// unnamed session first, then by name.
+pub fn order(sessions: &mut [SessionDetail]) {
+    sessions.sort_by(|a, b| (&a.agent, &a.name).cmp(&(&b.agent, &b.name)));
+}
+
+/// The session after `current` in `sessions` (before it when `forward` is
+/// false), wrapping around. From a session that is not in the list, cycling
+/// starts at the first (or last) one.
+pub fn step<'a>(
+    sessions: &'a [SessionDetail],
+    current: &str,
+    forward: bool,
+) -> Option<&'a SessionDetail> {
+    let count = sessions.len();
+    if count == 0 {
+        return None;
+    }
+    let position = sessions
+        .iter()
+        .position(|session| session.session_name == current);
+    let index = match (position, forward) {
+        (Some(index), true) => (index + 1) % count,
+        (Some(index), false) => (index + count - 1) % count,
+        (None, true) => 0,
+        (None, false) => count - 1,
+    };
+    sessions.get(index)
+}
+
+/// Menu entries switching `client` to each of `sessions`; the session it is
+/// showing is marked with `*`.
+pub fn menu_items(sessions: &[SessionDetail], client: &str, current: &str) -> Vec<MenuItem> {
+    sessions
+        .iter()
+        .zip(MENU_KEYS.chars())
+        .map(|(session, key)| {
+            let mut label = session.agent.clone();
+            if let Some(name) = &session.name {
+                label.push('/');
+                label.push_str(name);
+            }
+            if let Some(note) = &session.note {
+                label.push_str(" - ");
+                label.push_str(note);
+            }
+            if session.session_name == current {
+                label.push_str(" *");
+            }
+            MenuItem {
+                label,
+                key: key.to_string(),
+                command: format!(
+                    "switch-client -c {} -t {}",
+                    shell_words::quote(client),
+                    // The trailing `:` keeps tmux from reading an encoded `%XX`
+                    // in the name as a pane id.
+                    shell_words::quote(&format!("={}:", session.session_name))
+                ),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(agent: &str, name: Option<&str>) -> SessionDetail {
+        SessionDetail {
+            session_name: tmux::session_name(agent, name),
+            agent: agent.into(),
+            name: name.map(str::to_owned),
+            client_count: 0,
+            pane_command: None,
+            pinned: false,
+            crash_looping: false,
+            note: None,
+            cwd: None,
+        }
+    }
+
+    #[test]
+    fn splice_appends_then_replaces_block() {
+        let first = snippet(
+            "amux",
+            &Keys {
+                picker: "A",
+                next: "N",
+                prev: "P",
+            },
+        );
+        let (config, replaced) = splice("set -g mouse on", &first);
+        assert!(!replaced);
+        assert!(config.starts_with("set -g mouse on\n\n# >>> amux keys >>>\n"));
+        assert!(config.contains("bind-key N run-shell -b \"amux next --client '#{client_name}'\""));
+
+        let second = snippet(
+            "amux",
+            &Keys {
+                picker: "M",
+                next: "N",
+                prev: "P",
+            },
+        );
+        let (config, replaced) = splice(&format!("{config}set -g history-limit 1000\n"), &second);
+        assert!(replaced);
+        assert_eq!(config.matches(BEGIN_MARKER).count(), 1);
+        assert!(config.contains("bind-key M ") && !config.contains("bind-key A "));
+        assert!(config.ends_with("# <<< amux keys <<<\nset -g history-limit 1000\n"));
+    }
+
+    #[test]
+    fn step_wraps_in_stable_order() {
+        let mut sessions = vec![
+            session("codex", Some("b")),
+            session("claude", None),
+            session("codex", None),
+        ];
+        order(&mut sessions);
+        let names: Vec<_> = sessions.iter().map(|s| s.session_name.as_str()).collect();
+        assert_eq!(names, ["amux-claude", "amux-codex", "amux-codex--b"]);
+
+        let next = |current: &str, forward| {
+            step(&sessions, current, forward).map(|s| s.session_name.as_str())
+        };
+        assert_eq!(next("amux-codex", true), Some("amux-codex--b"));
+        assert_eq!(next("amux-codex--b", true), Some("amux-claude"));
+        assert_eq!(next("amux-claude", false), Some("amux-codex--b"));
+        assert_eq!(next("work", true), Some("amux-claude"));
+        assert!(step(&[], "work", true).is_none());
+    }
+
+    #[test]
+    fn menu_items_label_and_target_sessions() {
+        let mut named = session("codex", Some("pr#1"));
+        named.note = Some("review".into());
+        let items = menu_items(
+            &[session("claude", None), named],
+            "/dev/pts/1",
+            "amux-claude",
+        );
+        assert_eq!(items[0].label, "claude *");
+        assert_eq!(items[0].key, "1");
+        assert_eq!(items[1].label, "codex/pr#1 - review");
+        assert_eq!(
+            items[1].command,
+            "switch-client -c /dev/pts/1 -t '=amux-codex--pr%231:'"
+        );
+    }
+}
@@ -0,0 +1,646 @@
+//! A minimal TOML reader and writer covering the subset amux config files use:
+//! tables, arrays of tables, dotted keys, strings (basic, literal and their
+//! multi-line forms), integers, floats, booleans, arrays, and inline tables.
+
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+
+use crate::error::{bail, Result};
+
+pub type Table = BTreeMap<String, Value>;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    String(String),
+    Integer(i64),
+    Float(f64),
+    Boolean(bool),
+    Array(Vec<Value>),
+    Table(Table),
+}
+
+impl Value {
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::String(_) => "string",
+            Value::Integer(_) => "integer",
+            Value::Float(_) => "float",
+            Value::Boolean(_) => "boolean",
+            Value::Array(_) => "array",
+            Value::Table(_) => "table",
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_table(&self) -> Option<&Table> {
+        match self {
+            Value::Table(table) => Some(table),
+            _ => None,
+        }
+    }
+}
+
+pub fn parse(input: &str) -> Result<Table> {
+    let mut parser = Parser {
+        chars: input.chars().collect(),
+        pos: 0,
+        line: 1,
+    };
+    parser
+        .parse_document()
+        .or_else(|err| bail(format!("line {}: {err}", parser.line)))
+}
+
+/// Render a table as TOML, emitting plain keys before sub-tables.
+pub fn to_string(table: &Table) -> String {
+    let mut out = String::new();
+    write_table(&mut out, &[], table);
+    out
+}
+
+type ParseResult<T> = std::result::Result<T, String>;
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    line: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += 1;
+        if c == '\n' {
+            self.line += 1;
+        }
+        Some(c)
+    }
+
+    fn eat(&mut self, expected: char) -> bool {
+        if self.peek() == Some(expected) {
+            self.bump();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn starts_with(&self, text: &str) -> bool {
+        text.chars()
+            .enumerate()
+            .all(|(offset, c)| self.chars.get(self.pos + offset) == Some(&c))
+    }
+
+    fn skip_inline_space(&mut self) {
+        while matches!(self.peek(), Some(' ' | '\t')) {
+            self.bump();
+        }
+    }
+
+    fn skip_comment(&mut self) {
+        if self.peek() == Some('#') {
+            while !matches!(self.peek(), None | Some('\n')) {
+                self.bump();
+            }
+        }
+    }
+
+    /// Skip whitespace, newlines and comments (used between statements and array items).
+    fn skip_trivia(&mut self) {
+        loop {
+            match self.peek() {
+                Some(' ' | '\t' | '\r' | '\n') => {
+                    self.bump();
+                }
+                Some('#') => self.skip_comment(),
+                _ => break,
+            }
+        }
+    }
+
+    fn expect_line_end(&mut self) -> ParseResult<()> {
+        self.skip_inline_space();
+        self.skip_comment();
+        self.eat('\r');
+        match self.peek() {
+            None => Ok(()),
+            Some('\n') => {
+                self.bump();
+                Ok(())
+            }
+            Some(c) => Err(format!("unexpected '{c}' after value")),
+        }
+    }
+
+    fn parse_document(&mut self) -> ParseResult<Table> {
+        let mut root = Table::new();
+        let mut current: Vec<String> = Vec::new();
+        loop {
+            self.skip_trivia();
+            let Some(c) = self.peek() else {
+                return Ok(root);
+            };
+            if c == '[' {
+                self.bump();
+                let array = self.eat('[');
+                self.skip_inline_space();
+                let path = self.parse_key()?;
+                self.skip_inline_space();
+                if !self.eat(']') || (array && !self.eat(']')) {
+                    return Err("unterminated table header".to_string());
+                }
+                self.expect_line_end()?;
+                if array {
+                    let (last, parents) = path.split_last().expect("key is never empty");
+                    let parent = table_at(&mut root, parents)?;
+                    let entry = parent
+                        .entry(last.clone())
+                        .or_insert_with(|| Value::Array(Vec::new()));
+                    match entry {
+                        Value::Array(items) => items.push(Value::Table(Table::new())),
+                        _ => return Err(format!("'{}' is not an array of tables", path.join("."))),
+                    }
+                } else {
+                    table_at(&mut root, &path)?;
+                }
+                current = path;
+            } else {
+                let key = self.parse_key()?;
+                self.skip_inline_space();
+                if !self.eat('=') {
+                    return Err(format!("expected '=' after key '{}'", key.join(".")));
+                }
+                self.skip_inline_space();
+                let value = self.parse_value()?;
+                let table = table_at(&mut root, &current)?;
+                insert(table, &key, value)?;
+                self.expect_line_end()?;
+            }
+        }
+    }
+
+    fn parse_key(&mut self) -> ParseResult<Vec<String>> {
+        let mut parts = Vec::new();
+        loop {
+            self.skip_inline_space();
+            let part = match self.peek() {
+                Some('"') => {
+                    self.bump();
+                    self.parse_basic_string()?
+                }
+                Some('\'') => {
+                    self.bump();
+                    self.parse_literal_string()?
+                }
+                _ => {
+                    let start = self.pos;
+                    while self
+                        .peek()
+                        .is_some_and(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+                    {
+                        self.bump();
+                    }
+                    if start == self.pos {
+                        return Err(match self.peek() {
+                            Some(c) => format!("unexpected '{c}' in key"),
+                            None => "expected a key".to_string(),
+                        });
+                    }
+                    self.chars[start..self.pos].iter().collect()
+                }
+            };
+            parts.push(part);
+            self.skip_inline_space();
+            if !self.eat('.') {
+                return Ok(parts);
+            }
+        }
+    }
+
+    fn parse_value(&mut self) -> ParseResult<Value> {
+        match self.peek() {
+            Some('"') => {
+                if self.starts_with("\"\"\"") {
+                    self.pos += 3;
+                    self.parse_multiline_basic().map(Value::String)
+                } else {
+                    self.bump();
+                    self.parse_basic_string().map(Value::String)
+                }
+            }
+            Some('\'') => {
+                if self.starts_with("'''") {
+                    self.pos += 3;
+                    self.parse_multiline_literal().map(Value::String)
+                } else {
+                    self.bump();
+                    self.parse_literal_string().map(Value::String)
+                }
+            }
+            Some('[') => {
+                self.bump();
+                self.parse_array()
+            }
+            Some('{') => {
+                self.bump();
+                self.parse_inline_table()
+            }
+            Some('t') if self.starts_with("true") => {
+                self.pos += 4;
+                Ok(Value::Boolean(true))
+            }
+            Some('f') if self.starts_with("false") => {
+                self.pos += 5;
+                Ok(Value::Boolean(false))
+            }
+            Some(c) if c.is_ascii_digit() || c == '+' || c == '-' => self.parse_number(),
+            Some(c) => Err(format!("unsupported value starting with '{c}'")),
+            None => Err("expected a value".to_string()),
+        }
+    }
+
+    fn parse_basic_string(&mut self) -> ParseResult<String> {
+        let mut out = String::new();
+        loop {
+            match self.bump() {
+                None | Some('\n') => return Err("unterminated string".to_string()),
+                Some('"') => return Ok(out),
+                Some('\\') => out.push(self.parse_escape()?),
+                Some(c) => out.push(c),
+            }
+        }
+    }
+
+    fn parse_multiline_basic(&mut self) -> ParseResult<String> {
+        // A newline immediately after the opening delimiter is trimmed.
+        self.eat('\r');
+        self.eat('\n');
+        let mut out = String::new();
+        loop {
+            if self.starts_with("\"\"\"") {
+                self.pos += 3;
+                // Up to two extra quotes before the delimiter belong to the content.
+                for _ in 0..2 {
+                    if self.eat('"') {
+                        out.push('"');
+                    }
+                }
+                return Ok(out);
+            }
+            match self.bump() {
+                None => return Err("unterminated multi-line string".to_string()),
+                Some('\\') => {
+                    if matches!(self.peek(), Some(' ' | '\t' | '\r' | '\n')) {
+                        // Line-ending backslash: trim all following whitespace.
+                        while matches!(self.peek(), Some(' ' | '\t' | '\r' | '\n')) {
+                            self.bump();
+                        }
+                    } else {
+                        out.push(self.parse_escape()?);
+                    }
+                }
+                Some(c) => out.push(c),
+            }
+        }
+    }
+
+    fn parse_literal_string(&mut self) -> ParseResult<String> {
+        let mut out = String::new();
+        loop {
+            match self.bump() {
+                None | Some('\n') => return Err("unterminated string".to_string()),
+                Some('\'') => return Ok(out),
+                Some(c) => out.push(c),
+            }
+        }
+    }
+
+    fn parse_multiline_literal(&mut self) -> ParseResult<String> {
+        self.eat('\r');
+        self.eat('\n');
+        let mut out = String::new();
+        loop {
+            if self.starts_with("'''") {
+                self.pos += 3;
+                return Ok(out);
+            }
+            match self.bump() {
+                None => return Err("unterminated multi-line string".to_string()),
+                Some(c) => out.push(c),
+            }
+        }
+    }
+
+    fn parse_escape(&mut self) -> ParseResult<char> {
+        let c = self.bump().ok_or("unterminated escape")?;
+        Ok(match c {
+            'b' => '\u{8}',
+            't' => '\t',
+            'n' => '\n',
+            'f' => '\u{c}',
+            'r' => '\r',
+            'e' => '\u{1b}',
+            '"' => '"',
+            '\\' => '\\',
+            'u' | 'U' => {
+                let len = if c == 'u' { 4 } else { 8 };
+                let digits: String = (0..len).filter_map(|_| self.bump()).collect();
+                u32::from_str_radix(&digits, 16)
+                    .ok()
+                    .and_then(char::from_u32)
+                    .ok_or_else(|| format!("invalid unicode escape '\\{c}{digits}'"))?
+            }
+            other => return Err(format!("invalid escape '\\{other}'")),
+        })
+    }
+
+    fn parse_array(&mut self) -> ParseResult<Value> {
+        let mut items = Vec::new();
+        loop {
+            self.skip_trivia();
+            if self.eat(']') {
+                return Ok(Value::Array(items));
+            }
+            items.push(self.parse_value()?);
+            self.skip_trivia();
+            if self.eat(']') {
+                return Ok(Value::Array(items));
+            }
+            if !self.eat(',') {
+                return Err("expected ',' or ']' in array".to_string());
+            }
+        }
+    }
+
+    fn parse_inline_table(&mut self) -> ParseResult<Value> {
+        let mut table = Table::new();
+        self.skip_inline_space();
+        if self.eat('}') {
+            return Ok(Value::Table(table));
+        }
+        loop {
+            let key = self.parse_key()?;
+            self.skip_inline_space();
+            if !self.eat('=') {
+                return Err(format!("expected '=' after key '{}'", key.join(".")));
+            }
+            self.skip_inline_space();
+            let value = self.parse_value()?;
+            insert(&mut table, &key, value)?;
+            self.skip_inline_space();
+            if self.eat('}') {
+                return Ok(Value::Table(table));
+            }
+            if !self.eat(',') {
+                return Err("expected ',' or '}' in inline table".to_string());
+            }
+            self.skip_inline_space();
+        }
+    }
+
+    fn parse_number(&mut self) -> ParseResult<Value> {
+        let start = self.pos;
+        while self
+            .peek()
+            .is_some_and(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.' | '_'))
+        {
+            self.bump();
+        }
+        let raw: String = self.chars[start..self.pos]
+            .iter()
+            .filter(|c| **c != '_')
+            .collect();
+        if let Ok(value) = raw.parse::<i64>() {
+            return Ok(Value::Integer(value));
+        }
+        if raw.contains(['.', 'e', 'E']) {
+            if let Ok(value) = raw.parse::<f64>() {
+                return Ok(Value::Float(value));
+            }
+        }
+        Err(format!("invalid number '{raw}'"))
+    }
+}
+
+/// Walk (creating as needed) to the table at `path`, descending into the last
+/// element of arrays of tables.
+fn table_at<'a>(root: &'a mut Table, path: &[String]) -> ParseResult<&'a mut Table> {
+    let mut table = root;
+    for part in path {
+        let entry = table
+            .entry(part.clone())
+            .or_insert_with(|| Value::Table(Table::new()));
+        table = match entry {
+            Value::Table(inner) => inner,
+            Value::Array(items) => match items.last_mut() {
+                Some(Value::Table(inner)) => inner,
+                _ => return Err(format!("'{part}' is not a table")),
+            },
+            _ => return Err(format!("'{part}' is not a table")),
+        };
+    }
+    Ok(table)
+}
+
+fn insert(table: &mut Table, key: &[String], value: Value) -> ParseResult<()> {
+    let (last, parents) = key.split_last().expect("key is never empty");
+    let table = table_at(table, parents)?;
+    if table.contains_key(last) {
+        return Err(format!("duplicate key '{}'", key.join(".")));
+    }
+    table.insert(last.clone(), value);
+    Ok(())
+}
+
+fn write_table(out: &mut String, path: &[String], table: &Table) {
+    for (key, value) in table {
+        if !is_section(value) {
+            let _ = writeln!(out, "{} = {}", format_key(key), format_inline(value));
+        }
+    }
+    for (key, value) in table {
+        let mut child = path.to_vec();
+        child.push(key.clone());
+        let header = child
+            .iter()
+            .map(|part| format_key(part))
+            .collect::<Vec<_>>()
+            .join(".");
+        match value {
+            Value::Table(inner) => {
+                let has_values = inner.values().any(|value| !is_section(value));
+                if has_values || inner.is_empty() {
+                    if !out.is_empty() {
+                        out.push('\n');
+                    }
+                    let _ = writeln!(out, "[{header}]");
+                }
+                write_table(out, &child, inner);
+            }
+            Value::Array(items) if is_section(value) => {
+                for item in items {
+                    if !out.is_empty() {
+                        out.push('\n');
+                    }
+                    let _ = writeln!(out, "[[{header}]]");
+                    if let Value::Table(inner) = item {
+                        write_table(out, &child, inner);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn is_section(value: &Value) -> bool {
+    match value {
+        Value::Table(_) => true,
+        Value::Array(items) => {
+            !items.is_empty() && items.iter().all(|item| matches!(item, Value::Table(_)))
+        }
+        _ => false,
+    }
+}
+
+fn format_key(key: &str) -> String {
+    let bare = !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if bare {
+        key.to_string()
+    } else {
+        quote(key)
+    }
+}
+
+fn format_inline(value: &Value) -> String {
+    match value {
+        Value::String(text) => quote(text),
+        Value::Integer(number) => number.to_string(),
+        Value::Float(number) => {
+            if number.fract() == 0.0 && number.is_finite() {
+                format!("{number:.1}")
+            } else {
+                number.to_string()
+            }
+        }
+        Value::Boolean(flag) => flag.to_string(),
+        Value::Array(items) => {
+            let parts: Vec<String> = items.iter().map(format_inline).collect();
+            format!("[{}]", parts.join(", "))
+        }
+        Value::Table(table) => {
+            let parts: Vec<String> = table
+                .iter()
+                .map(|(key, value)| format!("{} = {}", format_key(key), format_inline(value)))
+                .collect();
+            if parts.is_empty() {
+                "{}".to_string()
+            } else {
+                format!("{{ {} }}", parts.join(", "))
+            }
+        }
+    }
+}
+
+fn quote(text: &str) -> String {
+    let mut out = String::with_capacity(text.len() + 2);
+    out.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            '\r' => out.push_str("\\r"),
+            c if c.is_control() => {
+                let _ = write!(out, "\\u{:04X}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_reads_tables_and_scalars() {
+        let doc = parse(
+            "top = 1\n# comment\n[agents.claude]\ncommand = \"claude --x\" # trailing\nenabled = true\nratio = 0.5\n",
+        )
+        .expect("document should parse");
+        assert_eq!(doc.get("top"), Some(&Value::Integer(1)));
+        let claude = doc["agents"].as_table().unwrap()["claude"]
+            .as_table()
+            .unwrap();
+        assert_eq!(claude["command"].as_str(), Some("claude --x"));
+        assert_eq!(claude["enabled"], Value::Boolean(true));
+        assert_eq!(claude["ratio"], Value::Float(0.5));
+    }
+
+    #[test]
+    fn parse_handles_strings_arrays_and_dotted_keys() {
+        let doc = parse(
+            "templates.review = \"Review:\\n{stdin}\"\nraw = '^> $'\nlist = [\n  \"a\", # first\n  \"b\",\n]\nblock = \"\"\"\nline one\nline two\"\"\"\ninline = { a = 1, b = 'x' }\n",
+        )
+        .expect("document should parse");
+        let templates = doc["templates"].as_table().unwrap();
+        assert_eq!(templates["review"].as_str(), Some("Review:\n{stdin}"));
+        assert_eq!(doc["raw"].as_str(), Some("^> $"));
+        assert_eq!(
+            doc["list"],
+            Value::Array(vec![Value::String("a".into()), Value::String("b".into())])
+        );
+        assert_eq!(doc["block"].as_str(), Some("line one\nline two"));
+        assert_eq!(doc["inline"].as_table().unwrap()["b"].as_str(), Some("x"));
+    }
+
+    #[test]
+    fn parse_supports_arrays_of_tables() {
+        let doc = parse("[[session]]\nagent = \"codex\"\n\n[[session]]\nagent = \"claude\"\n")
+            .expect("document should parse");
+        let Value::Array(items) = &doc["session"] else {
+            panic!("session should be an array");
+        };
+        assert_eq!(items.len(), 2);
+        assert_eq!(
+            items[1].as_table().unwrap()["agent"].as_str(),
+            Some("claude")
+        );
+    }
+
+    #[test]
+    fn parse_reports_line_numbers() {
+        let err = parse("a = 1\na = 2\n").expect_err("duplicate key should fail");
+        assert!(err.to_string().contains("line 2"), "got: {err}");
+        let err = parse("a = \n").expect_err("missing value should fail");
+        assert!(
+            err.to_string().contains("expected a value") || err.to_string().contains("unsupported")
+        );
+    }
+
+    #[test]
+    fn to_string_round_trips() {
+        let source = "version = 1\n\n[agents.my-agent]\ncommand = \"run \\\"x\\\"\"\nparams = [\"a\", \"b\"]\n\n[[session]]\nagent = \"codex\"\n";
+        let doc = parse(source).expect("document should parse");
+        let rendered = to_string(&doc);
+        assert_eq!(parse(&rendered).expect("rendered output should parse"), doc);
+        assert!(rendered.contains("[agents.my-agent]"));
+    }
+}
@@ -0,0 +1,106 @@
+//! `amux cp`: copy a local file or directory to where an agent can see it.
+//! That is the session's working directory for a local agent, or, when
+//! `[agents.<name>.transfer]` is set, a path on the SSH host, container, or
+//! pod the agent runs in.
+
+use std::path::Path;
+use std::process::Command;
+
+use crate::config::{Transfer, TransferVia};
+use crate::error::{bail, fail_with, with_context, ErrorKind, Result};
+
+/// The command copying `source` (an absolute path) to `path` for an agent
+/// whose session directory is `cwd`. An empty `path` keeps the source's name
+/// in the base directory.
+pub fn command(transfer: Option<&Transfer>, cwd: &Path, source: &Path, path: &str) -> Vec<String> {
+    let source = source.to_string_lossy().into_owned();
+    let Some(transfer) = transfer else {
+        let dest = cwd.join(path).to_string_lossy().into_owned();
+        return vec!["cp".into(), "-R".into(), source, dest];
+    };
+    let dest = format!(
+        "{}:{}",
+        transfer.target,
+        remote_path(transfer.dir.as_deref(), path)
+    );
+    match transfer.via {
+        TransferVia::Ssh => vec!["scp".into(), "-r".into(), source, dest],
+        TransferVia::Docker => vec!["docker".into(), "cp".into(), source, dest],
+        TransferVia::Kubectl => vec!["kubectl".into(), "cp".into(), source, dest],
+    }
+}
+
+/// `path` on the target: absolute paths stay as they are, relative ones are
+/// joined to `dir`, and nothing at all means `dir` itself.
+fn remote_path(dir: Option<&str>, path: &str) -> String {
+    match dir {
+        _ if path.starts_with('/') => path.to_string(),
+        Some(dir) if path.is_empty() => dir.to_string(),
+        Some(dir) => format!("{}/{path}", dir.trim_end_matches('/')),
+        None if path.is_empty() => ".".to_string(),
+        None => path.to_string(),
+    }
+}
+
+/// Run `command` as built by [`command`].
+pub fn run(command: &[String]) -> Result<()> {
+    let Some((program, args)) = command.split_first() else {
+        return bail("nothing to run");
+    };
+    let status = Command::new(program)
+        .args(args)
+        .status()
+        .map_err(|err| with_context(err, format!("failed to run {program}")))?;
+    if status.success() {
+        return Ok(());
+    }
+    Err(fail_with(
+        ErrorKind::Other,
+        format!("{program} exited with {status}"),
+        Some(format!("the copy ran: {}", shell_words::join(command))),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transfer(via: TransferVia, dir: Option<&str>) -> Transfer {
+        Transfer {
+            via,
+            target: "box".into(),
+            dir: dir.map(str::to_owned),
+        }
+    }
+
+    #[test]
+    fn command_copies_into_the_session_directory_by_default() {
+        let command = command(
+            None,
+            Path::new("/src/app"),
+            Path::new("/tmp/spec.md"),
+            "docs",
+        );
+        assert_eq!(command, ["cp", "-R", "/tmp/spec.md", "/src/app/docs"]);
+    }
+
+    #[test]
+    fn command_uses_the_configured_transfer() {
+        let source = Path::new("/tmp/spec.md");
+        let ssh = transfer(TransferVia::Ssh, Some("/work/"));
+        assert_eq!(
+            command(Some(&ssh), Path::new("/"), source, "docs/spec.md"),
+            ["scp", "-r", "/tmp/spec.md", "box:/work/docs/spec.md"]
+        );
+        let docker = transfer(TransferVia::Docker, None);
+        assert_eq!(
+            command(Some(&docker), Path::new("/"), source, ""),
+            ["docker", "cp", "/tmp/spec.md", "box:."]
+        );
+        let kubectl = transfer(TransferVia::Kubectl, Some("/app"));
+        assert_eq!(
+            command(Some(&kubectl), Path::new("/"), source, "/etc/spec.md"),
+            ["kubectl", "cp", "/tmp/spec.md", "box:/etc/spec.md"]
+        );
+    }
+}
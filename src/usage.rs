@@ -0,0 +1,396 @@
+//! Token and cost estimates for a session, read from the agent CLI's own logs.
+//!
+//! Each adapter knows where one CLI writes its transcripts and picks out the
+//! entries that belong to an amux session by working directory and start time:
+//!
+//! - Claude Code: `~/.claude/projects/<cwd slug>/*.jsonl` (or `$CLAUDE_CONFIG_DIR`),
+//!   one line per assistant message with a `usage` block.
+//! - Codex: `~/.codex/sessions/**/rollout-*.jsonl` (or `$CODEX_HOME`), whose
+//!   `token_count` events carry running totals.
+//!
+//! Costs use built-in list prices and are estimates only.
+
+use std::collections::BTreeSet;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use crate::json::{self, Value};
+use crate::time::parse_timestamp;
+use crate::tmux::SessionInfo;
+
+/// USD per million tokens: input, output, cache write, cache read. Matched by
+/// the longest model-name prefix.
+const PRICES: &[(&str, [f64; 4])] = &[
+    ("claude-opus-4-5", [5.0, 25.0, 6.25, 0.5]),
+    ("claude-opus-4", [15.0, 75.0, 18.75, 1.5]),
+    ("claude-sonnet-4", [3.0, 15.0, 3.75, 0.3]),
+    ("claude-3-7-sonnet", [3.0, 15.0, 3.75, 0.3]),
+    ("claude-3-5-sonnet", [3.0, 15.0, 3.75, 0.3]),
+    ("claude-haiku-4-5", [1.0, 5.0, 1.25, 0.1]),
+    ("claude-3-5-haiku", [0.8, 4.0, 1.0, 0.08]),
+    ("gpt-5", [1.25, 10.0, 0.0, 0.125]),
+    ("gpt-5-mini", [0.25, 2.0, 0.0, 0.025]),
+    ("gpt-4.1", [2.0, 8.0, 0.0, 0.5]),
+    ("o3", [2.0, 8.0, 0.0, 0.5]),
+    ("o4-mini", [1.1, 4.4, 0.0, 0.275]),
+    ("codex-mini", [1.5, 6.0, 0.0, 0.375]),
+];
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Usage {
+    /// Uncached input tokens.
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub cache_write_tokens: u64,
+    pub cache_read_tokens: u64,
+    /// Estimated cost, or `None` if some tokens came from a model without a known price.
+    pub cost_usd: Option<f64>,
+}
+
+impl Usage {
+    pub fn total_tokens(&self) -> u64 {
+        self.input_tokens + self.output_tokens + self.cache_write_tokens + self.cache_read_tokens
+    }
+
+    /// Compact form such as `48.2k tokens, ~$0.31`.
+    pub fn summary(&self) -> String {
+        let tokens = format!("{} tokens", compact_count(self.total_tokens()));
+        match self.cost_usd {
+            Some(cost) => format!("{tokens}, ~${cost:.2}"),
+            None => tokens,
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        json::object([
+            ("input_tokens", Value::from(self.input_tokens)),
+            ("output_tokens", Value::from(self.output_tokens)),
+            ("cache_write_tokens", Value::from(self.cache_write_tokens)),
+            ("cache_read_tokens", Value::from(self.cache_read_tokens)),
+            ("cost_usd", Value::from(self.cost_usd)),
+        ])
+    }
+
+    pub fn from_json(value: &Value) -> Option<Self> {
+        let count = |key: &str| value.get(key).and_then(Value::as_u64);
+        Some(Self {
+            input_tokens: count("input_tokens")?,
+            output_tokens: count("output_tokens")?,
+            cache_write_tokens: count("cache_write_tokens")?,
+            cache_read_tokens: count("cache_read_tokens")?,
+            cost_usd: value.get("cost_usd").and_then(Value::as_f64),
+        })
+    }
+
+    /// Add tokens billed at `model`'s price.
+    fn add(&mut self, model: Option<&str>, input: u64, output: u64, write: u64, read: u64) {
+        let first = self.total_tokens() == 0;
+        self.input_tokens += input;
+        self.output_tokens += output;
+        self.cache_write_tokens += write;
+        self.cache_read_tokens += read;
+
+        let cost = model.and_then(price).map(|[p_in, p_out, p_write, p_read]| {
+            (input as f64 * p_in
+                + output as f64 * p_out
+                + write as f64 * p_write
+                + read as f64 * p_read)
+                / 1_000_000.0
+        });
+        self.cost_usd = match (first, self.cost_usd, cost) {
+            (true, _, cost) => cost,
+            (false, Some(total), Some(cost)) => Some(total + cost),
+            _ => None,
+        };
+    }
+}
+
+/// Usage of the agent running in a session since the session was created, or
+/// `None` if there is no adapter for its CLI or no matching log entries.
+pub fn for_session(agent: &str, info: &SessionInfo) -> Option<Usage> {
+    let cwd = Path::new(info.cwd.as_deref()?);
+    let since = info.created?;
+    let usage = match adapter(agent, info.start_command.as_deref())? {
+        "claude" => claude_usage(&claude_dir()?, cwd, since),
+        _ => codex_usage(&codex_dir()?, cwd, since),
+    };
+    (usage.total_tokens() > 0).then_some(usage)
+}
+
+/// Which CLI's logs to read: the program the session started, falling back to
+/// the agent name (custom commands and `--raw-cmd` wrap the real program).
+fn adapter(agent: &str, start_command: Option<&str>) -> Option<&'static str> {
+    let program = start_command
+        .and_then(|command| shell_words::split(command).ok())
+        .and_then(|tokens| tokens.into_iter().next())
+        .and_then(|first| {
+            Path::new(&first)
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+        });
+    let known = |name: &str| ["claude", "codex"].into_iter().find(|known| *known == name);
+    program.as_deref().and_then(known).or_else(|| known(agent))
+}
+
+fn claude_dir() -> Option<PathBuf> {
+    env::var_os("CLAUDE_CONFIG_DIR")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| Some(PathBuf::from(env::var_os("HOME")?).join(".claude")))
+}
+
+fn codex_dir() -> Option<PathBuf> {
+    env::var_os("CODEX_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| Some(PathBuf::from(env::var_os("HOME")?).join(".codex")))
+}
+
+/// Claude Code names a project's transcript directory after its path with every
+/// non-alphanumeric character replaced by `-`.
+fn claude_project_slug(cwd: &Path) -> String {
+    cwd.to_string_lossy()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect()
+}
+
+fn claude_usage(base: &Path, cwd: &Path, since: u64) -> Usage {
+    let dir = base.join("projects").join(claude_project_slug(cwd));
+    let mut usage = Usage::default();
+    // A message is logged once per content block, each with the same usage.
+    let mut seen = BTreeSet::new();
+    for file in recent_logs(&dir, since, false) {
+        let Ok(text) = fs::read_to_string(&file) else {
+            continue;
+        };
+        for entry in text.lines().filter_map(|line| json::parse(line).ok()) {
+            let Some(message) = entry.get("message") else {
+                continue;
+            };
+            let Some(counts) = message.get("usage") else {
+                continue;
+            };
+            let timestamp = entry
+                .get("timestamp")
+                .and_then(Value::as_str)
+                .and_then(parse_timestamp);
+            if timestamp.is_some_and(|timestamp| timestamp < since) {
+                continue;
+            }
+            if let Some(id) = message.get("id").and_then(Value::as_str) {
+                if !seen.insert(id.to_string()) {
+                    continue;
+                }
+            }
+            let count = |key: &str| counts.get(key).and_then(Value::as_u64).unwrap_or_default();
+            usage.add(
+                message.get("model").and_then(Value::as_str),
+                count("input_tokens"),
+                count("output_tokens"),
+                count("cache_creation_input_tokens"),
+                count("cache_read_input_tokens"),
+            );
+        }
+    }
+    usage
+}
+
+fn codex_usage(base: &Path, cwd: &Path, since: u64) -> Usage {
+    let mut usage = Usage::default();
+    for file in recent_logs(&base.join("sessions"), since, true) {
+        let Ok(text) = fs::read_to_string(&file) else {
+            continue;
+        };
+        if let Some((model, tokens)) = codex_rollout_usage(&text, cwd, since) {
+            let [input, output, read] = tokens;
+            usage.add(model.as_deref(), input, output, 0, read);
+        }
+    }
+    usage
+}
+
+/// Tokens a Codex rollout used after `since` as `[uncached input, output,
+/// cached input]`, with the last model seen, if the rollout ran in `cwd`.
+fn codex_rollout_usage(text: &str, cwd: &Path, since: u64) -> Option<(Option<String>, [u64; 3])> {
+    let mut lines = text.lines().filter_map(|line| json::parse(line).ok());
+    let meta = lines.next()?;
+    if meta.path(&["payload", "cwd"]).and_then(Value::as_str) != Some(&*cwd.to_string_lossy()) {
+        return None;
+    }
+
+    let mut model = None;
+    let mut before = [0; 3];
+    let mut latest = [0; 3];
+    for entry in lines {
+        let payload = entry.get("payload");
+        match entry.get("type").and_then(Value::as_str) {
+            Some("turn_context") => {
+                if let Some(name) = payload.and_then(|p| p.get("model")).and_then(Value::as_str) {
+                    model = Some(name.to_string());
+                }
+            }
+            Some("event_msg") => {
+                let Some(totals) = payload
+                    .filter(|p| p.get("type").and_then(Value::as_str) == Some("token_count"))
+                    .and_then(|p| p.path(&["info", "total_token_usage"]))
+                else {
+                    continue;
+                };
+                let count = |key: &str| totals.get(key).and_then(Value::as_u64).unwrap_or_default();
+                let cached = count("cached_input_tokens");
+                let counts = [
+                    count("input_tokens").saturating_sub(cached),
+                    count("output_tokens"),
+                    cached,
+                ];
+                let timestamp = entry
+                    .get("timestamp")
+                    .and_then(Value::as_str)
+                    .and_then(parse_timestamp);
+                if timestamp.is_some_and(|timestamp| timestamp < since) {
+                    before = counts;
+                }
+                latest = counts;
+            }
+            _ => {}
+        }
+    }
+    let used = [0, 1, 2].map(|index| latest[index].saturating_sub(before[index]));
+    Some((model, used))
+}
+
+/// `.jsonl` files under `dir` modified at or after `since`.
+fn recent_logs(dir: &Path, since: u64, recursive: bool) -> Vec<PathBuf> {
+    let mut found = Vec::new();
+    let Ok(entries) = fs::read_dir(dir) else {
+        return found;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let Ok(meta) = entry.metadata() else {
+            continue;
+        };
+        if meta.is_dir() {
+            if recursive {
+                found.extend(recent_logs(&path, since, true));
+            }
+            continue;
+        }
+        let modified = meta
+            .modified()
+            .ok()
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map(|elapsed| elapsed.as_secs())
+            .unwrap_or_default();
+        if modified >= since && path.extension().is_some_and(|ext| ext == "jsonl") {
+            found.push(path);
+        }
+    }
+    found.sort();
+    found
+}
+
+fn price(model: &str) -> Option<[f64; 4]> {
+    let model = model.to_ascii_lowercase();
+    PRICES
+        .iter()
+        .filter(|(prefix, _)| model.starts_with(prefix))
+        .max_by_key(|(prefix, _)| prefix.len())
+        .map(|(_, prices)| *prices)
+}
+
+fn compact_count(count: u64) -> String {
+    match count {
+        0..=999 => count.to_string(),
+        1_000..=999_999 => format!("{:.1}k", count as f64 / 1_000.0),
+        _ => format!("{:.1}M", count as f64 / 1_000_000.0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn adapter_uses_the_started_program_then_the_agent_name() {
+        assert_eq!(
+            adapter("review", Some("/usr/local/bin/claude --model opus")),
+            Some("claude")
+        );
+        assert_eq!(
+            adapter("codex", Some("sh -c 'codex | tee out'")),
+            Some("codex")
+        );
+        assert_eq!(adapter("gemini", Some("gemini")), None);
+    }
+
+    #[test]
+    fn price_prefers_the_longest_prefix() {
+        assert_eq!(
+            price("claude-opus-4-5-20251101"),
+            Some([5.0, 25.0, 6.25, 0.5])
+        );
+        assert_eq!(
+            price("claude-opus-4-1-20250805"),
+            Some([15.0, 75.0, 18.75, 1.5])
+        );
+        assert_eq!(price("gpt-5-mini"), Some([0.25, 2.0, 0.0, 0.025]));
+        assert_eq!(price("llama3"), None);
+    }
+
+    #[test]
+    fn usage_cost_is_unknown_once_an_unpriced_model_appears() {
+        let mut usage = Usage::default();
+        usage.add(Some("claude-sonnet-4-5"), 1_000_000, 100_000, 0, 0);
+        assert_eq!(usage.summary(), "1.1M tokens, ~$4.50");
+        usage.add(Some("local-model"), 10, 10, 0, 0);
+        assert_eq!(usage.cost_usd, None);
+        assert_eq!(usage.summary(), "1.1M tokens");
+    }
+
+    #[test]
+    fn codex_rollout_counts_only_tokens_after_start() {
+        let rollout = [
+            r#"{"type":"session_meta","payload":{"cwd":"/work/repo"}}"#,
+            r#"{"type":"turn_context","payload":{"model":"gpt-5"}}"#,
+            r#"{"timestamp":"2023-11-14T22:00:00Z","type":"event_msg","payload":{"type":"token_count","info":{"total_token_usage":{"input_tokens":1000,"cached_input_tokens":200,"output_tokens":50}}}}"#,
+            r#"{"timestamp":"2023-11-14T22:30:00Z","type":"event_msg","payload":{"type":"token_count","info":{"total_token_usage":{"input_tokens":5000,"cached_input_tokens":1200,"output_tokens":450}}}}"#,
+        ]
+        .join("\n");
+        let (model, used) = codex_rollout_usage(&rollout, Path::new("/work/repo"), 1_700_000_000)
+            .expect("rollout should match");
+        assert_eq!(model.as_deref(), Some("gpt-5"));
+        assert_eq!(used, [3_000, 400, 1_000]);
+        assert!(codex_rollout_usage(&rollout, Path::new("/elsewhere"), 0).is_none());
+    }
+
+    #[test]
+    fn claude_usage_dedupes_messages_and_skips_older_entries() {
+        let base = env::temp_dir().join(format!("amux-usage-{}", std::process::id()));
+        let cwd = Path::new("/work/my.repo");
+        let dir = base.join("projects").join(claude_project_slug(cwd));
+        fs::create_dir_all(&dir).expect("create project dir");
+        let entry = |id: &str, timestamp: &str| {
+            format!(
+                r#"{{"type":"assistant","timestamp":"{timestamp}","message":{{"id":"{id}","model":"claude-sonnet-4-5","usage":{{"input_tokens":10,"output_tokens":20,"cache_read_input_tokens":100}}}}}}"#
+            )
+        };
+        let transcript = [
+            entry("msg_old", "2000-01-01T00:00:00Z"),
+            entry("msg_1", "2030-01-01T00:00:00Z"),
+            entry("msg_1", "2030-01-01T00:00:01Z"),
+            entry("msg_2", "2030-01-01T00:00:02Z"),
+        ]
+        .join("\n");
+        fs::write(dir.join("session.jsonl"), transcript).expect("write transcript");
+
+        let usage = claude_usage(&base, cwd, 1_700_000_000);
+        assert_eq!(usage.input_tokens, 20);
+        assert_eq!(usage.output_tokens, 40);
+        assert_eq!(usage.cache_read_tokens, 200);
+        let _ = fs::remove_dir_all(base);
+    }
+}
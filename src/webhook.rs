@@ -0,0 +1,502 @@
+//! `amux serve`: a small HTTP endpoint on 127.0.0.1 so other local tools (a
+//! browser extension, a chat bridge) can send prompts and start agents.
+//!
+//! Every request needs a bearer token: the main `[webhook] token`, which may do
+//! anything, or one of `[webhook.tokens]`, each limited to a scope and
+//! optionally to fewer operations. Only operations listed in `[webhook] allow`
+//! are accepted from anyone. An accepted request runs the matching amux
+//! command (`GET /status` is `amux ide list --json`, `POST /send` is
+//! `amux send`, `POST /start` is `amux start`, `POST /rm` is `amux rm`) and
+//! answers with its exit code and output as JSON.
+
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+use crate::config::{self, Scope, Webhook};
+use crate::error::{bail, fail, with_context, Result};
+use crate::json::{self, Value};
+
+/// Largest request body accepted.
+const MAX_BODY: usize = 1 << 20;
+
+/// How long a client may take to send its request.
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Default)]
+struct Request {
+    method: String,
+    path: String,
+    authorization: Option<String>,
+    body: String,
+}
+
+/// The amux command an accepted request runs.
+#[derive(Debug, PartialEq, Eq)]
+struct Action {
+    args: Vec<String>,
+    stdin: Option<String>,
+    /// Label of the token that asked for it, for the request log.
+    token: String,
+}
+
+/// What the presented token may do.
+struct Grant<'a> {
+    label: &'a str,
+    scope: Scope,
+    allow: Option<&'a [String]>,
+}
+
+/// Why a request was turned away.
+#[derive(Debug, PartialEq, Eq)]
+struct Rejection {
+    status: u16,
+    message: String,
+}
+
+fn reject(status: u16, message: impl Into<String>) -> Rejection {
+    Rejection {
+        status,
+        message: message.into(),
+    }
+}
+
+/// Answer requests on `settings.port` until the process is stopped, running
+/// accepted ones with the amux executable at `exe`. `token` is the main,
+/// unrestricted token, if there is one.
+pub fn serve(settings: &Webhook, token: Option<&str>, exe: &Path) -> Result<()> {
+    let listener = TcpListener::bind(("127.0.0.1", settings.port)).map_err(|err| {
+        with_context(
+            err,
+            format!("failed to listen on 127.0.0.1:{}", settings.port),
+        )
+    })?;
+    println!(
+        "amux: listening on http://127.0.0.1:{} (allowed: {})",
+        settings.port,
+        settings.allow.join(", ")
+    );
+    for stream in listener.incoming() {
+        let Ok(mut stream) = stream else {
+            continue;
+        };
+        let (status, body) = handle(&mut stream, settings, token, exe);
+        let _ = write_response(&mut stream, status, &body);
+    }
+    Ok(())
+}
+
+fn handle(
+    stream: &mut TcpStream,
+    settings: &Webhook,
+    token: Option<&str>,
+    exe: &Path,
+) -> (u16, Value) {
+    let _ = stream.set_read_timeout(Some(READ_TIMEOUT));
+    let request = match read_request(&mut BufReader::new(&*stream)) {
+        Ok(request) => request,
+        Err(err) => return (400, error_body(&err.to_string())),
+    };
+    let (outcome, label) = match route(&request, settings, token) {
+        Ok(action) => (
+            run(exe, &action).map_err(|err| reject(500, err.to_string())),
+            format!(" ({})", action.token),
+        ),
+        Err(rejection) => (Err(rejection), String::new()),
+    };
+    let (status, body) =
+        outcome.unwrap_or_else(|rejection| (rejection.status, error_body(&rejection.message)));
+    println!("{} {} {status}{label}", request.method, request.path);
+    (status, body)
+}
+
+fn error_body(message: &str) -> Value {
+    json::object([("ok", Value::from(false)), ("error", Value::from(message))])
+}
+
+fn read_request(reader: &mut impl BufRead) -> Result<Request> {
+    let mut line = String::new();
+    reader
+        .read_line(&mut line)
+        .map_err(|err| with_context(err, "failed to read request"))?;
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(path)) = (parts.next(), parts.next()) else {
+        return bail("malformed request line");
+    };
+    let mut request = Request {
+        method: method.to_string(),
+        path: path.to_string(),
+        ..Request::default()
+    };
+
+    let mut length = 0;
+    loop {
+        line.clear();
+        reader
+            .read_line(&mut line)
+            .map_err(|err| with_context(err, "failed to read request headers"))?;
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        let Some((name, value)) = header.split_once(':') else {
+            return bail(format!("malformed header '{header}'"));
+        };
+        let value = value.trim();
+        match name.trim().to_ascii_lowercase().as_str() {
+            "content-length" => {
+                length = value.parse().map_err(|_| fail("invalid Content-Length"))?;
+            }
+            "authorization" => request.authorization = Some(value.to_string()),
+            _ => {}
+        }
+    }
+    if length > MAX_BODY {
+        return bail(format!("request body over {MAX_BODY} bytes"));
+    }
+    let mut body = vec![0; length];
+    reader
+        .read_exact(&mut body)
+        .map_err(|err| with_context(err, "failed to read request body"))?;
+    request.body = String::from_utf8(body).map_err(|_| fail("request body is not UTF-8"))?;
+    Ok(request)
+}
+
+/// Check `request` against the tokens and allowlists and turn it into the
+/// amux command to run.
+fn route(
+    request: &Request,
+    settings: &Webhook,
+    token: Option<&str>,
+) -> std::result::Result<Action, Rejection> {
+    let presented = request
+        .authorization
+        .as_deref()
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .unwrap_or_default();
+    let grant = authenticate(presented.trim(), settings, token)
+        .ok_or_else(|| reject(401, "missing or wrong bearer token"))?;
+    let operation = request.path.trim_start_matches('/');
+    let Some(needed) = config::webhook_scope(operation) else {
+        return Err(reject(404, format!("no endpoint {}", request.path)));
+    };
+    let method = if needed == Scope::Read { "GET" } else { "POST" };
+    if request.method != method {
+        return Err(reject(405, format!("{} needs {method}", request.path)));
+    }
+    if !settings.allow.iter().any(|allowed| allowed == operation) {
+        return Err(reject(
+            403,
+            format!("'{operation}' is not in the webhook allowlist"),
+        ));
+    }
+    if grant.scope < needed {
+        return Err(reject(
+            403,
+            format!(
+                "token '{}' has the {} scope; '{operation}' needs {}",
+                grant.label,
+                grant.scope.as_str(),
+                needed.as_str()
+            ),
+        ));
+    }
+    if grant
+        .allow
+        .is_some_and(|allow| !allow.iter().any(|allowed| allowed == operation))
+    {
+        return Err(reject(
+            403,
+            format!("token '{}' may not use '{operation}'", grant.label),
+        ));
+    }
+
+    let token = grant.label.to_string();
+    if operation == "status" {
+        return Ok(Action {
+            args: vec!["ide".into(), "list".into(), "--json".into()],
+            stdin: None,
+            token,
+        });
+    }
+    let body = json::parse(&request.body)
+        .map_err(|err| reject(400, format!("body is not JSON: {err}")))?;
+    let field = |key: &str| body.get(key).and_then(Value::as_str);
+    let agent = field("agent").ok_or_else(|| reject(400, "missing \"agent\""))?;
+    // `--flag=value` keeps a value starting with '-' from being read as a flag.
+    let mut args = vec![operation.to_string(), format!("--agent={agent}")];
+    if let Some(name) = field("name") {
+        args.push(format!("--name={name}"));
+    }
+    let stdin = match operation {
+        "send" => Some(
+            field("text")
+                .ok_or_else(|| reject(400, "missing \"text\""))?
+                .to_string(),
+        ),
+        _ => None,
+    };
+    Ok(Action { args, stdin, token })
+}
+
+/// The grant of the token matching `presented`. Every token is compared, so
+/// the time taken does not reveal which one came close.
+fn authenticate<'a>(
+    presented: &str,
+    settings: &'a Webhook,
+    token: Option<&'a str>,
+) -> Option<Grant<'a>> {
+    // An empty token never authenticates, whatever the settings hold.
+    if presented.is_empty() {
+        return None;
+    }
+    let main = token.map(|token| {
+        (
+            token,
+            Grant {
+                label: "token",
+                scope: Scope::Admin,
+                allow: None,
+            },
+        )
+    });
+    let scoped = settings.tokens.iter().map(|(label, scoped)| {
+        (
+            scoped.token.as_str(),
+            Grant {
+                label,
+                scope: scoped.scope,
+                allow: scoped.allow.as_deref(),
+            },
+        )
+    });
+    main.into_iter()
+        .chain(scoped)
+        .fold(None, |found, (expected, grant)| {
+            if same_token(presented, expected) {
+                Some(grant)
+            } else {
+                found
+            }
+        })
+}
+
+/// Compare tokens without stopping at the first differing byte.
+fn same_token(presented: &str, expected: &str) -> bool {
+    let (a, b) = (presented.as_bytes(), expected.as_bytes());
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+fn run(exe: &Path, action: &Action) -> Result<(u16, Value)> {
+    let mut child = Command::new(exe)
+        .args(&action.args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| with_context(err, "failed to run amux"))?;
+    if let (Some(mut stdin), Some(text)) = (child.stdin.take(), &action.stdin) {
+        stdin
+            .write_all(text.as_bytes())
+            .map_err(|err| with_context(err, "failed to pass the text to amux send"))?;
+    }
+    let output = child
+        .wait_with_output()
+        .map_err(|err| with_context(err, "failed to run amux"))?;
+    let status = if output.status.success() { 200 } else { 422 };
+    let body = json::object([
+        ("ok", Value::from(output.status.success())),
+        (
+            "exit_code",
+            Value::from(output.status.code().map(i64::from)),
+        ),
+        (
+            "output",
+            Value::from(String::from_utf8_lossy(&output.stdout).into_owned()),
+        ),
+        (
+            "error",
+            Value::from(String::from_utf8_lossy(&output.stderr).into_owned()),
+        ),
+    ]);
+    Ok((status, body))
+}
+
+fn write_response(stream: &mut impl Write, status: u16, body: &Value) -> std::io::Result<()> {
+    let reason = match status {
+        200 => "OK",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        422 => "Unprocessable Entity",
+        _ => "Internal Server Error",
+    };
+    let body = format!("{body}\n");
+    write!(
+        stream,
+        "HTTP/1.1 {status} {reason}\r\nContent-Type: application/json\r\n\
+         Content-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )?;
+    stream.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::WebhookToken;
+    use std::io::Cursor;
+
+    fn settings(allow: &[&str]) -> Webhook {
+        Webhook {
+            allow: allow.iter().map(|op| op.to_string()).collect(),
+            ..Webhook::default()
+        }
+    }
+
+    fn scoped(token: &str, scope: Scope, allow: Option<&[&str]>) -> WebhookToken {
+        WebhookToken {
+            token: token.into(),
+            scope,
+            allow: allow.map(|ops| ops.iter().map(|op| op.to_string()).collect()),
+        }
+    }
+
+    fn request(path: &str, token: &str, body: &str) -> Request {
+        Request {
+            method: "POST".into(),
+            path: path.into(),
+            authorization: Some(format!("Bearer {token}")),
+            body: body.into(),
+        }
+    }
+
+    #[test]
+    fn read_request_parses_headers_and_body() {
+        let raw = "POST /send HTTP/1.1\r\nHost: localhost\r\nauthorization: Bearer abc\r\n\
+                   Content-Length: 17\r\n\r\n{\"agent\":\"codex\"}";
+        let request = read_request(&mut Cursor::new(raw)).expect("request should parse");
+        assert_eq!(request.method, "POST");
+        assert_eq!(request.path, "/send");
+        assert_eq!(request.authorization.as_deref(), Some("Bearer abc"));
+        assert_eq!(request.body, "{\"agent\":\"codex\"}");
+        assert!(read_request(&mut Cursor::new("\r\n")).is_err());
+    }
+
+    #[test]
+    fn route_checks_token_and_allowlist() {
+        let allow_send = settings(&["send"]);
+        let body = r#"{"agent":"codex","text":"hi"}"#;
+        let status = |request: &Request, settings: &Webhook| {
+            route(request, settings, Some("abc")).map_err(|rejection| rejection.status)
+        };
+        assert_eq!(
+            status(&request("/send", "nope", body), &allow_send),
+            Err(401)
+        );
+        assert_eq!(
+            status(&request("/start", "abc", body), &allow_send),
+            Err(403)
+        );
+        assert_eq!(
+            status(&request("/restart", "abc", body), &allow_send),
+            Err(404)
+        );
+        let mut get = request("/send", "abc", body);
+        get.method = "GET".into();
+        assert_eq!(status(&get, &allow_send), Err(405));
+        assert_eq!(
+            status(
+                &request("/send", "abc", r#"{"agent":"codex"}"#),
+                &allow_send
+            ),
+            Err(400)
+        );
+    }
+
+    #[test]
+    fn route_builds_amux_commands() {
+        let both = settings(&["send", "start"]);
+        let action = route(
+            &request(
+                "/send",
+                "abc",
+                r#"{"agent":"codex","name":"-f","text":"fix it"}"#,
+            ),
+            &both,
+            Some("abc"),
+        )
+        .expect("send should be accepted");
+        assert_eq!(action.args, ["send", "--agent=codex", "--name=-f"]);
+        assert_eq!(action.stdin.as_deref(), Some("fix it"));
+
+        let action = route(
+            &request("/start", "abc", r#"{"agent":"claude"}"#),
+            &both,
+            Some("abc"),
+        )
+        .expect("start should be accepted");
+        assert_eq!(action.args, ["start", "--agent=claude"]);
+        assert_eq!(action.stdin, None);
+        assert_eq!(action.token, "token");
+    }
+
+    #[test]
+    fn route_limits_scoped_tokens() {
+        let mut settings = settings(&["status", "send", "start", "rm"]);
+        settings
+            .tokens
+            .insert("statusbar".into(), scoped("r0", Scope::Read, None));
+        settings.tokens.insert(
+            "mcp".into(),
+            scoped("c0", Scope::Control, Some(&["status", "send"])),
+        );
+        let status = |path: &str, method: &str, token: &str| {
+            let mut request = request(path, token, r#"{"agent":"codex","text":"hi"}"#);
+            request.method = method.into();
+            route(&request, &settings, None)
+                .map(|action| action.token)
+                .map_err(|rejection| rejection.status)
+        };
+        assert_eq!(status("/status", "GET", "r0"), Ok("statusbar".into()));
+        assert_eq!(status("/status", "POST", "r0"), Err(405));
+        assert_eq!(status("/send", "POST", "r0"), Err(403));
+        assert_eq!(status("/rm", "POST", "r0"), Err(403));
+        assert_eq!(status("/send", "POST", "c0"), Ok("mcp".into()));
+        assert_eq!(status("/start", "POST", "c0"), Err(403));
+        assert_eq!(status("/rm", "POST", "c0"), Err(403));
+        assert_eq!(status("/status", "GET", "abc"), Err(401));
+    }
+
+    #[test]
+    fn route_refuses_empty_tokens() {
+        let mut settings = settings(&["status"]);
+        settings
+            .tokens
+            .insert("statusbar".into(), scoped("", Scope::Read, None));
+        let status = |authorization: Option<&str>| {
+            let request = Request {
+                method: "GET".into(),
+                path: "/status".into(),
+                authorization: authorization.map(str::to_string),
+                body: String::new(),
+            };
+            route(&request, &settings, Some(""))
+                .map(|action| action.token)
+                .map_err(|rejection| rejection.status)
+        };
+        assert_eq!(status(Some("Bearer ")), Err(401));
+        assert_eq!(status(None), Err(401));
+    }
+
+    #[test]
+    fn same_token_needs_an_exact_match() {
+        assert!(same_token("s3cret", "s3cret"));
+        assert!(!same_token("s3cre", "s3cret"));
+        assert!(!same_token("", "s3cret"));
+    }
+}
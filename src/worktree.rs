@@ -0,0 +1,126 @@
+//! Working-tree summaries for `amux status`, computed concurrently and cached
+//! briefly on disk so repeated status calls stay fast on large repositories.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use crate::git;
+use crate::parallel;
+use crate::paths;
+use crate::time::unix_millis;
+
+/// How long a cached summary is reused before git is asked again.
+const CACHE_TTL: Duration = Duration::from_secs(5);
+/// Concurrent `git status` invocations.
+const JOBS: usize = 4;
+
+/// Summary (`clean`, `3 changed, +1/-0`, ...) for every directory that is inside
+/// a git repository; other directories are omitted.
+pub fn summaries(dirs: &[PathBuf]) -> BTreeMap<PathBuf, String> {
+    let unique: Vec<PathBuf> = dirs
+        .iter()
+        .cloned()
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect();
+    let results = parallel::map_bounded(&unique, JOBS, |dir| summary(dir));
+    unique
+        .into_iter()
+        .zip(results)
+        .filter_map(|(dir, summary)| summary.map(|summary| (dir, summary)))
+        .collect()
+}
+
+fn summary(dir: &Path) -> Option<String> {
+    let cache = cache_path(dir);
+    let now = unix_millis(SystemTime::now());
+    if let Some(cached) = cache.as_deref().and_then(|path| read_cache(path, dir, now)) {
+        return cached;
+    }
+
+    let summary = match git::status(dir) {
+        Ok(status) => status.map(|status| status.summary()),
+        Err(_) => None,
+    };
+    if let Some(path) = cache {
+        write_cache(&path, dir, now, summary.as_deref());
+    }
+    summary
+}
+
+fn cache_path(dir: &Path) -> Option<PathBuf> {
+    let key = fnv1a(dir.to_string_lossy().as_bytes());
+    Some(
+        paths::state_dir()
+            .ok()?
+            .join("git")
+            .join(format!("{key:016x}")),
+    )
+}
+
+/// Cached entry as `Some(summary)` if fresh; `Some(None)` records "not a repository".
+fn read_cache(path: &Path, dir: &Path, now: u64) -> Option<Option<String>> {
+    let raw = fs::read_to_string(path).ok()?;
+    let mut fields = raw.splitn(3, '\t');
+    let at: u64 = fields.next()?.parse().ok()?;
+    let cached_dir = fields.next()?;
+    let summary = fields.next()?;
+    if cached_dir != dir.to_string_lossy() || now.saturating_sub(at) > CACHE_TTL.as_millis() as u64
+    {
+        return None;
+    }
+    Some((summary != "-").then(|| summary.to_string()))
+}
+
+fn write_cache(path: &Path, dir: &Path, now: u64, summary: Option<&str>) {
+    if let Some(parent) = path.parent() {
+        let _ = fs::create_dir_all(parent);
+    }
+    let line = format!(
+        "{now}\t{}\t{}",
+        dir.to_string_lossy(),
+        summary.unwrap_or("-")
+    );
+    // Best effort: a failed write only means the next call asks git again.
+    let _ = fs::write(path, line);
+}
+
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_file(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("amux-worktree-{}-{name}", std::process::id()))
+    }
+
+    #[test]
+    fn cache_round_trips_until_it_expires() {
+        let path = temp_file("fresh");
+        let dir = Path::new("/work/repo");
+        write_cache(&path, dir, 1_000, Some("2 changed"));
+        assert_eq!(
+            read_cache(&path, dir, 2_000),
+            Some(Some("2 changed".into()))
+        );
+        assert_eq!(read_cache(&path, dir, 1_000 + 60_000), None);
+        assert_eq!(read_cache(&path, Path::new("/elsewhere"), 2_000), None);
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn cache_remembers_non_repositories() {
+        let path = temp_file("norepo");
+        let dir = Path::new("/tmp");
+        write_cache(&path, dir, 1_000, None);
+        assert_eq!(read_cache(&path, dir, 1_500), Some(None));
+        let _ = fs::remove_file(path);
+    }
+}
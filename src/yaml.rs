@@ -0,0 +1,278 @@
+//! A minimal YAML reader for the subset tool config files use: block
+//! mappings and sequences nested by indentation, plain and quoted scalars,
+//! flow sequences (`[a, b]`), and comments. Anchors, multi-line scalars, and
+//! flow mappings are refused. Documents come back as JSON values.
+
+use crate::error::{bail, Result};
+use crate::json::{Object, Value};
+
+#[derive(Clone, Debug)]
+struct Line {
+    number: usize,
+    indent: usize,
+    text: String,
+}
+
+/// Parse a single YAML document.
+pub fn parse(text: &str) -> Result<Value> {
+    let mut lines = Vec::new();
+    for (index, raw) in text.lines().enumerate() {
+        let content = strip_comment(raw);
+        let trimmed = content.trim_start();
+        if trimmed.trim().is_empty() || trimmed.trim() == "---" {
+            continue;
+        }
+        if raw.starts_with('\t') {
+            return bail(format!("line {}: tabs cannot indent YAML", index + 1));
+        }
+        lines.push(Line {
+            number: index + 1,
+            indent: content.len() - trimmed.len(),
+            text: trimmed.trim_end().to_string(),
+        });
+    }
+    if lines.is_empty() {
+        return Ok(Value::Null);
+    }
+    let mut pos = 0;
+    let indent = lines[0].indent;
+    let value = block(&mut lines, &mut pos, indent)?;
+    match lines.get(pos) {
+        Some(line) => bail(format!("line {}: unexpected indentation", line.number)),
+        None => Ok(value),
+    }
+}
+
+fn block(lines: &mut [Line], pos: &mut usize, indent: usize) -> Result<Value> {
+    if is_item(&lines[*pos].text) {
+        sequence(lines, pos, indent)
+    } else {
+        mapping(lines, pos, indent)
+    }
+}
+
+fn is_item(text: &str) -> bool {
+    text == "-" || text.starts_with("- ")
+}
+
+fn sequence(lines: &mut [Line], pos: &mut usize, indent: usize) -> Result<Value> {
+    let mut items = Vec::new();
+    while let Some(line) = lines.get(*pos) {
+        if line.indent != indent || !is_item(&line.text) {
+            break;
+        }
+        let rest = line.text[1..].trim_start().to_string();
+        if rest.is_empty() {
+            *pos += 1;
+            items.push(nested(lines, pos, indent)?);
+        } else if split_key(&rest).is_some() {
+            // `- key: value` starts a mapping indented to where the key is.
+            let inner = indent + (line.text.len() - rest.len());
+            lines[*pos].indent = inner;
+            lines[*pos].text = rest;
+            items.push(mapping(lines, pos, inner)?);
+        } else {
+            let number = line.number;
+            *pos += 1;
+            items.push(scalar(&rest, number)?);
+        }
+    }
+    Ok(Value::Array(items))
+}
+
+fn mapping(lines: &mut [Line], pos: &mut usize, indent: usize) -> Result<Value> {
+    let mut entries = Object::new();
+    while let Some(line) = lines.get(*pos) {
+        if line.indent != indent || is_item(&line.text) {
+            if line.indent > indent {
+                return bail(format!("line {}: unexpected indentation", line.number));
+            }
+            break;
+        }
+        let number = line.number;
+        let Some((key, rest)) = split_key(&line.text) else {
+            return bail(format!("line {number}: expected 'key: value'"));
+        };
+        let key = unquote(key, number)?;
+        let rest = rest.to_string();
+        *pos += 1;
+        let value = if rest.is_empty() {
+            // A sequence may sit at the key's own indentation.
+            match lines.get(*pos) {
+                Some(next) if next.indent == indent && is_item(&next.text) => {
+                    sequence(lines, pos, indent)?
+                }
+                _ => nested(lines, pos, indent)?,
+            }
+        } else {
+            scalar(&rest, number)?
+        };
+        if entries.insert(key.clone(), value).is_some() {
+            return bail(format!("line {number}: duplicate key '{key}'"));
+        }
+    }
+    Ok(Value::Object(entries))
+}
+
+/// The block indented under the line before `pos`, or null when there is none.
+fn nested(lines: &mut [Line], pos: &mut usize, indent: usize) -> Result<Value> {
+    match lines.get(*pos) {
+        Some(next) if next.indent > indent => {
+            let inner = next.indent;
+            block(lines, pos, inner)
+        }
+        _ => Ok(Value::Null),
+    }
+}
+
+/// `key` and the rest of a `key: value` line, outside quotes.
+fn split_key(text: &str) -> Option<(&str, &str)> {
+    let mut quote = None;
+    for (index, c) in text.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') if index == 0 => quote = Some(c),
+            (Some(open), _) if c == open => quote = None,
+            (None, ':') => {
+                let rest = &text[index + 1..];
+                if rest.is_empty() || rest.starts_with(' ') {
+                    return Some((text[..index].trim(), rest.trim()));
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+fn scalar(text: &str, number: usize) -> Result<Value> {
+    if let Some(inner) = text.strip_prefix('[') {
+        let Some(inner) = inner.strip_suffix(']') else {
+            return bail(format!("line {number}: unterminated '['"));
+        };
+        return split_flow(inner)
+            .iter()
+            .filter(|item| !item.is_empty())
+            .map(|item| scalar(item, number))
+            .collect::<Result<Vec<_>>>()
+            .map(Value::Array);
+    }
+    if text.starts_with(['{', '&', '*', '|', '>', '!']) {
+        return bail(format!(
+            "line {number}: '{}' is beyond the YAML amux reads",
+            &text[..1]
+        ));
+    }
+    if text.starts_with(['"', '\'']) {
+        return unquote(text, number).map(Value::String);
+    }
+    Ok(match text {
+        "true" | "True" | "TRUE" | "yes" => Value::Bool(true),
+        "false" | "False" | "FALSE" | "no" => Value::Bool(false),
+        "null" | "Null" | "NULL" | "~" => Value::Null,
+        _ => match text.parse::<i64>() {
+            Ok(number) => Value::Integer(number),
+            Err(_) => Value::String(text.to_string()),
+        },
+    })
+}
+
+fn split_flow(text: &str) -> Vec<String> {
+    let mut items = vec![String::new()];
+    let mut quote = None;
+    for c in text.chars() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(open), _) if c == open => quote = None,
+            (None, ',') => {
+                items.push(String::new());
+                continue;
+            }
+            _ => {}
+        }
+        items.last_mut().expect("items is never empty").push(c);
+    }
+    items.iter().map(|item| item.trim().to_string()).collect()
+}
+
+fn unquote(text: &str, number: usize) -> Result<String> {
+    let Some(open) = text.chars().next().filter(|c| matches!(c, '"' | '\'')) else {
+        return Ok(text.to_string());
+    };
+    let Some(inner) = text[1..].strip_suffix(open) else {
+        return bail(format!("line {number}: unterminated quote"));
+    };
+    if open == '\'' {
+        return Ok(inner.replace("''", "'"));
+    }
+    let mut out = String::new();
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('t') => out.push('\t'),
+            Some(other) => out.push(other),
+            None => return bail(format!("line {number}: dangling '\\'")),
+        }
+    }
+    Ok(out)
+}
+
+/// `raw` without a trailing `# comment` that is outside quotes.
+fn strip_comment(raw: &str) -> &str {
+    let mut quote = None;
+    let mut previous = ' ';
+    for (index, c) in raw.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(open), _) if c == open => quote = None,
+            (None, '#') if previous.is_whitespace() => return &raw[..index],
+            _ => {}
+        }
+        previous = c;
+    }
+    raw
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_reads_nested_mappings_and_sequences() {
+        let doc = parse(
+            "# team agents\nagents:\n  reviewer:\n    command: \"my-agent --review\"  # quoted\n    args: [--fast, 'two words']\n  helper:\n    enabled: true\nlist:\n- name: a\n  port: 80\n- plain\n",
+        )
+        .expect("yaml should parse");
+        assert_eq!(
+            doc.path(&["agents", "reviewer", "command"])
+                .and_then(Value::as_str),
+            Some("my-agent --review")
+        );
+        assert_eq!(
+            doc.path(&["agents", "reviewer", "args"]),
+            Some(&Value::Array(vec![
+                Value::String("--fast".into()),
+                Value::String("two words".into())
+            ]))
+        );
+        assert_eq!(
+            doc.path(&["agents", "helper", "enabled"]),
+            Some(&Value::Bool(true))
+        );
+        let list = doc.get("list").and_then(Value::as_array).expect("list");
+        assert_eq!(list[0].get("port").and_then(Value::as_i64), Some(80));
+        assert_eq!(list[1].as_str(), Some("plain"));
+    }
+
+    #[test]
+    fn parse_refuses_what_it_cannot_read() {
+        assert!(parse("a: |\n  text\n").is_err());
+        assert!(parse("a: 1\na: 2\n").is_err());
+        assert!(parse("a:\n  b: 1\n    c: 2\n").is_err());
+        assert_eq!(parse("# nothing\n").expect("empty"), Value::Null);
+    }
+}
@@ -0,0 +1,288 @@
+//! The Zellij backend (`--backend zellij`), driven through the `zellij` CLI
+//! (0.40 or newer). A session is created in the background from a generated
+//! layout whose only pane runs the agent and closes when it exits, so the
+//! session ends with the agent as it does under tmux. Zellij has no session
+//! environment, so variables are passed with `env NAME=VALUE`, and it does
+//! not report attached clients, so sessions always show none.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+
+use crate::error::{fail_with, with_context, DynError, ErrorKind, Result};
+use crate::input::Settings;
+use crate::multiplexer::Multiplexer;
+use crate::paths;
+use crate::tmux::{self, SessionDetail};
+
+pub struct Zellij;
+
+impl Multiplexer for Zellij {
+    fn name(&self) -> &'static str {
+        "zellij"
+    }
+
+    fn list_sessions(&self) -> Result<Vec<SessionDetail>> {
+        Ok(running_sessions()?
+            .into_iter()
+            .filter_map(|session| {
+                let (agent, name) = tmux::parse_session_name(&session)?;
+                Some(SessionDetail {
+                    session_name: session,
+                    agent,
+                    name,
+                    client_count: 0,
+                    pane_command: None,
+                    pinned: false,
+                    crash_looping: false,
+                    note: None,
+                    cwd: None,
+                })
+            })
+            .collect())
+    }
+
+    fn has_session(&self, session: &str) -> Result<bool> {
+        Ok(running_sessions()?.iter().any(|running| running == session))
+    }
+
+    /// Zellij sessions carry no markers, so every `amux-*` one counts.
+    fn is_managed(&self, _session: &str) -> Result<bool> {
+        Ok(true)
+    }
+
+    fn holds_options(&self) -> bool {
+        false
+    }
+
+    fn new_session(
+        &self,
+        session: &str,
+        command_tokens: &[String],
+        env: &[(String, String)],
+        cwd: Option<&Path>,
+    ) -> Result<()> {
+        let path = layout_file(session)?;
+        fs::write(&path, layout(command_tokens, env, cwd))
+            .map_err(|err| with_context(err, format!("failed to write {}", path.display())))?;
+        // An exited session of the same name would be resurrected instead.
+        let _ = zellij(&["delete-session", session]);
+        let layout_arg = path.to_string_lossy();
+        let output = zellij(&[
+            "attach",
+            "--create-background",
+            session,
+            "options",
+            "--default-layout",
+            &layout_arg,
+        ])?;
+        checked("attach --create-background", output).map(drop)
+    }
+
+    fn attach(&self, session: &str, take_over: bool) -> Result<()> {
+        if take_over {
+            return Err(failure(
+                "zellij cannot detach other clients; attach without --take-over".to_string(),
+            ));
+        }
+        let status = Command::new("zellij")
+            .args(["attach", session])
+            .status()
+            .map_err(invoke_error)?;
+        if status.success() {
+            Ok(())
+        } else {
+            Err(failure(format!("zellij attach exited with {status}")))
+        }
+    }
+
+    fn kill_session(&self, session: &str) -> Result<()> {
+        checked("kill-session", zellij(&["kill-session", session])?)?;
+        // Drop the resurrection state too, so the name is free again.
+        let _ = zellij(&["delete-session", session]);
+        if let Ok(path) = layout_file(session) {
+            let _ = fs::remove_file(path);
+        }
+        Ok(())
+    }
+
+    fn capture(&self, session: &str, history: Option<usize>) -> Result<String> {
+        let path = paths::state_dir()?.join(format!("zellij-dump-{}.txt", std::process::id()));
+        let path_arg = path.to_string_lossy();
+        let mut args = vec!["--session", session, "action", "dump-screen", &path_arg];
+        if history.is_some() {
+            args.push("--full");
+        }
+        checked("action dump-screen", zellij(&args)?)?;
+        let text = fs::read_to_string(&path)
+            .map_err(|err| with_context(err, format!("failed to read {}", path.display())));
+        let _ = fs::remove_file(&path);
+        let text = text?;
+        // dump-screen has no line limit; keep the screen plus `history` lines.
+        Ok(match history {
+            Some(lines) => {
+                let all: Vec<&str> = text.lines().collect();
+                let start = all.len().saturating_sub(lines);
+                all[start..].join("\n") + "\n"
+            }
+            None => text,
+        })
+    }
+
+    /// `write-chars` types the whole text at once, so `_settings` go unused.
+    fn send_text(
+        &self,
+        session: &str,
+        text: &str,
+        _settings: &Settings,
+        enter: bool,
+    ) -> Result<()> {
+        checked(
+            "action write-chars",
+            zellij(&["--session", session, "action", "write-chars", text])?,
+        )?;
+        if enter {
+            checked(
+                "action write",
+                zellij(&["--session", session, "action", "write", "13"])?,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Names of the sessions that are running, leaving out exited ones Zellij
+/// keeps around for resurrection.
+fn running_sessions() -> Result<Vec<String>> {
+    let output = zellij(&["list-sessions", "--no-formatting"])?;
+    if !output.status.success() {
+        // With no sessions at all, zellij says so and fails.
+        let text = String::from_utf8_lossy(&output.stderr).to_lowercase()
+            + &String::from_utf8_lossy(&output.stdout).to_lowercase();
+        if text.contains("no active zellij sessions") {
+            return Ok(Vec::new());
+        }
+        return checked("list-sessions", output).map(|_| Vec::new());
+    }
+    Ok(parse_sessions(&String::from_utf8_lossy(&output.stdout)))
+}
+
+fn parse_sessions(output: &str) -> Vec<String> {
+    output
+        .lines()
+        .filter(|line| !line.contains("EXITED"))
+        .filter_map(|line| line.split_whitespace().next())
+        .map(str::to_string)
+        .collect()
+}
+
+/// A layout with one pane running the agent, closed when it exits.
+fn layout(command_tokens: &[String], env: &[(String, String)], cwd: Option<&Path>) -> String {
+    let mut argv: Vec<String> = Vec::new();
+    if !env.is_empty() {
+        argv.push("env".into());
+        argv.extend(env.iter().map(|(name, value)| format!("{name}={value}")));
+    }
+    argv.extend(command_tokens.iter().cloned());
+    let Some((program, args)) = argv.split_first() else {
+        return "layout {\n    pane\n}\n".to_string();
+    };
+    let mut pane = format!("pane command={} close_on_exit=true", kdl_string(program));
+    if let Some(cwd) = cwd {
+        pane.push_str(&format!(" cwd={}", kdl_string(&cwd.to_string_lossy())));
+    }
+    if args.is_empty() {
+        return format!("layout {{\n    {pane}\n}}\n");
+    }
+    let args: Vec<String> = args.iter().map(|arg| kdl_string(arg)).collect();
+    format!(
+        "layout {{\n    {pane} {{\n        args {}\n    }}\n}}\n",
+        args.join(" ")
+    )
+}
+
+fn kdl_string(text: &str) -> String {
+    let mut out = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn layout_file(session: &str) -> Result<PathBuf> {
+    let dir = paths::state_dir()?.join("zellij");
+    fs::create_dir_all(&dir)
+        .map_err(|err| with_context(err, format!("failed to create {}", dir.display())))?;
+    Ok(dir.join(format!("{session}.kdl")))
+}
+
+fn zellij(args: &[&str]) -> Result<Output> {
+    Command::new("zellij")
+        .args(args)
+        .stdin(Stdio::null())
+        .output()
+        .map_err(invoke_error)
+}
+
+fn checked(what: &str, output: Output) -> Result<Output> {
+    if output.status.success() {
+        return Ok(output);
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    Err(failure(format!(
+        "zellij {what} exited with {}: {}",
+        output.status,
+        stderr.trim()
+    )))
+}
+
+fn failure(message: String) -> DynError {
+    fail_with(ErrorKind::Other, message, None)
+}
+
+fn invoke_error(err: io::Error) -> DynError {
+    if err.kind() == io::ErrorKind::NotFound {
+        fail_with(
+            ErrorKind::Other,
+            "zellij not found".to_string(),
+            Some("install zellij, or use the tmux backend with --backend tmux".into()),
+        )
+    } else {
+        with_context(err, "failed to run zellij")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn layout_runs_the_agent_with_its_environment() {
+        let tokens = vec!["codex".to_string(), "say \"hi\"".to_string()];
+        let env = vec![("AMUX_AGENT".to_string(), "codex".to_string())];
+        assert_eq!(
+            layout(&tokens, &env, Some(Path::new("/work/api"))),
+            "layout {\n    pane command=\"env\" close_on_exit=true cwd=\"/work/api\" {\n        args \"AMUX_AGENT=codex\" \"codex\" \"say \\\"hi\\\"\"\n    }\n}\n"
+        );
+        assert_eq!(
+            layout(&["claude".to_string()], &[], None),
+            "layout {\n    pane command=\"claude\" close_on_exit=true\n}\n"
+        );
+    }
+
+    #[test]
+    fn parse_sessions_skips_exited_ones() {
+        let output = "amux-codex [Created 2m 3s ago] (current)\n\
+                      amux-claude--pr [Created 1h ago] (EXITED - attach to resurrect)\n\
+                      notes [Created 5s ago]\n";
+        assert_eq!(parse_sessions(output), ["amux-codex", "notes"]);
+    }
+}
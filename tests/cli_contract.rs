@@ -0,0 +1,222 @@
+//! The CLI contract scripts rely on: help text, JSON output, error reports, and
+//! exit codes, pinned as snapshots under `tests/snapshots/`. Every case runs
+//! the real binary against a fake `tmux` on `PATH` and a scratch home, so
+//! nothing depends on a tmux server or the machine's config.
+//!
+//! A change to any of this output is a change to the contract. When it is
+//! intended, rerun with `AMUX_UPDATE_SNAPSHOTS=1 cargo test --test
+//! cli_contract` and commit the updated snapshots with it.
+
+use std::env;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Answers the calls amux makes to read sessions, from `$FAKE_TMUX_DIR/sessions`
+/// (`list-sessions` output) when it exists and as if no server ran otherwise.
+const FAKE_TMUX: &str = r#"#!/bin/sh
+echo "$*" >> "$FAKE_TMUX_DIR/calls"
+while [ "$1" = "-L" ] || [ "$1" = "-f" ]; do shift 2; done
+case "$1" in
+  -V) echo "tmux 3.4" ;;
+  list-sessions)
+    if [ -f "$FAKE_TMUX_DIR/sessions" ]; then
+      cat "$FAKE_TMUX_DIR/sessions"
+    else
+      echo "no server running on $FAKE_TMUX_DIR/socket" >&2
+      exit 1
+    fi
+    ;;
+  has-session)
+    echo "can't find session" >&2
+    exit 1
+    ;;
+  *)
+    echo "fake tmux: unsupported command: $*" >&2
+    exit 1
+    ;;
+esac
+"#;
+
+static NEXT: AtomicUsize = AtomicUsize::new(0);
+
+/// A scratch home with a fake tmux, removed when dropped.
+struct Harness {
+    root: PathBuf,
+}
+
+impl Harness {
+    fn new() -> Self {
+        let root = env::temp_dir().join(format!(
+            "amux-contract-{}-{}",
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::Relaxed)
+        ));
+        let _ = fs::remove_dir_all(&root);
+        for dir in ["bin", "home", "state", "tmux"] {
+            fs::create_dir_all(root.join(dir)).expect("create harness directory");
+        }
+        let tmux = root.join("bin/tmux");
+        fs::write(&tmux, FAKE_TMUX).expect("write fake tmux");
+        fs::set_permissions(&tmux, fs::Permissions::from_mode(0o755)).expect("make tmux runnable");
+        fs::write(root.join("config.toml"), "").expect("write config");
+        Self { root }
+    }
+
+    /// Make `list-sessions` report `sessions`, one record of tmux format
+    /// fields per session (see `LIST_FIELDS` in src/tmux.rs).
+    fn with_sessions(self, sessions: &[[&str; 9]]) -> Self {
+        let output: String = sessions
+            .iter()
+            .map(|fields| {
+                let record: String = fields
+                    .iter()
+                    .map(|value| format!("{}:{value}", value.len()))
+                    .collect();
+                format!("{record}\n")
+            })
+            .collect();
+        fs::write(self.root.join("tmux/sessions"), output).expect("write sessions");
+        self
+    }
+
+    /// Run amux with `args` and describe the outcome as a snapshot.
+    fn run(&self, args: &[&str]) -> String {
+        let path = match env::var_os("PATH") {
+            Some(path) => {
+                let mut dirs = vec![self.root.join("bin")];
+                dirs.extend(env::split_paths(&path));
+                env::join_paths(dirs).expect("join PATH")
+            }
+            None => self.root.join("bin").into_os_string(),
+        };
+        let output = Command::new(env!("CARGO_BIN_EXE_amux"))
+            .args(args)
+            .env_clear()
+            .env("PATH", path)
+            .env("HOME", self.root.join("home"))
+            .env("XDG_STATE_HOME", self.root.join("state"))
+            .env("AMUX_CONFIG", self.root.join("config.toml"))
+            .env("FAKE_TMUX_DIR", self.root.join("tmux"))
+            .env("TERM", "xterm")
+            .env("TMUX", "")
+            .current_dir(self.root.join("home"))
+            .output()
+            .expect("run amux");
+        let root = self.root.to_string_lossy();
+        let text = |bytes: &[u8]| String::from_utf8_lossy(bytes).replace(root.as_ref(), "[ROOT]");
+        format!(
+            "$ amux {}\nexit: {}\n--- stdout\n{}--- stderr\n{}",
+            args.join(" "),
+            output
+                .status
+                .code()
+                .map_or("signal".into(), |code| code.to_string()),
+            text(&output.stdout),
+            text(&output.stderr)
+        )
+    }
+}
+
+impl Drop for Harness {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.root);
+    }
+}
+
+/// Compare `actual` with `tests/snapshots/NAME.txt`, or write it there when
+/// `AMUX_UPDATE_SNAPSHOTS` is set.
+fn assert_snapshot(name: &str, actual: &str) {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/snapshots")
+        .join(format!("{name}.txt"));
+    if env::var_os("AMUX_UPDATE_SNAPSHOTS").is_some() {
+        fs::write(&path, actual).expect("write snapshot");
+        return;
+    }
+    let expected = fs::read_to_string(&path).unwrap_or_else(|_| {
+        panic!(
+            "no snapshot {}; rerun with AMUX_UPDATE_SNAPSHOTS=1 to record it",
+            path.display()
+        )
+    });
+    assert!(
+        expected == actual,
+        "the output of `{name}` changed; if that is intended, rerun with \
+         AMUX_UPDATE_SNAPSHOTS=1 and commit the snapshot\n--- expected\n{expected}--- actual\n{actual}"
+    );
+}
+
+fn sample_sessions() -> Harness {
+    Harness::new().with_sessions(&[
+        [
+            "amux-codex--api",
+            "1",
+            "",
+            "1",
+            "node",
+            "codex",
+            "api",
+            "fix login",
+            "/work/api",
+        ],
+        [
+            "amux-claude",
+            "",
+            "",
+            "0",
+            "claude",
+            "claude",
+            "",
+            "",
+            "/work/web",
+        ],
+    ])
+}
+
+#[test]
+fn help_output_is_stable() {
+    let harness = Harness::new();
+    assert_snapshot("help", &harness.run(&["help"]));
+    assert_snapshot("help_flag", &harness.run(&["--help"]));
+    assert_snapshot("start_help", &harness.run(&["start", "--help"]));
+    assert_snapshot("status_help", &harness.run(&["status", "--help"]));
+}
+
+#[test]
+fn machine_readable_output_is_stable() {
+    let harness = sample_sessions();
+    assert_snapshot("status_json", &harness.run(&["status", "--format", "json"]));
+    assert_snapshot("list_json", &harness.run(&["list", "--format", "json"]));
+    assert_snapshot("agents_names", &harness.run(&["agents", "--names"]));
+    assert_snapshot("session_names", &harness.run(&["session-names", "codex"]));
+    let empty = Harness::new();
+    assert_snapshot(
+        "status_json_empty",
+        &empty.run(&["status", "--format", "json"]),
+    );
+    assert_snapshot("status_empty", &empty.run(&["status"]));
+    assert_snapshot(
+        "stats_json_empty",
+        &empty.run(&["stats", "--format", "json"]),
+    );
+}
+
+#[test]
+fn errors_and_exit_codes_are_stable() {
+    let harness = Harness::new();
+    // 1: the command ran and failed; 2: the command line was not understood.
+    assert_snapshot("show_missing", &harness.run(&["show", "codex"]));
+    assert_snapshot(
+        "show_missing_json_errors",
+        &harness.run(&["show", "codex", "--json-errors"]),
+    );
+    assert_snapshot("unknown_agent", &harness.run(&["rm", "nope"]));
+    assert_snapshot("usage_error", &harness.run(&["start", "--bogus"]));
+    assert_snapshot(
+        "usage_error_json_errors",
+        &harness.run(&["start", "--bogus", "--json-errors"]),
+    );
+}